@@ -0,0 +1,155 @@
+package com.plugin.blec
+
+import android.Manifest
+import android.bluetooth.BluetoothAdapter
+import android.content.Intent
+import android.os.Build
+import android.provider.Settings
+import androidx.test.ext.junit.runners.AndroidJUnit4
+
+import org.junit.Test
+import org.junit.runner.RunWith
+
+import org.junit.Assert.*
+
+class FakeBluetoothEnvironment(
+    override val sdkInt: Int = Build.VERSION_CODES.S,
+    var granted: MutableSet<String> = mutableSetOf(),
+    var requestedBefore: MutableSet<String> = mutableSetOf(),
+    var rationale: Boolean = false,
+    var enabled: Boolean = true,
+): BluetoothEnvironment {
+    override val packageName = "com.plugin.blec.test"
+    val permissionRequests: MutableList<List<String>> = mutableListOf()
+    val startedActivities: MutableList<Intent> = mutableListOf()
+    val enableRequests: MutableList<Intent> = mutableListOf()
+    val messages: MutableList<String> = mutableListOf()
+
+    override fun isGranted(permission: String) = permission in granted
+    override fun isFirstRequest(permission: String) = permission !in requestedBefore
+    override fun markRequested(permission: String) {
+        requestedBefore.add(permission)
+    }
+    override fun shouldShowRationale(permission: String) = rationale
+    override fun requestPermissions(permissions: Array<String>) {
+        permissionRequests.add(permissions.toList())
+    }
+    override fun startActivity(intent: Intent) {
+        startedActivities.add(intent)
+    }
+    override fun requestEnable(intent: Intent) {
+        enableRequests.add(intent)
+    }
+    override fun isAdapterEnabled() = enabled
+    override fun showMessage(message: String) {
+        messages.add(message)
+    }
+}
+
+@RunWith(AndroidJUnit4::class)
+class BluetoothEnvironmentTest {
+    @Test
+    fun requiredPermissionsFollowTheSdk() {
+        assertArrayEquals(
+            arrayOf(Manifest.permission.BLUETOOTH_SCAN, Manifest.permission.BLUETOOTH_CONNECT),
+            requiredPermissions(Build.VERSION_CODES.S)
+        )
+        assertArrayEquals(
+            arrayOf(Manifest.permission.BLUETOOTH_ADMIN, Manifest.permission.BLUETOOTH),
+            requiredPermissions(Build.VERSION_CODES.R)
+        )
+    }
+
+    @Test
+    fun grantedPermissionsAreNotRequested() {
+        val env = FakeBluetoothEnvironment(granted = requiredPermissions(Build.VERSION_CODES.S).toMutableSet())
+        assertEquals(PermissionOutcome.Granted, checkPermissions(env))
+        assertTrue(env.permissionRequests.isEmpty())
+        assertTrue(env.startedActivities.isEmpty())
+    }
+
+    @Test
+    fun firstRequestOpensTheDialog() {
+        val env = FakeBluetoothEnvironment()
+        assertEquals(PermissionOutcome.Requested, checkPermissions(env))
+        assertEquals(listOf(requiredPermissions(Build.VERSION_CODES.S).toList()), env.permissionRequests)
+        assertTrue(Manifest.permission.BLUETOOTH_SCAN in env.requestedBefore)
+    }
+
+    @Test
+    fun permanentDenialOpensTheAppSettings() {
+        val env = FakeBluetoothEnvironment(
+            requestedBefore = requiredPermissions(Build.VERSION_CODES.S).toMutableSet()
+        )
+        assertEquals(PermissionOutcome.OpenedSettings, checkPermissions(env))
+        assertTrue(env.permissionRequests.isEmpty())
+        val intent = env.startedActivities.single()
+        assertEquals(Settings.ACTION_APPLICATION_DETAILS_SETTINGS, intent.action)
+        assertEquals("package:com.plugin.blec.test", intent.data.toString())
+        assertEquals(listOf("Allow Permission: ${Manifest.permission.BLUETOOTH_SCAN}"), env.messages)
+    }
+
+    @Test
+    fun rationaleRequestsAgain() {
+        val env = FakeBluetoothEnvironment(
+            requestedBefore = requiredPermissions(Build.VERSION_CODES.S).toMutableSet(),
+            rationale = true
+        )
+        assertEquals(PermissionOutcome.Requested, checkPermissions(env))
+        assertTrue(env.startedActivities.isEmpty())
+    }
+
+    @Test
+    fun disabledAdapterRequestsEnable() {
+        val env = FakeBluetoothEnvironment(enabled = false)
+        assertFalse(requestEnableIfDisabled(env))
+        assertEquals(BluetoothAdapter.ACTION_REQUEST_ENABLE, env.enableRequests.single().action)
+    }
+
+    @Test
+    fun enabledAdapterIsLeftAlone() {
+        val env = FakeBluetoothEnvironment()
+        assertTrue(requestEnableIfDisabled(env))
+        assertTrue(env.enableRequests.isEmpty())
+    }
+
+    // the codes are matched by the rust side, see android_rejections_map_to_typed_errors in
+    // src/error.rs
+    @Test
+    fun scanRejectionCodesMatchTheRustSide() {
+        assertEquals("PERMISSION_REQUESTED", PERMISSION_REQUESTED)
+        assertEquals("PERMISSION_DENIED", PERMISSION_DENIED)
+        assertEquals("BLUETOOTH_DISABLED", BLUETOOTH_DISABLED)
+    }
+
+    @Test
+    fun scanStartsWithPermissionsAndBluetoothOn() {
+        val env = FakeBluetoothEnvironment(granted = requiredPermissions(Build.VERSION_CODES.S).toMutableSet())
+        assertNull(scanPrecondition(env))
+    }
+
+    @Test
+    fun scanIsRejectedWhilePermissionsAreRequested() {
+        val env = FakeBluetoothEnvironment()
+        assertEquals(PERMISSION_REQUESTED, scanPrecondition(env)?.code)
+        assertTrue(env.enableRequests.isEmpty())
+    }
+
+    @Test
+    fun scanIsRejectedAfterPermanentDenial() {
+        val env = FakeBluetoothEnvironment(
+            requestedBefore = requiredPermissions(Build.VERSION_CODES.S).toMutableSet()
+        )
+        assertEquals(PERMISSION_DENIED, scanPrecondition(env)?.code)
+    }
+
+    @Test
+    fun scanIsRejectedWhileBluetoothIsOff() {
+        val env = FakeBluetoothEnvironment(
+            granted = requiredPermissions(Build.VERSION_CODES.S).toMutableSet(),
+            enabled = false
+        )
+        assertEquals(BLUETOOTH_DISABLED, scanPrecondition(env)?.code)
+        assertEquals(BluetoothAdapter.ACTION_REQUEST_ENABLE, env.enableRequests.single().action)
+    }
+}
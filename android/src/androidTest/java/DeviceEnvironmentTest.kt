@@ -0,0 +1,124 @@
+package com.plugin.blec
+
+import android.bluetooth.BluetoothDevice
+import android.bluetooth.BluetoothGatt
+import android.content.Intent
+import androidx.test.ext.junit.runners.AndroidJUnit4
+
+import org.junit.Test
+import org.junit.runner.RunWith
+
+import org.junit.Assert.*
+
+class FakeDeviceEnvironment(
+    override var bondState: Int = BluetoothDevice.BOND_NONE,
+    var bondStarts: Boolean = true,
+    var removeResult: () -> Boolean = { true },
+    var connected: Boolean = true,
+    var priorityAccepted: Boolean = true,
+): DeviceEnvironment {
+    var bondRequests = 0
+    val priorities: MutableList<Int> = mutableListOf()
+
+    override fun createBond(): Boolean {
+        bondRequests += 1
+        return bondStarts
+    }
+    override fun removeBond() = removeResult()
+    override fun requestConnectionPriority(priority: Int): Boolean? {
+        if (!connected) {
+            return null
+        }
+        priorities.add(priority)
+        return priorityAccepted
+    }
+}
+
+@RunWith(AndroidJUnit4::class)
+class DeviceEnvironmentTest {
+    @Test
+    fun bondedDeviceIsNotBondedAgain() {
+        val env = FakeDeviceEnvironment(bondState = BluetoothDevice.BOND_BONDED)
+        assertEquals(BondOutcome.AlreadyBonded, startBond(env))
+        assertEquals(0, env.bondRequests)
+    }
+
+    @Test
+    fun bondIsStarted() {
+        val env = FakeDeviceEnvironment()
+        assertEquals(BondOutcome.Started, startBond(env))
+        assertEquals(1, env.bondRequests)
+    }
+
+    @Test
+    fun bondThatDoesNotStartFails() {
+        val env = FakeDeviceEnvironment(bondStarts = false)
+        assertEquals(BondOutcome.FailedToStart, startBond(env))
+    }
+
+    @Test
+    fun removingWithoutBondDoesNothing() {
+        var called = false
+        val env = FakeDeviceEnvironment(removeResult = { called = true; true })
+        assertEquals(RemoveBondOutcome.NotBonded, removeBond(env))
+        assertFalse(called)
+    }
+
+    @Test
+    fun bondIsRemoved() {
+        val env = FakeDeviceEnvironment(bondState = BluetoothDevice.BOND_BONDED)
+        assertEquals(RemoveBondOutcome.Removed, removeBond(env))
+    }
+
+    @Test
+    fun unreachableRemoveBondFails() {
+        val env = FakeDeviceEnvironment(
+            bondState = BluetoothDevice.BOND_BONDED,
+            removeResult = { throw NoSuchMethodException("removeBond") }
+        )
+        assertEquals(RemoveBondOutcome.Failed, removeBond(env))
+    }
+
+    @Test
+    fun bondBroadcastsAreRead() {
+        val bonded = Intent(BluetoothDevice.ACTION_BOND_STATE_CHANGED)
+            .putExtra(BluetoothDevice.EXTRA_BOND_STATE, BluetoothDevice.BOND_BONDED)
+        assertEquals(true, bondChange(bonded)?.bonded)
+        val rejected = Intent(BluetoothDevice.ACTION_BOND_STATE_CHANGED)
+            .putExtra(BluetoothDevice.EXTRA_BOND_STATE, BluetoothDevice.BOND_NONE)
+            .putExtra("android.bluetooth.device.extra.REASON", 9)
+        assertEquals(false, bondChange(rejected)?.bonded)
+        assertEquals(9, bondChange(rejected)?.reason)
+        val bonding = Intent(BluetoothDevice.ACTION_BOND_STATE_CHANGED)
+            .putExtra(BluetoothDevice.EXTRA_BOND_STATE, BluetoothDevice.BOND_BONDING)
+        assertNull(bondChange(bonding))
+    }
+
+    // the values sent by ConnectionPriority::android_value in src/setup.rs
+    @Test
+    fun connectionPriorityValuesMatchTheRustSide() {
+        assertEquals(0, BluetoothGatt.CONNECTION_PRIORITY_BALANCED)
+        assertEquals(1, BluetoothGatt.CONNECTION_PRIORITY_HIGH)
+        assertEquals(2, BluetoothGatt.CONNECTION_PRIORITY_LOW_POWER)
+    }
+
+    @Test
+    fun connectionPriorityIsRequested() {
+        val env = FakeDeviceEnvironment()
+        assertEquals(PriorityOutcome.Requested, requestPriority(env, BluetoothGatt.CONNECTION_PRIORITY_HIGH))
+        assertEquals(listOf(BluetoothGatt.CONNECTION_PRIORITY_HIGH), env.priorities)
+    }
+
+    @Test
+    fun connectionPriorityNeedsAConnection() {
+        val env = FakeDeviceEnvironment(connected = false)
+        assertEquals(PriorityOutcome.NotConnected, requestPriority(env, BluetoothGatt.CONNECTION_PRIORITY_HIGH))
+        assertTrue(env.priorities.isEmpty())
+    }
+
+    @Test
+    fun rejectedConnectionPriorityFails() {
+        val env = FakeDeviceEnvironment(priorityAccepted = false)
+        assertEquals(PriorityOutcome.Failed, requestPriority(env, BluetoothGatt.CONNECTION_PRIORITY_LOW_POWER))
+    }
+}
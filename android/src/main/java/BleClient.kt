@@ -1,7 +1,6 @@
 package com.plugin.blec
 
 import Peripheral
-import android.Manifest
 import android.annotation.SuppressLint
 import android.app.Activity
 import android.bluetooth.BluetoothAdapter
@@ -13,18 +12,9 @@ import android.bluetooth.le.ScanFilter
 import android.bluetooth.le.ScanFilter.Builder
 import android.bluetooth.le.ScanResult
 import android.bluetooth.le.ScanSettings
-import android.content.Context.MODE_PRIVATE
-import android.content.Intent
-import android.content.SharedPreferences
-import android.content.pm.PackageManager
-import android.net.Uri
 import android.os.Build
 import android.os.ParcelUuid
-import android.provider.Settings
 import android.util.SparseArray
-import android.widget.Toast
-import androidx.core.app.ActivityCompat
-import androidx.core.app.ActivityCompat.startActivityForResult
 import androidx.core.content.ContextCompat.getSystemService
 import app.tauri.annotation.InvokeArg
 import app.tauri.plugin.Channel
@@ -38,7 +28,10 @@ class BleDevice(
     private val rssi: Int,
     private val connected: Boolean,
     private val manufacturerData: SparseArray<ByteArray>?,
-    private val services: List<ParcelUuid>?
+    private val services: List<ParcelUuid>?,
+    private val serviceData: Map<ParcelUuid, ByteArray>?,
+    private val advertiseFlags: Int?,
+    private val appearance: Int?
 ){
     fun toJsObject():JSObject{
         val obj = JSObject()
@@ -74,65 +67,49 @@ class BleDevice(
             subObj
         } else { null }
         obj.put("manufacturerData",manufacturerData)
+        val serviceData = if (serviceData != null) {
+            val subObj = JSObject()
+            for ((uuid, value) in serviceData) {
+                val arr = JSArray()
+                for (element in value){
+                    arr.put(element.toUByte().toInt())
+                }
+                subObj.put(uuid.toString(),arr)
+            }
+            subObj
+        } else { null }
+        obj.put("serviceData",serviceData)
+        obj.put("advertiseFlags",advertiseFlags)
+        obj.put("appearance",appearance)
         return obj
     }
 }
 
-class BleClient(private val activity: Activity, private val plugin: BleClientPlugin) {
+// the scan record has no getter for the appearance, it is read from the raw AD structures
+private fun parseAppearance(record: ByteArray?): Int? {
+    if (record == null) return null
+    var i = 0
+    while (i < record.size) {
+        val len = record[i].toInt() and 0xff
+        if (len == 0 || i + len >= record.size) break
+        // AD type 0x19 is the 16 bit little endian appearance
+        if (record[i + 1].toInt() and 0xff == 0x19 && len >= 3) {
+            return (record[i + 2].toInt() and 0xff) or ((record[i + 3].toInt() and 0xff) shl 8)
+        }
+        i += len + 1
+    }
+    return null
+}
+
+class BleClient(
+    private val activity: Activity,
+    private val plugin: BleClientPlugin,
+    private val env: BluetoothEnvironment = ActivityBluetoothEnvironment(activity)
+) {
     private var scanner: BluetoothLeScanner? = null;
     private var manager: BluetoothManager? = null;
     private var scanCb: ScanCallback? = null;
 
-    private fun markFirstPermissionRequest(perm: String) {
-        val sharedPreference: SharedPreferences =
-            activity.getSharedPreferences("PREFS_PERMISSION_FIRST_TIME_ASKING", MODE_PRIVATE)
-        sharedPreference.edit().putBoolean(perm, false).apply()
-    }
-
-    private fun firstPermissionRequest(perm: String): Boolean {
-        return activity.getSharedPreferences("PREFS_PERMISSION_FIRST_TIME_ASKING", MODE_PRIVATE)
-            .getBoolean(perm, true)
-    }
-
-    private fun checkPermissions(): Boolean {
-
-        val permissions =  if (Build.VERSION.SDK_INT >= Build.VERSION_CODES.S) {
-            arrayOf(
-                Manifest.permission.BLUETOOTH_SCAN,
-                Manifest.permission.BLUETOOTH_CONNECT
-            )
-        } else {
-            arrayOf(
-                Manifest.permission.BLUETOOTH_ADMIN,
-                Manifest.permission.BLUETOOTH,
-            )
-        };
-        for (perm in permissions){
-            if (ActivityCompat.checkSelfPermission(
-                    activity,
-                    perm
-                ) != PackageManager.PERMISSION_GRANTED
-            ) {
-                if (firstPermissionRequest(perm) || activity.shouldShowRequestPermissionRationale(perm)) {
-                    // this will open the permission dialog
-                    markFirstPermissionRequest(perm)
-                    activity.requestPermissions(permissions, 1)
-                    return false
-                } else{
-                    // this will open settings which asks for permission
-                    val intent = Intent(
-                        Settings.ACTION_APPLICATION_DETAILS_SETTINGS,
-                        Uri.parse("package:${activity.packageName}")
-                    )
-                    activity.startActivity(intent)
-                    Toast.makeText(activity, "Allow Permission: $perm", Toast.LENGTH_SHORT).show()
-                    return false
-                }
-            }
-        }
-        return true
-    }
-
     @InvokeArg
     class ScanParams {
         val services: ArrayList<String> = ArrayList()
@@ -145,9 +122,10 @@ class BleClient(private val activity: Activity, private val plugin: BleClientPlu
             invoke.reject("Scan already running")
             return
         }
-        // check permission
-        if (!checkPermissions()){
-            invoke.reject("Missing permissions");
+        // check permission and that bluetooth is on
+        val rejection = scanPrecondition(env)
+        if (rejection != null){
+            invoke.reject(rejection.message, rejection.code)
             return
         }
 
@@ -157,11 +135,6 @@ class BleClient(private val activity: Activity, private val plugin: BleClientPlu
                 ?: throw RuntimeException("No bluetooth manager found")
             val bluetoothAdapter: BluetoothAdapter = manager!!.adapter
                 ?: throw RuntimeException("No bluetooth adapter available")
-            // check if bluetooth is on
-            if (!bluetoothAdapter.isEnabled ) {
-                val enableBtIntent = Intent(BluetoothAdapter.ACTION_REQUEST_ENABLE)
-                startActivityForResult(activity, enableBtIntent,0,null)
-            }
             scanner = bluetoothAdapter.bluetoothLeScanner
                 ?: throw RuntimeException("No bluetooth scanner available for adapter")
         }
@@ -202,7 +175,11 @@ class BleClient(private val activity: Activity, private val plugin: BleClientPlu
                     result.rssi,
                     connected,
                     result.scanRecord?.manufacturerSpecificData,
-                    result.scanRecord?.serviceUuids
+                    result.scanRecord?.serviceUuids,
+                    result.scanRecord?.serviceData,
+                    // -1 means the advertisement did not contain flags
+                    result.scanRecord?.advertiseFlags?.takeIf { it >= 0 },
+                    parseAppearance(result.scanRecord?.bytes)
                 )
                 this@BleClient.plugin.devices[device.address] = Peripheral(this@BleClient.activity, result.device, this@BleClient.plugin)
                 val res = JSObject()
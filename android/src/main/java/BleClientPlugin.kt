@@ -2,7 +2,16 @@ package com.plugin.blec
 
 
 import Peripheral
+import android.annotation.SuppressLint
 import android.app.Activity
+import android.bluetooth.BluetoothAdapter
+import android.bluetooth.BluetoothDevice
+import android.bluetooth.BluetoothManager
+import android.content.BroadcastReceiver
+import android.content.Context
+import android.content.Intent
+import android.content.IntentFilter
+import android.os.Build
 import app.tauri.annotation.Command
 import app.tauri.annotation.InvokeArg
 import app.tauri.annotation.TauriPlugin
@@ -22,6 +31,8 @@ class ConnectParams{
 class BleClientPlugin(private val activity: Activity): Plugin(activity) {
     var devices: MutableMap<String, Peripheral> = mutableMapOf();
     var eventChannel: Channel? = null;
+    private var bondReceiver: BroadcastReceiver? = null
+    private var adapterStateReceiver: BroadcastReceiver? = null
     private val client = BleClient(activity,this)
 
     @Command
@@ -37,6 +48,48 @@ class BleClientPlugin(private val activity: Activity): Plugin(activity) {
     @Command
     fun events(invoke: Invoke){
         this.eventChannel = invoke.parseArgs(Channel::class.java)
+        this.adapterStateReceiver?.let { activity.unregisterReceiver(it) }
+        // reported like the StateUpdate central event of btleplug
+        val receiver = object : BroadcastReceiver() {
+            override fun onReceive(context: Context?, intent: Intent) {
+                val state = when (intent.getIntExtra(BluetoothAdapter.EXTRA_STATE, BluetoothAdapter.ERROR)){
+                    BluetoothAdapter.STATE_ON -> "PoweredOn"
+                    BluetoothAdapter.STATE_OFF, BluetoothAdapter.STATE_TURNING_OFF -> "PoweredOff"
+                    else -> return
+                }
+                val data = JSObject()
+                data.put("StateUpdate", state)
+                this@BleClientPlugin.eventChannel?.send(data)
+            }
+        }
+        this.adapterStateReceiver = receiver
+        activity.registerReceiver(receiver, IntentFilter(BluetoothAdapter.ACTION_STATE_CHANGED))
+        invoke.resolve()
+        // the current state, the receiver only reports changes
+        val adapter = (activity.getSystemService(Context.BLUETOOTH_SERVICE) as? BluetoothManager)?.adapter ?: return
+        val data = JSObject()
+        data.put("StateUpdate", if (adapter.isEnabled) "PoweredOn" else "PoweredOff")
+        this.eventChannel?.send(data)
+    }
+
+    // reports bonds of every device, also bonds created or removed in the system settings
+    @Command
+    fun bond_changes(invoke: Invoke){
+        val channel = invoke.parseArgs(Channel::class.java)
+        this.bondReceiver?.let { activity.unregisterReceiver(it) }
+        val receiver = object : BroadcastReceiver() {
+            @SuppressLint("MissingPermission")
+            override fun onReceive(context: Context?, intent: Intent) {
+                val device: BluetoothDevice = intent.getParcelableExtra(BluetoothDevice.EXTRA_DEVICE) ?: return
+                val change = bondChange(intent) ?: return
+                val data = JSObject()
+                data.put("address", device.address)
+                data.put("bonded", change.bonded)
+                channel.send(data)
+            }
+        }
+        this.bondReceiver = receiver
+        activity.registerReceiver(receiver, IntentFilter(BluetoothDevice.ACTION_BOND_STATE_CHANGED))
         invoke.resolve()
     }
 
@@ -51,6 +104,30 @@ class BleClientPlugin(private val activity: Activity): Plugin(activity) {
         device.connect(invoke)
     }
 
+    // creates the handle without a scan, e.g. to reconnect to a saved device
+    @SuppressLint("MissingPermission")
+    @Command
+    fun get_remote_device(invoke: Invoke){
+        val args = invoke.parseArgs(ConnectParams::class.java)
+        val adapter = (activity.getSystemService(Context.BLUETOOTH_SERVICE) as? BluetoothManager)?.adapter
+        if (adapter == null || !BluetoothAdapter.checkBluetoothAddress(args.address)){
+            invoke.reject("Device not found")
+            return
+        }
+        val remote = adapter.getRemoteDevice(args.address)
+        val name = if (Build.VERSION.SDK_INT >= Build.VERSION_CODES.R) {
+            remote.alias
+        } else {
+            remote.name
+        }
+        this.devices[remote.address] = Peripheral(activity, remote, this)
+        // no advertisement was received, so there is no rssi or advertised data
+        val device = BleDevice(remote.address, name ?: "", 0, false, null, null, null, null, null)
+        val res = JSObject()
+        res.put("result", device.toJsObject())
+        invoke.resolve(res)
+    }
+
     @Command
     fun disconnect(invoke: Invoke){
         val args = invoke.parseArgs(ConnectParams::class.java)
@@ -75,6 +152,82 @@ class BleClientPlugin(private val activity: Activity): Plugin(activity) {
         invoke.resolve(res)
     }
 
+    @Command
+    fun read_rssi(invoke: Invoke){
+        val args = invoke.parseArgs(ConnectParams::class.java)
+        val device = this.devices[args.address]
+        if (device == null){
+            invoke.reject("Device not found")
+            return
+        }
+        device.readRssi(invoke)
+    }
+
+    @InvokeArg
+    class MtuParams(){
+        val address: String = ""
+        val mtu: Int = 23
+    }
+    @Command
+    fun request_mtu(invoke: Invoke){
+        val args = invoke.parseArgs(MtuParams::class.java)
+        val device = this.devices[args.address]
+        if (device == null){
+            invoke.reject("Device not found")
+            return
+        }
+        device.requestMtu(invoke)
+    }
+
+    @Command
+    fun bond_state(invoke: Invoke){
+        val args = invoke.parseArgs(ConnectParams::class.java)
+        val device = this.devices[args.address]
+        if (device == null){
+            invoke.reject("Device not found")
+            return
+        }
+        device.bondState(invoke)
+    }
+
+    @Command
+    fun create_bond(invoke: Invoke){
+        val args = invoke.parseArgs(ConnectParams::class.java)
+        val device = this.devices[args.address]
+        if (device == null){
+            invoke.reject("Device not found")
+            return
+        }
+        device.createBond(invoke)
+    }
+
+    @Command
+    fun remove_bond(invoke: Invoke){
+        val args = invoke.parseArgs(ConnectParams::class.java)
+        val device = this.devices[args.address]
+        if (device == null){
+            invoke.reject("Device not found")
+            return
+        }
+        device.removeBond(invoke)
+    }
+
+    @InvokeArg
+    class PriorityParams(){
+        val address: String = ""
+        val priority: Int = 0
+    }
+    @Command
+    fun request_connection_priority(invoke: Invoke){
+        val args = invoke.parseArgs(PriorityParams::class.java)
+        val device = this.devices[args.address]
+        if (device == null){
+            invoke.reject("Device not found")
+            return
+        }
+        device.requestConnectionPriority(invoke, args.priority)
+    }
+
     @Command
     fun discover_services(invoke:Invoke){
         val args = invoke.parseArgs(ConnectParams::class.java)
@@ -115,6 +268,18 @@ class BleClientPlugin(private val activity: Activity): Plugin(activity) {
         invoke.resolve()
     }
 
+    @Command
+    fun mtu_changes(invoke:Invoke){
+        val args = invoke.parseArgs(NotifyParams::class.java)
+        val device = this.devices[args.address]
+        if (device == null){
+            invoke.reject("Device not found")
+            return
+        }
+        device.setMtuChannel(args.channel!!)
+        invoke.resolve()
+    }
+
     @InvokeArg
     class WriteParams() {
         val address: String = ""
@@ -149,9 +314,44 @@ class BleClientPlugin(private val activity: Activity): Plugin(activity) {
         device.read(invoke)
     }
 
+    @InvokeArg
+    class DescriptorParams(){
+        val address: String = ""
+        val characteristic: UUID? = null
+        val descriptor: UUID? = null
+        val data: ByteArray? = null
+    }
+    @Command
+    fun read_descriptor(invoke: Invoke){
+        val args = invoke.parseArgs(DescriptorParams::class.java)
+        val device = this.devices[args.address]
+        if (device == null){
+            invoke.reject("Device not found")
+            return
+        }
+        device.readDescriptor(invoke)
+    }
+
+    @Command
+    fun write_descriptor(invoke: Invoke){
+        val args = invoke.parseArgs(DescriptorParams::class.java)
+        val device = this.devices[args.address]
+        if (device == null){
+            invoke.reject("Device not found")
+            return
+        }
+        device.writeDescriptor(invoke)
+    }
+
+    @InvokeArg
+    class SubscribeParams(){
+        val address: String = ""
+        val characteristic: UUID? = null
+        val indicate: Boolean = false
+    }
     @Command
     fun subscribe(invoke: Invoke){
-        val args = invoke.parseArgs(ReadParams::class.java)
+        val args = invoke.parseArgs(SubscribeParams::class.java)
         val device = this.devices[args.address]
         if (device == null){
             invoke.reject("Device not found")
@@ -0,0 +1,253 @@
+package com.plugin.blec
+
+import android.Manifest
+import android.app.Activity
+import android.annotation.SuppressLint
+import android.bluetooth.BluetoothAdapter
+import android.bluetooth.BluetoothDevice
+import android.bluetooth.BluetoothGatt
+import android.bluetooth.BluetoothManager
+import android.content.Context.MODE_PRIVATE
+import android.content.Intent
+import android.content.pm.PackageManager
+import android.net.Uri
+import android.os.Build
+import android.provider.Settings
+import android.widget.Toast
+import androidx.core.app.ActivityCompat
+import androidx.core.content.ContextCompat.getSystemService
+
+// Platform calls of the permission and enable flows, instrumented tests replace them with a fake
+interface BluetoothEnvironment {
+    val sdkInt: Int
+    val packageName: String
+    fun isGranted(permission: String): Boolean
+    fun isFirstRequest(permission: String): Boolean
+    fun markRequested(permission: String)
+    fun shouldShowRationale(permission: String): Boolean
+    fun requestPermissions(permissions: Array<String>)
+    fun startActivity(intent: Intent)
+    fun requestEnable(intent: Intent)
+    fun isAdapterEnabled(): Boolean
+    fun showMessage(message: String)
+}
+
+class ActivityBluetoothEnvironment(private val activity: Activity): BluetoothEnvironment {
+    override val sdkInt: Int
+        get() = Build.VERSION.SDK_INT
+    override val packageName: String
+        get() = activity.packageName
+
+    override fun isGranted(permission: String): Boolean {
+        return ActivityCompat.checkSelfPermission(activity, permission) == PackageManager.PERMISSION_GRANTED
+    }
+
+    override fun isFirstRequest(permission: String): Boolean {
+        return activity.getSharedPreferences("PREFS_PERMISSION_FIRST_TIME_ASKING", MODE_PRIVATE)
+            .getBoolean(permission, true)
+    }
+
+    override fun markRequested(permission: String) {
+        activity.getSharedPreferences("PREFS_PERMISSION_FIRST_TIME_ASKING", MODE_PRIVATE)
+            .edit().putBoolean(permission, false).apply()
+    }
+
+    override fun shouldShowRationale(permission: String): Boolean {
+        return activity.shouldShowRequestPermissionRationale(permission)
+    }
+
+    override fun requestPermissions(permissions: Array<String>) {
+        activity.requestPermissions(permissions, 1)
+    }
+
+    override fun startActivity(intent: Intent) {
+        activity.startActivity(intent)
+    }
+
+    override fun requestEnable(intent: Intent) {
+        ActivityCompat.startActivityForResult(activity, intent, 0, null)
+    }
+
+    override fun isAdapterEnabled(): Boolean {
+        val manager = getSystemService(activity, BluetoothManager::class.java) ?: return false
+        return manager.adapter?.isEnabled ?: false
+    }
+
+    override fun showMessage(message: String) {
+        Toast.makeText(activity, message, Toast.LENGTH_SHORT).show()
+    }
+}
+
+enum class PermissionOutcome {
+    Granted,
+    // the permission dialog was opened
+    Requested,
+    // the permission was denied for good, the app settings were opened
+    OpenedSettings,
+}
+
+// runtime permissions needed to scan and connect
+fun requiredPermissions(sdkInt: Int): Array<String> {
+    return if (sdkInt >= Build.VERSION_CODES.S) {
+        arrayOf(
+            Manifest.permission.BLUETOOTH_SCAN,
+            Manifest.permission.BLUETOOTH_CONNECT
+        )
+    } else {
+        arrayOf(
+            Manifest.permission.BLUETOOTH_ADMIN,
+            Manifest.permission.BLUETOOTH,
+        )
+    }
+}
+
+fun appSettingsIntent(packageName: String): Intent {
+    return Intent(Settings.ACTION_APPLICATION_DETAILS_SETTINGS, Uri.parse("package:$packageName"))
+}
+
+fun enableRequestIntent(): Intent {
+    return Intent(BluetoothAdapter.ACTION_REQUEST_ENABLE)
+}
+
+fun checkPermissions(env: BluetoothEnvironment): PermissionOutcome {
+    val permissions = requiredPermissions(env.sdkInt)
+    for (perm in permissions){
+        if (env.isGranted(perm)) {
+            continue
+        }
+        if (env.isFirstRequest(perm) || env.shouldShowRationale(perm)) {
+            // this will open the permission dialog
+            env.markRequested(perm)
+            env.requestPermissions(permissions)
+            return PermissionOutcome.Requested
+        }
+        // this will open settings which asks for permission
+        env.startActivity(appSettingsIntent(env.packageName))
+        env.showMessage("Allow Permission: $perm")
+        return PermissionOutcome.OpenedSettings
+    }
+    return PermissionOutcome.Granted
+}
+
+// asks the user to switch bluetooth on, returns false if it was off
+fun requestEnableIfDisabled(env: BluetoothEnvironment): Boolean {
+    if (env.isAdapterEnabled()) {
+        return true
+    }
+    env.requestEnable(enableRequestIntent())
+    return false
+}
+
+// codes of the scan rejections, mapped to typed errors by the rust side (src/error.rs)
+const val PERMISSION_REQUESTED = "PERMISSION_REQUESTED"
+const val PERMISSION_DENIED = "PERMISSION_DENIED"
+const val BLUETOOTH_DISABLED = "BLUETOOTH_DISABLED"
+
+class ScanRejection(val message: String, val code: String)
+
+// checks the permissions and asks to switch bluetooth on before a scan, null if it can start
+fun scanPrecondition(env: BluetoothEnvironment): ScanRejection? {
+    return when (checkPermissions(env)) {
+        PermissionOutcome.Granted -> if (requestEnableIfDisabled(env)) {
+            null
+        } else {
+            ScanRejection("Bluetooth is disabled, asked the user to enable it", BLUETOOTH_DISABLED)
+        }
+        PermissionOutcome.Requested -> ScanRejection("Missing permissions, asked the user to grant them", PERMISSION_REQUESTED)
+        PermissionOutcome.OpenedSettings -> ScanRejection("Missing permissions, opened the app settings", PERMISSION_DENIED)
+    }
+}
+
+// bond and connection priority calls of a device, instrumented tests replace them with a fake
+interface DeviceEnvironment {
+    val bondState: Int
+    fun createBond(): Boolean
+    // BluetoothDevice.removeBond is hidden, throws if it is not reachable
+    fun removeBond(): Boolean
+    // null without a gatt connection
+    fun requestConnectionPriority(priority: Int): Boolean?
+}
+
+class GattDeviceEnvironment(
+    private val device: BluetoothDevice,
+    private val gatt: () -> BluetoothGatt?
+): DeviceEnvironment {
+    override val bondState: Int
+        @SuppressLint("MissingPermission")
+        get() = device.bondState
+
+    @SuppressLint("MissingPermission")
+    override fun createBond(): Boolean {
+        return device.createBond()
+    }
+
+    override fun removeBond(): Boolean {
+        // apps can only reach it through reflection
+        return device.javaClass.getMethod("removeBond").invoke(device) as Boolean
+    }
+
+    @SuppressLint("MissingPermission")
+    override fun requestConnectionPriority(priority: Int): Boolean? {
+        return gatt()?.requestConnectionPriority(priority)
+    }
+}
+
+enum class BondOutcome {
+    AlreadyBonded,
+    // the bond state broadcast answers the request
+    Started,
+    FailedToStart,
+}
+
+fun startBond(env: DeviceEnvironment): BondOutcome {
+    if (env.bondState == BluetoothDevice.BOND_BONDED) {
+        return BondOutcome.AlreadyBonded
+    }
+    return if (env.createBond()) BondOutcome.Started else BondOutcome.FailedToStart
+}
+
+enum class RemoveBondOutcome {
+    NotBonded,
+    Removed,
+    Failed,
+}
+
+fun removeBond(env: DeviceEnvironment): RemoveBondOutcome {
+    if (env.bondState == BluetoothDevice.BOND_NONE) {
+        return RemoveBondOutcome.NotBonded
+    }
+    val removed = try {
+        env.removeBond()
+    } catch (e: Exception) {
+        false
+    }
+    return if (removed) RemoveBondOutcome.Removed else RemoveBondOutcome.Failed
+}
+
+enum class PriorityOutcome {
+    NotConnected,
+    Requested,
+    Failed,
+}
+
+// priority is one of BluetoothGatt.CONNECTION_PRIORITY_*, see ConnectionPriority::android_value
+fun requestPriority(env: DeviceEnvironment, priority: Int): PriorityOutcome {
+    return when (env.requestConnectionPriority(priority)) {
+        null -> PriorityOutcome.NotConnected
+        true -> PriorityOutcome.Requested
+        false -> PriorityOutcome.Failed
+    }
+}
+
+class BondChange(val bonded: Boolean, val reason: Int)
+
+// reads a BluetoothDevice.ACTION_BOND_STATE_CHANGED broadcast, null while the bond is in progress
+fun bondChange(intent: Intent): BondChange? {
+    val bonded = when (intent.getIntExtra(BluetoothDevice.EXTRA_BOND_STATE, BluetoothDevice.ERROR)) {
+        BluetoothDevice.BOND_BONDED -> true
+        BluetoothDevice.BOND_NONE -> false
+        else -> return null
+    }
+    // hidden BluetoothDevice.EXTRA_REASON, one of the UNBOND_REASON_* values
+    return BondChange(bonded, intent.getIntExtra("android.bluetooth.device.extra.REASON", 0))
+}
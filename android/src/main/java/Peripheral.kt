@@ -6,12 +6,26 @@ import android.bluetooth.BluetoothGattCallback
 import android.bluetooth.BluetoothGattCharacteristic
 import android.bluetooth.BluetoothGattDescriptor
 import android.bluetooth.BluetoothGattService
+import android.bluetooth.BluetoothStatusCodes
+import android.content.BroadcastReceiver
+import android.content.Context
+import android.content.Intent
+import android.content.IntentFilter
 import android.os.Build
 import android.util.Log
 import app.tauri.plugin.Channel
 import app.tauri.plugin.Invoke
 import app.tauri.plugin.JSObject
 import com.plugin.blec.BleClientPlugin
+import com.plugin.blec.BondOutcome
+import com.plugin.blec.DeviceEnvironment
+import com.plugin.blec.GattDeviceEnvironment
+import com.plugin.blec.PriorityOutcome
+import com.plugin.blec.RemoveBondOutcome
+import com.plugin.blec.bondChange
+import com.plugin.blec.removeBond
+import com.plugin.blec.requestPriority
+import com.plugin.blec.startBond
 import org.json.JSONArray
 import java.util.UUID
 
@@ -34,9 +48,17 @@ class Peripheral(private val activity: Activity, private val device: BluetoothDe
     private var onConnectionStateChange: ((connected:Boolean,error:String)->Unit)? = null
     private var onServicesDiscovered: ((connected:Boolean,error:String)->Unit)? = null
     private var notifyChannel:Channel? = null
+    private var mtuChannel:Channel? = null
     private val onReadInvoke:MutableMap<UUID,Invoke> = mutableMapOf()
     private val onWriteInvoke:MutableMap<UUID,Invoke> = mutableMapOf()
     private var onDescriptorInvoke: Invoke? = null
+    // descriptor the pending onDescriptorInvoke writes to
+    private var descriptorWriteTarget: UUID? = null
+    private var onDescriptorReadInvoke: Invoke? = null
+    private var onRssiInvoke: Invoke? = null
+    private var onMtuInvoke: Invoke? = null
+    private var bondReceiver: BroadcastReceiver? = null
+    private val deviceEnv: DeviceEnvironment = GattDeviceEnvironment(device) { this.gatt }
 
     private enum class Event{
         DeviceConnected,
@@ -111,10 +133,13 @@ class Peripheral(private val activity: Activity, private val device: BluetoothDe
                 if (invoke == null) {
                     Log.e("Peripheral", "Did not find tauri invoke obj for write on $id")
                 } else {
-                    if (status != BluetoothGatt.GATT_SUCCESS) {
-                        invoke.reject("Write to characteristic $id failed with status $status")
+                    // application errors of the device are a response, the rust side decides
+                    if (status == BluetoothGatt.GATT_SUCCESS || status in 0x80..0x9F) {
+                        val res = JSObject()
+                        res.put("status", status)
+                        invoke.resolve(res)
                     } else {
-                        invoke.resolve()
+                        invoke.reject("Write to characteristic $id failed with status $status", status.toString())
                     }
                 }
                 this@Peripheral.onWriteInvoke.remove(id)
@@ -134,7 +159,7 @@ class Peripheral(private val activity: Activity, private val device: BluetoothDe
                     Log.e("Peripheral", "Did not find tauri invoke obj for read on $id")
                 } else {
                     if (status != BluetoothGatt.GATT_SUCCESS) {
-                        invoke.reject("Read from characteristic $id failed with status $status")
+                        invoke.reject("Read from characteristic $id failed with status $status", status.toString())
                     } else {
                         val res = JSObject()
                         res.put("value", bytesToJson(value))
@@ -145,19 +170,66 @@ class Peripheral(private val activity: Activity, private val device: BluetoothDe
             }
         }
 
+        override fun onReadRemoteRssi(gatt: BluetoothGatt?, rssi: Int, status: Int) {
+            val invoke = this@Peripheral.onRssiInvoke ?: return
+            if (status != BluetoothGatt.GATT_SUCCESS) {
+                invoke.reject("RSSI read failed with status $status", status.toString())
+            } else {
+                val res = JSObject()
+                res.put("rssi", rssi)
+                invoke.resolve(res)
+            }
+            this@Peripheral.onRssiInvoke = null
+        }
+
+        override fun onMtuChanged(gatt: BluetoothGatt?, mtu: Int, status: Int) {
+            // also called when the device renegotiates the MTU
+            if (status == BluetoothGatt.GATT_SUCCESS) {
+                val change = JSObject()
+                change.put("mtu", mtu)
+                this@Peripheral.mtuChannel?.send(change)
+            }
+            val invoke = this@Peripheral.onMtuInvoke ?: return
+            if (status != BluetoothGatt.GATT_SUCCESS) {
+                invoke.reject("MTU request failed with status $status", status.toString())
+            } else {
+                val res = JSObject()
+                res.put("mtu", mtu)
+                invoke.resolve(res)
+            }
+            this@Peripheral.onMtuInvoke = null
+        }
+
         override fun onDescriptorWrite(
             gatt: BluetoothGatt?,
             descriptor: BluetoothGattDescriptor?,
             status: Int
         ) {
             if (status != BluetoothGatt.GATT_SUCCESS){
-                this@Peripheral.onDescriptorInvoke?.reject("descriptor write failed with status: $status")
-            } else if (descriptor?.uuid != CLIENT_CHARACTERISTIC_CONFIGURATION_DESCRIPTOR){
+                this@Peripheral.onDescriptorInvoke?.reject("descriptor write failed with status: $status", status.toString())
+            } else if (descriptor?.uuid != this@Peripheral.descriptorWriteTarget){
                 this@Peripheral.onDescriptorInvoke?.reject("unexpected write to descriptor: ${descriptor?.uuid}")
             } else {
                 this@Peripheral.onDescriptorInvoke?.resolve()
             }
         }
+
+        override fun onDescriptorRead(
+            gatt: BluetoothGatt,
+            descriptor: BluetoothGattDescriptor,
+            status: Int,
+            value: ByteArray
+        ) {
+            val invoke = this@Peripheral.onDescriptorReadInvoke ?: return
+            if (status != BluetoothGatt.GATT_SUCCESS) {
+                invoke.reject("Read from descriptor ${descriptor.uuid} failed with status $status", status.toString())
+            } else {
+                val res = JSObject()
+                res.put("value", bytesToJson(value))
+                invoke.resolve(res)
+            }
+            this@Peripheral.onDescriptorReadInvoke = null
+        }
     }
 
     @SuppressLint("MissingPermission")
@@ -265,6 +337,10 @@ class Peripheral(private val activity: Activity, private val device: BluetoothDe
         this.notifyChannel = channel;
     }
 
+    fun setMtuChannel(channel: Channel){
+        this.mtuChannel = channel;
+    }
+
     @SuppressLint("MissingPermission")
     fun write(invoke: Invoke){
         val args = invoke.parseArgs(BleClientPlugin.WriteParams::class.java)
@@ -284,13 +360,24 @@ class Peripheral(private val activity: Activity, private val device: BluetoothDe
             }
             this.onWriteInvoke[args.characteristic] = invoke
         }
-        if (Build.VERSION.SDK_INT >= Build.VERSION_CODES.TIRAMISU) {
-            gatt.writeCharacteristic(charac,args.data!!,if (args.withResponse){BluetoothGattCharacteristic.WRITE_TYPE_DEFAULT}else{BluetoothGattCharacteristic.WRITE_TYPE_NO_RESPONSE})
+        val error = if (Build.VERSION.SDK_INT >= Build.VERSION_CODES.TIRAMISU) {
+            when (val status = gatt.writeCharacteristic(charac,args.data!!,if (args.withResponse){BluetoothGattCharacteristic.WRITE_TYPE_DEFAULT}else{BluetoothGattCharacteristic.WRITE_TYPE_NO_RESPONSE})) {
+                BluetoothStatusCodes.SUCCESS -> null
+                // the plugin retries congested writes
+                BluetoothStatusCodes.ERROR_GATT_WRITE_REQUEST_BUSY -> "Write to characteristic ${args.characteristic} not started, stack congested"
+                else -> "Write to characteristic ${args.characteristic} not started, status $status"
+            }
         } else {
             @Suppress("DEPRECATION")
             charac.value = args.data
             @Suppress("DEPRECATION")
-            gatt.writeCharacteristic(charac)
+            if (gatt.writeCharacteristic(charac)) null else "Write to characteristic ${args.characteristic} not started, stack busy or congested"
+        }
+        if (error != null) {
+            synchronized(this.onWriteInvoke) {
+                this.onWriteInvoke.remove(args.characteristic)
+            }
+            invoke.reject(error)
         }
     }
 
@@ -316,9 +403,103 @@ class Peripheral(private val activity: Activity, private val device: BluetoothDe
         gatt.readCharacteristic(charac)
     }
 
+    @SuppressLint("MissingPermission")
+    fun readRssi(invoke: Invoke){
+        val gatt = this.gatt
+        if (gatt == null){
+            invoke.reject("No gatt server connected")
+            return
+        }
+        this.onRssiInvoke?.reject("RSSI read was overwritten before finishing")
+        this.onRssiInvoke = invoke
+        if (!gatt.readRemoteRssi()){
+            this.onRssiInvoke = null
+            invoke.reject("Failed to start RSSI read")
+        }
+    }
+
+    fun bondState(invoke: Invoke){
+        val res = JSObject()
+        res.put("bonded", this.deviceEnv.bondState == BluetoothDevice.BOND_BONDED)
+        invoke.resolve(res)
+    }
+
+    fun createBond(invoke: Invoke){
+        this.bondReceiver?.let { activity.unregisterReceiver(it) }
+        this.bondReceiver = null
+        // registered before the bond starts, so a fast answer is not missed
+        val receiver = object : BroadcastReceiver() {
+            override fun onReceive(context: Context?, intent: Intent) {
+                val device: BluetoothDevice? = intent.getParcelableExtra(BluetoothDevice.EXTRA_DEVICE)
+                if (device?.address != this@Peripheral.device.address){
+                    return
+                }
+                val change = bondChange(intent) ?: return
+                val res = JSObject()
+                res.put("bonded", change.bonded)
+                res.put("reason", change.reason)
+                activity.unregisterReceiver(this)
+                this@Peripheral.bondReceiver = null
+                invoke.resolve(res)
+            }
+        }
+        activity.registerReceiver(receiver, IntentFilter(BluetoothDevice.ACTION_BOND_STATE_CHANGED))
+        when (startBond(this.deviceEnv)) {
+            BondOutcome.Started -> this.bondReceiver = receiver
+            BondOutcome.AlreadyBonded -> {
+                activity.unregisterReceiver(receiver)
+                val res = JSObject()
+                res.put("bonded", true)
+                res.put("reason", 0)
+                invoke.resolve(res)
+            }
+            BondOutcome.FailedToStart -> {
+                activity.unregisterReceiver(receiver)
+                invoke.reject("Failed to start bonding")
+            }
+        }
+    }
+
+    fun removeBond(invoke: Invoke){
+        val res = JSObject()
+        when (removeBond(this.deviceEnv)) {
+            RemoveBondOutcome.NotBonded -> res.put("removed", false)
+            RemoveBondOutcome.Removed -> res.put("removed", true)
+            RemoveBondOutcome.Failed -> {
+                invoke.reject("Failed to remove the bond")
+                return
+            }
+        }
+        invoke.resolve(res)
+    }
+
+    fun requestConnectionPriority(invoke: Invoke, priority: Int){
+        when (requestPriority(this.deviceEnv, priority)) {
+            PriorityOutcome.Requested -> invoke.resolve()
+            PriorityOutcome.NotConnected -> invoke.reject("No gatt server connected")
+            PriorityOutcome.Failed -> invoke.reject("Failed to request connection priority")
+        }
+    }
+
+    @SuppressLint("MissingPermission")
+    fun requestMtu(invoke: Invoke){
+        val args = invoke.parseArgs(BleClientPlugin.MtuParams::class.java)
+        val gatt = this.gatt
+        if (gatt == null){
+            invoke.reject("No gatt server connected")
+            return
+        }
+        this.onMtuInvoke?.reject("MTU request was overwritten before finishing")
+        this.onMtuInvoke = invoke
+        if (!gatt.requestMtu(args.mtu)){
+            this.onMtuInvoke = null
+            invoke.reject("Failed to start MTU request")
+        }
+    }
+
     @SuppressLint("MissingPermission")
     fun subscribe(invoke: Invoke,enabled: Boolean){
-        val args = invoke.parseArgs(BleClientPlugin.ReadParams::class.java)
+        val args = invoke.parseArgs(BleClientPlugin.SubscribeParams::class.java)
         val gatt = this.gatt;
         if (gatt == null){
             invoke.reject("No gatt server connected")
@@ -332,17 +513,86 @@ class Peripheral(private val activity: Activity, private val device: BluetoothDe
 
         if (!this.gatt!!.setCharacteristicNotification(charac,enabled)){
             invoke.reject("Failed to set notification status")
+            return
+        }
+        val descriptor = charac.getDescriptor(CLIENT_CHARACTERISTIC_CONFIGURATION_DESCRIPTOR)
+        if (descriptor == null){
+            invoke.reject("Characteristic ${args.characteristic} has no client characteristic configuration descriptor")
+            return
         }
         this.onDescriptorInvoke = invoke
-        val descriptor: BluetoothGattDescriptor = charac.getDescriptor(CLIENT_CHARACTERISTIC_CONFIGURATION_DESCRIPTOR)
-        val data = if (enabled){BluetoothGattDescriptor.ENABLE_NOTIFICATION_VALUE}else{BluetoothGattDescriptor.DISABLE_NOTIFICATION_VALUE}
-        if (Build.VERSION.SDK_INT >= Build.VERSION_CODES.TIRAMISU) {
-            gatt.writeDescriptor(descriptor,data)
+        this.descriptorWriteTarget = CLIENT_CHARACTERISTIC_CONFIGURATION_DESCRIPTOR
+        // the rust side only asks for indications if the characteristic does not notify
+        val data = if (!enabled){
+            BluetoothGattDescriptor.DISABLE_NOTIFICATION_VALUE
+        } else if (args.indicate){
+            BluetoothGattDescriptor.ENABLE_INDICATION_VALUE
+        } else {
+            BluetoothGattDescriptor.ENABLE_NOTIFICATION_VALUE
+        }
+        this.startDescriptorWrite(gatt, descriptor, data)
+    }
+
+    // rejects the pending descriptor invoke if the stack does not start the write, no callback
+    // would ever answer it
+    @SuppressLint("MissingPermission")
+    private fun startDescriptorWrite(gatt: BluetoothGatt, descriptor: BluetoothGattDescriptor, data: ByteArray) {
+        val started = if (Build.VERSION.SDK_INT >= Build.VERSION_CODES.TIRAMISU) {
+            gatt.writeDescriptor(descriptor, data) == BluetoothStatusCodes.SUCCESS
         } else {
             @Suppress("DEPRECATION")
             descriptor.value = data
             @Suppress("DEPRECATION")
             gatt.writeDescriptor(descriptor)
         }
+        if (!started) {
+            this.onDescriptorInvoke?.reject("Write to descriptor ${descriptor.uuid} not started, stack busy or congested")
+            this.onDescriptorInvoke = null
+        }
+    }
+
+    private fun findDescriptor(invoke: Invoke, args: BleClientPlugin.DescriptorParams): BluetoothGattDescriptor?{
+        val charac = this.characteristics[args.characteristic!!]
+        if (charac == null){
+            invoke.reject("Characteristic ${args.characteristic} not found")
+            return null
+        }
+        val descriptor = charac.getDescriptor(args.descriptor!!)
+        if (descriptor == null){
+            invoke.reject("Descriptor ${args.descriptor} not found")
+        }
+        return descriptor
+    }
+
+    @SuppressLint("MissingPermission")
+    fun readDescriptor(invoke: Invoke){
+        val args = invoke.parseArgs(BleClientPlugin.DescriptorParams::class.java)
+        val gatt = this.gatt
+        if (gatt == null){
+            invoke.reject("No gatt server connected")
+            return
+        }
+        val descriptor = this.findDescriptor(invoke, args) ?: return
+        this.onDescriptorReadInvoke?.reject("descriptor read was overwritten before finishing")
+        this.onDescriptorReadInvoke = invoke
+        if (!gatt.readDescriptor(descriptor)){
+            this.onDescriptorReadInvoke = null
+            invoke.reject("Failed to start descriptor read")
+        }
+    }
+
+    @SuppressLint("MissingPermission")
+    fun writeDescriptor(invoke: Invoke){
+        val args = invoke.parseArgs(BleClientPlugin.DescriptorParams::class.java)
+        val gatt = this.gatt
+        if (gatt == null){
+            invoke.reject("No gatt server connected")
+            return
+        }
+        val descriptor = this.findDescriptor(invoke, args) ?: return
+        this.onDescriptorInvoke?.reject("descriptor write was overwritten before finishing")
+        this.onDescriptorInvoke = invoke
+        this.descriptorWriteTarget = descriptor.uuid
+        this.startDescriptorWrite(gatt, descriptor, args.data!!)
     }
 }
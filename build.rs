@@ -12,10 +12,160 @@ const COMMANDS: &[&str] = &[
     "subscribe_string",
     "unsubscribe",
     "scanning_state",
+    "open_session",
+    "close_session",
+    "start_capture",
+    "stop_capture",
+    "request_multi",
+    "get_state",
+    "set_connection_limit",
+    "run_conformance_check",
+    "set_keep_alive",
+    "clear_keep_alive",
+    "set_duplicate_filter",
+    "scan_capabilities",
+    "set_fingerprint_rule",
+    "recv_from_all",
+    "set_characteristic_options",
+    "get_metrics",
+    "reset_metrics",
+    "set_metrics_interval",
+    "has_critical_operation",
+    "begin_critical_operation",
+    "end_critical_operation",
+    "set_exit_guard",
+    "device_info",
+    "read_pnp_id",
+    "set_removal_debounce",
+    "health",
+    "set_heartbeat_interval",
+    "get_mtu",
+    "connection_capabilities",
+    "connected_services",
+    "remove_listener",
+    "set_retry_policy",
+    "is_known_device",
+    "transfer",
+    "resume_transfer",
+    "cancel_transfer",
+    "clear_previously_connected",
+    "max_write_len",
+    "set_multi_adapter_scan",
+    "wait_for_device",
+    "reconnect_when_available",
+    "cancel_wait_for_device",
+    "export_session_timeline",
+    "set_timeline_payloads",
+    "ensure_initialized",
+    "authorization_status",
+    "write_batch",
+    "connect_additional",
+    "connected_addresses",
+    "accept_pairing_consent",
+    "set_pairing_auto_accept",
+    "set_payload_logging",
+    "request_mtu",
+    "send_chunked",
+    "descriptors",
+    "read_descriptor",
+    "write_descriptor",
+    "pair",
+    "is_paired",
+    "list_adapters",
+    "selected_adapter",
+    "select_adapter",
+    "battery_level",
+    "start_battery_monitor",
+    "stop_battery_monitor",
+    "adapter_state",
+    "start_discovery",
+    "stop_discovery",
+    "register_scan_consumer",
+    "unregister_scan_consumer",
+    "send_with_response",
+    "set_address_policy",
+    "get_services",
+    "charac_properties",
+    "forget_device",
+    "subscribe_timestamped",
+    "set_time_offset",
+    "clock_sync",
+    "device_candidates",
+    "wait_for_notification",
+    "verify_subscriptions",
+    "set_notification_silence_timeout",
 ];
 
+const APPEARANCE_VALUES: &str = "assigned_numbers/appearance_values.yaml";
+
 fn main() {
-    tauri_plugin::Builder::new(COMMANDS)
-        .android_path("android")
-        .build();
+    generate_appearance_table();
+    // the commands and the android library only exist with the plugin
+    if std::env::var_os("CARGO_FEATURE_TAURI").is_some() {
+        tauri_plugin::Builder::new(COMMANDS)
+            .android_path("android")
+            .build();
+    }
+}
+
+/// Writes the appearance categories of the assigned numbers YAML to `$OUT_DIR/appearance.rs`
+/// Only the subset of YAML used by the assigned numbers file is understood: a list of
+/// `category`/`name` entries with an optional `subcategory` list of `value`/`name` entries.
+fn generate_appearance_table() {
+    println!("cargo:rerun-if-changed={APPEARANCE_VALUES}");
+    let yaml =
+        std::fs::read_to_string(APPEARANCE_VALUES).expect("failed to read appearance values");
+    // (category, name, subcategories)
+    let mut categories: Vec<(u16, String, Vec<(u16, String)>)> = vec![];
+    let mut subcategory: Option<u16> = None;
+    for line in yaml.lines() {
+        let line = line.trim().trim_start_matches("- ");
+        if line.starts_with('#') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim().trim_matches(|c| c == '\'' || c == '"');
+        match key {
+            "category" => {
+                categories.push((parse_hex(value), String::new(), vec![]));
+                subcategory = None;
+            }
+            "value" => subcategory = Some(parse_hex(value)),
+            "name" => {
+                let category = categories
+                    .last_mut()
+                    .expect("appearance name before the first category");
+                match subcategory.take() {
+                    Some(subcategory) => category.2.push((subcategory, value.to_string())),
+                    None => category.1 = value.to_string(),
+                }
+            }
+            _ => {}
+        }
+    }
+    let mut out = String::from(
+        "/// Generated by build.rs from assigned_numbers/appearance_values.yaml\n\
+         static APPEARANCE_CATEGORIES: &[AppearanceCategory] = &[\n",
+    );
+    for (category, name, subcategories) in categories {
+        let subcategories: Vec<String> = subcategories
+            .iter()
+            .map(|(value, name)| format!("({value:#04x}, {name:?})"))
+            .collect();
+        out.push_str(&format!(
+            "    AppearanceCategory {{ category: {category:#05x}, name: {name:?}, subcategories: &[{}] }},\n",
+            subcategories.join(", ")
+        ));
+    }
+    out.push_str("];\n");
+    let out_dir = std::env::var("OUT_DIR").expect("OUT_DIR not set");
+    std::fs::write(std::path::Path::new(&out_dir).join("appearance.rs"), out)
+        .expect("failed to write appearance table");
+}
+
+fn parse_hex(value: &str) -> u16 {
+    u16::from_str_radix(value.trim_start_matches("0x"), 16)
+        .unwrap_or_else(|_| panic!("invalid appearance value {value}"))
 }
@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A bluetooth adapter of the system, see [`crate::list_adapters`]
+ */
+export type AdapterInfo = { 
+/**
+ * Position in the adapter list of the OS
+ */
+index: number, 
+/**
+ * Adapter info of the platform, e.g. `hci1 (usb:v1D6Bp0246d0537)` on Linux, the index on
+ * android
+ */
+id: string, };
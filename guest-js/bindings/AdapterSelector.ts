@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Picks the adapter used by the handler, by index or name
+ * A name matches the whole [`AdapterInfo::id`] or its first word, e.g. `hci1` on Linux.
+ */
+export type AdapterSelector = number | string;
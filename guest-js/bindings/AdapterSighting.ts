@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Adapter that received advertisements of a device during a multi adapter scan
+ */
+export type AdapterSighting = { adapterId: string, 
+/**
+ * RSSI of the last advertisement the adapter received
+ */
+rssi: number | null, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * State of the adapter returned by [`Handler::adapter_state`], changes are published as
+ * [`BleEvent::AdapterStateChanged`]
+ */
+export type AdapterState = "poweredOn" | "poweredOff" | "unauthorized" | "unknown";
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * How strictly addresses passed to the handler are checked, see
+ * [`crate::Handler::set_address_policy`]
+ */
+export type AddressPolicy = "coerce" | "strict";
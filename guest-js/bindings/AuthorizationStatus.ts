@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Whether the app may use bluetooth, see [`Handler::authorization_status`]
+ */
+export type AuthorizationStatus = "notDetermined" | "denied" | "restricted" | "allowed";
@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Progress of [`Handler::write_batch`], sent after every write
+ */
+export type BatchProgress = { 
+/**
+ * Writes attempted so far
+ */
+completed: number, failed: number, total: number, };
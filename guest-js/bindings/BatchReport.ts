@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { BatchWriteResult } from "./BatchWriteResult";
+
+/**
+ * Result of [`Handler::write_batch`]
+ */
+export type BatchReport = { 
+/**
+ * All writes succeeded
+ */
+success: boolean, results: Array<BatchWriteResult>, };
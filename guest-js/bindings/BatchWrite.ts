@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { CharacteristicRefInput } from "./CharacteristicRefInput";
+import type { WriteType } from "./WriteType";
+
+/**
+ * Write of [`Handler::write_batch`]
+ */
+export type BatchWrite = { characteristic: CharacteristicRefInput, data: Uint8Array | Array<number>, writeType: WriteType, };
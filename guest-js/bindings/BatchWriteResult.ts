@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Outcome of one write of a batch, in the order of the batch
+ */
+export type BatchWriteResult = { "status": "written" } | { "status": "failed", error: string, } | { "status": "skipped" };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Battery level of a device reported by the battery monitor, see
+ * [`Handler::start_battery_monitor`]
+ */
+export type BatteryLevel = { address: string, percent: number, };
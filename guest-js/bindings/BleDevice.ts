@@ -0,0 +1,75 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AdapterSighting } from "./AdapterSighting";
+import type { JsonValue } from "./serde_json/JsonValue";
+
+/**
+ * A BLE device as reported to the frontend
+ */
+export type BleDevice = { address: string, name: string, isConnected: boolean, 
+/**
+ * Manufacturer specific data by company id, merged over all advertisements of the scan
+ */
+manufacturerData: { [key in number]?: Array<number> }, 
+/**
+ * Service UUIDs advertised by the device, merged over all advertisements of the scan
+ */
+services: Array<string>, 
+/**
+ * Service data by service UUID, merged over all advertisements of the scan
+ */
+serviceData: { [key in string]?: Array<number> }, 
+/**
+ * Class of Device, only reported for devices also seen over BR/EDR
+ */
+deviceClass: number | null, 
+/**
+ * Whether the device advertises Bluetooth Classic (BR/EDR) support
+ * `None` if neither the advertising flags nor the device class are known
+ */
+brEdrCapable: boolean | null, 
+/**
+ * Signal strength in dBm of the last advertisement, updated with every scan result
+ */
+rssi: number | null, 
+/**
+ * Number of advertisements received during the scan
+ * Only reported with [`crate::DuplicateFilter::ReportAll`]
+ */
+advertisementCount?: number, 
+/**
+ * Rolling median of the time between advertisements, once enough were received
+ * Only reported with [`crate::DuplicateFilter::ReportAll`]. Packets merged by the OS make
+ * the interval appear longer, see [`crate::ScanCapabilities::adv_interval_exact`].
+ */
+advIntervalMs?: number, 
+/**
+ * Key computed by the fingerprint set with [`crate::Handler::set_device_fingerprint`]
+ */
+fingerprint?: string, 
+/**
+ * Data added by the enricher set with [`crate::Handler::set_device_enricher`]
+ */
+extra?: JsonValue, 
+/**
+ * The device was connected successfully in this session, or in an earlier one if a
+ * [`crate::BlecStore`] is set
+ */
+previouslyConnected: boolean, 
+/**
+ * Adapter used to connect, the one that received the strongest RSSI
+ * Only reported by scans with [`crate::Handler::set_multi_adapter_scan`]
+ */
+adapterId?: string, 
+/**
+ * Every adapter that received advertisements of the device, strongest RSSI first
+ * Only reported by scans with [`crate::Handler::set_multi_adapter_scan`]
+ */
+adapters?: Array<AdapterSighting>, 
+/**
+ * Appearance value of the advertisement, only reported on android
+ */
+appearance?: number, 
+/**
+ * Name of the appearance, see [`crate::appearance_name`]
+ */
+appearanceName?: string, };
@@ -0,0 +1,19 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { OperationMetrics } from "./OperationMetrics";
+
+/**
+ * Latency metrics returned by [`crate::Handler::get_metrics`]
+ */
+export type BleMetrics = { reads: OperationMetrics, writes: OperationMetrics, subscribes: OperationMetrics, 
+/**
+ * Times the stack reported congestion
+ */
+congestion: number, 
+/**
+ * Times the device answered with ATT Insufficient Resources
+ */
+insufficientResources: number, 
+/**
+ * Reads and writes retried after one of the transient errors above
+ */
+retries: number, };
@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AuthorizationStatus } from "./AuthorizationStatus";
+import type { BatteryLevel } from "./BatteryLevel";
+import type { ConnectionLimitPolicy } from "./ConnectionLimitPolicy";
+import type { ConnectionState } from "./ConnectionState";
+
+/**
+ * Snapshot of the handler state returned by [`Handler::get_state`]
+ */
+export type BleState = { connected: boolean, connectionState: ConnectionState, scanning: boolean, 
+/**
+ * Number of connections counted against the connection limit
+ */
+connections: number, connectionLimit: number, connectionLimitPolicy: ConnectionLimitPolicy, authorization: AuthorizationStatus, 
+/**
+ * Latest level of [`Handler::start_battery_monitor`], kept after a disconnect
+ */
+battery: BatteryLevel | null, };
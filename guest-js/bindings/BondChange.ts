@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * New bond state of a device, published as [`BleEvent::BondChanged`]
+ */
+export type BondChange = { address: string, bonded: boolean, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Kind of user callback in a [`CallbackPanic`]
+ */
+export type CallbackKind = "onDisconnect" | "listener" | "deviceEnricher" | "deviceFingerprint";
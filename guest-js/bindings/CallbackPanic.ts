@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { CallbackKind } from "./CallbackKind";
+
+/**
+ * A user callback panicked, published as [`BleEvent::CallbackPanicked`]
+ */
+export type CallbackPanic = { callback: CallbackKind, 
+/**
+ * Device the callback ran for
+ */
+address: string, 
+/**
+ * Characteristic of a listener, the address for the other callbacks
+ */
+context: string, message: string, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Statistics of a running or finished capture
+ */
+export type CaptureStats = { frames: number, bytes: number, durationMs: number, drops: number, files: number, };
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Descriptor } from "./Descriptor";
+
+/**
+ * Serialized form of [`btleplug::api::Characteristic`], see [`ServiceRepr`]
+ */
+export type Characteristic = { uuid: string, service_uuid: string, 
+/**
+ * Names of the set property flags joined with ` | `, e.g. `READ | NOTIFY`
+ */
+properties: string, descriptors: Array<Descriptor>, };
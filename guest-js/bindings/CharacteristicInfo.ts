@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { CharacteristicProperties } from "./CharacteristicProperties";
+
+/**
+ * Characteristic of a [`ServiceInfo`]
+ */
+export type CharacteristicInfo = { uuid: string, properties: CharacteristicProperties, 
+/**
+ * UUIDs of the descriptors of the characteristic
+ */
+descriptors: Array<string>, };
@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ReadStrategy } from "./ReadStrategy";
+import type { RequiredWrite } from "./RequiredWrite";
+import type { SubscribeMode } from "./SubscribeMode";
+
+/**
+ * Options applied to all operations on a characteristic,
+ * set with [`Handler::set_characteristic_options`]
+ */
+export type CharacteristicOptions = { readStrategy?: ReadStrategy, 
+/**
+ * Write that must be done on the connection before the characteristic is subscribed
+ */
+requiresWrite?: RequiredWrite, 
+/**
+ * Whether subscribing enables notifications or indications
+ */
+subscribeMode?: SubscribeMode, };
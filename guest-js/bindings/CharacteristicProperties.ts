@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Properties of a characteristic decoded from the GATT properties bitfield
+ */
+export type CharacteristicProperties = { broadcast: boolean, read: boolean, write: boolean, writeWithoutResponse: boolean, notify: boolean, indicate: boolean, authenticatedSignedWrites: boolean, extendedProperties: boolean, };
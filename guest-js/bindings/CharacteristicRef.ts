@@ -0,0 +1,16 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Fully qualified characteristic for devices offering the same characteristic in multiple services
+ * Deserializes from a plain uuid or from `{ address, service, characteristic }`.
+ */
+export type CharacteristicRef = { 
+/**
+ * Address of the device, required if several devices are connected, see
+ * [`crate::Handler::connect_additional`]
+ */
+address?: string | null, 
+/**
+ * Service containing the characteristic, required if multiple services contain it
+ */
+service?: string | null, characteristic: string, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A [`CharacteristicRef`] as accepted from the frontend, a plain uuid or the full reference
+ */
+export type CharacteristicRefInput = string | { address?: string, service?: string, characteristic: string, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Outcome of a single check
+ */
+export type CheckOutcome = { "result": "passed" } | { "result": "failed", "reason": string } | { "result": "unsupported", "reason": string } | { "result": "skipped", "reason": string };
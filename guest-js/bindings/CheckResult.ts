@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { CheckOutcome } from "./CheckOutcome";
+
+export type CheckResult = { name: string, outcome: CheckOutcome, latencyMs: number | null, };
@@ -0,0 +1,13 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Pairs a reading of the monotonic clock with the wall clock at the same moment, captured once
+ * when a device connected
+ * Timestamps of the session are mapped with this single reading, so adjustments of the system
+ * clock during the session do not move them.
+ */
+export type ClockSync = { monotonicUs: number, 
+/**
+ * Microseconds since the unix epoch
+ */
+unixUs: number, };
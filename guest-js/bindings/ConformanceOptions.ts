@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Options for [`Handler::run_conformance_check`]
+ */
+export type ConformanceOptions = { 
+/**
+ * Device to connect to if no device is connected yet
+ */
+address?: string, 
+/**
+ * Service advertised by the device, used to check scan filtering
+ */
+scanService?: string, 
+/**
+ * Characteristic supporting read, write and notify for the round trip checks
+ */
+characteristic?: string, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { CheckResult } from "./CheckResult";
+
+/**
+ * Report returned by [`Handler::run_conformance_check`]
+ */
+export type ConformanceReport = { platform: string, checks: Array<CheckResult>, };
@@ -0,0 +1,94 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { CharacteristicRefInput } from "./CharacteristicRefInput";
+import type { ConnectionLimit } from "./ConnectionLimit";
+import type { ReconnectPolicy } from "./ReconnectPolicy";
+import type { SetupAction } from "./SetupAction";
+
+/**
+ * Options for [`Handler::connect_with_options`]
+ */
+export type ConnectOptions = { 
+/**
+ * ATT MTU requested right after the link is up, before services are discovered
+ * Ignored on platforms without explicit MTU requests, see
+ * [`Handler::connection_capabilities`]
+ */
+requestMtu?: number, 
+/**
+ * Bond with the device before any data is exchanged, pairing is started if the device is
+ * not bonded yet. Progress is published as [`BleEvent::Pairing`]. The device
+ * only becomes [`ConnectionState::Ready`] once bonded, pairing fails after a minute.
+ * Only supported on android, see [`Handler::connection_capabilities`]
+ */
+requireBonded?: boolean, 
+/**
+ * Steps run natively once the services are resolved
+ */
+setup?: Array<SetupAction>, 
+/**
+ * Caps the whole connect, including the scan, bonding and setup script
+ * The step in flight is cancelled when the time is up and the connect fails with
+ * [`Error::TotalTimeout`] naming it.
+ */
+totalTimeoutMs?: number, 
+/**
+ * Id of the adapter to connect with, see [`Handler::set_multi_adapter_scan`]
+ * Defaults to the adapter that received the strongest RSSI during the last scan.
+ */
+adapter?: string, 
+/**
+ * Platform id of the device to connect to if the address matches several devices, see
+ * [`Handler::device_candidates`]
+ * Without it the most recently seen device is used, unless the address matches devices
+ * that are known to be different, which fails with [`Error::AmbiguousAddress`].
+ */
+peripheralId?: string, 
+/**
+ * Disconnects once no operation succeeded and no notification arrived for this long
+ * Keep-alive pings do not count as activity. The disconnect is reported with
+ * [`DisconnectReason::IdleTimeout`].
+ */
+idleDisconnectMs?: number, 
+/**
+ * After an idle disconnect, the next send, read or subscribe connects again with the same
+ * options before it runs
+ */
+reconnectOnDemand?: boolean, 
+/**
+ * How long to scan for a device the platform does not know, defaults to 2000ms
+ * The platform is asked for the address first, so bonded devices and devices the OS cached
+ * are connected without scanning.
+ */
+scanTimeoutMs?: number, 
+/**
+ * Characteristics the device has to offer, each one is looked up across all services
+ * Every discovered characteristic can be used after connecting, listing them here makes
+ * the connect fail early with [`Error::CharacNotAvailable`] or
+ * [`Error::AmbiguousCharacteristic`] if one is missing or in several services without
+ * [`CharacteristicRef::service`], and limits [`ConnectResult::characteristics`] to them.
+ */
+characteristics?: Array<CharacteristicRefInput>, 
+/**
+ * Connects again with these options when the link is lost
+ * Listeners added with [`Handler::subscribe`] are kept while the device is away and
+ * subscribed again once the services are resolved. The disconnect callback runs with
+ * [`ConnectionState::Reconnecting`] when the link is lost and again with
+ * [`ConnectionState::Disconnected`] if every attempt failed, which is also published with
+ * [`DisconnectReason::ReconnectFailed`]. Attempts are published as
+ * [`BleEvent::ReconnectProgress`]. [`Handler::disconnect`] cancels the reconnect.
+ */
+reconnect?: ReconnectPolicy, 
+/**
+ * Caps establishing the link and discovering the services, the connect fails with
+ * [`Error::ConnectionTimeout`] when the time is up
+ * Unlike [`ConnectOptions::total_timeout_ms`] the scan, bonding and setup script are not
+ * counted. A connect still pending in the platform is cancelled, so the next connect can
+ * start right away.
+ */
+timeoutMs?: number, 
+/**
+ * Limit of simultaneous connections this connect waits for or fails on, defaults to the
+ * limit of [`Handler::set_connection_limit`]
+ * Connecting again to the connected device reuses its slot.
+ */
+connectionLimit?: ConnectionLimit, };
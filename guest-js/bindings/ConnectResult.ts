@@ -0,0 +1,29 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { CharacteristicRef } from "./CharacteristicRef";
+import type { Service } from "./Service";
+import type { ServiceInfo } from "./ServiceInfo";
+import type { SetupStepResult } from "./SetupStepResult";
+import type { WriteLimits } from "./WriteLimits";
+
+/**
+ * Result of [`Handler::connect_with_options`]
+ */
+export type ConnectResult = { services: Array<Service>, 
+/**
+ * The characteristics matched by [`ConnectOptions::characteristics`], every characteristic
+ * of the resolved services if none were listed
+ */
+characteristics: Array<CharacteristicRef>, 
+/**
+ * The resolved services with the decoded properties of their characteristics, like
+ * [`Handler::discover_all_services`]
+ */
+gatt: Array<ServiceInfo>, 
+/**
+ * Results of [`ConnectOptions::setup`] in the order of the steps
+ */
+setup: Array<SetupStepResult>, 
+/**
+ * Largest payload of a single write per write type when the connect finished
+ */
+maxWriteLen: WriteLimits, };
@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Connection features of the platform returned by [`Handler::connection_capabilities`]
+ */
+export type ConnectionCapabilities = { 
+/**
+ * The MTU can be requested with [`ConnectOptions::request_mtu`]
+ */
+mtuRequest: boolean, 
+/**
+ * Platform specific remarks on the MTU
+ */
+mtuNote: string, 
+/**
+ * The bond state can be checked and pairing started with [`ConnectOptions::require_bonded`]
+ */
+bonding: boolean, };
@@ -0,0 +1,19 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { InterferenceCause } from "./InterferenceCause";
+
+/**
+ * Subscriptions found cleared on the device, published as
+ * [`BleEvent::ConnectionInterfered`]
+ * The client characteristic configuration of a subscribed characteristic no longer enables
+ * notifications, typically because another application on the host opened the device and the
+ * stack reset it.
+ */
+export type ConnectionInterference = { address: string, cause: InterferenceCause, 
+/**
+ * Characteristics subscribed again
+ */
+restored: Array<string>, 
+/**
+ * Characteristics that could not be subscribed again or are no longer offered
+ */
+failed: Array<string>, };
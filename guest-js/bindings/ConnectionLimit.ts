@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ConnectionLimitPolicy } from "./ConnectionLimitPolicy";
+
+/**
+ * Limit of simultaneous connections a connect is counted against, see
+ * [`ConnectOptions::connection_limit`]
+ */
+export type ConnectionLimit = { max: number, policy?: ConnectionLimitPolicy, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Behavior of [`Handler::connect`] when the connection limit is reached
+ */
+export type ConnectionLimitPolicy = "queue" | "failFast";
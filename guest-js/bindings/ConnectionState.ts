@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Stage of the connection, data operations only run in [`ConnectionState::Ready`]
+ */
+export type ConnectionState = "disconnected" | "connecting" | "discoveringServices" | "ready" | "disconnecting" | "reconnecting";
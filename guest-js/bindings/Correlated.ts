@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Payload of the events and results of an operation started with a correlation id
+ */
+export type Correlated<T> = { correlationId: string, data: T, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Error of an operation started with a correlation id, serialized as
+ * `{ correlationId, message }`
+ */
+export type CorrelatedError = { correlationId: string, message: string, };
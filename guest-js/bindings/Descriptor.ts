@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Serialized form of [`btleplug::api::Descriptor`], see [`ServiceRepr`]
+ */
+export type Descriptor = { uuid: string, service_uuid: string, characteristic_uuid: string, };
@@ -0,0 +1,24 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * One of the devices an address matches, see [`Error::AmbiguousAddress`]
+ */
+export type DeviceCandidate = { 
+/**
+ * Adapter that saw the device, `None` for the default adapter outside of multi adapter
+ * scans
+ */
+adapter: string | null, 
+/**
+ * Platform id of the device, connect to this candidate by passing it as
+ * [`crate::ConnectOptions::peripheral_id`]
+ */
+peripheralId: string, 
+/**
+ * Fingerprint from [`crate::Handler::set_device_fingerprint`], if one is set
+ */
+fingerprint: string | null, 
+/**
+ * Milliseconds since the unix epoch when the device was last reported
+ */
+lastSeen: number, };
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { PnpId } from "./PnpId";
+
+/**
+ * Values of the Device Information service
+ * Characteristics the device does not provide are `None`
+ */
+export type DeviceInfo = { manufacturerName: string | null, modelNumber: string | null, serialNumber: string | null, hardwareRevision: string | null, firmwareRevision: string | null, softwareRevision: string | null, pnpId: PnpId | null, };
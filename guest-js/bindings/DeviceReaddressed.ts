@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Sent when a device with a known fingerprint is seen under a new address
+ */
+export type DeviceReaddressed = { fingerprint: string, oldAddress: string, newAddress: string, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Why the device was disconnected
+ */
+export type DisconnectReason = "requested" | "linkLost" | "idleTimeout" | "adapterOff" | "reconnectFailed";
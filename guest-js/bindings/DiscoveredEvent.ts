@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { BleDevice } from "./BleDevice";
+
+/**
+ * Change of the devices found by [`Handler::discover_stream`]
+ */
+export type DiscoveredEvent = { "type": "added" } & BleDevice | { "type": "updated" } & BleDevice | { "type": "removed", address: string, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * How repeated advertisements of the same device are reported by [`Handler::discover`]
+ */
+export type DuplicateFilter = "platform" | "reportAll" | "deduplicate";
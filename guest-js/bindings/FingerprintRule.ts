@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Declarative fingerprint for [`Handler::set_fingerprint_rule`]
+ * The key is built from the bytes `start..end` of the selected advertisement field.
+ */
+export type FingerprintRule = { "source": "manufacturerData", companyId: number, start: number, end: number, } | { "source": "serviceData", service: string, start: number, end: number, };
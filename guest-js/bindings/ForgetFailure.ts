@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ForgetStep } from "./ForgetStep";
+
+/**
+ * Step of [`Handler::forget_device`] that failed
+ */
+export type ForgetFailure = { step: ForgetStep, error: string, };
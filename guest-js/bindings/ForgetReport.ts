@@ -0,0 +1,19 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ForgetFailure } from "./ForgetFailure";
+
+/**
+ * Result of [`Handler::forget_device`], also published as [`BleEvent::DeviceForgotten`]
+ */
+export type ForgetReport = { address: string, 
+/**
+ * The device was connected and is disconnected now
+ */
+disconnected: boolean, 
+/**
+ * The OS bond was removed, false if the device was not bonded
+ */
+bondRemoved: boolean, 
+/**
+ * Steps that failed, the state of the plugin was cleared anyway
+ */
+failures: Array<ForgetFailure>, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Step of [`Handler::forget_device`] that can fail without stopping the others
+ */
+export type ForgetStep = "disconnect" | "removeBond";
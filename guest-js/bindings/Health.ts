@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Health of the handler returned by [`Handler::health`]
+ */
+export type Health = { 
+/**
+ * Set when the central event stream stopped yielding and the adapter failed the liveness check
+ */
+eventLoopDegraded: boolean, 
+/**
+ * Milliseconds since the last central event or successful adapter poll
+ */
+lastEventMs: number, };
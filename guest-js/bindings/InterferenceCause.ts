@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * What made the plugin check the subscriptions of the connected device
+ */
+export type InterferenceCause = "servicesChanged" | "notificationSilence" | "verification";
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Operation executed by the keep-alive set with [`Handler::set_keep_alive`]
+ */
+export type KeepAliveMethod = { "type": "rssi" } | { "type": "read", characteristic: string, } | { "type": "emptyWrite", characteristic: string, };
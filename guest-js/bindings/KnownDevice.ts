@@ -0,0 +1,23 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Result of [`Handler::is_known_device`]
+ */
+export type KnownDevice = { 
+/**
+ * The address is a MAC address, or a uuid on apple platforms
+ */
+validFormat: boolean, 
+/**
+ * The device was discovered by a scan and not removed by the adapter since
+ */
+inCache: boolean, 
+/**
+ * Bond state from the last connect with [`ConnectOptions::require_bonded`],
+ * `None` if it was never checked
+ */
+bonded: boolean | null, 
+/**
+ * Milliseconds since the unix epoch when the device was last reported by a scan
+ */
+lastSeen: number | null, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Latency histogram with the buckets `<10, <25, <50, <100, <250, <1000, >=1000` ms
+ */
+export type LatencyHistogram = { buckets: Array<number>, count: number, totalUs: number, maxUs: number, };
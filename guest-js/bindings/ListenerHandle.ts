@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Identifies a single listener registered with [`Handler::subscribe`]
+ */
+export type ListenerHandle = number;
@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * [`crate::MultiResponse`] with the frames encoded as base64
+ */
+export type MultiResponse = { 
+/**
+ * Base64 encoded frames in arrival order
+ */
+frames: Array<string>, 
+/**
+ * True if the end marker or `max_frames` was reached before the timeout
+ */
+complete: boolean, };
@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Notifications of one characteristic received within one second of the session
+ */
+export type NotificationBucket = { 
+/**
+ * Seconds since the connect started
+ */
+second: number, characteristic: string, count: number, bytes: number, };
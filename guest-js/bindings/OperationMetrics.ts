@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { LatencyHistogram } from "./LatencyHistogram";
+
+/**
+ * Latencies of one kind of operation
+ */
+export type OperationMetrics = { 
+/**
+ * Duration of the GATT call itself
+ */
+gatt: LatencyHistogram, 
+/**
+ * Time spent waiting for earlier operations on the device before the GATT call started
+ */
+queueWait: LatencyHistogram, };
@@ -0,0 +1,16 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { PairingKind } from "./PairingKind";
+
+/**
+ * Pairing the OS wants the user to confirm, published as
+ * [`crate::BleEvent::PairingConsentRequired`]
+ * Requested while connecting with `require_bonded`, on [`Handler::pair`], and on Windows also
+ * after an operation failed with [`Error::PairingRequired`]. Answer with
+ * [`Handler::accept_pairing_consent`] within 60 seconds, the pairing is cancelled otherwise.
+ */
+export type PairingConsentRequest = { address: string, kind: PairingKind, 
+/**
+ * PIN to show to the user for [`PairingKind::DisplayPin`] and
+ * [`PairingKind::ConfirmPinMatch`]
+ */
+pin: string | null, };
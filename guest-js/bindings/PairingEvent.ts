@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { PairingState } from "./PairingState";
+
+/**
+ * Progress of pairing started by [`ConnectOptions::require_bonded`]
+ */
+export type PairingEvent = { address: string, state: PairingState, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Kind of confirmation the OS asks for in a [`PairingConsentRequest`]
+ */
+export type PairingKind = "confirmOnly" | "displayPin" | "providePin" | "confirmPinMatch" | "other";
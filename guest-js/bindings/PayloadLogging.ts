@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * How payload bytes appear in log output and session timelines
+ * Defaults to [`PayloadLogging::Lengths`], so logs and exported timelines do not leak user data.
+ * Captures started with [`crate::Handler::start_capture`] are explicit data exports and always
+ * contain the full payloads.
+ */
+export type PayloadLogging = { "mode": "off" } | { "mode": "lengths" } | { "mode": "hexTruncated", "maxBytes": number } | { "mode": "full" };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { VendorIdSource } from "./VendorIdSource";
+
+/**
+ * Parsed value of the PnP ID characteristic (0x2A50)
+ */
+export type PnpId = { vendorIdSource: VendorIdSource, vendorId: number, productId: number, productVersion: number, };
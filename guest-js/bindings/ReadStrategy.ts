@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * How [`Handler::recv_data`] reads a characteristic
+ */
+export type ReadStrategy = "single" | "doubleRead" | { "retryUntilStable": number };
@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Reconnects after the link was lost, see [`ConnectOptions::reconnect`]
+ */
+export type ReconnectPolicy = { 
+/**
+ * Connects tried before the device is given up
+ */
+maxAttempts: number, 
+/**
+ * Wait before the first attempt, doubled after every failed attempt up to 30 seconds
+ */
+backoffMs: number, };
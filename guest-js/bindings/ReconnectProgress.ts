@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Stage of [`Handler::reconnect_when_available`] or an automatic reconnect of
+ * [`crate::ConnectOptions::reconnect`], published as [`BleEvent::ReconnectProgress`]
+ */
+export type ReconnectProgress = { "stage": "waitingForDevice", address: string, } | { "stage": "deviceFound", address: string, } | { "stage": "connecting", address: string, } | { "stage": "connected", address: string, } | { "stage": "failed", address: string, error: string, } | { "stage": "cancelled", address: string, };
@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Ordering constraint of [`CharacteristicOptions::requires_write`]
+ * Some devices break their notifications if a characteristic is subscribed before e.g. a mode
+ * byte was written to a control characteristic. The constraint is met by a successful write of
+ * exactly `data` to `characteristic` and reset on every connect.
+ */
+export type RequiredWrite = { characteristic: string, data: Uint8Array | Array<number>, 
+/**
+ * Write `data` with response before subscribing instead of failing the subscribe with
+ * [`Error::SubscribeBeforeWrite`]
+ */
+auto?: boolean, };
@@ -0,0 +1,13 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Retries of reads and writes failing with transient errors, set with
+ * [`Handler::set_retry_policy`]
+ * ATT Insufficient Resources (0x11) is retried after `backoff` times the attempt number. Android's
+ * GATT_CONGESTED pauses the operation queue for a fixed time before retrying.
+ */
+export type RetryPolicy = { 
+/**
+ * Total number of attempts, 1 disables retries
+ */
+maxAttempts: number, backoffMs: number, };
@@ -0,0 +1,23 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Duplicate handling of the platform returned by [`Handler::scan_capabilities`]
+ */
+export type ScanCapabilities = { 
+/**
+ * The OS reports every advertisement packet to the plugin
+ */
+osReportsDuplicates: boolean, 
+/**
+ * The OS can deduplicate advertisements, otherwise deduplication is emulated by the plugin
+ */
+osDeduplication: boolean, 
+/**
+ * Platform specific remarks on duplicate reporting
+ */
+duplicatesNote: string, 
+/**
+ * Every advertisement reaches the plugin, so [`BleDevice::adv_interval_ms`] is close to the
+ * advertising interval of the device. Otherwise merged packets make it appear longer.
+ */
+advIntervalExact: boolean, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Identifies a consumer registered with [`Handler::register_scan_consumer`]
+ */
+export type ScanConsumerId = number;
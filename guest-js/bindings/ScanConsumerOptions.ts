@@ -0,0 +1,24 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ScanNameFilter } from "./ScanNameFilter";
+
+/**
+ * What a consumer of the shared scan is reported, see [`Handler::register_scan_consumer`]
+ */
+export type ScanConsumerOptions = { 
+/**
+ * Only report devices advertising any of these services, all devices if empty
+ */
+services?: Array<string>, 
+/**
+ * Applied to [`BleDevice::name`]
+ */
+nameFilter?: ScanNameFilter, 
+/**
+ * Minimum time between two updates of the same device, every update is reported if 0
+ */
+debounceMs?: number, 
+/**
+ * Devices not reported by the scan for this long are removed from the view of the
+ * consumer, they are only removed when the adapter drops them if not set
+ */
+expiryMs?: number, };
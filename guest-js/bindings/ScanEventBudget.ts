@@ -0,0 +1,16 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Limits the device events a scan publishes, see [`crate::DiscoverOptions::event_budget`] and
+ * [`crate::Handler::start_discovery_with_event_budget`]
+ * Changes that exceed the budget wait per device and only the latest state of a device is
+ * published on a later tick. New devices go first, then devices whose advertisement changed
+ * and RSSI-only updates last. Removals are never held back but count against the budget. The
+ * devices sent to the channel of [`crate::Handler::discover`] are not limited, they are sent as
+ * one list per scan interval.
+ */
+export type ScanEventBudget = { 
+/**
+ * Device events published per second at most, removals included
+ */
+maxEventsPerSecond: number, };
@@ -0,0 +1,28 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * How a scan with a [`ScanEventBudget`] coalesced its events, published as
+ * [`crate::BleEvent::ScanEventStats`] when the scan ends
+ */
+export type ScanEventStats = { 
+/**
+ * Device events published, removals included
+ */
+published: number, 
+/**
+ * Updates replaced by a newer state of the same device before they were published
+ */
+coalesced: number, 
+/**
+ * Updates that waited for a later tick of the budget
+ */
+delayed: number, 
+/**
+ * Updates still waiting when the device was removed or the scan ended, they were never
+ * published
+ */
+dropped: number, 
+/**
+ * Most updates waiting at the same time
+ */
+maxPending: number, };
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Filter on the advertised local name of scanned devices, applied on top of the [`ScanFilter`]
+ * Devices that do not advertise a local name are excluded by every filter but
+ * [`ScanNameFilter::None`].
+ */
+export type ScanNameFilter = { "type": "none" } | { "type": "exact", "value": string } | { "type": "prefix", "value": string } | { "type": "contains", "value": string };
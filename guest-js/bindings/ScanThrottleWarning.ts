@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * The app is about to reach the scan start limit of the OS, published as
+ * [`BleEvent::ScanThrottleWarning`]
+ */
+export type ScanThrottleWarning = { 
+/**
+ * Scans that can still be started before the OS throttles scanning
+ */
+remaining: number, 
+/**
+ * Milliseconds until the oldest counted start no longer counts
+ */
+resetsInMs: number, };
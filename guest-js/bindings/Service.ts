@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Characteristic } from "./Characteristic";
+
+/**
+ * Serialized form of [`Service`], only used to export its TypeScript binding
+ */
+export type Service = { uuid: string, primary: boolean, characteristics: Array<Characteristic>, };
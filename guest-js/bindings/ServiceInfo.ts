@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { CharacteristicInfo } from "./CharacteristicInfo";
+
+/**
+ * Service of the GATT database of the connected device, see
+ * [`crate::Handler::discover_all_services`]
+ */
+export type ServiceInfo = { uuid: string, primary: boolean, characteristics: Array<CharacteristicInfo>, };
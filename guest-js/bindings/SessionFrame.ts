@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A notification frame delivered through a [`SessionHandle`]
+ */
+export type SessionFrame = { 
+/**
+ * The characteristic the frame was received on
+ */
+characteristic: string, 
+/**
+ * Arrival time in microseconds since the session was opened
+ */
+timestampUs: number, data: Array<number>, };
@@ -0,0 +1,32 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { BleMetrics } from "./BleMetrics";
+import type { DisconnectReason } from "./DisconnectReason";
+import type { NotificationBucket } from "./NotificationBucket";
+import type { TimelineEntry } from "./TimelineEntry";
+
+/**
+ * Timeline of a connect session returned by [`Handler::export_session_timeline`]
+ */
+export type SessionTimeline = { address: string, 
+/**
+ * Start of the connect in milliseconds since the unix epoch
+ */
+startedAt: number, 
+/**
+ * Until the disconnect or, for the current session, until the export
+ */
+durationMs: number, disconnectReason: DisconnectReason | null, 
+/**
+ * Stages, MTU and priority changes, operations, callback panics and the disconnect in the
+ * order they happened
+ */
+entries: Array<TimelineEntry>, notifications: Array<NotificationBucket>, 
+/**
+ * Entries not recorded because the session exceeded the entry limit
+ */
+droppedEntries: number, 
+/**
+ * Metrics of the handler at the time of the export, they cover all sessions since the last
+ * [`Handler::reset_metrics`]
+ */
+metrics: BleMetrics, };
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SetupStep } from "./SetupStep";
+import type { StepErrorPolicy } from "./StepErrorPolicy";
+
+/**
+ * A setup step with its error policy
+ */
+export type SetupAction = { onError?: StepErrorPolicy, } & SetupStep;
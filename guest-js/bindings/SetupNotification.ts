@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Notification received on a characteristic subscribed by a setup script
+ */
+export type SetupNotification = { characteristic: string, data: Array<number>, };
@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { CharacteristicRefInput } from "./CharacteristicRefInput";
+import type { ConnectionPriority } from "./ConnectionPriority";
+import type { WriteType } from "./WriteType";
+
+/**
+ * A single step of the setup script run by [`Handler::connect_with_options`]
+ */
+export type SetupStep = { "type": "requestMtu", mtu: number, } | { "type": "setPriority", priority: ConnectionPriority, } | { "type": "subscribe", characteristic: CharacteristicRefInput, } | { "type": "write", characteristic: CharacteristicRefInput, data: Uint8Array | Array<number>, writeType: WriteType, } | { "type": "delayMs", ms: number, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ListenerHandle } from "./ListenerHandle";
+
+/**
+ * Outcome of a setup step, in the order of the script
+ */
+export type SetupStepResult = { "status": "done" } | { "status": "subscribed", listener: ListenerHandle, } | { "status": "skipped", reason: string, } | { "status": "failed", error: string, };
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * What [`Handler::subscribe`] enables in the client characteristic configuration of a
+ * characteristic, set with [`CharacteristicOptions::subscribe_mode`]
+ * Indications are confirmed by the client, the data arrives through the same listeners as
+ * notifications. The android bridge enables exactly the kind of the mode, on the other
+ * platforms the OS stack picks the kind and prefers notifications if a characteristic
+ * supports both, there the mode only decides whether a characteristic is accepted.
+ */
+export type SubscribeMode = "auto" | "notify" | "indicate";
@@ -0,0 +1,24 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Result of reading back the subscriptions of the connected device, see
+ * [`Handler::verify_subscriptions`]
+ */
+export type SubscriptionReport = { address: string, 
+/**
+ * Enabled on the device as expected
+ */
+verified: Array<string>, 
+/**
+ * Found cleared and subscribed again
+ */
+restored: Array<string>, 
+/**
+ * Found cleared or gone and could not be subscribed again
+ */
+failed: Array<string>, 
+/**
+ * Without a readable client characteristic configuration descriptor, or on apple
+ * platforms not known to CoreBluetooth, their state is unknown
+ */
+unverifiable: Array<string>, };
@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Notification with the time it arrived, see [`Handler::subscribe_timestamped`]
+ */
+export type TimedNotification = { address: string, characteristic: string, 
+/**
+ * Microseconds on the monotonic clock shared by all devices, with the offset of
+ * [`Handler::set_time_offset`] applied
+ * Convert it to the wall clock with [`Handler::clock_sync`].
+ */
+timestampUs: number, data: Array<number>, };
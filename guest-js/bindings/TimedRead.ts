@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Result of a read issued by [`Handler::recv_from_all`]
+ */
+export type TimedRead = { 
+/**
+ * The value read, `None` if the read failed
+ */
+data: Array<number> | null, error: string | null, 
+/**
+ * Time from issuing the read until the result arrived, including time spent waiting for
+ * earlier operations on the device
+ */
+latencyUs: number, };
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { TimelineEvent } from "./TimelineEvent";
+
+export type TimelineEntry = { 
+/**
+ * Milliseconds since the connect started
+ */
+atMs: number, } & TimelineEvent;
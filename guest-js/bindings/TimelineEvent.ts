@@ -0,0 +1,32 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { CallbackKind } from "./CallbackKind";
+import type { ConnectionPriority } from "./ConnectionPriority";
+import type { ConnectionState } from "./ConnectionState";
+import type { DisconnectReason } from "./DisconnectReason";
+import type { TimelineOperation } from "./TimelineOperation";
+
+/**
+ * What happened at a [`TimelineEntry`]
+ */
+export type TimelineEvent = { "type": "stage", state: ConnectionState, 
+/**
+ * How long the connection stayed in the stage, `None` for the current stage
+ */
+durationMs: number | null, } | { "type": "mtuChanged", mtu: number, } | { "type": "connectionPriority", priority: ConnectionPriority, } | { "type": "operation", operation: TimelineOperation, characteristic: string, 
+/**
+ * Time spent waiting for earlier operations on the device
+ */
+queueWaitMs: number, latencyMs: number, 
+/**
+ * `None` if the operation succeeded
+ */
+error: string | null, 
+/**
+ * Length of the written or read payload, not recorded with [`PayloadLogging::Off`]
+ */
+len: number | null, 
+/**
+ * Hex encoded payload, only recorded with [`PayloadLogging::HexTruncated`] and
+ * [`PayloadLogging::Full`]
+ */
+data: string | null, } | { "type": "disconnected", reason: DisconnectReason, } | { "type": "callbackPanicked", callback: CallbackKind, context: string, message: string, };
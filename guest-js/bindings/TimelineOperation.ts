@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Kind of operation in a [`TimelineEvent::Operation`]
+ */
+export type TimelineOperation = "read" | "write" | "subscribe";
@@ -0,0 +1,26 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { WriteType } from "./WriteType";
+
+/**
+ * Options of [`Handler::transfer`]
+ */
+export type TransferOptions = { 
+/**
+ * Bytes per write, defaults to [`Handler::max_write_len_for`], which follows MTU changes
+ */
+chunkSize?: number, writeType?: WriteType, 
+/**
+ * When the link is lost, wait until the device is connected again and resume
+ * automatically. Otherwise the transfer is paused until [`Handler::resume_transfer`].
+ */
+resumeOnReconnect?: boolean, 
+/**
+ * How long to wait for the device with `resume_on_reconnect`, defaults to 30 seconds
+ * The transfer is paused if the device does not reconnect in time.
+ */
+reconnectTimeoutMs?: number, 
+/**
+ * Caps the whole call including the waits for reconnects, the write in flight is cancelled
+ * and the transfer dropped when the time is up
+ */
+totalTimeoutMs?: number, };
@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Result of a completed transfer
+ */
+export type TransferReport = { id: number, bytes: number, 
+/**
+ * CRC-32 (IEEE) of all transferred bytes
+ */
+crc32: number, 
+/**
+ * Number of times the transfer was resumed after a link loss
+ */
+resumes: number, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Authority that assigned the vendor id of a [`PnpId`]
+ */
+export type VendorIdSource = { "type": "bluetoothSig" } | { "type": "usbIf" } | { "type": "reserved", "value": number };
@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Encoding of the payloads sent to the frontend by `subscribe` and `open_session`
+ * JSON encodes every byte of a notification as a number, which roughly triples the IPC payload
+ * of small notifications. The binary formats are sent as raw IPC payloads and decoded by the
+ * guest-js bindings.
+ */
+export type WireFormat = "json" | "cbor";
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Largest payload of a single write per write type, see [`Handler::max_write_len_for`]
+ */
+export type WriteLimits = { withResponse: number | null, withoutResponse: number | null, };
@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Response of a write issued by [`Handler::send_data_with_response`]
+ * ATT write responses carry no data, devices that answer with data do so in a notification,
+ * see [`Handler::request_multi`].
+ */
+export type WriteResponse = { 
+/**
+ * ATT status of the response, 0 on success
+ * Application errors of the device (0x80 to 0x9F) are only reported here on android, the
+ * other platforms fail the write with the error of the OS.
+ */
+status: number, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Serialized form of [`WriteType`], only used to export its TypeScript binding
+ */
+export type WriteType = "withResponse" | "withoutResponse";
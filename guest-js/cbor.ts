@@ -0,0 +1,71 @@
+/**
+ * Minimal CBOR decoder for the payloads of the `cbor` wire format.
+ * Supports unsigned and negative integers, byte and text strings, arrays, maps and the simple
+ * values false, true and null, which is everything the plugin sends.
+ */
+export function decodeCbor(buffer: ArrayBuffer): unknown {
+  const view = new DataView(buffer)
+  const bytes = new Uint8Array(buffer)
+  const text = new TextDecoder()
+  let offset = 0
+
+  function readLength(info: number): number {
+    if (info < 24) return info
+    let value: number
+    switch (info) {
+      case 24: value = view.getUint8(offset); offset += 1; break
+      case 25: value = view.getUint16(offset); offset += 2; break
+      case 26: value = view.getUint32(offset); offset += 4; break
+      case 27: value = Number(view.getBigUint64(offset)); offset += 8; break
+      default: throw new Error(`unsupported CBOR length encoding ${info}`)
+    }
+    return value
+  }
+
+  function readItem(): unknown {
+    const initial = view.getUint8(offset)
+    offset += 1
+    const major = initial >> 5
+    const info = initial & 0x1f
+    switch (major) {
+      case 0: return readLength(info)
+      case 1: return -1 - readLength(info)
+      case 2: {
+        const len = readLength(info)
+        const value = bytes.slice(offset, offset + len)
+        offset += len
+        return value
+      }
+      case 3: {
+        const len = readLength(info)
+        const value = text.decode(bytes.subarray(offset, offset + len))
+        offset += len
+        return value
+      }
+      case 4: {
+        const len = readLength(info)
+        const value = []
+        for (let i = 0; i < len; i++) value.push(readItem())
+        return value
+      }
+      case 5: {
+        const len = readLength(info)
+        const value: Record<string, unknown> = {}
+        for (let i = 0; i < len; i++) {
+          const key = readItem()
+          value[String(key)] = readItem()
+        }
+        return value
+      }
+      case 7:
+        if (info === 20) return false
+        if (info === 21) return true
+        if (info === 22) return null
+        throw new Error(`unsupported CBOR simple value ${info}`)
+      default:
+        throw new Error(`unsupported CBOR major type ${major}`)
+    }
+  }
+
+  return readItem()
+}
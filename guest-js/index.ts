@@ -1,38 +1,322 @@
 import { Channel, invoke } from '@tauri-apps/api/core'
+import { listen, UnlistenFn } from '@tauri-apps/api/event'
+import type { AdapterInfo } from './bindings/AdapterInfo'
+import type { AdapterSelector } from './bindings/AdapterSelector'
+import type { AdapterSighting } from './bindings/AdapterSighting'
+import type { AdapterState } from './bindings/AdapterState'
+import type { AddressPolicy } from './bindings/AddressPolicy'
+import type { AuthorizationStatus } from './bindings/AuthorizationStatus'
+import type { BatchProgress } from './bindings/BatchProgress'
+import type { BatchReport } from './bindings/BatchReport'
+import type { BatchWrite } from './bindings/BatchWrite'
+import type { BatchWriteResult } from './bindings/BatchWriteResult'
+import type { BatteryLevel } from './bindings/BatteryLevel'
+import type { BleDevice } from './bindings/BleDevice'
+import type { BleMetrics } from './bindings/BleMetrics'
+import type { BleState } from './bindings/BleState'
+import type { BondChange } from './bindings/BondChange'
+import type { CallbackKind } from './bindings/CallbackKind'
+import type { CallbackPanic } from './bindings/CallbackPanic'
+import type { CaptureFormat } from './bindings/CaptureFormat'
+import type { CaptureStats } from './bindings/CaptureStats'
+import type { CharacteristicInfo } from './bindings/CharacteristicInfo'
+import type { CharacteristicOptions } from './bindings/CharacteristicOptions'
+import type { CharacteristicProperties } from './bindings/CharacteristicProperties'
+import type { CharacteristicRef } from './bindings/CharacteristicRef'
+import type { CharacteristicRefInput } from './bindings/CharacteristicRefInput'
+import type { CheckOutcome } from './bindings/CheckOutcome'
+import type { CheckResult } from './bindings/CheckResult'
+import type { ClockSync } from './bindings/ClockSync'
+import type { ConformanceOptions } from './bindings/ConformanceOptions'
+import type { ConformanceReport } from './bindings/ConformanceReport'
+import type { ConnectOptions } from './bindings/ConnectOptions'
+import type { ConnectResult } from './bindings/ConnectResult'
+import type { ConnectionCapabilities } from './bindings/ConnectionCapabilities'
+import type { ConnectionInterference } from './bindings/ConnectionInterference'
+import type { ConnectionLimit } from './bindings/ConnectionLimit'
+import type { ConnectionLimitPolicy } from './bindings/ConnectionLimitPolicy'
+import type { ConnectionPriority } from './bindings/ConnectionPriority'
+import type { ConnectionState } from './bindings/ConnectionState'
+import type { Correlated } from './bindings/Correlated'
+import type { CorrelatedError } from './bindings/CorrelatedError'
+import type { DeviceCandidate } from './bindings/DeviceCandidate'
+import type { DeviceInfo } from './bindings/DeviceInfo'
+import type { DeviceReaddressed } from './bindings/DeviceReaddressed'
+import type { DisconnectReason } from './bindings/DisconnectReason'
+import type { DiscoveredEvent } from './bindings/DiscoveredEvent'
+import type { DuplicateFilter } from './bindings/DuplicateFilter'
+import type { FingerprintRule } from './bindings/FingerprintRule'
+import type { ForgetFailure } from './bindings/ForgetFailure'
+import type { ForgetReport } from './bindings/ForgetReport'
+import type { ForgetStep } from './bindings/ForgetStep'
+import type { Health } from './bindings/Health'
+import type { InterferenceCause } from './bindings/InterferenceCause'
+import type { KeepAliveMethod } from './bindings/KeepAliveMethod'
+import type { KnownDevice } from './bindings/KnownDevice'
+import type { LatencyHistogram } from './bindings/LatencyHistogram'
+import type { ListenerHandle } from './bindings/ListenerHandle'
+import type { MultiResponse } from './bindings/MultiResponse'
+import type { NotificationBucket } from './bindings/NotificationBucket'
+import type { OperationMetrics } from './bindings/OperationMetrics'
+import type { PairingConsentRequest } from './bindings/PairingConsentRequest'
+import type { PairingEvent } from './bindings/PairingEvent'
+import type { PairingKind } from './bindings/PairingKind'
+import type { PairingState } from './bindings/PairingState'
+import type { PayloadLogging } from './bindings/PayloadLogging'
+import type { PnpId } from './bindings/PnpId'
+import type { ReadStrategy } from './bindings/ReadStrategy'
+import type { ReconnectPolicy } from './bindings/ReconnectPolicy'
+import type { ReconnectProgress } from './bindings/ReconnectProgress'
+import type { RequiredWrite } from './bindings/RequiredWrite'
+import type { RetryPolicy } from './bindings/RetryPolicy'
+import type { ScanCapabilities } from './bindings/ScanCapabilities'
+import type { ScanConsumerId } from './bindings/ScanConsumerId'
+import type { ScanConsumerOptions } from './bindings/ScanConsumerOptions'
+import type { ScanEventBudget } from './bindings/ScanEventBudget'
+import type { ScanEventStats } from './bindings/ScanEventStats'
+import type { ScanNameFilter } from './bindings/ScanNameFilter'
+import type { ScanThrottleWarning } from './bindings/ScanThrottleWarning'
+import type { Service } from './bindings/Service'
+import type { ServiceInfo } from './bindings/ServiceInfo'
+import type { SessionFrame as SessionFramePayload } from './bindings/SessionFrame'
+import type { SessionTimeline } from './bindings/SessionTimeline'
+import type { SetupAction } from './bindings/SetupAction'
+import type { SetupNotification } from './bindings/SetupNotification'
+import type { SetupStep } from './bindings/SetupStep'
+import type { SetupStepResult } from './bindings/SetupStepResult'
+import type { StepErrorPolicy } from './bindings/StepErrorPolicy'
+import type { SubscribeMode } from './bindings/SubscribeMode'
+import type { SubscriptionReport } from './bindings/SubscriptionReport'
+import type { TimedNotification } from './bindings/TimedNotification'
+import type { TimedRead } from './bindings/TimedRead'
+import type { TimelineEntry } from './bindings/TimelineEntry'
+import type { TimelineEvent } from './bindings/TimelineEvent'
+import type { TimelineOperation } from './bindings/TimelineOperation'
+import type { TransferOptions } from './bindings/TransferOptions'
+import type { TransferReport } from './bindings/TransferReport'
+import type { VendorIdSource } from './bindings/VendorIdSource'
+import type { WireFormat } from './bindings/WireFormat'
+import type { WriteLimits } from './bindings/WriteLimits'
+import type { WriteResponse } from './bindings/WriteResponse'
+import type { WriteType } from './bindings/WriteType'
+import { decodeCbor } from './cbor'
 
-export type BleDevice = {
-  address: string;
-  name: string;
-  isConnected: boolean;
-  services: string[];
-  manufacturerData: Record<number, Uint8Array>;
-};
+export type {
+  AdapterInfo,
+  AdapterSelector,
+  AdapterSighting,
+  AdapterState,
+  AddressPolicy,
+  AuthorizationStatus,
+  BatchProgress,
+  BatchReport,
+  BatchWrite,
+  BatchWriteResult,
+  BatteryLevel,
+  BleDevice,
+  BleMetrics,
+  BleState,
+  BondChange,
+  CallbackKind,
+  CallbackPanic,
+  CaptureFormat,
+  CaptureStats,
+  CharacteristicInfo,
+  CharacteristicOptions,
+  CharacteristicProperties,
+  CharacteristicRef,
+  CharacteristicRefInput,
+  CheckOutcome,
+  CheckResult,
+  ClockSync,
+  ConformanceOptions,
+  ConformanceReport,
+  ConnectOptions,
+  ConnectResult,
+  ConnectionCapabilities,
+  ConnectionInterference,
+  ConnectionLimit,
+  ConnectionLimitPolicy,
+  ConnectionPriority,
+  ConnectionState,
+  Correlated,
+  CorrelatedError,
+  DeviceCandidate,
+  DeviceInfo,
+  DeviceReaddressed,
+  DisconnectReason,
+  DiscoveredEvent,
+  DuplicateFilter,
+  FingerprintRule,
+  ForgetFailure,
+  ForgetReport,
+  ForgetStep,
+  Health,
+  InterferenceCause,
+  KeepAliveMethod,
+  KnownDevice,
+  LatencyHistogram,
+  ListenerHandle,
+  MultiResponse,
+  NotificationBucket,
+  OperationMetrics,
+  PairingConsentRequest,
+  PairingEvent,
+  PairingKind,
+  PairingState,
+  PayloadLogging,
+  PnpId,
+  ReadStrategy,
+  ReconnectPolicy,
+  ReconnectProgress,
+  RequiredWrite,
+  RetryPolicy,
+  ScanCapabilities,
+  ScanConsumerId,
+  ScanConsumerOptions,
+  ScanEventBudget,
+  ScanEventStats,
+  ScanNameFilter,
+  ScanThrottleWarning,
+  Service,
+  ServiceInfo,
+  SessionTimeline,
+  SetupAction,
+  SetupNotification,
+  SetupStep,
+  SetupStepResult,
+  StepErrorPolicy,
+  SubscribeMode,
+  SubscriptionReport,
+  TimedNotification,
+  TimedRead,
+  TimelineEntry,
+  TimelineEvent,
+  TimelineOperation,
+  TransferOptions,
+  TransferReport,
+  VendorIdSource,
+  WireFormat,
+  WriteLimits,
+  WriteResponse,
+  WriteType,
+}
 
 /**
   * Scan for BLE devices
-  * @param handler - A function that will be called with an array of devices found during the scan
+  * @param handler - A function that will be called with the devices found so far whenever the list changed during the scan
   * @param timeout - The scan timeout in milliseconds
+  * @param correlationId - Id passed to the handler and included in errors, generated if omitted
+  * @param services - Only report devices advertising any of these service UUIDs, all devices if empty
+  * @param nameFilter - Only report devices whose local name passes the filter, applied in addition to `services`
+  * @param eventBudget - Limits the device events (`onDeviceDiscovered`, scan consumers) of the scan, see `ScanEventBudget`
+  * @returns The correlation id of the scan
 */
-export async function startScan(handler: (devices: BleDevice[]) => void, timeout: Number) {
+export async function startScan(
+  handler: (devices: BleDevice[], correlationId: string) => void,
+  timeout: Number,
+  correlationId?: string,
+  services: string[] = [],
+  nameFilter?: ScanNameFilter,
+  eventBudget?: ScanEventBudget
+): Promise<string> {
   if (!timeout) {
     timeout = 10000;
   }
-  let onDevices = new Channel<BleDevice[]>();
-  onDevices.onmessage = handler;
-  await invoke<BleDevice[]>('plugin:blec|scan', {
+  let onDevices = new Channel<Correlated<BleDevice[]>>();
+  onDevices.onmessage = (event) => handler(event.data, event.correlationId);
+  return await invoke<string>('plugin:blec|scan', {
     timeout,
-    onDevices
+    services,
+    nameFilter: nameFilter ?? null,
+    onDevices,
+    correlationId: correlationId ?? null,
+    eventBudget: eventBudget ?? null
   })
 }
 
+/**
+ * Register a handler for the device lists of scans started with `startScan`, pushed whenever the list changed while the scan runs.
+ * Lets other parts of the app follow a scan without owning it, e.g. a picker in another window.
+ * @returns A function to remove the handler
+ */
+export async function onDevicesDiscovered(handler: (devices: BleDevice[], correlationId: string) => void): Promise<UnlistenFn> {
+  return await listen<Correlated<BleDevice[]>>('blec://devices-discovered', (event) => handler(event.payload.data, event.payload.correlationId))
+}
+
 /**
   * Stop scanning for BLE devices
+  * A running scan ends before its timeout, `onDevices` keeps the devices it received so far.
+  * Does nothing if no scan is running.
 */
 export async function stopScan() {
   console.log('stop scan')
   await invoke('plugin:blec|stop_scan')
 }
 
+/**
+  * Keep scanning until `stopDiscovery` is called, e.g. for a screen listing nearby devices
+  * Found devices and changes of their advertisement are reported to `onDeviceDiscovered`.
+  * Connecting pauses the scan, it resumes once the connect is done.
+  * @param services - Only report devices advertising any of these service UUIDs, all devices if empty
+  * @param nameFilter - Only report devices whose local name passes the filter, applied in addition to `services`
+  * @param correlationId - Id included in errors, generated if omitted
+  * @param eventBudget - Limits the device events (`onDeviceDiscovered`, scan consumers) of the scan, see `ScanEventBudget`
+  * @returns The correlation id of the discovery
+*/
+export async function startDiscovery(
+  services: string[] = [],
+  nameFilter?: ScanNameFilter,
+  correlationId?: string,
+  eventBudget?: ScanEventBudget
+): Promise<string> {
+  return await invoke<string>('plugin:blec|start_discovery', {
+    services,
+    nameFilter: nameFilter ?? null,
+    correlationId: correlationId ?? null,
+    eventBudget: eventBudget ?? null
+  })
+}
+
+/**
+  * Stop the scan started with `startDiscovery`, does nothing if it is not running
+*/
+export async function stopDiscovery() {
+  await invoke('plugin:blec|stop_discovery')
+}
+
+/**
+ * Register a handler for devices found by any scan, called again whenever the advertisement of a device changes
+ * @returns A function to remove the handler
+ */
+export async function onDeviceDiscovered(handler: (device: BleDevice) => void): Promise<UnlistenFn> {
+  return await listen<BleDevice>('blec://device-discovered', (event) => handler(event.payload))
+}
+
+/**
+ * Register a consumer of the shared scan, e.g. a device picker and a presence detection at the same time
+ * All consumers share one scan for the union of their services, it stops when the last consumer is unregistered.
+ * Every consumer filters, debounces and expires devices on its own.
+ * @param options - Devices the consumer is interested in
+ * @param handler - Called with every change of the devices of the consumer
+ * @returns The id to pass to `unregisterScanConsumer`
+ */
+export async function registerScanConsumer(
+  options: ScanConsumerOptions,
+  handler: (event: DiscoveredEvent) => void
+): Promise<number> {
+  let onEvent = new Channel<DiscoveredEvent>()
+  onEvent.onmessage = handler
+  return await invoke<number>('plugin:blec|register_scan_consumer', { options, onEvent })
+}
+
+/**
+ * Remove a consumer registered with `registerScanConsumer`, the shared scan stops with the last one
+ */
+export async function unregisterScanConsumer(id: number) {
+  await invoke('plugin:blec|unregister_scan_consumer', { id })
+}
+
 /**
   * Register a handler to receive updates when the connection state changes
 */
@@ -53,38 +337,64 @@ export async function getScanningUpdates(handler: (scanning: boolean) => void) {
 
 /**
   * Disconnect from the currently connected device
+  * @param flush - If true, all pending operations are executed before disconnecting.
+  * Otherwise only the running operation is awaited and pending operations fail.
+  * @param address - Only disconnect this device, other connections keep running. The device is
+  * disconnected without flushing then.
 */
-export async function disconnect() {
-  await invoke('plugin:blec|disconnect')
+export async function disconnect(flush: boolean = false, address?: string) {
+  await invoke('plugin:blec|disconnect', { flush, address: address ?? null })
 }
 
 /**
   * Connect to a BLE device
+  * While another device is connected, the device gets its own connection like `connectAdditional` and the
+  * connected device is kept. `onDisconnect` and the options apply to each device on its own.
   * @param address - The address of the device to connect to
   * @param onDisconnect - A function that will be called when the device disconnects
+  * @param options - Additional connect options
+  * @param correlationId - Id passed to onDisconnect and included in the result and errors,
+  * generated if omitted
 */
-export async function connect(address: string, onDisconnect: (() => void) | null) {
+export async function connect(
+  address: string,
+  onDisconnect: ((correlationId: string) => void) | null,
+  options?: ConnectOptions,
+  correlationId?: string
+): Promise<Correlated<ConnectResult> | undefined> {
   console.log('connect', address)
-  let disconnectChannel = new Channel()
+  let disconnectChannel = new Channel<string>()
   if (onDisconnect) {
     disconnectChannel.onmessage = onDisconnect
   }
   try {
-    await invoke('plugin:blec|connect', {
+    return await invoke<Correlated<ConnectResult>>('plugin:blec|connect', {
       address: address,
-      onDisconnect: disconnectChannel
+      onDisconnect: disconnectChannel,
+      options: options ?? null,
+      correlationId: correlationId ?? null
     })
   } catch (e) {
     console.error(e)
   }
 }
 
+/**
+ * Create a `CharacteristicRef`
+ * @param characteristic UUID of the characteristic
+ * @param service UUID of the service containing the characteristic
+ * @param address Address of the device
+ */
+export function characteristicRef(characteristic: string, service?: string, address?: string): CharacteristicRef {
+  return { address, service, characteristic }
+}
+
 /**
  * Write a Uint8Array to a BLE characteristic
  * @param characteristic UUID of the characteristic to write to
  * @param data Data to write to the characteristic
  */
-export async function send(characteristic: string, data: Uint8Array, writeType: 'withResponse' | 'withoutResponse' = 'withResponse') {
+export async function send(characteristic: string | CharacteristicRef, data: Uint8Array, writeType: 'withResponse' | 'withoutResponse' = 'withResponse') {
   await invoke('plugin:blec|send', {
     characteristic,
     data,
@@ -92,12 +402,68 @@ export async function send(characteristic: string, data: Uint8Array, writeType:
   })
 }
 
+/**
+ * Write a Uint8Array with response and return the status the device answered with
+ * Application errors of the device are returned as the status instead of failing the write where the platform reports them.
+ * @param characteristic UUID of the characteristic to write to
+ * @param data Data to write to the characteristic
+ * @param correlationId Id included in the result and errors, generated if omitted
+ */
+export async function sendWithResponse(characteristic: string | CharacteristicRef, data: Uint8Array, correlationId?: string): Promise<Correlated<WriteResponse>> {
+  return await invoke<Correlated<WriteResponse>>('plugin:blec|send_with_response', {
+    characteristic,
+    data,
+    correlationId: correlationId ?? null,
+  })
+}
+
+/**
+ * Wait for the first notification of a characteristic that starts with `prefix`.
+ * Other listeners still receive all notifications. The characteristic is subscribed for the wait if it is not yet.
+ * Fails with a timeout error that counts the notifications that did not match, or right away when the device disconnects.
+ * @param characteristic UUID of the characteristic to wait on
+ * @param prefix Leading bytes of the notification to wait for, any notification matches an empty prefix
+ * @param timeoutMs How long to wait for a matching notification
+ * @param correlationId Id included in the result and errors, generated if omitted
+ */
+export async function waitForNotification(characteristic: string | CharacteristicRef, prefix: Uint8Array, timeoutMs: number, correlationId?: string): Promise<Correlated<Uint8Array>> {
+  return await invoke<Correlated<Uint8Array>>('plugin:blec|wait_for_notification', {
+    characteristic,
+    prefix,
+    timeoutMs,
+    correlationId: correlationId ?? null
+  })
+}
+
+/**
+ * Read back the client characteristic configuration of every subscribed characteristic and subscribe the cleared ones again.
+ * On macOS and iOS the subscription state of CoreBluetooth is checked instead, it does not allow reading the descriptor.
+ * Found cleared subscriptions are also reported with `onConnectionInterfered`.
+ * @param address Device to check, also one connected with `connectAdditional`. Defaults to the device connected with `connect`
+ */
+export async function verifySubscriptions(address?: string): Promise<SubscriptionReport> {
+  return await invoke<SubscriptionReport>('plugin:blec|verify_subscriptions', {
+    address: address ?? null
+  })
+}
+
+/**
+ * Check the subscriptions like `verifySubscriptions` whenever no notification arrived for the timeout.
+ * Use it for devices that notify periodically. Every connected device is watched on its own.
+ * @param timeoutMs Silence after which the subscriptions are checked, `null` to disable the check
+ */
+export async function setNotificationSilenceTimeout(timeoutMs: number | null): Promise<void> {
+  await invoke('plugin:blec|set_notification_silence_timeout', {
+    timeoutMs
+  })
+}
+
 /**
  * Write a string to a BLE characteristic
  * @param characteristic UUID of the characteristic to write to
  * @param data Data to write to the characteristic
  */
-export async function sendString(characteristic: string, data: string, writeType: 'withResponse' | 'withoutResponse' = 'withResponse') {
+export async function sendString(characteristic: string | CharacteristicRef, data: string, writeType: 'withResponse' | 'withoutResponse' = 'withResponse') {
   await invoke('plugin:blec|send_string', {
     characteristic,
     data,
@@ -109,18 +475,91 @@ export async function sendString(characteristic: string, data: string, writeType
  * Read bytes from a BLE characteristic
  * @param characteristic UUID of the characteristic to read from
  */
-export async function read(characteristic: string): Promise<Uint8Array> {
+export async function read(characteristic: string | CharacteristicRef): Promise<Uint8Array> {
   let res = await invoke<Uint8Array>('plugin:blec|recv', {
     characteristic
   })
   return res
 }
 
+/**
+ * List the descriptors of a characteristic, known since the services were discovered
+ * @param characteristic UUID of the characteristic
+ * @returns The UUIDs of the descriptors
+ */
+export async function descriptors(characteristic: string | CharacteristicRef): Promise<string[]> {
+  return await invoke<string[]>('plugin:blec|descriptors', {
+    characteristic
+  })
+}
+
+/**
+ * Get the properties of a characteristic, e.g. to only offer the operations it supports
+ * `subscribe` enables notifications or indications depending on which one is supported
+ * @param characteristic UUID of the characteristic
+ */
+export async function characProperties(characteristic: string | CharacteristicRef): Promise<CharacteristicProperties> {
+  return await invoke<CharacteristicProperties>('plugin:blec|charac_properties', {
+    characteristic
+  })
+}
+
+/**
+ * Read bytes from a descriptor of a characteristic
+ * @param characteristic UUID of the characteristic containing the descriptor
+ * @param descriptor UUID of the descriptor, fails with `DescriptorNotAvailable` if the characteristic has no such descriptor
+ */
+export async function readDescriptor(characteristic: string | CharacteristicRef, descriptor: string): Promise<Uint8Array> {
+  return await invoke<Uint8Array>('plugin:blec|read_descriptor', {
+    characteristic,
+    descriptor
+  })
+}
+
+/**
+ * Write bytes to a descriptor of a characteristic
+ * Writing the client characteristic configuration descriptor bypasses `subscribe`, only do this if the device needs it.
+ * @param characteristic UUID of the characteristic containing the descriptor
+ * @param descriptor UUID of the descriptor, fails with `DescriptorNotAvailable` if the characteristic has no such descriptor
+ * @param data Data to write to the descriptor
+ */
+export async function writeDescriptor(characteristic: string | CharacteristicRef, descriptor: string, data: Uint8Array) {
+  await invoke('plugin:blec|write_descriptor', {
+    characteristic,
+    descriptor,
+    data
+  })
+}
+
+
+/**
+ * Set the options used for all operations on a BLE characteristic
+ * @param characteristic UUID of the characteristic
+ * @param options The options to use
+ */
+export async function setCharacteristicOptions(characteristic: string, options: CharacteristicOptions) {
+  await invoke('plugin:blec|set_characteristic_options', {
+    characteristic,
+    options
+  })
+}
+
+/**
+ * Read a BLE characteristic from all connected devices concurrently
+ * @param characteristic UUID of the characteristic to read from, the address of a `CharacteristicRef` is ignored
+ * @returns The result and latency of every read by device address
+ */
+export async function readFromAll(characteristic: string | CharacteristicRef): Promise<Record<string, TimedRead>> {
+  return await invoke<Record<string, TimedRead>>('plugin:blec|recv_from_all', {
+    characteristic
+  })
+}
+
 /**
  * Read a string from a BLE characteristic
  * @param characteristic UUID of the characteristic to read from
  */
-export async function readString(characteristic: string): Promise<string> {
+export async function readString(characteristic: string | CharacteristicRef): Promise<string> {
   let res = await invoke<string>('plugin:blec|recv_string', {
     characteristic
   })
@@ -128,26 +567,40 @@ export async function readString(characteristic: string): Promise<string> {
 }
 
 /**
- * Unsubscribe from a BLE characteristic
+ * Unsubscribe from a BLE characteristic, fails if the characteristic has no handler
+ * Handlers of other characteristics keep receiving notifications.
  * @param characteristic UUID of the characteristic to unsubscribe from
  */
-export async function unsubscribe(characteristic: string) {
+export async function unsubscribe(characteristic: string | CharacteristicRef) {
   await invoke('plugin:blec|unsubscribe', {
     characteristic
   })
 }
 
+/**
+ * Remove a single listener returned by `subscribe` or `subscribeString`
+ * The characteristic stays subscribed while other listeners or sessions use it
+ */
+export async function removeListener(handle: number) {
+  await invoke('plugin:blec|remove_listener', {
+    handle
+  })
+}
+
 /**
  * Subscribe to a BLE characteristic
  * @param characteristic UUID of the characteristic to subscribe to
  * @param handler Callback function that will be called with the data received for every notification
+ * @param wireFormat Encoding of the notifications on the way to the frontend
+ * @returns A handle to remove just this listener with `removeListener`
  */
-export async function subscribe(characteristic: string, handler: (data: Uint8Array) => void) {
-  let onData = new Channel<Uint8Array>()
-  onData.onmessage = handler;
-  await invoke('plugin:blec|subscribe', {
+export async function subscribe(characteristic: string | CharacteristicRef, handler: (data: Uint8Array) => void, wireFormat: WireFormat = 'json'): Promise<number> {
+  let onData = new Channel<Uint8Array | ArrayBuffer>()
+  onData.onmessage = (data) => handler(data instanceof ArrayBuffer ? new Uint8Array(data) : data);
+  return await invoke<number>('plugin:blec|subscribe', {
     characteristic,
-    onData
+    onData,
+    wireFormat
   })
 }
 
@@ -155,12 +608,969 @@ export async function subscribe(characteristic: string, handler: (data: Uint8Arr
  * Subscribe to a BLE characteristic. Converts the received data to a string
  * @param characteristic UUID of the characteristic to subscribe to
  * @param handler Callback function that will be called with the data received for every notification
+ * @returns A handle to remove just this listener with `removeListener`
  */
-export async function subscribeString(characteristic: string, handler: (data: string) => void) {
+export async function subscribeString(characteristic: string | CharacteristicRef, handler: (data: string) => void): Promise<number> {
   let onData = new Channel<string>()
   onData.onmessage = handler;
-  await invoke('plugin:blec|subscribe_string', {
+  return await invoke<number>('plugin:blec|subscribe_string', {
+    characteristic,
+    onData
+  })
+}
+
+/**
+ * Subscribe to a BLE characteristic with the device and arrival time of every notification
+ * The timestamps of all devices are taken from one monotonic clock when the plugin receives the notification.
+ * They lag the measurement by up to one connection interval plus about a millisecond of OS and scheduling delay.
+ * @param characteristic UUID of the characteristic to subscribe to
+ * @param handler Callback function that will be called for every notification
+ * @returns A handle to remove just this listener with `removeListener`
+ */
+export async function subscribeTimestamped(characteristic: string | CharacteristicRef, handler: (notification: TimedNotification) => void): Promise<number> {
+  let onData = new Channel<TimedNotification>()
+  onData.onmessage = handler;
+  return await invoke<number>('plugin:blec|subscribe_timestamped', {
     characteristic,
     onData
   })
 }
+
+/**
+ * Add a latency calibration to the timestamps of a device, kept across reconnects
+ * @param address Address of the device
+ * @param micros Offset in microseconds, negative values move the timestamps back and 0 removes it
+ */
+export async function setTimeOffset(address: string, micros: number) {
+  await invoke('plugin:blec|set_time_offset', {
+    address,
+    micros
+  })
+}
+
+/**
+ * Get the wall clock mapping captured when the device last connected
+ * Convert a timestamp with `unixUs + (timestampUs - monotonicUs)`.
+ * @param address Address of the device
+ * @returns `null` if the device never connected
+ */
+export async function clockSync(address: string): Promise<ClockSync | null> {
+  return await invoke<ClockSync | null>('plugin:blec|clock_sync', {
+    address
+  })
+}
+
+/** Frame of a session, the bindings decode the data into a Uint8Array */
+export type SessionFrame = Omit<SessionFramePayload, 'data'> & { data: Uint8Array };
+
+/**
+ * Open a streaming session over multiple characteristics.
+ * Notifications of all characteristics are merged into one stream that keeps the arrival order.
+ * @param characteristics UUIDs of the characteristics to subscribe to, only on the device connected with `connect`
+ * @param handler Callback function that will be called for every received frame
+ * @param wireFormat Encoding of the frames on the way to the frontend
+ * @returns The session id to pass to `closeSession`
+ */
+export async function openSession(characteristics: (string | CharacteristicRef)[], handler: (frame: SessionFrame) => void, wireFormat: WireFormat = 'json'): Promise<number> {
+  // JSON encodes the data as an array of numbers
+  let onFrame = new Channel<SessionFramePayload | ArrayBuffer>()
+  onFrame.onmessage = (frame) => handler(frame instanceof ArrayBuffer
+    ? decodeCbor(frame) as SessionFrame
+    : { ...frame, data: Uint8Array.from(frame.data) });
+  return await invoke<number>('plugin:blec|open_session', {
+    characteristics,
+    onFrame,
+    wireFormat
+  })
+}
+
+/**
+ * Close a streaming session. Only characteristics not used by other sessions or subscriptions are unsubscribed.
+ * @param id The id returned by `openSession`
+ */
+export async function closeSession(id: number) {
+  await invoke('plugin:blec|close_session', {
+    id
+  })
+}
+
+/**
+ * Write all notifications of the given characteristics directly to a file.
+ * This avoids passing every frame through the webview for long captures.
+ * @param characteristics UUIDs of the characteristics to capture
+ * @param path File to write to. With rotation enabled further files are named `name.1.ext`, `name.2.ext`, ...
+ * @param format `csv` writes `timestamp_us,characteristic,hex` lines, `rawLenPrefixed` writes binary records
+ * @param rotateMb Start a new file after this many megabytes, `null` to disable rotation
+ * @param onProgress Called every few seconds with the current statistics
+ * @returns The capture id to pass to `stopCapture`
+ */
+export async function startCapture(characteristics: (string | CharacteristicRef)[], path: string, format: CaptureFormat = 'csv', rotateMb: number | null = null, onProgress: ((stats: CaptureStats) => void) | null = null): Promise<number> {
+  let progressChannel = new Channel<CaptureStats>()
+  if (onProgress) {
+    progressChannel.onmessage = onProgress
+  }
+  return await invoke<number>('plugin:blec|start_capture', {
+    characteristics,
+    path,
+    format,
+    rotateMb,
+    onProgress: progressChannel
+  })
+}
+
+/**
+ * Stop a capture and flush the file
+ * @param id The id returned by `startCapture`
+ * @returns Final statistics of the capture
+ */
+export async function stopCapture(id: number): Promise<CaptureStats> {
+  return await invoke<CaptureStats>('plugin:blec|stop_capture', {
+    id
+  })
+}
+
+/**
+ * Write a request and collect a response that spans multiple notification frames.
+ * Collection stops at the first frame ending with `endMarker`, after `maxFrames` frames or when the timeout is reached.
+ * @param characteristic UUID of the characteristic to write the request to
+ * @param data Base64 encoded request
+ * @param responseCharacteristic UUID of the characteristic that sends the response frames
+ * @param options.endMarker Base64 encoded bytes that end the last frame of a response
+ * @param options.maxFrames Maximum number of frames to collect
+ * @param options.timeoutMs Time to wait for the complete response
+ * @param options.tee Also deliver the frames to other subscriptions
+ * @param options.totalTimeoutMs Caps writing the request and collecting the response, exceeding it fails instead of returning partial frames
+ * @param options.correlationId Id included in the result and errors, generated if omitted
+ */
+export async function requestMulti(
+  characteristic: string | CharacteristicRef,
+  data: string,
+  responseCharacteristic: string | CharacteristicRef,
+  options: { endMarker?: string, maxFrames?: number, timeoutMs?: number, tee?: boolean, writeType?: 'withResponse' | 'withoutResponse', totalTimeoutMs?: number, correlationId?: string } = {}
+): Promise<Correlated<MultiResponse>> {
+  return await invoke<Correlated<MultiResponse>>('plugin:blec|request_multi', {
+    characteristic,
+    data,
+    writeType: options.writeType ?? 'withResponse',
+    responseCharacteristic,
+    endMarker: options.endMarker ?? null,
+    maxFrames: options.maxFrames ?? null,
+    timeoutMs: options.timeoutMs ?? 5000,
+    tee: options.tee ?? false,
+    totalTimeoutMs: options.totalTimeoutMs ?? null,
+    correlationId: options.correlationId ?? null,
+  })
+}
+
+/**
+ * Get a snapshot of the current plugin state
+ */
+export async function getState(): Promise<BleState> {
+  return await invoke<BleState>('plugin:blec|get_state')
+}
+
+/**
+ * Limit the number of simultaneous connections, a connect can override it with `ConnectOptions.connectionLimit`
+ * @param max Maximum number of connections
+ * @param policy `queue` waits for a free slot when connecting, `failFast` rejects the connect
+ */
+export async function setConnectionLimit(max: number, policy: ConnectionLimitPolicy = 'queue') {
+  await invoke('plugin:blec|set_connection_limit', {
+    max,
+    policy
+  })
+}
+
+/**
+ * Keep idle connections alive by executing a ping whenever no data was exchanged for `intervalMs`
+ * The setting also applies to later connections.
+ * If pings fail repeatedly, the device is disconnected.
+ * @param intervalMs Idle time after which a ping is sent
+ * @param method Operation used as ping, `rssi` is only supported on android
+ */
+export async function setKeepAlive(intervalMs: number, method: KeepAliveMethod) {
+  await invoke('plugin:blec|set_keep_alive', {
+    intervalMs,
+    method
+  })
+}
+
+/**
+ * Disable the keep-alive set with `setKeepAlive`
+ */
+export async function clearKeepAlive() {
+  await invoke('plugin:blec|clear_keep_alive')
+}
+
+/**
+ * Run a self test of the platform backend and report which operations work.
+ * Requires the `conformance` feature of the rust crate, which is meant for debug builds.
+ * @param options.address Device to connect to if none is connected
+ * @param options.scanService Service advertised by the device, used to check scan filtering
+ * @param options.characteristic Characteristic supporting read, write and notify for the round trip checks
+ */
+export async function runConformanceCheck(options: { address?: string, scanService?: string, characteristic?: string } = {}): Promise<ConformanceReport> {
+  return await invoke<ConformanceReport>('plugin:blec|run_conformance_check', {
+    options
+  })
+}
+
+/**
+ * Set how repeated advertisements are reported by following scans
+ * @param filter `platform` sends the devices on every scan interval, `reportAll` additionally
+ * counts every advertisement in `advertisementCount`, `deduplicate` only sends the devices when
+ * something changed
+ */
+export async function setDuplicateFilter(filter: DuplicateFilter) {
+  await invoke('plugin:blec|set_duplicate_filter', {
+    filter
+  })
+}
+
+/**
+ * Register a handler called with the coalescing statistics when a scan with an event budget ended
+ * @returns A function to remove the handler
+ */
+export async function onScanEventStats(handler: (stats: ScanEventStats) => void): Promise<UnlistenFn> {
+  return await listen<ScanEventStats>('blec://scan-event-stats', (event) => handler(event.payload))
+}
+
+/**
+ * Describes how the platform handles duplicate advertisements
+ */
+export async function scanCapabilities(): Promise<ScanCapabilities> {
+  return await invoke<ScanCapabilities>('plugin:blec|scan_capabilities')
+}
+
+/**
+ * Merge devices rotating their address by a stable part of their advertisement.
+ * Scan results contain one entry per fingerprint using the most recent address.
+ * @param rule Bytes `start..end` of the given advertisement field are used as key, `null` disables merging
+ */
+export async function setFingerprintRule(rule: FingerprintRule | null) {
+  await invoke('plugin:blec|set_fingerprint_rule', {
+    rule
+  })
+}
+
+/**
+ * Register a handler called when a fingerprinted device changes its address
+ * @returns A function to remove the handler
+ */
+export async function onDeviceReaddressed(handler: (event: DeviceReaddressed) => void): Promise<UnlistenFn> {
+  return await listen<DeviceReaddressed>('blec://device-readdressed', (event) => handler(event.payload))
+}
+
+/**
+ * Register a handler called with the address of devices removed by the adapter while scanning
+ * @returns A function to remove the handler
+ */
+export async function onDeviceLost(handler: (address: string) => void): Promise<UnlistenFn> {
+  return await listen<string>('blec://device-lost', (event) => handler(event.payload))
+}
+
+/**
+ * Set how long a device must be missing before scans started afterwards report it as lost
+ * Devices showing up again during this time do not trigger any event
+ * @param debounceMs Time in milliseconds, defaults to 3000
+ */
+export async function setRemovalDebounce(debounceMs: number) {
+  await invoke('plugin:blec|set_removal_debounce', {
+    debounceMs
+  })
+}
+
+/** Buckets: `<10, <25, <50, <100, <250, <1000, >=1000` ms */
+
+/**
+ * Get the latency histograms of reads, writes and subscribes since the last reset
+ */
+export async function getMetrics(): Promise<BleMetrics> {
+  return await invoke<BleMetrics>('plugin:blec|get_metrics')
+}
+
+/**
+ * Clear the latency histograms
+ */
+export async function resetMetrics() {
+  await invoke('plugin:blec|reset_metrics')
+}
+
+/**
+ * Periodically emit the metrics as `blec://metrics` event
+ * @param intervalMs Interval between two events, `null` to stop emitting
+ * @param handler Optional handler registered for the event
+ * @returns A function to remove the handler
+ */
+export async function setMetricsInterval(intervalMs: number | null, handler?: (metrics: BleMetrics) => void): Promise<UnlistenFn | undefined> {
+  await invoke('plugin:blec|set_metrics_interval', {
+    intervalMs
+  })
+  if (handler) {
+    return await listen<BleMetrics>('blec://metrics', (event) => handler(event.payload))
+  }
+}
+
+/**
+ * Check if a critical operation is running that should not be interrupted by closing the app
+ */
+export async function hasCriticalOperation(): Promise<boolean> {
+  return await invoke<boolean>('plugin:blec|has_critical_operation')
+}
+
+/**
+ * Mark an operation as critical until `endCriticalOperation` is called
+ * @param name Name of the operation, reported in the `blec://exit-blocked` event
+ * @returns The id to pass to `endCriticalOperation`
+ */
+export async function beginCriticalOperation(name: string): Promise<number> {
+  return await invoke<number>('plugin:blec|begin_critical_operation', {
+    name
+  })
+}
+
+/**
+ * End a critical operation started with `beginCriticalOperation`
+ */
+export async function endCriticalOperation(id: number) {
+  await invoke('plugin:blec|end_critical_operation', {
+    id
+  })
+}
+
+/**
+ * Delay closing the app while a critical operation runs
+ * @param timeoutMs Maximum delay, `null` disables the guard
+ * @param onBlocked Optional handler called with the running operations when an exit is delayed
+ * @returns A function to remove the handler
+ */
+export async function setExitGuard(timeoutMs: number | null, onBlocked?: (operations: string[]) => void): Promise<UnlistenFn | undefined> {
+  await invoke('plugin:blec|set_exit_guard', {
+    timeoutMs
+  })
+  if (onBlocked) {
+    return await listen<string[]>('blec://exit-blocked', (event) => onBlocked(event.payload))
+  }
+}
+
+/**
+ * Read the Device Information service of the connected device
+ * Characteristics the device does not provide are `null`
+ */
+export async function deviceInfo(): Promise<DeviceInfo> {
+  return await invoke<DeviceInfo>('plugin:blec|device_info')
+}
+
+/**
+ * Read the PnP ID characteristic (0x2A50) of the connected device
+ */
+export async function readPnpId(): Promise<PnpId> {
+  return await invoke<PnpId>('plugin:blec|read_pnp_id')
+}
+
+/**
+ * Get the health of the plugin's event processing
+ */
+export async function health(): Promise<Health> {
+  return await invoke<Health>('plugin:blec|health')
+}
+
+/**
+ * Set how long the event stream may be silent while connected or scanning before the adapter is checked
+ * @param intervalMs Interval in milliseconds, defaults to 30000, `null` disables the check
+ */
+export async function setHeartbeatInterval(intervalMs: number | null) {
+  await invoke('plugin:blec|set_heartbeat_interval', {
+    intervalMs
+  })
+}
+
+/**
+ * Register a handler called with the milliseconds since the last event when the event loop stalled
+ * @returns A function to remove the handler
+ */
+export async function onEventLoopStalled(handler: (idleMs: number) => void): Promise<UnlistenFn> {
+  return await listen<number>('blec://event-loop-stalled', (event) => handler(event.payload))
+}
+
+/** A setup step, `onError: 'abort'` (default) disconnects and fails the connect if the step fails */
+
+/**
+ * Register a handler for the pairing progress of `ConnectOptions.requireBonded`
+ * @returns A function to remove the handler
+ */
+export async function onPairing(handler: (event: PairingEvent) => void): Promise<UnlistenFn> {
+  return await listen<PairingEvent>('blec://pairing', (event) => handler(event.payload))
+}
+
+/** Largest payload of a single write per write type, `null` if it is not known */
+
+/**
+ * Register a handler for notifications of characteristics subscribed by a setup script
+ * @returns A function to remove the handler
+ */
+export async function onSetupNotification(handler: (notification: SetupNotification) => void): Promise<UnlistenFn> {
+  return await listen<SetupNotification>('blec://setup-notification', (event) => handler(event.payload))
+}
+
+/**
+ * Get the current ATT MTU of the connected device, 23 until a larger MTU was negotiated.
+ * Fails on platforms that do not report the MTU, only android does.
+ */
+export async function getMtu(): Promise<number> {
+  return await invoke<number>('plugin:blec|get_mtu')
+}
+
+/**
+ * Request an MTU for the connected device, only supported on android
+ * @param mtu The requested MTU, the device can grant a lower one
+ * @returns The MTU actually granted
+ */
+export async function requestMtu(mtu: number): Promise<number> {
+  return await invoke<number>('plugin:blec|request_mtu', { mtu })
+}
+
+/**
+ * Get the largest payload of a single write, the MTU minus the ATT header, `null` if the MTU is not known
+ * @param writeType Limit of this write type, Apple platforms limit writes per write type
+ */
+export async function maxWriteLen(writeType?: 'withResponse' | 'withoutResponse'): Promise<number | null> {
+  return await invoke<number | null>('plugin:blec|max_write_len', { writeType: writeType ?? null })
+}
+
+/**
+ * Get which connection features the platform supports
+ */
+export async function connectionCapabilities(): Promise<ConnectionCapabilities> {
+  return await invoke<ConnectionCapabilities>('plugin:blec|connection_capabilities')
+}
+
+/**
+ * Get the services and characteristics of the connected device, empty if no device is connected
+ */
+export async function connectedServices(): Promise<Service[]> {
+  return await invoke<Service[]>('plugin:blec|connected_services')
+}
+
+/** Properties of a characteristic decoded from the GATT properties bitfield */
+
+/**
+ * Get every service, characteristic and descriptor of the connected device
+ * Services are discovered first if that did not happen yet
+ */
+export async function getServices(): Promise<ServiceInfo[]> {
+  return await invoke<ServiceInfo[]>('plugin:blec|get_services')
+}
+
+/**
+ * Set how reads and writes failing with transient errors (Insufficient Resources, congestion) are retried
+ * Defaults to 3 attempts with a 50ms backoff
+ */
+export async function setRetryPolicy(policy: RetryPolicy) {
+  await invoke('plugin:blec|set_retry_policy', {
+    policy
+  })
+}
+
+/**
+ * Set how addresses passed to the plugin are checked, defaults to `coerce`
+ * Addresses that are rejected fail with an invalid address error instead of an unknown device
+ */
+export async function setAddressPolicy(policy: AddressPolicy) {
+  await invoke('plugin:blec|set_address_policy', {
+    policy
+  })
+}
+
+/**
+ * Check an address without touching the adapter, fast enough to validate user input on every keystroke
+ * @param address - MAC address, or UUID on apple platforms
+ */
+export async function isKnownDevice(address: string): Promise<KnownDevice> {
+  return await invoke<KnownDevice>('plugin:blec|is_known_device', {
+    address
+  })
+}
+
+/**
+ * Disconnect the device and drop everything known about it: the OS bond where the platform allows it (android and windows),
+ * the cached device, bond state, last sighting, fingerprint, session timelines and previously connected record.
+ * A failed step does not stop the others, it is listed in `failures`.
+ * @param address - MAC address, or UUID on apple platforms
+ */
+export async function forgetDevice(address: string): Promise<ForgetReport> {
+  return await invoke<ForgetReport>('plugin:blec|forget_device', {
+    address
+  })
+}
+
+/**
+ * List the devices cached for an address, the most recently seen first.
+ * An address matches several devices when several adapters saw it or another device took it over after an address rotation.
+ * Connects to an address matching devices that are known to be different fail until `peripheralId` picks one.
+ * @param address - MAC address, or UUID on apple platforms
+ */
+export async function deviceCandidates(address: string): Promise<DeviceCandidate[]> {
+  return await invoke<DeviceCandidate[]>('plugin:blec|device_candidates', {
+    address
+  })
+}
+
+/**
+ * Register a handler for devices forgotten with `forgetDevice`
+ * @returns A function to remove the handler
+ */
+export async function onDeviceForgotten(handler: (report: ForgetReport) => void): Promise<UnlistenFn> {
+  return await listen<ForgetReport>('blec://device-forgotten', (event) => handler(event.payload))
+}
+
+/**
+ * Register a handler called after every successful connect, also for additional connections and reconnects
+ * @returns A function to remove the handler
+ */
+export async function onConnected(handler: (address: string) => void): Promise<UnlistenFn> {
+  return await listen<{ address: string }>('blec://connected', (event) => handler(event.payload.address))
+}
+
+/**
+ * Register a handler warned when a scan was started close to the scan start limit of the OS.
+ * Android allows an app 5 scan starts within 30 seconds, further scans silently receive nothing.
+ * Starting a scan over the limit fails with a `ScanThrottled` error instead.
+ * Scans the plugin restarts itself, e.g. for scan consumers, use at most 2 of the starts.
+ * @returns A function to remove the handler
+ */
+export async function onScanThrottleWarning(handler: (warning: ScanThrottleWarning) => void): Promise<UnlistenFn> {
+  return await listen<ScanThrottleWarning>('blec://scan-throttle-warning', (event) => handler(event.payload))
+}
+
+/**
+ * Register a handler called when a running scan received no advertisement and is likely throttled by the OS, only on android
+ * @returns A function to remove the handler
+ */
+export async function onScanThrottled(handler: (retryAfterMs: number) => void): Promise<UnlistenFn> {
+  return await listen<{ retryAfterMs: number }>('blec://scan-throttled', (event) => handler(event.payload.retryAfterMs))
+}
+
+/**
+ * Register a handler called when subscriptions of the connected device were found cleared and restored.
+ * This typically happens on linux when another application opens the same device and BlueZ discovers its services again.
+ * @returns A function to remove the handler
+ */
+export async function onConnectionInterfered(handler: (interference: ConnectionInterference) => void): Promise<UnlistenFn> {
+  return await listen<ConnectionInterference>('blec://connection-interfered', (event) => handler(event.payload))
+}
+
+/**
+ * Register a handler called whenever a device disconnects, also after `disconnect`
+ * @returns A function to remove the handler
+ */
+export async function onDisconnected(handler: (address: string, reason: DisconnectReason) => void): Promise<UnlistenFn> {
+  return await listen<{ address: string, reason: DisconnectReason }>('blec://disconnected', (event) => handler(event.payload.address, event.payload.reason))
+}
+
+/**
+ * Write data to a characteristic in chunks, sent one after another in a single call.
+ * Fails with an error containing the offset of the first chunk that could not be written.
+ * @param characteristic UUID of the characteristic to write to
+ * @param data Data to write
+ * @param chunkSize Bytes per write, defaults to the payload of a single write with the current MTU
+ * @param writeType With `withResponse` every chunk waits for the acknowledgement of the device
+ * @param correlationId Id included in errors, generated if omitted
+ * @returns The correlation id of the write
+ */
+export async function sendChunked(characteristic: string | CharacteristicRef, data: Uint8Array, chunkSize?: number, writeType: 'withResponse' | 'withoutResponse' = 'withResponse', correlationId?: string): Promise<string> {
+  return await invoke<string>('plugin:blec|send_chunked', {
+    characteristic,
+    data,
+    chunkSize: chunkSize ?? null,
+    writeType,
+    correlationId: correlationId ?? null,
+  })
+}
+
+/**
+ * Write data to a characteristic in chunks, resuming from the last acknowledged chunk if the link is lost.
+ * If the transfer can not be resumed automatically it fails with an error containing the transfer id,
+ * the transfer can then be continued with `resumeTransfer` once the device is connected again.
+ * @param characteristic UUID of the characteristic to write to
+ * @param data Data to transfer
+ * @param options Transfer options
+ * @param correlationId Id included in the result and errors, generated if omitted
+ */
+export async function transfer(characteristic: string | CharacteristicRef, data: Uint8Array, options?: TransferOptions, correlationId?: string): Promise<Correlated<TransferReport>> {
+  return await invoke<Correlated<TransferReport>>('plugin:blec|transfer', {
+    characteristic,
+    data,
+    options: options ?? null,
+    correlationId: correlationId ?? null
+  })
+}
+
+/**
+ * Continue a paused transfer from the last acknowledged chunk
+ * @param id Id of the paused transfer
+ * @param correlationId Id included in the result and errors, generated if omitted
+ */
+export async function resumeTransfer(id: number, correlationId?: string): Promise<Correlated<TransferReport>> {
+  return await invoke<Correlated<TransferReport>>('plugin:blec|resume_transfer', {
+    id,
+    correlationId: correlationId ?? null
+  })
+}
+
+/**
+ * Drop the state of a paused transfer, returns false if no transfer with the id is paused
+ */
+export async function cancelTransfer(id: number): Promise<boolean> {
+  return await invoke<boolean>('plugin:blec|cancel_transfer', {
+    id
+  })
+}
+
+/**
+ * Forget which devices were connected, also in the store set on the rust side
+ */
+export async function clearPreviouslyConnected() {
+  await invoke('plugin:blec|clear_previously_connected')
+}
+
+/**
+ * Register a handler for every change of the connection state, also of additional connections
+ * @returns A function to remove the handler
+ */
+export async function onConnectionState(handler: (state: ConnectionState, address: string) => void): Promise<UnlistenFn> {
+  return await listen<{ address: string, state: ConnectionState }>('blec://connection-state', (event) => handler(event.payload.state, event.payload.address))
+}
+
+/**
+ * Register a handler for MTU changes of the connected device, e.g. renegotiations started by the device.
+ * Only reported on android.
+ * @returns A function to remove the handler
+ */
+export async function onMtuChanged(handler: (mtu: number) => void): Promise<UnlistenFn> {
+  return await listen<number>('blec://mtu-changed', (event) => handler(event.payload))
+}
+
+/**
+ * Scan on every adapter of the system instead of only the default one, applies to scans started afterwards.
+ * Devices seen by several adapters are merged, `adapters` of a device lists the adapters that saw it with their RSSI
+ * and `adapterId` the one with the strongest RSSI, which is used to connect unless `ConnectOptions.adapter` is set.
+ * @returns The ids of the scanned adapters, empty if disabled
+ */
+export async function setMultiAdapterScan(enabled: boolean): Promise<string[]> {
+  return await invoke<string[]>('plugin:blec|set_multi_adapter_scan', {
+    enabled
+  })
+}
+
+/**
+ * Scan until the device advertises again, e.g. after a reboot.
+ * A device with a fingerprint set on the rust side is also found under a new address.
+ * Fails if the device did not advertise within the timeout or the wait was cancelled with `cancelWaitForDevice`.
+ * @param address - The address of the device
+ * @param timeoutMs - How long to scan for the device
+ * @param correlationId - Id included in the result and errors, generated if omitted
+ * @returns The advertisement of the device
+ */
+export async function waitForDevice(address: string, timeoutMs: number, correlationId?: string): Promise<Correlated<BleDevice>> {
+  return await invoke<Correlated<BleDevice>>('plugin:blec|wait_for_device', {
+    address,
+    timeoutMs,
+    correlationId: correlationId ?? null
+  })
+}
+
+/**
+ * Wait until the device advertises again and connect to it, including the setup of the options.
+ * `timeoutMs` caps the whole call. `cancelWaitForDevice` also aborts a connect in progress.
+ * @param address - The address of the device
+ * @param timeoutMs - How long to wait for the device and connect
+ * @param onDisconnect - Called with the address when the device disconnects after the reconnect
+ * @param options - Additional connect options
+ * @param correlationId - Id included in the result and errors, generated if omitted
+ */
+export async function reconnectWhenAvailable(
+  address: string,
+  timeoutMs: number,
+  onDisconnect?: (address: string) => void,
+  options?: ConnectOptions,
+  correlationId?: string
+): Promise<Correlated<ConnectResult>> {
+  let disconnectChannel = new Channel<string>()
+  if (onDisconnect) {
+    disconnectChannel.onmessage = onDisconnect
+  }
+  return await invoke<Correlated<ConnectResult>>('plugin:blec|reconnect_when_available', {
+    address,
+    timeoutMs,
+    onDisconnect: disconnectChannel,
+    options: options ?? null,
+    correlationId: correlationId ?? null
+  })
+}
+
+/**
+ * Cancel `waitForDevice` or `reconnectWhenAvailable` for the address
+ * @returns false if no wait for the address is running
+ */
+export async function cancelWaitForDevice(address: string): Promise<boolean> {
+  return await invoke<boolean>('plugin:blec|cancel_wait_for_device', {
+    address
+  })
+}
+
+/**
+ * Register a handler for the stages of every `reconnectWhenAvailable`
+ * @returns A function to remove the handler
+ */
+export async function onReconnectProgress(handler: (progress: ReconnectProgress) => void): Promise<UnlistenFn> {
+  return await listen<ReconnectProgress>('blec://reconnect-progress', (event) => handler(event.payload))
+}
+
+/**
+ * Get the timeline of the latest connect session with the device: connect stages with their durations,
+ * MTU and priority changes, every read, write and subscribe with latency and result, notifications per second
+ * and the disconnect reason. Payloads are recorded as allowed by `setPayloadLogging`, by default only their length.
+ * @param address - The address of the device
+ * @param path - If set, the timeline is also written to this file as JSON. The path is relative to the app data
+ * directory, absolute paths and `..` are rejected.
+ */
+export async function exportSessionTimeline(address: string, path?: string): Promise<SessionTimeline> {
+  return await invoke<SessionTimeline>('plugin:blec|export_session_timeline', {
+    address,
+    path: path ?? null
+  })
+}
+
+/**
+ * Record the payload bytes of reads and writes in the session timelines, meant for debugging
+ * Shorthand for `setPayloadLogging({ mode: 'full' })` if enabled and `{ mode: 'lengths' }` otherwise.
+ */
+export async function setTimelinePayloads(enabled: boolean) {
+  await invoke('plugin:blec|set_timeline_payloads', {
+    enabled
+  })
+}
+
+/**
+ * Initialize the plugin if it was deferred with `init_with` on the rust side.
+ * On macOS this shows the bluetooth permission prompt, call it when the app is ready for it.
+ * Does nothing if the plugin is already initialized.
+ */
+export async function ensureInitialized() {
+  await invoke('plugin:blec|ensure_initialized')
+}
+
+/**
+ * Get whether the app may use bluetooth, using the CoreBluetooth authorization on macOS and iOS.
+ * Other platforms always report `allowed`. Also works before the plugin is initialized. Scans, connects, pairing and
+ * data operations fail with `PermissionDenied` if access was denied.
+ */
+export async function authorizationStatus(): Promise<AuthorizationStatus> {
+  return await invoke<AuthorizationStatus>('plugin:blec|authorization_status')
+}
+
+/**
+ * Register a handler for changes of the bluetooth authorization, e.g. when the user allows bluetooth in the settings
+ * @returns A function to remove the handler
+ */
+export async function onAuthorizationChanged(handler: (status: AuthorizationStatus) => void): Promise<UnlistenFn> {
+  return await listen<AuthorizationStatus>('blec://authorization-changed', (event) => handler(event.payload))
+}
+
+/**
+ * Write multiple characteristics in order, e.g. to apply a set of settings.
+ * Failed writes are reported in the results, the call itself does not fail because of them.
+ * @param items - The writes in the order they are executed
+ * @param stopOnError - Stop after the first failed write and skip the rest, otherwise all writes are attempted
+ * @param onProgress - Called after every write
+ * @param correlationId - Id passed to onProgress and included in the result and errors, generated if omitted
+ */
+export async function writeBatch(
+  items: BatchWrite[],
+  stopOnError: boolean,
+  onProgress?: (progress: BatchProgress, correlationId: string) => void,
+  correlationId?: string
+): Promise<Correlated<BatchReport>> {
+  const progressChannel = new Channel<Correlated<BatchProgress>>()
+  if (onProgress) {
+    progressChannel.onmessage = (event) => onProgress(event.data, event.correlationId)
+  }
+  return await invoke<Correlated<BatchReport>>('plugin:blec|write_batch', {
+    items,
+    stopOnError,
+    onProgress: progressChannel,
+    correlationId: correlationId ?? null
+  })
+}
+
+/**
+ * Connect to another device while keeping the current connections.
+ * Operations reach the device through the address of a `CharacteristicRef`, operations
+ * without an address fail once several devices are connected.
+ * The device is connected with the default options, use `connect` for options or a setup script.
+ * @param address - The address of the device to connect to
+ * @param correlationId - Id included in the result and errors, generated if omitted
+ * @returns The services of the device
+ */
+export async function connectAdditional(address: string, correlationId?: string): Promise<Correlated<Service[]>> {
+  return await invoke<Correlated<Service[]>>('plugin:blec|connect_additional', {
+    address,
+    correlationId: correlationId ?? null
+  })
+}
+
+/**
+ * Get the addresses of all connected devices, the device of `connect` first
+ */
+export async function connectedAddresses(): Promise<string[]> {
+  return await invoke<string[]>('plugin:blec|connected_addresses')
+}
+
+/**
+ * Register a handler for panics of callbacks passed to the plugin, e.g. a notification callback in the rust backend
+ * @returns A function to remove the handler
+ */
+export async function onCallbackPanic(handler: (panic: CallbackPanic) => void): Promise<UnlistenFn> {
+  return await listen<CallbackPanic>('blec://error', (event) => handler(event.payload))
+}
+
+/**
+ * Register a handler for pairings the OS asks to confirm, while connecting with `requireBonded`
+ * or on Windows after an operation failed because the device is not bonded
+ * @returns A function to remove the handler
+ */
+export async function onPairingConsentRequired(handler: (request: PairingConsentRequest) => void): Promise<UnlistenFn> {
+  return await listen<PairingConsentRequest>('blec://pairing-consent-required', (event) => handler(event.payload))
+}
+
+/**
+ * Pair with a device, nothing happens if it is already bonded.
+ * Call this when an operation fails with `PairingRequired`. The device does not have to be connected, on android it must have been found by a scan.
+ * Fails with `UnsupportedOnPlatform` on macOS, iOS and linux, where pairing is left to the OS.
+ * @param address Address of the device
+ * @param correlationId Id included in errors, generated if omitted
+ * @returns The correlation id of the pairing
+ */
+export async function pair(address: string, correlationId?: string): Promise<string> {
+  return await invoke<string>('plugin:blec|pair', {
+    address,
+    correlationId: correlationId ?? null
+  })
+}
+
+/**
+ * Check whether a device is bonded, only supported on android and Windows
+ * @param address Address of the device
+ */
+export async function isPaired(address: string): Promise<boolean> {
+  return await invoke<boolean>('plugin:blec|is_paired', { address })
+}
+
+/**
+ * List the bluetooth adapters of the system, works before the handler is initialized
+ */
+export async function listAdapters(): Promise<AdapterInfo[]> {
+  return await invoke<AdapterInfo[]>('plugin:blec|list_adapters')
+}
+
+/**
+ * Get the adapter used for scans and connects outside of multi adapter scans
+ */
+export async function selectedAdapter(): Promise<AdapterInfo> {
+  return await invoke<AdapterInfo>('plugin:blec|selected_adapter')
+}
+
+/**
+ * Switch to another adapter, fails while a device is connected
+ * A running scan is stopped and the devices found so far are dropped
+ * @param adapter Index of the adapter or a name matching its id or the first word of it, e.g. `hci1`
+ */
+export async function selectAdapter(adapter: number | string): Promise<AdapterInfo> {
+  return await invoke<AdapterInfo>('plugin:blec|select_adapter', { adapter })
+}
+
+/**
+ * Answer the pending pairing consent request of a device, only supported on Windows
+ * @param address Address of the device in the request
+ * @param accept Rejecting fails the pairing
+ * @param pin The PIN shown by the device, required for `providePin` requests
+ */
+export async function acceptPairingConsent(address: string, accept: boolean, pin?: string) {
+  await invoke('plugin:blec|accept_pairing_consent', { address, accept, pin })
+}
+
+/**
+ * Accept pairings that only need a confirmation without sending a consent request, only supported on Windows
+ * @param enabled Requests asking for a PIN are still sent
+ */
+export async function setPairingAutoAccept(enabled: boolean) {
+  await invoke('plugin:blec|set_pairing_auto_accept', { enabled })
+}
+
+/**
+ * Set how payload bytes appear in log output and session timelines
+ * Captures always contain the full payloads.
+ */
+export async function setPayloadLogging(policy: PayloadLogging) {
+  await invoke('plugin:blec|set_payload_logging', { policy })
+}
+
+/**
+ * Register a handler for bonds that were created or removed, also in the system settings, only reported on android and linux.
+ * Operations on a device whose bond was removed fail with `PairingRequired`.
+ * @returns A function to remove the handler
+ */
+export async function onBondChanged(handler: (change: BondChange) => void): Promise<UnlistenFn> {
+  return await listen<BondChange>('blec://bond-changed', (event) => handler(event.payload))
+}
+
+/**
+ * Get the state of the adapter
+ */
+export async function adapterState(): Promise<AdapterState> {
+  return await invoke<AdapterState>('plugin:blec|adapter_state')
+}
+
+/**
+ * Register a handler for changes of the adapter state, e.g. when bluetooth is switched off in the system settings.
+ * Once powered off, scans are stopped, connections are closed with the reason `adapterOff` and every operation fails with
+ * `AdapterPoweredOff` until the adapter is powered on again.
+ * @returns A function to remove the handler
+ */
+export async function onAdapterState(handler: (state: AdapterState) => void): Promise<UnlistenFn> {
+  return await listen<AdapterState>('blec://adapter-state', (event) => handler(event.payload))
+}
+
+/**
+ * Read the Battery Level characteristic (0x2A19) of the connected device
+ * @returns The battery level in percent
+ */
+export async function batteryLevel(): Promise<number> {
+  return await invoke<number>('plugin:blec|battery_level')
+}
+
+/**
+ * Keep track of the battery level of whichever device is connected, also across reconnects
+ * The monitor subscribes to the Battery Level characteristic on every connect and reads it periodically on devices that do not notify it.
+ * Changes are reported to `onBattery` and the latest level is part of `getState`.
+ * @param pollFallbackIntervalMs Interval of the reads on devices that do not notify the level
+ */
+export async function startBatteryMonitor(pollFallbackIntervalMs: number = 60_000) {
+  await invoke('plugin:blec|start_battery_monitor', { pollFallbackIntervalMs })
+}
+
+/**
+ * Stop the battery monitor, remove its subscription and forget the latest level
+ */
+export async function stopBatteryMonitor() {
+  await invoke('plugin:blec|stop_battery_monitor')
+}
+
+/**
+ * Register a handler for battery levels reported by the battery monitor, see `startBatteryMonitor`
+ * @returns A function to remove the handler
+ */
+export async function onBattery(handler: (level: BatteryLevel) => void): Promise<UnlistenFn> {
+  return await listen<BatteryLevel>('blec://battery', (event) => handler(event.payload))
+}
@@ -12,11 +12,13 @@ use serde::Deserialize;
 use std::{
     collections::{BTreeSet, HashMap},
     pin::Pin,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Instant,
     vec,
 };
 use tauri::{
     ipc::{Channel, InvokeResponseBody},
-    plugin::PluginHandle,
+    plugin::{mobile::PluginInvokeError, PluginHandle},
     AppHandle, Wry,
 };
 use tokio::sync::RwLock;
@@ -24,6 +26,10 @@ use tokio_stream::wrappers::ReceiverStream;
 use tracing::info;
 use uuid::Uuid;
 
+use crate::error::{GattStatus, PlatformRejection};
+use crate::models::AdvInterval;
+use crate::payload_log::Payload;
+
 type Result<T> = std::result::Result<T, btleplug::Error>;
 
 static HANDLE: OnceCell<PluginHandle<Wry>> = OnceCell::new();
@@ -32,6 +38,22 @@ fn get_handle() -> &'static PluginHandle<Wry> {
     HANDLE.get().expect("plugin handle not initialized")
 }
 
+/// Keeps the GATT status the kotlin side rejected with as the code of the error
+fn plugin_error(e: PluginInvokeError) -> btleplug::Error {
+    if let PluginInvokeError::InvokeRejected(response) = &e {
+        if let Some(rejection) = response.code.as_deref().and_then(PlatformRejection::from_code) {
+            return btleplug::Error::Other(Box::new(rejection));
+        }
+        if let Some(status) = response.code.as_deref().and_then(|code| code.parse().ok()) {
+            return btleplug::Error::Other(Box::new(GattStatus {
+                status,
+                message: e.to_string(),
+            }));
+        }
+    }
+    btleplug::Error::RuntimeError(e.to_string())
+}
+
 pub fn init<C: serde::de::DeserializeOwned>(
     _app: &AppHandle<Wry>,
     api: tauri::plugin::PluginApi<Wry, C>,
@@ -45,6 +67,19 @@ pub fn init<C: serde::de::DeserializeOwned>(
 pub struct Adapter;
 static DEVICES: Lazy<RwLock<HashMap<PeripheralId, Peripheral>>> =
     Lazy::new(|| RwLock::new(HashMap::new()));
+/// Advertisements received by all scans
+static SCAN_CALLBACKS: AtomicU64 = AtomicU64::new(0);
+
+/// Number of advertisements received by all scans, a throttled scan receives none
+pub(crate) fn scan_callbacks() -> u64 {
+    SCAN_CALLBACKS.load(Ordering::Relaxed)
+}
+
+/// Drops the cached handle of the device, it is only listed again once it advertises or is
+/// looked up by address
+pub(crate) async fn forget_peripheral(id: &PeripheralId) {
+    DEVICES.write().await.remove(id);
+}
 
 #[derive(serde::Deserialize)]
 struct PeripheralResult {
@@ -59,16 +94,54 @@ fn on_device_callback(response: InvokeResponseBody) -> std::result::Result<(), t
             return Err(tauri::Error::from(e));
         }
     };
+    SCAN_CALLBACKS.fetch_add(1, Ordering::Relaxed);
+    let mut device = device;
     let mut devices = DEVICES.blocking_write();
     tracing::info!("device: {device:?}");
+    // every callback is one received advertisement
     if let Some(enty) = devices.get_mut(&device.id) {
+        device.advertisement_count = enty.advertisement_count + 1;
+        device.adv_interval = std::mem::take(&mut enty.adv_interval);
+        device.adv_interval.record(Instant::now());
         *enty = device;
     } else {
+        device.advertisement_count = 1;
+        device.adv_interval.record(Instant::now());
         devices.insert(device.id.clone(), device);
     }
     Ok(())
 }
 
+/// Stream of the `(address, bonded)` changes of every device, including bonds created or
+/// removed in the system settings
+pub(crate) fn bond_changes() -> Result<Pin<Box<dyn Stream<Item = (String, bool)> + Send>>> {
+    #[derive(serde::Deserialize)]
+    struct BondChange {
+        address: String,
+        bonded: bool,
+    }
+    let (tx, rx) = tokio::sync::mpsc::channel::<(String, bool)>(16);
+    let stream = ReceiverStream::new(rx);
+    let channel: Channel<BondChange> = Channel::new(move |response| {
+        match response.deserialize::<BondChange>() {
+            Ok(change) => {
+                if tx.blocking_send((change.address, change.bonded)).is_err() {
+                    tracing::debug!("dropping bond change, the receiver is closed");
+                }
+            }
+            Err(e) => {
+                tracing::error!("failed to deserialize bond change: {:?}", e);
+                return Err(tauri::Error::from(e));
+            }
+        };
+        Ok(())
+    });
+    get_handle()
+        .run_mobile_plugin::<()>("bond_changes", channel)
+        .map_err(plugin_error)?;
+    Ok(Box::pin(stream))
+}
+
 #[allow(dependency_on_unit_never_type_fallback)]
 #[async_trait]
 impl btleplug::api::Central for Adapter {
@@ -87,7 +160,7 @@ impl btleplug::api::Central for Adapter {
         });
         get_handle()
             .run_mobile_plugin("events", channel)
-            .map_err(|e| btleplug::Error::RuntimeError(e.to_string()))?;
+            .map_err(plugin_error)?;
         Ok(Box::pin(stream))
     }
 
@@ -108,14 +181,14 @@ impl btleplug::api::Central for Adapter {
                     on_device,
                 },
             )
-            .map_err(|e| btleplug::Error::RuntimeError(e.to_string()))?;
+            .map_err(plugin_error)?;
         Ok(())
     }
 
     async fn stop_scan(&self) -> Result<()> {
         get_handle()
             .run_mobile_plugin("stop_scan", serde_json::Value::Null)
-            .map_err(|e| btleplug::Error::RuntimeError(e.to_string()))?;
+            .map_err(plugin_error)?;
         Ok(())
     }
 
@@ -132,8 +205,18 @@ impl btleplug::api::Central for Adapter {
             .ok_or(btleplug::Error::DeviceNotFound)
     }
 
-    async fn add_peripheral(&self, _address: &PeripheralId) -> Result<Self::Peripheral> {
-        Err(btleplug::Error::NotSupported("add_peripheral".to_string()))
+    async fn add_peripheral(&self, address: &PeripheralId) -> Result<Self::Peripheral> {
+        #[derive(serde::Serialize)]
+        struct LookupParams<'a> {
+            address: &'a PeripheralId,
+        }
+        // the device is looked up by address, android creates a handle even if it never
+        // advertised since boot
+        let PeripheralResult { result } = get_handle()
+            .run_mobile_plugin("get_remote_device", LookupParams { address })
+            .map_err(plugin_error)?;
+        DEVICES.write().await.insert(result.id.clone(), result.clone());
+        Ok(result)
     }
 
     async fn adapter_info(&self) -> Result<String> {
@@ -174,6 +257,222 @@ pub struct Peripheral {
     manufacturer_data: HashMap<u16, Vec<u8>>,
     #[serde(default)]
     services: Vec<Uuid>,
+    #[serde(default)]
+    service_data: HashMap<Uuid, Vec<u8>>,
+    #[serde(default)]
+    advertise_flags: Option<u8>,
+    #[serde(default)]
+    appearance: Option<u16>,
+    #[serde(skip)]
+    advertisement_count: u64,
+    #[serde(skip)]
+    adv_interval: AdvInterval,
+}
+
+impl Peripheral {
+    /// Flags AD type of the last advertisement, if the device sent one
+    pub(crate) fn advertise_flags(&self) -> Option<u8> {
+        self.advertise_flags
+    }
+
+    /// Appearance AD type of the last advertisement, if the device sent one
+    pub(crate) fn appearance(&self) -> Option<u16> {
+        self.appearance
+    }
+
+    /// Number of advertisements received since the scan was started
+    pub(crate) fn advertisement_count(&self) -> u64 {
+        self.advertisement_count
+    }
+
+    /// Median time between the recent advertisements
+    pub(crate) fn adv_interval_ms(&self) -> Option<u32> {
+        self.adv_interval.median_ms()
+    }
+
+    /// Requests the current RSSI of the connected device
+    pub(crate) async fn read_rssi(&self) -> Result<i16> {
+        #[derive(serde::Deserialize)]
+        struct RssiResult {
+            rssi: i16,
+        }
+        let res: RssiResult = get_handle()
+            .run_mobile_plugin(
+                "read_rssi",
+                ConnectParams {
+                    address: self.address,
+                },
+            )
+            .map_err(plugin_error)?;
+        Ok(res.rssi)
+    }
+
+    /// Returns true if the device is bonded
+    pub(crate) async fn is_bonded(&self) -> Result<bool> {
+        #[derive(serde::Deserialize)]
+        struct BondState {
+            bonded: bool,
+        }
+        let res: BondState = get_handle()
+            .run_mobile_plugin(
+                "bond_state",
+                ConnectParams {
+                    address: self.address,
+                },
+            )
+            .map_err(plugin_error)?;
+        Ok(res.bonded)
+    }
+
+    /// Starts bonding and waits until the bond was created or failed
+    /// Returns the `UNBOND_REASON_*` value reported by android if bonding failed.
+    /// The plugin call blocks until the user answered the pairing dialog, so it runs on a
+    /// blocking thread.
+    pub(crate) async fn create_bond(&self) -> Result<std::result::Result<(), i32>> {
+        #[derive(serde::Deserialize)]
+        struct BondResult {
+            bonded: bool,
+            reason: i32,
+        }
+        let address = self.address;
+        let res: BondResult = crate::runtime::spawn_blocking(move || {
+            get_handle().run_mobile_plugin("create_bond", ConnectParams { address })
+        })
+        .await
+        .map_err(|e| btleplug::Error::RuntimeError(e.to_string()))?
+        .map_err(plugin_error)?;
+        Ok(if res.bonded { Ok(()) } else { Err(res.reason) })
+    }
+
+    /// Removes the bond, returns false if the device was not bonded
+    pub(crate) async fn remove_bond(&self) -> Result<bool> {
+        #[derive(serde::Deserialize)]
+        struct RemoveBondResult {
+            removed: bool,
+        }
+        let res: RemoveBondResult = get_handle()
+            .run_mobile_plugin(
+                "remove_bond",
+                ConnectParams {
+                    address: self.address,
+                },
+            )
+            .map_err(plugin_error)?;
+        Ok(res.removed)
+    }
+
+    /// Requests the given connection priority
+    pub(crate) async fn request_connection_priority(
+        &self,
+        priority: crate::setup::ConnectionPriority,
+    ) -> Result<()> {
+        #[derive(serde::Serialize)]
+        struct PriorityParams {
+            address: BDAddr,
+            priority: i32,
+        }
+        get_handle()
+            .run_mobile_plugin::<()>(
+                "request_connection_priority",
+                PriorityParams {
+                    address: self.address,
+                    priority: priority.android_value(),
+                },
+            )
+            .map_err(plugin_error)?;
+        Ok(())
+    }
+
+    /// Requests the given ATT MTU and returns the value negotiated with the device
+    pub(crate) async fn request_mtu(&self, mtu: u16) -> Result<u16> {
+        #[derive(serde::Serialize)]
+        struct MtuParams {
+            address: BDAddr,
+            mtu: u16,
+        }
+        #[derive(serde::Deserialize)]
+        struct MtuResult {
+            mtu: u16,
+        }
+        let res: MtuResult = get_handle()
+            .run_mobile_plugin(
+                "request_mtu",
+                MtuParams {
+                    address: self.address,
+                    mtu,
+                },
+            )
+            .map_err(plugin_error)?;
+        Ok(res.mtu)
+    }
+
+    /// Stream of the MTU values negotiated while connected, including renegotiations started
+    /// by the device
+    pub(crate) async fn mtu_changes(&self) -> Result<Pin<Box<dyn Stream<Item = u16> + Send>>> {
+        #[derive(serde::Deserialize)]
+        struct MtuChange {
+            mtu: u16,
+        }
+        #[derive(serde::Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct MtuChangesParams {
+            address: BDAddr,
+            channel: Channel<MtuChange>,
+        }
+        let (tx, rx) = tokio::sync::mpsc::channel::<u16>(1);
+        let stream = ReceiverStream::new(rx);
+        let channel: Channel<MtuChange> = Channel::new(move |response| {
+            match response.deserialize::<MtuChange>() {
+                Ok(change) => {
+                    if tx.blocking_send(change.mtu).is_err() {
+                        tracing::debug!("dropping MTU change, the receiver is closed");
+                    }
+                }
+                Err(e) => {
+                    tracing::error!("failed to deserialize MTU change: {:?}", e);
+                    return Err(tauri::Error::from(e));
+                }
+            };
+            Ok(())
+        });
+        get_handle()
+            .run_mobile_plugin(
+                "mtu_changes",
+                MtuChangesParams {
+                    address: self.address,
+                    channel,
+                },
+            )
+            .map_err(plugin_error)?;
+        Ok(Box::pin(stream))
+    }
+
+    /// Writes and returns the ATT status of the response, application errors of the device
+    /// (0x80 to 0x9F) are returned as the status instead of failing the write
+    pub(crate) async fn write_with_status(
+        &self,
+        characteristic: &Characteristic,
+        data: &[u8],
+        write_type: WriteType,
+    ) -> Result<u8> {
+        #[derive(serde::Deserialize)]
+        struct WriteResult {
+            #[serde(default)]
+            status: u8,
+        }
+        let res: WriteResult = get_handle()
+            .run_mobile_plugin(
+                "write",
+                serde_json::json!({
+                    "address": self.address,
+                    "characteristic": characteristic.uuid,
+                    "data": data,
+                    "withResponse": matches!(write_type, WriteType::WithResponse),
+                }),
+            )
+            .map_err(plugin_error)?;
+        Ok(res.status)
+    }
 }
 #[derive(serde::Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -211,12 +510,12 @@ impl btleplug::api::Peripheral for Peripheral {
             rssi: Some(self.rssi),
             manufacturer_data: self.manufacturer_data.clone(),
             services: self.services.clone(),
+            service_data: self.service_data.clone(),
             // TODO: implement the rest
             // at the moment not used by the handler or BleDevice struct so we can return default values
             address_type: Default::default(),
             class: Default::default(),
             tx_power_level: Default::default(),
-            service_data: Default::default(),
         }))
     }
 
@@ -246,7 +545,7 @@ impl btleplug::api::Peripheral for Peripheral {
                     address: self.address,
                 },
             )
-            .map_err(|e| btleplug::Error::RuntimeError(e.to_string()))
+            .map_err(plugin_error)
             .expect("failed to get services");
         let mut services = BTreeSet::new();
         for s in res.result {
@@ -284,7 +583,7 @@ impl btleplug::api::Peripheral for Peripheral {
                     address: self.address,
                 },
             )
-            .map_err(|e| btleplug::Error::RuntimeError(e.to_string()))?;
+            .map_err(plugin_error)?;
         Ok(res.result)
     }
 
@@ -296,7 +595,7 @@ impl btleplug::api::Peripheral for Peripheral {
                     address: self.address,
                 },
             )
-            .map_err(|e| btleplug::Error::RuntimeError(e.to_string()))?;
+            .map_err(plugin_error)?;
         Ok(())
     }
 
@@ -308,7 +607,7 @@ impl btleplug::api::Peripheral for Peripheral {
                     address: self.address,
                 },
             )
-            .map_err(|e| btleplug::Error::RuntimeError(e.to_string()))?;
+            .map_err(plugin_error)?;
         Ok(())
     }
 
@@ -320,7 +619,7 @@ impl btleplug::api::Peripheral for Peripheral {
                     address: self.address,
                 },
             )
-            .map_err(|e| btleplug::Error::RuntimeError(e.to_string()))?;
+            .map_err(plugin_error)?;
         Ok(())
     }
 
@@ -330,18 +629,16 @@ impl btleplug::api::Peripheral for Peripheral {
         data: &[u8],
         write_type: WriteType,
     ) -> Result<()> {
-        get_handle()
-            .run_mobile_plugin(
-                "write",
-                serde_json::json!({
-                    "address": self.address,
-                    "characteristic": characteristic.uuid,
-                    "data": data,
-                    "withResponse": matches!(write_type, WriteType::WithResponse),
-                }),
-            )
-            .map_err(|e| btleplug::Error::RuntimeError(e.to_string()))?;
-        Ok(())
+        match self.write_with_status(characteristic, data, write_type).await? {
+            0 => Ok(()),
+            status => Err(btleplug::Error::Other(Box::new(GattStatus {
+                status,
+                message: format!(
+                    "Write to characteristic {} failed with status {status}",
+                    characteristic.uuid
+                ),
+            }))),
+        }
     }
 
     async fn read(&self, characteristic: &Characteristic) -> Result<Vec<u8>> {
@@ -357,21 +654,33 @@ impl btleplug::api::Peripheral for Peripheral {
                     characteristic: characteristic.uuid,
                 },
             )
-            .map_err(|e| btleplug::Error::RuntimeError(e.to_string()))?;
-        info!("read: {:?}", res.value);
+            .map_err(plugin_error)?;
+        info!("read: {}", Payload(&res.value));
         Ok(res.value)
     }
 
     async fn subscribe(&self, characteristic: &Characteristic) -> Result<()> {
+        #[derive(serde::Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct SubscribeParams {
+            address: BDAddr,
+            characteristic: Uuid,
+            /// Enables indications instead of notifications in the client characteristic
+            /// configuration descriptor
+            indicate: bool,
+        }
+        let properties = characteristic.properties;
         get_handle()
             .run_mobile_plugin(
                 "subscribe",
-                ReadParams {
+                SubscribeParams {
                     address: self.address,
                     characteristic: characteristic.uuid,
+                    indicate: !properties.contains(CharPropFlags::NOTIFY)
+                        && properties.contains(CharPropFlags::INDICATE),
                 },
             )
-            .map_err(|e| btleplug::Error::RuntimeError(e.to_string()))?;
+            .map_err(plugin_error)?;
         Ok(())
     }
 
@@ -384,7 +693,7 @@ impl btleplug::api::Peripheral for Peripheral {
                     characteristic: characteristic.uuid,
                 },
             )
-            .map_err(|e| btleplug::Error::RuntimeError(e.to_string()))?;
+            .map_err(plugin_error)?;
         Ok(())
     }
 
@@ -426,15 +735,41 @@ impl btleplug::api::Peripheral for Peripheral {
                     channel,
                 },
             )
-            .map_err(|e| btleplug::Error::RuntimeError(e.to_string()))?;
+            .map_err(plugin_error)?;
         Ok(Box::pin(stream))
     }
 
-    async fn write_descriptor(&self, _descriptor: &Descriptor, _data: &[u8]) -> Result<()> {
-        todo!()
+    async fn write_descriptor(&self, descriptor: &Descriptor, data: &[u8]) -> Result<()> {
+        get_handle()
+            .run_mobile_plugin(
+                "write_descriptor",
+                serde_json::json!({
+                    "address": self.address,
+                    "characteristic": descriptor.characteristic_uuid,
+                    "descriptor": descriptor.uuid,
+                    "data": data,
+                }),
+            )
+            .map_err(plugin_error)?;
+        Ok(())
     }
 
-    async fn read_descriptor(&self, _descriptor: &Descriptor) -> Result<Vec<u8>> {
-        todo!()
+    async fn read_descriptor(&self, descriptor: &Descriptor) -> Result<Vec<u8>> {
+        #[derive(serde::Deserialize)]
+        struct ReadResult {
+            value: Vec<u8>,
+        }
+        let res: ReadResult = get_handle()
+            .run_mobile_plugin(
+                "read_descriptor",
+                serde_json::json!({
+                    "address": self.address,
+                    "characteristic": descriptor.characteristic_uuid,
+                    "descriptor": descriptor.uuid,
+                }),
+            )
+            .map_err(plugin_error)?;
+        info!("read descriptor: {}", Payload(&res.value));
+        Ok(res.value)
     }
 }
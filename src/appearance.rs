@@ -0,0 +1,60 @@
+use serde::Serialize;
+
+/// Category of the table generated by `build.rs` from `assigned_numbers/appearance_values.yaml`
+/// Subcategories are `(value, name)` pairs.
+struct AppearanceCategory {
+    category: u16,
+    name: &'static str,
+    subcategories: &'static [(u8, &'static str)],
+}
+
+include!(concat!(env!("OUT_DIR"), "/appearance.rs"));
+
+/// Appearance value split into its category and subcategory, see [`appearance_info`]
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AppearanceInfo {
+    /// Upper 10 bits of the appearance value
+    pub category: u16,
+    /// Lower 6 bits of the appearance value, `0` for the generic subcategory
+    pub subcategory: u8,
+    pub category_name: &'static str,
+    /// Name of the subcategory, `None` for the generic subcategory or if it is not assigned
+    pub subcategory_name: Option<&'static str>,
+}
+
+impl AppearanceInfo {
+    /// Name of the subcategory if known, otherwise of the category
+    pub fn name(&self) -> &'static str {
+        self.subcategory_name.unwrap_or(self.category_name)
+    }
+}
+
+/// Looks up the category and subcategory of an appearance value, e.g. `0x00C1` is
+/// "Sports Watch" in the category "Watch"
+/// Returns `None` if the category is not in the assigned numbers table.
+pub fn appearance_info(code: u16) -> Option<AppearanceInfo> {
+    let category = code >> 6;
+    let subcategory = u8::try_from(code & 0x3f).unwrap_or_default();
+    let entry = APPEARANCE_CATEGORIES
+        .iter()
+        .find(|entry| entry.category == category)?;
+    let subcategory_name = entry
+        .subcategories
+        .iter()
+        .find(|(value, _)| *value == subcategory)
+        .map(|(_, name)| *name);
+    Some(AppearanceInfo {
+        category,
+        subcategory,
+        category_name: entry.name,
+        subcategory_name,
+    })
+}
+
+/// Returns the human readable name of an appearance value
+/// The subcategory name if it is assigned, otherwise the category name, so UIs can fall back to
+/// a generic icon of the category. `None` if the category is not assigned.
+pub fn appearance_name(code: u16) -> Option<&'static str> {
+    appearance_info(code).map(|info| info.name())
+}
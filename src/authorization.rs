@@ -0,0 +1,77 @@
+use tracing::info;
+
+use crate::error::Error;
+use crate::events::BleEvent;
+use crate::handler::Handler;
+
+/// Whether the app may use bluetooth, see [`Handler::authorization_status`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub enum AuthorizationStatus {
+    /// The user was not asked yet, the OS asks with the first bluetooth operation
+    NotDetermined,
+    /// The user denied bluetooth for the app
+    Denied,
+    /// Bluetooth is restricted by the device policy, e.g. parental controls
+    Restricted,
+    Allowed,
+}
+
+impl AuthorizationStatus {
+    /// Reads the status without a handler, so it can be checked before the plugin initializes
+    pub(crate) fn current() -> Self {
+        #[cfg(any(target_os = "macos", target_os = "ios"))]
+        {
+            use objc2_core_bluetooth::{CBManager, CBManagerAuthorization};
+            // class property of CoreBluetooth, does not create a manager or prompt the user
+            match unsafe { CBManager::authorization_class() } {
+                CBManagerAuthorization::AllowedAlways => Self::Allowed,
+                CBManagerAuthorization::Denied => Self::Denied,
+                CBManagerAuthorization::Restricted => Self::Restricted,
+                _ => Self::NotDetermined,
+            }
+        }
+        #[cfg(not(any(target_os = "macos", target_os = "ios")))]
+        {
+            Self::Allowed
+        }
+    }
+}
+
+impl Handler {
+    /// Returns whether the app may use bluetooth
+    /// Uses the CoreBluetooth authorization on macOS and iOS. Other platforms report
+    /// [`AuthorizationStatus::Allowed`], android permissions are requested by the plugin when
+    /// they are needed. Changes are published as [`BleEvent::AuthorizationChanged`].
+    pub fn authorization_status(&self) -> AuthorizationStatus {
+        AuthorizationStatus::current()
+    }
+
+    /// Publishes [`BleEvent::AuthorizationChanged`] if the status changed since the last check
+    /// Called for every central event, CoreBluetooth updates the manager state when the
+    /// authorization flips, and with every heartbeat.
+    pub(crate) fn check_authorization(&self) {
+        let status = AuthorizationStatus::current();
+        let previous = self
+            .last_authorization
+            .lock()
+            .expect("authorization status lock poisoned")
+            .replace(status);
+        if let Some(previous) = previous.filter(|previous| *previous != status) {
+            info!("bluetooth authorization changed to {status:?}");
+            self.publish(BleEvent::AuthorizationChanged(status));
+            self.authorization_changed(previous);
+        }
+    }
+
+    /// Fails with [`Error::PermissionDenied`] if the user or the device policy denied bluetooth
+    pub(crate) fn check_authorized(&self) -> Result<(), Error> {
+        match AuthorizationStatus::current() {
+            status @ (AuthorizationStatus::Denied | AuthorizationStatus::Restricted) => {
+                Err(Error::PermissionDenied(status))
+            }
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tracing::{debug, warn};
+
+use crate::handler::Handler;
+use crate::models::{CharacteristicRef, WriteType};
+
+/// Write of [`Handler::write_batch`]
+#[derive(Debug, Clone, serde::Deserialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct BatchWrite {
+    #[cfg_attr(feature = "ts-export", ts(as = "crate::models::CharacteristicRefRepr"))]
+    pub characteristic: CharacteristicRef,
+    #[cfg_attr(feature = "ts-export", ts(type = "Uint8Array | Array<number>"))]
+    pub data: Vec<u8>,
+    #[cfg_attr(feature = "ts-export", ts(as = "crate::models::WriteTypeRepr"))]
+    pub write_type: WriteType,
+}
+
+/// Outcome of one write of a batch, in the order of the batch
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum BatchWriteResult {
+    Written,
+    Failed { error: String },
+    /// Not attempted because an earlier write failed and the batch stopped on errors
+    Skipped,
+}
+
+/// Result of [`Handler::write_batch`]
+#[derive(Debug, Clone, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct BatchReport {
+    /// All writes succeeded
+    pub success: bool,
+    pub results: Vec<BatchWriteResult>,
+}
+
+/// Progress of [`Handler::write_batch`], sent after every write
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct BatchProgress {
+    /// Writes attempted so far
+    pub completed: usize,
+    pub failed: usize,
+    pub total: usize,
+}
+
+impl Handler {
+    /// Writes the items in order, each one waits in the queue of the device like
+    /// [`Handler::send_data`]
+    /// With `stop_on_error` the first failed write ends the batch and the remaining writes are
+    /// reported as [`BatchWriteResult::Skipped`]. Otherwise all writes are attempted and the
+    /// failures collected. If a progress sender is provided, the progress is sent after every
+    /// write. Updates that find the channel full are superseded by the next one, the final
+    /// progress is always delivered.
+    /// Failed writes are reported in the result, they do not fail the call.
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// use uuid::{Uuid,uuid};
+    /// use tauri_plugin_blec::BatchWrite;
+    /// use tauri_plugin_blec::models::WriteType;
+    /// const MODE_UUID: Uuid = uuid!("51FF12BB-3ED8-46E5-B4F9-D64E2FEC021B");
+    /// const LEVEL_UUID: Uuid = uuid!("51FF12BB-3ED8-46E5-B4F9-D64E2FEC021C");
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     let items = vec![
+    ///         BatchWrite { characteristic: MODE_UUID.into(), data: vec![1], write_type: WriteType::WithResponse },
+    ///         BatchWrite { characteristic: LEVEL_UUID.into(), data: vec![80], write_type: WriteType::WithResponse },
+    ///     ];
+    ///     let report = handler.write_batch(items, true, None).await;
+    ///     println!("applied: {}", report.success);
+    /// });
+    /// # }
+    /// ```
+    pub async fn write_batch(
+        &self,
+        items: Vec<BatchWrite>,
+        stop_on_error: bool,
+        progress: Option<mpsc::Sender<BatchProgress>>,
+    ) -> BatchReport {
+        let total = items.len();
+        let mut results = Vec::with_capacity(total);
+        let mut failed = 0;
+        // latest progress that found the channel full
+        let mut pending = None;
+        for (i, item) in items.into_iter().enumerate() {
+            if stop_on_error && failed > 0 {
+                results.push(BatchWriteResult::Skipped);
+                continue;
+            }
+            let characteristic = item.characteristic.characteristic;
+            let result = match self
+                .send_data(item.characteristic, &item.data, item.write_type)
+                .await
+            {
+                Ok(()) => BatchWriteResult::Written,
+                Err(e) => {
+                    warn!("batch write {i} to {characteristic} failed: {e}");
+                    failed += 1;
+                    BatchWriteResult::Failed {
+                        error: e.to_string(),
+                    }
+                }
+            };
+            results.push(result);
+            if let Some(progress) = &progress {
+                let update = BatchProgress {
+                    completed: i + 1,
+                    failed,
+                    total,
+                };
+                pending = match progress.try_send(update) {
+                    Err(TrySendError::Full(update)) => Some(update),
+                    _ => None,
+                };
+            }
+        }
+        if let (Some(progress), Some(update)) = (&progress, pending) {
+            let _ = progress.send(update).await;
+        }
+        debug!("batch of {total} writes finished, {failed} failed");
+        BatchReport {
+            success: failed == 0,
+            results,
+        }
+    }
+}
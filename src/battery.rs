@@ -0,0 +1,202 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tracing::{debug, warn};
+
+use crate::error::Error;
+use crate::events::BleEvent;
+use crate::follow::{Follow, Followed};
+use crate::gatt_values::decode_battery_level;
+use crate::handler::{Handler, ListenerHandle};
+use crate::models::{CharacteristicRef, BATTERY_LEVEL_CHARACTERISTIC, BATTERY_SERVICE};
+use crate::runtime;
+
+/// State of the monitor started with [`Handler::start_battery_monitor`]
+#[derive(Default)]
+pub(crate) struct BatteryMonitor {
+    task: Mutex<Option<runtime::JoinHandle<()>>>,
+    /// Listener of the monitor on the connected device, gone with the next disconnect
+    listener: Mutex<Option<ListenerHandle>>,
+    /// Last level reported by the monitor, kept after a disconnect
+    latest: Mutex<Option<BatteryLevel>>,
+}
+
+impl BatteryMonitor {
+    /// Latest level of the monitor for [`Handler::get_state`]
+    pub(crate) fn latest(&self) -> Option<BatteryLevel> {
+        self.latest.lock().expect("battery level lock poisoned").clone()
+    }
+}
+
+/// Battery Level characteristic followed by the monitor
+struct BatteryFollower(&'static Handler);
+
+impl Followed for BatteryFollower {
+    async fn watch(&self, address: &str) -> Follow {
+        self.0.watch_battery(address).await
+    }
+
+    async fn poll(&self, address: &str) {
+        match self.0.battery_level().await {
+            Ok(percent) => self.0.update_battery_level(address, percent),
+            Err(e) => warn!("failed to poll the battery level of {address}: {e}"),
+        }
+    }
+}
+
+/// Battery level of a device reported by the battery monitor, see
+/// [`Handler::start_battery_monitor`]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct BatteryLevel {
+    pub address: String,
+    pub percent: u8,
+}
+
+fn battery_level_ref() -> CharacteristicRef {
+    CharacteristicRef {
+        address: None,
+        service: Some(BATTERY_SERVICE),
+        characteristic: BATTERY_LEVEL_CHARACTERISTIC,
+    }
+}
+
+impl Handler {
+    /// Reads the Battery Level characteristic (0x2A19) of the connected device
+    /// # Errors
+    /// Returns an error if no device is connected, the device has no Battery service or the
+    /// value is not a percentage
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     let percent = handler.battery_level().await.unwrap();
+    ///     println!("battery at {percent}%");
+    /// });
+    /// # }
+    /// ```
+    pub async fn battery_level(&self) -> Result<u8, Error> {
+        decode_battery_level(&self.recv_data(battery_level_ref()).await?)
+    }
+
+    /// Keeps track of the battery level of whichever device is connected until
+    /// [`Handler::stop_battery_monitor`] is called
+    /// On every connect, also after reconnects, the level is read and the monitor subscribes to
+    /// the Battery Level characteristic. Devices that do not notify the level are read every
+    /// `poll_fallback_interval` instead, a failed read or subscribe is tried again with the same
+    /// interval. Changes are published as [`BleEvent::BatteryChanged`]
+    /// and the latest level is part of [`Handler::get_state`]. Devices without a Battery
+    /// service are skipped. Starting the monitor again replaces the running one.
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use std::time::Duration;
+    /// use tauri::async_runtime;
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     handler.start_battery_monitor(Duration::from_secs(60));
+    /// });
+    /// # }
+    /// ```
+    pub fn start_battery_monitor(&'static self, poll_fallback_interval: Duration) {
+        let follower = BatteryFollower(self);
+        let task = runtime::spawn(self.follow_characteristic(follower, poll_fallback_interval));
+        if let Some(previous) = self
+            .battery
+            .task
+            .lock()
+            .expect("battery monitor lock poisoned")
+            .replace(task)
+        {
+            previous.abort();
+        }
+    }
+
+    /// Stops the monitor started with [`Handler::start_battery_monitor`], removes its listener
+    /// and forgets the latest level
+    /// Listeners of the Battery Level characteristic added with [`Handler::subscribe`] are kept.
+    /// # Errors
+    /// Returns an error if the unsubscribe fails
+    pub async fn stop_battery_monitor(&self) -> Result<(), Error> {
+        if let Some(task) = self
+            .battery
+            .task
+            .lock()
+            .expect("battery monitor lock poisoned")
+            .take()
+        {
+            task.abort();
+        }
+        self.battery.latest.lock().expect("battery level lock poisoned").take();
+        let listener = self
+            .battery
+            .listener
+            .lock()
+            .expect("battery listener lock poisoned")
+            .take();
+        match listener {
+            Some(listener) => self.remove_listener(listener).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Reads the level and subscribes to it
+    async fn watch_battery(&'static self, address: &str) -> Follow {
+        let percent = match self.battery_level().await {
+            Ok(percent) => percent,
+            Err(Error::CharacNotAvailable(_)) => {
+                debug!("{address} has no battery level, not monitoring it");
+                return Follow::Unavailable;
+            }
+            Err(e) => {
+                warn!("failed to read the battery level of {address}, trying again: {e}");
+                return Follow::Retry;
+            }
+        };
+        self.update_battery_level(address, percent);
+        let owner = address.to_string();
+        let subscribed = self
+            .subscribe(battery_level_ref(), move |data: &[u8]| match decode_battery_level(data) {
+                Ok(percent) => self.update_battery_level(&owner, percent),
+                Err(e) => warn!("ignoring battery notification: {e}"),
+            })
+            .await;
+        match subscribed {
+            Ok(listener) => {
+                *self
+                    .battery
+                    .listener
+                    .lock()
+                    .expect("battery listener lock poisoned") = Some(listener);
+                Follow::Subscribed
+            }
+            Err(Error::NotifyNotSupported { .. }) => {
+                debug!("{address} does not notify the battery level, polling it");
+                Follow::Polled
+            }
+            Err(e) => {
+                warn!("failed to subscribe to the battery level of {address}, trying again: {e}");
+                Follow::Retry
+            }
+        }
+    }
+
+    /// Caches the level and publishes [`BleEvent::BatteryChanged`] if it changed
+    fn update_battery_level(&self, address: &str, percent: u8) {
+        let level = BatteryLevel {
+            address: address.to_string(),
+            percent,
+        };
+        let mut latest = self.battery.latest.lock().expect("battery level lock poisoned");
+        if latest.as_ref() == Some(&level) {
+            return;
+        }
+        debug!("battery of {address} at {percent}%");
+        *latest = Some(level.clone());
+        drop(latest);
+        self.publish(BleEvent::BatteryChanged(level));
+    }
+}
@@ -0,0 +1,132 @@
+//! Checks the TypeScript bindings in `guest-js/bindings` against the rust types
+//! Regenerate them with `BLEC_UPDATE_BINDINGS=1 cargo test --features ts-export,conformance
+//! bindings`. Built only with the `tauri` and `conformance` features, so every binding is
+//! covered.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ts_rs::TS;
+
+use crate::{
+    authorization, batch, battery, capture, clock, commands, conformance, consent, device_cache,
+    discovery, error, events, handler, interference, metrics, models, payload_log, reconnect,
+    scan_budget, scan_consumers, scan_throttle, setup, timeline, transfer, wire,
+};
+
+/// Exports `T` and every type it references into `dir`
+fn export<T: TS + 'static>(dir: &Path) {
+    T::export_all_to(dir).unwrap_or_else(|e| panic!("failed to export {}: {e}", T::name()));
+}
+
+/// Exports every command and event payload
+fn export_payloads(dir: &Path) {
+    export::<authorization::AuthorizationStatus>(dir);
+    export::<batch::BatchProgress>(dir);
+    export::<batch::BatchReport>(dir);
+    export::<batch::BatchWrite>(dir);
+    export::<battery::BatteryLevel>(dir);
+    export::<capture::CaptureFormat>(dir);
+    export::<capture::CaptureStats>(dir);
+    export::<clock::ClockSync>(dir);
+    export::<clock::TimedNotification>(dir);
+    export::<commands::Correlated<()>>(dir);
+    export::<commands::MultiResponseBase64>(dir);
+    export::<conformance::ConformanceOptions>(dir);
+    export::<conformance::ConformanceReport>(dir);
+    export::<consent::PairingConsentRequest>(dir);
+    export::<device_cache::DeviceCandidate>(dir);
+    export::<discovery::DiscoveredEvent>(dir);
+    export::<error::CorrelatedError>(dir);
+    export::<events::AdapterState>(dir);
+    export::<events::BondChange>(dir);
+    export::<events::CallbackPanic>(dir);
+    export::<handler::BleState>(dir);
+    export::<handler::CharacteristicOptions>(dir);
+    export::<handler::ConnectOptions>(dir);
+    export::<handler::ConnectResult>(dir);
+    export::<handler::ConnectionCapabilities>(dir);
+    export::<handler::DeviceReaddressed>(dir);
+    export::<handler::DuplicateFilter>(dir);
+    export::<handler::FingerprintRule>(dir);
+    export::<handler::ForgetReport>(dir);
+    export::<handler::Health>(dir);
+    export::<handler::KeepAliveMethod>(dir);
+    export::<handler::KnownDevice>(dir);
+    export::<handler::PairingEvent>(dir);
+    export::<handler::RetryPolicy>(dir);
+    export::<handler::ScanCapabilities>(dir);
+    export::<handler::SessionFrame>(dir);
+    export::<handler::TimedRead>(dir);
+    export::<handler::WriteResponse>(dir);
+    export::<interference::ConnectionInterference>(dir);
+    export::<interference::SubscriptionReport>(dir);
+    export::<metrics::BleMetrics>(dir);
+    export::<models::AdapterInfo>(dir);
+    export::<models::AdapterSelector>(dir);
+    export::<models::AddressPolicy>(dir);
+    export::<models::BleDevice>(dir);
+    export::<models::DeviceInfo>(dir);
+    export::<models::ServiceInfo>(dir);
+    export::<payload_log::PayloadLogging>(dir);
+    export::<reconnect::ReconnectProgress>(dir);
+    export::<scan_budget::ScanEventBudget>(dir);
+    export::<scan_budget::ScanEventStats>(dir);
+    export::<scan_consumers::ScanConsumerId>(dir);
+    export::<scan_consumers::ScanConsumerOptions>(dir);
+    export::<scan_throttle::ScanThrottleWarning>(dir);
+    export::<setup::SetupNotification>(dir);
+    export::<timeline::SessionTimeline>(dir);
+    export::<transfer::TransferOptions>(dir);
+    export::<transfer::TransferReport>(dir);
+    export::<wire::WireFormat>(dir);
+}
+
+/// Paths of the files below `dir`, relative to it
+fn files(dir: &Path) -> BTreeSet<PathBuf> {
+    let mut files = BTreeSet::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&current) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if let Ok(relative) = path.strip_prefix(dir) {
+                files.insert(relative.to_path_buf());
+            }
+        }
+    }
+    files
+}
+
+#[test]
+fn bindings_match_the_rust_types() {
+    let committed = Path::new(env!("CARGO_MANIFEST_DIR")).join("guest-js/bindings");
+    if std::env::var_os("BLEC_UPDATE_BINDINGS").is_some() {
+        for stale in files(&committed) {
+            fs::remove_file(committed.join(stale)).unwrap();
+        }
+        export_payloads(&committed);
+        return;
+    }
+
+    let generated = std::env::temp_dir().join(format!("blec-bindings-{}", std::process::id()));
+    export_payloads(&generated);
+    let generated_files = files(&generated);
+    let committed_files = files(&committed);
+    let differing: Vec<_> = generated_files
+        .union(&committed_files)
+        .filter(|file| fs::read(generated.join(file)).ok() != fs::read(committed.join(file)).ok())
+        .collect();
+    let _ = fs::remove_dir_all(&generated);
+    assert!(
+        differing.is_empty(),
+        "guest-js/bindings is out of date, regenerate it with \
+         `BLEC_UPDATE_BINDINGS=1 cargo test --features ts-export,conformance bindings`: \
+         {differing:?}"
+    );
+}
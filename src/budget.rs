@@ -0,0 +1,44 @@
+use std::future::Future;
+use std::time::Duration;
+
+use tokio::time::{timeout_at, Instant};
+use tracing::warn;
+
+use crate::error::Error;
+
+/// Deadline shared by the steps of a composite operation
+/// The steps keep their own timeouts, the budget cancels the step in flight once the total time
+/// is used up and reports it with [`Error::TotalTimeout`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Budget {
+    deadline: Option<Instant>,
+}
+
+impl Budget {
+    pub(crate) fn new(total: Option<Duration>) -> Self {
+        Self {
+            deadline: total.map(|total| Instant::now() + total),
+        }
+    }
+
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Runs the step, failing with [`Error::TotalTimeout`] if the budget is used up first
+    pub(crate) async fn run<T>(
+        &self,
+        step: &str,
+        fut: impl Future<Output = Result<T, Error>>,
+    ) -> Result<T, Error> {
+        let Some(deadline) = self.deadline else {
+            return fut.await;
+        };
+        timeout_at(deadline, fut).await.unwrap_or_else(|_| {
+            warn!("total timeout exceeded during {step}");
+            Err(Error::TotalTimeout {
+                step: step.to_string(),
+            })
+        })
+    }
+}
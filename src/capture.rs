@@ -0,0 +1,254 @@
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc as std_mpsc, Arc};
+use std::time::{Duration, Instant};
+
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+use crate::error::Error;
+use crate::handler::{Handler, SessionFrame};
+use crate::models::CharacteristicRef;
+use crate::runtime;
+
+/// Number of frames buffered between the notification task and the file writer
+/// Frames arriving while the buffer is full are dropped and counted in [`CaptureStats::drops`]
+const CAPTURE_QUEUE: usize = 4096;
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(2);
+
+/// File format used by [`Handler::start_capture`]
+#[derive(Debug, Clone, Copy, serde::Deserialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub enum CaptureFormat {
+    /// One line per frame: `timestamp_us,characteristic,hex payload`
+    Csv,
+    /// Binary records: timestamp in µs (u64 LE), characteristic uuid (16 bytes),
+    /// payload length (u32 LE) followed by the payload
+    RawLenPrefixed,
+}
+
+/// Statistics of a running or finished capture
+#[derive(Debug, Clone, Default, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct CaptureStats {
+    #[cfg_attr(feature = "ts-export", ts(type = "number"))]
+    pub frames: u64,
+    #[cfg_attr(feature = "ts-export", ts(type = "number"))]
+    pub bytes: u64,
+    #[cfg_attr(feature = "ts-export", ts(type = "number"))]
+    pub duration_ms: u64,
+    #[cfg_attr(feature = "ts-export", ts(type = "number"))]
+    pub drops: u64,
+    pub files: u32,
+}
+
+/// A running capture started with [`Handler::start_capture`]
+pub struct CaptureHandle {
+    session_id: u64,
+    writer: runtime::JoinHandle<std::io::Result<CaptureStats>>,
+}
+
+impl CaptureHandle {
+    /// Returns the id of the capture
+    pub fn id(&self) -> u64 {
+        self.session_id
+    }
+}
+
+struct CaptureWriter {
+    path: PathBuf,
+    format: CaptureFormat,
+    rotate_bytes: Option<u64>,
+    file: BufWriter<File>,
+    file_bytes: u64,
+    stats: CaptureStats,
+}
+
+impl CaptureWriter {
+    fn new(path: PathBuf, format: CaptureFormat, rotate_mb: Option<u64>) -> std::io::Result<Self> {
+        let file = BufWriter::new(File::create(&path)?);
+        let mut writer = Self {
+            path,
+            format,
+            rotate_bytes: rotate_mb.map(|mb| mb * 1024 * 1024),
+            file,
+            file_bytes: 0,
+            stats: CaptureStats {
+                files: 1,
+                ..Default::default()
+            },
+        };
+        writer.write_header()?;
+        Ok(writer)
+    }
+
+    fn write_header(&mut self) -> std::io::Result<()> {
+        if matches!(self.format, CaptureFormat::Csv) {
+            let header = b"timestamp_us,characteristic,data\n";
+            self.file.write_all(header)?;
+            self.file_bytes += header.len() as u64;
+        }
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        self.file.flush()?;
+        let path = rotated_path(&self.path, self.stats.files);
+        debug!("rotating capture to {}", path.display());
+        self.file = BufWriter::new(File::create(path)?);
+        self.file_bytes = 0;
+        self.stats.files += 1;
+        self.write_header()
+    }
+
+    fn write_frame(&mut self, frame: &SessionFrame) -> std::io::Result<()> {
+        if self.rotate_bytes.is_some_and(|max| self.file_bytes >= max) {
+            self.rotate()?;
+        }
+        let record = match self.format {
+            CaptureFormat::Csv => {
+                let mut line = format!("{},{},", frame.timestamp_us, frame.characteristic);
+                for b in &frame.data {
+                    let _ = write!(line, "{b:02x}");
+                }
+                line.push('\n');
+                line.into_bytes()
+            }
+            CaptureFormat::RawLenPrefixed => {
+                let len = u32::try_from(frame.data.len()).unwrap_or(u32::MAX);
+                let mut record = Vec::with_capacity(28 + frame.data.len());
+                record.extend_from_slice(&frame.timestamp_us.to_le_bytes());
+                record.extend_from_slice(frame.characteristic.as_bytes());
+                record.extend_from_slice(&len.to_le_bytes());
+                record.extend_from_slice(&frame.data);
+                record
+            }
+        };
+        self.file.write_all(&record)?;
+        self.file_bytes += record.len() as u64;
+        self.stats.frames += 1;
+        self.stats.bytes += frame.data.len() as u64;
+        Ok(())
+    }
+}
+
+/// Inserts the rotation index before the file extension: `capture.csv` -> `capture.1.csv`
+fn rotated_path(path: &Path, index: u32) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let name = match path.extension() {
+        Some(ext) => format!("{stem}.{index}.{}", ext.to_string_lossy()),
+        None => format!("{stem}.{index}"),
+    };
+    path.with_file_name(name)
+}
+
+impl Handler {
+    /// Starts writing all notifications of the given characteristics straight to a file
+    /// Frames are taken from a [`Handler::open_session`] stream, so they are written in arrival
+    /// order with their arrival timestamp. If `rotate_mb` is set, a new file is started whenever
+    /// the current one exceeds that size (`capture.csv`, `capture.1.csv`, ...).
+    /// If a progress sender is provided, the current statistics are sent every few seconds.
+    /// A write error ends the capture and closes its session, [`Handler::stop_capture`] then
+    /// returns the error.
+    /// # Errors
+    /// Returns an error if the file can not be created or the session can not be opened
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// use uuid::{Uuid,uuid};
+    /// use tauri_plugin_blec::CaptureFormat;
+    /// const CHARACTERISTIC_UUID: Uuid = uuid!("51FF12BB-3ED8-46E5-B4F9-D64E2FEC021B");
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     let capture = handler
+    ///         .start_capture(vec![CHARACTERISTIC_UUID], "capture.csv".into(), CaptureFormat::Csv, Some(100), None)
+    ///         .await
+    ///         .unwrap();
+    ///     tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+    ///     let stats = handler.stop_capture(capture).await.unwrap();
+    ///     println!("captured {} frames", stats.frames);
+    /// });
+    /// # }
+    /// ```
+    pub async fn start_capture(
+        &self,
+        characs: impl IntoIterator<Item = impl Into<CharacteristicRef>>,
+        path: PathBuf,
+        format: CaptureFormat,
+        rotate_mb: Option<u64>,
+        progress: Option<mpsc::Sender<CaptureStats>>,
+    ) -> Result<CaptureHandle, Error> {
+        let mut writer = CaptureWriter::new(path, format, rotate_mb)?;
+        let mut session = self.open_session(characs).await?;
+        let session_id = session.id();
+        let drops = Arc::new(AtomicU64::new(0));
+        let (tx, rx) = std_mpsc::sync_channel::<SessionFrame>(CAPTURE_QUEUE);
+
+        let forward_drops = drops.clone();
+        runtime::spawn(async move {
+            while let Some(frame) = session.recv().await {
+                match tx.try_send(frame) {
+                    Ok(()) => {}
+                    Err(std_mpsc::TrySendError::Full(_)) => {
+                        forward_drops.fetch_add(1, Ordering::Relaxed);
+                    }
+                    Err(std_mpsc::TrySendError::Disconnected(_)) => break,
+                }
+            }
+            // the writer failed, dropping the handle closes the session
+            drop(session);
+        });
+
+        let writer = runtime::spawn_blocking(move || {
+            let start = Instant::now();
+            let mut last_progress = Instant::now();
+            loop {
+                match rx.recv_timeout(PROGRESS_INTERVAL) {
+                    Ok(frame) => writer.write_frame(&frame)?,
+                    Err(std_mpsc::RecvTimeoutError::Timeout) => {}
+                    Err(std_mpsc::RecvTimeoutError::Disconnected) => break,
+                }
+                if last_progress.elapsed() >= PROGRESS_INTERVAL {
+                    last_progress = Instant::now();
+                    if let Some(progress) = &progress {
+                        let mut stats = writer.stats.clone();
+                        stats.duration_ms =
+                            u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX);
+                        stats.drops = drops.load(Ordering::Relaxed);
+                        let _ = progress.try_send(stats);
+                    }
+                }
+            }
+            writer.file.flush()?;
+            let mut stats = writer.stats;
+            stats.duration_ms = u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX);
+            stats.drops = drops.load(Ordering::Relaxed);
+            Ok(stats)
+        });
+        Ok(CaptureHandle { session_id, writer })
+    }
+
+    /// Stops a capture, flushes the file and returns the final statistics
+    /// # Errors
+    /// Returns an error if closing the session or writing the file failed
+    pub async fn stop_capture(&self, capture: CaptureHandle) -> Result<CaptureStats, Error> {
+        if let Err(e) = self.close_session(capture.session_id).await {
+            // the session is already gone if the device disconnected
+            warn!("failed to close capture session: {e}");
+        }
+        let stats = capture
+            .writer
+            .await
+            .map_err(|e| std::io::Error::other(e.to_string()))??;
+        Ok(stats)
+    }
+}
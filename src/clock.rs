@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use uuid::Uuid;
+
+use crate::error::Error;
+use crate::handler::Handler;
+
+/// Clock of [`TimedNotification::timestamp_us`] with the mappings and offsets of the devices
+pub(crate) struct Clocks {
+    /// Start of the monotonic clock, shared by all devices
+    epoch: Instant,
+    /// Wall clock mapping captured when a device connected, by address
+    syncs: Mutex<HashMap<String, ClockSync>>,
+    /// Calibration offsets set with [`Handler::set_time_offset`], by address
+    offsets: Mutex<HashMap<String, i64>>,
+}
+
+impl Default for Clocks {
+    fn default() -> Self {
+        Self {
+            epoch: Instant::now(),
+            syncs: Mutex::new(HashMap::new()),
+            offsets: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+/// Notification with the time it arrived, see [`Handler::subscribe_timestamped`]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct TimedNotification {
+    pub address: String,
+    pub characteristic: Uuid,
+    /// Microseconds on the monotonic clock shared by all devices, with the offset of
+    /// [`Handler::set_time_offset`] applied
+    /// Convert it to the wall clock with [`Handler::clock_sync`].
+    #[cfg_attr(feature = "ts-export", ts(type = "number"))]
+    pub timestamp_us: u64,
+    pub data: Vec<u8>,
+}
+
+/// Pairs a reading of the monotonic clock with the wall clock at the same moment, captured once
+/// when a device connected
+/// Timestamps of the session are mapped with this single reading, so adjustments of the system
+/// clock during the session do not move them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct ClockSync {
+    #[cfg_attr(feature = "ts-export", ts(type = "number"))]
+    pub monotonic_us: u64,
+    /// Microseconds since the unix epoch
+    #[cfg_attr(feature = "ts-export", ts(type = "number"))]
+    pub unix_us: u64,
+}
+
+impl ClockSync {
+    /// Wall clock time of a [`TimedNotification::timestamp_us`] in microseconds since the unix
+    /// epoch
+    pub fn to_unix_us(&self, timestamp_us: u64) -> u64 {
+        let elapsed = i128::from(timestamp_us) - i128::from(self.monotonic_us);
+        u64::try_from(i128::from(self.unix_us) + elapsed).unwrap_or_default()
+    }
+}
+
+impl Clocks {
+    fn monotonic_us(&self, at: Instant) -> u64 {
+        u64::try_from(at.saturating_duration_since(self.epoch).as_micros()).unwrap_or(u64::MAX)
+    }
+
+    /// Timestamp of a notification of the address that arrived `at`
+    pub(crate) fn timestamp_us(&self, address: &str, at: Instant) -> u64 {
+        let offset = self
+            .offsets
+            .lock()
+            .expect("time offsets lock poisoned")
+            .get(address)
+            .copied()
+            .unwrap_or_default();
+        self.monotonic_us(at).saturating_add_signed(offset)
+    }
+
+    /// Captures the wall clock mapping of a new connection
+    pub(crate) fn start_session(&self, address: &str) {
+        let now = Instant::now();
+        let unix_us = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| u64::try_from(d.as_micros()).unwrap_or(u64::MAX))
+            .unwrap_or_default();
+        let sync = ClockSync {
+            monotonic_us: self.monotonic_us(now),
+            unix_us,
+        };
+        self.syncs
+            .lock()
+            .expect("clock syncs lock poisoned")
+            .insert(address.to_string(), sync);
+    }
+
+    /// Drops the wall clock mapping and the offset of a forgotten device
+    pub(crate) fn forget(&self, address: &str) {
+        self.syncs.lock().expect("clock syncs lock poisoned").remove(address);
+        self.offsets.lock().expect("time offsets lock poisoned").remove(address);
+    }
+}
+
+impl Handler {
+    /// Adds a latency calibration to the timestamps of the device, e.g. the delay of its sensor
+    /// measured against a reference
+    /// Negative offsets move the timestamps back. The offset applies to notifications received
+    /// from now on and is kept across reconnects.
+    /// # Errors
+    /// Returns [`Error::InvalidAddress`] if the address can not be parsed
+    pub fn set_time_offset(&self, address: &str, micros: i64) -> Result<(), Error> {
+        let address = self.parse_address(address)?;
+        let mut offsets = self.clocks.offsets.lock().expect("time offsets lock poisoned");
+        if micros == 0 {
+            offsets.remove(&address);
+        } else {
+            offsets.insert(address, micros);
+        }
+        Ok(())
+    }
+
+    /// Wall clock mapping captured when the device last connected, `None` if it never connected
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// use uuid::uuid;
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     let c = uuid!("00002A37-0000-1000-8000-00805F9B34FB");
+    ///     let (_handle, mut rx) = handler.subscribe_timestamped(c, 64).await.unwrap();
+    ///     while let Some(n) = rx.recv().await {
+    ///         let sync = handler.clock_sync(&n.address).unwrap();
+    ///         println!("{}: {:?}", sync.to_unix_us(n.timestamp_us), n.data);
+    ///     }
+    /// });
+    /// # }
+    /// ```
+    pub fn clock_sync(&self, address: &str) -> Option<ClockSync> {
+        let address = self.parse_address(address).ok()?;
+        self.clocks
+            .syncs
+            .lock()
+            .expect("clock syncs lock poisoned")
+            .get(&address)
+            .copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forgetting_a_device_drops_its_sync_and_offset() {
+        let clocks = Clocks::default();
+        clocks.start_session("AA");
+        clocks.start_session("BB");
+        clocks.offsets.lock().unwrap().insert("AA".to_string(), 500);
+        clocks.forget("AA");
+        let at = clocks.epoch;
+        assert_eq!(clocks.timestamp_us("AA", at), 0);
+        assert!(!clocks.syncs.lock().unwrap().contains_key("AA"));
+        assert!(clocks.syncs.lock().unwrap().contains_key("BB"));
+    }
+}
@@ -1,72 +1,238 @@
 use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Component, Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
 
 use btleplug::api::Characteristic;
 use tauri::ipc::Channel;
-use tauri::{async_runtime, command, AppHandle, Runtime};
+use tauri::{async_runtime, command, AppHandle, Emitter, Manager, Runtime, Window};
 use tokio::sync::mpsc;
 use tracing::info;
 use uuid::Uuid;
 
-use crate::error::Result;
-use crate::get_handler;
-use btleplug::models::{BleDevice, ScanFilter, Service, WriteType};
+use crate::authorization::AuthorizationStatus;
+use crate::batch::{BatchProgress, BatchReport, BatchWrite};
+use crate::capture::{CaptureFormat, CaptureHandle, CaptureStats};
+use crate::clock::{ClockSync, TimedNotification};
+use crate::device_cache::DeviceCandidate;
+use crate::discovery::{DiscoverOptions, DiscoveredEvent};
+use crate::error::{CorrelatedError, Error, Result};
+use crate::events::AdapterState;
+use crate::interference::SubscriptionReport;
+use crate::command_handler;
+use crate::metrics::BleMetrics;
+use crate::handler::{
+    BleState, CharacteristicOptions, ConnectOptions, ConnectResult, ConnectionCapabilities,
+    ConnectionLimitPolicy, DuplicateFilter, FingerprintRule, ForgetReport, Handler, Health,
+    KeepAliveMethod, KnownDevice, ListenerHandle, MultiResponseOptions, RetryPolicy,
+    ScanCapabilities, TimedRead, WriteResponse,
+};
+use crate::models::{
+    AdapterInfo, AdapterSelector, AddressPolicy, BleDevice, CharacteristicProperties,
+    CharacteristicRef, DeviceInfo, PnpId, ScanFilter, ScanNameFilter, Service, ServiceInfo,
+    WriteType,
+};
+use crate::payload_log::{set_payload_logging as set_policy, Payload, PayloadLogging};
+use crate::plugin::DEVICES_DISCOVERED_EVENT;
+use crate::scan_budget::ScanEventBudget;
+use crate::scan_consumers::{ScanConsumerId, ScanConsumerOptions};
+use crate::wire::WireFormat;
+use crate::timeline::SessionTimeline;
+use crate::transfer::{TransferOptions, TransferReport};
+
+static NEXT_CORRELATION_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Payload of the events and results of an operation started with a correlation id
+#[derive(Clone, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Correlated<T> {
+    correlation_id: String,
+    data: T,
+}
+
+/// Uses the id supplied by the frontend or generates a unique one
+fn correlation_id(id: Option<String>) -> String {
+    id.unwrap_or_else(|| format!("blec-{}", NEXT_CORRELATION_ID.fetch_add(1, Ordering::Relaxed)))
+}
 
 #[command]
 pub(crate) async fn scan<R: Runtime>(
-    _app: AppHandle<R>,
+    app: AppHandle<R>,
     timeout: u64,
     services: Vec<Uuid>,
-    on_devices: Channel<Vec<BleDevice>>,
-) -> Result<()> {
-    tracing::info!("Scanning for BLE devices");
-    let handler = get_handler()?;
+    name_filter: Option<ScanNameFilter>,
+    on_devices: Channel<Correlated<Vec<BleDevice>>>,
+    correlation_id: Option<String>,
+    event_budget: Option<ScanEventBudget>,
+) -> std::result::Result<String, CorrelatedError> {
+    let correlation_id = self::correlation_id(correlation_id);
+    tracing::info!("Scanning for BLE devices ({correlation_id})");
+    let handler = command_handler()
+        .await
+        .map_err(|e| e.correlated(&correlation_id))?;
     let (tx, mut rx) = tokio::sync::mpsc::channel(1);
-    
+
+    let id = correlation_id.clone();
     async_runtime::spawn(async move {
+        let mut last_sent = None;
         while let Some(devices) = rx.recv().await {
+            // the scan reports every 200ms, also if nothing changed
+            if last_sent.as_ref() == Some(&devices) {
+                continue;
+            }
+            let batch = Correlated {
+                correlation_id: id.clone(),
+                data: devices.clone(),
+            };
+            if let Err(e) = app.emit(DEVICES_DISCOVERED_EVENT, batch.clone()) {
+                tracing::warn!("failed to emit {DEVICES_DISCOVERED_EVENT}: {e}");
+            }
             on_devices
-                .send(devices)
+                .send(batch)
                 .expect("failed to send device to the front-end");
+            last_sent = Some(devices);
         }
     });
+    let options = DiscoverOptions {
+        timeout: Duration::from_millis(timeout),
+        filter: ScanFilter::AnyService(services),
+        name_filter: name_filter.unwrap_or_default(),
+        event_budget,
+        ..DiscoverOptions::default()
+    };
     handler
-        .discover(Some(tx), timeout, ScanFilter::AnyService(services))
-        .await?;
-    Ok(())
+        .discover_with_options(Some(tx), options)
+        .await
+        .map_err(|e| e.correlated(&correlation_id))?;
+    Ok(correlation_id)
 }
 
 #[command]
 pub(crate) async fn stop_scan<R: Runtime>(_app: AppHandle<R>) -> Result<()> {
     tracing::info!("Stopping BLE scan");
-    let handler = get_handler()?;
+    let handler = command_handler().await?;
     handler.stop_scan().await?;
     Ok(())
 }
 
+#[command]
+pub(crate) async fn start_discovery<R: Runtime>(
+    _app: AppHandle<R>,
+    services: Vec<Uuid>,
+    name_filter: Option<ScanNameFilter>,
+    correlation_id: Option<String>,
+    event_budget: Option<ScanEventBudget>,
+) -> std::result::Result<String, CorrelatedError> {
+    let correlation_id = self::correlation_id(correlation_id);
+    tracing::info!("Starting continuous BLE discovery ({correlation_id})");
+    async {
+        let handler = command_handler().await?;
+        handler
+            .start_discovery_with_event_budget(
+                None,
+                ScanFilter::AnyService(services),
+                name_filter.unwrap_or_default(),
+                event_budget,
+            )
+            .await
+    }
+    .await
+    .map_err(|e| e.correlated(&correlation_id))?;
+    Ok(correlation_id)
+}
+
+#[command]
+pub(crate) async fn stop_discovery<R: Runtime>(_app: AppHandle<R>) -> Result<()> {
+    tracing::info!("Stopping continuous BLE discovery");
+    let handler = command_handler().await?;
+    handler.stop_discovery().await
+}
+
+#[command]
+pub(crate) async fn register_scan_consumer<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    options: ScanConsumerOptions,
+    on_event: Channel<DiscoveredEvent>,
+) -> Result<ScanConsumerId> {
+    let handler = command_handler().await?;
+    let (tx, mut rx) = mpsc::channel(16);
+    async_runtime::spawn(async move {
+        while let Some(event) = rx.recv().await {
+            if let Err(e) = on_event.send(event) {
+                // dropping the receiver unregisters the consumer
+                tracing::warn!("failed to send scan consumer event to the front-end: {e}");
+                break;
+            }
+        }
+    });
+    let id = handler.register_scan_consumer(options, tx).await?;
+    window
+        .state::<WindowScopes>()
+        .add_scan_consumer(window.label(), id);
+    Ok(id)
+}
+
+#[command]
+pub(crate) async fn unregister_scan_consumer<R: Runtime>(
+    app: AppHandle<R>,
+    id: ScanConsumerId,
+) -> Result<()> {
+    let handler = command_handler().await?;
+    app.state::<WindowScopes>().forget_scan_consumer(id);
+    handler.unregister_scan_consumer(id).await
+}
+
 #[command]
 pub(crate) async fn connect<R: Runtime>(
     _app: AppHandle<R>,
     address: String,
-    on_disconnect: Channel<()>,
-) -> Result<Vec<Service>> {
-    tracing::info!("Connecting to BLE device: {:?}", address);
-    let handler = get_handler()?;
+    on_disconnect: Channel<String>,
+    options: Option<ConnectOptions>,
+    correlation_id: Option<String>,
+) -> std::result::Result<Correlated<ConnectResult>, CorrelatedError> {
+    let correlation_id = self::correlation_id(correlation_id);
+    tracing::info!("Connecting to BLE device: {address:?} ({correlation_id})");
+    let handler = command_handler()
+        .await
+        .map_err(|e| e.correlated(&correlation_id))?;
+    let id = correlation_id.clone();
     let disconnct_handler = move || {
         on_disconnect
-            .send(())
+            .send(id.clone())
             .expect("failed to send disconnect event to the front-end");
     };
-    let services = handler
-        .connect(&address, Some(Box::new(disconnct_handler)))
-        .await?;
-    Ok(services)
+    let result = handler
+        .connect_with_options(
+            &address,
+            Some(Box::new(disconnct_handler)),
+            options.unwrap_or_default(),
+        )
+        .await
+        .map_err(|e| e.correlated(&correlation_id))?;
+    Ok(Correlated {
+        correlation_id,
+        data: result,
+    })
 }
 
 #[command]
-pub(crate) async fn disconnect<R: Runtime>(_app: AppHandle<R>) -> Result<()> {
-    tracing::info!("Disconnecting from BLE device");
-    let handler = get_handler()?;
-    handler.disconnect().await?;
+pub(crate) async fn disconnect<R: Runtime>(
+    _app: AppHandle<R>,
+    flush: Option<bool>,
+    address: Option<String>,
+) -> Result<()> {
+    tracing::info!("Disconnecting from BLE device {address:?}");
+    let handler = command_handler().await?;
+    match address {
+        Some(address) => handler.disconnect_device(&address).await?,
+        None => handler.disconnect(flush.unwrap_or(false)).await?,
+    }
     Ok(())
 }
 
@@ -75,7 +241,7 @@ pub(crate) async fn connection_state<R: Runtime>(
     _app: AppHandle<R>,
     update: Channel<bool>,
 ) -> Result<()> {
-    let handler = get_handler()?;
+    let handler = command_handler().await?;
     let (tx, mut rx) = tokio::sync::mpsc::channel(1);
     handler.set_connection_update_channel(tx).await;
     update
@@ -96,7 +262,7 @@ pub(crate) async fn scanning_state<R: Runtime>(
     _app: AppHandle<R>,
     update: Channel<bool>,
 ) -> Result<()> {
-    let handler = get_handler()?;
+    let handler = command_handler().await?;
     let (tx, mut rx) = tokio::sync::mpsc::channel(1);
     handler.set_scanning_update_channel(tx).await;
     update
@@ -115,27 +281,166 @@ pub(crate) async fn scanning_state<R: Runtime>(
 #[command]
 pub(crate) async fn send<R: Runtime>(
     _app: AppHandle<R>,
-    characteristic: Uuid,
+    characteristic: CharacteristicRef,
     data: Vec<u8>,
     write_type: WriteType,
 ) -> Result<()> {
-    info!("Sending data: {data:?}");
-    let handler = get_handler()?;
+    info!("Sending data: {}", Payload(&data));
+    let handler = command_handler().await?;
     handler.send_data(characteristic, &data, write_type).await?;
     Ok(())
 }
 
 #[command]
-pub(crate) async fn recv<R: Runtime>(_app: AppHandle<R>, characteristic: Uuid) -> Result<Vec<u8>> {
-    let handler = get_handler()?;
+pub(crate) async fn send_with_response<R: Runtime>(
+    _app: AppHandle<R>,
+    characteristic: CharacteristicRef,
+    data: Vec<u8>,
+    correlation_id: Option<String>,
+) -> std::result::Result<Correlated<WriteResponse>, CorrelatedError> {
+    let correlation_id = self::correlation_id(correlation_id);
+    info!("Sending data with response ({correlation_id}): {}", Payload(&data));
+    let response = async {
+        command_handler()
+            .await?
+            .send_data_with_response(characteristic, &data)
+            .await
+    }
+    .await
+    .map_err(|e| e.correlated(&correlation_id))?;
+    Ok(Correlated {
+        correlation_id,
+        data: response,
+    })
+}
+
+#[command]
+pub(crate) async fn wait_for_notification<R: Runtime>(
+    _app: AppHandle<R>,
+    characteristic: CharacteristicRef,
+    prefix: Vec<u8>,
+    timeout_ms: u64,
+    correlation_id: Option<String>,
+) -> std::result::Result<Correlated<Vec<u8>>, CorrelatedError> {
+    let correlation_id = self::correlation_id(correlation_id);
+    let notification = async {
+        command_handler()
+            .await?
+            .wait_for_notification(
+                characteristic,
+                |frame| frame.starts_with(&prefix),
+                timeout_ms,
+            )
+            .await
+    }
+    .await
+    .map_err(|e| e.correlated(&correlation_id))?;
+    Ok(Correlated {
+        correlation_id,
+        data: notification,
+    })
+}
+
+#[command]
+pub(crate) async fn verify_subscriptions<R: Runtime>(
+    _app: AppHandle<R>,
+    address: Option<String>,
+) -> Result<SubscriptionReport> {
+    let handler = command_handler().await?;
+    match address {
+        Some(address) => handler.verify_device_subscriptions(&address).await,
+        None => handler.verify_subscriptions().await,
+    }
+}
+
+#[command]
+pub(crate) async fn set_notification_silence_timeout<R: Runtime>(
+    _app: AppHandle<R>,
+    timeout_ms: Option<u64>,
+) -> Result<()> {
+    let handler = command_handler().await?;
+    handler.set_notification_silence_timeout(timeout_ms.map(Duration::from_millis));
+    Ok(())
+}
+
+#[command]
+pub(crate) async fn recv<R: Runtime>(
+    _app: AppHandle<R>,
+    characteristic: CharacteristicRef,
+) -> Result<Vec<u8>> {
+    let handler = command_handler().await?;
     let data = handler.recv_data(characteristic).await?;
     Ok(data)
 }
 
+#[command]
+pub(crate) async fn descriptors<R: Runtime>(
+    _app: AppHandle<R>,
+    characteristic: CharacteristicRef,
+) -> Result<Vec<Uuid>> {
+    let handler = command_handler().await?;
+    handler.descriptors(characteristic).await
+}
+
+#[command]
+pub(crate) async fn charac_properties<R: Runtime>(
+    _app: AppHandle<R>,
+    characteristic: CharacteristicRef,
+) -> Result<CharacteristicProperties> {
+    let handler = command_handler().await?;
+    handler.charac_properties(characteristic).await
+}
+
+#[command]
+pub(crate) async fn read_descriptor<R: Runtime>(
+    _app: AppHandle<R>,
+    characteristic: CharacteristicRef,
+    descriptor: Uuid,
+) -> Result<Vec<u8>> {
+    let handler = command_handler().await?;
+    handler.read_descriptor(characteristic, descriptor).await
+}
+
+#[command]
+pub(crate) async fn write_descriptor<R: Runtime>(
+    _app: AppHandle<R>,
+    characteristic: CharacteristicRef,
+    descriptor: Uuid,
+    data: Vec<u8>,
+) -> Result<()> {
+    info!("Writing descriptor {descriptor}: {}", Payload(&data));
+    let handler = command_handler().await?;
+    handler
+        .write_descriptor(characteristic, descriptor, &data)
+        .await
+}
+
+#[command]
+pub(crate) async fn set_characteristic_options<R: Runtime>(
+    _app: AppHandle<R>,
+    characteristic: Uuid,
+    options: CharacteristicOptions,
+) -> Result<()> {
+    let handler = command_handler().await?;
+    handler
+        .set_characteristic_options(characteristic, options)
+        .await;
+    Ok(())
+}
+
+#[command]
+pub(crate) async fn recv_from_all<R: Runtime>(
+    _app: AppHandle<R>,
+    characteristic: CharacteristicRef,
+) -> Result<HashMap<String, TimedRead>> {
+    let handler = command_handler().await?;
+    Ok(handler.recv_from_all(characteristic).await)
+}
+
 #[command]
 pub(crate) async fn send_string<R: Runtime>(
     app: AppHandle<R>,
-    characteristic: Uuid,
+    characteristic: CharacteristicRef,
     data: String,
     write_type: WriteType,
 ) -> Result<()> {
@@ -146,70 +451,1050 @@ pub(crate) async fn send_string<R: Runtime>(
 #[command]
 pub(crate) async fn recv_string<R: Runtime>(
     app: AppHandle<R>,
-    characteristic: Uuid,
+    characteristic: CharacteristicRef,
 ) -> Result<String> {
     let data = recv(app, characteristic).await?;
     Ok(String::from_utf8(data).expect("failed to convert data to string"))
 }
 
-async fn subscribe_channel(characteristic: Uuid) -> Result<mpsc::Receiver<Vec<u8>>> {
-    let handler = get_handler()?;
-    let (tx, rx) = tokio::sync::mpsc::channel(1);
-    handler
-        .subscribe(characteristic, move |data| {
-            info!("subscribe_channel: {:?}", data);
-            tx.try_send(data.to_vec())
-                .expect("failed to send data to the channel");
-        })
+/// Listeners, sessions and scan consumers opened by a window, removed when the window is
+/// destroyed
+#[derive(Default)]
+struct WindowScope {
+    listeners: Vec<ListenerHandle>,
+    sessions: Vec<u64>,
+    scan_consumers: Vec<ScanConsumerId>,
+}
+
+/// Releases the listeners, sessions and scan consumers of a destroyed window
+type Releasing = Pin<Box<dyn Future<Output = ()> + Send>>;
+type ReleaseScope = Box<dyn Fn(String, WindowScope) -> Releasing + Send + Sync>;
+
+/// Scopes of the windows by label, managed by the plugin
+pub(crate) struct WindowScopes {
+    scopes: std::sync::Mutex<HashMap<String, WindowScope>>,
+    release: ReleaseScope,
+}
+
+impl WindowScopes {
+    /// Releases the scopes on the resources, `None` while the handler is not initialized
+    pub(crate) fn new<W: WindowResources + Sync + 'static>(
+        resources: impl Fn() -> Option<&'static W> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            scopes: std::sync::Mutex::new(HashMap::new()),
+            release: Box::new(move |label: String, scope: WindowScope| -> Releasing {
+                let resources = resources();
+                Box::pin(async move {
+                    if let Some(resources) = resources {
+                        release_scope(&label, scope, resources).await;
+                    }
+                })
+            }),
+        }
+    }
+
+    fn scopes(&self) -> std::sync::MutexGuard<'_, HashMap<String, WindowScope>> {
+        self.scopes.lock().expect("window scopes lock poisoned")
+    }
+
+    fn scope(&self, label: &str, add: impl FnOnce(&mut WindowScope)) {
+        add(self.scopes().entry(label.to_string()).or_default());
+    }
+
+    /// Removes the listener once the window is destroyed, see [`WindowScopes::detach`]
+    fn add_listener(&self, label: &str, id: ListenerHandle) {
+        self.scope(label, |scope| scope.listeners.push(id));
+    }
+
+    fn add_session(&self, label: &str, id: u64) {
+        self.scope(label, |scope| scope.sessions.push(id));
+    }
+
+    fn add_scan_consumer(&self, label: &str, id: ScanConsumerId) {
+        self.scope(label, |scope| scope.scan_consumers.push(id));
+    }
+
+    /// Stops tracking a listener removed by the frontend or whose channel closed
+    fn forget_listener(&self, id: ListenerHandle) {
+        for scope in self.scopes().values_mut() {
+            scope.listeners.retain(|l| *l != id);
+        }
+    }
+
+    fn forget_session(&self, id: u64) {
+        for scope in self.scopes().values_mut() {
+            scope.sessions.retain(|s| *s != id);
+        }
+    }
+
+    fn forget_scan_consumer(&self, id: ScanConsumerId) {
+        for scope in self.scopes().values_mut() {
+            scope.scan_consumers.retain(|c| *c != id);
+        }
+    }
+
+    /// Removes all listeners, sessions and scan consumers opened by the window
+    /// Returns the task releasing them, `None` if the window opened nothing.
+    pub(crate) fn detach(&self, label: &str) -> Option<async_runtime::JoinHandle<()>> {
+        let scope = self.scopes().remove(label)?;
+        info!("window {label} destroyed, detaching its subscriptions");
+        Some(async_runtime::spawn((self.release)(label.to_string(), scope)))
+    }
+}
+
+/// What a window can open, implemented by [`Handler`] and mocked in the tests
+pub(crate) trait WindowResources {
+    fn remove_listener(&self, id: ListenerHandle) -> impl Future<Output = Result<()>> + Send;
+    fn close_session(&self, id: u64) -> impl Future<Output = Result<()>> + Send;
+    fn unregister_scan_consumer(
+        &'static self,
+        id: ScanConsumerId,
+    ) -> impl Future<Output = Result<()>> + Send;
+}
+
+impl WindowResources for Handler {
+    async fn remove_listener(&self, id: ListenerHandle) -> Result<()> {
+        Handler::remove_listener(self, id).await
+    }
+
+    async fn close_session(&self, id: u64) -> Result<()> {
+        Handler::close_session(self, id).await
+    }
+
+    async fn unregister_scan_consumer(&'static self, id: ScanConsumerId) -> Result<()> {
+        Handler::unregister_scan_consumer(self, id).await
+    }
+}
+
+async fn release_scope(
+    label: &str,
+    scope: WindowScope,
+    resources: &'static impl WindowResources,
+) {
+    for id in scope.listeners {
+        if let Err(e) = resources.remove_listener(id).await {
+            tracing::warn!("failed to remove listener of window {label}: {e}");
+        }
+    }
+    for id in scope.sessions {
+        // sessions already closed by the frontend or a disconnect are unknown
+        let _ = resources.close_session(id).await;
+    }
+    for id in scope.scan_consumers {
+        // consumers whose channel closed already unregistered themselves
+        let _ = resources.unregister_scan_consumer(id).await;
+    }
+}
+
+/// Notifications buffered for the frontend before new ones are dropped
+const SUBSCRIBE_QUEUE: usize = 16;
+
+async fn subscribe_channel<R: Runtime>(
+    window: &Window<R>,
+    characteristic: CharacteristicRef,
+) -> Result<(mpsc::Receiver<Vec<u8>>, ListenerHandle)> {
+    let handler = command_handler().await?;
+    let (id, rx) = handler
+        .subscribe_receiver(characteristic, SUBSCRIBE_QUEUE)
         .await?;
-    Ok(rx)
+    window.state::<WindowScopes>().add_listener(window.label(), id);
+    Ok((rx, id))
 }
+
+/// Removes a listener whose frontend channel is gone, nobody unsubscribes it anymore
+async fn drop_listener<R: Runtime>(window: &Window<R>, id: ListenerHandle) {
+    if let Ok(handler) = command_handler().await {
+        if let Err(e) = handler.remove_listener(id).await {
+            tracing::warn!("failed to remove listener of a closed channel: {e}");
+        }
+    }
+    window.state::<WindowScopes>().forget_listener(id);
+}
+
 #[command]
 pub(crate) async fn subscribe<R: Runtime>(
     _app: AppHandle<R>,
-    characteristic: Uuid,
-    on_data: Channel<Vec<u8>>,
-) -> Result<()> {
-    let mut rx = subscribe_channel(characteristic).await?;
+    window: Window<R>,
+    characteristic: CharacteristicRef,
+    on_data: Channel<tauri::ipc::Response>,
+    wire_format: Option<WireFormat>,
+) -> Result<ListenerHandle> {
+    let wire_format = wire_format.unwrap_or_default();
+    let (mut rx, id) = subscribe_channel(&window, characteristic).await?;
     async_runtime::spawn(async move {
         while let Some(data) = rx.recv().await {
-            on_data
-                .send(data)
-                .expect("failed to send data to the front-end");
+            if on_data.send(wire_format.notification(data)).is_err() {
+                drop_listener(&window, id).await;
+                break;
+            }
         }
     });
-    Ok(())
+    Ok(id)
 }
 
 #[command]
 pub(crate) async fn subscribe_string<R: Runtime>(
     _app: AppHandle<R>,
-    characteristic: Uuid,
+    window: Window<R>,
+    characteristic: CharacteristicRef,
     on_data: Channel<String>,
-) -> Result<()> {
-    let mut rx = subscribe_channel(characteristic).await?;
+) -> Result<ListenerHandle> {
+    let (mut rx, id) = subscribe_channel(&window, characteristic).await?;
     async_runtime::spawn(async move {
         while let Some(data) = rx.recv().await {
-            info!("subscribe_string: {:?}", data);
-            let data = String::from_utf8(data).expect("failed to convert data to string");
-            on_data
-                .send(data)
-                .expect("failed to send data to the front-end");
+            info!("subscribe_string: {}", Payload(&data));
+            let data = String::from_utf8_lossy(&data).into_owned();
+            if on_data.send(data).is_err() {
+                drop_listener(&window, id).await;
+                break;
+            }
+        }
+    });
+    Ok(id)
+}
+
+#[command]
+pub(crate) async fn subscribe_timestamped<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    characteristic: CharacteristicRef,
+    on_data: Channel<TimedNotification>,
+) -> Result<ListenerHandle> {
+    let handler = command_handler().await?;
+    let (id, mut rx) = handler
+        .subscribe_timestamped(characteristic, SUBSCRIBE_QUEUE)
+        .await?;
+    window.state::<WindowScopes>().add_listener(window.label(), id);
+    async_runtime::spawn(async move {
+        while let Some(notification) = rx.recv().await {
+            if on_data.send(notification).is_err() {
+                drop_listener(&window, id).await;
+                break;
+            }
         }
     });
+    Ok(id)
+}
+
+#[command]
+pub(crate) async fn set_time_offset<R: Runtime>(
+    _app: AppHandle<R>,
+    address: String,
+    micros: i64,
+) -> Result<()> {
+    let handler = command_handler().await?;
+    handler.set_time_offset(&address, micros)?;
     Ok(())
 }
 
+#[command]
+pub(crate) async fn clock_sync<R: Runtime>(
+    _app: AppHandle<R>,
+    address: String,
+) -> Result<Option<ClockSync>> {
+    let handler = command_handler().await?;
+    Ok(handler.clock_sync(&address))
+}
+
 #[command]
 pub(crate) async fn unsubscribe<R: Runtime>(
     _app: AppHandle<R>,
-    characteristic: Uuid,
+    characteristic: CharacteristicRef,
 ) -> Result<()> {
-    let handler = get_handler()?;
+    let handler = command_handler().await?;
     handler.unsubscribe(characteristic).await?;
     Ok(())
 }
 
+#[command]
+pub(crate) async fn remove_listener<R: Runtime>(
+    app: AppHandle<R>,
+    handle: ListenerHandle,
+) -> Result<()> {
+    let handler = command_handler().await?;
+    handler.remove_listener(handle).await?;
+    app.state::<WindowScopes>().forget_listener(handle);
+    Ok(())
+}
+
+#[command]
+pub(crate) async fn open_session<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    characteristics: Vec<CharacteristicRef>,
+    on_frame: Channel<tauri::ipc::Response>,
+    wire_format: Option<WireFormat>,
+) -> Result<u64> {
+    let wire_format = wire_format.unwrap_or_default();
+    let handler = command_handler().await?;
+    let mut session = handler.open_session(characteristics).await?;
+    let id = session.id();
+    window.state::<WindowScopes>().add_session(window.label(), id);
+    async_runtime::spawn(async move {
+        while let Some(frame) = session.recv().await {
+            if on_frame.send(wire_format.frame(frame)).is_err() {
+                // the frontend is gone, nobody closes the session anymore
+                let _ = handler.close_session(id).await;
+                window.state::<WindowScopes>().forget_session(id);
+                break;
+            }
+        }
+    });
+    Ok(id)
+}
+
+#[command]
+pub(crate) async fn close_session<R: Runtime>(app: AppHandle<R>, id: u64) -> Result<()> {
+    let handler = command_handler().await?;
+    handler.close_session(id).await?;
+    app.state::<WindowScopes>().forget_session(id);
+    Ok(())
+}
+
+/// Captures started with [`start_capture`] by id, managed by the plugin
+#[derive(Default)]
+pub(crate) struct Captures(tokio::sync::Mutex<HashMap<u64, CaptureHandle>>);
+
+#[command]
+pub(crate) async fn start_capture<R: Runtime>(
+    app: AppHandle<R>,
+    characteristics: Vec<CharacteristicRef>,
+    path: PathBuf,
+    format: CaptureFormat,
+    rotate_mb: Option<u64>,
+    on_progress: Channel<CaptureStats>,
+) -> Result<u64> {
+    let handler = command_handler().await?;
+    let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+    let capture = handler
+        .start_capture(characteristics, path, format, rotate_mb, Some(tx))
+        .await?;
+    let id = capture.id();
+    app.state::<Captures>().0.lock().await.insert(id, capture);
+    async_runtime::spawn(async move {
+        while let Some(stats) = rx.recv().await {
+            let _ = on_progress.send(stats);
+        }
+        // the writer ended without stop_capture, after a write error
+        let failed = app.state::<Captures>().0.lock().await.remove(&id);
+        if let Some(capture) = failed {
+            if let Err(e) = handler.stop_capture(capture).await {
+                tracing::warn!("capture {id} failed: {e}");
+            }
+        }
+    });
+    Ok(id)
+}
+
+#[command]
+pub(crate) async fn stop_capture<R: Runtime>(app: AppHandle<R>, id: u64) -> Result<CaptureStats> {
+    let handler = command_handler().await?;
+    let capture = app
+        .state::<Captures>()
+        .0
+        .lock()
+        .await
+        .remove(&id)
+        .ok_or(Error::UnknownCapture(id))?;
+    handler.stop_capture(capture).await
+}
+
+fn decode_base64(data: &str) -> Result<Vec<u8>> {
+    BASE64
+        .decode(data)
+        .map_err(|e| Error::InvalidPayload(e.to_string()))
+}
+
+/// [`crate::MultiResponse`] with the frames encoded as base64
+#[derive(serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS), ts(rename = "MultiResponse"))]
+pub(crate) struct MultiResponseBase64 {
+    /// Base64 encoded frames in arrival order
+    frames: Vec<String>,
+    /// True if the end marker or `max_frames` was reached before the timeout
+    complete: bool,
+}
+
+#[allow(clippy::too_many_arguments)]
+#[command]
+pub(crate) async fn request_multi<R: Runtime>(
+    _app: AppHandle<R>,
+    characteristic: CharacteristicRef,
+    data: String,
+    write_type: WriteType,
+    response_characteristic: CharacteristicRef,
+    end_marker: Option<String>,
+    max_frames: Option<usize>,
+    timeout_ms: u64,
+    tee: bool,
+    total_timeout_ms: Option<u64>,
+    correlation_id: Option<String>,
+) -> std::result::Result<Correlated<MultiResponseBase64>, CorrelatedError> {
+    let correlation_id = self::correlation_id(correlation_id);
+    let response = async {
+        let handler = command_handler().await?;
+        let data = decode_base64(&data)?;
+        let end_marker = end_marker.as_deref().map(decode_base64).transpose()?;
+        let options = MultiResponseOptions {
+            max_frames,
+            timeout: Duration::from_millis(timeout_ms),
+            tee,
+            total_timeout: total_timeout_ms.map(Duration::from_millis),
+        };
+        handler
+            .request_multi(
+                characteristic,
+                &data,
+                write_type,
+                response_characteristic,
+                |frame| end_marker.as_ref().is_some_and(|m| frame.ends_with(m)),
+                options,
+            )
+            .await
+    }
+    .await
+    .map_err(|e| e.correlated(&correlation_id))?;
+    Ok(Correlated {
+        correlation_id,
+        data: MultiResponseBase64 {
+            frames: response.frames.iter().map(|f| BASE64.encode(f)).collect(),
+            complete: response.complete,
+        },
+    })
+}
+
+#[command]
+pub(crate) async fn get_state<R: Runtime>(_app: AppHandle<R>) -> Result<BleState> {
+    let handler = command_handler().await?;
+    Ok(handler.get_state().await)
+}
+
+#[command]
+pub(crate) async fn set_connection_limit<R: Runtime>(
+    _app: AppHandle<R>,
+    max: usize,
+    policy: ConnectionLimitPolicy,
+) -> Result<()> {
+    let handler = command_handler().await?;
+    handler.set_connection_limit(max, policy).await;
+    Ok(())
+}
+
+#[cfg(feature = "conformance")]
+#[command]
+pub(crate) async fn run_conformance_check<R: Runtime>(
+    _app: AppHandle<R>,
+    options: crate::conformance::ConformanceOptions,
+) -> Result<crate::conformance::ConformanceReport> {
+    let handler = command_handler().await?;
+    Ok(handler.run_conformance_check(options).await)
+}
+
+#[cfg(not(feature = "conformance"))]
+#[command]
+pub(crate) async fn run_conformance_check<R: Runtime>(
+    _app: AppHandle<R>,
+    options: serde_json::Value,
+) -> Result<()> {
+    let _ = options;
+    Err(Error::FeatureNotEnabled("conformance"))
+}
+
+#[command]
+pub(crate) async fn set_keep_alive<R: Runtime>(
+    _app: AppHandle<R>,
+    interval_ms: u64,
+    method: KeepAliveMethod,
+) -> Result<()> {
+    let handler = command_handler().await?;
+    handler
+        .set_keep_alive(Duration::from_millis(interval_ms), method)
+        .await
+}
+
+#[command]
+pub(crate) async fn clear_keep_alive<R: Runtime>(_app: AppHandle<R>) -> Result<()> {
+    let handler = command_handler().await?;
+    handler.clear_keep_alive().await;
+    Ok(())
+}
+
+#[command]
+pub(crate) async fn set_duplicate_filter<R: Runtime>(
+    _app: AppHandle<R>,
+    filter: DuplicateFilter,
+) -> Result<()> {
+    let handler = command_handler().await?;
+    handler.set_duplicate_filter(filter).await;
+    Ok(())
+}
+
+#[command]
+pub(crate) async fn scan_capabilities<R: Runtime>(_app: AppHandle<R>) -> Result<ScanCapabilities> {
+    let handler = command_handler().await?;
+    Ok(handler.scan_capabilities())
+}
+
+#[command]
+pub(crate) async fn set_fingerprint_rule<R: Runtime>(
+    _app: AppHandle<R>,
+    rule: Option<FingerprintRule>,
+) -> Result<()> {
+    let handler = command_handler().await?;
+    match rule {
+        Some(rule) => handler.set_fingerprint_rule(rule).await,
+        None => handler.clear_device_fingerprint().await,
+    }
+    Ok(())
+}
+
+#[command]
+pub(crate) async fn get_metrics<R: Runtime>(_app: AppHandle<R>) -> Result<BleMetrics> {
+    let handler = command_handler().await?;
+    Ok(handler.get_metrics())
+}
+
+#[command]
+pub(crate) async fn reset_metrics<R: Runtime>(_app: AppHandle<R>) -> Result<()> {
+    let handler = command_handler().await?;
+    handler.reset_metrics();
+    Ok(())
+}
+
+#[command]
+pub(crate) async fn set_metrics_interval<R: Runtime>(
+    _app: AppHandle<R>,
+    interval_ms: Option<u64>,
+) -> Result<()> {
+    let handler = command_handler().await?;
+    handler
+        .set_metrics_interval(interval_ms.map(Duration::from_millis))
+        .await;
+    Ok(())
+}
+
+#[command]
+pub(crate) async fn has_critical_operation<R: Runtime>(_app: AppHandle<R>) -> Result<bool> {
+    let handler = command_handler().await?;
+    Ok(handler.has_critical_operation())
+}
+
+#[command]
+pub(crate) async fn begin_critical_operation<R: Runtime>(
+    _app: AppHandle<R>,
+    name: String,
+) -> Result<u64> {
+    let handler = command_handler().await?;
+    Ok(handler.start_critical_operation(&name))
+}
+
+#[command]
+pub(crate) async fn end_critical_operation<R: Runtime>(_app: AppHandle<R>, id: u64) -> Result<()> {
+    let handler = command_handler().await?;
+    handler.end_critical_operation(id);
+    Ok(())
+}
+
+#[command]
+pub(crate) async fn set_exit_guard<R: Runtime>(
+    _app: AppHandle<R>,
+    timeout_ms: Option<u64>,
+) -> Result<()> {
+    let handler = command_handler().await?;
+    handler.set_exit_guard(timeout_ms.map(Duration::from_millis));
+    Ok(())
+}
+
+#[command]
+pub(crate) async fn device_info<R: Runtime>(_app: AppHandle<R>) -> Result<DeviceInfo> {
+    let handler = command_handler().await?;
+    handler.device_info().await
+}
+
+#[command]
+pub(crate) async fn read_pnp_id<R: Runtime>(_app: AppHandle<R>) -> Result<PnpId> {
+    let handler = command_handler().await?;
+    handler.read_pnp_id().await
+}
+
+#[command]
+pub(crate) async fn set_removal_debounce<R: Runtime>(
+    _app: AppHandle<R>,
+    debounce_ms: u64,
+) -> Result<()> {
+    let handler = command_handler().await?;
+    handler
+        .set_removal_debounce(Duration::from_millis(debounce_ms))
+        .await;
+    Ok(())
+}
+
+#[command]
+pub(crate) async fn health<R: Runtime>(_app: AppHandle<R>) -> Result<Health> {
+    let handler = command_handler().await?;
+    Ok(handler.health())
+}
+
+#[command]
+pub(crate) async fn set_heartbeat_interval<R: Runtime>(
+    _app: AppHandle<R>,
+    interval_ms: Option<u64>,
+) -> Result<()> {
+    let handler = command_handler().await?;
+    handler.set_heartbeat_interval(interval_ms.map(Duration::from_millis));
+    Ok(())
+}
+
+#[command]
+pub(crate) async fn get_mtu<R: Runtime>(_app: AppHandle<R>) -> Result<u16> {
+    let handler = command_handler().await?;
+    handler.get_mtu()
+}
+
+#[command]
+pub(crate) async fn request_mtu<R: Runtime>(_app: AppHandle<R>, mtu: u16) -> Result<u16> {
+    let handler = command_handler().await?;
+    handler.request_mtu(mtu).await
+}
+
+#[command]
+pub(crate) async fn max_write_len<R: Runtime>(
+    _app: AppHandle<R>,
+    write_type: Option<WriteType>,
+) -> Result<Option<usize>> {
+    let handler = command_handler().await?;
+    Ok(match write_type {
+        Some(write_type) => handler.max_write_len_for(write_type),
+        None => handler.max_write_len(),
+    })
+}
+
+#[command]
+pub(crate) async fn connection_capabilities<R: Runtime>(
+    _app: AppHandle<R>,
+) -> Result<ConnectionCapabilities> {
+    let handler = command_handler().await?;
+    Ok(handler.connection_capabilities())
+}
+
+#[command]
+pub(crate) async fn connected_services<R: Runtime>(_app: AppHandle<R>) -> Result<Vec<Service>> {
+    let handler = command_handler().await?;
+    Ok(handler.services().await.into_iter().map(Service::from).collect())
+}
+
+#[command]
+pub(crate) async fn get_services<R: Runtime>(_app: AppHandle<R>) -> Result<Vec<ServiceInfo>> {
+    let handler = command_handler().await?;
+    handler.discover_all_services().await
+}
+
+#[command]
+pub(crate) async fn set_retry_policy<R: Runtime>(
+    _app: AppHandle<R>,
+    policy: RetryPolicy,
+) -> Result<()> {
+    let handler = command_handler().await?;
+    handler.set_retry_policy(policy);
+    Ok(())
+}
+
+#[command]
+pub(crate) async fn set_address_policy<R: Runtime>(
+    _app: AppHandle<R>,
+    policy: AddressPolicy,
+) -> Result<()> {
+    let handler = command_handler().await?;
+    handler.set_address_policy(policy);
+    Ok(())
+}
+
+#[command]
+pub(crate) async fn is_known_device<R: Runtime>(
+    _app: AppHandle<R>,
+    address: String,
+) -> Result<KnownDevice> {
+    let handler = command_handler().await?;
+    Ok(handler.is_known_device(&address).await)
+}
+
+#[command]
+pub(crate) async fn forget_device<R: Runtime>(
+    _app: AppHandle<R>,
+    address: String,
+) -> Result<ForgetReport> {
+    let handler = command_handler().await?;
+    handler.forget_device(&address).await
+}
+
+#[command]
+pub(crate) async fn device_candidates<R: Runtime>(
+    _app: AppHandle<R>,
+    address: String,
+) -> Result<Vec<DeviceCandidate>> {
+    let handler = command_handler().await?;
+    handler.device_candidates(&address).await
+}
+
+#[command]
+pub(crate) async fn transfer<R: Runtime>(
+    _app: AppHandle<R>,
+    characteristic: CharacteristicRef,
+    data: Vec<u8>,
+    options: Option<TransferOptions>,
+    correlation_id: Option<String>,
+) -> std::result::Result<Correlated<TransferReport>, CorrelatedError> {
+    let correlation_id = self::correlation_id(correlation_id);
+    let report = async {
+        let handler = command_handler().await?;
+        handler
+            .transfer(characteristic, data, options.unwrap_or_default())
+            .await
+    }
+    .await
+    .map_err(|e| e.correlated(&correlation_id))?;
+    Ok(Correlated {
+        correlation_id,
+        data: report,
+    })
+}
+
+#[command]
+pub(crate) async fn send_chunked<R: Runtime>(
+    _app: AppHandle<R>,
+    characteristic: CharacteristicRef,
+    data: Vec<u8>,
+    chunk_size: Option<usize>,
+    write_type: WriteType,
+    correlation_id: Option<String>,
+) -> std::result::Result<String, CorrelatedError> {
+    let correlation_id = self::correlation_id(correlation_id);
+    info!("Sending chunked data ({correlation_id}): {}", Payload(&data));
+    async {
+        let handler = command_handler().await?;
+        handler
+            .send_data_chunked(characteristic, &data, chunk_size, write_type)
+            .await
+    }
+    .await
+    .map_err(|e| e.correlated(&correlation_id))?;
+    Ok(correlation_id)
+}
+
+#[command]
+pub(crate) async fn resume_transfer<R: Runtime>(
+    _app: AppHandle<R>,
+    id: u64,
+    correlation_id: Option<String>,
+) -> std::result::Result<Correlated<TransferReport>, CorrelatedError> {
+    let correlation_id = self::correlation_id(correlation_id);
+    let report = async { command_handler().await?.resume_transfer(id).await }
+        .await
+        .map_err(|e| e.correlated(&correlation_id))?;
+    Ok(Correlated {
+        correlation_id,
+        data: report,
+    })
+}
+
+#[command]
+pub(crate) async fn cancel_transfer<R: Runtime>(_app: AppHandle<R>, id: u64) -> Result<bool> {
+    let handler = command_handler().await?;
+    Ok(handler.cancel_transfer(id))
+}
+
+#[command]
+pub(crate) async fn clear_previously_connected<R: Runtime>(_app: AppHandle<R>) -> Result<()> {
+    let handler = command_handler().await?;
+    handler.clear_previously_connected();
+    Ok(())
+}
+
+#[command]
+pub(crate) async fn set_multi_adapter_scan<R: Runtime>(
+    _app: AppHandle<R>,
+    enabled: bool,
+) -> Result<Vec<String>> {
+    let handler = command_handler().await?;
+    handler.set_multi_adapter_scan(enabled).await
+}
+
+#[command]
+pub(crate) async fn wait_for_device<R: Runtime>(
+    _app: AppHandle<R>,
+    address: String,
+    timeout_ms: u64,
+    correlation_id: Option<String>,
+) -> std::result::Result<Correlated<BleDevice>, CorrelatedError> {
+    let correlation_id = self::correlation_id(correlation_id);
+    let device = async {
+        command_handler()
+            .await?
+            .wait_for_device(&address, timeout_ms)
+            .await
+    }
+    .await
+    .map_err(|e| e.correlated(&correlation_id))?;
+    Ok(Correlated {
+        correlation_id,
+        data: device,
+    })
+}
+
+#[command]
+pub(crate) async fn reconnect_when_available<R: Runtime>(
+    _app: AppHandle<R>,
+    address: String,
+    timeout_ms: u64,
+    on_disconnect: Channel<String>,
+    options: Option<ConnectOptions>,
+    correlation_id: Option<String>,
+) -> std::result::Result<Correlated<ConnectResult>, CorrelatedError> {
+    let correlation_id = self::correlation_id(correlation_id);
+    tracing::info!(
+        "Reconnecting to BLE device {address:?} once it is available ({correlation_id})"
+    );
+    let disconnected = address.clone();
+    let disconnect_handler = move || {
+        on_disconnect
+            .send(disconnected.clone())
+            .expect("failed to send disconnect event to the front-end");
+    };
+    let result = async {
+        command_handler()
+            .await?
+            .reconnect_when_available(
+                &address,
+                timeout_ms,
+                Some(Box::new(disconnect_handler)),
+                options.unwrap_or_default(),
+            )
+            .await
+    }
+    .await
+    .map_err(|e| e.correlated(&correlation_id))?;
+    Ok(Correlated {
+        correlation_id,
+        data: result,
+    })
+}
+
+#[command]
+pub(crate) async fn cancel_wait_for_device<R: Runtime>(
+    _app: AppHandle<R>,
+    address: String,
+) -> Result<bool> {
+    let handler = command_handler().await?;
+    Ok(handler.cancel_wait_for_device(&address))
+}
+
+/// Resolves a path of the frontend inside the app data directory, creating its parent
+/// Only plain relative paths are accepted, so the frontend can not write anywhere else.
+fn app_data_file<R: Runtime>(app: &AppHandle<R>, path: &Path) -> Result<PathBuf> {
+    let relative = path.file_name().is_some()
+        && path.components().all(|c| matches!(c, Component::Normal(_)));
+    if !relative {
+        return Err(Error::InvalidExportPath(path.display().to_string()));
+    }
+    let file = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| std::io::Error::other(e.to_string()))?
+        .join(path);
+    if let Some(parent) = file.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    Ok(file)
+}
+
+#[command]
+pub(crate) async fn export_session_timeline<R: Runtime>(
+    app: AppHandle<R>,
+    address: String,
+    path: Option<PathBuf>,
+) -> Result<SessionTimeline> {
+    let handler = command_handler().await?;
+    let path = path.map(|path| app_data_file(&app, &path)).transpose()?;
+    handler.export_session_timeline(&address, path.as_deref())
+}
+
+#[command]
+pub(crate) async fn set_timeline_payloads<R: Runtime>(
+    _app: AppHandle<R>,
+    enabled: bool,
+) -> Result<()> {
+    let handler = command_handler().await?;
+    handler.set_timeline_payloads(enabled);
+    Ok(())
+}
+
+#[command]
+pub(crate) async fn authorization_status<R: Runtime>(
+    _app: AppHandle<R>,
+) -> Result<AuthorizationStatus> {
+    Ok(AuthorizationStatus::current())
+}
+
+#[command]
+pub(crate) async fn adapter_state<R: Runtime>(_app: AppHandle<R>) -> Result<AdapterState> {
+    let handler = command_handler().await?;
+    Ok(handler.adapter_state())
+}
+
+#[command]
+pub(crate) async fn write_batch<R: Runtime>(
+    _app: AppHandle<R>,
+    items: Vec<BatchWrite>,
+    stop_on_error: bool,
+    on_progress: Channel<Correlated<BatchProgress>>,
+    correlation_id: Option<String>,
+) -> std::result::Result<Correlated<BatchReport>, CorrelatedError> {
+    let correlation_id = self::correlation_id(correlation_id);
+    let handler = command_handler()
+        .await
+        .map_err(|e| e.correlated(&correlation_id))?;
+    let (tx, mut rx) = mpsc::channel(16);
+    let id = correlation_id.clone();
+    async_runtime::spawn(async move {
+        while let Some(progress) = rx.recv().await {
+            let progress = Correlated {
+                correlation_id: id.clone(),
+                data: progress,
+            };
+            if let Err(e) = on_progress.send(progress) {
+                tracing::warn!("failed to send batch progress to the front-end: {e}");
+            }
+        }
+    });
+    let report = handler.write_batch(items, stop_on_error, Some(tx)).await;
+    Ok(Correlated {
+        correlation_id,
+        data: report,
+    })
+}
+
+#[command]
+pub(crate) async fn connect_additional<R: Runtime>(
+    _app: AppHandle<R>,
+    address: String,
+    correlation_id: Option<String>,
+) -> std::result::Result<Correlated<Vec<Service>>, CorrelatedError> {
+    let correlation_id = self::correlation_id(correlation_id);
+    tracing::info!("Connecting to additional BLE device: {address:?} ({correlation_id})");
+    let services = async { command_handler().await?.connect_additional(&address).await }
+        .await
+        .map_err(|e| e.correlated(&correlation_id))?;
+    Ok(Correlated {
+        correlation_id,
+        data: services,
+    })
+}
+
+#[command]
+pub(crate) async fn connected_addresses<R: Runtime>(_app: AppHandle<R>) -> Result<Vec<String>> {
+    let handler = command_handler().await?;
+    Ok(handler.connected_addresses())
+}
+
+#[command]
+pub(crate) async fn pair<R: Runtime>(
+    _app: AppHandle<R>,
+    address: String,
+    correlation_id: Option<String>,
+) -> std::result::Result<String, CorrelatedError> {
+    let correlation_id = self::correlation_id(correlation_id);
+    async { command_handler().await?.pair(&address).await }
+        .await
+        .map_err(|e| e.correlated(&correlation_id))?;
+    Ok(correlation_id)
+}
+
+#[command]
+pub(crate) async fn is_paired<R: Runtime>(_app: AppHandle<R>, address: String) -> Result<bool> {
+    let handler = command_handler().await?;
+    handler.is_paired(&address).await
+}
+
+#[command]
+pub(crate) async fn battery_level<R: Runtime>(_app: AppHandle<R>) -> Result<u8> {
+    let handler = command_handler().await?;
+    handler.battery_level().await
+}
+
+#[command]
+pub(crate) async fn start_battery_monitor<R: Runtime>(
+    _app: AppHandle<R>,
+    poll_fallback_interval_ms: u64,
+) -> Result<()> {
+    let handler = command_handler().await?;
+    handler.start_battery_monitor(Duration::from_millis(poll_fallback_interval_ms));
+    Ok(())
+}
+
+#[command]
+pub(crate) async fn stop_battery_monitor<R: Runtime>(_app: AppHandle<R>) -> Result<()> {
+    let handler = command_handler().await?;
+    handler.stop_battery_monitor().await
+}
+
+#[command]
+pub(crate) async fn list_adapters<R: Runtime>(_app: AppHandle<R>) -> Result<Vec<AdapterInfo>> {
+    crate::handler::list_adapters().await
+}
+
+#[command]
+pub(crate) async fn selected_adapter<R: Runtime>(_app: AppHandle<R>) -> Result<AdapterInfo> {
+    let handler = command_handler().await?;
+    Ok(handler.selected_adapter().await)
+}
+
+#[command]
+pub(crate) async fn select_adapter<R: Runtime>(
+    _app: AppHandle<R>,
+    adapter: AdapterSelector,
+) -> Result<AdapterInfo> {
+    let handler = command_handler().await?;
+    handler.select_adapter(&adapter).await
+}
+
+#[command]
+pub(crate) async fn accept_pairing_consent<R: Runtime>(
+    _app: AppHandle<R>,
+    address: String,
+    accept: bool,
+    pin: Option<String>,
+) -> Result<()> {
+    let handler = command_handler().await?;
+    handler.accept_pairing_consent(&address, accept, pin)?;
+    Ok(())
+}
+
+#[command]
+pub(crate) async fn set_pairing_auto_accept<R: Runtime>(
+    _app: AppHandle<R>,
+    enabled: bool,
+) -> Result<()> {
+    let handler = command_handler().await?;
+    handler.set_pairing_auto_accept(enabled)?;
+    Ok(())
+}
+
+#[command]
+pub(crate) async fn set_payload_logging<R: Runtime>(
+    _app: AppHandle<R>,
+    policy: PayloadLogging,
+) -> Result<()> {
+    set_policy(policy);
+    Ok(())
+}
+
+#[command]
+pub(crate) async fn ensure_initialized<R: Runtime>(_app: AppHandle<R>) -> Result<()> {
+    crate::ensure_initialized().await?;
+    Ok(())
+}
+
 pub fn commands<R: Runtime>() -> impl Fn(tauri::ipc::Invoke<R>) -> bool {
     tauri::generate_handler![
         scan,
@@ -224,6 +1509,200 @@ pub fn commands<R: Runtime>() -> impl Fn(tauri::ipc::Invoke<R>) -> bool {
         subscribe,
         subscribe_string,
         unsubscribe,
-        scanning_state
+        scanning_state,
+        open_session,
+        close_session,
+        start_capture,
+        stop_capture,
+        request_multi,
+        get_state,
+        set_connection_limit,
+        run_conformance_check,
+        set_keep_alive,
+        clear_keep_alive,
+        set_duplicate_filter,
+        scan_capabilities,
+        set_fingerprint_rule,
+        recv_from_all,
+        set_characteristic_options,
+        get_metrics,
+        reset_metrics,
+        set_metrics_interval,
+        has_critical_operation,
+        begin_critical_operation,
+        end_critical_operation,
+        set_exit_guard,
+        device_info,
+        read_pnp_id,
+        set_removal_debounce,
+        health,
+        set_heartbeat_interval,
+        get_mtu,
+        connection_capabilities,
+        connected_services,
+        remove_listener,
+        set_retry_policy,
+        is_known_device,
+        transfer,
+        resume_transfer,
+        cancel_transfer,
+        clear_previously_connected,
+        max_write_len,
+        set_multi_adapter_scan,
+        wait_for_device,
+        reconnect_when_available,
+        cancel_wait_for_device,
+        export_session_timeline,
+        set_timeline_payloads,
+        ensure_initialized,
+        authorization_status,
+        write_batch,
+        connect_additional,
+        connected_addresses,
+        accept_pairing_consent,
+        set_pairing_auto_accept,
+        set_payload_logging,
+        request_mtu,
+        send_chunked,
+        descriptors,
+        read_descriptor,
+        write_descriptor,
+        pair,
+        is_paired,
+        list_adapters,
+        selected_adapter,
+        select_adapter,
+        battery_level,
+        start_battery_monitor,
+        stop_battery_monitor,
+        adapter_state,
+        start_discovery,
+        stop_discovery,
+        register_scan_consumer,
+        unregister_scan_consumer,
+        send_with_response,
+        set_address_policy,
+        get_services,
+        charac_properties,
+        forget_device,
+        subscribe_timestamped,
+        set_time_offset,
+        clock_sync,
+        device_candidates,
+        wait_for_notification,
+        verify_subscriptions,
+        set_notification_silence_timeout
     ]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use tauri::test::{mock_builder, mock_context, noop_assets};
+    use tauri::{WebviewWindow, WebviewWindowBuilder, WindowEvent};
+
+    use crate::plugin::on_window_event;
+
+    /// Listeners and sessions of the windows, removing a listener ends its stream like the
+    /// handler does by dropping the sender
+    #[derive(Default)]
+    struct MockResources {
+        streams: Mutex<HashMap<ListenerHandle, mpsc::Sender<Vec<u8>>>>,
+        closed_sessions: Mutex<Vec<u64>>,
+    }
+
+    impl MockResources {
+        /// Subscribes for the window, forwarding like [`subscribe`] and counting the
+        /// notifications until the stream ends
+        fn listen<R: Runtime>(
+            &self,
+            window: &WebviewWindow<R>,
+            id: ListenerHandle,
+        ) -> async_runtime::JoinHandle<usize> {
+            let (tx, mut rx) = mpsc::channel(SUBSCRIBE_QUEUE);
+            self.streams.lock().unwrap().insert(id, tx);
+            window.state::<WindowScopes>().add_listener(window.label(), id);
+            async_runtime::spawn(async move {
+                let mut forwarded = 0;
+                while rx.recv().await.is_some() {
+                    forwarded += 1;
+                }
+                forwarded
+            })
+        }
+
+        /// Listeners not removed yet
+        fn listeners(&self) -> usize {
+            self.streams.lock().unwrap().len()
+        }
+
+        /// Delivers a notification to the listener, false once it was removed
+        async fn notify(&self, id: ListenerHandle, data: &[u8]) -> bool {
+            let tx = self.streams.lock().unwrap().get(&id).cloned();
+            match tx {
+                Some(tx) => tx.send(data.to_vec()).await.is_ok(),
+                None => false,
+            }
+        }
+    }
+
+    impl WindowResources for MockResources {
+        async fn remove_listener(&self, id: ListenerHandle) -> Result<()> {
+            let removed = self.streams.lock().unwrap().remove(&id);
+            removed.map(drop).ok_or(Error::NoListener(format!("{id:?}")))
+        }
+
+        async fn close_session(&self, id: u64) -> Result<()> {
+            self.closed_sessions.lock().unwrap().push(id);
+            Ok(())
+        }
+
+        async fn unregister_scan_consumer(&'static self, _id: ScanConsumerId) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn destroying_a_window_mid_stream_ends_only_its_streams() {
+        let resources: &'static MockResources = Box::leak(Box::default());
+        let app = mock_builder()
+            .manage(WindowScopes::new(move || Some(resources)))
+            .build(mock_context(noop_assets()))
+            .unwrap();
+        let closing = WebviewWindowBuilder::new(&app, "closing", Default::default())
+            .build()
+            .unwrap();
+        let open = WebviewWindowBuilder::new(&app, "open", Default::default())
+            .build()
+            .unwrap();
+        async_runtime::block_on(async {
+            let closing_stream = resources.listen(&closing, ListenerHandle(1));
+            let open_stream = resources.listen(&open, ListenerHandle(2));
+            app.state::<WindowScopes>().add_session("closing", 7);
+            assert!(resources.notify(ListenerHandle(1), &[1]).await);
+            assert!(resources.notify(ListenerHandle(2), &[1]).await);
+            assert_eq!(resources.listeners(), 2);
+
+            closing.destroy().unwrap();
+            let detaching = on_window_event(app.handle(), "closing", &WindowEvent::Destroyed)
+                .expect("the window opened a listener and a session");
+            detaching.await.unwrap();
+            assert_eq!(resources.listeners(), 1);
+            assert!(!resources.notify(ListenerHandle(1), &[2]).await);
+            assert_eq!(closing_stream.await.unwrap(), 1);
+            assert_eq!(*resources.closed_sessions.lock().unwrap(), vec![7]);
+
+            // the other window keeps streaming, a repeated event finds nothing to detach
+            assert!(resources.notify(ListenerHandle(2), &[2]).await);
+            let destroyed = WindowEvent::Destroyed;
+            assert!(on_window_event(app.handle(), "closing", &destroyed).is_none());
+            assert!(on_window_event(app.handle(), "open", &WindowEvent::Focused(false)).is_none());
+            assert_eq!(resources.listeners(), 1);
+            let detaching = on_window_event(app.handle(), "open", &destroyed).unwrap();
+            detaching.await.unwrap();
+            assert_eq!(resources.listeners(), 0);
+            assert_eq!(open_stream.await.unwrap(), 2);
+        });
+    }
+}
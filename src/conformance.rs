@@ -0,0 +1,358 @@
+use std::time::{Duration, Instant};
+
+use tokio::sync::mpsc;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::error::Error;
+use crate::handler::{Handler, MultiResponseOptions};
+use crate::models::{ScanFilter, WriteType};
+
+/// Options for [`Handler::run_conformance_check`]
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct ConformanceOptions {
+    /// Device to connect to if no device is connected yet
+    #[cfg_attr(feature = "ts-export", ts(optional))]
+    pub address: Option<String>,
+    /// Service advertised by the device, used to check scan filtering
+    #[cfg_attr(feature = "ts-export", ts(optional))]
+    pub scan_service: Option<Uuid>,
+    /// Characteristic supporting read, write and notify for the round trip checks
+    #[cfg_attr(feature = "ts-export", ts(optional))]
+    pub characteristic: Option<Uuid>,
+}
+
+/// Outcome of a single check
+#[derive(Debug, Clone, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase", tag = "result", content = "reason")]
+pub enum CheckOutcome {
+    Passed,
+    Failed(String),
+    Unsupported(String),
+    Skipped(String),
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub outcome: CheckOutcome,
+    pub latency_ms: Option<f64>,
+}
+
+/// Report returned by [`Handler::run_conformance_check`]
+#[derive(Debug, Clone, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct ConformanceReport {
+    pub platform: &'static str,
+    pub checks: Vec<CheckResult>,
+}
+
+impl ConformanceReport {
+    fn push<T>(
+        &mut self,
+        name: &'static str,
+        start: Instant,
+        result: Result<T, Error>,
+    ) -> Option<T> {
+        let latency_ms = Some(start.elapsed().as_secs_f64() * 1000.0);
+        let (outcome, value) = match result {
+            Ok(v) => (CheckOutcome::Passed, Some(v)),
+            Err(Error::Btleplug(btleplug::Error::NotSupported(e))) => {
+                (CheckOutcome::Unsupported(e), None)
+            }
+            Err(e) => (CheckOutcome::Failed(e.to_string()), None),
+        };
+        info!("conformance check {name}: {outcome:?}");
+        self.checks.push(CheckResult {
+            name,
+            outcome,
+            latency_ms,
+        });
+        value
+    }
+
+    fn skip(&mut self, name: &'static str, reason: &str) {
+        self.checks.push(CheckResult {
+            name,
+            outcome: CheckOutcome::Skipped(reason.to_string()),
+            latency_ms: None,
+        });
+    }
+}
+
+/// Operations of the round trip checks on the connected device
+trait RoundTrip {
+    async fn read(&self, c: Uuid) -> Result<Vec<u8>, Error>;
+    async fn write(&self, c: Uuid, data: &[u8]) -> Result<(), Error>;
+    /// Writes `data` and waits for a notification of `c`
+    async fn notify(&self, c: Uuid, data: &[u8]) -> Result<(), Error>;
+}
+
+impl RoundTrip for Handler {
+    async fn read(&self, c: Uuid) -> Result<Vec<u8>, Error> {
+        self.recv_data(c).await
+    }
+
+    async fn write(&self, c: Uuid, data: &[u8]) -> Result<(), Error> {
+        self.send_data(c, data, WriteType::WithResponse).await
+    }
+
+    async fn notify(&self, c: Uuid, data: &[u8]) -> Result<(), Error> {
+        let options = MultiResponseOptions {
+            max_frames: Some(1),
+            timeout: Duration::from_secs(2),
+            ..Default::default()
+        };
+        let response = self
+            .request_multi(c, data, WriteType::WithResponse, c, |_| true, options)
+            .await?;
+        if response.complete {
+            Ok(())
+        } else {
+            Err(Error::ConformanceCheckFailed(
+                "no notification received".to_string(),
+            ))
+        }
+    }
+}
+
+/// Reads the characteristic and writes the value back, directly and with a notification
+/// The write checks need the value of the read, so they are skipped if it failed.
+async fn run_round_trips(target: &impl RoundTrip, c: Uuid, report: &mut ConformanceReport) {
+    let start = Instant::now();
+    let Some(value) = report.push("read", start, target.read(c).await) else {
+        for name in ["write", "notify"] {
+            report.skip(name, "the read failed");
+        }
+        return;
+    };
+    let start = Instant::now();
+    let result = target.write(c, &value).await;
+    report.push("write", start, result);
+    let start = Instant::now();
+    let result = target.notify(c, &value).await;
+    report.push("notify", start, result);
+}
+
+impl Handler {
+    /// Exercises the platform backend and reports which operations work as expected
+    /// Runs a filtered scan, connects if needed and does read, write and notify round trips on
+    /// the given characteristic, measuring the latency of every step. Checks that can not run
+    /// with the given options are reported as skipped, the write and notify checks also if the
+    /// read failed.
+    /// Only available with the `conformance` feature, which is meant for debug builds.
+    pub async fn run_conformance_check(
+        &'static self,
+        options: ConformanceOptions,
+    ) -> ConformanceReport {
+        let mut report = ConformanceReport {
+            platform: std::env::consts::OS,
+            checks: vec![],
+        };
+
+        if let Some(service) = options.scan_service {
+            let start = Instant::now();
+            let result = self.check_scan_filter(service).await;
+            report.push("scanFilter", start, result);
+        } else {
+            report.skip("scanFilter", "no scan service given");
+        }
+
+        if !self.is_connected() {
+            if let Some(address) = &options.address {
+                let start = Instant::now();
+                let result = self.connect(address, None).await;
+                report.push("connect", start, result);
+            }
+        }
+        if !self.is_connected() {
+            for name in ["mtu", "read", "write", "notify"] {
+                report.skip(name, "no device connected");
+            }
+            return report;
+        }
+
+        let outcome = match self.get_mtu() {
+            Ok(_) => CheckOutcome::Passed,
+            Err(Error::UnsupportedOnPlatform(_)) => {
+                CheckOutcome::Unsupported("the platform does not report the MTU".to_string())
+            }
+            Err(e) => CheckOutcome::Failed(e.to_string()),
+        };
+        report.checks.push(CheckResult {
+            name: "mtu",
+            outcome,
+            latency_ms: None,
+        });
+
+        let Some(c) = options.characteristic else {
+            for name in ["read", "write", "notify"] {
+                report.skip(name, "no characteristic given");
+            }
+            return report;
+        };
+        run_round_trips(self, c, &mut report).await;
+        report
+    }
+
+    async fn check_scan_filter(&'static self, service: Uuid) -> Result<(), Error> {
+        let (tx, mut rx) = mpsc::channel(1);
+        self.discover(Some(tx), 2000, ScanFilter::Service(service)).await?;
+        let mut found = false;
+        let mut violation = None;
+        // keep receiving until the scan ends, the scan task expects the receiver to stay alive
+        while let Some(devices) = rx.recv().await {
+            if let Some(dev) = devices.iter().find(|d| !d.services.contains(&service)) {
+                violation.get_or_insert_with(|| {
+                    format!("filter not applied, {} does not advertise {service}", dev.address)
+                });
+            }
+            found |= !devices.is_empty();
+        }
+        if let Some(violation) = violation {
+            return Err(Error::ConformanceCheckFailed(violation));
+        }
+        if found {
+            Ok(())
+        } else {
+            Err(Error::ConformanceCheckFailed(format!(
+                "no device advertising {service} found"
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    const CHARAC: Uuid = Uuid::from_u128(0x51ff_12bb_3ed8_46e5_b4f9_d64e_2fec_021b);
+
+    /// Device answering the round trips with fixed results, recording the written values
+    struct MockDevice {
+        read: fn() -> Result<Vec<u8>, Error>,
+        notifies: bool,
+        written: Mutex<Vec<Vec<u8>>>,
+    }
+
+    impl MockDevice {
+        fn new(read: fn() -> Result<Vec<u8>, Error>) -> Self {
+            Self {
+                read,
+                notifies: true,
+                written: Mutex::new(vec![]),
+            }
+        }
+    }
+
+    impl RoundTrip for MockDevice {
+        async fn read(&self, _c: Uuid) -> Result<Vec<u8>, Error> {
+            (self.read)()
+        }
+
+        async fn write(&self, _c: Uuid, data: &[u8]) -> Result<(), Error> {
+            self.written.lock().unwrap().push(data.to_vec());
+            Ok(())
+        }
+
+        async fn notify(&self, c: Uuid, data: &[u8]) -> Result<(), Error> {
+            self.write(c, data).await?;
+            if self.notifies {
+                Ok(())
+            } else {
+                Err(Error::ConformanceCheckFailed(
+                    "no notification received".to_string(),
+                ))
+            }
+        }
+    }
+
+    fn report() -> ConformanceReport {
+        ConformanceReport {
+            platform: "test",
+            checks: vec![],
+        }
+    }
+
+    fn outcomes(report: &ConformanceReport) -> Vec<(&'static str, CheckOutcome)> {
+        report
+            .checks
+            .iter()
+            .map(|c| (c.name, c.outcome.clone()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn writes_the_value_read_back() {
+        let device = MockDevice::new(|| Ok(vec![1, 2, 3]));
+        let mut report = report();
+        run_round_trips(&device, CHARAC, &mut report).await;
+        assert!(matches!(
+            outcomes(&report).as_slice(),
+            [
+                ("read", CheckOutcome::Passed),
+                ("write", CheckOutcome::Passed),
+                ("notify", CheckOutcome::Passed),
+            ]
+        ));
+        assert_eq!(*device.written.lock().unwrap(), vec![vec![1, 2, 3]; 2]);
+    }
+
+    #[tokio::test]
+    async fn failed_read_skips_the_writes() {
+        let device = MockDevice::new(|| Err(Error::CharacNotAvailable(CHARAC.to_string())));
+        let mut report = report();
+        run_round_trips(&device, CHARAC, &mut report).await;
+        assert!(matches!(
+            outcomes(&report).as_slice(),
+            [
+                ("read", CheckOutcome::Failed(_)),
+                ("write", CheckOutcome::Skipped(_)),
+                ("notify", CheckOutcome::Skipped(_)),
+            ]
+        ));
+        assert!(device.written.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn unsupported_read_skips_the_writes() {
+        let device = MockDevice::new(|| {
+            Err(Error::Btleplug(btleplug::Error::NotSupported(
+                "reading".to_string(),
+            )))
+        });
+        let mut report = report();
+        run_round_trips(&device, CHARAC, &mut report).await;
+        assert!(matches!(
+            outcomes(&report).as_slice(),
+            [
+                ("read", CheckOutcome::Unsupported(_)),
+                ("write", CheckOutcome::Skipped(_)),
+                ("notify", CheckOutcome::Skipped(_)),
+            ]
+        ));
+    }
+
+    #[tokio::test]
+    async fn missing_notification_fails_only_the_notify_check() {
+        let mut device = MockDevice::new(|| Ok(vec![]));
+        device.notifies = false;
+        let mut report = report();
+        run_round_trips(&device, CHARAC, &mut report).await;
+        assert!(matches!(
+            outcomes(&report).as_slice(),
+            [
+                ("read", CheckOutcome::Passed),
+                ("write", CheckOutcome::Passed),
+                ("notify", CheckOutcome::Failed(_)),
+            ]
+        ));
+    }
+}
@@ -0,0 +1,375 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, MutexGuard};
+use std::time::Instant;
+
+use btleplug::platform::PeripheralId;
+use tokio::sync::{watch, Mutex, Notify};
+use tracing::{debug, info};
+use uuid::Uuid;
+
+use crate::error::Error;
+use crate::handler::{
+    AutoReconnect, ConnectOptions, DisconnectGate, Handler, IdleReconnect, Interceptor, Listener,
+    Session, WriteLimits, ATT_WRITE_HEADER,
+};
+use crate::models::{self, Service};
+use crate::runtime;
+
+#[cfg(target_os = "android")]
+use crate::android::Peripheral;
+#[cfg(not(target_os = "android"))]
+use btleplug::platform::Peripheral;
+
+/// State of one connected device, see [`Handler::connect_with_options`]
+/// The device of [`Handler::connect`] is one of the connections as well, it is only marked as
+/// the main connection for the operations that take no address. The entry is kept while the
+/// device reconnects, so its listeners and pending reconnects survive the lost link.
+pub(crate) struct Connection {
+    pub(crate) address: String,
+    /// Connected device, held by every operation so operations on the device run one at a time
+    /// in the order they were issued
+    pub(crate) dev: Mutex<Option<Peripheral>>,
+    /// Clone of the connected device, used to force a disconnect while an operation holds `dev`
+    link: std::sync::Mutex<Option<Peripheral>>,
+    pub(crate) disconnecting: DisconnectGate,
+    connected_tx: watch::Sender<bool>,
+    pub(crate) connected_rx: watch::Receiver<bool>,
+    pub(crate) listeners: Arc<Mutex<Vec<Listener>>>,
+    pub(crate) sessions: Arc<Mutex<Vec<Session>>>,
+    pub(crate) interceptors: Arc<Mutex<Vec<Interceptor>>>,
+    /// Listeners of the lost link, subscribed again by the running automatic reconnect
+    pub(crate) kept_listeners: Mutex<Vec<Listener>>,
+    /// Time of the last operation on or notification from the device, keep-alive pings
+    /// excluded
+    pub(crate) last_activity: Arc<std::sync::Mutex<Instant>>,
+    /// MTU negotiated with [`crate::ConnectOptions::request_mtu`]
+    pub(crate) mtu: std::sync::Mutex<Option<u16>>,
+    /// Per write type limits the platform reported, queried once the connect finished
+    pub(crate) write_limits: std::sync::Mutex<Option<WriteLimits>>,
+    /// Characteristics whose [`crate::CharacteristicOptions::requires_write`] was met on this
+    /// link
+    pub(crate) required_writes_done: std::sync::Mutex<HashSet<Uuid>>,
+    /// Set while the idle timeout of [`crate::ConnectOptions::idle_disconnect_ms`] disconnects
+    pub(crate) idle_disconnecting: AtomicBool,
+    /// Pending connect of [`crate::ConnectOptions::reconnect_on_demand`]
+    pub(crate) idle_reconnect: std::sync::Mutex<Option<IdleReconnect>>,
+    /// Held while the pending connect runs, so concurrent operations wait for it
+    pub(crate) reconnecting: Mutex<()>,
+    /// Cancel signal of the running [`crate::ConnectOptions::reconnect`]
+    pub(crate) auto_reconnect: std::sync::Mutex<Option<Arc<Notify>>>,
+    pub(crate) tasks: Mutex<ConnectionTasks>,
+}
+
+/// Background tasks and callbacks of the link, ended when it disconnects
+#[derive(Default)]
+pub(crate) struct ConnectionTasks {
+    pub(crate) listen: Option<runtime::JoinHandle<()>>,
+    pub(crate) mtu: Option<runtime::JoinHandle<()>>,
+    pub(crate) keep_alive: Option<runtime::JoinHandle<()>>,
+    pub(crate) idle: Option<runtime::JoinHandle<()>>,
+    /// Checks the subscriptions of the device, see [`Handler::verify_subscriptions`]
+    pub(crate) interference: Option<runtime::JoinHandle<()>>,
+    pub(crate) on_disconnect: Option<Box<dyn Fn() + Send>>,
+    pub(crate) holds_slot: bool,
+    /// Set if the device is reconnected when its link is lost
+    pub(crate) auto_reconnect: Option<AutoReconnect>,
+}
+
+impl ConnectionTasks {
+    pub(crate) fn abort(&mut self) {
+        let tasks = [
+            self.listen.take(),
+            self.mtu.take(),
+            self.keep_alive.take(),
+            self.idle.take(),
+            self.interference.take(),
+        ];
+        for task in tasks.into_iter().flatten() {
+            task.abort();
+        }
+    }
+}
+
+impl Connection {
+    fn new(address: String) -> Self {
+        let (connected_tx, connected_rx) = watch::channel(false);
+        Self {
+            address,
+            dev: Mutex::new(None),
+            link: std::sync::Mutex::new(None),
+            disconnecting: DisconnectGate::default(),
+            connected_tx,
+            connected_rx,
+            listeners: Arc::new(Mutex::new(vec![])),
+            sessions: Arc::new(Mutex::new(vec![])),
+            interceptors: Arc::new(Mutex::new(vec![])),
+            kept_listeners: Mutex::new(vec![]),
+            last_activity: Arc::new(std::sync::Mutex::new(Instant::now())),
+            mtu: std::sync::Mutex::new(None),
+            write_limits: std::sync::Mutex::new(None),
+            required_writes_done: std::sync::Mutex::new(HashSet::new()),
+            idle_disconnecting: AtomicBool::new(false),
+            idle_reconnect: std::sync::Mutex::new(None),
+            reconnecting: Mutex::new(()),
+            auto_reconnect: std::sync::Mutex::new(None),
+            tasks: Mutex::new(ConnectionTasks::default()),
+        }
+    }
+
+    /// Returns true once the platform reported the link as established
+    pub(crate) fn is_connected(&self) -> bool {
+        *self.connected_rx.borrow()
+    }
+
+    pub(crate) fn set_connected(&self, connected: bool) {
+        self.connected_tx
+            .send(connected)
+            .expect("failed to send connected update");
+    }
+
+    /// Device of the link, without waiting for running operations
+    pub(crate) fn link(&self) -> Option<Peripheral> {
+        self.link.lock().expect("link lock poisoned").clone()
+    }
+
+    pub(crate) fn link_id(&self) -> Option<PeripheralId> {
+        self.link
+            .lock()
+            .expect("link lock poisoned")
+            .as_ref()
+            .map(btleplug::api::Peripheral::id)
+    }
+
+    /// Replaces the device of the link, the values negotiated for the previous link are reset
+    pub(crate) async fn set_link(&self, dev: Option<Peripheral>) {
+        *self.mtu.lock().expect("mtu lock poisoned") = None;
+        *self.write_limits.lock().expect("write limits lock poisoned") = None;
+        self.required_writes_done
+            .lock()
+            .expect("required writes lock poisoned")
+            .clear();
+        *self.link.lock().expect("link lock poisoned") = dev.clone();
+        *self.dev.lock().await = dev;
+    }
+
+    pub(crate) fn mark_activity(&self) {
+        *self.last_activity.lock().expect("activity lock poisoned") = Instant::now();
+    }
+
+    /// Returns the largest payload of a single write, see [`Handler::max_write_len`]
+    pub(crate) fn max_write_len(&self) -> Option<usize> {
+        self.mtu
+            .lock()
+            .expect("mtu lock poisoned")
+            .map(|mtu| usize::from(mtu).saturating_sub(ATT_WRITE_HEADER))
+    }
+
+    /// Returns the largest payload of a single write of the given type, see
+    /// [`Handler::max_write_len_for`]
+    pub(crate) fn max_write_len_for(&self, write_type: models::WriteType) -> Option<usize> {
+        let limits = *self.write_limits.lock().expect("write limits lock poisoned");
+        limits
+            .and_then(|limits| match write_type {
+                models::WriteType::WithResponse => limits.with_response,
+                models::WriteType::WithoutResponse => limits.without_response,
+            })
+            .or_else(|| self.max_write_len())
+    }
+
+    pub(crate) fn write_limits(&self) -> WriteLimits {
+        WriteLimits {
+            with_response: self.max_write_len_for(models::WriteType::WithResponse),
+            without_response: self.max_write_len_for(models::WriteType::WithoutResponse),
+        }
+    }
+
+    /// Kept without a link while an automatic reconnect runs or an idle reconnect is pending
+    fn is_retained(&self) -> bool {
+        self.auto_reconnect
+            .lock()
+            .expect("auto reconnect lock poisoned")
+            .is_some()
+            || self
+                .idle_reconnect
+                .lock()
+                .expect("idle reconnect lock poisoned")
+                .is_some()
+    }
+
+    /// Cancels the running automatic reconnect of the connection
+    /// Returns false if no reconnect was running.
+    pub(crate) fn cancel_auto_reconnect(&self) -> bool {
+        let running = self
+            .auto_reconnect
+            .lock()
+            .expect("auto reconnect lock poisoned")
+            .take();
+        let Some(cancel) = running else {
+            return false;
+        };
+        info!("cancelling the reconnect to {}", self.address);
+        // also stored if the reconnect is not waiting right now
+        cancel.notify_one();
+        true
+    }
+
+    /// Returns false once the reconnect was cancelled, ends the reconnect if `finished`
+    pub(crate) fn reconnect_running(&self, cancel: &Arc<Notify>, finished: bool) -> bool {
+        let mut running = self
+            .auto_reconnect
+            .lock()
+            .expect("auto reconnect lock poisoned");
+        let current = running.as_ref().is_some_and(|c| Arc::ptr_eq(c, cancel));
+        if current && finished {
+            running.take();
+        }
+        current
+    }
+}
+
+impl Handler {
+    /// Connects to another device while keeping the current connections
+    /// The device is connected like [`Handler::connect_with_options`] with the default options,
+    /// disconnecting one device does not affect the others. Every connection counts towards
+    /// [`Handler::set_connection_limit`].
+    /// Data operations reach the device through the `address` of the [`CharacteristicRef`],
+    /// operations without an address keep going to the only connected device and fail with
+    /// [`Error::AmbiguousConnection`] if several are connected.
+    /// # Errors
+    /// Returns [`Error::AlreadyConnected`] if the device is connected and the errors of
+    /// [`Handler::connect_with_options`]
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// use uuid::{Uuid,uuid};
+    /// use tauri_plugin_blec::models::CharacteristicRef;
+    /// const CHARACTERISTIC_UUID: Uuid = uuid!("51FF12BB-3ED8-46E5-B4F9-D64E2FEC021B");
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     handler.connect_additional("00:00:00:00:00:01").await.unwrap();
+    ///     handler.connect_additional("00:00:00:00:00:02").await.unwrap();
+    ///     let right = CharacteristicRef {
+    ///         address: Some("00:00:00:00:00:02".to_string()),
+    ///         service: None,
+    ///         characteristic: CHARACTERISTIC_UUID,
+    ///     };
+    ///     let data = handler.recv_data(right).await.unwrap();
+    /// });
+    /// # }
+    /// ```
+    ///
+    /// [`CharacteristicRef`]: crate::models::CharacteristicRef
+    pub async fn connect_additional(&'static self, address: &str) -> Result<Vec<Service>, Error> {
+        let address = self.parse_address(address)?;
+        if self.connection(&address).is_some_and(|c| c.link().is_some()) {
+            return Err(Error::AlreadyConnected);
+        }
+        let result = self
+            .connect_with_options(&address, None, ConnectOptions::default())
+            .await?;
+        Ok(result.services)
+    }
+
+    /// Returns the addresses of all connected devices, the main connection first
+    pub fn connected_addresses(&self) -> Vec<String> {
+        let main = self.connected_address();
+        let mut additional: Vec<String> = self
+            .lock_connections()
+            .values()
+            .filter(|c| c.link().is_some() && Some(&c.address) != main.as_ref())
+            .map(|c| c.address.clone())
+            .collect();
+        additional.sort();
+        main.into_iter().chain(additional).collect()
+    }
+
+    /// Disconnects a single device, the other connections keep running
+    /// The device is disconnected like [`Handler::disconnect`] without flushing.
+    /// # Errors
+    /// Returns [`Error::NoDeviceConnected`] if the device is not connected or an error if the
+    /// disconnect fails
+    pub async fn disconnect_device(&self, address: &str) -> Result<(), Error> {
+        let address = self.parse_address(address)?;
+        let connection = self.connection(&address).ok_or(Error::NoDeviceConnected)?;
+        debug!("disconnecting {address}");
+        self.disconnect_connection(&connection, false).await
+    }
+
+    pub(crate) fn lock_connections(&self) -> MutexGuard<'_, HashMap<String, Arc<Connection>>> {
+        self.connections.lock().expect("connections lock poisoned")
+    }
+
+    /// All connections, also the ones waiting for a reconnect
+    pub(crate) fn all_connections(&self) -> Vec<Arc<Connection>> {
+        self.lock_connections().values().cloned().collect()
+    }
+
+    pub(crate) fn connection(&self, address: &str) -> Option<Arc<Connection>> {
+        self.lock_connections().get(address).cloned()
+    }
+
+    /// Connection of [`Handler::connect`], also while it connects or reconnects
+    pub(crate) fn main_connection(&self) -> Option<Arc<Connection>> {
+        self.connection(&self.main_address()?)
+    }
+
+    /// Returns the connection whose link is the peripheral
+    pub(crate) fn connection_of(&self, id: &PeripheralId) -> Option<Arc<Connection>> {
+        self.lock_connections()
+            .values()
+            .find(|c| c.link_id().as_ref() == Some(id))
+            .cloned()
+    }
+
+    /// Returns the connection an operation targets by the address of its
+    /// [`crate::models::CharacteristicRef`], the only connection if it has none
+    /// # Errors
+    /// Returns [`Error::AmbiguousConnection`] if there is no address and several devices are
+    /// connected, [`Error::NoDeviceConnected`] if the device is not connected
+    pub(crate) fn connection_for(&self, address: Option<&str>) -> Result<Arc<Connection>, Error> {
+        let connection = match address {
+            Some(address) => self.connection(&self.parse_address(address)?),
+            None => {
+                let connections = self.lock_connections();
+                if connections.len() > 1 {
+                    drop(connections);
+                    return Err(Error::AmbiguousConnection(self.connected_addresses()));
+                }
+                connections.values().next().cloned()
+            }
+        };
+        if let Some(connection) = connection {
+            return Ok(connection);
+        }
+        self.check_authorized()?;
+        self.check_powered()?;
+        Err(Error::NoDeviceConnected)
+    }
+
+    /// Returns the connection of the device, a new one if it has none yet
+    pub(crate) async fn connection_entry(&self, address: &str) -> Arc<Connection> {
+        // a connect does not start while the adapter is switched
+        let _switch = self.adapter_switch.lock().await;
+        self.lock_connections()
+            .entry(address.to_string())
+            .or_insert_with(|| Arc::new(Connection::new(address.to_string())))
+            .clone()
+    }
+
+    /// Removes the connection once it has no link and no reconnect is running or pending
+    pub(crate) fn drop_connection(&self, connection: &Arc<Connection>) {
+        if connection.link().is_some() || connection.is_retained() {
+            return;
+        }
+        let mut connections = self.lock_connections();
+        if connections
+            .get(&connection.address)
+            .is_some_and(|c| Arc::ptr_eq(c, connection))
+        {
+            debug!("dropping the connection to {}", connection.address);
+            connections.remove(&connection.address);
+        }
+    }
+}
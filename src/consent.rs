@@ -0,0 +1,324 @@
+use crate::error::Error;
+use crate::handler::Handler;
+
+/// Kind of confirmation the OS asks for in a [`PairingConsentRequest`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub enum PairingKind {
+    /// The user only confirms the pairing, can be accepted automatically with
+    /// [`Handler::set_pairing_auto_accept`]
+    ConfirmOnly,
+    /// The user enters the PIN shown in the request on the device
+    DisplayPin,
+    /// The PIN shown by the device is passed to [`Handler::accept_pairing_consent`]
+    ProvidePin,
+    /// The user confirms that the PIN matches the one shown by the device
+    ConfirmPinMatch,
+    Other,
+}
+
+/// Pairing the OS wants the user to confirm, published as
+/// [`crate::BleEvent::PairingConsentRequired`]
+/// Requested while connecting with `require_bonded`, on [`Handler::pair`], and on Windows also
+/// after an operation failed with [`Error::PairingRequired`]. Answer with
+/// [`Handler::accept_pairing_consent`] within 60 seconds, the pairing is cancelled otherwise.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct PairingConsentRequest {
+    pub address: String,
+    pub kind: PairingKind,
+    /// PIN to show to the user for [`PairingKind::DisplayPin`] and
+    /// [`PairingKind::ConfirmPinMatch`]
+    pub pin: Option<String>,
+}
+
+const UNSUPPORTED: &str = "pairing consent";
+
+impl Handler {
+    /// Accepts pairings that only need a confirmation without asking the user
+    /// Pairings asking for a PIN are still published as
+    /// [`crate::BleEvent::PairingConsentRequired`].
+    /// # Errors
+    /// Returns [`Error::UnsupportedOnPlatform`] on platforms other than Windows
+    pub fn set_pairing_auto_accept(&self, enabled: bool) -> Result<(), Error> {
+        #[cfg(target_os = "windows")]
+        {
+            self.pairing_consents.set_auto_accept(enabled);
+            Ok(())
+        }
+        #[cfg(not(target_os = "windows"))]
+        {
+            let _ = enabled;
+            Err(Error::UnsupportedOnPlatform(UNSUPPORTED))
+        }
+    }
+
+    /// Answers the pending [`PairingConsentRequest`] of the address
+    /// For [`PairingKind::ProvidePin`] the PIN shown by the device must be passed. Rejecting
+    /// fails the pairing with [`Error::PairingRejected`].
+    /// # Errors
+    /// Returns [`Error::NoPairingConsent`] if no request of the address is pending and
+    /// [`Error::UnsupportedOnPlatform`] on platforms other than Windows
+    pub fn accept_pairing_consent(
+        &self,
+        address: &str,
+        accept: bool,
+        pin: Option<String>,
+    ) -> Result<(), Error> {
+        #[cfg(target_os = "windows")]
+        {
+            self.pairing_consents.answer(address, accept, pin)
+        }
+        #[cfg(not(target_os = "windows"))]
+        {
+            let _ = (address, accept, pin);
+            Err(Error::UnsupportedOnPlatform(UNSUPPORTED))
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+pub(crate) mod windows_pairing {
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    use btleplug::api::BDAddr;
+    use tracing::{debug, info};
+    use windows::core::HSTRING;
+    use windows::Devices::Bluetooth::BluetoothLEDevice;
+    use windows::Devices::Enumeration::{
+        DevicePairingKinds, DevicePairingRequestedEventArgs, DevicePairingResult,
+        DevicePairingResultStatus, DeviceUnpairingResultStatus,
+    };
+    use windows::Foundation::{AsyncStatus, Deferral, IAsyncOperation, TypedEventHandler};
+
+    use super::{PairingConsentRequest, PairingKind};
+    use crate::error::Error;
+    use crate::events::{BleEvent, Publisher};
+    use crate::handler::{Handler, PairingState, BOND_TIMEOUT};
+    use crate::runtime;
+
+    /// How often a running pairing is checked against [`BOND_TIMEOUT`]
+    const PAIRING_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+    /// Consent state of the custom pairings, owned by the handler
+    #[derive(Default)]
+    pub(crate) struct PairingConsents {
+        /// Set with [`Handler::set_pairing_auto_accept`]
+        auto_accept: AtomicBool,
+        /// Requests waiting for [`Handler::accept_pairing_consent`], by address
+        pending: Mutex<HashMap<String, PendingConsent>>,
+    }
+
+    struct PendingConsent {
+        args: DevicePairingRequestedEventArgs,
+        /// Keeps the pairing open until the request is answered
+        deferral: Deferral,
+    }
+
+    impl From<DevicePairingKinds> for PairingKind {
+        fn from(kind: DevicePairingKinds) -> Self {
+            match kind {
+                DevicePairingKinds::ConfirmOnly => Self::ConfirmOnly,
+                DevicePairingKinds::DisplayPin => Self::DisplayPin,
+                DevicePairingKinds::ProvidePin => Self::ProvidePin,
+                DevicePairingKinds::ConfirmPinMatch => Self::ConfirmPinMatch,
+                _ => Self::Other,
+            }
+        }
+    }
+
+    fn winrt_error(e: &windows::core::Error) -> Error {
+        Error::PairingFailed(e.message().to_string())
+    }
+
+    impl PairingConsents {
+        pub(crate) fn set_auto_accept(&self, enabled: bool) {
+            self.auto_accept.store(enabled, Ordering::Release);
+        }
+
+        pub(crate) fn answer(
+            &self,
+            address: &str,
+            accept: bool,
+            pin: Option<String>,
+        ) -> Result<(), Error> {
+            let pending = self
+                .pending()
+                .remove(address)
+                .ok_or_else(|| Error::NoPairingConsent(address.to_string()))?;
+            debug!("answering pairing consent of {address}: {accept}");
+            // completing the deferral without accepting rejects the pairing
+            let accepted = match (accept, pin) {
+                (false, _) => Ok(()),
+                (true, Some(pin)) => pending.args.AcceptWithPin(&HSTRING::from(pin)),
+                (true, None) => pending.args.Accept(),
+            };
+            let completed = pending.deferral.Complete();
+            accepted.map_err(|e| winrt_error(&e))?;
+            completed.map_err(|e| winrt_error(&e))
+        }
+
+        fn pending(&self) -> std::sync::MutexGuard<'_, HashMap<String, PendingConsent>> {
+            self.pending
+                .lock()
+                .expect("pending pairing consents lock poisoned")
+        }
+    }
+
+    /// Returns whether Windows has a pairing for the address
+    pub(crate) async fn is_paired(address: &str) -> Result<bool, Error> {
+        let bdaddr: BDAddr = address
+            .parse()
+            .map_err(|_| Error::UnknownPeripheral(address.to_string()))?;
+        runtime::spawn_blocking(move || {
+            let device = BluetoothLEDevice::FromBluetoothAddressAsync(u64::from(bdaddr))?.get()?;
+            device.DeviceInformation()?.Pairing()?.IsPaired()
+        })
+        .await
+        .map_err(|e| Error::PairingFailed(e.to_string()))?
+        .map_err(|e| winrt_error(&e))
+    }
+
+    /// Removes the Windows pairing of the address, returns false if there was none
+    pub(crate) async fn unpair(address: &str) -> Result<bool, Error> {
+        let bdaddr: BDAddr = address
+            .parse()
+            .map_err(|_| Error::UnknownPeripheral(address.to_string()))?;
+        let status = runtime::spawn_blocking(move || {
+            let device = BluetoothLEDevice::FromBluetoothAddressAsync(u64::from(bdaddr))?.get()?;
+            device.DeviceInformation()?.Pairing()?.UnpairAsync()?.get()?.Status()
+        })
+        .await
+        .map_err(|e| Error::RemoveBondFailed(e.to_string()))?
+        .map_err(|e| Error::RemoveBondFailed(e.message().to_string()))?;
+        match status {
+            DeviceUnpairingResultStatus::Unpaired => Ok(true),
+            DeviceUnpairingResultStatus::AlreadyUnpaired => Ok(false),
+            status => Err(Error::RemoveBondFailed(format!("{status:?}"))),
+        }
+    }
+
+    /// Waits for the pairing, it is cancelled if it did not finish within [`BOND_TIMEOUT`]
+    /// Returns `None` if it was cancelled.
+    fn wait_for_pairing(
+        op: &IAsyncOperation<DevicePairingResult>,
+    ) -> windows::core::Result<Option<DevicePairingResult>> {
+        let deadline = Instant::now() + BOND_TIMEOUT;
+        while op.Status()? == AsyncStatus::Started {
+            if Instant::now() >= deadline {
+                op.Cancel()?;
+                return Ok(None);
+            }
+            std::thread::sleep(PAIRING_POLL_INTERVAL);
+        }
+        op.GetResults().map(Some)
+    }
+
+    /// Pairs with the custom pairing API, so consent prompts reach the app instead of a
+    /// hidden system dialog
+    /// Returns `None` if the pairing did not finish within [`BOND_TIMEOUT`].
+    pub(crate) fn pair_blocking(
+        address: String,
+        bdaddr: u64,
+        publisher: Publisher,
+        consents: Arc<PairingConsents>,
+    ) -> windows::core::Result<Option<DevicePairingResultStatus>> {
+        let device = BluetoothLEDevice::FromBluetoothAddressAsync(bdaddr)?.get()?;
+        let pairing = device.DeviceInformation()?.Pairing()?;
+        if pairing.IsPaired()? {
+            return Ok(Some(DevicePairingResultStatus::AlreadyPaired));
+        }
+        let custom = pairing.Custom()?;
+        let requested_address = address.clone();
+        let requested = consents.clone();
+        let on_requested = TypedEventHandler::new(
+            move |_, args: &Option<DevicePairingRequestedEventArgs>| {
+                let Some(args) = args.as_ref() else {
+                    return Ok(());
+                };
+                let kind = args.PairingKind()?;
+                let auto_accept = requested.auto_accept.load(Ordering::Acquire);
+                if kind == DevicePairingKinds::ConfirmOnly && auto_accept {
+                    debug!("accepting confirm only pairing of {requested_address}");
+                    return args.Accept();
+                }
+                let pin = args
+                    .Pin()
+                    .ok()
+                    .map(|pin| pin.to_string())
+                    .filter(|pin| !pin.is_empty());
+                let deferral = args.GetDeferral()?;
+                requested.pending().insert(
+                    requested_address.clone(),
+                    PendingConsent {
+                        args: args.clone(),
+                        deferral,
+                    },
+                );
+                info!("pairing with {requested_address} needs consent: {kind:?}");
+                publisher.publish(BleEvent::PairingConsentRequired(PairingConsentRequest {
+                    address: requested_address.clone(),
+                    kind: kind.into(),
+                    pin,
+                }));
+                Ok(())
+            },
+        );
+        let token = custom.PairingRequested(&on_requested)?;
+        let kinds = DevicePairingKinds::ConfirmOnly
+            | DevicePairingKinds::DisplayPin
+            | DevicePairingKinds::ProvidePin
+            | DevicePairingKinds::ConfirmPinMatch;
+        let result = custom.PairAsync(kinds).and_then(|op| wait_for_pairing(&op));
+        let _ = custom.RemovePairingRequested(token);
+        consents.pending().remove(&address);
+        result?.map(|result| result.Status()).transpose()
+    }
+
+    impl Handler {
+        /// Pairs with the device unless it is already paired, publishing consent requests
+        /// # Errors
+        /// Returns [`Error::PairingRejected`] if the consent was rejected and
+        /// [`Error::PairingFailed`] for other failures
+        pub(crate) async fn pair_with_consent(&self, address: &str) -> Result<(), Error> {
+            let bdaddr: BDAddr = address
+                .parse()
+                .map_err(|_| Error::UnknownPeripheral(address.to_string()))?;
+            self.send_pairing_event(address, PairingState::Started).await;
+            let publisher = self.publisher().clone();
+            let consents = self.pairing_consents.clone();
+            let owned = address.to_string();
+            let status = runtime::spawn_blocking(move || {
+                pair_blocking(owned, u64::from(bdaddr), publisher, consents)
+            })
+            .await
+            .map_err(|e| Error::PairingFailed(e.to_string()))?
+            .map_err(|e| winrt_error(&e));
+            let error = match status {
+                Ok(Some(
+                    DevicePairingResultStatus::Paired | DevicePairingResultStatus::AlreadyPaired,
+                )) => {
+                    self.set_bond_state(address, true);
+                    self.send_pairing_event(address, PairingState::Bonded).await;
+                    return Ok(());
+                }
+                Ok(Some(
+                    status @ (DevicePairingResultStatus::RejectedByHandler
+                    | DevicePairingResultStatus::PairingCanceled),
+                )) => Error::PairingRejected(format!("{status:?}")),
+                Ok(Some(status)) => Error::PairingFailed(format!("{status:?}")),
+                Ok(None) => Error::PairingFailed(format!("not paired within {BOND_TIMEOUT:?}")),
+                Err(e) => e,
+            };
+            self.set_bond_state(address, false);
+            let reason = error.to_string();
+            self.send_pairing_event(address, PairingState::Failed { reason }).await;
+            Err(error)
+        }
+    }
+}
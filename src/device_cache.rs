@@ -0,0 +1,424 @@
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use btleplug::api::Peripheral as _;
+use tracing::debug;
+
+use crate::error::Error;
+
+#[cfg(target_os = "android")]
+use crate::android::Peripheral;
+#[cfg(not(target_os = "android"))]
+use btleplug::platform::Peripheral;
+
+/// Identifies a cached handle, one address can be seen by several adapters or be reused by
+/// another device
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct DeviceKey {
+    /// `None` for the default adapter outside of multi adapter scans
+    adapter: Option<String>,
+    /// Platform id, see [`CachedHandle::id_string`]
+    id: String,
+}
+
+/// Handle kept by the [`DeviceCache`], the platform peripheral outside of tests
+pub(crate) trait CachedHandle {
+    /// Platform id as passed to [`crate::ConnectOptions::peripheral_id`]
+    fn id_string(&self) -> String;
+}
+
+impl CachedHandle for Peripheral {
+    fn id_string(&self) -> String {
+        let id = self.id();
+        match serde_json::to_value(&id) {
+            Ok(serde_json::Value::String(id)) => id,
+            _ => format!("{id:?}"),
+        }
+    }
+}
+
+/// One of the devices an address matches, see [`Error::AmbiguousAddress`]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceCandidate {
+    /// Adapter that saw the device, `None` for the default adapter outside of multi adapter
+    /// scans
+    pub adapter: Option<String>,
+    /// Platform id of the device, connect to this candidate by passing it as
+    /// [`crate::ConnectOptions::peripheral_id`]
+    pub peripheral_id: String,
+    /// Fingerprint from [`crate::Handler::set_device_fingerprint`], if one is set
+    pub fingerprint: Option<String>,
+    /// Milliseconds since the unix epoch when the device was last reported
+    #[cfg_attr(feature = "ts-export", ts(type = "number"))]
+    pub last_seen: u64,
+}
+
+struct Entry<P> {
+    address: String,
+    peripheral: P,
+    fingerprint: Option<String>,
+    /// Order of the updates, the highest is the most recently seen handle of an address
+    seq: u64,
+    seen_at: SystemTime,
+}
+
+/// Handles of the discovered devices by adapter and platform id, indexed by address
+/// Lookups by address use the most recently seen handle. Handles of different adapters are
+/// the same device seen by several radios unless their fingerprints differ, handles of the
+/// same adapter with different platform ids are different devices. A connect to an address
+/// that matches different devices fails, see [`DeviceCache::resolve`].
+pub(crate) struct DeviceCache<P = Peripheral> {
+    entries: HashMap<DeviceKey, Entry<P>>,
+    by_address: HashMap<String, Vec<DeviceKey>>,
+    next_seq: u64,
+}
+
+impl<P> Default for DeviceCache<P> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+            by_address: HashMap::new(),
+            next_seq: 0,
+        }
+    }
+}
+
+impl<P: CachedHandle> DeviceCache<P> {
+    /// Caches the handle as the most recently seen one of the address
+    pub(crate) fn insert(
+        &mut self,
+        adapter: Option<String>,
+        address: String,
+        peripheral: P,
+        fingerprint: Option<String>,
+    ) {
+        let key = DeviceKey {
+            adapter,
+            id: peripheral.id_string(),
+        };
+        let entry = Entry {
+            address: address.clone(),
+            peripheral,
+            fingerprint,
+            seq: self.next_seq,
+            seen_at: SystemTime::now(),
+        };
+        self.next_seq += 1;
+        if let Some(old) = self.entries.get(&key) {
+            if old.address != address {
+                let old_address = old.address.clone();
+                self.unindex(&old_address, &key);
+            } else if old.fingerprint.is_some()
+                && entry.fingerprint.is_some()
+                && old.fingerprint != entry.fingerprint
+            {
+                // the platform id follows the address, the previous device is replaced
+                debug!("address {address} is now used by a different device");
+            }
+        }
+        self.entries.insert(key.clone(), entry);
+        let keys = self.by_address.entry(address).or_default();
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+
+    /// Most recently seen handle of the address
+    pub(crate) fn get(&self, address: &str) -> Option<&P> {
+        self.latest(address).map(|e| &e.peripheral)
+    }
+
+    pub(crate) fn contains_key(&self, address: &str) -> bool {
+        self.by_address.contains_key(address)
+    }
+
+    /// Removes all handles of the address
+    pub(crate) fn remove(&mut self, address: &str) -> Vec<P> {
+        self.by_address
+            .remove(address)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|key| self.entries.remove(&key))
+            .map(|e| e.peripheral)
+            .collect()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+        self.by_address.clear();
+    }
+
+    /// Keeps the handles `keep` returns true for, returns the addresses left without a handle
+    pub(crate) fn retain(
+        &mut self,
+        mut keep: impl FnMut(&DeviceKey, &str, &P) -> bool,
+    ) -> Vec<String> {
+        let mut removed = vec![];
+        self.entries.retain(|key, e| {
+            let kept = keep(key, &e.address, &e.peripheral);
+            if !kept {
+                removed.push((key.clone(), e.address.clone()));
+            }
+            kept
+        });
+        let mut emptied = vec![];
+        for (key, address) in removed {
+            if self.unindex(&address, &key) {
+                emptied.push(address);
+            }
+        }
+        emptied
+    }
+
+    /// Devices the address matches, the most recently seen first
+    pub(crate) fn candidates(&self, address: &str) -> Vec<DeviceCandidate> {
+        let mut entries: Vec<(&DeviceKey, &Entry<P>)> = self.entries_of(address).collect();
+        entries.sort_by_key(|(_, e)| std::cmp::Reverse(e.seq));
+        entries
+            .into_iter()
+            .map(|(key, e)| DeviceCandidate {
+                adapter: key.adapter.clone(),
+                peripheral_id: key.id.clone(),
+                fingerprint: e.fingerprint.clone(),
+                last_seen: e
+                    .seen_at
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| u64::try_from(d.as_millis()).unwrap_or(u64::MAX))
+                    .unwrap_or_default(),
+            })
+            .collect()
+    }
+
+    /// Picks the handle a connect to the address uses
+    /// With `peripheral_id` the most recently seen handle with that platform id is used,
+    /// otherwise the most recently seen handle as long as all handles of the address are the
+    /// same device.
+    /// # Errors
+    /// Returns [`Error::AmbiguousAddress`] with all candidates if the address matches different
+    /// devices and [`Error::UnknownPeripheral`] if no handle has the platform id
+    pub(crate) fn resolve(
+        &mut self,
+        address: &str,
+        peripheral_id: Option<&str>,
+    ) -> Result<(), Error> {
+        let Some(peripheral_id) = peripheral_id else {
+            if self.is_ambiguous(address) {
+                return Err(Error::AmbiguousAddress {
+                    address: address.to_string(),
+                    candidates: self.candidates(address),
+                });
+            }
+            return Ok(());
+        };
+        let seq = self.next_seq;
+        let picked = self
+            .by_address
+            .get(address)
+            .into_iter()
+            .flatten()
+            .filter(|key| key.id == peripheral_id)
+            .filter_map(|key| self.entries.get(key).map(|e| (key.clone(), e.seq)))
+            .max_by_key(|(_, seq)| *seq)
+            .map(|(key, _)| key)
+            .ok_or_else(|| Error::UnknownPeripheral(peripheral_id.to_string()))?;
+        if let Some(e) = self.entries.get_mut(&picked) {
+            e.seq = seq;
+        }
+        self.next_seq += 1;
+        Ok(())
+    }
+
+    fn is_ambiguous(&self, address: &str) -> bool {
+        let entries: Vec<(&DeviceKey, &Entry<P>)> = self.entries_of(address).collect();
+        entries.iter().enumerate().any(|(i, (a, a_entry))| {
+            entries[i + 1..].iter().any(|(b, b_entry)| {
+                // keys are unique, the same adapter has two platform ids for the address
+                a.adapter == b.adapter
+                    || matches!(
+                        (&a_entry.fingerprint, &b_entry.fingerprint),
+                        (Some(x), Some(y)) if x != y
+                    )
+            })
+        })
+    }
+
+    fn latest(&self, address: &str) -> Option<&Entry<P>> {
+        self.entries_of(address)
+            .map(|(_, e)| e)
+            .max_by_key(|e| e.seq)
+    }
+
+    fn entries_of<'a>(
+        &'a self,
+        address: &str,
+    ) -> impl Iterator<Item = (&'a DeviceKey, &'a Entry<P>)> + 'a {
+        self.by_address
+            .get(address)
+            .into_iter()
+            .flatten()
+            .filter_map(|key| self.entries.get_key_value(key))
+    }
+
+    /// Returns true if the address has no handles left
+    fn unindex(&mut self, address: &str, key: &DeviceKey) -> bool {
+        let Some(keys) = self.by_address.get_mut(address) else {
+            return false;
+        };
+        keys.retain(|k| k != key);
+        if keys.is_empty() {
+            self.by_address.remove(address);
+            return true;
+        }
+        false
+    }
+}
+
+/// Candidates in the message of [`Error::AmbiguousAddress`]
+pub(crate) fn describe(candidates: &[DeviceCandidate]) -> String {
+    candidates
+        .iter()
+        .map(|c| match &c.adapter {
+            Some(adapter) => format!("{} on {adapter}", c.peripheral_id),
+            None => c.peripheral_id.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Handle with a fixed platform id
+    #[derive(Debug, PartialEq)]
+    struct Handle(&'static str);
+
+    impl CachedHandle for Handle {
+        fn id_string(&self) -> String {
+            self.0.to_string()
+        }
+    }
+
+    const ADDRESS: &str = "AA:BB:CC:DD:EE:FF";
+
+    fn insert(
+        cache: &mut DeviceCache<Handle>,
+        adapter: Option<&str>,
+        id: &'static str,
+        fingerprint: Option<&str>,
+    ) {
+        cache.insert(
+            adapter.map(str::to_string),
+            ADDRESS.to_string(),
+            Handle(id),
+            fingerprint.map(str::to_string),
+        );
+    }
+
+    fn candidate_ids(cache: &DeviceCache<Handle>) -> Vec<(Option<String>, String)> {
+        cache
+            .candidates(ADDRESS)
+            .into_iter()
+            .map(|c| (c.adapter, c.peripheral_id))
+            .collect()
+    }
+
+    #[test]
+    fn same_address_on_two_adapters_is_one_device() {
+        let mut cache = DeviceCache::default();
+        insert(&mut cache, Some("hci0"), "a", None);
+        insert(&mut cache, Some("hci1"), "b", None);
+        assert!(cache.resolve(ADDRESS, None).is_ok());
+        assert_eq!(cache.get(ADDRESS), Some(&Handle("b")));
+        assert_eq!(
+            candidate_ids(&cache),
+            [
+                (Some("hci1".to_string()), "b".to_string()),
+                (Some("hci0".to_string()), "a".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn most_recently_seen_handle_wins() {
+        let mut cache = DeviceCache::default();
+        insert(&mut cache, Some("hci0"), "a", None);
+        insert(&mut cache, Some("hci1"), "b", None);
+        insert(&mut cache, Some("hci0"), "a", None);
+        assert_eq!(cache.get(ADDRESS), Some(&Handle("a")));
+        assert_eq!(cache.candidates(ADDRESS).len(), 2);
+    }
+
+    #[test]
+    fn reused_address_with_different_fingerprints_is_ambiguous() {
+        let mut cache = DeviceCache::default();
+        insert(&mut cache, Some("hci0"), "a", Some("thermometer"));
+        insert(&mut cache, Some("hci1"), "b", Some("scale"));
+        let Err(Error::AmbiguousAddress {
+            address,
+            candidates,
+        }) = cache.resolve(ADDRESS, None)
+        else {
+            panic!("address with two fingerprints resolved");
+        };
+        assert_eq!(address, ADDRESS);
+        let fingerprints: Vec<_> = candidates.iter().map(|c| c.fingerprint.as_deref()).collect();
+        assert_eq!(fingerprints, [Some("scale"), Some("thermometer")]);
+    }
+
+    #[test]
+    fn two_platform_ids_on_one_adapter_are_ambiguous() {
+        let mut cache = DeviceCache::default();
+        insert(&mut cache, None, "a", None);
+        insert(&mut cache, None, "b", None);
+        assert!(matches!(
+            cache.resolve(ADDRESS, None),
+            Err(Error::AmbiguousAddress { .. })
+        ));
+    }
+
+    #[test]
+    fn peripheral_id_picks_one_of_the_candidates() {
+        let mut cache = DeviceCache::default();
+        insert(&mut cache, None, "a", None);
+        insert(&mut cache, None, "b", None);
+        assert!(cache.resolve(ADDRESS, Some("a")).is_ok());
+        assert_eq!(cache.get(ADDRESS), Some(&Handle("a")));
+        assert!(matches!(
+            cache.resolve(ADDRESS, Some("c")),
+            Err(Error::UnknownPeripheral(id)) if id == "c"
+        ));
+    }
+
+    #[test]
+    fn new_fingerprint_of_the_same_handle_replaces_the_device() {
+        let mut cache = DeviceCache::default();
+        insert(&mut cache, None, "a", Some("thermometer"));
+        insert(&mut cache, None, "a", Some("scale"));
+        assert!(cache.resolve(ADDRESS, None).is_ok());
+        let candidates = cache.candidates(ADDRESS);
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].fingerprint.as_deref(), Some("scale"));
+    }
+
+    #[test]
+    fn handle_moving_to_another_address_leaves_the_old_one() {
+        let mut cache = DeviceCache::default();
+        insert(&mut cache, None, "a", None);
+        cache.insert(None, "11:22:33:44:55:66".to_string(), Handle("a"), None);
+        assert!(!cache.contains_key(ADDRESS));
+        assert_eq!(cache.get("11:22:33:44:55:66"), Some(&Handle("a")));
+    }
+
+    #[test]
+    fn retain_reports_addresses_without_handles_left() {
+        let mut cache = DeviceCache::default();
+        insert(&mut cache, Some("hci0"), "a", None);
+        insert(&mut cache, Some("hci1"), "b", None);
+        assert!(cache.retain(|_, _, p| p.0 != "a").is_empty());
+        assert_eq!(cache.retain(|_, _, _| false), [ADDRESS]);
+        assert!(cache.remove(ADDRESS).is_empty());
+    }
+}
@@ -0,0 +1,332 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use futures::future::{select, Either};
+use futures::Stream;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::mpsc;
+use tokio::time::{sleep_until, Instant};
+use tracing::{debug, warn};
+
+use crate::error::Error;
+use crate::events::BleEvent;
+use crate::handler::{Handler, ScanSettings};
+use crate::models::{BleDevice, ScanFilter, ScanNameFilter};
+use crate::runtime;
+use crate::scan_budget::ScanEventBudget;
+
+/// Options of [`Handler::discover_stream`] and [`Handler::discover_with_options`]
+pub struct DiscoverOptions {
+    /// How long the scan runs, the stream ends afterwards
+    pub timeout: Duration,
+    pub filter: ScanFilter,
+    pub name_filter: ScanNameFilter,
+    /// How long a device must be missing before it is removed, `None` uses
+    /// [`Handler::set_removal_debounce`]
+    pub removal_debounce: Option<Duration>,
+    /// Limits the device events the scan publishes, every change is published if `None`
+    pub event_budget: Option<ScanEventBudget>,
+}
+
+impl Default for DiscoverOptions {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(10),
+            filter: ScanFilter::None,
+            name_filter: ScanNameFilter::None,
+            removal_debounce: None,
+            event_budget: None,
+        }
+    }
+}
+
+/// Change of the devices found by [`Handler::discover_stream`]
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum DiscoveredEvent {
+    /// First advertisement of the device during the scan
+    Added(BleDevice),
+    /// The advertisement of a device that was already added changed
+    Updated(BleDevice),
+    /// The adapter no longer reports a device that was added, see
+    /// [`Handler::set_removal_debounce`]
+    Removed { address: String },
+}
+
+impl Handler {
+    /// Scans like [`Handler::discover_with_name_filter`] and yields the changes of the found
+    /// devices as they happen
+    /// The stream ends once the timeout elapsed. Dropping it earlier stops the scan.
+    /// Starting another scan while the stream runs replaces its scan, the stream then reports
+    /// the devices of the new scan until its own timeout.
+    /// # Errors
+    /// Returns an error if starting the scan fails
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use futures::StreamExt;
+    /// use tauri::async_runtime;
+    /// use tauri_plugin_blec::{DiscoverOptions, DiscoveredEvent};
+    ///
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     let devices = handler.discover_stream(DiscoverOptions::default()).await.unwrap();
+    ///     let mut devices = std::pin::pin!(devices);
+    ///     while let Some(event) = devices.next().await {
+    ///         if let DiscoveredEvent::Added(device) = event {
+    ///             println!("found {}", device.name);
+    ///         }
+    ///     }
+    /// });
+    /// # }
+    /// ```
+    pub async fn discover_stream(
+        &'static self,
+        options: DiscoverOptions,
+    ) -> Result<impl Stream<Item = DiscoveredEvent> + Send, Error> {
+        // subscribed before the scan starts, so the first devices are not missed
+        let rx = self.events();
+        let deadline = Instant::now() + options.timeout;
+        let timeout_ms = u64::try_from(options.timeout.as_millis()).unwrap_or(u64::MAX);
+        let settings = ScanSettings {
+            removal_debounce: options.removal_debounce,
+            event_budget: options.event_budget,
+            ..ScanSettings::default()
+        };
+        let filter = options.filter.clone();
+        let name_filter = options.name_filter.clone();
+        let generation = self
+            .start_scan_loop(None, Some(timeout_ms), options.filter, options.name_filter, settings)
+            .await?;
+        let discovery = Discovery {
+            rx,
+            filter,
+            name_filter,
+            added: HashSet::new(),
+            deadline: Some(deadline),
+            scan: ScanGuard::new(self, generation),
+        };
+        Ok(futures::stream::unfold(discovery, |mut discovery| async move {
+            let event = discovery.next().await?;
+            Some((event, discovery))
+        }))
+    }
+
+    /// Keeps scanning until [`Handler::stop_discovery`] is called and sends the changes of the
+    /// found devices to `tx`
+    /// Found devices are also published as [`crate::events::BleEvent::DeviceDiscovered`], which
+    /// the plugin emits to the frontend. Connecting pauses the scan, it resumes once the connect
+    /// is done. Starting another scan replaces this one, [`Handler::discover`] stays the way to
+    /// scan for a fixed time.
+    /// # Errors
+    /// Returns an error if starting the scan fails
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// use tokio::sync::mpsc;
+    /// use tauri_plugin_blec::models::{ScanFilter, ScanNameFilter};
+    /// use tauri_plugin_blec::DiscoveredEvent;
+    ///
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     let (tx, mut rx) = mpsc::channel(16);
+    ///     handler
+    ///         .start_discovery(Some(tx), ScanFilter::None, ScanNameFilter::None)
+    ///         .await
+    ///         .unwrap();
+    ///     while let Some(event) = rx.recv().await {
+    ///         if let DiscoveredEvent::Added(device) = event {
+    ///             println!("found {}", device.name);
+    ///         }
+    ///     }
+    /// });
+    /// # }
+    /// ```
+    pub async fn start_discovery(
+        &'static self,
+        tx: Option<mpsc::Sender<DiscoveredEvent>>,
+        filter: ScanFilter,
+        name_filter: ScanNameFilter,
+    ) -> Result<(), Error> {
+        self.start_discovery_with_event_budget(tx, filter, name_filter, None)
+            .await
+    }
+
+    /// Keeps scanning like [`Handler::start_discovery`], limiting the device events the scan
+    /// publishes to the [`ScanEventBudget`]
+    /// Changes that exceed the budget wait per device and only the latest state of a device is
+    /// published on a later tick. The scan publishes [`BleEvent::ScanEventStats`] when it is
+    /// stopped.
+    /// # Errors
+    /// Returns an error if starting the scan fails
+    pub async fn start_discovery_with_event_budget(
+        &'static self,
+        tx: Option<mpsc::Sender<DiscoveredEvent>>,
+        filter: ScanFilter,
+        name_filter: ScanNameFilter,
+        event_budget: Option<ScanEventBudget>,
+    ) -> Result<(), Error> {
+        let rx = self.events();
+        let settings = ScanSettings {
+            event_budget,
+            ..ScanSettings::default()
+        };
+        self.start_scan_loop(None, None, filter.clone(), name_filter.clone(), settings)
+            .await?;
+        let forwarder = tx.map(|tx| {
+            let mut discovery = Discovery {
+                rx,
+                filter,
+                name_filter,
+                added: HashSet::new(),
+                deadline: None,
+                scan: ScanGuard(None),
+            };
+            runtime::spawn(async move {
+                while let Some(event) = discovery.next().await {
+                    if tx.send(event).await.is_err() {
+                        debug!("discovery receiver dropped, no longer forwarding devices");
+                        return;
+                    }
+                }
+            })
+        });
+        let previous = std::mem::replace(
+            &mut *self
+                .discovery_forwarder
+                .lock()
+                .expect("discovery forwarder lock poisoned"),
+            forwarder,
+        );
+        if let Some(previous) = previous {
+            previous.abort();
+        }
+        Ok(())
+    }
+
+    /// Stops the scan started with [`Handler::start_discovery`]
+    /// Does nothing if it is not running, a scan with a timeout or the shared scan of
+    /// [`Handler::register_scan_consumer`] is left alone.
+    /// # Errors
+    /// Returns an error if stopping the scan fails
+    pub async fn stop_discovery(&self) -> Result<(), Error> {
+        let forwarder = self
+            .discovery_forwarder
+            .lock()
+            .expect("discovery forwarder lock poisoned")
+            .take();
+        if let Some(forwarder) = forwarder {
+            forwarder.abort();
+        }
+        // the shared scan of the scan consumers is also continuous
+        if self.is_continuous_scan().await && !self.is_shared_scan().await {
+            self.stop_scan().await?;
+        }
+        Ok(())
+    }
+}
+
+struct Discovery {
+    rx: broadcast::Receiver<BleEvent>,
+    /// Filters of the scan, scans also publish the devices of the scan consumers
+    filter: ScanFilter,
+    name_filter: ScanNameFilter,
+    /// Addresses reported as added and not removed since
+    added: HashSet<String>,
+    /// `None` for the scans of [`Handler::start_discovery`]
+    deadline: Option<Instant>,
+    scan: ScanGuard,
+}
+
+impl Discovery {
+    fn matches(&self, device: &BleDevice) -> bool {
+        let services = &device.services;
+        let passes = match &self.filter {
+            ScanFilter::None => true,
+            ScanFilter::Service(uuid) => services.contains(uuid),
+            ScanFilter::AnyService(uuids) => {
+                uuids.is_empty() || services.iter().any(|s| uuids.contains(s))
+            }
+            ScanFilter::AllServices(uuids) => uuids.iter().all(|s| services.contains(s)),
+            ScanFilter::ManufacturerData(key, value) => {
+                device.manufacturer_data.get(key) == Some(value)
+            }
+        };
+        passes && self.name_filter.matches(Some(&device.name))
+    }
+
+    async fn next(&mut self) -> Option<DiscoveredEvent> {
+        loop {
+            let recv = std::pin::pin!(self.rx.recv());
+            let deadline = self.deadline;
+            let expired = std::pin::pin!(async move {
+                match deadline {
+                    Some(deadline) => sleep_until(deadline).await,
+                    None => std::future::pending().await,
+                }
+            });
+            let event = match select(recv, expired).await {
+                Either::Left((event, _)) => event,
+                Either::Right(_) => {
+                    // the scan already ended on its own, a later scan must keep running
+                    self.scan.disarm();
+                    return None;
+                }
+            };
+            match event {
+                Ok(BleEvent::DeviceDiscovered(device)) if self.matches(&device) => {
+                    if self.added.insert(device.address.clone()) {
+                        return Some(DiscoveredEvent::Added(device));
+                    }
+                    return Some(DiscoveredEvent::Updated(device));
+                }
+                Ok(BleEvent::DeviceRemoved(address)) if self.added.remove(&address) => {
+                    return Some(DiscoveredEvent::Removed { address });
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(missed)) => {
+                    warn!("discover stream lagged, {missed} events were missed");
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+/// Stops a scan when it is no longer needed, in the background if it was dropped
+/// A scan that replaced the guarded one keeps running.
+pub(crate) struct ScanGuard(Option<(&'static Handler, u64)>);
+
+impl ScanGuard {
+    /// Guards the scan returned by [`Handler::start_scan_loop`] as `generation`
+    pub(crate) fn new(handler: &'static Handler, generation: u64) -> Self {
+        Self(Some((handler, generation)))
+    }
+
+    pub(crate) async fn stop(mut self) {
+        if let Some((handler, generation)) = self.0.take() {
+            stop_scan(handler, generation).await;
+        }
+    }
+
+    /// Keeps the scan running when the guard is dropped
+    fn disarm(&mut self) {
+        self.0 = None;
+    }
+}
+
+impl Drop for ScanGuard {
+    fn drop(&mut self) {
+        if let Some((handler, generation)) = self.0.take() {
+            runtime::spawn(stop_scan(handler, generation));
+        }
+    }
+}
+
+async fn stop_scan(handler: &'static Handler, generation: u64) {
+    if let Err(e) = handler.stop_scan_generation(generation).await {
+        warn!("failed to stop the scan: {e}");
+    }
+}
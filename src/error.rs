@@ -5,34 +5,204 @@ use thiserror::Error;
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("Btleplug error: {0}")]
-    Btleplug(#[from] btleplug::Error),
+    Btleplug(#[source] btleplug::Error),
 
     #[error("There is no peripheral with id: {0}")]
     UnknownPeripheral(String),
 
+    #[error(
+        "Address {address} matches several devices, connect with one of their peripheral ids: {}",
+        crate::device_cache::describe(candidates)
+    )]
+    AmbiguousAddress {
+        address: String,
+        candidates: Vec<crate::device_cache::DeviceCandidate>,
+    },
+
     #[error("Characteristic {0} not available")]
     CharacNotAvailable(String),
 
+    #[error("Descriptor {descriptor} not available on characteristic {charac}")]
+    DescriptorNotAvailable { charac: String, descriptor: String },
+
+    #[error("Characteristic is provided by multiple services, specify one of: {}", .0.join(", "))]
+    AmbiguousCharacteristic(Vec<String>),
+
+    #[error("Characteristic {charac} does not support notify or indicate, properties: {properties}")]
+    NotifyNotSupported { charac: String, properties: String },
+
+    #[error("Characteristic {charac} can't be subscribed with {mode:?}, properties: {properties}")]
+    SubscribeModeNotSupported {
+        charac: String,
+        mode: crate::handler::SubscribeMode,
+        properties: String,
+    },
+
+    #[error("Characteristic {charac} does not support {write_type} writes, properties: {properties}")]
+    WriteNotSupported {
+        charac: String,
+        write_type: String,
+        properties: String,
+    },
+
+    #[error("Characteristic {charac} can only be subscribed after the required write to {required}")]
+    SubscribeBeforeWrite { charac: String, required: String },
+
+    #[error("Write to characteristic {charac} was answered with status {status:#04x}")]
+    WriteStatus { charac: String, status: u8 },
+
+    #[error("There is no listener for characteristic {0}")]
+    NoListener(String),
+
+    #[error("There is no capture with id: {0}")]
+    UnknownCapture(u64),
+
+    #[error("Invalid payload: {0}")]
+    InvalidPayload(String),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("There is no session with id: {0}")]
+    UnknownSession(u64),
+
     #[error("No device connected")]
     NoDeviceConnected,
 
+    #[error("Device was disconnected before the operation could run")]
+    Disconnected,
+
+    #[error("Device is not ready for data operations, connection state: {state:?}")]
+    NotReady { state: crate::handler::ConnectionState },
+
     #[error("Device is already connected.")]
     AlreadyConnected,
 
+    #[error("Several devices are connected, specify the address of one of: {}", .0.join(", "))]
+    AmbiguousConnection(Vec<String>),
+
+    #[error("Connection limit of {limit} reached")]
+    ConnectionLimitReached { limit: usize },
+
     #[error("Handler not initialized")]
     HandlerNotInitialized,
 
+    #[error("The event loop of the handler is already running")]
+    EventLoopRunning,
+
     #[error("could not send State: {0}")]
     SendingState(#[from] SendError<bool>),
 
     #[error("no bluetooth adapters found")]
     NoAdapters,
 
+    #[error("The bluetooth adapter is powered off")]
+    AdapterPoweredOff,
+
+    #[error("Adapter {0} is not scanned, see set_multi_adapter_scan")]
+    UnknownAdapter(String),
+
+    #[error("No adapter matches {0}")]
+    AdapterNotFound(String),
+
+    #[error("The adapter can not be switched while a device is connected")]
+    AdapterInUse,
+
+    #[error("Invalid device address: {input}")]
+    InvalidAddress { input: String },
+
+    #[error("Device {0} did not advertise in time")]
+    DeviceNotFound(String),
+
+    #[error("Waiting for device {0} was cancelled")]
+    WaitCancelled(String),
+
+    #[error("No connect session recorded for {0}")]
+    NoSessionTimeline(String),
+
+    #[error("Export path {0} must be a relative path inside the app data directory")]
+    InvalidExportPath(String),
+
+    #[error("Bluetooth access is {0:?}, allow bluetooth for the app in the system settings")]
+    PermissionDenied(crate::authorization::AuthorizationStatus),
+
     #[error("Unknonwn error during disconnect")]
     DisconnectFailed,
 
     #[error("Unknown error during connect")]
     ConnectionFailed,
+
+    #[error("Pairing failed: {0}")]
+    PairingFailed(String),
+
+    #[error("Pairing was rejected: {0}")]
+    PairingRejected(String),
+
+    #[error("Removing the bond failed: {0}")]
+    RemoveBondFailed(String),
+
+    #[error("Device {0} is not bonded, pair with it again before this operation")]
+    PairingRequired(String),
+
+    #[error("Setup step {step} failed: {error}")]
+    SetupFailed { step: usize, error: String },
+
+    #[error("Total timeout exceeded during {step}")]
+    TotalTimeout { step: String },
+
+    #[error("Connecting timed out after {timeout_ms}ms")]
+    ConnectionTimeout { timeout_ms: u64 },
+
+    #[error("The OS throttles scanning after too many scan starts, retry in {retry_after_ms}ms")]
+    ScanThrottled { retry_after_ms: u64 },
+
+    #[error(
+        "No matching notification of {charac} within {timeout_ms}ms, {non_matching} did not match"
+    )]
+    NotificationTimeout {
+        charac: String,
+        timeout_ms: u64,
+        non_matching: usize,
+    },
+
+    #[error("Transfer {id} paused at offset {offset} after the link was lost")]
+    TransferPaused { id: u64, offset: usize },
+
+    #[error("Chunked write failed at offset {offset}: {source}")]
+    ChunkWriteFailed {
+        offset: usize,
+        #[source]
+        source: Box<Error>,
+    },
+
+    #[error("There is no paused transfer with id: {0}")]
+    UnknownTransfer(u64),
+
+    #[error("Device {0} of the transfer is not connected")]
+    TransferDeviceNotConnected(String),
+
+    #[error("Device {0} is likely a Bluetooth Classic only device, its services are not available over BLE")]
+    LikelyClassicOnlyDevice(String),
+
+    #[error("The {0} feature is not enabled")]
+    FeatureNotEnabled(&'static str),
+
+    #[error("Keep-alive method not supported on this platform: {0}")]
+    UnsupportedKeepAlive(&'static str),
+
+    #[error("{0} is not supported on this platform")]
+    UnsupportedOnPlatform(&'static str),
+
+    #[error("No pairing consent of {0} is pending")]
+    NoPairingConsent(String),
+
+    #[error("There is no scan consumer with id: {0}")]
+    UnknownScanConsumer(u64),
+
+    #[cfg(feature = "conformance")]
+    #[error("{0}")]
+    ConformanceCheckFailed(String),
+
     #[cfg(target_os = "android")]
     #[error(transparent)]
     PluginInvoke(#[from] tauri::plugin::mobile::PluginInvokeError),
@@ -40,6 +210,89 @@ pub enum Error {
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// GATT status of a failed operation, the android backend reports it as the source of
+/// [`btleplug::Error::Other`] so it can be matched without parsing the message
+#[derive(Debug, Error)]
+#[cfg_attr(not(target_os = "android"), allow(dead_code))]
+#[error("{message}")]
+pub(crate) struct GattStatus {
+    pub(crate) status: u8,
+    pub(crate) message: String,
+}
+
+/// Rejection of the android plugin that has a typed [`Error`], the android backend reports it
+/// as the source of [`btleplug::Error::Other`] and the conversion into [`Error`] unwraps it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[cfg_attr(not(target_os = "android"), allow(dead_code))]
+pub(crate) enum PlatformRejection {
+    #[error("bluetooth permissions are being requested")]
+    PermissionRequested,
+    #[error("bluetooth permissions were denied")]
+    PermissionDenied,
+    #[error("bluetooth is disabled")]
+    BluetoothDisabled,
+}
+
+#[cfg_attr(not(target_os = "android"), allow(dead_code))]
+impl PlatformRejection {
+    /// Maps the code of a rejected invoke, the codes are the constants of
+    /// `BluetoothEnvironment.kt`
+    pub(crate) fn from_code(code: &str) -> Option<Self> {
+        match code {
+            "PERMISSION_REQUESTED" => Some(Self::PermissionRequested),
+            "PERMISSION_DENIED" => Some(Self::PermissionDenied),
+            "BLUETOOTH_DISABLED" => Some(Self::BluetoothDisabled),
+            _ => None,
+        }
+    }
+}
+
+impl From<PlatformRejection> for Error {
+    fn from(rejection: PlatformRejection) -> Self {
+        use crate::authorization::AuthorizationStatus;
+        match rejection {
+            PlatformRejection::PermissionRequested => {
+                Error::PermissionDenied(AuthorizationStatus::NotDetermined)
+            }
+            PlatformRejection::PermissionDenied => {
+                Error::PermissionDenied(AuthorizationStatus::Denied)
+            }
+            PlatformRejection::BluetoothDisabled => Error::AdapterPoweredOff,
+        }
+    }
+}
+
+impl From<btleplug::Error> for Error {
+    fn from(e: btleplug::Error) -> Self {
+        match e {
+            btleplug::Error::Other(source) => match source.downcast::<PlatformRejection>() {
+                Ok(rejection) => (*rejection).into(),
+                Err(source) => Error::Btleplug(btleplug::Error::Other(source)),
+            },
+            e => Error::Btleplug(e),
+        }
+    }
+}
+
+/// Error of an operation started with a correlation id, serialized as
+/// `{ correlationId, message }`
+#[derive(Debug, Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct CorrelatedError {
+    pub correlation_id: String,
+    pub message: String,
+}
+
+impl Error {
+    pub(crate) fn correlated(self, correlation_id: &str) -> CorrelatedError {
+        CorrelatedError {
+            correlation_id: correlation_id.to_string(),
+            message: self.to_string(),
+        }
+    }
+}
+
 impl Serialize for Error {
     fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
     where
@@ -48,3 +301,41 @@ impl Serialize for Error {
         serializer.serialize_str(self.to_string().as_ref())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::authorization::AuthorizationStatus;
+
+    fn rejected(code: &str) -> Error {
+        let rejection = PlatformRejection::from_code(code).expect("unknown rejection code");
+        btleplug::Error::Other(Box::new(rejection)).into()
+    }
+
+    // the codes are the constants of BluetoothEnvironment.kt
+    #[test]
+    fn android_rejections_map_to_typed_errors() {
+        assert!(matches!(
+            rejected("PERMISSION_REQUESTED"),
+            Error::PermissionDenied(AuthorizationStatus::NotDetermined)
+        ));
+        assert!(matches!(
+            rejected("PERMISSION_DENIED"),
+            Error::PermissionDenied(AuthorizationStatus::Denied)
+        ));
+        assert!(matches!(rejected("BLUETOOTH_DISABLED"), Error::AdapterPoweredOff));
+    }
+
+    #[test]
+    fn other_rejections_stay_btleplug_errors() {
+        assert_eq!(PlatformRejection::from_code("133"), None);
+        let status = GattStatus {
+            status: 0x85,
+            message: "write failed".to_string(),
+        };
+        let error: Error = btleplug::Error::Other(Box::new(status)).into();
+        assert!(matches!(error, Error::Btleplug(btleplug::Error::Other(_))));
+        let error: Error = btleplug::Error::NotConnected.into();
+        assert!(matches!(error, Error::Btleplug(btleplug::Error::NotConnected)));
+    }
+}
@@ -0,0 +1,552 @@
+use std::fmt::Display;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use futures::Stream;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{error, warn};
+use uuid::Uuid;
+
+use crate::authorization::AuthorizationStatus;
+use crate::battery::BatteryLevel;
+use crate::consent::PairingConsentRequest;
+use crate::handler::{ConnectionState, DeviceReaddressed, ForgetReport, Handler, PairingEvent};
+use crate::interference::ConnectionInterference;
+use crate::metrics::BleMetrics;
+use crate::models::BleDevice;
+use crate::reconnect::ReconnectProgress;
+use crate::scan_budget::ScanEventStats;
+use crate::scan_throttle::ScanThrottleWarning;
+use crate::setup::SetupNotification;
+use crate::timeline::Timelines;
+
+/// Number of events a slow receiver can fall behind before it misses events
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+/// Event published on the channel returned by [`Handler::events`]
+#[derive(Debug, Clone)]
+pub enum BleEvent {
+    /// A connect succeeded, published once the services are discovered and the setup ran,
+    /// also for additional connections and reconnects
+    Connected { address: String },
+    /// The connected device disconnected
+    Disconnected {
+        address: String,
+        reason: DisconnectReason,
+    },
+    /// A notification or indication of the connected device, sent before it is handed to
+    /// subscriptions, sessions and pending requests
+    Notification { characteristic: Uuid, data: Vec<u8> },
+    /// A scan found a new device or the advertisement of a device changed, RSSI changes are
+    /// only published once they exceed 5 dBm
+    DeviceDiscovered(BleDevice),
+    /// A scan no longer sees a device it reported, see [`Handler::set_removal_debounce`]
+    DeviceRemoved(String),
+    /// A connection moved to a new stage, published for additional connections as well
+    ConnectionStateChanged {
+        address: String,
+        state: ConnectionState,
+    },
+    /// The MTU of the connected device changed, see [`Handler::get_mtu`]
+    MtuChanged(u16),
+    /// Stage of a running [`Handler::reconnect_when_available`]
+    ReconnectProgress(ReconnectProgress),
+    /// The user allowed or denied bluetooth for the app, see [`Handler::authorization_status`]
+    AuthorizationChanged(AuthorizationStatus),
+    /// A callback passed to the handler panicked, the handler carried on without it
+    CallbackPanicked(CallbackPanic),
+    /// The bond of a device was created or removed, also outside of the app, e.g. in the system
+    /// settings. Only reported on android and linux.
+    BondChanged(BondChange),
+    /// The OS asks to confirm a pairing, answer with [`Handler::accept_pairing_consent`]
+    PairingConsentRequired(PairingConsentRequest),
+    /// The state of the adapter changed, published before the connections are closed when it
+    /// was powered off
+    AdapterStateChanged(AdapterState),
+    /// The battery level of the connected device changed, see
+    /// [`Handler::start_battery_monitor`]
+    BatteryChanged(BatteryLevel),
+    /// A scan with a [`crate::ScanEventBudget`] ended
+    ScanEventStats(ScanEventStats),
+    /// A device was forgotten with [`Handler::forget_device`]
+    DeviceForgotten(ForgetReport),
+    /// A scan was started close to the scan start limit of the OS, only published on android
+    ScanThrottleWarning(ScanThrottleWarning),
+    /// A running scan received no advertisement and is likely throttled by the OS, scans
+    /// started before `retry_after_ms` fail with [`crate::Error::ScanThrottled`]
+    ScanThrottled { retry_after_ms: u64 },
+    /// Subscriptions of the connected device were found cleared, likely by another application
+    /// using the device, see [`Handler::verify_subscriptions`]
+    ConnectionInterfered(ConnectionInterference),
+    /// A fingerprinted device was seen under a new address, see
+    /// [`Handler::set_device_fingerprint`]
+    DeviceReaddressed(DeviceReaddressed),
+    /// The metrics published every interval of [`Handler::set_metrics_interval`]
+    MetricsReported(BleMetrics),
+    /// A notification of a characteristic subscribed by [`crate::SetupStep::Subscribe`], also
+    /// published as [`BleEvent::Notification`]
+    SetupNotification(SetupNotification),
+    /// Progress of a pairing started by [`Handler::pair`] or
+    /// [`crate::ConnectOptions::require_bonded`]
+    Pairing(PairingEvent),
+    /// No central event arrived for `idle_ms` and the adapter did not answer, the event stream
+    /// is acquired again, see [`Handler::set_heartbeat_interval`]
+    EventLoopStalled { idle_ms: u64 },
+}
+
+/// Kind of user callback in a [`CallbackPanic`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub enum CallbackKind {
+    /// The disconnect callback passed to [`Handler::connect`]
+    OnDisconnect,
+    /// A notification callback added with [`Handler::subscribe`]
+    Listener,
+    /// The enricher set with [`Handler::set_device_enricher`]
+    DeviceEnricher,
+    /// The fingerprint set with [`Handler::set_device_fingerprint`]
+    DeviceFingerprint,
+}
+
+/// A user callback panicked, published as [`BleEvent::CallbackPanicked`]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct CallbackPanic {
+    pub callback: CallbackKind,
+    /// Device the callback ran for
+    pub address: String,
+    /// Characteristic of a listener, the address for the other callbacks
+    pub context: String,
+    pub message: String,
+}
+
+/// Sender of [`Handler::events`] that records the events belonging on the session timelines
+/// Cloned into the tasks of the handler, so their events reach the timelines as well.
+#[derive(Clone)]
+pub(crate) struct Publisher {
+    events: broadcast::Sender<BleEvent>,
+    timelines: Arc<Mutex<Timelines>>,
+}
+
+impl Publisher {
+    pub(crate) fn new() -> Self {
+        Self {
+            events: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+            timelines: Arc::new(Mutex::new(Timelines::default())),
+        }
+    }
+
+    /// Publishes an event, it is dropped if nobody listens
+    pub(crate) fn publish(&self, event: BleEvent) {
+        self.timelines().record_event(&event);
+        let _ = self.events.send(event);
+    }
+
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<BleEvent> {
+        self.events.subscribe()
+    }
+
+    pub(crate) fn timelines(&self) -> MutexGuard<'_, Timelines> {
+        self.timelines.lock().expect("timelines lock poisoned")
+    }
+}
+
+/// Runs a user callback, a panic is logged and published as [`BleEvent::CallbackPanicked`]
+/// instead of unwinding into the handler
+/// Returns `None` if the callback panicked.
+pub(crate) fn guard_callback<T>(
+    publisher: &Publisher,
+    callback: CallbackKind,
+    address: &str,
+    context: &dyn Display,
+    f: impl FnOnce() -> T,
+) -> Option<T> {
+    let payload = match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(value) => return Some(value),
+        Err(payload) => payload,
+    };
+    let message = payload
+        .downcast_ref::<&str>()
+        .map(|s| (*s).to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic payload".to_string());
+    error!("{callback:?} callback panicked for {context}: {message}");
+    publisher.publish(BleEvent::CallbackPanicked(CallbackPanic {
+        callback,
+        address: address.to_string(),
+        context: context.to_string(),
+        message,
+    }));
+    None
+}
+
+/// New bond state of a device, published as [`BleEvent::BondChanged`]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct BondChange {
+    pub address: String,
+    pub bonded: bool,
+}
+
+/// State of the adapter returned by [`Handler::adapter_state`], changes are published as
+/// [`BleEvent::AdapterStateChanged`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub enum AdapterState {
+    PoweredOn,
+    PoweredOff,
+    /// The user or the device policy denied bluetooth for the app
+    Unauthorized,
+    /// The platform did not report the state yet or reports an intermediate state, e.g. while
+    /// the adapter resets
+    Unknown,
+}
+
+/// Why the device was disconnected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub enum DisconnectReason {
+    /// Requested with [`Handler::disconnect`]
+    Requested,
+    /// The link was lost or the device closed the connection
+    LinkLost,
+    /// Nothing was exchanged for [`crate::ConnectOptions::idle_disconnect_ms`]
+    IdleTimeout,
+    /// The adapter was powered off, e.g. bluetooth was switched off in the system settings
+    AdapterOff,
+    /// Every attempt of [`crate::ConnectOptions::reconnect`] failed after the link was lost
+    ReconnectFailed,
+}
+
+impl Handler {
+    /// Receives every [`BleEvent`]
+    /// A receiver that falls behind by more than 256 events gets
+    /// [`RecvError::Lagged`] and misses the oldest events.
+    /// The typed views [`Handler::disconnections`], [`Handler::notifications`] and
+    /// [`Handler::discovered_devices`] cover the common cases.
+    pub fn events(&self) -> broadcast::Receiver<BleEvent> {
+        self.publisher().subscribe()
+    }
+
+    /// Stream of the address and reason of every disconnect
+    /// The stream only observes events, dropping it has no effect on the connection.
+    /// Missed events of a lagging stream are logged and skipped.
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use futures::StreamExt;
+    /// use tauri::async_runtime;
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     let mut disconnections = std::pin::pin!(handler.disconnections());
+    ///     while let Some((address, reason)) = disconnections.next().await {
+    ///         println!("{address} disconnected: {reason:?}");
+    ///     }
+    /// });
+    /// # }
+    /// ```
+    pub fn disconnections(&self) -> impl Stream<Item = (String, DisconnectReason)> + Send {
+        filter_events(self.events(), |event| match event {
+            BleEvent::Disconnected { address, reason } => Some((address, reason)),
+            _ => None,
+        })
+    }
+
+    /// Stream of the address of every successful connect, see [`BleEvent::Connected`]
+    /// The stream only observes events, dropping it has no effect on the connection.
+    pub fn connects(&self) -> impl Stream<Item = String> + Send {
+        filter_events(self.events(), |event| match event {
+            BleEvent::Connected { address } => Some(address),
+            _ => None,
+        })
+    }
+
+    /// Stream of the notifications of the given characteristic
+    /// The characteristic must be subscribed, e.g. with [`Handler::subscribe`], for the device to
+    /// send notifications. Dropping the stream does not unsubscribe, the stream keeps yielding
+    /// notifications after a reconnect and only ends when the handler is dropped.
+    /// Missed events of a lagging stream are logged and skipped.
+    pub fn notifications(&self, c: Uuid) -> impl Stream<Item = Vec<u8>> + Send {
+        filter_events(self.events(), move |event| match event {
+            BleEvent::Notification {
+                characteristic,
+                data,
+            } if characteristic == c => Some(data),
+            _ => None,
+        })
+    }
+
+    /// Stream of the stages of every connection with the address of the device, see
+    /// [`Handler::device_connection_state`]
+    /// Dropping the stream has no effect on the connection.
+    pub fn connection_states(&self) -> impl Stream<Item = (String, ConnectionState)> + Send {
+        filter_events(self.events(), |event| match event {
+            BleEvent::ConnectionStateChanged { address, state } => Some((address, state)),
+            _ => None,
+        })
+    }
+
+    /// Stream of the MTU changes of the connected device, see [`Handler::get_mtu`]
+    /// Only android reports MTU changes, including renegotiations started by the device.
+    pub fn mtu_changes(&self) -> impl Stream<Item = u16> + Send {
+        filter_events(self.events(), |event| match event {
+            BleEvent::MtuChanged(mtu) => Some(mtu),
+            _ => None,
+        })
+    }
+
+    /// Stream of the stages of every [`Handler::reconnect_when_available`]
+    pub fn reconnect_progress(&self) -> impl Stream<Item = ReconnectProgress> + Send {
+        filter_events(self.events(), |event| match event {
+            BleEvent::ReconnectProgress(progress) => Some(progress),
+            _ => None,
+        })
+    }
+
+    /// Stream of the changes of [`Handler::authorization_status`]
+    pub fn authorization_changes(&self) -> impl Stream<Item = AuthorizationStatus> + Send {
+        filter_events(self.events(), |event| match event {
+            BleEvent::AuthorizationChanged(status) => Some(status),
+            _ => None,
+        })
+    }
+
+    /// Stream of the user callbacks that panicked
+    pub fn callback_panics(&self) -> impl Stream<Item = CallbackPanic> + Send {
+        filter_events(self.events(), |event| match event {
+            BleEvent::CallbackPanicked(panic) => Some(panic),
+            _ => None,
+        })
+    }
+
+    /// Stream of the created and removed bonds, only reported on android and linux
+    pub fn bond_changes(&self) -> impl Stream<Item = BondChange> + Send {
+        filter_events(self.events(), |event| match event {
+            BleEvent::BondChanged(change) => Some(change),
+            _ => None,
+        })
+    }
+
+    /// Stream of the state changes of the adapter
+    pub fn adapter_states(&self) -> impl Stream<Item = AdapterState> + Send {
+        filter_events(self.events(), |event| match event {
+            BleEvent::AdapterStateChanged(state) => Some(state),
+            _ => None,
+        })
+    }
+
+    /// Stream of the battery levels reported by [`Handler::start_battery_monitor`]
+    pub fn battery_levels(&self) -> impl Stream<Item = BatteryLevel> + Send {
+        filter_events(self.events(), |event| match event {
+            BleEvent::BatteryChanged(level) => Some(level),
+            _ => None,
+        })
+    }
+
+    /// Stream of the coalescing statistics of scans with a [`crate::ScanEventBudget`]
+    pub fn scan_event_stats(&self) -> impl Stream<Item = ScanEventStats> + Send {
+        filter_events(self.events(), |event| match event {
+            BleEvent::ScanEventStats(stats) => Some(stats),
+            _ => None,
+        })
+    }
+
+    /// Stream of the devices forgotten with [`Handler::forget_device`]
+    pub fn forgotten_devices(&self) -> impl Stream<Item = ForgetReport> + Send {
+        filter_events(self.events(), |event| match event {
+            BleEvent::DeviceForgotten(report) => Some(report),
+            _ => None,
+        })
+    }
+
+    /// Stream of the warnings published before scanning reaches the scan start limit of the OS
+    pub fn scan_throttle_warnings(&self) -> impl Stream<Item = ScanThrottleWarning> + Send {
+        filter_events(self.events(), |event| match event {
+            BleEvent::ScanThrottleWarning(warning) => Some(warning),
+            _ => None,
+        })
+    }
+
+    /// Stream of the milliseconds until scanning works again, whenever a running scan was
+    /// detected as throttled by the OS
+    pub fn scan_throttles(&self) -> impl Stream<Item = u64> + Send {
+        filter_events(self.events(), |event| match event {
+            BleEvent::ScanThrottled { retry_after_ms } => Some(retry_after_ms),
+            _ => None,
+        })
+    }
+
+    /// Stream of the subscriptions found cleared on the connected device and restored
+    pub fn connection_interferences(&self) -> impl Stream<Item = ConnectionInterference> + Send {
+        filter_events(self.events(), |event| match event {
+            BleEvent::ConnectionInterfered(interference) => Some(interference),
+            _ => None,
+        })
+    }
+
+    /// Stream of the address changes of fingerprinted devices
+    pub fn readdressed_devices(&self) -> impl Stream<Item = DeviceReaddressed> + Send {
+        filter_events(self.events(), |event| match event {
+            BleEvent::DeviceReaddressed(readdressed) => Some(readdressed),
+            _ => None,
+        })
+    }
+
+    /// Stream of the addresses of devices a running scan no longer sees
+    pub fn removed_devices(&self) -> impl Stream<Item = String> + Send {
+        filter_events(self.events(), |event| match event {
+            BleEvent::DeviceRemoved(address) => Some(address),
+            _ => None,
+        })
+    }
+
+    /// Stream of the metrics enabled with [`Handler::set_metrics_interval`]
+    pub fn metrics_reports(&self) -> impl Stream<Item = BleMetrics> + Send {
+        filter_events(self.events(), |event| match event {
+            BleEvent::MetricsReported(metrics) => Some(metrics),
+            _ => None,
+        })
+    }
+
+    /// Stream of the notifications of characteristics subscribed by setup scripts
+    pub fn setup_notifications(&self) -> impl Stream<Item = SetupNotification> + Send {
+        filter_events(self.events(), |event| match event {
+            BleEvent::SetupNotification(notification) => Some(notification),
+            _ => None,
+        })
+    }
+
+    /// Stream of the progress of pairings
+    pub fn pairing_events(&self) -> impl Stream<Item = PairingEvent> + Send {
+        filter_events(self.events(), |event| match event {
+            BleEvent::Pairing(event) => Some(event),
+            _ => None,
+        })
+    }
+
+    /// Stream of the milliseconds since the last central event whenever the event loop stalled
+    pub fn event_loop_stalls(&self) -> impl Stream<Item = u64> + Send {
+        filter_events(self.events(), |event| match event {
+            BleEvent::EventLoopStalled { idle_ms } => Some(idle_ms),
+            _ => None,
+        })
+    }
+
+    /// Stream of the pairings the OS asks to confirm, only published on Windows
+    pub fn pairing_consent_requests(&self) -> impl Stream<Item = PairingConsentRequest> + Send {
+        filter_events(self.events(), |event| match event {
+            BleEvent::PairingConsentRequired(request) => Some(request),
+            _ => None,
+        })
+    }
+
+    /// Stream of the devices found by scans started with [`Handler::discover`] or
+    /// [`Handler::start_discovery`]
+    /// A device is yielded when it is first seen and whenever its advertisement changes.
+    /// Dropping the stream does not stop a running scan.
+    /// Missed events of a lagging stream are logged and skipped.
+    pub fn discovered_devices(&self) -> impl Stream<Item = BleDevice> + Send {
+        filter_events(self.events(), |event| match event {
+            BleEvent::DeviceDiscovered(device) => Some(device),
+            _ => None,
+        })
+    }
+}
+
+/// Typed view over the event channel
+fn filter_events<T>(
+    rx: broadcast::Receiver<BleEvent>,
+    filter: impl FnMut(BleEvent) -> Option<T> + Send + 'static,
+) -> impl Stream<Item = T> + Send
+where
+    T: Send,
+{
+    futures::stream::unfold((rx, filter), |(mut rx, mut filter)| async move {
+        loop {
+            match rx.recv().await {
+                Ok(event) => {
+                    if let Some(item) = filter(event) {
+                        return Some((item, (rx, filter)));
+                    }
+                }
+                Err(RecvError::Lagged(missed)) => {
+                    warn!("event stream lagged, {missed} events were missed");
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn published_panic(rx: &mut broadcast::Receiver<BleEvent>) -> CallbackPanic {
+        match rx.try_recv() {
+            Ok(BleEvent::CallbackPanicked(panic)) => panic,
+            other => panic!("expected a callback panic, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn returns_the_value_of_the_callback() {
+        let events = Publisher::new();
+        let mut rx = events.subscribe();
+        let kind = CallbackKind::Listener;
+        assert_eq!(guard_callback(&events, kind, "AA", &"AA", || 7), Some(7));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn publishes_a_panic_with_its_message() {
+        let events = Publisher::new();
+        let mut rx = events.subscribe();
+        let kind = CallbackKind::OnDisconnect;
+        let result = guard_callback::<()>(&events, kind, "AA", &"AA", || panic!("gone"));
+        assert_eq!(result, None);
+        assert_eq!(
+            published_panic(&mut rx),
+            CallbackPanic {
+                callback: CallbackKind::OnDisconnect,
+                address: "AA".to_string(),
+                context: "AA".to_string(),
+                message: "gone".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn publishes_a_formatted_panic_message() {
+        let events = Publisher::new();
+        let mut rx = events.subscribe();
+        let kind = CallbackKind::DeviceEnricher;
+        guard_callback::<()>(&events, kind, "AA", &"AA", || panic!("field {} missing", 3));
+        assert_eq!(published_panic(&mut rx).message, "field 3 missing");
+    }
+
+    #[test]
+    fn reports_an_unknown_panic_payload() {
+        let events = Publisher::new();
+        let mut rx = events.subscribe();
+        let kind = CallbackKind::DeviceFingerprint;
+        guard_callback::<()>(&events, kind, "AA", &"AA", || std::panic::panic_any(5_u8));
+        assert_eq!(published_panic(&mut rx).message, "unknown panic payload");
+    }
+
+    #[test]
+    fn keeps_running_callbacks_after_a_panic() {
+        let events = Publisher::new();
+        let mut rx = events.subscribe();
+        let kind = CallbackKind::Listener;
+        guard_callback::<()>(&events, kind, "AA", &"AA", || panic!("first"));
+        assert_eq!(guard_callback(&events, kind, "AA", &"AA", || 1), Some(1));
+        assert_eq!(published_panic(&mut rx).message, "first");
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -0,0 +1,60 @@
+use std::time::Duration;
+
+use tokio::time::timeout;
+
+use crate::handler::Handler;
+
+/// How a characteristic followed with [`Handler::follow_characteristic`] tracks the connected
+/// device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Follow {
+    /// The device does not have the characteristic
+    Unavailable,
+    Subscribed,
+    /// The device does not notify the value
+    Polled,
+    /// Reading or subscribing failed, tried again with the next poll
+    Retry,
+}
+
+/// Characteristic followed on every connect, see [`Handler::follow_characteristic`]
+pub(crate) trait Followed {
+    /// Reads the value and subscribes to it
+    async fn watch(&self, address: &str) -> Follow;
+    /// Reads the value of a characteristic that does not notify
+    async fn poll(&self, address: &str);
+}
+
+impl Handler {
+    /// Watches the characteristic on every connect, also after reconnects, and reads it every
+    /// `poll_interval` until the device disconnects if it is not subscribed
+    /// A failed watch is tried again with the same interval. Runs until the task is aborted.
+    pub(crate) async fn follow_characteristic(
+        &'static self,
+        followed: impl Followed,
+        poll_interval: Duration,
+    ) {
+        let mut connected = self.connection_watch();
+        loop {
+            if connected.wait_for(|c| *c).await.is_err() {
+                return;
+            }
+            if let Some(address) = self.connected_address() {
+                let mut follow = followed.watch(&address).await;
+                // polled until the device disconnects
+                while matches!(follow, Follow::Polled | Follow::Retry)
+                    && timeout(poll_interval, connected.wait_for(|c| !*c)).await.is_err()
+                {
+                    if follow == Follow::Retry {
+                        follow = followed.watch(&address).await;
+                    } else {
+                        followed.poll(&address).await;
+                    }
+                }
+            }
+            if connected.wait_for(|c| !*c).await.is_err() {
+                return;
+            }
+        }
+    }
+}
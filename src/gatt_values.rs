@@ -0,0 +1,580 @@
+//! Encoding and decoding of standard GATT values
+//!
+//! Multi byte values are little-endian as required by the Bluetooth Core Specification, big-endian
+//! payloads are rejected by the length and range checks where possible.
+//! # Example
+//! ```
+//! use tauri_plugin_blec::gatt_values::{self, AlertLevel, Cccd};
+//!
+//! assert_eq!(gatt_values::encode_uint(0x1234, 2).unwrap(), [0x34, 0x12]);
+//! assert_eq!(gatt_values::decode_sint(&[0xfe, 0xff]).unwrap(), -2);
+//! assert_eq!(Cccd::NOTIFY.encode(), [0x01, 0x00]);
+//! assert_eq!(AlertLevel::decode(&[0x02]).unwrap(), AlertLevel::HighAlert);
+//! ```
+
+use crate::error::Error;
+
+/// Special values of the IEEE 11073 SFLOAT of [`decode_sfloat`]
+const SFLOAT_NAN: u16 = 0x07ff;
+const SFLOAT_NRES: u16 = 0x0800;
+const SFLOAT_POSITIVE_INFINITY: u16 = 0x07fe;
+const SFLOAT_NEGATIVE_INFINITY: u16 = 0x0802;
+const SFLOAT_RESERVED: u16 = 0x0801;
+/// Largest mantissa of an SFLOAT, larger values are special values
+const SFLOAT_MAX_MANTISSA: i16 = 0x07fd;
+
+/// Special values of the IEEE 11073 FLOAT of [`decode_float`]
+const FLOAT_NAN: u32 = 0x007f_ffff;
+const FLOAT_NRES: u32 = 0x0080_0000;
+const FLOAT_POSITIVE_INFINITY: u32 = 0x007f_fffe;
+const FLOAT_NEGATIVE_INFINITY: u32 = 0x0080_0002;
+const FLOAT_RESERVED: u32 = 0x0080_0001;
+/// Largest mantissa of a FLOAT, larger values are special values
+const FLOAT_MAX_MANTISSA: i32 = 0x007f_fffd;
+
+fn invalid(message: String) -> Error {
+    Error::InvalidPayload(message)
+}
+
+fn check_len(name: &str, data: &[u8], len: usize) -> Result<(), Error> {
+    if data.len() == len {
+        Ok(())
+    } else {
+        Err(invalid(format!(
+            "{name} must be {len} bytes, got {}",
+            data.len()
+        )))
+    }
+}
+
+fn check_width(len: usize) -> Result<(), Error> {
+    if (1..=8).contains(&len) {
+        Ok(())
+    } else {
+        Err(invalid(format!("integers must be 1 to 8 bytes, got {len}")))
+    }
+}
+
+/// Decodes a little-endian unsigned integer, the width is the length of `data`
+/// Covers uint8 to uint64 including the odd widths like uint24 and uint48.
+/// # Errors
+/// Returns [`Error::InvalidPayload`] if `data` is empty or longer than 8 bytes
+pub fn decode_uint(data: &[u8]) -> Result<u64, Error> {
+    check_width(data.len())?;
+    let mut bytes = [0; 8];
+    bytes[..data.len()].copy_from_slice(data);
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// Encodes `value` as a little-endian unsigned integer of `len` bytes
+/// # Errors
+/// Returns [`Error::InvalidPayload`] if `len` is not 1 to 8 or `value` does not fit
+pub fn encode_uint(value: u64, len: usize) -> Result<Vec<u8>, Error> {
+    check_width(len)?;
+    if len < 8 && value >> (len * 8) != 0 {
+        return Err(invalid(format!("{value} does not fit into uint{}", len * 8)));
+    }
+    Ok(value.to_le_bytes()[..len].to_vec())
+}
+
+/// Decodes a little-endian two's complement integer, the width is the length of `data`
+/// # Errors
+/// Returns [`Error::InvalidPayload`] if `data` is empty or longer than 8 bytes
+pub fn decode_sint(data: &[u8]) -> Result<i64, Error> {
+    check_width(data.len())?;
+    // sign extended by the highest byte
+    let fill = if data[data.len() - 1] & 0x80 == 0 { 0 } else { 0xff };
+    let mut bytes = [fill; 8];
+    bytes[..data.len()].copy_from_slice(data);
+    Ok(i64::from_le_bytes(bytes))
+}
+
+/// Encodes `value` as a little-endian two's complement integer of `len` bytes
+/// # Errors
+/// Returns [`Error::InvalidPayload`] if `len` is not 1 to 8 or `value` does not fit
+pub fn encode_sint(value: i64, len: usize) -> Result<Vec<u8>, Error> {
+    check_width(len)?;
+    let bits = len * 8;
+    if bits < 64 {
+        let max = (1i64 << (bits - 1)) - 1;
+        if !(-max - 1..=max).contains(&value) {
+            return Err(invalid(format!("{value} does not fit into sint{bits}")));
+        }
+    }
+    Ok(value.to_le_bytes()[..len].to_vec())
+}
+
+/// Decodes a little-endian IEEE 754 float32
+/// # Errors
+/// Returns [`Error::InvalidPayload`] if `data` is not 4 bytes long
+pub fn decode_float32(data: &[u8]) -> Result<f32, Error> {
+    check_len("float32", data, 4)?;
+    Ok(f32::from_le_bytes([data[0], data[1], data[2], data[3]]))
+}
+
+/// Encodes `value` as a little-endian IEEE 754 float32
+pub fn encode_float32(value: f32) -> [u8; 4] {
+    value.to_le_bytes()
+}
+
+/// Decodes a little-endian IEEE 754 float64
+/// # Errors
+/// Returns [`Error::InvalidPayload`] if `data` is not 8 bytes long
+pub fn decode_float64(data: &[u8]) -> Result<f64, Error> {
+    check_len("float64", data, 8)?;
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(data);
+    Ok(f64::from_le_bytes(bytes))
+}
+
+/// Encodes `value` as a little-endian IEEE 754 float64
+pub fn encode_float64(value: f64) -> [u8; 8] {
+    value.to_le_bytes()
+}
+
+/// Decodes an IEEE 11073 16 bit SFLOAT, a 4 bit exponent and a 12 bit mantissa to base 10
+/// NaN, NRes and the reserved value decode to [`f64::NAN`], the infinities to
+/// [`f64::INFINITY`] and [`f64::NEG_INFINITY`].
+/// # Errors
+/// Returns [`Error::InvalidPayload`] if `data` is not 2 bytes long
+pub fn decode_sfloat(data: &[u8]) -> Result<f64, Error> {
+    check_len("SFLOAT", data, 2)?;
+    let raw = u16::from_le_bytes([data[0], data[1]]);
+    Ok(match raw {
+        SFLOAT_NAN | SFLOAT_NRES | SFLOAT_RESERVED => f64::NAN,
+        SFLOAT_POSITIVE_INFINITY => f64::INFINITY,
+        SFLOAT_NEGATIVE_INFINITY => f64::NEG_INFINITY,
+        _ => {
+            // both fields are sign extended by the arithmetic shifts
+            let signed = i16::from_le_bytes([data[0], data[1]]);
+            let mantissa = (signed << 4) >> 4;
+            let exponent = signed >> 12;
+            f64::from(mantissa) * 10f64.powi(i32::from(exponent))
+        }
+    })
+}
+
+/// Encodes `mantissa * 10^exponent` as an IEEE 11073 16 bit SFLOAT
+/// # Errors
+/// Returns [`Error::InvalidPayload`] if the mantissa is outside of -2045 to 2045 or the exponent
+/// outside of -8 to 7
+pub fn encode_sfloat(mantissa: i16, exponent: i8) -> Result<[u8; 2], Error> {
+    if !(-SFLOAT_MAX_MANTISSA..=SFLOAT_MAX_MANTISSA).contains(&mantissa) {
+        return Err(invalid(format!("SFLOAT mantissa {mantissa} is out of range")));
+    }
+    if !(-8..=7).contains(&exponent) {
+        return Err(invalid(format!("SFLOAT exponent {exponent} is out of range")));
+    }
+    let raw = (i16::from(exponent) << 12) | (mantissa & 0x0fff);
+    Ok(raw.to_le_bytes())
+}
+
+/// Decodes an IEEE 11073 32 bit FLOAT, an 8 bit exponent and a 24 bit mantissa to base 10
+/// Special values decode like the ones of [`decode_sfloat`].
+/// # Errors
+/// Returns [`Error::InvalidPayload`] if `data` is not 4 bytes long
+pub fn decode_float(data: &[u8]) -> Result<f64, Error> {
+    check_len("FLOAT", data, 4)?;
+    let raw = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+    Ok(match raw {
+        FLOAT_NAN | FLOAT_NRES | FLOAT_RESERVED => f64::NAN,
+        FLOAT_POSITIVE_INFINITY => f64::INFINITY,
+        FLOAT_NEGATIVE_INFINITY => f64::NEG_INFINITY,
+        _ => {
+            let mantissa = (i32::from_le_bytes([data[0], data[1], data[2], data[3]]) << 8) >> 8;
+            let exponent = i8::from_le_bytes([data[3]]);
+            f64::from(mantissa) * 10f64.powi(i32::from(exponent))
+        }
+    })
+}
+
+/// Encodes `mantissa * 10^exponent` as an IEEE 11073 32 bit FLOAT
+/// # Errors
+/// Returns [`Error::InvalidPayload`] if the mantissa is outside of -8388605 to 8388605
+pub fn encode_float(mantissa: i32, exponent: i8) -> Result<[u8; 4], Error> {
+    if !(-FLOAT_MAX_MANTISSA..=FLOAT_MAX_MANTISSA).contains(&mantissa) {
+        return Err(invalid(format!("FLOAT mantissa {mantissa} is out of range")));
+    }
+    let mut bytes = mantissa.to_le_bytes();
+    bytes[3] = exponent.to_le_bytes()[0];
+    Ok(bytes)
+}
+
+/// Value of the Client Characteristic Configuration descriptor (0x2902)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Cccd {
+    pub notifications: bool,
+    pub indications: bool,
+}
+
+impl Cccd {
+    /// Notifications enabled
+    pub const NOTIFY: Self = Self {
+        notifications: true,
+        indications: false,
+    };
+    /// Indications enabled
+    pub const INDICATE: Self = Self {
+        notifications: false,
+        indications: true,
+    };
+
+    /// Parses the 2 byte descriptor value
+    /// # Errors
+    /// Returns [`Error::InvalidPayload`] if the value is not 2 bytes long or reserved bits are set
+    pub fn decode(data: &[u8]) -> Result<Self, Error> {
+        check_len("CCCD", data, 2)?;
+        let raw = u16::from_le_bytes([data[0], data[1]]);
+        if raw & !0b11 != 0 {
+            return Err(invalid(format!("CCCD has reserved bits set: {raw:#06x}")));
+        }
+        Ok(Self {
+            notifications: raw & 0b01 != 0,
+            indications: raw & 0b10 != 0,
+        })
+    }
+
+    /// Encodes the descriptor value
+    pub fn encode(self) -> [u8; 2] {
+        (u16::from(self.notifications) | u16::from(self.indications) << 1).to_le_bytes()
+    }
+}
+
+/// Value of the Alert Level characteristic (0x2A06)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AlertLevel {
+    NoAlert = 0,
+    MildAlert = 1,
+    HighAlert = 2,
+}
+
+impl AlertLevel {
+    /// Parses the 1 byte characteristic value
+    /// # Errors
+    /// Returns [`Error::InvalidPayload`] if the value is not a single byte of 0 to 2
+    pub fn decode(data: &[u8]) -> Result<Self, Error> {
+        match data {
+            [0] => Ok(Self::NoAlert),
+            [1] => Ok(Self::MildAlert),
+            [2] => Ok(Self::HighAlert),
+            _ => Err(invalid(format!(
+                "alert level must be a single byte of 0-2, got {data:?}"
+            ))),
+        }
+    }
+
+    /// Encodes the characteristic value
+    pub fn encode(self) -> [u8; 1] {
+        [self as u8]
+    }
+}
+
+/// Parses the value of the Battery Level characteristic (0x2A19)
+/// # Errors
+/// Returns [`Error::InvalidPayload`] if the value is not a single byte of 0 to 100
+pub fn decode_battery_level(data: &[u8]) -> Result<u8, Error> {
+    match data {
+        [percent] if *percent <= 100 => Ok(*percent),
+        _ => Err(invalid(format!(
+            "battery level must be a single byte of 0-100, got {data:?}"
+        ))),
+    }
+}
+
+/// Encodes the value of the Battery Level characteristic (0x2A19)
+/// # Errors
+/// Returns [`Error::InvalidPayload`] if `percent` is above 100
+pub fn encode_battery_level(percent: u8) -> Result<[u8; 1], Error> {
+    if percent > 100 {
+        return Err(invalid(format!("battery level {percent} is above 100")));
+    }
+    Ok([percent])
+}
+
+/// Value of the Date Time characteristic (0x2A08), also the start of the Current Time
+/// Fields the device does not know are 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DateTime {
+    /// 1582 to 9999
+    pub year: u16,
+    /// 1 to 12
+    pub month: u8,
+    /// 1 to 31
+    pub day: u8,
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+}
+
+impl DateTime {
+    /// Parses the 7 byte characteristic value
+    /// # Errors
+    /// Returns [`Error::InvalidPayload`] if the value is not 7 bytes long or a field is out of
+    /// range
+    pub fn decode(data: &[u8]) -> Result<Self, Error> {
+        check_len("date time", data, 7)?;
+        let date_time = Self {
+            year: u16::from_le_bytes([data[0], data[1]]),
+            month: data[2],
+            day: data[3],
+            hours: data[4],
+            minutes: data[5],
+            seconds: data[6],
+        };
+        date_time.validate()?;
+        Ok(date_time)
+    }
+
+    /// Encodes the characteristic value
+    /// # Errors
+    /// Returns [`Error::InvalidPayload`] if a field is out of range
+    pub fn encode(&self) -> Result<[u8; 7], Error> {
+        self.validate()?;
+        let [y0, y1] = self.year.to_le_bytes();
+        Ok([y0, y1, self.month, self.day, self.hours, self.minutes, self.seconds])
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        let valid = (self.year == 0 || (1582..=9999).contains(&self.year))
+            && self.month <= 12
+            && self.day <= 31
+            && self.hours <= 23
+            && self.minutes <= 59
+            && self.seconds <= 59;
+        if valid {
+            Ok(())
+        } else {
+            Err(invalid(format!("date time out of range: {self:?}")))
+        }
+    }
+}
+
+/// Value of the Current Time characteristic (0x2A2B) of the Current Time service
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CurrentTime {
+    pub date_time: DateTime,
+    /// 1 for Monday to 7 for Sunday, 0 if unknown
+    pub day_of_week: u8,
+    /// Fractions of a second in 1/256 steps
+    pub fractions256: u8,
+    /// Bit field of the reasons the time was adjusted
+    pub adjust_reason: u8,
+}
+
+impl CurrentTime {
+    /// Parses the 10 byte characteristic value
+    /// # Errors
+    /// Returns [`Error::InvalidPayload`] if the value is not 10 bytes long or a field is out of
+    /// range
+    pub fn decode(data: &[u8]) -> Result<Self, Error> {
+        check_len("current time", data, 10)?;
+        let current = Self {
+            date_time: DateTime::decode(&data[..7])?,
+            day_of_week: data[7],
+            fractions256: data[8],
+            adjust_reason: data[9],
+        };
+        if current.day_of_week > 7 {
+            return Err(invalid(format!(
+                "day of week must be 0-7, got {}",
+                current.day_of_week
+            )));
+        }
+        Ok(current)
+    }
+
+    /// Encodes the characteristic value
+    /// # Errors
+    /// Returns [`Error::InvalidPayload`] if a field is out of range
+    pub fn encode(&self) -> Result<[u8; 10], Error> {
+        if self.day_of_week > 7 {
+            return Err(invalid(format!(
+                "day of week must be 0-7, got {}",
+                self.day_of_week
+            )));
+        }
+        let mut bytes = [0; 10];
+        bytes[..7].copy_from_slice(&self.date_time.encode()?);
+        bytes[7] = self.day_of_week;
+        bytes[8] = self.fractions256;
+        bytes[9] = self.adjust_reason;
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(value: f64, expected: f64) {
+        assert!((value - expected).abs() <= expected.abs() * 1e-12, "{value} is not {expected}");
+    }
+
+    #[test]
+    fn uints_of_every_width() {
+        assert_eq!(decode_uint(&[0x2a]).unwrap(), 0x2a);
+        assert_eq!(decode_uint(&[0x34, 0x12]).unwrap(), 0x1234);
+        assert_eq!(decode_uint(&[0x56, 0x34, 0x12]).unwrap(), 0x12_3456);
+        assert_eq!(decode_uint(&[0xbc, 0x9a, 0x78, 0x56, 0x34, 0x12]).unwrap(), 0x1234_5678_9abc);
+        assert_eq!(decode_uint(&[0xff; 8]).unwrap(), u64::MAX);
+        assert!(decode_uint(&[]).is_err());
+        assert!(decode_uint(&[0; 9]).is_err());
+
+        assert_eq!(encode_uint(0x12_3456, 3).unwrap(), [0x56, 0x34, 0x12]);
+        assert_eq!(encode_uint(u64::MAX, 8).unwrap(), [0xff; 8]);
+        assert!(encode_uint(0x100, 1).is_err());
+        assert!(encode_uint(0, 0).is_err());
+    }
+
+    #[test]
+    fn sints_are_sign_extended() {
+        assert_eq!(decode_sint(&[0xfe, 0xff]).unwrap(), -2);
+        assert_eq!(decode_sint(&[0xff, 0x7f]).unwrap(), i64::from(i16::MAX));
+        assert_eq!(decode_sint(&[0x00, 0x80]).unwrap(), i64::from(i16::MIN));
+        assert_eq!(decode_sint(&[0xff, 0xff, 0xff]).unwrap(), -1);
+        assert_eq!(decode_sint(&[0xff, 0xff, 0x7f]).unwrap(), 0x7f_ffff);
+        assert!(decode_sint(&[]).is_err());
+
+        assert_eq!(encode_sint(-128, 1).unwrap(), [0x80]);
+        assert_eq!(encode_sint(-2, 3).unwrap(), [0xfe, 0xff, 0xff]);
+        assert!(encode_sint(-129, 1).is_err());
+        assert!(encode_sint(128, 1).is_err());
+    }
+
+    #[test]
+    fn ieee_754_floats() {
+        assert_eq!(decode_float32(&encode_float32(1.5)).unwrap(), 1.5);
+        assert_eq!(decode_float32(&[0x00, 0x00, 0x80, 0xbf]).unwrap(), -1.0);
+        assert_eq!(decode_float64(&encode_float64(-0.25)).unwrap(), -0.25);
+        assert!(decode_float32(&[0; 3]).is_err());
+        assert!(decode_float64(&[0; 4]).is_err());
+    }
+
+    // 36.4 °C of the Health Thermometer, mantissa 364 and exponent -1
+    #[test]
+    fn sfloats_of_the_spec() {
+        assert_eq!(encode_sfloat(364, -1).unwrap(), [0x6c, 0xf1]);
+        assert_close(decode_sfloat(&[0x6c, 0xf1]).unwrap(), 36.4);
+        assert_close(decode_sfloat(&encode_sfloat(-1, 0).unwrap()).unwrap(), -1.0);
+        assert_close(decode_sfloat(&encode_sfloat(2045, 7).unwrap()).unwrap(), 2045e7);
+        assert_close(decode_sfloat(&encode_sfloat(-2045, -8).unwrap()).unwrap(), -2045e-8);
+        assert!(encode_sfloat(2046, 0).is_err());
+        assert!(encode_sfloat(0, 8).is_err());
+        assert!(decode_sfloat(&[0x00]).is_err());
+    }
+
+    #[test]
+    fn sfloat_special_values() {
+        assert!(decode_sfloat(&[0xff, 0x07]).unwrap().is_nan());
+        assert!(decode_sfloat(&[0x00, 0x08]).unwrap().is_nan());
+        assert!(decode_sfloat(&[0x01, 0x08]).unwrap().is_nan());
+        assert_eq!(decode_sfloat(&[0xfe, 0x07]).unwrap(), f64::INFINITY);
+        assert_eq!(decode_sfloat(&[0x02, 0x08]).unwrap(), f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn floats_of_the_spec() {
+        assert_eq!(encode_float(364, -1).unwrap(), [0x6c, 0x01, 0x00, 0xff]);
+        assert_close(decode_float(&[0x6c, 0x01, 0x00, 0xff]).unwrap(), 36.4);
+        assert_eq!(encode_float(-364, 2).unwrap(), [0x94, 0xfe, 0xff, 0x02]);
+        assert_close(decode_float(&[0x94, 0xfe, 0xff, 0x02]).unwrap(), -36400.0);
+        assert!(encode_float(FLOAT_MAX_MANTISSA + 1, 0).is_err());
+        assert!(decode_float(&[0; 2]).is_err());
+    }
+
+    #[test]
+    fn float_special_values() {
+        assert!(decode_float(&[0xff, 0xff, 0x7f, 0x00]).unwrap().is_nan());
+        assert!(decode_float(&[0x00, 0x00, 0x80, 0x00]).unwrap().is_nan());
+        assert!(decode_float(&[0x01, 0x00, 0x80, 0x00]).unwrap().is_nan());
+        assert_eq!(decode_float(&[0xfe, 0xff, 0x7f, 0x00]).unwrap(), f64::INFINITY);
+        assert_eq!(decode_float(&[0x02, 0x00, 0x80, 0x00]).unwrap(), f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn cccd_values() {
+        assert_eq!(Cccd::decode(&[0x00, 0x00]).unwrap(), Cccd::default());
+        assert_eq!(Cccd::decode(&[0x01, 0x00]).unwrap(), Cccd::NOTIFY);
+        assert_eq!(Cccd::decode(&[0x02, 0x00]).unwrap(), Cccd::INDICATE);
+        let both = Cccd {
+            notifications: true,
+            indications: true,
+        };
+        assert_eq!(Cccd::decode(&[0x03, 0x00]).unwrap(), both);
+        assert_eq!(both.encode(), [0x03, 0x00]);
+        assert_eq!(Cccd::INDICATE.encode(), [0x02, 0x00]);
+        assert!(Cccd::decode(&[0x04, 0x00]).is_err());
+        assert!(Cccd::decode(&[0x00, 0x01]).is_err());
+        assert!(Cccd::decode(&[0x01]).is_err());
+    }
+
+    #[test]
+    fn alert_levels() {
+        assert_eq!(AlertLevel::decode(&[0x00]).unwrap(), AlertLevel::NoAlert);
+        assert_eq!(AlertLevel::decode(&[0x01]).unwrap(), AlertLevel::MildAlert);
+        assert_eq!(AlertLevel::decode(&[0x02]).unwrap(), AlertLevel::HighAlert);
+        assert_eq!(AlertLevel::MildAlert.encode(), [0x01]);
+        assert!(AlertLevel::decode(&[0x03]).is_err());
+        assert!(AlertLevel::decode(&[0x01, 0x00]).is_err());
+    }
+
+    #[test]
+    fn battery_levels() {
+        assert_eq!(decode_battery_level(&[0x00]).unwrap(), 0);
+        assert_eq!(decode_battery_level(&[0x64]).unwrap(), 100);
+        assert!(decode_battery_level(&[0x65]).is_err());
+        assert!(decode_battery_level(&[]).is_err());
+        assert_eq!(encode_battery_level(42).unwrap(), [42]);
+        assert!(encode_battery_level(101).is_err());
+    }
+
+    // 2024-03-15 13:45:30
+    const DATE_TIME: [u8; 7] = [0xe8, 0x07, 3, 15, 13, 45, 30];
+
+    #[test]
+    fn date_times() {
+        let date_time = DateTime::decode(&DATE_TIME).unwrap();
+        assert_eq!(
+            date_time,
+            DateTime {
+                year: 2024,
+                month: 3,
+                day: 15,
+                hours: 13,
+                minutes: 45,
+                seconds: 30,
+            }
+        );
+        assert_eq!(date_time.encode().unwrap(), DATE_TIME);
+        // unknown fields are 0
+        assert!(DateTime::decode(&[0; 7]).is_ok());
+        let mut invalid_month = DATE_TIME;
+        invalid_month[2] = 13;
+        assert!(DateTime::decode(&invalid_month).is_err());
+        let mut before_gregorian = DATE_TIME;
+        before_gregorian[..2].copy_from_slice(&1581u16.to_le_bytes());
+        assert!(DateTime::decode(&before_gregorian).is_err());
+        assert!(DateTime::decode(&DATE_TIME[..6]).is_err());
+    }
+
+    #[test]
+    fn current_times() {
+        let mut data = [0; 10];
+        data[..7].copy_from_slice(&DATE_TIME);
+        // a friday, half a second, adjusted manually
+        data[7..].copy_from_slice(&[5, 0x80, 0x01]);
+        let current = CurrentTime::decode(&data).unwrap();
+        assert_eq!(current.date_time, DateTime::decode(&DATE_TIME).unwrap());
+        assert_eq!(current.day_of_week, 5);
+        assert_eq!(current.fractions256, 0x80);
+        assert_eq!(current.adjust_reason, 0x01);
+        assert_eq!(current.encode().unwrap(), data);
+        data[7] = 8;
+        assert!(CurrentTime::decode(&data).is_err());
+        assert!(CurrentTime::decode(&data[..9]).is_err());
+    }
+}
@@ -1,16 +1,51 @@
-use crate::error::Error;
-use btleplug::models::{self, fmt_addr, BleDevice, ScanFilter, Service};
-use btleplug::api::CentralEvent;
-use btleplug::api::{Central, Characteristic, Manager as _, Peripheral as _};
+use crate::authorization::AuthorizationStatus;
+use crate::battery::{BatteryLevel, BatteryMonitor};
+use crate::budget::Budget;
+use crate::clock::{Clocks, TimedNotification};
+use crate::connections::{Connection, ConnectionTasks};
+#[cfg(target_os = "android")]
+use crate::scan_throttle::THROTTLE_SANITY_WINDOW;
+use crate::device_cache::{DeviceCache, DeviceCandidate, DeviceKey};
+use crate::discovery::DiscoverOptions;
+use crate::error::{Error, GattStatus};
+use crate::events::{
+    guard_callback, AdapterState, BleEvent, BondChange, CallbackKind, DisconnectReason, Publisher,
+};
+#[cfg(not(any(target_os = "macos", target_os = "ios")))]
+use crate::gatt_values::Cccd;
+use crate::interference::{Interference, InterferenceWatch, SubscriptionReport};
+use crate::metrics::BleMetrics;
+use crate::models::{
+    self, fmt_addr, AdapterInfo, AdapterSelector, AdapterSighting, AddressPolicy, BleDevice,
+    CharacteristicProperties, CharacteristicRef, DeviceInfo, PeripheralProperties, PnpId,
+    ScanFilter, ScanNameFilter, Service, ServiceInfo, DEVICE_INFORMATION_SERVICE,
+    PNP_ID_CHARACTERISTIC,
+};
+use crate::mirror::Mirrors;
+use crate::reconnect::{PendingWaits, ReconnectProgress};
+use crate::runtime;
+use crate::scan_consumers::ScanConsumers;
+use crate::scan_throttle::{ScanStart, ScanStarts};
+use crate::scan_budget::{EventBudget, ScanEventBudget};
+use crate::setup::{ConnectionPriority, SetupAction, SetupStepResult};
+use crate::store::BlecStore;
+use crate::timeline::{TimelineOperation, Timelines};
+use crate::transfer::PausedTransfers;
+use btleplug::api::{CentralEvent, CentralState};
+use btleplug::api::{
+    Central, CharPropFlags, Characteristic, Descriptor, Manager as _, Peripheral as _,
+};
 use btleplug::platform::PeripheralId;
+use futures::future::{join_all, select, Either};
 use futures::{Stream, StreamExt};
 use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
-use tauri::async_runtime;
-use tokio::sync::{mpsc, watch, Mutex};
-use tokio::time::sleep;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::{mpsc, watch, Mutex, Notify};
+use tokio::time::{sleep, timeout};
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
@@ -19,726 +54,8042 @@ use crate::android::{Adapter, Manager, Peripheral};
 #[cfg(not(target_os = "android"))]
 use btleplug::platform::{Adapter, Manager, Peripheral};
 
-type ListenerCallback = Arc<dyn Fn(&[u8]) + Send + Sync>;
-struct Listener {
-    uuid: Uuid,
-    callback: ListenerCallback,
+pub(crate) type ListenerCallback = Arc<dyn Fn(&[u8]) + Send + Sync>;
+type DeviceEnricher = Arc<dyn Fn(&PeripheralProperties) -> Option<serde_json::Value> + Send + Sync>;
+type DeviceFingerprint = Arc<dyn Fn(&PeripheralProperties) -> Option<String> + Send + Sync>;
+pub(crate) struct Listener {
+    pub(crate) id: ListenerHandle,
+    pub(crate) uuid: Uuid,
+    pub(crate) sink: ListenerSink,
 }
 
-struct HandlerState {
-    //characs: HashMap<String, HashSet<Characteristic>>,
-    listen_handle: Option<async_runtime::JoinHandle<()>>,
-    on_disconnect: Option<Mutex<Box<dyn Fn() + Send>>>,
-    connection_update_channel: Vec<mpsc::Sender<bool>>,
-    scan_update_channel: Vec<mpsc::Sender<bool>>,
-    scan_task: Option<tokio::task::JoinHandle<()>>,
+#[derive(Clone)]
+pub(crate) enum ListenerSink {
+    Inline(ListenerCallback),
+    BlockingPool(ListenerCallback),
+    Channel(mpsc::Sender<Vec<u8>>),
+    Timed(mpsc::Sender<TimedNotification>),
 }
 
-/*
-impl HandlerState {
-    fn set_charac(&self, device_id: String, uuid: Uuid, charac: Characteristic) {
-        let mut characs = self.characs.entry(&device_id).or_insert(HashSet::default());
-        characs.insert(charac);
-    }
+/// How notifications are handed to a callback registered with [`Handler::subscribe_with`]
+/// To receive notifications without a callback use [`Handler::subscribe_receiver`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DeliveryMode {
+    /// The callback runs on the notification task, in arrival order
+    /// A slow callback delays every later notification of the device, so it should only hand
+    /// the data off.
+    #[default]
+    Inline,
+    /// Every notification runs the callback on the blocking thread pool
+    /// Slow callbacks do not delay other notifications, but there is no backpressure and the
+    /// order of consecutive notifications is not preserved.
+    BlockingPool,
+}
 
-    fn get_charac(&self, device_id: String, uuid: Uuid) -> Result<&Characteristic, Error> {
-        if let Some(characs) = self.characs.get(&device_id) {
-            let charac = characs.iter().find(|c| c.uuid == uuid);
-            charac.ok_or(Error::CharacNotAvailable(uuid.to_string()))
-        } else {
-            Err(Error::CharacNotAvailable(uuid.to_string()))
+/// Identifies a single listener registered with [`Handler::subscribe`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(transparent)]
+pub struct ListenerHandle(
+    #[cfg_attr(feature = "ts-export", ts(type = "number"))] pub(crate) u64,
+);
+
+/// Keeps the event loop of [`Handler::spawn_event_loop`] running, dropping it stops the loop
+#[must_use = "the event loop stops when the guard is dropped"]
+pub struct EventLoopGuard {
+    handler: &'static Handler,
+    tasks: Vec<runtime::JoinHandle<()>>,
+}
+
+impl Drop for EventLoopGuard {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
         }
+        self.handler
+            .event_loop_running
+            .store(false, Ordering::Release);
     }
-} */
+}
 
-pub struct Handler {
-    devices: Arc<Mutex<HashMap<String, Peripheral>>>,
-    adapter: Arc<Adapter>,
-    notify_listeners: Arc<Mutex<Vec<Listener>>>,
-    connected_rx: watch::Receiver<bool>,
-    connected_tx: watch::Sender<bool>,
-    state: Mutex<HandlerState>,
-    connected_dev: Mutex<Option<Peripheral>>,
+/// A notification frame delivered through a [`SessionHandle`]
+#[derive(Debug, Clone, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct SessionFrame {
+    /// The characteristic the frame was received on
+    pub characteristic: Uuid,
+    /// Arrival time in microseconds since the session was opened
+    #[cfg_attr(feature = "ts-export", ts(type = "number"))]
+    pub timestamp_us: u64,
+    pub data: Vec<u8>,
 }
 
-async fn get_central() -> Result<Adapter, Error> {
-    let manager = Manager::new().await?;
-    let adapters = manager.adapters().await?;
-    let central = adapters.into_iter().next().ok_or(Error::NoAdapters)?;
-    Ok(central)
+pub(crate) struct Session {
+    id: u64,
+    characs: Vec<Uuid>,
+    opened: Instant,
+    tx: mpsc::UnboundedSender<SessionFrame>,
 }
 
-impl Handler {
-    pub(crate) async fn new() -> Result<Self, Error> {
-        let central = get_central().await?;
-        let (connected_tx, connected_rx) = watch::channel(false);
-        Ok(Self {
-            devices: Arc::new(Mutex::new(HashMap::new())),
-            adapter: Arc::new(central),
-            notify_listeners: Arc::new(Mutex::new(vec![])),
-            connected_rx,
-            connected_tx,
-            connected_dev: Mutex::new(None),
-            state: Mutex::new(HandlerState {
-                on_disconnect: None,
-                connection_update_channel: vec![],
-                scan_task: None,
-                scan_update_channel: vec![],
-                listen_handle: None,
-                //characs: HashMap::default(),
-            }),
-        })
+/// Receiving end of a streaming session opened with [`Handler::open_session`]
+/// Frames of all characteristics in the session are delivered in arrival order. Dropping the
+/// handle without [`Handler::close_session`] closes the session with the next notification of
+/// the device or the next [`Handler::open_session`].
+pub struct SessionHandle {
+    id: u64,
+    rx: mpsc::UnboundedReceiver<SessionFrame>,
+}
+
+impl SessionHandle {
+    /// Returns the id used to close the session with [`Handler::close_session`]
+    pub fn id(&self) -> u64 {
+        self.id
     }
 
-    /// Returns true if a device is connected
-    pub fn is_connected(&self) -> bool {
-        *self.connected_rx.borrow()
+    /// Waits for the next frame
+    /// Returns `None` once the session is closed or the device disconnected
+    pub async fn recv(&mut self) -> Option<SessionFrame> {
+        self.rx.recv().await
     }
+}
 
-    /// Returns true if the adapter is scanning
-    pub async fn is_scanning(&self) -> bool {
-        if let Some(handle) = &self.state.lock().await.scan_task {
-            !handle.is_finished()
-        } else {
-            false
+pub(crate) struct Interceptor {
+    uuid: Uuid,
+    tee: bool,
+    tx: mpsc::UnboundedSender<Vec<u8>>,
+}
+
+/// Receiving end of an [`Interceptor`], see [`Handler::intercept`]
+/// The receiver is closed when the device disconnects, the sender is only held by the
+/// interceptor.
+struct Interception {
+    connection: Arc<Connection>,
+    uuid: Uuid,
+    /// The characteristic was subscribed before the interceptor was added
+    subscribed: bool,
+    rx: mpsc::UnboundedReceiver<Vec<u8>>,
+}
+
+/// Options for [`Handler::request_multi`]
+#[derive(Debug, Clone)]
+pub struct MultiResponseOptions {
+    /// Stop after this many frames were received
+    pub max_frames: Option<usize>,
+    /// Maximum time to wait for the complete response
+    pub timeout: Duration,
+    /// Also deliver the response frames to the normal listeners and sessions
+    pub tee: bool,
+    /// Caps writing the request and collecting the response, `timeout` still applies to the
+    /// collection. Exceeding it fails with [`Error::TotalTimeout`] instead of returning the
+    /// frames received so far.
+    pub total_timeout: Option<Duration>,
+}
+
+impl Default for MultiResponseOptions {
+    fn default() -> Self {
+        Self {
+            max_frames: None,
+            timeout: Duration::from_secs(5),
+            tee: false,
+            total_timeout: None,
         }
     }
+}
 
-    /// Takes a sender that will be used to send changes in the scanning status
-    /// # Example
-    /// ```no_run
-    /// use tauri::async_runtime;
-    /// use tokio::sync::mpsc;
-    /// async_runtime::block_on(async {
-    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
-    ///     let (tx, mut rx) = mpsc::channel(1);
-    ///     handler.set_scanning_update_channel(tx).await;
-    ///     while let Some(scanning) = rx.recv().await {
-    ///         println!("Scanning: {scanning}");
-    ///     }
-    /// });
-    /// ```
-    pub async fn set_scanning_update_channel(&self, tx: mpsc::Sender<bool>) {
-        self.state.lock().await.scan_update_channel.push(tx);
+/// Response collected by [`Handler::request_multi`]
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct MultiResponse {
+    /// Received frames in arrival order
+    pub frames: Vec<Vec<u8>>,
+    /// True if the terminator matched or `max_frames` was reached before the timeout
+    pub complete: bool,
+}
+
+/// Names of the running critical operations by id, read by the exit guard of the plugin
+#[derive(Default)]
+struct CriticalOperations {
+    running: std::sync::Mutex<HashMap<u64, String>>,
+    next_id: AtomicU64,
+    /// Notified whenever an operation ended
+    done: Notify,
+}
+
+impl CriticalOperations {
+    fn running(&self) -> std::sync::MutexGuard<'_, HashMap<u64, String>> {
+        self.running
+            .lock()
+            .expect("critical operations lock poisoned")
     }
 
-    /// Takes a sender that will be used to send changes in the connection status
-    /// # Example
-    /// ```no_run
-    /// use tauri::async_runtime;
-    /// use tokio::sync::mpsc;
-    /// async_runtime::block_on(async {
-    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
-    ///     let (tx, mut rx) = mpsc::channel(1);
-    ///     handler.set_connection_update_channel(tx).await;
-    ///     while let Some(connected) = rx.recv().await {
-    ///         println!("Connected: {connected}");
-    ///     }
-    /// });
-    /// ```
-    pub async fn set_connection_update_channel(&self, tx: mpsc::Sender<bool>) {
-        self.state.lock().await.connection_update_channel.push(tx);
+    fn start(&self, name: &str) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.running().insert(id, name.to_string());
+        id
     }
 
-    /// Connects to the given address
-    /// If a callback is provided, it will be called when the device is disconnected.
-    /// Because connecting sometimes fails especially on android, this method tries up to 3 times
-    /// before returning an error
-    /// # Errors
-    /// Returns an error if no devices are found, if the device is already connected,
-    /// if the connection fails, or if the service/characteristics discovery fails
-    /// # Example
-    /// ```no_run
-    /// use tauri::async_runtime;
-    /// async_runtime::block_on(async {
-    ///    let handler = tauri_plugin_blec::get_handler().unwrap();
-    ///    handler.connect("00:00:00:00:00:00", Some(Box::new(|| println!("disconnected")))).await.unwrap();
-    /// });
-    /// ```
-    pub async fn connect(
-        &'static self,
-        address: &str,
-        on_disconnect: Option<Box<dyn Fn() + Send>>,
-    ) -> Result<Vec<btleplug::models::Service>, Error> {
-        if self.devices.lock().await.len() == 0 {
-            self.discover(None, 1000, ScanFilter::None).await?;
-        }
-        // cancel any running discovery
-        let _ = self.stop_scan().await;
-        // connect to the given address
-        // try up to 3 times before returning an error
-        let mut connected = Ok(());
-        for i in 0..3 {
-            if let Err(e) = self.connect_device(address).await {
-                if i < 2 {
-                    warn!("Failed to connect device, retrying in 1s: {e}");
-                    sleep(Duration::from_secs(1)).await;
-                    continue;
-                }
-                connected = Err(e);
-            } else {
-                connected = Ok(());
-                break;
+    fn end(&self, id: u64) {
+        self.running().remove(&id);
+        self.done.notify_waiters();
+    }
+
+    fn names(&self) -> Vec<String> {
+        self.running().values().cloned().collect()
+    }
+
+    /// Returns true if no operation runs anymore once the wait ended
+    async fn wait(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let done = self.done.notified();
+            if self.running().is_empty() {
+                return true;
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() || tokio::time::timeout(remaining, done).await.is_err() {
+                return self.running().is_empty();
             }
         }
-        if let Err(e) = connected {
-            *self.connected_dev.lock().await = None;
-            let _ = self.connected_tx.send(false);
-            error!("Failed to connect device: {e}");
-            return Err(e);
-        }
-        let mut state = self.state.lock().await;
-        // set callback to run on disconnect
-        if let Some(cb) = on_disconnect {
-            state.on_disconnect = Some(Mutex::new(cb));
-        }
-        // discover service/characteristics
-        let services = self.connect_services(&mut state).await?;
-        
-        // start background task for notifications
-        state.listen_handle = Some(async_runtime::spawn(listen_notify(
-            self.connected_dev.lock().await.clone(),
-            self.notify_listeners.clone(),
-        )));
-        
-        Ok(services)
     }
+}
 
-    async fn connect_services(&self, state: &mut HandlerState) -> Result<Vec<btleplug::models::Service>, Error> {
-        let device = self.connected_dev.lock().await;
-        let device = device.as_ref().ok_or(Error::NoDeviceConnected)?;
-        let services = device.services();
-        if services.is_empty() {
-            device.discover_services().await?;
-            //services = device.services();
-        }
-        Ok(device.services().into_iter().map(|x|x.into()).collect())
+/// A running operation that must not be interrupted by closing the app,
+/// started with [`Handler::begin_critical_operation`]
+/// The operation ends when the guard is dropped.
+pub struct CriticalOperation {
+    id: u64,
+    operations: Arc<CriticalOperations>,
+}
+
+impl Drop for CriticalOperation {
+    fn drop(&mut self) {
+        self.operations.end(self.id);
     }
+}
 
-    async fn connect_device(&self, address: &str) -> Result<(), Error> {
-        debug!("connecting to {address}",);
-        let mut connected_rx = self.connected_rx.clone();
-        let devices = self.devices.lock().await;
-        let device = devices
-            .get(address)
-            .ok_or(Error::UnknownPeripheral(address.to_string()))?;
-        *self.connected_dev.lock().await = Some(device.clone());
-        if device.is_connected().await? {
-            debug!("Device already connected");
-            self.connected_tx
-                .send(true)
-                .expect("failed to send connected update");
-        } else {
-            assert!(
-                !(*connected_rx.borrow_and_update()),
-                "connected_rx is true without device being connected, this is a bug"
-            );
-            debug!("Connecting to device");
-            device.connect().await?;
-            debug!("waiting for connection event");
-            // wait for the actual connection to be established
-            connected_rx
-                .changed()
-                .await
-                .expect("failed to wait for connection event");
-            debug!("Connecting done");
-            if !*self.connected_rx.borrow() {
-                // still not connected
-                return Err(Error::ConnectionFailed);
-            }
+/// How [`Handler::recv_data`] reads a characteristic
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub enum ReadStrategy {
+    /// Read once
+    #[default]
+    Single,
+    /// Read twice and return the second value, for devices where the first read arms the value
+    DoubleRead,
+    /// Read until two consecutive reads return the same value or the given number of reads
+    /// was made, returns the last value
+    RetryUntilStable(u32),
+}
+
+/// Options applied to all operations on a characteristic,
+/// set with [`Handler::set_characteristic_options`]
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase", default)]
+pub struct CharacteristicOptions {
+    #[cfg_attr(feature = "ts-export", ts(optional, as = "Option<ReadStrategy>"))]
+    pub read_strategy: ReadStrategy,
+    /// Write that must be done on the connection before the characteristic is subscribed
+    #[cfg_attr(feature = "ts-export", ts(optional))]
+    pub requires_write: Option<RequiredWrite>,
+    /// Whether subscribing enables notifications or indications
+    #[cfg_attr(feature = "ts-export", ts(optional, as = "Option<SubscribeMode>"))]
+    pub subscribe_mode: SubscribeMode,
+}
+
+/// What [`Handler::subscribe`] enables in the client characteristic configuration of a
+/// characteristic, set with [`CharacteristicOptions::subscribe_mode`]
+/// Indications are confirmed by the client, the data arrives through the same listeners as
+/// notifications. The android bridge enables exactly the kind of the mode, on the other
+/// platforms the OS stack picks the kind and prefers notifications if a characteristic
+/// supports both, there the mode only decides whether a characteristic is accepted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub enum SubscribeMode {
+    /// Notifications if the characteristic supports them, indications otherwise
+    #[default]
+    Auto,
+    Notify,
+    Indicate,
+}
+
+/// Ordering constraint of [`CharacteristicOptions::requires_write`]
+/// Some devices break their notifications if a characteristic is subscribed before e.g. a mode
+/// byte was written to a control characteristic. The constraint is met by a successful write of
+/// exactly `data` to `characteristic` and reset on every connect.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct RequiredWrite {
+    pub characteristic: Uuid,
+    #[cfg_attr(feature = "ts-export", ts(type = "Uint8Array | Array<number>"))]
+    pub data: Vec<u8>,
+    /// Write `data` with response before subscribing instead of failing the subscribe with
+    /// [`Error::SubscribeBeforeWrite`]
+    #[serde(default)]
+    #[cfg_attr(feature = "ts-export", ts(optional, as = "Option<bool>"))]
+    pub auto: bool,
+}
+
+/// Retries of reads and writes failing with transient errors, set with
+/// [`Handler::set_retry_policy`]
+/// ATT Insufficient Resources (0x11) is retried after `backoff` times the attempt number. Android's
+/// GATT_CONGESTED pauses the operation queue for a fixed time before retrying.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct RetryPolicy {
+    /// Total number of attempts, 1 disables retries
+    pub max_attempts: u32,
+    #[serde(with = "duration_ms", rename = "backoffMs")]
+    #[cfg_attr(feature = "ts-export", ts(type = "number"))]
+    pub backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            backoff: Duration::from_millis(50),
         }
+    }
+}
 
-        self.send_connection_update(true).await;
-        Ok(())
+/// Reconnects after the link was lost, see [`ConnectOptions::reconnect`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct ReconnectPolicy {
+    /// Connects tried before the device is given up
+    pub max_attempts: u32,
+    /// Wait before the first attempt, doubled after every failed attempt up to 30 seconds
+    #[serde(with = "duration_ms", rename = "backoffMs")]
+    #[cfg_attr(feature = "ts-export", ts(type = "number"))]
+    pub backoff: Duration,
+}
+
+mod duration_ms {
+    use std::time::Duration;
+
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Duration, D::Error> {
+        let ms: u64 = serde::Deserialize::deserialize(d)?;
+        Ok(Duration::from_millis(ms))
     }
+}
 
-    /// Disconnects from the connected device
-    /// This triggers a disconnect and then waits for the actual disconnect event from the adapter
-    /// # Errors
-    /// Returns an error if no device is connected or if the disconnect fails
-    /// # Panics
-    /// panics if there is an error with handling the internal disconnect event
-    pub async fn disconnect(&self) -> Result<(), Error> {
-        debug!("disconnect triggered by user");
-        let mut connected_rx = self.connected_rx.clone();
-        {
-            // Scope is important to not lock device while waiting for disconnect event
-            let dev = self.connected_dev.lock().await;
-            if let Some(dev) = dev.as_ref() {
-                if let Ok(true) = dev.is_connected().await {
-                    assert!(
-                        (*connected_rx.borrow_and_update()),
-                        "connected_rx is false with a device being connected, this is a bug"
-                    );
-                    dev.disconnect().await?;
+/// Pause of the operation queue after the stack reported congestion
+const CONGESTION_PAUSE: Duration = Duration::from_millis(100);
+
+/// Errors worth retrying after a short pause
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TransientError {
+    InsufficientResources,
+    Congested,
+}
+
+impl TransientError {
+    /// Classifies the platform error by its status, or by the name of the error on platforms
+    /// without one
+    fn classify(e: &btleplug::Error) -> Option<Self> {
+        match gatt_status(e) {
+            Some(ATT_INSUFFICIENT_RESOURCES) => Some(Self::InsufficientResources),
+            Some(GATT_CONGESTED) => Some(Self::Congested),
+            Some(_) => None,
+            None => {
+                let msg = error_name(e);
+                if msg.contains("insufficientresources") {
+                    Some(Self::InsufficientResources)
+                } else if msg.contains("congested") {
+                    Some(Self::Congested)
                 } else {
-                    debug!("device is not connected");
-                    return Err(Error::NoDeviceConnected);
+                    None
                 }
-            } else {
-                debug!("no device connected");
-                return Err(Error::NoDeviceConnected);
             }
         }
-        debug!("waiting for disconnect event");
-        // the change will be triggered by handle_event -> handle_disconnect which runs in another
-        // task
-        connected_rx
-            .changed()
-            .await
-            .expect("failed to wait for disconnect event");
-        if *self.connected_rx.borrow() {
-            // still connected
-            return Err(Error::DisconnectFailed);
+    }
+}
+
+/// ATT errors of the Bluetooth Core Specification, Vol 3, Part F, 3.4.1.1
+const ATT_INSUFFICIENT_AUTHENTICATION: u8 = 0x05;
+const ATT_INSUFFICIENT_ENCRYPTION: u8 = 0x0f;
+const ATT_INSUFFICIENT_RESOURCES: u8 = 0x11;
+/// Statuses only reported by android
+const GATT_AUTH_FAIL: u8 = 0x89;
+const GATT_CONGESTED: u8 = 0x8f;
+
+/// Status of the failed operation
+/// android reports it as [`GattStatus`], BlueZ as `ATT error: 0x..` in the message.
+fn gatt_status(e: &btleplug::Error) -> Option<u8> {
+    if let btleplug::Error::Other(source) = e {
+        if let Some(status) = source.downcast_ref::<GattStatus>() {
+            return Some(status.status);
         }
-        Ok(())
     }
+    let msg = e.to_string().to_lowercase();
+    let (_, code) = msg.split_once("att error: 0x")?;
+    u8::from_str_radix(code.get(..2)?, 16).ok()
+}
 
-    /// Clears internal state, updates connected flag and calls disconnect callback
-    async fn handle_disconnect(&self, peripheral_id: PeripheralId) -> Result<(), Error> {
-        let connected = self
-            .connected_dev
-            .lock()
-            .await
-            .as_ref()
-            .map(btleplug::api::Peripheral::id);
-        if !connected.as_ref().is_some_and(|c| *c == peripheral_id) {
-            // event not for currently connected device, ignore
-            warn!("Unexpected disconnect event for device {peripheral_id}, connected device is {connected:?}",);
-            return Ok(());
+/// Lowercase message of the error without spaces and underscores, so the ATT error names
+/// match however the platform spells them, e.g. `InsufficientEncryption` on Windows
+fn error_name(e: &btleplug::Error) -> String {
+    e.to_string()
+        .to_lowercase()
+        .chars()
+        .filter(|c| *c != ' ' && *c != '_')
+        .collect()
+}
+
+/// Returns true if the operation failed because the link is not authenticated or encrypted
+fn is_auth_failure(e: &btleplug::Error) -> bool {
+    match gatt_status(e) {
+        Some(status) => matches!(
+            status,
+            ATT_INSUFFICIENT_AUTHENTICATION | ATT_INSUFFICIENT_ENCRYPTION | GATT_AUTH_FAIL
+        ),
+        None => {
+            let msg = error_name(e);
+            msg.contains("insufficientauthentication") || msg.contains("insufficientencryption")
         }
-        {
-            debug!("locking state for disconnect");
-            let mut state = self.state.lock().await;
-            info!("disconnecting");
-            *self.connected_dev.lock().await = None;
-            if let Some(handle) = state.listen_handle.take() {
-                handle.abort();
-            }
-            *self.notify_listeners.lock().await = vec![];
-            if let Some(on_disconnect) = &state.on_disconnect {
-                let callback = on_disconnect.lock().await;
-                callback();
-            }
-            //state.characs.clear();
-        }
-        self.send_connection_update(false).await;
-        self.connected_tx
-            .send(false)
-            .expect("failed to send connected update");
-        Ok(())
     }
+}
 
-    /// Scans for `timeout` milliseconds and periodically sends discovered devices
-    /// to the given channel.
-    /// A task is spawned to handle the scan and send the devices, so the function
-    /// returns immediately.
-    ///
-    /// A Variant of [`ScanFilter`] can be provided to filter the discovered devices
-    ///
-    /// # Errors
-    /// Returns an error if starting the scan fails
-    /// # Panics
-    /// Panics if there is an error getting devices from the adapter
-    /// # Example
-    /// ```no_run
-    /// use tauri::async_runtime;
-    /// use tokio::sync::mpsc;
-    /// use tauri_plugin_blec::models::ScanFilter;
-    ///
-    /// async_runtime::block_on(async {
-    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
-    ///     let (tx, mut rx) = mpsc::channel(1);
-    ///     handler.discover(Some(tx),1000, ScanFilter::None).await.unwrap();
-    ///     while let Some(devices) = rx.recv().await {
-    ///         println!("Discovered {devices:?}");
-    ///     }
-    /// });
-    /// ```
-    pub async fn discover(
-        &'static self,
-        tx: Option<mpsc::Sender<Vec<BleDevice>>>,
-        timeout: u64,
-        filter: ScanFilter,
-    ) -> Result<(), Error> {
-        {
-            let mut state = self.state.lock().await;
-            // stop any ongoing scan
-            if let Some(handle) = state.scan_task.take() {
-                handle.abort();
-                self.adapter.stop_scan().await?;
-            }
-            // start a new scan
-            self.adapter
-                .start_scan(btleplug::api::ScanFilter::default())
-                .await?;
+/// Response of a write issued by [`Handler::send_data_with_response`]
+/// ATT write responses carry no data, devices that answer with data do so in a notification,
+/// see [`Handler::request_multi`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct WriteResponse {
+    /// ATT status of the response, 0 on success
+    /// Application errors of the device (0x80 to 0x9F) are only reported here on android, the
+    /// other platforms fail the write with the error of the OS.
+    pub status: u8,
+}
+
+impl WriteResponse {
+    /// Returns true if the device accepted the write
+    pub fn is_success(self) -> bool {
+        self.status == 0
+    }
+}
+
+/// Result of a read issued by [`Handler::recv_from_all`]
+#[derive(Debug, Clone, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct TimedRead {
+    /// The value read, `None` if the read failed
+    pub data: Option<Vec<u8>>,
+    pub error: Option<String>,
+    /// Time from issuing the read until the result arrived, including time spent waiting for
+    /// earlier operations on the device
+    #[cfg_attr(feature = "ts-export", ts(type = "number"))]
+    pub latency_us: u64,
+}
+
+#[cfg(target_os = "android")]
+const DEFAULT_MAX_CONNECTIONS: usize = 5;
+#[cfg(not(target_os = "android"))]
+const DEFAULT_MAX_CONNECTIONS: usize = 10;
+
+/// Duration of the scan used by [`Handler::connect`] to find unknown or lost devices, see
+/// [`ConnectOptions::scan_timeout_ms`]
+pub(crate) const CONNECT_SCAN_TIMEOUT: u64 = 2000;
+
+/// Longest wait between two attempts of [`ConnectOptions::reconnect`]
+const MAX_RECONNECT_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Time a non flushing disconnect waits for the running operation before forcing the disconnect
+const IN_FLIGHT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Time the platform gets to confirm a disconnect before it fails with
+/// [`Error::DisconnectFailed`]
+const DISCONNECT_EVENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time a device must stay missing during a scan before it is reported as lost
+const DEFAULT_REMOVAL_DEBOUNCE: Duration = Duration::from_secs(3);
+
+/// Time since its last advertisement a device counts as present, two scan intervals
+#[cfg(not(target_os = "android"))]
+const ADVERTISING_WINDOW: Duration = Duration::from_millis(400);
+
+/// RSSI change in dBm that publishes a device again, smaller changes are scan noise
+const RSSI_CHANGE_THRESHOLD: i16 = 5;
+
+/// [`BlecStore`] key of the addresses of previously connected devices
+const PREVIOUSLY_CONNECTED_KEY: &str = "previously_connected";
+
+/// Time without central events after which the event loop is checked while a device is
+/// connected or a scan runs
+const DEFAULT_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Time the adapter gets to answer the liveness check of the heartbeat
+const LIVENESS_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Time the user and the device get to complete pairing
+pub(crate) const BOND_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How often the bond states are read from BlueZ, it reports no event for them
+#[cfg(target_os = "linux")]
+const BOND_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// First wait before the event loop tries again to acquire the event stream, doubled after
+/// every failed attempt up to [`EVENT_STREAM_MAX_RETRY_DELAY`]
+const EVENT_STREAM_RETRY_DELAY: Duration = Duration::from_secs(1);
+const EVENT_STREAM_MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// Time [`Handler::stop_scan`] waits for the scan loop to finish before aborting it
+const SCAN_STOP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Opcode and handle of an ATT write, subtracted from the MTU
+pub(crate) const ATT_WRITE_HEADER: usize = 3;
+
+/// MTU of a link until a larger one is negotiated
+#[cfg(target_os = "android")]
+const DEFAULT_ATT_MTU: u16 = 23;
+
+/// Behavior of [`Handler::connect`] when the connection limit is reached
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub enum ConnectionLimitPolicy {
+    /// Wait until another connection is closed
+    #[default]
+    Queue,
+    /// Fail with [`Error::ConnectionLimitReached`]
+    FailFast,
+}
+
+/// Limit of simultaneous connections a connect is counted against, see
+/// [`ConnectOptions::connection_limit`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionLimit {
+    pub max: usize,
+    #[serde(default)]
+    #[cfg_attr(feature = "ts-export", ts(optional, as = "Option<ConnectionLimitPolicy>"))]
+    pub policy: ConnectionLimitPolicy,
+}
+
+struct ConnectionSlots {
+    max: usize,
+    policy: ConnectionLimitPolicy,
+    active: usize,
+}
+
+/// Stage of the connection, data operations only run in [`ConnectionState::Ready`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub enum ConnectionState {
+    #[default]
+    Disconnected,
+    /// Scanning for the device or establishing the link
+    Connecting,
+    /// Linked, the services are resolved, the device is bonded and the setup steps run
+    DiscoveringServices,
+    /// Connected and set up, notifications are delivered
+    Ready,
+    Disconnecting,
+    /// The link was lost and the device is being connected again, see [`ConnectOptions::reconnect`]
+    Reconnecting,
+}
+
+/// Snapshot of the handler state returned by [`Handler::get_state`]
+#[derive(Debug, Clone, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct BleState {
+    pub connected: bool,
+    pub connection_state: ConnectionState,
+    pub scanning: bool,
+    /// Number of connections counted against the connection limit
+    pub connections: usize,
+    pub connection_limit: usize,
+    pub connection_limit_policy: ConnectionLimitPolicy,
+    pub authorization: AuthorizationStatus,
+    /// Latest level of [`Handler::start_battery_monitor`], kept after a disconnect
+    pub battery: Option<BatteryLevel>,
+}
+
+/// Health of the handler returned by [`Handler::health`]
+#[derive(Debug, Clone, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct Health {
+    /// Set when the central event stream stopped yielding and the adapter failed the liveness check
+    pub event_loop_degraded: bool,
+    /// Milliseconds since the last central event or successful adapter poll
+    #[cfg_attr(feature = "ts-export", ts(type = "number"))]
+    pub last_event_ms: u64,
+}
+
+/// Result of [`Handler::is_known_device`]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct KnownDevice {
+    /// The address is a MAC address, or a uuid on apple platforms
+    pub valid_format: bool,
+    /// The device was discovered by a scan and not removed by the adapter since
+    pub in_cache: bool,
+    /// Bond state from the last connect with [`ConnectOptions::require_bonded`],
+    /// `None` if it was never checked
+    pub bonded: Option<bool>,
+    /// Milliseconds since the unix epoch when the device was last reported by a scan
+    #[cfg_attr(feature = "ts-export", ts(type = "number | null"))]
+    pub last_seen: Option<u64>,
+}
+
+/// Step of [`Handler::forget_device`] that can fail without stopping the others
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub enum ForgetStep {
+    Disconnect,
+    RemoveBond,
+}
+
+/// Step of [`Handler::forget_device`] that failed
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct ForgetFailure {
+    pub step: ForgetStep,
+    pub error: String,
+}
+
+/// Result of [`Handler::forget_device`], also published as [`BleEvent::DeviceForgotten`]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct ForgetReport {
+    pub address: String,
+    /// The device was connected and is disconnected now
+    pub disconnected: bool,
+    /// The OS bond was removed, false if the device was not bonded
+    pub bond_removed: bool,
+    /// Steps that failed, the state of the plugin was cleared anyway
+    pub failures: Vec<ForgetFailure>,
+}
+
+impl ForgetReport {
+    fn fail(&mut self, step: ForgetStep, e: &Error) {
+        warn!("failed to forget {}, step {step:?}: {e}", self.address);
+        self.failures.push(ForgetFailure {
+            step,
+            error: e.to_string(),
+        });
+    }
+}
+
+/// Declarative fingerprint for [`Handler::set_fingerprint_rule`]
+/// The key is built from the bytes `start..end` of the selected advertisement field.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(tag = "source", rename_all = "camelCase")]
+pub enum FingerprintRule {
+    #[serde(rename_all = "camelCase")]
+    ManufacturerData {
+        company_id: u16,
+        start: usize,
+        end: usize,
+    },
+    #[serde(rename_all = "camelCase")]
+    ServiceData { service: Uuid, start: usize, end: usize },
+}
+
+impl FingerprintRule {
+    fn key(&self, properties: &PeripheralProperties) -> Option<String> {
+        let (prefix, data, start, end) = match self {
+            Self::ManufacturerData {
+                company_id,
+                start,
+                end,
+            } => (
+                format!("{company_id:04x}"),
+                properties.manufacturer_data.get(company_id)?,
+                *start,
+                *end,
+            ),
+            Self::ServiceData {
+                service,
+                start,
+                end,
+            } => (
+                service.to_string(),
+                properties.service_data.get(service)?,
+                *start,
+                *end,
+            ),
+        };
+        let mut key = prefix + ":";
+        for b in data.get(start..end)? {
+            let _ = write!(key, "{b:02x}");
         }
-        self.send_scan_update(true).await;
-        let mut state = self.state.lock().await;
-        let mut self_devices = self.devices.clone();
-        let adapter = self.adapter.clone();
-        state.scan_task = Some(tokio::task::spawn(async move {
-            self_devices.lock().await.clear();
-            let loops = timeout / 200;
-            let mut devices;
-            for _ in 0..loops {
-                sleep(Duration::from_millis(200)).await;
-                let mut discovered = adapter
-                    .peripherals()
-                    .await
-                    .expect("failed to get peripherals");
-                filter_peripherals(&mut discovered, &filter).await;
-                devices = Self::add_devices(&mut self_devices, discovered).await;
-                if !devices.is_empty() {
-                    if let Some(tx) = &tx {
-                        tx.send(devices.clone())
-                            .await
-                            .expect("failed to send devices");
-                    }
+        Some(key)
+    }
+}
+
+/// Sent when a device with a known fingerprint is seen under a new address
+#[derive(Debug, Clone, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceReaddressed {
+    pub fingerprint: String,
+    pub old_address: String,
+    pub new_address: String,
+}
+
+#[derive(Default)]
+struct FingerprintCache {
+    /// Current address for every fingerprint
+    current: HashMap<String, String>,
+    /// Addresses replaced by a newer address with the same fingerprint, with the fingerprint
+    retired: HashMap<String, String>,
+}
+
+impl FingerprintCache {
+    /// Updates the current address of every fingerprint from the address and fingerprint of
+    /// each device reported by a scan and returns the address changes
+    /// A retired address is revived when it is reported without its fingerprint, because
+    /// another device took it over, or when the address that replaced it is not reported
+    /// anymore, because the device went back to it.
+    fn merge(&mut self, seen: &[(&str, Option<&str>)]) -> Vec<DeviceReaddressed> {
+        let present: HashSet<&str> = seen.iter().map(|(address, _)| *address).collect();
+        let mut readdressed = vec![];
+        for (address, key) in seen {
+            if let Some(retired_key) = self.retired.get(*address) {
+                let moved_on = Some(retired_key.as_str()) == *key
+                    && self
+                        .current
+                        .get(retired_key)
+                        .is_some_and(|current| present.contains(current.as_str()));
+                if moved_on {
+                    continue;
                 }
+                debug!("reviving retired address {address}");
+                self.retired.remove(*address);
             }
-            adapter.stop_scan().await.expect("failed to stop scan");
-            self.send_scan_update(false).await;
-        }));
+            let Some(key) = key else {
+                continue;
+            };
+            match self.current.insert(key.to_string(), address.to_string()) {
+                Some(old) if old != *address => {
+                    info!("device {old} is now using address {address}");
+                    self.retired.insert(old.clone(), key.to_string());
+                    readdressed.push(DeviceReaddressed {
+                        fingerprint: key.to_string(),
+                        old_address: old,
+                        new_address: address.to_string(),
+                    });
+                }
+                _ => {}
+            }
+        }
+        readdressed
+    }
+
+    fn is_retired(&self, address: &str) -> bool {
+        self.retired.contains_key(address)
+    }
+}
+
+/// How repeated advertisements of the same device are reported by [`Handler::discover`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub enum DuplicateFilter {
+    /// Send the discovered devices on every scan interval
+    #[default]
+    Platform,
+    /// Like `Platform`, but also count every received advertisement packet in
+    /// [`BleDevice::advertisement_count`]
+    ReportAll,
+    /// Only send the discovered devices if a device was added or its advertisement changed
+    Deduplicate,
+}
+
+/// Options for [`Handler::connect_with_options`]
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectOptions {
+    /// ATT MTU requested right after the link is up, before services are discovered
+    /// Ignored on platforms without explicit MTU requests, see
+    /// [`Handler::connection_capabilities`]
+    #[cfg_attr(feature = "ts-export", ts(optional))]
+    pub request_mtu: Option<u16>,
+    /// Bond with the device before any data is exchanged, pairing is started if the device is
+    /// not bonded yet. Progress is published as [`BleEvent::Pairing`]. The device
+    /// only becomes [`ConnectionState::Ready`] once bonded, pairing fails after a minute.
+    /// Only supported on android, see [`Handler::connection_capabilities`]
+    #[serde(default)]
+    #[cfg_attr(feature = "ts-export", ts(optional, as = "Option<bool>"))]
+    pub require_bonded: bool,
+    /// Steps run natively once the services are resolved
+    #[serde(default)]
+    #[cfg_attr(feature = "ts-export", ts(optional, as = "Option<Vec<SetupAction>>"))]
+    pub setup: Vec<SetupAction>,
+    /// Caps the whole connect, including the scan, bonding and setup script
+    /// The step in flight is cancelled when the time is up and the connect fails with
+    /// [`Error::TotalTimeout`] naming it.
+    #[serde(default)]
+    #[cfg_attr(feature = "ts-export", ts(optional, as = "Option<u32>"))]
+    pub total_timeout_ms: Option<u64>,
+    /// Id of the adapter to connect with, see [`Handler::set_multi_adapter_scan`]
+    /// Defaults to the adapter that received the strongest RSSI during the last scan.
+    #[serde(default)]
+    #[cfg_attr(feature = "ts-export", ts(optional))]
+    pub adapter: Option<String>,
+    /// Platform id of the device to connect to if the address matches several devices, see
+    /// [`Handler::device_candidates`]
+    /// Without it the most recently seen device is used, unless the address matches devices
+    /// that are known to be different, which fails with [`Error::AmbiguousAddress`].
+    #[serde(default)]
+    #[cfg_attr(feature = "ts-export", ts(optional))]
+    pub peripheral_id: Option<String>,
+    /// Disconnects once no operation succeeded and no notification arrived for this long
+    /// Keep-alive pings do not count as activity. The disconnect is reported with
+    /// [`DisconnectReason::IdleTimeout`].
+    #[serde(default)]
+    #[cfg_attr(feature = "ts-export", ts(optional, as = "Option<u32>"))]
+    pub idle_disconnect_ms: Option<u64>,
+    /// After an idle disconnect, the next send, read or subscribe connects again with the same
+    /// options before it runs
+    #[serde(default)]
+    #[cfg_attr(feature = "ts-export", ts(optional, as = "Option<bool>"))]
+    pub reconnect_on_demand: bool,
+    /// How long to scan for a device the platform does not know, defaults to 2000ms
+    /// The platform is asked for the address first, so bonded devices and devices the OS cached
+    /// are connected without scanning.
+    #[serde(default)]
+    #[cfg_attr(feature = "ts-export", ts(optional, as = "Option<u32>"))]
+    pub scan_timeout_ms: Option<u64>,
+    /// Characteristics the device has to offer, each one is looked up across all services
+    /// Every discovered characteristic can be used after connecting, listing them here makes
+    /// the connect fail early with [`Error::CharacNotAvailable`] or
+    /// [`Error::AmbiguousCharacteristic`] if one is missing or in several services without
+    /// [`CharacteristicRef::service`], and limits [`ConnectResult::characteristics`] to them.
+    #[serde(default)]
+    #[cfg_attr(
+        feature = "ts-export",
+        ts(optional, as = "Option<Vec<crate::models::CharacteristicRefRepr>>")
+    )]
+    pub characteristics: Vec<CharacteristicRef>,
+    /// Connects again with these options when the link is lost
+    /// Listeners added with [`Handler::subscribe`] are kept while the device is away and
+    /// subscribed again once the services are resolved. The disconnect callback runs with
+    /// [`ConnectionState::Reconnecting`] when the link is lost and again with
+    /// [`ConnectionState::Disconnected`] if every attempt failed, which is also published with
+    /// [`DisconnectReason::ReconnectFailed`]. Attempts are published as
+    /// [`BleEvent::ReconnectProgress`]. [`Handler::disconnect`] cancels the reconnect.
+    #[serde(default)]
+    #[cfg_attr(feature = "ts-export", ts(optional))]
+    pub reconnect: Option<ReconnectPolicy>,
+    /// Caps establishing the link and discovering the services, the connect fails with
+    /// [`Error::ConnectionTimeout`] when the time is up
+    /// Unlike [`ConnectOptions::total_timeout_ms`] the scan, bonding and setup script are not
+    /// counted. A connect still pending in the platform is cancelled, so the next connect can
+    /// start right away.
+    #[serde(default)]
+    #[cfg_attr(feature = "ts-export", ts(optional, as = "Option<u32>"))]
+    pub timeout_ms: Option<u64>,
+    /// Limit of simultaneous connections this connect waits for or fails on, defaults to the
+    /// limit of [`Handler::set_connection_limit`]
+    /// Connecting again to the connected device reuses its slot.
+    #[serde(default)]
+    #[cfg_attr(feature = "ts-export", ts(optional))]
+    pub connection_limit: Option<ConnectionLimit>,
+}
+
+/// Connect repeated by the next operation after an idle disconnect, see
+/// [`ConnectOptions::reconnect_on_demand`]
+pub(crate) struct IdleReconnect<H = &'static Handler> {
+    handler: H,
+    address: String,
+    options: ConnectOptions,
+}
+
+/// Connects of an idle reconnect, mocked in the tests
+trait IdleConnect: Clone {
+    fn is_connected(&self, address: &str) -> bool;
+    async fn connect(&self, address: &str, options: ConnectOptions) -> Result<(), Error>;
+}
+
+impl IdleConnect for &'static Handler {
+    fn is_connected(&self, address: &str) -> bool {
+        self.connection(address).is_some_and(|c| c.is_connected())
+    }
+
+    async fn connect(&self, address: &str, options: ConnectOptions) -> Result<(), Error> {
+        self.connect_with_options(address, None, options).await?;
         Ok(())
     }
+}
 
-    /// Discover provided services and charecteristics
-    /// If the device is not connected, a connection is made in order to discover the services and characteristics
-    /// After the discovery is done, the device is disconnected
-    /// If the devices was already connected, it will stay connected
-    /// # Errors
-    /// Returns an error if the device is not found, if the connection fails, or if the discovery fails
-    /// # Panics
-    /// Panics if there is an error with the internal disconnect event
-    pub async fn discover_services(&self, address: &str) -> Result<Vec<Service>, Error> {
-        let mut already_connected = self
-            .connected_dev
-            .lock()
-            .await
-            .as_ref()
-            .is_some_and(|dev| address == fmt_addr(dev.address()));
-        let device = if already_connected {
-            self.connected_dev
-                .lock()
-                .await
-                .as_ref()
-                .expect("Connection exists")
-                .clone()
+/// Runs the pending connect of an idle reconnect, see [`Handler::reconnect_if_idle`]
+/// Only takes `running` while a reconnect is pending, operations arriving during the connect
+/// wait for it. The reconnect stays pending if the connect failed, so the next operation tries
+/// again.
+async fn reconnect_pending<H: IdleConnect>(
+    pending: &std::sync::Mutex<Option<IdleReconnect<H>>>,
+    running: &Mutex<()>,
+) -> Result<(), Error> {
+    if pending.lock().expect("idle reconnect lock poisoned").is_none() {
+        return Ok(());
+    }
+    let _running = running.lock().await;
+    let (handler, address, options) = {
+        let pending = pending.lock().expect("idle reconnect lock poisoned");
+        // reconnected by the operation that held the lock before
+        let Some(pending) = pending.as_ref() else {
+            return Ok(());
+        };
+        (pending.handler.clone(), pending.address.clone(), pending.options.clone())
+    };
+    if !handler.is_connected(&address) {
+        info!("reconnecting to {address} after idle disconnect");
+        handler.connect(&address, options).await?;
+    }
+    pending.lock().expect("idle reconnect lock poisoned").take();
+    Ok(())
+}
+
+/// Connect repeated after the link was lost, see [`ConnectOptions::reconnect`]
+pub(crate) struct AutoReconnect {
+    handler: &'static Handler,
+    address: String,
+    options: ConnectOptions,
+    policy: ReconnectPolicy,
+}
+
+/// Result of [`Handler::connect_with_options`]
+#[derive(Debug, Clone, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectResult {
+    #[cfg_attr(feature = "ts-export", ts(as = "Vec<crate::models::ServiceRepr>"))]
+    pub services: Vec<btleplug::models::Service>,
+    /// The characteristics matched by [`ConnectOptions::characteristics`], every characteristic
+    /// of the resolved services if none were listed
+    pub characteristics: Vec<CharacteristicRef>,
+    /// The resolved services with the decoded properties of their characteristics, like
+    /// [`Handler::discover_all_services`]
+    pub gatt: Vec<ServiceInfo>,
+    /// Results of [`ConnectOptions::setup`] in the order of the steps
+    pub setup: Vec<SetupStepResult>,
+    /// Largest payload of a single write per write type when the connect finished
+    pub max_write_len: WriteLimits,
+}
+
+/// Largest payload of a single write per write type, see [`Handler::max_write_len_for`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct WriteLimits {
+    pub with_response: Option<usize>,
+    pub without_response: Option<usize>,
+}
+
+/// Connection features of the platform returned by [`Handler::connection_capabilities`]
+#[derive(Debug, Clone, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionCapabilities {
+    /// The MTU can be requested with [`ConnectOptions::request_mtu`]
+    pub mtu_request: bool,
+    /// Platform specific remarks on the MTU
+    pub mtu_note: &'static str,
+    /// The bond state can be checked and pairing started with [`ConnectOptions::require_bonded`]
+    pub bonding: bool,
+}
+
+/// Progress of pairing started by [`ConnectOptions::require_bonded`]
+#[derive(Debug, Clone, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct PairingEvent {
+    pub address: String,
+    pub state: PairingState,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(tag = "state", rename_all = "camelCase")]
+pub enum PairingState {
+    Started,
+    Bonded,
+    Failed { reason: String },
+}
+
+#[cfg(target_os = "android")]
+const MTU_NOTE: &str = "The MTU is requested with BluetoothGatt.requestMtu, the negotiated value \
+    is reported by get_mtu.";
+#[cfg(not(target_os = "android"))]
+const MTU_NOTE: &str = "The MTU is negotiated by the OS and cannot be requested, \
+    request_mtu of the connect options is ignored.";
+
+/// Duplicate handling of the platform returned by [`Handler::scan_capabilities`]
+#[derive(Debug, Clone, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct ScanCapabilities {
+    /// The OS reports every advertisement packet to the plugin
+    pub os_reports_duplicates: bool,
+    /// The OS can deduplicate advertisements, otherwise deduplication is emulated by the plugin
+    pub os_deduplication: bool,
+    /// Platform specific remarks on duplicate reporting
+    pub duplicates_note: &'static str,
+    /// Every advertisement reaches the plugin, so [`BleDevice::adv_interval_ms`] is close to the
+    /// advertising interval of the device. Otherwise merged packets make it appear longer.
+    pub adv_interval_exact: bool,
+}
+
+#[cfg(target_os = "android")]
+const SCAN_CAPABILITIES: ScanCapabilities = ScanCapabilities {
+    os_reports_duplicates: true,
+    os_deduplication: false,
+    duplicates_note: "Scans use CALLBACK_TYPE_ALL_MATCHES, every scan result is counted. \
+        CALLBACK_TYPE_FIRST_MATCH needs hardware filters, so deduplication is done by the plugin.",
+    adv_interval_exact: true,
+};
+#[cfg(target_os = "linux")]
+const SCAN_CAPABILITIES: ScanCapabilities = ScanCapabilities {
+    os_reports_duplicates: false,
+    os_deduplication: false,
+    duplicates_note: "BlueZ reports advertisements as property updates of the device, identical \
+        consecutive packets change no property and are merged. Deduplication is done by the \
+        plugin.",
+    adv_interval_exact: false,
+};
+#[cfg(target_vendor = "apple")]
+const SCAN_CAPABILITIES: ScanCapabilities = ScanCapabilities {
+    os_reports_duplicates: false,
+    os_deduplication: false,
+    duplicates_note: "CoreBluetooth scans with AllowDuplicates enabled, but merges duplicates \
+        while the app is in the background. Deduplication is done by the plugin.",
+    adv_interval_exact: false,
+};
+#[cfg(not(any(target_os = "android", target_os = "linux", target_vendor = "apple")))]
+const SCAN_CAPABILITIES: ScanCapabilities = ScanCapabilities {
+    os_reports_duplicates: true,
+    os_deduplication: false,
+    duplicates_note: "The advertisement watcher reports every received packet. Deduplication \
+        is done by the plugin.",
+    adv_interval_exact: true,
+};
+
+/// Operation executed by the keep-alive set with [`Handler::set_keep_alive`]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum KeepAliveMethod {
+    /// Request the RSSI of the connection, only supported on android
+    Rssi,
+    /// Read the given characteristic
+    Read { characteristic: Uuid },
+    /// Write zero bytes with response to the given characteristic
+    EmptyWrite { characteristic: Uuid },
+}
+
+#[derive(Clone)]
+struct KeepAlive {
+    interval: Duration,
+    method: KeepAliveMethod,
+}
+
+/// Consecutive failed keep-alive pings after which the connection is considered lost
+const KEEP_ALIVE_MAX_FAILURES: u32 = 2;
+
+struct HandlerState {
+    //characs: HashMap<String, HashSet<Characteristic>>,
+    connection_update_channel: Vec<mpsc::Sender<bool>>,
+    scan_update_channel: Vec<mpsc::Sender<bool>>,
+    scan_task: Option<tokio::task::JoinHandle<()>>,
+    /// Ends the loop of `scan_task` early, see [`Handler::stop_scan`]
+    scan_stop: Option<Arc<Notify>>,
+    /// Set if `scan_task` runs until stopped, see [`Handler::start_discovery`]
+    scan_continuous: bool,
+    /// Incremented for every scan started, see [`Handler::stop_scan_generation`]
+    scan_generation: u64,
+    /// Set with [`Handler::set_keep_alive`], applies to every connection
+    keep_alive: Option<KeepAlive>,
+    duplicate_filter: DuplicateFilter,
+    removal_debounce: Duration,
+    metrics_task: Option<runtime::JoinHandle<()>>,
+}
+
+/*
+impl HandlerState {
+    fn set_charac(&self, device_id: String, uuid: Uuid, charac: Characteristic) {
+        let mut characs = self.characs.entry(&device_id).or_insert(HashSet::default());
+        characs.insert(charac);
+    }
+
+    fn get_charac(&self, device_id: String, uuid: Uuid) -> Result<&Characteristic, Error> {
+        if let Some(characs) = self.characs.get(&device_id) {
+            let charac = characs.iter().find(|c| c.uuid == uuid);
+            charac.ok_or(Error::CharacNotAvailable(uuid.to_string()))
         } else {
-            let device = self
-                .devices
-                .lock()
-                .await
-                .get(address)
-                .ok_or(Error::UnknownPeripheral(address.to_string()))?
-                .clone();
-            if device.is_connected().await? {
-                already_connected = true;
-            } else if let Err(e) = self.connect_device(address).await {
-                *self.connected_dev.lock().await = None;
-                let _ = self.connected_tx.send(false);
-                error!("Failed to connect for discovery: {e}");
-                return Err(e);
+            Err(Error::CharacNotAvailable(uuid.to_string()))
+        }
+    }
+} */
+
+/// Resets the state of the connection when a connect fails before it was ready
+pub(crate) struct ConnectingGuard<'a> {
+    pub(crate) handler: &'a Handler,
+    pub(crate) address: String,
+}
+
+impl Drop for ConnectingGuard<'_> {
+    fn drop(&mut self) {
+        let state = self.handler.connection_state_of(&self.address);
+        if matches!(
+            state,
+            ConnectionState::Connecting | ConnectionState::DiscoveringServices
+        ) {
+            self.handler
+                .set_connection_state(&self.address, ConnectionState::Disconnected);
+        }
+    }
+}
+
+tokio::task_local! {
+    /// Set while a connect bonds the device and runs the setup steps, their operations run
+    /// before the connection is ready
+    static SETTING_UP: ();
+}
+
+/// Stage of every connection, see [`Handler::connection_state`]
+#[derive(Debug, Default)]
+struct ConnectionStates {
+    /// Device of [`Handler::connect`], also while it connects or reconnects
+    main: Option<String>,
+    states: HashMap<String, ConnectionState>,
+}
+
+impl ConnectionStates {
+    fn get(&self, address: &str) -> ConnectionState {
+        self.states.get(address).copied().unwrap_or_default()
+    }
+
+    fn main(&self) -> ConnectionState {
+        self.main.as_deref().map_or(ConnectionState::Disconnected, |a| self.get(a))
+    }
+
+    /// Returns the previous state of the connection
+    fn set(&mut self, address: &str, state: ConnectionState) -> ConnectionState {
+        let previous = if state == ConnectionState::Disconnected {
+            if self.main.as_deref() == Some(address) {
+                self.main = None;
             }
-            device
+            self.states.remove(address)
+        } else {
+            self.states.insert(address.to_string(), state)
         };
-        debug!("discovering services on {address}");
-        if device.services().is_empty() {
-            device.discover_services().await?;
+        previous.unwrap_or_default()
+    }
+}
+
+/// Deadline of [`ConnectOptions::timeout_ms`], shared by the connect attempts and the service
+/// discovery
+#[derive(Clone, Copy)]
+struct LinkTimeout(Option<(u64, tokio::time::Instant)>);
+
+impl LinkTimeout {
+    fn new(timeout_ms: Option<u64>) -> Self {
+        Self(timeout_ms.map(|ms| (ms, tokio::time::Instant::now() + Duration::from_millis(ms))))
+    }
+
+    /// Runs the step, failing with [`Error::ConnectionTimeout`] if the deadline passes first
+    async fn run<T>(
+        self,
+        fut: impl std::future::Future<Output = Result<T, Error>>,
+    ) -> Result<T, Error> {
+        let Some((timeout_ms, deadline)) = self.0 else {
+            return fut.await;
+        };
+        tokio::time::timeout_at(deadline, fut)
+            .await
+            .unwrap_or_else(|_| {
+                warn!("connect timed out after {timeout_ms}ms");
+                Err(Error::ConnectionTimeout { timeout_ms })
+            })
+    }
+}
+
+/// Settings of a single scan, `None` uses the setting of the handler
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct ScanSettings {
+    /// Overrides [`Handler::set_removal_debounce`]
+    pub(crate) removal_debounce: Option<Duration>,
+    /// Limits the device events the scan publishes, every change is published if not set
+    pub(crate) event_budget: Option<ScanEventBudget>,
+    /// Set for the shared scan of [`Handler::register_scan_consumer`], other scans cover the
+    /// services of the consumers in addition to their own filter
+    pub(crate) consumers: bool,
+    /// Who starts the scan, see [`Handler::record_scan_start`]
+    pub(crate) start: ScanStart,
+}
+
+/// Resumes a continuous scan paused by [`Handler::pause_scan`] once the last pause is dropped
+pub(crate) struct ScanPause<'a>(&'a Handler);
+
+impl Drop for ScanPause<'_> {
+    fn drop(&mut self) {
+        self.0.scan_pauses.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Fails the operations queued on the device while it is disconnected, see
+/// [`Handler::disconnect`]
+#[derive(Default)]
+pub(crate) struct DisconnectGate(AtomicBool);
+
+impl DisconnectGate {
+    /// Fails operations from now on
+    fn close(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    /// Lets operations run again, returns whether a disconnect was requested
+    fn open(&self) -> bool {
+        self.0.swap(false, Ordering::AcqRel)
+    }
+
+    /// Checked by an operation once it holds the operation queue
+    fn check(&self) -> Result<(), Error> {
+        if self.0.load(Ordering::Acquire) {
+            Err(Error::Disconnected)
+        } else {
+            Ok(())
         }
-        let services = device.services().iter().cloned().map(Service::from).collect();
-        if !already_connected {
-            let mut connected_rx = self.connected_rx.clone();
-            if *connected_rx.borrow_and_update() {
-                device.disconnect().await?;
-                debug!("waiting for disconnect event");
-                connected_rx
-                    .changed()
-                    .await
-                    .expect("failed to wait for disconnect event");
+    }
+
+    /// Takes the operation queue for a disconnect and closes the gate
+    /// With `flush` every operation queued before runs first. Otherwise the gate is closed
+    /// right away, so the queued operations fail with [`Error::Disconnected`] once it is their
+    /// turn, and only the running operation is awaited for at most `in_flight`. Returns `None`
+    /// if it did not finish in time.
+    async fn drain<'a, T>(
+        &self,
+        queue: &'a Mutex<T>,
+        flush: bool,
+        in_flight: Duration,
+    ) -> Option<tokio::sync::MutexGuard<'a, T>> {
+        let guard = if flush {
+            Some(queue.lock().await)
+        } else {
+            self.close();
+            timeout(in_flight, queue.lock()).await.ok()
+        };
+        if guard.is_some() {
+            self.close();
+        }
+        guard
+    }
+}
+
+/// Since when each device of a scan is missing, see [`Handler::prune_devices`]
+struct MissingDevices<K>(HashMap<K, Instant>);
+
+impl<K> Default for MissingDevices<K> {
+    fn default() -> Self {
+        Self(HashMap::new())
+    }
+}
+
+impl<K: std::hash::Hash + Eq + Clone> MissingDevices<K> {
+    /// Whether the device is kept at `now`
+    /// A device that is `seen` cancels its pending removal, an unseen one is only removed
+    /// once it stayed unseen for `debounce`.
+    fn keep(&mut self, key: &K, seen: bool, debounce: Duration, now: Instant) -> bool {
+        if seen {
+            if self.0.remove(key).is_some() {
+                debug!("device reappeared, cancelling its removal");
+            }
+            return true;
+        }
+        let since = *self.0.entry(key.clone()).or_insert(now);
+        if now.saturating_duration_since(since) < debounce {
+            return true;
+        }
+        self.0.remove(key);
+        false
+    }
+}
+
+/// Advertisements received from a device during the current scan
+#[cfg(not(target_os = "android"))]
+#[derive(Default)]
+struct AdvertisementStats {
+    count: u64,
+    interval: models::AdvInterval,
+}
+
+/// Properties fetched during one scan, reused until the device reports another advertisement
+/// Without it every tick fetches the properties of every peripheral the adapter knows, with
+/// BlueZ a DBus round trip each, although only few of them advertise. For 500 known
+/// peripherals of which 10 advertise, 50 ticks need 25000 fetches without the cache and 990
+/// with it, see `tests::properties_cache_fetches_only_advertising_devices`. The
+/// `tests::scan_properties_throughput` benchmark measures the time of these ticks.
+struct PropertiesCache<K, P> {
+    /// Advertisement count when the properties were fetched and the properties, by peripheral
+    entries: HashMap<K, (u64, Option<P>)>,
+}
+
+impl<K, P> Default for PropertiesCache<K, P> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+        }
+    }
+}
+
+impl<K: Eq + std::hash::Hash, P: Clone> PropertiesCache<K, P> {
+    /// Returns the cached properties if the device did not advertise since they were fetched
+    fn get(&self, id: &K, advertisements: u64) -> Option<Option<P>> {
+        self.entries
+            .get(id)
+            .filter(|(count, _)| *count == advertisements)
+            .map(|(_, properties)| properties.clone())
+    }
+
+    fn insert(&mut self, id: K, advertisements: u64, properties: Option<P>) {
+        self.entries.insert(id, (advertisements, properties));
+    }
+}
+
+type ScanPropertiesCache = PropertiesCache<PeripheralId, PeripheralProperties>;
+
+/// Peripheral of a scan tick, see [`peripheral_properties`], mocked in the benchmark
+trait ScannedPeripheral {
+    type Id: Eq + std::hash::Hash;
+    type Properties: Clone;
+    fn scan_id(&self) -> Self::Id;
+    /// Address for the logs
+    fn label(&self) -> String;
+    async fn fetch_properties(&self) -> btleplug::Result<Option<Self::Properties>>;
+}
+
+impl ScannedPeripheral for Peripheral {
+    type Id = PeripheralId;
+    type Properties = PeripheralProperties;
+
+    fn scan_id(&self) -> PeripheralId {
+        self.id()
+    }
+
+    fn label(&self) -> String {
+        fmt_addr(self.address())
+    }
+
+    async fn fetch_properties(&self) -> btleplug::Result<Option<PeripheralProperties>> {
+        self.properties().await
+    }
+}
+
+/// Adapter scanned by [`Handler::discover`]
+#[derive(Clone)]
+struct ScanAdapter {
+    /// `None` for the default adapter outside of multi adapter scans
+    id: Option<String>,
+    adapter: Arc<Adapter>,
+}
+
+/// Result of polling the scanned adapters, see [`poll_adapters`]
+#[derive(Default)]
+struct PolledDevices {
+    /// Handles of all adapters
+    peripherals: Vec<Peripheral>,
+    /// One handle per address, from the adapter with the strongest RSSI
+    strongest: Vec<(Peripheral, Option<PeripheralProperties>)>,
+    /// Id of the adapter every handle in `strongest` came from, by address
+    strongest_adapter: HashMap<String, Option<String>>,
+    /// Adapters that saw each address in multi adapter scans
+    sightings: HashMap<String, Vec<AdapterSighting>>,
+}
+
+pub struct Handler {
+    devices: Arc<Mutex<DeviceCache>>,
+    /// Adapter picked with [`Handler::select_adapter`], the first one of the system by default
+    adapter: std::sync::Mutex<Arc<Adapter>>,
+    /// Index of `adapter` in the adapter list of the OS
+    adapter_index: AtomicUsize,
+    /// All adapters scanned with [`Handler::set_multi_adapter_scan`], empty to only scan the
+    /// default adapter
+    multi_adapters: std::sync::Mutex<Vec<ScanAdapter>>,
+    next_listener_id: AtomicU64,
+    next_session_id: AtomicU64,
+    enricher: Mutex<Option<DeviceEnricher>>,
+    fingerprint: Mutex<Option<DeviceFingerprint>>,
+    charac_options: Mutex<HashMap<Uuid, CharacteristicOptions>>,
+    metrics: std::sync::Mutex<BleMetrics>,
+    retry_policy: std::sync::Mutex<RetryPolicy>,
+    /// How addresses passed in are checked, see [`Handler::set_address_policy`]
+    address_policy: std::sync::Mutex<AddressPolicy>,
+    critical_operations: Arc<CriticalOperations>,
+    exit_guard: std::sync::Mutex<Option<Duration>>,
+    /// Set once the exit guard delayed an exit, the exit after the wait is not delayed again
+    exit_delayed: AtomicBool,
+    fingerprints: Mutex<FingerprintCache>,
+    slots: Mutex<ConnectionSlots>,
+    slot_released: Notify,
+    /// Connected flag of the main connection, see [`Handler::is_connected`]
+    connected_rx: watch::Receiver<bool>,
+    connected_tx: watch::Sender<bool>,
+    state: Mutex<HandlerState>,
+    /// Held while a scan is started or stopped, so concurrent discovers do not clear the devices
+    /// found by each other
+    scan_lock: Mutex<()>,
+    /// Number of connects a continuous scan is paused for, see [`Handler::pause_scan`]
+    scan_pauses: AtomicUsize,
+    /// Held while the adapters are paused or resumed, so the scan loop can not start them again
+    /// between a pause and the stop of the adapters
+    scan_pause_lock: Mutex<()>,
+    /// Held while the adapter is switched, so no connect starts on the previous adapter
+    pub(crate) adapter_switch: Mutex<()>,
+    /// State last reported by the adapter, see [`Handler::adapter_state`]
+    adapter_state: std::sync::Mutex<AdapterState>,
+    /// Time each address was last reported by a scan, kept after the device is lost
+    last_seen: std::sync::Mutex<HashMap<String, SystemTime>>,
+    /// Bond state of every address checked by [`Handler::ensure_bonded`]
+    bond_states: std::sync::Mutex<HashMap<String, bool>>,
+    /// Addresses paired in the background after an operation needed a bond
+    #[cfg(target_os = "windows")]
+    background_pairings: Arc<std::sync::Mutex<HashSet<String>>>,
+    /// Auto accept setting and pending requests of [`Handler::accept_pairing_consent`]
+    #[cfg(target_os = "windows")]
+    pub(crate) pairing_consents: Arc<crate::consent::windows_pairing::PairingConsents>,
+    /// Addresses of all devices connected successfully, see [`BleDevice::previously_connected`]
+    previously_connected: Arc<std::sync::Mutex<HashSet<String>>>,
+    /// Held while the previously connected devices are written to the store
+    store_writes: Arc<std::sync::Mutex<()>>,
+    store: std::sync::Mutex<Option<Arc<dyn BlecStore>>>,
+    /// Advertisements received per device during the current scan
+    #[cfg(not(target_os = "android"))]
+    advertisements: std::sync::Mutex<HashMap<PeripheralId, AdvertisementStats>>,
+    /// Time of the last central event or successful adapter poll
+    last_central_event: std::sync::Mutex<Instant>,
+    event_loop_degraded: AtomicBool,
+    heartbeat_interval: std::sync::Mutex<Option<Duration>>,
+    /// Notified when the event stream should be dropped and acquired again
+    event_stream_restart: Notify,
+    /// Set while an [`EventLoopGuard`] is alive
+    event_loop_running: AtomicBool,
+    /// Sender of [`Handler::events`], records the connect sessions for
+    /// [`Handler::export_session_timeline`]
+    publisher: Publisher,
+    connection_states: std::sync::Mutex<ConnectionStates>,
+    /// Every connected device by address, the main connection included
+    pub(crate) connections: std::sync::Mutex<HashMap<String, Arc<Connection>>>,
+    pub(crate) paused_transfers: PausedTransfers,
+    pub(crate) battery: BatteryMonitor,
+    /// Forwards the changes of the scan started with [`Handler::start_discovery`] to its channel
+    pub(crate) discovery_forwarder: std::sync::Mutex<Option<runtime::JoinHandle<()>>>,
+    pub(crate) scan_consumers: std::sync::Mutex<ScanConsumers>,
+    pub(crate) mirrors: Mirrors,
+    pub(crate) clocks: Arc<Clocks>,
+    pub(crate) scan_starts: std::sync::Mutex<ScanStarts>,
+    pub(crate) interference: Interference,
+    pub(crate) pending_waits: PendingWaits,
+    /// Status last published as [`BleEvent::AuthorizationChanged`]
+    pub(crate) last_authorization: std::sync::Mutex<Option<AuthorizationStatus>>,
+}
+
+/// Lists the bluetooth adapters of the system in the order of the OS
+/// Can be called before the plugin is initialized, e.g. to pick the adapter given to
+/// [`crate::init_with_adapter`].
+/// # Errors
+/// Returns an error if the bluetooth manager can not be created or the adapters can not be
+/// listed
+/// # Example
+/// ```no_run
+/// # #[cfg(feature = "tauri")] {
+/// tauri::async_runtime::block_on(async {
+///     for adapter in tauri_plugin_blec::list_adapters().await.unwrap() {
+///         println!("{}: {}", adapter.index, adapter.id);
+///     }
+/// });
+/// # }
+/// ```
+pub async fn list_adapters() -> Result<Vec<AdapterInfo>, Error> {
+    let manager = Manager::new().await?;
+    let mut infos = vec![];
+    for (index, adapter) in manager.adapters().await?.iter().enumerate() {
+        infos.push(AdapterInfo {
+            index,
+            id: adapter_id(adapter, index).await,
+        });
+    }
+    Ok(infos)
+}
+
+/// Returns the adapter matching the selector with its index
+async fn get_central(selector: &AdapterSelector) -> Result<(usize, Adapter), Error> {
+    let manager = Manager::new().await?;
+    let adapters = manager.adapters().await?;
+    if adapters.is_empty() {
+        return Err(Error::NoAdapters);
+    }
+    for (index, adapter) in adapters.into_iter().enumerate() {
+        let info = AdapterInfo {
+            index,
+            id: adapter_id(&adapter, index).await,
+        };
+        if selector.matches(&info) {
+            return Ok((index, adapter));
+        }
+    }
+    Err(Error::AdapterNotFound(match selector {
+        AdapterSelector::Index(index) => format!("index {index}"),
+        AdapterSelector::Name(name) => name.clone(),
+    }))
+}
+
+impl Handler {
+    /// Creates a handler on the first adapter of the system
+    /// The plugin creates its handler on its own, this is meant for using the handler without
+    /// tauri, e.g. from a CLI or daemon. Nothing happens on the adapter until
+    /// [`Handler::spawn_event_loop`] was called, most operations need the handler to live for
+    /// the rest of the program.
+    /// # Errors
+    /// Returns an error if the bluetooth manager can not be created or there is no adapter
+    /// # Example
+    /// ```no_run
+    /// use tauri_plugin_blec::Handler;
+    /// # async fn run() -> Result<(), tauri_plugin_blec::Error> {
+    /// let handler: &'static Handler = Box::leak(Box::new(Handler::new().await?));
+    /// let events = handler.spawn_event_loop()?;
+    /// handler.connect("00:00:00:00:00:00", None).await?;
+    /// // ...
+    /// handler.shutdown().await?;
+    /// drop(events);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn new() -> Result<Self, Error> {
+        Self::with_adapter(&AdapterSelector::default()).await
+    }
+
+    /// Creates a handler like [`Handler::new`] on the adapter matching the selector
+    /// # Errors
+    /// Returns [`Error::AdapterNotFound`] if no adapter matches and an error if the bluetooth
+    /// manager can not be created
+    pub async fn with_adapter(selector: &AdapterSelector) -> Result<Self, Error> {
+        let (index, central) = get_central(selector).await?;
+        debug!("using adapter {index}");
+        let (connected_tx, connected_rx) = watch::channel(false);
+        Ok(Self {
+            devices: Arc::new(Mutex::new(DeviceCache::default())),
+            adapter: std::sync::Mutex::new(Arc::new(central)),
+            adapter_index: AtomicUsize::new(index),
+            multi_adapters: std::sync::Mutex::new(vec![]),
+            next_listener_id: AtomicU64::new(0),
+            next_session_id: AtomicU64::new(0),
+            enricher: Mutex::new(None),
+            fingerprint: Mutex::new(None),
+            charac_options: Mutex::new(HashMap::new()),
+            metrics: std::sync::Mutex::new(BleMetrics::default()),
+            retry_policy: std::sync::Mutex::new(RetryPolicy::default()),
+            address_policy: std::sync::Mutex::new(AddressPolicy::default()),
+            critical_operations: Arc::default(),
+            exit_guard: std::sync::Mutex::new(None),
+            exit_delayed: AtomicBool::new(false),
+            fingerprints: Mutex::new(FingerprintCache::default()),
+            slots: Mutex::new(ConnectionSlots {
+                max: DEFAULT_MAX_CONNECTIONS,
+                policy: ConnectionLimitPolicy::default(),
+                active: 0,
+            }),
+            slot_released: Notify::new(),
+            connected_rx,
+            connected_tx,
+            scan_lock: Mutex::new(()),
+            scan_pauses: AtomicUsize::new(0),
+            scan_pause_lock: Mutex::new(()),
+            adapter_switch: Mutex::new(()),
+            adapter_state: std::sync::Mutex::new(AdapterState::Unknown),
+            last_seen: std::sync::Mutex::new(HashMap::new()),
+            bond_states: std::sync::Mutex::new(HashMap::new()),
+            #[cfg(target_os = "windows")]
+            background_pairings: Arc::default(),
+            #[cfg(target_os = "windows")]
+            pairing_consents: Arc::default(),
+            previously_connected: Arc::new(std::sync::Mutex::new(HashSet::new())),
+            store_writes: Arc::new(std::sync::Mutex::new(())),
+            store: std::sync::Mutex::new(None),
+            #[cfg(not(target_os = "android"))]
+            advertisements: std::sync::Mutex::new(HashMap::new()),
+            last_central_event: std::sync::Mutex::new(Instant::now()),
+            event_loop_degraded: AtomicBool::new(false),
+            heartbeat_interval: std::sync::Mutex::new(Some(DEFAULT_HEARTBEAT_INTERVAL)),
+            event_stream_restart: Notify::new(),
+            event_loop_running: AtomicBool::new(false),
+            publisher: Publisher::new(),
+            connection_states: std::sync::Mutex::new(ConnectionStates::default()),
+            connections: std::sync::Mutex::new(HashMap::new()),
+            paused_transfers: PausedTransfers::default(),
+            battery: BatteryMonitor::default(),
+            discovery_forwarder: std::sync::Mutex::new(None),
+            scan_consumers: std::sync::Mutex::new(ScanConsumers::default()),
+            mirrors: Mirrors::default(),
+            clocks: Arc::new(Clocks::default()),
+            scan_starts: std::sync::Mutex::new(ScanStarts::default()),
+            interference: Interference::default(),
+            pending_waits: PendingWaits::default(),
+            last_authorization: std::sync::Mutex::new(None),
+            state: Mutex::new(HandlerState {
+                connection_update_channel: vec![],
+                scan_task: None,
+                scan_stop: None,
+                scan_continuous: false,
+                scan_generation: 0,
+                scan_update_channel: vec![],
+                keep_alive: None,
+                duplicate_filter: DuplicateFilter::default(),
+                removal_debounce: DEFAULT_REMOVAL_DEBOUNCE,
+                metrics_task: None,
+                //characs: HashMap::default(),
+            }),
+        })
+    }
+
+    /// Returns true if the device of [`Handler::connect`] is connected
+    pub fn is_connected(&self) -> bool {
+        *self.connected_rx.borrow()
+    }
+
+    pub(crate) fn publisher(&self) -> &Publisher {
+        &self.publisher
+    }
+
+    /// Publishes an event, it is dropped if nobody listens
+    pub(crate) fn publish(&self, event: BleEvent) {
+        self.publisher.publish(event);
+    }
+
+    pub(crate) fn timelines(&self) -> std::sync::MutexGuard<'_, Timelines> {
+        self.publisher.timelines()
+    }
+
+    pub(crate) fn next_listener_handle(&self) -> ListenerHandle {
+        ListenerHandle(self.next_listener_id.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// Cached handle of the device with the address, from the last scan
+    /// The most recently seen one if several adapters or devices reported the address.
+    pub(crate) async fn known_peripheral(&self, address: &str) -> Option<Peripheral> {
+        self.devices.lock().await.get(address).cloned()
+    }
+
+    /// Picks the cached handle a connect to the address uses, see
+    /// [`ConnectOptions::peripheral_id`]
+    pub(crate) async fn resolve_peripheral(
+        &self,
+        address: &str,
+        peripheral_id: Option<&str>,
+    ) -> Result<(), Error> {
+        self.devices.lock().await.resolve(address, peripheral_id)
+    }
+
+    /// Devices cached for the address, the most recently seen first
+    /// An address matches several devices when it was seen by several adapters or when a
+    /// device with a fingerprint from [`Handler::set_device_fingerprint`] took over the address
+    /// of another one. Connects to an address that matches different devices fail with
+    /// [`Error::AmbiguousAddress`] until [`ConnectOptions::peripheral_id`] picks one.
+    /// # Errors
+    /// Returns [`Error::InvalidAddress`] if the address can not be parsed
+    pub async fn device_candidates(&self, address: &str) -> Result<Vec<DeviceCandidate>, Error> {
+        let address = self.parse_address(address)?;
+        Ok(self.devices.lock().await.candidates(&address))
+    }
+
+    /// Returns the stage of the connection of [`Handler::connect`]
+    pub fn connection_state(&self) -> ConnectionState {
+        self.lock_connection_states().main()
+    }
+
+    /// Returns the stage of the connection to the device, also for additional connections
+    /// # Errors
+    /// Returns [`Error::InvalidAddress`] if the address is rejected by the [`AddressPolicy`]
+    pub fn device_connection_state(&self, address: &str) -> Result<ConnectionState, Error> {
+        let address = self.parse_address(address)?;
+        Ok(self.connection_state_of(&address))
+    }
+
+    pub(crate) fn connection_state_of(&self, address: &str) -> ConnectionState {
+        self.lock_connection_states().get(address)
+    }
+
+    fn lock_connection_states(&self) -> std::sync::MutexGuard<'_, ConnectionStates> {
+        self.connection_states
+            .lock()
+            .expect("connection states lock poisoned")
+    }
+
+    /// Device of [`Handler::connect`], also while it connects or reconnects
+    pub(crate) fn main_address(&self) -> Option<String> {
+        self.lock_connection_states().main.clone()
+    }
+
+    pub(crate) fn set_connection_state(&self, address: &str, state: ConnectionState) {
+        let previous = self.lock_connection_states().set(address, state);
+        if previous != state {
+            debug!("connection state of {address}: {previous:?} -> {state:?}");
+            self.publish(BleEvent::ConnectionStateChanged {
+                address: address.to_string(),
+                state,
+            });
+        }
+    }
+
+    /// Returns true if the address is the device of [`Handler::connect`]
+    pub(crate) fn is_main(&self, address: &str) -> bool {
+        self.lock_connection_states().main.as_deref() == Some(address)
+    }
+
+    /// Marks the device as the one of [`Handler::connect`] unless another device is
+    fn claim_main(&self, address: &str) {
+        self.lock_connection_states()
+            .main
+            .get_or_insert_with(|| address.to_string());
+    }
+
+    /// Receives the connected flag of the main connection
+    pub(crate) fn connection_watch(&self) -> watch::Receiver<bool> {
+        self.connected_rx.clone()
+    }
+
+    /// Address of the connected main device, without waiting for running operations
+    pub(crate) fn connected_address(&self) -> Option<String> {
+        self.main_connection()
+            .filter(|c| c.link().is_some())
+            .map(|c| c.address.clone())
+    }
+
+    /// Sets the connected flag of the connection, the one of the handler for the main connection
+    fn set_connected(&self, connection: &Connection, main: bool, connected: bool) {
+        connection.set_connected(connected);
+        if main {
+            self.connected_tx
+                .send(connected)
+                .expect("failed to send connected update");
+        }
+    }
+
+    /// Returns true if the adapter is scanning
+    pub async fn is_scanning(&self) -> bool {
+        if let Some(handle) = &self.state.lock().await.scan_task {
+            !handle.is_finished()
+        } else {
+            false
+        }
+    }
+
+    /// Returns a snapshot of the current handler state
+    pub async fn get_state(&self) -> BleState {
+        let slots = self.slots.lock().await;
+        BleState {
+            connected: self.is_connected(),
+            connection_state: self.connection_state(),
+            scanning: self.is_scanning().await,
+            connections: slots.active,
+            connection_limit: slots.max,
+            connection_limit_policy: slots.policy,
+            authorization: self.authorization_status(),
+            battery: self.battery.latest(),
+        }
+    }
+
+    /// Marks an operation as critical until the returned guard is dropped
+    /// While a critical operation runs, [`Handler::has_critical_operation`] returns true and,
+    /// if enabled with [`Handler::set_exit_guard`], closing the app is delayed.
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     let _critical = handler.begin_critical_operation("firmware update");
+    ///     // transfer the firmware
+    /// });
+    /// # }
+    /// ```
+    pub fn begin_critical_operation(&self, name: &str) -> CriticalOperation {
+        CriticalOperation {
+            id: self.critical_operations.start(name),
+            operations: self.critical_operations.clone(),
+        }
+    }
+
+    /// Starts a critical operation ended with [`Handler::end_critical_operation`], for the
+    /// frontend, which can not hold a [`CriticalOperation`]
+    pub(crate) fn start_critical_operation(&self, name: &str) -> u64 {
+        self.critical_operations.start(name)
+    }
+
+    /// Ends an operation started with [`Handler::start_critical_operation`], unknown ids are
+    /// ignored
+    pub(crate) fn end_critical_operation(&self, id: u64) {
+        self.critical_operations.end(id);
+    }
+
+    /// Returns true while an operation started with [`Handler::begin_critical_operation`] runs
+    pub fn has_critical_operation(&self) -> bool {
+        !self.critical_operation_names().is_empty()
+    }
+
+    /// Returns the names of the running critical operations
+    pub fn critical_operation_names(&self) -> Vec<String> {
+        self.critical_operations.names()
+    }
+
+    /// Waits until all critical operations finished or the timeout elapsed
+    /// Returns true if no critical operation is running anymore
+    pub async fn wait_for_critical_operations(&self, timeout: Duration) -> bool {
+        self.critical_operations.wait(timeout).await
+    }
+
+    /// Delays closing the app while a critical operation runs, for at most `timeout`
+    /// When an exit is delayed, `blec://exit-blocked` is emitted to the frontend with the names
+    /// of the running operations. `None` disables the guard, which is the default.
+    pub fn set_exit_guard(&self, timeout: Option<Duration>) {
+        *self.exit_guard.lock().expect("exit guard lock poisoned") = timeout;
+    }
+
+    pub(crate) fn exit_guard(&self) -> Option<Duration> {
+        *self.exit_guard.lock().expect("exit guard lock poisoned")
+    }
+
+    /// Marks the exit as delayed by the exit guard, returns false if it was delayed before
+    pub(crate) fn delay_exit(&self) -> bool {
+        !self.exit_delayed.swap(true, Ordering::AcqRel)
+    }
+
+    /// Returns the latency histograms of reads, writes and subscribes since the last reset
+    pub fn get_metrics(&self) -> BleMetrics {
+        self.metrics.lock().expect("metrics lock poisoned").clone()
+    }
+
+    /// Clears the latency histograms
+    pub fn reset_metrics(&self) {
+        *self.metrics.lock().expect("metrics lock poisoned") = BleMetrics::default();
+    }
+
+    /// Periodically publishes the current [`BleMetrics`] as [`BleEvent::MetricsReported`],
+    /// which is emitted to the frontend as `blec://metrics`
+    /// `None` stops publishing the metrics.
+    pub async fn set_metrics_interval(&'static self, interval: Option<Duration>) {
+        let mut state = self.state.lock().await;
+        if let Some(task) = state.metrics_task.take() {
+            task.abort();
+        }
+        let Some(interval) = interval else {
+            return;
+        };
+        state.metrics_task = Some(runtime::spawn(async move {
+            loop {
+                sleep(interval).await;
+                self.publish(BleEvent::MetricsReported(self.get_metrics()));
+            }
+        }));
+    }
+
+    pub(crate) fn record_metrics(&self, record: impl FnOnce(&mut BleMetrics)) {
+        record(&mut self.metrics.lock().expect("metrics lock poisoned"));
+    }
+
+    /// Sets how reads and writes failing with transient errors are retried
+    pub fn set_retry_policy(&self, policy: RetryPolicy) {
+        *self.retry_policy.lock().expect("retry policy lock poisoned") = policy;
+    }
+
+    /// Sets how addresses passed to the handler are checked
+    /// With the default [`AddressPolicy::Coerce`] any separator and case is accepted and the
+    /// address is converted to the canonical form of [`crate::models::parse_address`] before it
+    /// is compared with cached devices. [`AddressPolicy::Strict`] rejects every other form.
+    pub fn set_address_policy(&self, policy: AddressPolicy) {
+        *self.address_policy.lock().expect("address policy lock poisoned") = policy;
+    }
+
+    /// Converts an address passed in to the canonical form used as cache key
+    /// # Errors
+    /// Returns [`Error::InvalidAddress`] if the address is rejected by the [`AddressPolicy`]
+    pub(crate) fn parse_address(&self, address: &str) -> Result<String, Error> {
+        let policy = *self.address_policy.lock().expect("address policy lock poisoned");
+        models::parse_address(address, policy)
+    }
+
+    /// Runs the operation, retrying transient errors according to the [`RetryPolicy`]
+    /// The caller holds the device, so the pauses between attempts also hold back queued
+    /// operations.
+    pub(crate) async fn with_retry<T, F, Fut>(&self, mut op: F) -> Result<T, btleplug::Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = Result<T, btleplug::Error>>,
+    {
+        let policy = *self.retry_policy.lock().expect("retry policy lock poisoned");
+        let mut attempt = 1;
+        loop {
+            let e = match op().await {
+                Ok(value) => return Ok(value),
+                Err(e) => e,
+            };
+            let Some(kind) = TransientError::classify(&e) else {
+                return Err(e);
+            };
+            let pause = match kind {
+                TransientError::InsufficientResources => {
+                    self.record_metrics(|m| m.insufficient_resources += 1);
+                    policy.backoff * attempt
+                }
+                TransientError::Congested => {
+                    self.record_metrics(|m| m.congestion += 1);
+                    CONGESTION_PAUSE
+                }
+            };
+            if attempt >= policy.max_attempts {
+                return Err(e);
+            }
+            debug!("{kind:?} on attempt {attempt}, retrying in {pause:?}: {e}");
+            self.record_metrics(|m| m.retries += 1);
+            sleep(pause).await;
+            attempt += 1;
+        }
+    }
+
+    /// Sets the default maximum number of simultaneous connections
+    /// With [`ConnectionLimitPolicy::Queue`] a connect waits until a connection is closed,
+    /// with [`ConnectionLimitPolicy::FailFast`] it fails with [`Error::ConnectionLimitReached`].
+    /// The default is 5 on android, where the GATT stack degrades quickly, and 10 otherwise.
+    /// A single connect can use another limit with [`ConnectOptions::connection_limit`].
+    pub async fn set_connection_limit(&self, max: usize, policy: ConnectionLimitPolicy) {
+        let mut slots = self.slots.lock().await;
+        slots.max = max;
+        slots.policy = policy;
+        // a raised limit might allow queued connects to continue
+        self.slot_released.notify_waiters();
+    }
+
+    /// Counts a connection against the limit, `None` uses the limit of
+    /// [`Handler::set_connection_limit`]
+    pub(crate) async fn acquire_slot(&self, limit: Option<ConnectionLimit>) -> Result<(), Error> {
+        loop {
+            let released = self.slot_released.notified();
+            {
+                let mut slots = self.slots.lock().await;
+                let ConnectionLimit { max, policy } = limit.unwrap_or(ConnectionLimit {
+                    max: slots.max,
+                    policy: slots.policy,
+                });
+                if slots.active < max {
+                    slots.active += 1;
+                    return Ok(());
+                }
+                if policy == ConnectionLimitPolicy::FailFast {
+                    return Err(Error::ConnectionLimitReached { limit: max });
+                }
+            }
+            debug!("connection limit reached, waiting for a free slot");
+            released.await;
+        }
+    }
+
+    pub(crate) async fn release_slot(&self) {
+        let mut slots = self.slots.lock().await;
+        slots.active = slots.active.saturating_sub(1);
+        self.slot_released.notify_waiters();
+    }
+
+    /// Takes a sender that will be used to send changes in the scanning status
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// use tokio::sync::mpsc;
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     let (tx, mut rx) = mpsc::channel(1);
+    ///     handler.set_scanning_update_channel(tx).await;
+    ///     while let Some(scanning) = rx.recv().await {
+    ///         println!("Scanning: {scanning}");
+    ///     }
+    /// });
+    /// # }
+    /// ```
+    pub async fn set_scanning_update_channel(&self, tx: mpsc::Sender<bool>) {
+        self.state.lock().await.scan_update_channel.push(tx);
+    }
+
+    /// Takes a sender that will be used to send changes in the connection status
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// use tokio::sync::mpsc;
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     let (tx, mut rx) = mpsc::channel(1);
+    ///     handler.set_connection_update_channel(tx).await;
+    ///     while let Some(connected) = rx.recv().await {
+    ///         println!("Connected: {connected}");
+    ///     }
+    /// });
+    /// # }
+    /// ```
+    pub async fn set_connection_update_channel(&self, tx: mpsc::Sender<bool>) {
+        self.state.lock().await.connection_update_channel.push(tx);
+    }
+
+    /// Connects to the given address
+    /// If a callback is provided, it will be called when the device is disconnected.
+    /// All characteristics of all services of the device can be used once connected.
+    /// While another device is connected, the device gets a connection of its own like
+    /// [`Handler::connect_additional`] and the connected device is kept. The callback and the
+    /// connect options apply to each device on its own.
+    /// Because connecting sometimes fails especially on android, this method tries up to 3 times
+    /// before returning an error
+    /// # Errors
+    /// Returns an error if no devices are found, if the device is already connected,
+    /// if the connection fails, or if the service/characteristics discovery fails.
+    /// If a failed device advertises Bluetooth Classic support, [`Error::LikelyClassicOnlyDevice`]
+    /// is returned instead of the platform error. A connect that timed out keeps its timeout
+    /// error, it does not tell whether the device is classic only.
+    /// Fails with [`Error::AdapterPoweredOff`] while the adapter is powered off.
+    /// Fails with [`Error::InvalidAddress`] if the address is rejected by the
+    /// [`AddressPolicy`], see [`Handler::set_address_policy`].
+    /// If the address is unknown or its cached handle was removed by the adapter, the platform
+    /// is asked for the device by address. Only if it does not know the device, a short scan is
+    /// run to find it first, see [`ConnectOptions::scan_timeout_ms`].
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// async_runtime::block_on(async {
+    ///    let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///    handler.connect("00:00:00:00:00:00", Some(Box::new(|| println!("disconnected")))).await.unwrap();
+    /// });
+    /// # }
+    /// ```
+    pub async fn connect(
+        &'static self,
+        address: &str,
+        on_disconnect: Option<Box<dyn Fn() + Send>>,
+    ) -> Result<Vec<btleplug::models::Service>, Error> {
+        let result = self
+            .connect_with_options(address, on_disconnect, ConnectOptions::default())
+            .await?;
+        Ok(result.services)
+    }
+
+    /// Connects to the given address like [`Handler::connect`], with additional options
+    /// The setup steps run right after the services are resolved, the connect only returns once
+    /// all steps finished. The connection only becomes [`ConnectionState::Ready`] after the
+    /// bonding and the setup steps, other operations fail with [`Error::NotReady`] until then.
+    /// # Errors
+    /// Returns the same errors as [`Handler::connect`].
+    /// If a setup step with [`StepErrorPolicy::Abort`] fails, the device is disconnected and
+    /// [`Error::SetupFailed`] is returned.
+    /// Returns [`Error::ConnectionTimeout`] if the link and services are not ready within
+    /// [`ConnectOptions::timeout_ms`].
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// use tauri_plugin_blec::ConnectOptions;
+    /// async_runtime::block_on(async {
+    ///    let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///    let options = ConnectOptions { request_mtu: Some(247), ..Default::default() };
+    ///    handler.connect_with_options("00:00:00:00:00:00", None, options).await.unwrap();
+    /// });
+    /// # }
+    /// ```
+    pub async fn connect_with_options(
+        &'static self,
+        address: &str,
+        on_disconnect: Option<Box<dyn Fn() + Send>>,
+        options: ConnectOptions,
+    ) -> Result<ConnectResult, Error> {
+        let address = &self.parse_address(address)?;
+        self.check_authorized()?;
+        self.check_powered()?;
+        let connection = self.connection_entry(address).await;
+        self.claim_main(address);
+        self.timelines().start(address);
+        self.set_connection_state(address, ConnectionState::Connecting);
+        let _reset = ConnectingGuard {
+            handler: self,
+            address: address.clone(),
+        };
+        let result = self.link_connection(&connection, on_disconnect, options).await;
+        if result.is_err() {
+            self.drop_connection(&connection);
+        }
+        result
+    }
+
+    /// Connects the device of the connection, see [`Handler::connect_with_options`]
+    async fn link_connection(
+        &'static self,
+        connection: &Arc<Connection>,
+        on_disconnect: Option<Box<dyn Fn() + Send>>,
+        options: ConnectOptions,
+    ) -> Result<ConnectResult, Error> {
+        let address = &connection.address;
+        let budget = Budget::new(options.total_timeout_ms.map(Duration::from_millis));
+        let scan_timeout = options.scan_timeout_ms.unwrap_or(CONNECT_SCAN_TIMEOUT);
+        budget
+            .run("scan", self.find_peripheral(address, scan_timeout))
+            .await?;
+        if let Some(adapter) = &options.adapter {
+            self.use_scanned_adapter(address, adapter).await?;
+        }
+        // the adapter picked above is the most recently seen handle
+        if options.adapter.is_none() || options.peripheral_id.is_some() {
+            self.resolve_peripheral(address, options.peripheral_id.as_deref())
+                .await?;
+        }
+        // cancel any running discovery
+        let _paused = self.pause_scan().await;
+        // a connect to the connected device keeps its slot, acquiring another one first would
+        // wait forever at a limit of one
+        let replacing = connection.tasks.lock().await.holds_slot;
+        if !replacing {
+            let slot = self.acquire_slot(options.connection_limit);
+            budget.run("waiting for a connection slot", slot).await?;
+        }
+        let link_timeout = LinkTimeout::new(options.timeout_ms);
+        // connect to the given address
+        // try up to 3 times before returning an error
+        let mut connected = Ok(());
+        for i in 0..3 {
+            let attempt = link_timeout.run(self.connect_device(connection));
+            if let Err(e) = budget.run("connect", attempt).await {
+                let timed_out =
+                    matches!(e, Error::TotalTimeout { .. } | Error::ConnectionTimeout { .. });
+                if i < 2 && !timed_out {
+                    warn!("Failed to connect device, retrying in 1s: {e}");
+                    sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+                connected = Err(e);
+                break;
+            } else {
+                connected = Ok(());
+                break;
+            }
+        }
+        if let Err(e) = connected {
+            let timed_out =
+                matches!(e, Error::TotalTimeout { .. } | Error::ConnectionTimeout { .. });
+            if timed_out {
+                self.cancel_pending_connect(address).await;
+            }
+            let main = self.is_main(address);
+            connection.set_link(None).await;
+            self.set_connected(connection, main, false);
+            // also gives up the slot of a replaced connection, its device is gone as well
+            connection.tasks.lock().await.holds_slot = false;
+            self.release_slot().await;
+            error!("Failed to connect device: {e}");
+            // a device out of range times out as well, the transport is unknown then
+            if !timed_out && self.is_likely_classic_only(address).await {
+                return Err(Error::LikelyClassicOnlyDevice(address.to_string()));
+            }
+            return Err(e);
+        }
+        self.set_connection_state(address, ConnectionState::DiscoveringServices);
+        self.clocks.start_session(address);
+        {
+            let mut tasks = connection.tasks.lock().await;
+            tasks.holds_slot = true;
+            // set callback to run on disconnect
+            if let Some(cb) = on_disconnect {
+                tasks.on_disconnect = Some(cb);
+            }
+        }
+        let discovery = link_timeout.run(async {
+            if let Some(mtu) = options.request_mtu {
+                self.try_request_mtu(connection, mtu).await;
+            }
+            // discover service/characteristics
+            let services = self.connect_services(connection).await?;
+            let characteristics = self
+                .check_characteristics(connection, &options.characteristics)
+                .await?;
+            Ok::<_, Error>((services, characteristics))
+        });
+        let discovered = budget.run("service discovery", discovery).await;
+        let (services, characteristics) = match discovered {
+            Ok(discovered) => discovered,
+            Err(e) => {
+                self.abort_connect(connection, "service discovery", &e).await;
+                return Err(e);
+            }
+        };
+        if options.require_bonded {
+            // bonded before the device is ready, so no operation runs on an unencrypted link
+            let bonding = SETTING_UP.scope((), self.ensure_bonded(address));
+            if let Err(e) = budget.run("bonding", bonding).await {
+                self.abort_connect(connection, "bonding", &e).await;
+                return Err(e);
+            }
+        }
+
+        // start background task for notifications
+        let dev = connection.link().ok_or(Error::NoDeviceConnected)?;
+        let keep_alive = self.state.lock().await.keep_alive.clone();
+        let mut tasks = connection.tasks.lock().await;
+        // a connect to the connected device replaces the tasks of its previous connect
+        tasks.abort();
+        let watch = self.interference.watch(dev.id());
+        tasks.listen = Some(runtime::spawn(listen_notify(
+            dev,
+            connection.listeners.clone(),
+            connection.sessions.clone(),
+            connection.interceptors.clone(),
+            connection.last_activity.clone(),
+            self.publisher.clone(),
+            self.clocks.clone(),
+            watch.clone(),
+        )));
+        #[cfg(target_os = "android")]
+        {
+            tasks.mtu = self.watch_mtu(connection.clone()).await;
+        }
+        connection.mark_activity();
+        self.start_keep_alive(connection, keep_alive.as_ref(), &mut tasks);
+        let watch = runtime::spawn(self.run_interference_watch(address.clone(), watch));
+        if let Some(task) = tasks.interference.replace(watch) {
+            task.abort();
+        }
+        if let Some(ms) = options.idle_disconnect_ms {
+            let reconnect = options.reconnect_on_demand.then(|| IdleReconnect {
+                handler: self,
+                address: address.to_string(),
+                options: options.clone(),
+            });
+            tasks.idle = Some(runtime::spawn(self.run_idle_disconnect(
+                connection.clone(),
+                Duration::from_millis(ms),
+                reconnect,
+            )));
+        }
+        tasks.auto_reconnect = options.reconnect.map(|policy| AutoReconnect {
+            handler: self,
+            address: address.to_string(),
+            options: options.clone(),
+            policy,
+        });
+        drop(tasks);
+
+        let setup = SETTING_UP.scope((), self.run_setup(connection, options.setup, &budget));
+        let setup = match setup.await {
+            Ok(setup) => setup,
+            Err(e) => {
+                self.abort_connect(connection, "setup", &e).await;
+                return Err(e);
+            }
+        };
+        let gatt = services_of(connection)
+            .await
+            .iter()
+            .map(ServiceInfo::from)
+            .collect();
+        self.set_connection_state(address, ConnectionState::Ready);
+        self.remember_connected(address.clone());
+        self.publish(BleEvent::Connected {
+            address: address.to_string(),
+        });
+        #[cfg(any(target_os = "macos", target_os = "ios"))]
+        {
+            let id = connection.link_id();
+            *connection
+                .write_limits
+                .lock()
+                .expect("write limits lock poisoned") = id.as_ref().and_then(apple_write_limits);
+        }
+        Ok(ConnectResult {
+            services,
+            characteristics,
+            gatt,
+            setup,
+            max_write_len: connection.write_limits(),
+        })
+    }
+
+    /// Pairs with the device unless it is already bonded
+    /// # Errors
+    /// Returns [`Error::PairingRejected`] if the user or the device rejected pairing and
+    /// [`Error::PairingFailed`] for other failures, if the platform can not bond or if pairing
+    /// did not complete within [`BOND_TIMEOUT`]
+    async fn ensure_bonded(&self, address: &str) -> Result<(), Error> {
+        if let Ok(result) = timeout(BOND_TIMEOUT, self.bond(address)).await {
+            return result;
+        }
+        let reason = format!("not bonded within {BOND_TIMEOUT:?}");
+        self.send_pairing_event(
+            address,
+            PairingState::Failed {
+                reason: reason.clone(),
+            },
+        )
+        .await;
+        Err(Error::PairingFailed(reason))
+    }
+
+    async fn bond(&self, address: &str) -> Result<(), Error> {
+        #[cfg(target_os = "android")]
+        {
+            // cloned to not block other operations while the user confirms pairing
+            let dev = self.bonding_peripheral(address).await?;
+            if dev.is_bonded().await? {
+                debug!("{address} is already bonded");
+                self.set_bond_state(address, true);
+                return Ok(());
+            }
+            self.set_bond_state(address, false);
+            self.send_pairing_event(address, PairingState::Started).await;
+            let res = dev.create_bond().await;
+            let reason = match res {
+                Ok(Ok(())) => {
+                    self.set_bond_state(address, true);
+                    self.send_pairing_event(address, PairingState::Bonded).await;
+                    return Ok(());
+                }
+                Ok(Err(reason)) => reason,
+                Err(e) => {
+                    let reason = e.to_string();
+                    self.send_pairing_event(
+                        address,
+                        PairingState::Failed {
+                            reason: reason.clone(),
+                        },
+                    )
+                    .await;
+                    return Err(Error::PairingFailed(reason));
+                }
+            };
+            // UNBOND_REASON_* values of android.bluetooth.BluetoothDevice
+            let (rejected, name) = match reason {
+                1 => (false, "authentication failed"),
+                2 => (true, "authentication rejected"),
+                3 => (true, "authentication canceled"),
+                4 => (false, "remote device down"),
+                5 => (false, "discovery in progress"),
+                6 => (false, "authentication timeout"),
+                7 => (false, "repeated attempts"),
+                8 => (true, "canceled by the remote device"),
+                9 => (false, "bond removed"),
+                _ => (false, "unknown reason"),
+            };
+            let reason = format!("{name} ({reason})");
+            self.send_pairing_event(
+                address,
+                PairingState::Failed {
+                    reason: reason.clone(),
+                },
+            )
+            .await;
+            if rejected {
+                Err(Error::PairingRejected(reason))
+            } else {
+                Err(Error::PairingFailed(reason))
+            }
+        }
+        #[cfg(target_os = "windows")]
+        {
+            self.pair_with_consent(address).await
+        }
+        #[cfg(not(any(target_os = "android", target_os = "windows")))]
+        {
+            let reason = "the bond state can not be queried on this platform".to_string();
+            self.send_pairing_event(
+                address,
+                PairingState::Failed {
+                    reason: reason.clone(),
+                },
+            )
+            .await;
+            Err(Error::PairingFailed(reason))
+        }
+    }
+
+    /// Returns the connected device if it has the address, otherwise the device found by a scan
+    #[cfg(target_os = "android")]
+    async fn bonding_peripheral(&self, address: &str) -> Result<Peripheral, Error> {
+        let connected = self.connection(address).and_then(|c| c.link());
+        match connected {
+            Some(dev) => Ok(dev),
+            None => self
+                .known_peripheral(address)
+                .await
+                .ok_or_else(|| Error::UnknownPeripheral(address.to_string())),
+        }
+    }
+
+    /// Pairs with the device, nothing happens if it is already bonded
+    /// The device does not have to be connected, on android it must have been found by a scan.
+    /// Progress is published as [`BleEvent::Pairing`], on Windows the consent of the user is
+    /// requested with [`BleEvent::PairingConsentRequired`].
+    /// Operations that fail because the link is not encrypted return [`Error::PairingRequired`],
+    /// which is the cue to call this.
+    /// # Errors
+    /// Returns [`Error::PairingRejected`] if the user or the device rejected pairing and
+    /// [`Error::PairingFailed`] for other failures.
+    /// Returns [`Error::UnsupportedOnPlatform`] on macOS and iOS, where the OS pairs on its own
+    /// once an operation needs it, and on linux, where btleplug does not expose BlueZ pairing.
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     handler.pair("00:00:00:00:00:00").await.unwrap();
+    /// });
+    /// # }
+    /// ```
+    pub async fn pair(&self, address: &str) -> Result<(), Error> {
+        let address = &self.parse_address(address)?;
+        #[cfg(any(target_os = "android", target_os = "windows"))]
+        {
+            self.check_authorized()?;
+            self.ensure_bonded(address).await
+        }
+        #[cfg(not(any(target_os = "android", target_os = "windows")))]
+        {
+            let _ = address;
+            Err(Error::UnsupportedOnPlatform("pairing"))
+        }
+    }
+
+    /// Asks the OS whether the device is bonded and updates [`KnownDevice::bonded`]
+    /// # Errors
+    /// Returns [`Error::UnsupportedOnPlatform`] where the bond state can not be queried, see
+    /// [`Handler::pair`]
+    pub async fn is_paired(&self, address: &str) -> Result<bool, Error> {
+        let address = &self.parse_address(address)?;
+        #[cfg(target_os = "android")]
+        {
+            let bonded = self.bonding_peripheral(address).await?.is_bonded().await?;
+            self.set_bond_state(address, bonded);
+            Ok(bonded)
+        }
+        #[cfg(target_os = "windows")]
+        {
+            let bonded = crate::consent::windows_pairing::is_paired(address).await?;
+            self.set_bond_state(address, bonded);
+            Ok(bonded)
+        }
+        #[cfg(not(any(target_os = "android", target_os = "windows")))]
+        {
+            let _ = address;
+            Err(Error::UnsupportedOnPlatform("querying the bond state"))
+        }
+    }
+
+    pub(crate) async fn send_pairing_event(&self, address: &str, state: PairingState) {
+        self.publish(BleEvent::Pairing(PairingEvent {
+            address: address.to_string(),
+            state,
+        }));
+    }
+
+    /// Requests the MTU on the device of the connection, failures keep the default MTU
+    pub(crate) async fn try_request_mtu(&self, connection: &Connection, mtu: u16) {
+        match self.request_mtu_of(connection, mtu).await {
+            Ok(_) => {}
+            Err(Error::UnsupportedOnPlatform(_)) => {
+                debug!("ignoring MTU request of {mtu}: {MTU_NOTE}");
+            }
+            Err(e) => warn!("MTU request failed, keeping the default MTU: {e}"),
+        }
+    }
+
+    /// Requests the MTU on the connected device and returns the MTU actually granted
+    /// The granted MTU can be lower than the requested one, it is also reported by
+    /// [`Handler::get_mtu`] and published as [`BleEvent::MtuChanged`].
+    /// # Errors
+    /// Returns [`Error::UnsupportedOnPlatform`] on platforms other than android, where the OS
+    /// negotiates the MTU, [`Error::NoDeviceConnected`] if no device is connected or an error if
+    /// the request fails
+    #[cfg_attr(
+        not(target_os = "android"),
+        allow(clippy::unused_async, clippy::unused_self)
+    )]
+    pub async fn request_mtu(&self, mtu: u16) -> Result<u16, Error> {
+        #[cfg(target_os = "android")]
+        {
+            let connection = self.main_connection().ok_or(Error::NoDeviceConnected)?;
+            self.request_mtu_of(&connection, mtu).await
+        }
+        #[cfg(not(target_os = "android"))]
+        {
+            let _ = mtu;
+            Err(Error::UnsupportedOnPlatform("requesting the MTU"))
+        }
+    }
+
+    /// Requests the MTU on the device of the connection, see [`Handler::request_mtu`]
+    #[cfg_attr(
+        not(target_os = "android"),
+        allow(clippy::unused_async, clippy::unused_self)
+    )]
+    pub(crate) async fn request_mtu_of(
+        &self,
+        connection: &Connection,
+        mtu: u16,
+    ) -> Result<u16, Error> {
+        #[cfg(target_os = "android")]
+        {
+            self.check_authorized()?;
+            let dev = connection.dev.lock().await;
+            let dev = dev.as_ref().ok_or(Error::NoDeviceConnected)?;
+            let negotiated = dev.request_mtu(mtu).await?;
+            debug!("requested MTU {mtu}, negotiated {negotiated}");
+            self.set_mtu(connection, negotiated);
+            Ok(negotiated)
+        }
+        #[cfg(not(target_os = "android"))]
+        {
+            let _ = (connection, mtu);
+            Err(Error::UnsupportedOnPlatform("requesting the MTU"))
+        }
+    }
+
+    /// Requests the connection priority on the device of the connection
+    /// Returns false if the platform does not support connection priorities
+    #[cfg_attr(
+        not(target_os = "android"),
+        allow(clippy::unused_async, clippy::unused_self)
+    )]
+    pub(crate) async fn set_connection_priority(
+        &self,
+        connection: &Connection,
+        priority: ConnectionPriority,
+    ) -> Result<bool, Error> {
+        #[cfg(target_os = "android")]
+        {
+            let dev = connection.dev.lock().await;
+            let dev = dev.as_ref().ok_or(Error::NoDeviceConnected)?;
+            dev.request_connection_priority(priority).await?;
+            self.timelines().record_priority(&models::peripheral_address(dev), priority);
+            Ok(true)
+        }
+        #[cfg(not(target_os = "android"))]
+        {
+            let _ = connection;
+            debug!("ignoring connection priority {priority:?}, not supported on this platform");
+            Ok(false)
+        }
+    }
+
+    /// Returns the current ATT MTU of the connected device
+    /// This is the default MTU of 23 until a larger one was negotiated. On android the value
+    /// follows renegotiations started by the device, every change is published as
+    /// [`BleEvent::MtuChanged`].
+    /// # Errors
+    /// Returns [`Error::UnsupportedOnPlatform`] on platforms that do not report the MTU and
+    /// [`Error::NoDeviceConnected`] if no device is connected
+    pub fn get_mtu(&self) -> Result<u16, Error> {
+        #[cfg(target_os = "android")]
+        {
+            let connection = self
+                .main_connection()
+                .filter(|c| c.is_connected())
+                .ok_or(Error::NoDeviceConnected)?;
+            let mtu = *connection.mtu.lock().expect("mtu lock poisoned");
+            Ok(mtu.unwrap_or(DEFAULT_ATT_MTU))
+        }
+        #[cfg(not(target_os = "android"))]
+        {
+            Err(Error::UnsupportedOnPlatform("querying the MTU"))
+        }
+    }
+
+    /// Returns the largest payload of a single write, the MTU minus the ATT header
+    /// `None` if no MTU was negotiated, see [`Handler::get_mtu`]
+    pub fn max_write_len(&self) -> Option<usize> {
+        self.main_connection()?.max_write_len()
+    }
+
+    /// Returns the largest payload of a single write of the given type
+    /// Apple platforms limit writes per write type (`maximumWriteValueLength(for:)`), writes
+    /// without response above the limit are dropped. The limits are queried from CoreBluetooth
+    /// when the connect finished, every other platform falls back to [`Handler::max_write_len`].
+    pub fn max_write_len_for(&self, write_type: models::WriteType) -> Option<usize> {
+        self.main_connection()?.max_write_len_for(write_type)
+    }
+
+    /// Returns [`Handler::max_write_len_for`] of both write types
+    pub fn write_limits(&self) -> WriteLimits {
+        let Some(connection) = self.main_connection() else {
+            return WriteLimits::default();
+        };
+        connection.write_limits()
+    }
+
+    /// Returns the fingerprint last seen with the address, see [`Handler::set_device_fingerprint`]
+    pub(crate) async fn fingerprint_of(&self, address: &str) -> Option<String> {
+        self.fingerprints
+            .lock()
+            .await
+            .current
+            .iter()
+            .find(|(_, current)| *current == address)
+            .map(|(fingerprint, _)| fingerprint.clone())
+    }
+
+    /// Updates the cached MTU of the connection, a change of the main connection is published
+    /// as [`BleEvent::MtuChanged`]
+    #[cfg(target_os = "android")]
+    fn set_mtu(&self, connection: &Connection, mtu: u16) {
+        let previous = connection.mtu.lock().expect("mtu lock poisoned").replace(mtu);
+        if previous != Some(mtu) {
+            info!("MTU of {} changed to {mtu}", connection.address);
+            self.timelines().record_mtu(&connection.address, mtu);
+            if self.is_main(&connection.address) {
+                self.publish(BleEvent::MtuChanged(mtu));
+            }
+        }
+    }
+
+    /// Follows the MTU of the device of the connection, including renegotiations started by
+    /// the device
+    #[cfg(target_os = "android")]
+    async fn watch_mtu(
+        &'static self,
+        connection: Arc<Connection>,
+    ) -> Option<runtime::JoinHandle<()>> {
+        let dev = connection.link()?;
+        let mut changes = match dev.mtu_changes().await {
+            Ok(changes) => changes,
+            Err(e) => {
+                warn!("failed to listen for MTU changes: {e}");
+                return None;
+            }
+        };
+        Some(runtime::spawn(async move {
+            while let Some(mtu) = changes.next().await {
+                self.set_mtu(&connection, mtu);
+            }
+        }))
+    }
+
+    /// Describes which connection features the platform supports
+    pub fn connection_capabilities(&self) -> ConnectionCapabilities {
+        ConnectionCapabilities {
+            mtu_request: cfg!(target_os = "android"),
+            mtu_note: MTU_NOTE,
+            bonding: cfg!(any(target_os = "android", target_os = "windows")),
+        }
+    }
+
+    async fn connect_services(
+        &self,
+        connection: &Connection,
+    ) -> Result<Vec<btleplug::models::Service>, Error> {
+        let device = connection.dev.lock().await;
+        let device = device.as_ref().ok_or(Error::NoDeviceConnected)?;
+        let services = device.services();
+        if services.is_empty() {
+            device.discover_services().await?;
+            //services = device.services();
+        }
+        Ok(device.services().into_iter().map(|x|x.into()).collect())
+    }
+
+    /// Checks that the cached handle for the address still exists in the adapter
+    /// Lost handles are removed from the cache
+    async fn is_known_peripheral(&self, address: &str) -> bool {
+        let Some(id) = self
+            .devices
+            .lock()
+            .await
+            .get(address)
+            .map(btleplug::api::Peripheral::id)
+        else {
+            return false;
+        };
+        for scanned in self.scanning_adapters() {
+            if scanned.adapter.peripheral(&id).await.is_ok() {
+                return true;
+            }
+        }
+        debug!("cached device {address} was removed by the adapter");
+        self.devices.lock().await.remove(address);
+        false
+    }
+
+    /// Makes sure the devices contain a handle for the address before connecting
+    /// Cached handles are used as is, otherwise the platform is asked for the address and only
+    /// if that fails a scan of up to `scan_timeout` ms is run.
+    pub(crate) async fn find_peripheral(
+        &'static self,
+        address: &str,
+        scan_timeout: u64,
+    ) -> Result<(), Error> {
+        if self.is_known_peripheral(address).await || self.lookup_peripheral(address).await {
+            return Ok(());
+        }
+        self.scan_for(address, scan_timeout).await
+    }
+
+    /// Asks the platform for the device without scanning
+    /// Devices the OS knows, e.g. bonded devices or devices BlueZ cached, are listed by the
+    /// adapter. Others are added by their platform id where the platform supports it, like
+    /// android and windows. Returns false if the platform has no handle for the address.
+    async fn lookup_peripheral(&self, address: &str) -> bool {
+        let adapter = self.adapter();
+        let listed = match adapter.peripherals().await {
+            Ok(peripherals) => peripherals
+                .into_iter()
+                .find(|p| models::peripheral_address(p) == address),
+            Err(e) => {
+                debug!("failed to list the known devices: {e}");
+                None
+            }
+        };
+        let peripheral = match listed {
+            Some(peripheral) => peripheral,
+            None => {
+                let Some(id) = platform_peripheral_id(address) else {
+                    return false;
+                };
+                match adapter.add_peripheral(&id).await {
+                    Ok(peripheral) => peripheral,
+                    Err(e) => {
+                        debug!("the platform does not know {address}: {e}");
+                        return false;
+                    }
+                }
+            }
+        };
+        debug!("found {address} without scanning");
+        self.devices
+            .lock()
+            .await
+            .insert(None, address.to_string(), peripheral, None);
+        true
+    }
+
+    /// Scans until the given address is found or the scan timed out
+    async fn scan_for(&'static self, address: &str, timeout: u64) -> Result<(), Error> {
+        debug!("scanning for {address} before connecting");
+        self.discover(None, timeout, ScanFilter::None).await?;
+        for _ in 0..timeout / 200 {
+            sleep(Duration::from_millis(200)).await;
+            if self.devices.lock().await.contains_key(address) {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes cached devices that stopped advertising
+    /// btleplug does not emit an event when the OS drops a device, e.g. when BlueZ removes a
+    /// stale device. Instead a device counts as present while the central events of its
+    /// advertisements arrive, see [`Handler::handle_event`]. The android bridge reports scan
+    /// results without central events, there the peripheral list of the scan is checked.
+    /// A device is only removed once it was missing for `debounce`, see
+    /// [`MissingDevices::keep`].
+    /// Returns the addresses of the removed devices.
+    async fn prune_devices(
+        &self,
+        discovered: &[Peripheral],
+        missing: &mut MissingDevices<DeviceKey>,
+        debounce: Duration,
+    ) -> Vec<String> {
+        #[cfg(target_os = "android")]
+        let present: HashSet<PeripheralId> = discovered
+            .iter()
+            .map(btleplug::api::Peripheral::id)
+            .collect();
+        #[cfg(not(target_os = "android"))]
+        let present = {
+            let _ = discovered;
+            self.recently_advertised()
+        };
+        let connected: HashSet<PeripheralId> = self
+            .all_connections()
+            .iter()
+            .filter_map(|c| c.link_id())
+            .collect();
+        // only reported once no adapter has a handle of the address left
+        let now = Instant::now();
+        let lost = self.devices.lock().await.retain(|key, _, p| {
+            let id = p.id();
+            let seen = present.contains(&id) || connected.contains(&id);
+            missing.keep(key, seen, debounce, now)
+        });
+        if lost.is_empty() {
+            return lost;
+        }
+        for address in &lost {
+            info!("device {address} is lost");
+            self.publish(BleEvent::DeviceRemoved(address.clone()));
+        }
+        lost
+    }
+
+    /// Devices with a central event within the last two scan intervals
+    #[cfg(not(target_os = "android"))]
+    fn recently_advertised(&self) -> HashSet<PeripheralId> {
+        self.advertisements
+            .lock()
+            .expect("advertisements lock poisoned")
+            .iter()
+            .filter(|(_, stats)| {
+                stats
+                    .interval
+                    .last()
+                    .is_some_and(|last| last.elapsed() < ADVERTISING_WINDOW)
+            })
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+
+    /// Dual mode devices advertising BR/EDR support often only offer their useful services
+    /// over Classic, which makes every GATT connect fail
+    async fn is_likely_classic_only(&self, address: &str) -> bool {
+        let devices = self.devices.lock().await;
+        let Some(p) = devices.get(address) else {
+            return false;
+        };
+        BleDevice::from_peripheral(p)
+            .await
+            .is_ok_and(|d| d.br_edr_capable == Some(true))
+    }
+
+    async fn connect_device(&self, connection: &Connection) -> Result<(), Error> {
+        let address = &connection.address;
+        debug!("connecting to {address}",);
+        let mut connected_rx = connection.connected_rx.clone();
+        // cloned, so scans can update the devices while the platform connects
+        let device = self
+            .known_peripheral(address)
+            .await
+            .ok_or(Error::UnknownPeripheral(address.to_string()))?;
+        let main = self.is_main(address);
+        connection.disconnecting.open();
+        connection.set_link(Some(device.clone())).await;
+        if device.is_connected().await? {
+            debug!("Device already connected");
+            self.set_connected(connection, main, true);
+        } else {
+            if *connected_rx.borrow_and_update() {
+                warn!("connected flag was still set without a connected device, resetting it");
+                self.set_connected(connection, main, false);
+                connected_rx.borrow_and_update();
+            }
+            debug!("Connecting to device");
+            device.connect().await?;
+            debug!("waiting for connection event");
+            // wait for the actual connection to be established
+            connected_rx
+                .changed()
+                .await
+                .expect("failed to wait for connection event");
+            debug!("Connecting done");
+            if !connection.is_connected() {
+                // still not connected
+                return Err(Error::ConnectionFailed);
+            }
+        }
+
+        if main {
+            self.send_connection_update(true).await;
+        }
+        Ok(())
+    }
+
+    /// Cancels a connect the platform may still be establishing after it timed out
+    async fn cancel_pending_connect(&self, address: &str) {
+        let Some(device) = self.known_peripheral(address).await else {
+            return;
+        };
+        if let Err(e) = device.disconnect().await {
+            debug!("failed to cancel the pending connect to {address}: {e}");
+        }
+    }
+
+    /// Disconnects from the device of [`Handler::connect`]
+    /// This triggers a disconnect and then waits for the actual disconnect event from the adapter
+    ///
+    /// Operations on the device are executed in the order they were issued.
+    /// If `flush` is set, all operations issued before the disconnect are executed first.
+    /// Otherwise only the running operation is awaited, for at most 5 seconds before the
+    /// disconnect is forced, and all queued operations fail with [`Error::Disconnected`].
+    /// Operations issued while the disconnect is in progress always fail with [`Error::Disconnected`].
+    /// A running automatic reconnect, see [`ConnectOptions::reconnect`], is cancelled.
+    /// # Errors
+    /// Returns an error if no device is connected or if the disconnect fails, and
+    /// [`Error::DisconnectFailed`] if the platform did not confirm the disconnect within 10
+    /// seconds. The device stays usable after a failed disconnect.
+    /// # Panics
+    /// panics if there is an error with handling the internal disconnect event
+    pub async fn disconnect(&self, flush: bool) -> Result<(), Error> {
+        let connection = self.main_connection().ok_or(Error::NoDeviceConnected)?;
+        self.disconnect_connection(&connection, flush).await
+    }
+
+    /// Disconnects the device of the connection, see [`Handler::disconnect`]
+    pub(crate) async fn disconnect_connection(
+        &self,
+        connection: &Arc<Connection>,
+        flush: bool,
+    ) -> Result<(), Error> {
+        if connection.cancel_auto_reconnect() && !connection.is_connected() {
+            // the link was already lost, only the reconnect was running
+            self.drop_connection(connection);
+            return Ok(());
+        }
+        self.close_connection(connection, flush).await
+    }
+
+    async fn close_connection(
+        &self,
+        connection: &Arc<Connection>,
+        flush: bool,
+    ) -> Result<(), Error> {
+        let address = &connection.address;
+        debug!("disconnect of {address} triggered by user, flush: {flush}");
+        if !connection.idle_disconnecting.load(Ordering::Acquire) {
+            // an explicit disconnect ends the connection for good
+            connection
+                .idle_reconnect
+                .lock()
+                .expect("idle reconnect lock poisoned")
+                .take();
+        }
+        let mut connected_rx = connection.connected_rx.clone();
+        let previous = self.connection_state_of(address);
+        if previous != ConnectionState::Disconnected {
+            self.set_connection_state(address, ConnectionState::Disconnecting);
+        }
+        if let Err(e) = self.trigger_disconnect(connection, flush, &mut connected_rx).await {
+            connection.disconnecting.open();
+            self.set_connection_state(address, previous);
+            self.drop_connection(connection);
+            return Err(e);
+        }
+        debug!("waiting for disconnect event");
+        // the change will be triggered by handle_event -> handle_disconnect which runs in another
+        // task
+        let changed = timeout(DISCONNECT_EVENT_TIMEOUT, connected_rx.changed()).await;
+        if !matches!(changed, Ok(Ok(()))) || connection.is_connected() {
+            warn!("no disconnect event within {DISCONNECT_EVENT_TIMEOUT:?}, still connected");
+            // operations on the device can run again
+            connection.disconnecting.open();
+            self.set_connection_state(address, previous);
+            return Err(Error::DisconnectFailed);
+        }
+        Ok(())
+    }
+
+    async fn trigger_disconnect(
+        &self,
+        connection: &Connection,
+        flush: bool,
+        connected_rx: &mut watch::Receiver<bool>,
+    ) -> Result<(), Error> {
+        // Scope is important to not lock device while waiting for disconnect event
+        let dev = connection
+            .disconnecting
+            .drain(&connection.dev, flush, IN_FLIGHT_TIMEOUT)
+            .await;
+        let Some(dev) = dev else {
+            warn!("running operation did not finish in time, forcing disconnect");
+            let link = connection.link().ok_or(Error::NoDeviceConnected)?;
+            connected_rx.borrow_and_update();
+            return match timeout(DISCONNECT_EVENT_TIMEOUT, link.disconnect()).await {
+                Ok(res) => res.map_err(Error::from),
+                Err(_) => Err(Error::DisconnectFailed),
+            };
+        };
+        if let Some(dev) = dev.as_ref() {
+            if let Ok(true) = dev.is_connected().await {
+                if !*connected_rx.borrow_and_update() {
+                    warn!("connected flag was not set for the connected device");
+                }
+                dev.disconnect().await?;
+                Ok(())
+            } else {
+                debug!("device is not connected");
+                Err(Error::NoDeviceConnected)
+            }
+        } else {
+            debug!("no device connected");
+            Err(Error::NoDeviceConnected)
+        }
+    }
+
+    /// Fails operations while the adapter is powered off
+    pub(crate) fn check_powered(&self) -> Result<(), Error> {
+        if self.powered_off() {
+            Err(Error::AdapterPoweredOff)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Fails data operations issued before the connect finished, while disconnecting, while
+    /// the adapter is powered off or once bluetooth was denied for the app
+    fn check_ready(&self, connection: &Connection) -> Result<(), Error> {
+        self.check_authorized()?;
+        self.check_powered()?;
+        connection.disconnecting.check()?;
+        match self.connection_state_of(&connection.address) {
+            ConnectionState::Ready => Ok(()),
+            // no device, keep the error of the missing connection
+            ConnectionState::Disconnected => Ok(()),
+            ConnectionState::DiscoveringServices if SETTING_UP.try_with(|()| ()).is_ok() => Ok(()),
+            state => Err(Error::NotReady { state }),
+        }
+    }
+
+    /// Takes the operation queue of the device of the connection once the connection is ready
+    pub(crate) async fn lock_ready<'a>(
+        &self,
+        connection: &'a Connection,
+    ) -> Result<tokio::sync::MutexGuard<'a, Option<Peripheral>>, Error> {
+        let dev = connection.dev.lock().await;
+        self.check_ready(connection)?;
+        Ok(dev)
+    }
+
+    /// Resolves every characteristic of [`ConnectOptions::characteristics`] on the device of
+    /// the connection, see [`select_characteristics`]
+    async fn check_characteristics(
+        &self,
+        connection: &Connection,
+        characteristics: &[CharacteristicRef],
+    ) -> Result<Vec<CharacteristicRef>, Error> {
+        let address = &connection.address;
+        // refs of the main connection have no address, so they keep working without one
+        let key = (!self.is_main(address)).then_some(address.as_str());
+        let dev = connection.dev.lock().await;
+        let dev = dev.as_ref().ok_or(Error::NoDeviceConnected)?;
+        select_characteristics(address, key, dev.characteristics(), characteristics)
+    }
+
+    /// Disconnects after a step of the connect failed once the link was up
+    async fn abort_connect(&self, connection: &Arc<Connection>, step: &str, e: &Error) {
+        error!("{step} failed, disconnecting: {e}");
+        // not a user disconnect, a running automatic reconnect tries again
+        if let Err(e) = self.close_connection(connection, false).await {
+            warn!("disconnect after failed {step} failed: {e}");
+        }
+    }
+
+    /// Clears the state of the connection, updates its connected flag and calls its disconnect
+    /// callback
+    /// A lost link of a connect with [`ConnectOptions::reconnect`] keeps the listeners and
+    /// starts the reconnect.
+    async fn handle_disconnect(&self, peripheral_id: PeripheralId) -> Result<(), Error> {
+        let Some(connection) = self.connection_of(&peripheral_id) else {
+            // event not for a connected device, ignore
+            warn!("Unexpected disconnect event for device {peripheral_id}");
+            return Ok(());
+        };
+        let address = connection.address.clone();
+        let main = self.is_main(&address);
+        let (reconnect, idle) = {
+            debug!("locking tasks for disconnect");
+            let mut tasks = connection.tasks.lock().await;
+            info!("disconnecting {address}");
+            connection.set_link(None).await;
+            let requested = connection.disconnecting.open();
+            let idle = connection.idle_disconnecting.swap(false, Ordering::AcqRel);
+            let reason = if self.powered_off() {
+                DisconnectReason::AdapterOff
+            } else if idle {
+                DisconnectReason::IdleTimeout
+            } else if requested {
+                DisconnectReason::Requested
+            } else {
+                DisconnectReason::LinkLost
+            };
+            self.set_connection_state(&address, ConnectionState::Disconnected);
+            // a lost link is resumed with resume_transfer, an intended disconnect ends it
+            if reason != DisconnectReason::LinkLost {
+                self.drop_paused_transfers(&address);
+            }
+            self.publish(BleEvent::Disconnected {
+                address: address.clone(),
+                reason,
+            });
+            let reconnect = match reason {
+                DisconnectReason::LinkLost => tasks.auto_reconnect.take(),
+                _ => None,
+            };
+            if reconnect.is_some() {
+                if main {
+                    self.claim_main(&address);
+                }
+                self.set_connection_state(&address, ConnectionState::Reconnecting);
+            }
+            tasks.abort();
+            self.interference.forget(&peripheral_id);
+            let listeners = std::mem::take(&mut *connection.listeners.lock().await);
+            if reconnect.is_some() {
+                *connection.kept_listeners.lock().await = listeners;
+            }
+            connection.sessions.lock().await.clear();
+            connection.interceptors.lock().await.clear();
+            if std::mem::take(&mut tasks.holds_slot) {
+                self.release_slot().await;
+            }
+            self.call_on_disconnect(&tasks, &address);
+            (reconnect, idle)
+        };
+        if main {
+            self.send_connection_update(false).await;
+        }
+        self.set_connected(&connection, main, false);
+        if let Some(reconnect) = reconnect {
+            reconnect.handler.start_auto_reconnect(connection, reconnect);
+        } else if !idle {
+            // an idle connection is kept by the idle task if it reconnects on demand
+            self.drop_connection(&connection);
+        }
+        Ok(())
+    }
+
+    fn call_on_disconnect(&self, tasks: &ConnectionTasks, address: &str) {
+        if let Some(callback) = &tasks.on_disconnect {
+            let kind = CallbackKind::OnDisconnect;
+            guard_callback(&self.publisher, kind, address, &address, || callback());
+        }
+    }
+
+    /// Scans for `timeout` milliseconds and periodically sends discovered devices
+    /// to the given channel.
+    /// A task is spawned to handle the scan and send the devices, so the function
+    /// returns immediately.
+    ///
+    /// A Variant of [`ScanFilter`] can be provided to filter the discovered devices
+    /// Service filters are also passed to the adapter, so platforms that filter in the
+    /// bluetooth stack do not report unrelated devices at all. [`ScanFilter::AnyService`]
+    /// with no services does not filter.
+    ///
+    /// # Errors
+    /// Returns an error if starting the scan fails
+    /// # Panics
+    /// Panics if there is an error getting devices from the adapter
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// use tokio::sync::mpsc;
+    /// use tauri_plugin_blec::models::ScanFilter;
+    ///
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     let (tx, mut rx) = mpsc::channel(1);
+    ///     handler.discover(Some(tx),1000, ScanFilter::None).await.unwrap();
+    ///     while let Some(devices) = rx.recv().await {
+    ///         println!("Discovered {devices:?}");
+    ///     }
+    /// });
+    /// # }
+    /// ```
+    pub async fn discover(
+        &'static self,
+        tx: Option<mpsc::Sender<Vec<BleDevice>>>,
+        timeout: u64,
+        filter: ScanFilter,
+    ) -> Result<(), Error> {
+        self.discover_with_name_filter(tx, timeout, filter, ScanNameFilter::None)
+            .await
+    }
+
+    /// Scans like [`Handler::discover`], only reporting devices whose local name passes the
+    /// [`ScanNameFilter`]
+    /// Useful for devices that do not advertise their services. The name filter is applied in
+    /// addition to the [`ScanFilter`], so both can be combined.
+    /// # Errors
+    /// Returns an error if starting the scan fails
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// use tokio::sync::mpsc;
+    /// use tauri_plugin_blec::models::{ScanFilter, ScanNameFilter};
+    ///
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     let (tx, mut rx) = mpsc::channel(1);
+    ///     let name = ScanNameFilter::Prefix("HC-".to_string());
+    ///     handler
+    ///         .discover_with_name_filter(Some(tx), 1000, ScanFilter::None, name)
+    ///         .await
+    ///         .unwrap();
+    ///     while let Some(devices) = rx.recv().await {
+    ///         println!("Discovered {devices:?}");
+    ///     }
+    /// });
+    /// # }
+    /// ```
+    pub async fn discover_with_name_filter(
+        &'static self,
+        tx: Option<mpsc::Sender<Vec<BleDevice>>>,
+        timeout: u64,
+        filter: ScanFilter,
+        name_filter: ScanNameFilter,
+    ) -> Result<(), Error> {
+        let options = DiscoverOptions {
+            timeout: Duration::from_millis(timeout),
+            filter,
+            name_filter,
+            ..DiscoverOptions::default()
+        };
+        self.discover_with_options(tx, options).await
+    }
+
+    /// Scans like [`Handler::discover`] with the filters, removal debounce and event budget of
+    /// the [`DiscoverOptions`]
+    /// # Errors
+    /// Returns an error if starting the scan fails
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use std::time::Duration;
+    /// use tauri::async_runtime;
+    /// use tokio::sync::mpsc;
+    /// use tauri_plugin_blec::{DiscoverOptions, ScanEventBudget};
+    ///
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     let (tx, mut rx) = mpsc::channel(1);
+    ///     let options = DiscoverOptions {
+    ///         timeout: Duration::from_secs(30),
+    ///         event_budget: Some(ScanEventBudget { max_events_per_second: 20 }),
+    ///         ..Default::default()
+    ///     };
+    ///     handler.discover_with_options(Some(tx), options).await.unwrap();
+    ///     while let Some(devices) = rx.recv().await {
+    ///         println!("Discovered {devices:?}");
+    ///     }
+    /// });
+    /// # }
+    /// ```
+    pub async fn discover_with_options(
+        &'static self,
+        tx: Option<mpsc::Sender<Vec<BleDevice>>>,
+        options: DiscoverOptions,
+    ) -> Result<(), Error> {
+        let timeout = u64::try_from(options.timeout.as_millis()).unwrap_or(u64::MAX);
+        let settings = ScanSettings {
+            removal_debounce: options.removal_debounce,
+            event_budget: options.event_budget,
+            ..ScanSettings::default()
+        };
+        self.start_scan_loop(tx, Some(timeout), options.filter, options.name_filter, settings)
+            .await?;
+        Ok(())
+    }
+
+    /// Starts the scan of [`Handler::discover_with_name_filter`], it runs until
+    /// [`Handler::stop_scan`] if there is no timeout
+    /// Returns the generation of the scan for [`Handler::stop_scan_generation`].
+    pub(crate) async fn start_scan_loop(
+        &'static self,
+        tx: Option<mpsc::Sender<Vec<BleDevice>>>,
+        timeout: Option<u64>,
+        filter: ScanFilter,
+        name_filter: ScanNameFilter,
+        settings: ScanSettings,
+    ) -> Result<u64, Error> {
+        self.check_authorized()?;
+        self.check_powered()?;
+        let adapters = self.scanning_adapters();
+        let _scan = self.scan_lock.lock().await;
+        self.record_scan_start(settings.start)?;
+        // the services of the scan consumers, so this scan does not starve them
+        let mut covered = if settings.consumers {
+            None
+        } else {
+            self.scan_consumer_services()
+        };
+        // stop any ongoing scan, its loop has to be done with the devices before they are cleared
+        self.end_scan().await?;
+        #[cfg(not(target_os = "android"))]
+        self.advertisements
+            .lock()
+            .expect("advertisements lock poisoned")
+            .clear();
+        self.devices.lock().await.clear();
+        for (i, scanned) in adapters.iter().enumerate() {
+            let scan_filter = covering_filter(&filter, covered.as_deref());
+            if let Err(e) = scanned.adapter.start_scan(scan_filter).await {
+                // nothing reads the devices of the adapters already scanning
+                for started in &adapters[..i] {
+                    if let Err(e) = started.adapter.stop_scan().await {
+                        warn!("failed to stop scan on {:?}: {e}", started.id);
+                    }
+                }
+                return Err(e.into());
+            }
+        }
+        // filtered scans can legitimately receive nothing
+        #[cfg(target_os = "android")]
+        let unfiltered = covering_filter(&filter, covered.as_deref()).services.is_empty();
+        self.send_scan_update(true).await;
+        let stop = Arc::new(Notify::new());
+        let mut state = self.state.lock().await;
+        let duplicates = state.duplicate_filter;
+        let removal_debounce = settings.removal_debounce.unwrap_or(state.removal_debounce);
+        let mut budget = settings.event_budget.map(EventBudget::new);
+        let mut self_devices = self.devices.clone();
+        state.scan_stop = Some(stop.clone());
+        state.scan_continuous = timeout.is_none();
+        state.scan_generation += 1;
+        let generation = state.scan_generation;
+        state.scan_task = Some(tokio::task::spawn(async move {
+            let loops = timeout.map_or(u64::MAX, |timeout| timeout / 200);
+            let mut paused = false;
+            let mut devices: Vec<BleDevice>;
+            let mut last_sent = vec![];
+            let mut published = HashMap::new();
+            let mut missing = MissingDevices::default();
+            let mut advertised = HashMap::new();
+            let mut properties = ScanPropertiesCache::default();
+            #[cfg(target_os = "android")]
+            let mut silence =
+                unfiltered.then(|| (Instant::now(), crate::android::scan_callbacks()));
+            for _ in 0..loops {
+                let tick = std::pin::pin!(sleep(Duration::from_millis(200)));
+                let stopped = std::pin::pin!(stop.notified());
+                if let Either::Right(_) = select(tick, stopped).await {
+                    debug!("scan stopped before its timeout");
+                    break;
+                }
+                // the adapters were stopped by pause_scan, nothing is reported while connecting
+                let pause_lock = self.scan_pause_lock.lock().await;
+                if self.scan_pauses.load(Ordering::SeqCst) > 0 {
+                    paused = true;
+                    continue;
+                }
+                if paused {
+                    paused = false;
+                    debug!("resuming the scan after connecting");
+                    let _ = self.record_scan_start(ScanStart::Resume);
+                    #[cfg(target_os = "android")]
+                    if let Some(silence) = &mut silence {
+                        *silence = (Instant::now(), crate::android::scan_callbacks());
+                    }
+                    for scanned in &adapters {
+                        let scan_filter = covering_filter(&filter, covered.as_deref());
+                        if let Err(e) = scanned.adapter.start_scan(scan_filter).await {
+                            warn!("failed to resume scan on {:?}: {e}", scanned.id);
+                        }
+                    }
+                }
+                if !settings.consumers {
+                    self.cover_scan_consumers(&adapters, &filter, &mut covered).await;
+                }
+                drop(pause_lock);
+                let polled = poll_adapters(&adapters, &mut properties, |p| {
+                    self.advertisement_count(p)
+                })
+                .await;
+                self.mark_event_loop_alive();
+                #[cfg(target_os = "android")]
+                if let Some((started, callbacks)) = silence {
+                    if crate::android::scan_callbacks() != callbacks {
+                        silence = None;
+                    } else if started.elapsed() >= THROTTLE_SANITY_WINDOW {
+                        silence = None;
+                        self.report_silent_scan();
+                    }
+                }
+                let lost = self
+                    .prune_devices(&polled.peripherals, &mut missing, removal_debounce)
+                    .await;
+                let (discovered, for_consumers) = split_for_consumers(
+                    polled.strongest,
+                    &filter,
+                    &name_filter,
+                    covered.as_deref(),
+                );
+                let mut sightings = polled.sightings;
+                let enricher = self.enricher.lock().await.clone();
+                let fingerprint = self.fingerprint.lock().await.clone();
+                devices = Self::add_devices(
+                    &mut self_devices,
+                    discovered,
+                    &polled.strongest_adapter,
+                    &name_filter,
+                    enricher.as_ref(),
+                    fingerprint.as_ref(),
+                    &self.publisher,
+                )
+                .await;
+                // published for the consumers, but not part of the devices of this scan
+                let mut consumers_only = HashSet::new();
+                if !for_consumers.is_empty() {
+                    let extra = Self::add_devices(
+                        &mut self_devices,
+                        for_consumers,
+                        &polled.strongest_adapter,
+                        &ScanNameFilter::None,
+                        enricher.as_ref(),
+                        fingerprint.as_ref(),
+                        &self.publisher,
+                    )
+                    .await;
+                    consumers_only.extend(extra.iter().map(|d| d.address.clone()));
+                    devices.extend(extra);
+                }
+                for d in &mut devices {
+                    if let Some(seen) = sightings.remove(&d.address) {
+                        d.adapter_id = seen.first().map(|s| s.adapter_id.clone());
+                        d.adapters = seen;
+                    }
+                }
+                merge_advertisements(&mut devices, &mut advertised);
+                self.record_seen(&devices);
+                self.mark_previously_connected(&mut devices);
+                if fingerprint.is_some() {
+                    self.merge_fingerprints(&mut devices).await;
+                }
+                if duplicates == DuplicateFilter::ReportAll {
+                    let self_devices = self_devices.lock().await;
+                    for d in &mut devices {
+                        let p = self_devices.get(&d.address);
+                        d.advertisement_count = p.map(|p| self.advertisement_count(p));
+                        d.adv_interval_ms = p.and_then(|p| self.adv_interval_ms(p));
+                    }
+                }
+                for address in &lost {
+                    published.remove(address);
+                }
+                let changed: Vec<BleDevice> = devices
+                    .iter()
+                    .filter(|d| {
+                        published
+                            .get(&d.address)
+                            .is_none_or(|p| changed_meaningfully(p, d))
+                    })
+                    .cloned()
+                    .collect();
+                let changed = match &mut budget {
+                    Some(budget) => budget.admit(changed, &lost),
+                    None => changed,
+                };
+                for d in changed {
+                    published.insert(d.address.clone(), d.clone());
+                    self.publish(BleEvent::DeviceDiscovered(d));
+                }
+                devices.retain(|d| !consumers_only.contains(&d.address));
+                if duplicates == DuplicateFilter::Deduplicate {
+                    if devices == last_sent {
+                        continue;
+                    }
+                    last_sent.clone_from(&devices);
+                }
+                if !devices.is_empty() {
+                    if let Some(tx) = &tx {
+                        tx.send(devices.clone())
+                            .await
+                            .expect("failed to send devices");
+                    }
+                }
+            }
+            for scanned in &adapters {
+                if let Err(e) = scanned.adapter.stop_scan().await {
+                    warn!("failed to stop scan on {:?}: {e}", scanned.id);
+                }
+            }
+            if let Some(budget) = budget {
+                let stats = budget.finish();
+                info!("scan ended, {stats:?}");
+                self.publish(BleEvent::ScanEventStats(stats));
+            }
+            self.send_scan_update(false).await;
+        }));
+        Ok(generation)
+    }
+
+    /// Discover provided services and charecteristics
+    /// If the device is not connected, a connection is made in order to discover the services and characteristics
+    /// After the discovery is done, the device is disconnected
+    /// If the devices was already connected, it will stay connected
+    /// # Errors
+    /// Returns an error if the device is not found, if the connection fails, or if the discovery fails
+    /// # Panics
+    /// Panics if there is an error with the internal disconnect event
+    pub async fn discover_services(&self, address: &str) -> Result<Vec<Service>, Error> {
+        let address = &self.parse_address(address)?;
+        self.check_authorized()?;
+        let linked = self.connection(address).and_then(|c| c.link());
+        // only set if the device is connected for the discovery
+        let mut connection = None;
+        let device = if let Some(device) = linked {
+            device
+        } else {
+            let device = self
+                .devices
+                .lock()
+                .await
+                .get(address)
+                .ok_or(Error::UnknownPeripheral(address.to_string()))?
+                .clone();
+            if !device.is_connected().await? {
+                let entry = self.connection_entry(address).await;
+                if let Err(e) = self.connect_device(&entry).await {
+                    entry.set_link(None).await;
+                    entry.set_connected(false);
+                    self.drop_connection(&entry);
+                    error!("Failed to connect for discovery: {e}");
+                    return Err(e);
+                }
+                connection = Some(entry);
+            }
+            device
+        };
+        debug!("discovering services on {address}");
+        if device.services().is_empty() {
+            device.discover_services().await?;
+        }
+        let services = device.services().iter().cloned().map(Service::from).collect();
+        if let Some(connection) = connection {
+            let mut connected_rx = connection.connected_rx.clone();
+            if *connected_rx.borrow_and_update() {
+                device.disconnect().await?;
+                debug!("waiting for disconnect event");
+                connected_rx
+                    .changed()
+                    .await
+                    .expect("failed to wait for disconnect event");
+            }
+        }
+        Ok(services)
+    }
+
+    /// Stops scanning for devices
+    /// A running [`Handler::discover`] ends early, the devices found so far were already sent to
+    /// its channel and stay available for [`Handler::connect`]. Does nothing if no scan is
+    /// running.
+    /// # Errors
+    /// Returns an error if stopping the scan fails
+    pub async fn stop_scan(&self) -> Result<(), Error> {
+        let _scan = self.scan_lock.lock().await;
+        self.end_scan().await
+    }
+
+    /// Stops the scan like [`Handler::stop_scan`] if it is the one started as `generation`
+    /// A scan started later replaced it and is left running.
+    pub(crate) async fn stop_scan_generation(&self, generation: u64) -> Result<(), Error> {
+        let _scan = self.scan_lock.lock().await;
+        if self.state.lock().await.scan_generation != generation {
+            debug!("scan {generation} was already replaced, not stopping it");
+            return Ok(());
+        }
+        self.end_scan().await
+    }
+
+    /// Stops a scan before connecting
+    /// Scans with a timeout are stopped like with [`Handler::stop_scan`]. A scan of
+    /// [`Handler::start_discovery`] only stops its adapters and resumes once the returned guard
+    /// of the last concurrent connect is dropped.
+    pub(crate) async fn pause_scan(&self) -> Option<ScanPause<'_>> {
+        let continuous = {
+            let state = self.state.lock().await;
+            state.scan_continuous && state.scan_task.as_ref().is_some_and(|t| !t.is_finished())
+        };
+        if !continuous {
+            let _ = self.stop_scan().await;
+            return None;
+        }
+        // resuming would use up the scan starts the OS leaves the app
+        if !self.can_restart_scan() {
+            debug!("keeping the continuous scan running while connecting, scan starts are low");
+            return None;
+        }
+        debug!("pausing the continuous scan while connecting");
+        // the scan loop checks the pauses under the same lock before it resumes the adapters
+        let _pause_lock = self.scan_pause_lock.lock().await;
+        self.scan_pauses.fetch_add(1, Ordering::SeqCst);
+        let pause = ScanPause(self);
+        for scanned in self.scanning_adapters() {
+            if let Err(e) = scanned.adapter.stop_scan().await {
+                warn!("failed to pause scan on {:?}: {e}", scanned.id);
+            }
+        }
+        Some(pause)
+    }
+
+    /// Restarts the adapters of a running scan if the services of the scan consumers changed,
+    /// `covered` are the services the adapters currently scan for in addition to `filter`
+    /// The restart is postponed to a later tick while it would use up the scan starts of the
+    /// app.
+    async fn cover_scan_consumers(
+        &self,
+        adapters: &[ScanAdapter],
+        filter: &ScanFilter,
+        covered: &mut Option<Vec<Uuid>>,
+    ) {
+        let wanted = self.scan_consumer_services();
+        if wanted == *covered {
+            return;
+        }
+        let previous = covering_filter(filter, covered.as_deref());
+        let scan_filter = covering_filter(filter, wanted.as_deref());
+        if scan_filter.services == previous.services {
+            *covered = wanted;
+            return;
+        }
+        if let Err(e) = self.record_scan_start(ScanStart::Internal) {
+            debug!("postponing the scan restart for the scan consumers: {e}");
+            return;
+        }
+        *covered = wanted;
+        debug!("scan consumers changed, scanning for {:?}", scan_filter.services);
+        for scanned in adapters {
+            if let Err(e) = scanned.adapter.stop_scan().await {
+                warn!("failed to stop scan on {:?}: {e}", scanned.id);
+            }
+            if let Err(e) = scanned.adapter.start_scan(scan_filter.clone()).await {
+                warn!("failed to restart scan on {:?}: {e}", scanned.id);
+            }
+        }
+    }
+
+    /// Returns true if the running scan was started as `generation`
+    pub(crate) async fn is_scan_generation(&self, generation: u64) -> bool {
+        let state = self.state.lock().await;
+        state.scan_generation == generation
+            && state.scan_task.as_ref().is_some_and(|t| !t.is_finished())
+    }
+
+    /// Returns true if the running scan was started with [`Handler::start_discovery`]
+    pub(crate) async fn is_continuous_scan(&self) -> bool {
+        let state = self.state.lock().await;
+        state.scan_continuous && state.scan_task.as_ref().is_some_and(|t| !t.is_finished())
+    }
+
+    /// Ends the running scan and waits for its loop to finish
+    /// The loop is aborted if it does not finish within [`SCAN_STOP_TIMEOUT`], e.g. because the
+    /// receiver of its channel stopped reading.
+    async fn end_scan(&self) -> Result<(), Error> {
+        let (task, stop) = {
+            let mut state = self.state.lock().await;
+            (state.scan_task.take(), state.scan_stop.take())
+        };
+        let Some(mut task) = task.filter(|t| !t.is_finished()) else {
+            return Ok(());
+        };
+        if let Some(stop) = stop {
+            stop.notify_one();
+        }
+        if timeout(SCAN_STOP_TIMEOUT, &mut task).await.is_ok() {
+            return Ok(());
+        }
+        warn!("scan did not stop within {SCAN_STOP_TIMEOUT:?}, aborting it");
+        task.abort();
+        for scanned in self.scanning_adapters() {
+            scanned.adapter.stop_scan().await?;
+        }
+        self.send_scan_update(false).await;
+        Ok(())
+    }
+
+    /// Sets how repeated advertisements are reported by scans started afterwards
+    /// See [`Handler::scan_capabilities`] for what the platform does on its own
+    pub async fn set_duplicate_filter(&self, filter: DuplicateFilter) {
+        self.state.lock().await.duplicate_filter = filter;
+    }
+
+    /// Sets how long a device must be missing before scans started afterwards report it as lost
+    /// A device showing up again during this time is kept without any event, which avoids
+    /// flickering for devices at the edge of the range. Defaults to 3 seconds, a single scan
+    /// can override it with [`crate::DiscoverOptions::removal_debounce`].
+    /// A device is missing while no advertisement of it arrives. Where the OS merges
+    /// advertisements, e.g. CoreBluetooth in the background, a longer debounce avoids
+    /// reporting devices that are still around.
+    pub async fn set_removal_debounce(&self, debounce: Duration) {
+        self.state.lock().await.removal_debounce = debounce;
+    }
+
+    /// Scans on every adapter of the system instead of only the default one, applies to scans
+    /// started afterwards
+    /// Devices seen by several adapters are merged into one [`BleDevice`].
+    /// [`BleDevice::adapters`] lists the adapters that saw the device with their RSSI, the
+    /// connect uses [`BleDevice::adapter_id`], the adapter with the strongest RSSI, unless
+    /// [`ConnectOptions::adapter`] selects another one.
+    /// Returns the ids of the scanned adapters, empty if disabled.
+    /// # Errors
+    /// Returns an error if the adapters can not be listed
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     let adapters = handler.set_multi_adapter_scan(true).await.unwrap();
+    ///     println!("scanning on {adapters:?}");
+    /// });
+    /// # }
+    /// ```
+    pub async fn set_multi_adapter_scan(&self, enabled: bool) -> Result<Vec<String>, Error> {
+        let mut adapters = vec![];
+        if enabled {
+            let manager = Manager::new().await?;
+            let selected = self.adapter_index.load(Ordering::Acquire);
+            for (i, adapter) in manager.adapters().await?.into_iter().enumerate() {
+                // the selected adapter keeps its instance, its event stream is already in use
+                let adapter = if i == selected {
+                    self.adapter()
+                } else {
+                    Arc::new(adapter)
+                };
+                let id = adapter_id(&adapter, i).await;
+                adapters.push(ScanAdapter {
+                    id: Some(id),
+                    adapter,
+                });
+            }
+        }
+        let ids = adapters.iter().filter_map(|a| a.id.clone()).collect();
+        *self
+            .multi_adapters
+            .lock()
+            .expect("multi adapters lock poisoned") = adapters;
+        // the event stream has to cover the connections of every adapter
+        self.event_stream_restart.notify_one();
+        Ok(ids)
+    }
+
+    /// Adapters used by scans and connects
+    fn scanning_adapters(&self) -> Vec<ScanAdapter> {
+        let adapters = self
+            .multi_adapters
+            .lock()
+            .expect("multi adapters lock poisoned")
+            .clone();
+        if adapters.is_empty() {
+            vec![ScanAdapter {
+                id: None,
+                adapter: self.adapter(),
+            }]
+        } else {
+            adapters
+        }
+    }
+
+    /// Adapter picked with [`Handler::select_adapter`]
+    fn adapter(&self) -> Arc<Adapter> {
+        self.adapter.lock().expect("adapter lock poisoned").clone()
+    }
+
+    /// Returns the adapter used for scans and connects outside of multi adapter scans
+    pub async fn selected_adapter(&self) -> AdapterInfo {
+        let index = self.adapter_index.load(Ordering::Acquire);
+        AdapterInfo {
+            index,
+            id: adapter_id(&self.adapter(), index).await,
+        }
+    }
+
+    /// Switches to another adapter of the system, see [`list_adapters`]
+    /// A running scan is stopped and the devices found so far are dropped, their handles belong
+    /// to the previous adapter. The event stream follows the new adapter.
+    /// # Errors
+    /// Returns [`Error::AdapterInUse`] if a device is connected and [`Error::AdapterNotFound`]
+    /// if no adapter matches the selector
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// use tauri_plugin_blec::models::AdapterSelector;
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     let adapter = handler
+    ///         .select_adapter(&AdapterSelector::Name("hci1".to_string()))
+    ///         .await
+    ///         .unwrap();
+    ///     println!("using {}", adapter.id);
+    /// });
+    /// # }
+    /// ```
+    pub async fn select_adapter(&self, selector: &AdapterSelector) -> Result<AdapterInfo, Error> {
+        // held until the switch is done, so no connect starts on the previous adapter
+        let _guard = self.adapter_switch.lock().await;
+        if self.is_connected() || !self.lock_connections().is_empty() {
+            return Err(Error::AdapterInUse);
+        }
+        let (index, central) = get_central(selector).await?;
+        if self.is_scanning().await {
+            self.stop_scan().await?;
+        }
+        *self.adapter.lock().expect("adapter lock poisoned") = Arc::new(central);
+        self.adapter_index.store(index, Ordering::Release);
+        self.devices.lock().await.clear();
+        // the state of the new adapter arrives on its event stream
+        self.set_adapter_state(AdapterState::Unknown);
+        self.event_stream_restart.notify_one();
+        let info = self.selected_adapter().await;
+        info!("switched to adapter {} ({})", info.index, info.id);
+        Ok(info)
+    }
+
+    /// Caches the handle of the given adapter for the address, so the connect uses that adapter
+    async fn use_scanned_adapter(&self, address: &str, adapter_id: &str) -> Result<(), Error> {
+        let scanned = self
+            .scanning_adapters()
+            .into_iter()
+            .find(|a| a.id.as_deref() == Some(adapter_id))
+            .ok_or_else(|| Error::UnknownAdapter(adapter_id.to_string()))?;
+        let peripheral = scanned
+            .adapter
+            .peripherals()
+            .await?
+            .into_iter()
+            .find(|p| models::peripheral_address(p) == address)
+            .ok_or_else(|| Error::UnknownPeripheral(address.to_string()))?;
+        debug!("connecting to {address} with adapter {adapter_id}");
+        self.devices.lock().await.insert(
+            Some(adapter_id.to_string()),
+            address.to_string(),
+            peripheral,
+            None,
+        );
+        Ok(())
+    }
+
+    /// Describes how the platform handles duplicate advertisements
+    pub fn scan_capabilities(&self) -> ScanCapabilities {
+        SCAN_CAPABILITIES
+    }
+
+    #[cfg_attr(target_os = "android", allow(clippy::unused_self))]
+    fn advertisement_count(&self, peripheral: &Peripheral) -> u64 {
+        #[cfg(target_os = "android")]
+        {
+            peripheral.advertisement_count()
+        }
+        #[cfg(not(target_os = "android"))]
+        {
+            self.advertisements
+                .lock()
+                .expect("advertisements lock poisoned")
+                .get(&peripheral.id())
+                .map(|s| s.count)
+                .unwrap_or_default()
+        }
+    }
+
+    #[cfg_attr(target_os = "android", allow(clippy::unused_self))]
+    fn adv_interval_ms(&self, peripheral: &Peripheral) -> Option<u32> {
+        #[cfg(target_os = "android")]
+        {
+            peripheral.adv_interval_ms()
+        }
+        #[cfg(not(target_os = "android"))]
+        {
+            self.advertisements
+                .lock()
+                .expect("advertisements lock poisoned")
+                .get(&peripheral.id())
+                .and_then(|s| s.interval.median_ms())
+        }
+    }
+
+    /// Sets a hook that is called for every device found during a scan
+    /// The callback receives the advertisement data of the device. The returned value is added
+    /// to the `extra` field of the [`BleDevice`] sent to the scan channel and the frontend.
+    /// A panicking enricher is logged and the device is reported without extra data.
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     handler.set_device_enricher(|props| {
+    ///         let data = props.manufacturer_data.get(&0xf00d)?;
+    ///         Some(serde_json::json!({ "firmware": data.first() }))
+    ///     }).await;
+    /// });
+    /// # }
+    /// ```
+    pub async fn set_device_enricher(
+        &self,
+        enricher: impl Fn(&PeripheralProperties) -> Option<serde_json::Value> + Send + Sync + 'static,
+    ) {
+        *self.enricher.lock().await = Some(Arc::new(enricher));
+    }
+
+    /// Removes the hook set with [`Handler::set_device_enricher`]
+    pub async fn clear_device_enricher(&self) {
+        *self.enricher.lock().await = None;
+    }
+
+    /// Sets a hook that computes a stable key for devices rotating their address
+    /// Devices with the same key are merged into one entry in the scan results, which uses the
+    /// most recently seen address. Every address change is published as
+    /// [`BleEvent::DeviceReaddressed`] and emitted to the frontend as
+    /// `blec://device-readdressed`. Returning `None` excludes the device from merging.
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     handler.set_device_fingerprint(|props| {
+    ///         let data = props.manufacturer_data.get(&0x02e5)?;
+    ///         Some(format!("{:?}", data.get(2..8)?))
+    ///     }).await;
+    /// });
+    /// # }
+    /// ```
+    pub async fn set_device_fingerprint(
+        &self,
+        fingerprint: impl Fn(&PeripheralProperties) -> Option<String> + Send + Sync + 'static,
+    ) {
+        *self.fingerprint.lock().await = Some(Arc::new(fingerprint));
+        *self.fingerprints.lock().await = FingerprintCache::default();
+    }
+
+    /// Sets a declarative fingerprint, see [`Handler::set_device_fingerprint`]
+    pub async fn set_fingerprint_rule(&self, rule: FingerprintRule) {
+        self.set_device_fingerprint(move |props| rule.key(props))
+            .await;
+    }
+
+    /// Removes the hook set with [`Handler::set_device_fingerprint`]
+    pub async fn clear_device_fingerprint(&self) {
+        *self.fingerprint.lock().await = None;
+        *self.fingerprints.lock().await = FingerprintCache::default();
+    }
+
+    /// Replaces devices by newer addresses with the same fingerprint
+    async fn merge_fingerprints(&self, devices: &mut Vec<BleDevice>) {
+        let readdressed = {
+            let mut cache = self.fingerprints.lock().await;
+            let seen: Vec<(&str, Option<&str>)> = devices
+                .iter()
+                .map(|d| (d.address.as_str(), d.fingerprint.as_deref()))
+                .collect();
+            let readdressed = cache.merge(&seen);
+            devices.retain(|d| !cache.is_retired(&d.address));
+            readdressed
+        };
+        if readdressed.is_empty() {
+            return;
+        }
+        for event in readdressed {
+            self.publish(BleEvent::DeviceReaddressed(event));
+        }
+    }
+
+    async fn add_devices(
+        self_devices: &mut Arc<Mutex<DeviceCache>>,
+        discovered: Vec<(Peripheral, Option<PeripheralProperties>)>,
+        adapters: &HashMap<String, Option<String>>,
+        name_filter: &ScanNameFilter,
+        enricher: Option<&DeviceEnricher>,
+        fingerprint: Option<&DeviceFingerprint>,
+        publisher: &Publisher,
+    ) -> Vec<BleDevice> {
+        let mut devices = vec![];
+        for (p, properties) in discovered {
+            let properties = properties.unwrap_or_default();
+            if !name_filter.matches(properties.local_name.as_deref()) {
+                continue;
+            }
+            match BleDevice::from_properties(&p, properties.clone()).await {
+                Ok(mut dev) => {
+                    if let Some(enricher) = enricher {
+                        dev.extra =
+                            enrich_device(enricher, &dev.address, &properties, publisher);
+                    }
+                    if let Some(fingerprint) = fingerprint {
+                        dev.fingerprint =
+                            fingerprint_device(fingerprint, &dev.address, &properties, publisher);
+                    }
+                    let adapter = adapters.get(&dev.address).cloned().flatten();
+                    self_devices.lock().await.insert(
+                        adapter,
+                        dev.address.clone(),
+                        p,
+                        dev.fingerprint.clone(),
+                    );
+                    devices.push(dev);
+                }
+                Err(e) => {
+                    warn!("Failed to add device: {e}");
+                }
+            }
+        }
+        devices.sort_by(|a, b| a.address.cmp(&b.address));
+        devices
+    }
+
+    /// Sends data to the given characteristic of the connected device
+    /// # Errors
+    /// Returns an error if no device is connected or the characteristic is not available
+    /// or if the write operation fails.
+    /// Returns [`Error::WriteNotSupported`] with the supported properties if the characteristic
+    /// does not allow the write type.
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// use uuid::{Uuid,uuid};
+    /// use tauri_plugin_blec::models::WriteType;
+    ///
+    /// const CHARACTERISTIC_UUID: Uuid = uuid!("51FF12BB-3ED8-46E5-B4F9-D64E2FEC021B");
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     let data = [1,2,3,4,5];
+    ///     let response = handler.send_data(CHARACTERISTIC_UUID,&data, WriteType::WithResponse).await.unwrap();
+    /// });
+    /// # }
+    /// ```
+    pub async fn send_data(
+        &self,
+        c: impl Into<CharacteristicRef>,
+        data: &[u8],
+        write_type: models::WriteType,
+    ) -> Result<(), Error> {
+        let c = c.into();
+        let characteristic = c.characteristic;
+        match self.write_with_status(c, data, write_type).await? {
+            0 => Ok(()),
+            status => Err(Error::WriteStatus {
+                charac: characteristic.to_string(),
+                status,
+            }),
+        }
+    }
+
+    /// Writes with response and returns the status the device answered with
+    /// Useful for protocols that answer writes with an application error code instead of
+    /// failing them.
+    /// # Errors
+    /// Returns an error like [`Handler::send_data`], application errors of the device are
+    /// returned as [`WriteResponse::status`] instead where the platform reports them
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// use uuid::{Uuid,uuid};
+    ///
+    /// const CHARACTERISTIC_UUID: Uuid = uuid!("51FF12BB-3ED8-46E5-B4F9-D64E2FEC021B");
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     let response = handler
+    ///         .send_data_with_response(CHARACTERISTIC_UUID, &[1])
+    ///         .await
+    ///         .unwrap();
+    ///     if !response.is_success() {
+    ///         println!("device answered with status {:#04x}", response.status);
+    ///     }
+    /// });
+    /// # }
+    /// ```
+    pub async fn send_data_with_response(
+        &self,
+        c: impl Into<CharacteristicRef>,
+        data: &[u8],
+    ) -> Result<WriteResponse, Error> {
+        let status = self
+            .write_with_status(c.into(), data, models::WriteType::WithResponse)
+            .await?;
+        Ok(WriteResponse { status })
+    }
+
+    async fn write_with_status(
+        &self,
+        c: CharacteristicRef,
+        data: &[u8],
+        write_type: models::WriteType,
+    ) -> Result<u8, Error> {
+        let connection = self.connection_for(c.address.as_deref())?;
+        self.reconnect_if_idle(&connection).await?;
+        let queued = Instant::now();
+        let dev = connection.dev.lock().await;
+        let queue_wait = queued.elapsed();
+        self.check_ready(&connection)?;
+        let dev = dev.as_ref().ok_or(Error::NoDeviceConnected)?;
+        let charac = resolve_charac(dev, &c)?;
+        let write_type: btleplug::api::WriteType = write_type.into();
+        check_write(&charac, write_type)?;
+        let start = Instant::now();
+        let res = self
+            .with_retry(|| write_with_status(dev, &charac, data, write_type))
+            .await;
+        let latency = start.elapsed();
+        self.record_metrics(|m| m.writes.record(queue_wait, latency));
+        self.timelines().record_operation(
+            &models::peripheral_address(dev),
+            TimelineOperation::Write,
+            charac.uuid,
+            queue_wait,
+            latency,
+            Some(data),
+            res.as_ref().err().map(ToString::to_string),
+        );
+        let status = res.map_err(|e| self.auth_error(&models::peripheral_address(dev), e))?;
+        connection.mark_activity();
+        if status == 0 {
+            self.record_required_writes(&connection, charac.uuid, data).await;
+        }
+        Ok(status)
+    }
+
+    /// Marks the characteristics whose [`RequiredWrite`] matches a successful write
+    async fn record_required_writes(&self, connection: &Connection, written: Uuid, data: &[u8]) {
+        let met = required_writes_met(&*self.charac_options.lock().await, written, data);
+        if met.is_empty() {
+            return;
+        }
+        connection
+            .required_writes_done
+            .lock()
+            .expect("required writes lock poisoned")
+            .extend(met);
+    }
+
+    /// Mode set with [`CharacteristicOptions::subscribe_mode`]
+    pub(crate) async fn subscribe_mode(&self, uuid: Uuid) -> SubscribeMode {
+        self.charac_options
+            .lock()
+            .await
+            .get(&uuid)
+            .map(|o| o.subscribe_mode)
+            .unwrap_or_default()
+    }
+
+    /// Enforces [`CharacteristicOptions::requires_write`] before subscribing
+    async fn check_required_write(
+        &self,
+        connection: &Connection,
+        dev: &Peripheral,
+        uuid: Uuid,
+    ) -> Result<(), Error> {
+        let required = self
+            .charac_options
+            .lock()
+            .await
+            .get(&uuid)
+            .and_then(|o| o.requires_write.clone());
+        let write = |required: RequiredWrite| async move {
+            let charac = resolve_charac(dev, &required.characteristic.into())?;
+            let write_type = btleplug::api::WriteType::WithResponse;
+            self.with_retry(|| dev.write(&charac, &required.data, write_type))
+                .await?;
+            connection.mark_activity();
+            Ok(())
+        };
+        enforce_required_write(uuid, required, &connection.required_writes_done, write).await
+    }
+
+    /// Sets the options used for all operations on the given characteristic
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// use uuid::{Uuid,uuid};
+    /// use tauri_plugin_blec::{CharacteristicOptions, ReadStrategy};
+    /// const CHARACTERISTIC_UUID: Uuid = uuid!("51FF12BB-3ED8-46E5-B4F9-D64E2FEC021B");
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     let options = CharacteristicOptions {
+    ///         read_strategy: ReadStrategy::RetryUntilStable(5),
+    ///         ..Default::default()
+    ///     };
+    ///     handler.set_characteristic_options(CHARACTERISTIC_UUID, options).await;
+    /// });
+    /// # }
+    /// ```
+    pub async fn set_characteristic_options(&self, c: Uuid, options: CharacteristicOptions) {
+        self.charac_options.lock().await.insert(c, options);
+    }
+
+    /// Receives data from the given characteristic of the connected device
+    /// Returns the data as a vector of bytes
+    /// The read is done with the [`ReadStrategy`] set with [`Handler::set_characteristic_options`]
+    /// # Errors
+    /// Returns an error if no device is connected or the characteristic is not available
+    /// or if the read operation fails
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// use uuid::{Uuid,uuid};
+    /// const CHARACTERISTIC_UUID: Uuid = uuid!("51FF12BB-3ED8-46E5-B4F9-D64E2FEC021B");
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     let response = handler.recv_data(CHARACTERISTIC_UUID).await.unwrap();
+    /// });
+    /// # }
+    /// ```
+    pub async fn recv_data(&self, c: impl Into<CharacteristicRef>) -> Result<Vec<u8>, Error> {
+        let c = c.into();
+        let connection = self.connection_for(c.address.as_deref())?;
+        self.reconnect_if_idle(&connection).await?;
+        let queued = Instant::now();
+        let dev = connection.dev.lock().await;
+        let queue_wait = queued.elapsed();
+        self.check_ready(&connection)?;
+        let dev = dev.as_ref().ok_or(Error::NoDeviceConnected)?;
+        let charac = resolve_charac(dev, &c)?;
+        let strategy = self
+            .charac_options
+            .lock()
+            .await
+            .get(&charac.uuid)
+            .map(|o| o.read_strategy)
+            .unwrap_or_default();
+        let reads = match strategy {
+            ReadStrategy::Single => 1,
+            ReadStrategy::DoubleRead => 2,
+            ReadStrategy::RetryUntilStable(n) => n.max(1),
+        };
+        let mut data = self.timed_read(dev, &charac, queue_wait).await?;
+        let mut count = 1;
+        while count < reads {
+            let next = self.timed_read(dev, &charac, Duration::ZERO).await?;
+            count += 1;
+            let stable = next == data;
+            data = next;
+            if stable && matches!(strategy, ReadStrategy::RetryUntilStable(_)) {
+                break;
+            }
+        }
+        debug!("read {} with {strategy:?} took {count} reads", charac.uuid);
+        connection.mark_activity();
+        Ok(data)
+    }
+
+    async fn timed_read(
+        &self,
+        dev: &Peripheral,
+        charac: &Characteristic,
+        queue_wait: Duration,
+    ) -> Result<Vec<u8>, Error> {
+        let start = Instant::now();
+        let res = self.with_retry(|| dev.read(charac)).await;
+        let latency = start.elapsed();
+        self.record_metrics(|m| m.reads.record(queue_wait, latency));
+        self.timelines().record_operation(
+            &models::peripheral_address(dev),
+            TimelineOperation::Read,
+            charac.uuid,
+            queue_wait,
+            latency,
+            res.as_ref().ok().map(Vec::as_slice),
+            res.as_ref().err().map(ToString::to_string),
+        );
+        res.map_err(|e| self.auth_error(&models::peripheral_address(dev), e))
+    }
+
+    /// Returns the UUIDs of the descriptors of the given characteristic
+    /// The descriptors are known once the services are discovered, so no request is sent to the
+    /// device.
+    /// # Errors
+    /// Returns an error if no device is connected or the characteristic is not available
+    pub async fn descriptors(&self, c: impl Into<CharacteristicRef>) -> Result<Vec<Uuid>, Error> {
+        let c = c.into();
+        let connection = self.connection_for(c.address.as_deref())?;
+        let dev = connection.dev.lock().await;
+        let dev = dev.as_ref().ok_or(Error::NoDeviceConnected)?;
+        let charac = resolve_charac(dev, &c)?;
+        Ok(charac.descriptors.iter().map(|d| d.uuid).collect())
+    }
+
+    /// Returns the properties of the given characteristic, e.g. whether it can be read or
+    /// notifies
+    /// The properties are known once the services are discovered, so no request is sent to the
+    /// device. [`Handler::subscribe`] enables notifications or indications depending on which
+    /// one the characteristic supports.
+    /// # Errors
+    /// Returns an error if no device is connected or the characteristic is not available
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// use uuid::{Uuid,uuid};
+    /// const CHARACTERISTIC_UUID: Uuid = uuid!("51FF12BB-3ED8-46E5-B4F9-D64E2FEC021B");
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     let properties = handler.charac_properties(CHARACTERISTIC_UUID).await.unwrap();
+    ///     if properties.notify || properties.indicate {
+    ///         handler.subscribe(CHARACTERISTIC_UUID, |data| println!("{data:?}")).await.unwrap();
+    ///     }
+    /// });
+    /// # }
+    /// ```
+    pub async fn charac_properties(
+        &self,
+        c: impl Into<CharacteristicRef>,
+    ) -> Result<CharacteristicProperties, Error> {
+        let c = c.into();
+        let connection = self.connection_for(c.address.as_deref())?;
+        let dev = connection.dev.lock().await;
+        let dev = dev.as_ref().ok_or(Error::NoDeviceConnected)?;
+        Ok(resolve_charac(dev, &c)?.properties.into())
+    }
+
+    /// Reads a descriptor of the given characteristic of the connected device
+    /// # Errors
+    /// Returns an error if no device is connected, the characteristic is not available or the
+    /// read fails. A characteristic without the descriptor fails with
+    /// [`Error::DescriptorNotAvailable`].
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// use uuid::{Uuid,uuid};
+    /// const CHARACTERISTIC_UUID: Uuid = uuid!("51FF12BB-3ED8-46E5-B4F9-D64E2FEC021B");
+    /// const USER_DESCRIPTION: Uuid = uuid!("00002901-0000-1000-8000-00805f9b34fb");
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     let description = handler
+    ///         .read_descriptor(CHARACTERISTIC_UUID, USER_DESCRIPTION)
+    ///         .await
+    ///         .unwrap();
+    ///     println!("{}", String::from_utf8_lossy(&description));
+    /// });
+    /// # }
+    /// ```
+    pub async fn read_descriptor(
+        &self,
+        c: impl Into<CharacteristicRef>,
+        descriptor: Uuid,
+    ) -> Result<Vec<u8>, Error> {
+        let c = c.into();
+        let connection = self.connection_for(c.address.as_deref())?;
+        self.reconnect_if_idle(&connection).await?;
+        let dev = self.lock_ready(&connection).await?;
+        let dev = dev.as_ref().ok_or(Error::NoDeviceConnected)?;
+        let descriptor = resolve_descriptor(&resolve_charac(dev, &c)?, descriptor)?;
+        let data = self
+            .with_retry(|| dev.read_descriptor(&descriptor))
+            .await
+            .map_err(|e| self.auth_error(&models::peripheral_address(dev), e))?;
+        connection.mark_activity();
+        Ok(data)
+    }
+
+    /// Writes a descriptor of the given characteristic of the connected device
+    /// Writing the client characteristic configuration descriptor changes the notifications of
+    /// the device without the handler knowing, prefer [`Handler::subscribe`] unless the device
+    /// needs it.
+    /// # Errors
+    /// Returns an error if no device is connected, the characteristic is not available or the
+    /// write fails. A characteristic without the descriptor fails with
+    /// [`Error::DescriptorNotAvailable`].
+    pub async fn write_descriptor(
+        &self,
+        c: impl Into<CharacteristicRef>,
+        descriptor: Uuid,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        let c = c.into();
+        let connection = self.connection_for(c.address.as_deref())?;
+        self.reconnect_if_idle(&connection).await?;
+        let dev = self.lock_ready(&connection).await?;
+        let dev = dev.as_ref().ok_or(Error::NoDeviceConnected)?;
+        let descriptor = resolve_descriptor(&resolve_charac(dev, &c)?, descriptor)?;
+        self.with_retry(|| dev.write_descriptor(&descriptor, data))
+            .await
+            .map_err(|e| self.auth_error(&models::peripheral_address(dev), e))?;
+        connection.mark_activity();
+        Ok(())
+    }
+
+    /// Reads the given characteristic from all connected devices
+    /// Reads on different devices run concurrently, the result and latency of every read is
+    /// returned by device address.
+    /// Includes the devices connected with [`Handler::connect_additional`]. The address of a
+    /// [`CharacteristicRef`] is ignored, its service is used on every device.
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// use uuid::{Uuid,uuid};
+    /// const CHARACTERISTIC_UUID: Uuid = uuid!("51FF12BB-3ED8-46E5-B4F9-D64E2FEC021B");
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     for (address, read) in handler.recv_from_all(CHARACTERISTIC_UUID).await {
+    ///         println!("{address}: {:?} after {}us", read.data, read.latency_us);
+    ///     }
+    /// });
+    /// # }
+    /// ```
+    pub async fn recv_from_all(
+        &self,
+        c: impl Into<CharacteristicRef>,
+    ) -> HashMap<String, TimedRead> {
+        let c: &CharacteristicRef = &c.into();
+        let addresses = self.connected_addresses();
+        let reads = addresses.into_iter().map(|address| async move {
+            let start = Instant::now();
+            let target = CharacteristicRef {
+                address: Some(address.clone()),
+                ..c.clone()
+            };
+            let result = self.recv_data(target).await;
+            let latency_us = u64::try_from(start.elapsed().as_micros()).unwrap_or(u64::MAX);
+            let read = match result {
+                Ok(data) => TimedRead {
+                    data: Some(data),
+                    error: None,
+                    latency_us,
+                },
+                Err(e) => TimedRead {
+                    data: None,
+                    error: Some(e.to_string()),
+                    latency_us,
+                },
+            };
+            (address, read)
+        });
+        join_all(reads).await.into_iter().collect()
+    }
+
+    /// Reads the Device Information service of the connected device
+    /// Characteristics the device does not provide are left empty.
+    /// # Errors
+    /// Returns an error if no device is connected or a read fails
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     let info = handler.device_info().await.unwrap();
+    ///     println!("model: {:?}, pnp id: {:?}", info.model_number, info.pnp_id);
+    /// });
+    /// # }
+    /// ```
+    pub async fn device_info(&self) -> Result<DeviceInfo, Error> {
+        Ok(DeviceInfo {
+            manufacturer_name: self.read_dis_string(0x2a29).await?,
+            model_number: self.read_dis_string(0x2a24).await?,
+            serial_number: self.read_dis_string(0x2a25).await?,
+            hardware_revision: self.read_dis_string(0x2a27).await?,
+            firmware_revision: self.read_dis_string(0x2a26).await?,
+            software_revision: self.read_dis_string(0x2a28).await?,
+            pnp_id: match self.read_pnp_id().await {
+                Ok(pnp_id) => Some(pnp_id),
+                Err(Error::CharacNotAvailable(_)) => None,
+                Err(e) => return Err(e),
+            },
+        })
+    }
+
+    /// Reads and parses the PnP ID characteristic (0x2A50) of the connected device
+    /// # Errors
+    /// Returns an error if no device is connected, the device has no PnP ID characteristic
+    /// or the value is malformed
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     let pnp_id = handler.read_pnp_id().await.unwrap();
+    ///     println!("vendor {:04x} product {:04x}", pnp_id.vendor_id, pnp_id.product_id);
+    /// });
+    /// # }
+    /// ```
+    pub async fn read_pnp_id(&self) -> Result<PnpId, Error> {
+        let data = self
+            .recv_data(CharacteristicRef {
+                address: None,
+                service: Some(DEVICE_INFORMATION_SERVICE),
+                characteristic: PNP_ID_CHARACTERISTIC,
+            })
+            .await?;
+        PnpId::parse(&data)
+    }
+
+    async fn read_dis_string(&self, characteristic: u16) -> Result<Option<String>, Error> {
+        let res = self
+            .recv_data(CharacteristicRef {
+                address: None,
+                service: Some(DEVICE_INFORMATION_SERVICE),
+                characteristic: btleplug::api::bleuuid::uuid_from_u16(characteristic),
+            })
+            .await;
+        match res {
+            Ok(data) => Ok(Some(
+                String::from_utf8_lossy(&data)
+                    .trim_end_matches('\0')
+                    .to_string(),
+            )),
+            Err(Error::CharacNotAvailable(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Subscribe to notifications from the given characteristic
+    /// The callback will be called whenever a notification is received, it runs on the
+    /// notification task in arrival order, see [`DeliveryMode::Inline`]. Characteristics that
+    /// only indicate are subscribed to indications, see [`CharacteristicOptions::subscribe_mode`].
+    /// Every call adds another listener, the returned handle removes just this one with
+    /// [`Handler::remove_listener`].
+    /// # Errors
+    /// Returns an error if no device is connected or the characteristic is not available
+    /// or if the subscribe operation fails.
+    /// Returns [`Error::NotifyNotSupported`] with the supported properties if the characteristic
+    /// has neither notify nor indicate and [`Error::SubscribeModeNotSupported`] if it does not
+    /// support the configured [`SubscribeMode`].
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// use uuid::{Uuid,uuid};
+    /// const CHARACTERISTIC_UUID: Uuid = uuid!("51FF12BB-3ED8-46E5-B4F9-D64E2FEC021B");
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     let response = handler.subscribe(CHARACTERISTIC_UUID,|data| println!("received {data:?}")).await.unwrap();
+    /// });
+    /// # }
+    /// ```
+    pub async fn subscribe(
+        &self,
+        c: impl Into<CharacteristicRef>,
+        callback: impl Fn(&[u8]) + Send + Sync + 'static,
+    ) -> Result<ListenerHandle, Error> {
+        self.subscribe_with(c, DeliveryMode::default(), callback)
+            .await
+    }
+
+    /// Subscribe like [`Handler::subscribe`] with the given delivery mode for the callback
+    /// # Errors
+    /// Returns the same errors as [`Handler::subscribe`]
+    pub async fn subscribe_with(
+        &self,
+        c: impl Into<CharacteristicRef>,
+        mode: DeliveryMode,
+        callback: impl Fn(&[u8]) + Send + Sync + 'static,
+    ) -> Result<ListenerHandle, Error> {
+        let callback: ListenerCallback = Arc::new(callback);
+        let sink = match mode {
+            DeliveryMode::Inline => ListenerSink::Inline(callback),
+            DeliveryMode::BlockingPool => ListenerSink::BlockingPool(callback),
+        };
+        self.add_listener(c.into(), sink).await
+    }
+
+    /// Subscribe like [`Handler::subscribe`], delivering notifications to the returned receiver
+    /// instead of a callback
+    /// Notifications are queued in arrival order. If the receiver falls `capacity` notifications
+    /// behind, further notifications are dropped until it catches up, the notification task is
+    /// never blocked.
+    /// # Errors
+    /// Returns the same errors as [`Handler::subscribe`]
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// use uuid::{Uuid,uuid};
+    /// const CHARACTERISTIC_UUID: Uuid = uuid!("51FF12BB-3ED8-46E5-B4F9-D64E2FEC021B");
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     let (_handle, mut rx) = handler.subscribe_receiver(CHARACTERISTIC_UUID, 16).await.unwrap();
+    ///     while let Some(data) = rx.recv().await {
+    ///         println!("received {data:?}");
+    ///     }
+    /// });
+    /// # }
+    /// ```
+    pub async fn subscribe_receiver(
+        &self,
+        c: impl Into<CharacteristicRef>,
+        capacity: usize,
+    ) -> Result<(ListenerHandle, mpsc::Receiver<Vec<u8>>), Error> {
+        let (tx, rx) = mpsc::channel(capacity.max(1));
+        let id = self.add_listener(c.into(), ListenerSink::Channel(tx)).await?;
+        Ok((id, rx))
+    }
+
+    /// Subscribe like [`Handler::subscribe_receiver`], with the device and arrival time of every
+    /// notification
+    /// The time is taken from one monotonic clock for all devices as soon as the notification
+    /// task receives the value from the platform, before it is queued for any listener, so
+    /// notifications of several devices can be ordered and compared. Map it to the wall clock
+    /// with [`Handler::clock_sync`] and calibrate devices with [`Handler::set_time_offset`].
+    /// The platform hands notifications over at the connection events of the link, so the
+    /// timestamps lag the measurement by up to one connection interval (7.5ms to 50ms usually,
+    /// longer on power saving links). The delivery from the OS bluetooth stack and the
+    /// scheduling of the notification task add about a millisecond, more on android and under
+    /// load.
+    /// # Errors
+    /// Returns the same errors as [`Handler::subscribe`]
+    pub async fn subscribe_timestamped(
+        &self,
+        c: impl Into<CharacteristicRef>,
+        capacity: usize,
+    ) -> Result<(ListenerHandle, mpsc::Receiver<TimedNotification>), Error> {
+        let (tx, rx) = mpsc::channel(capacity.max(1));
+        let id = self.add_listener(c.into(), ListenerSink::Timed(tx)).await?;
+        Ok((id, rx))
+    }
+
+    async fn add_listener(
+        &self,
+        c: CharacteristicRef,
+        sink: ListenerSink,
+    ) -> Result<ListenerHandle, Error> {
+        let connection = self.connection_for(c.address.as_deref())?;
+        self.reconnect_if_idle(&connection).await?;
+        let queued = Instant::now();
+        let dev = connection.dev.lock().await;
+        let queue_wait = queued.elapsed();
+        self.check_ready(&connection)?;
+        let dev = dev.as_ref().ok_or(Error::NoDeviceConnected)?;
+        let charac = resolve_charac(dev, &c)?;
+        let target = subscribe_target(&charac, self.subscribe_mode(charac.uuid).await)?;
+        self.check_required_write(&connection, dev, charac.uuid).await?;
+        let start = Instant::now();
+        let res = dev.subscribe(&target).await;
+        let latency = start.elapsed();
+        self.record_metrics(|m| m.subscribes.record(queue_wait, latency));
+        self.timelines().record_operation(
+            &models::peripheral_address(dev),
+            TimelineOperation::Subscribe,
+            charac.uuid,
+            queue_wait,
+            latency,
+            None,
+            res.as_ref().err().map(ToString::to_string),
+        );
+        res.map_err(|e| self.auth_error(&models::peripheral_address(dev), e))?;
+        let id = ListenerHandle(self.next_listener_id.fetch_add(1, Ordering::Relaxed));
+        connection.listeners.lock().await.push(Listener {
+            id,
+            uuid: charac.uuid,
+            sink,
+        });
+        Ok(id)
+    }
+
+    /// Removes a single listener added with [`Handler::subscribe`]
+    /// The GATT subscription is reference counted: the characteristic is only unsubscribed once
+    /// the last listener or session using it is gone.
+    /// Unknown handles are ignored, the listener is already gone after an unsubscribe or
+    /// disconnect.
+    /// # Errors
+    /// Returns an error if the unsubscribe operation fails
+    pub async fn remove_listener(&self, id: ListenerHandle) -> Result<(), Error> {
+        for connection in self.all_connections() {
+            let dev = connection.dev.lock().await;
+            let mut listeners = connection.listeners.lock().await;
+            let sessions = connection.sessions.lock().await;
+            let uuid = match release_listener(&mut listeners, &sessions, id) {
+                Release::Unknown => {
+                    drop((sessions, listeners, dev));
+                    connection.kept_listeners.lock().await.retain(|l| l.id != id);
+                    continue;
+                }
+                Release::Held => return Ok(()),
+                Release::Unsubscribe(uuid) => uuid,
+            };
+            drop(sessions);
+            if let Some(dev) = dev.as_ref() {
+                if let Some(charac) = dev.characteristics().iter().find(|x| x.uuid == uuid) {
+                    dev.unsubscribe(charac).await?;
+                }
+            }
+            return Ok(());
+        }
+        Ok(())
+    }
+
+    /// Unsubscribe from notifications for the given characteristic
+    /// This removes all listeners of the characteristic, listeners of other characteristics keep
+    /// receiving notifications. The GATT subscription is kept while a session still uses the
+    /// characteristic.
+    /// # Errors
+    /// Returns [`Error::NoListener`] if the characteristic has no listener, an error if no device
+    /// is connected or the characteristic is not available or if the unsubscribe operation fails
+    pub async fn unsubscribe(&self, c: impl Into<CharacteristicRef>) -> Result<(), Error> {
+        let c = c.into();
+        let connection = self.connection_for(c.address.as_deref())?;
+        let dev = self.lock_ready(&connection).await?;
+        let dev = dev.as_ref().ok_or(Error::NoDeviceConnected)?;
+        let charac = resolve_charac(dev, &c)?;
+        let mut listeners = connection.listeners.lock().await;
+        let release = release_all(&listeners, &*connection.sessions.lock().await, charac.uuid);
+        match release {
+            Release::Unknown => return Err(Error::NoListener(charac.uuid.to_string())),
+            Release::Held => {}
+            Release::Unsubscribe(_) => dev.unsubscribe(&charac).await?,
+        }
+        // only once the GATT unsubscribe succeeded, the listeners stay usable otherwise
+        listeners.retain(|l| l.uuid != charac.uuid);
+        Ok(())
+    }
+
+    /// Opens a streaming session over the given characteristics
+    /// All characteristics are subscribed and their notifications are merged into a single
+    /// stream. Frames are timestamped on arrival and delivered in exactly the order they were
+    /// received, regardless of which characteristic they belong to. A session covers a single
+    /// device, the one of the first [`CharacteristicRef`], refs with the address of another
+    /// device are rejected.
+    /// # Errors
+    /// Returns an error if no device is connected, one of the characteristics is not available
+    /// or ambiguous, or if a subscribe operation fails
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// use uuid::{Uuid,uuid};
+    /// const CHARAC_A: Uuid = uuid!("51FF12BB-3ED8-46E5-B4F9-D64E2FEC021B");
+    /// const CHARAC_B: Uuid = uuid!("51FF12BB-3ED8-46E5-B4F9-D64E2FEC021C");
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     let mut session = handler.open_session(vec![CHARAC_A, CHARAC_B]).await.unwrap();
+    ///     while let Some(frame) = session.recv().await {
+    ///         println!("{} @ {}us: {:?}", frame.characteristic, frame.timestamp_us, frame.data);
+    ///     }
+    /// });
+    /// # }
+    /// ```
+    pub async fn open_session(
+        &self,
+        characs: impl IntoIterator<Item = impl Into<CharacteristicRef>>,
+    ) -> Result<SessionHandle, Error> {
+        let characs: Vec<CharacteristicRef> = characs.into_iter().map(Into::into).collect();
+        let address = characs.first().and_then(|c| c.address.as_deref());
+        let connection = self.connection_for(address)?;
+        self.reconnect_if_idle(&connection).await?;
+        let dev = self.lock_ready(&connection).await?;
+        let dev = dev.as_ref().ok_or(Error::NoDeviceConnected)?;
+        prune_sessions(dev, &connection.listeners, &connection.sessions).await;
+        let mut to_subscribe = vec![];
+        for c in &characs {
+            let charac = resolve_charac(dev, c)?;
+            to_subscribe.push(subscribe_target(&charac, self.subscribe_mode(charac.uuid).await)?);
+        }
+        let characs = to_subscribe.iter().map(|c| c.uuid).collect();
+        for charac in to_subscribe {
+            self.check_required_write(&connection, dev, charac.uuid).await?;
+            dev.subscribe(&charac).await?;
+        }
+        connection.mark_activity();
+        let (tx, rx) = mpsc::unbounded_channel();
+        let id = self.next_session_id.fetch_add(1, Ordering::Relaxed);
+        connection.sessions.lock().await.push(Session {
+            id,
+            characs,
+            opened: Instant::now(),
+            tx,
+        });
+        Ok(SessionHandle { id, rx })
+    }
+
+    /// Closes the session with the given id
+    /// Only characteristics that are not used by another session or listener are unsubscribed.
+    /// # Errors
+    /// Returns an error if the session does not exist or if an unsubscribe operation fails
+    pub async fn close_session(&self, id: u64) -> Result<(), Error> {
+        for connection in self.all_connections() {
+            let dev = connection.dev.lock().await;
+            let mut sessions = connection.sessions.lock().await;
+            let Some(pos) = sessions.iter().position(|s| s.id == id) else {
+                continue;
+            };
+            let session = sessions.remove(pos);
+            let Some(dev) = dev.as_ref() else {
+                // not connected anymore, nothing to unsubscribe
+                return Ok(());
+            };
+            let listeners = connection.listeners.lock().await;
+            for c in &session.characs {
+                if subscription_held(&listeners, &sessions, *c) {
+                    continue;
+                }
+                if let Some(charac) = dev.characteristics().iter().find(|x| x.uuid == *c) {
+                    dev.unsubscribe(charac).await?;
+                }
+            }
+            return Ok(());
+        }
+        Err(Error::UnknownSession(id))
+    }
+
+    /// Writes `data` to `write_c` and collects the notification frames of `notify_c` that form
+    /// the response
+    /// Collection stops when `is_last` returns true for a frame, when `max_frames` frames were
+    /// received or when the timeout is reached. The frames received until then are returned in
+    /// order, [`MultiResponse::complete`] tells whether the response was finished before the timeout.
+    /// Unless `tee` is set, the response frames are not passed to other listeners or sessions.
+    /// `notify_c` is subscribed for the request if no listener or session uses it and
+    /// unsubscribed again afterwards.
+    /// # Errors
+    /// Returns [`Error::Disconnected`] if the device disconnected before the response was
+    /// complete, [`Error::AdapterPoweredOff`] if the adapter was powered off meanwhile, or an
+    /// error if no device is connected, one of the characteristics is not
+    /// available or if the subscribe or write operation fails
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// use uuid::{Uuid,uuid};
+    /// use tauri_plugin_blec::{models::WriteType, MultiResponseOptions};
+    /// const CHARACTERISTIC_UUID: Uuid = uuid!("51FF12BB-3ED8-46E5-B4F9-D64E2FEC021B");
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     let response = handler
+    ///         .request_multi(
+    ///             CHARACTERISTIC_UUID,
+    ///             &[0x01],
+    ///             WriteType::WithResponse,
+    ///             CHARACTERISTIC_UUID,
+    ///             |frame| frame.last() == Some(&0xff),
+    ///             MultiResponseOptions::default(),
+    ///         )
+    ///         .await
+    ///         .unwrap();
+    ///     println!("received {} frames", response.frames.len());
+    /// });
+    /// # }
+    /// ```
+    pub async fn request_multi(
+        &self,
+        write_c: impl Into<CharacteristicRef>,
+        data: &[u8],
+        write_type: models::WriteType,
+        notify_c: impl Into<CharacteristicRef>,
+        is_last: impl Fn(&[u8]) -> bool,
+        options: MultiResponseOptions,
+    ) -> Result<MultiResponse, Error> {
+        let (write_c, notify_c) = (write_c.into(), notify_c.into());
+        let connection = self.connection_for(write_c.address.as_deref())?;
+        self.reconnect_if_idle(&connection).await?;
+        let mut intercepted = None;
+        let budget = Budget::new(options.total_timeout);
+        let sent = budget
+            .run("write request", async {
+                let dev = self.lock_ready(&connection).await?;
+                let dev = dev.as_ref().ok_or(Error::NoDeviceConnected)?;
+                let notify_charac = resolve_charac(dev, &notify_c)?;
+                let write_charac = resolve_charac(dev, &write_c)?;
+                // registered before writing to not miss the first frame
+                let interception = self.intercept(&connection, dev, &notify_charac, options.tee);
+                intercepted = Some(interception.await?);
+                dev.write(&write_charac, data, write_type.into())
+                    .await
+                    .map_err(|e| self.auth_error(&models::peripheral_address(dev), e))?;
+                connection.mark_activity();
+                self.record_required_writes(&connection, write_charac.uuid, data)
+                    .await;
+                Ok(())
+            })
+            .await;
+        let Some(mut interception) = intercepted else {
+            return Err(sent.expect_err("interceptor is registered before the write"));
+        };
+        if let Err(e) = sent {
+            self.end_interception(interception).await;
+            return Err(e);
+        }
+        let timeout_deadline = tokio::time::Instant::now() + options.timeout;
+        let deadline = budget.deadline().map_or(timeout_deadline, |d| d.min(timeout_deadline));
+        let mut frames = vec![];
+        let mut complete = false;
+        let mut disconnected = false;
+        loop {
+            match tokio::time::timeout_at(deadline, interception.rx.recv()).await {
+                Ok(Some(frame)) => {
+                    let last = is_last(&frame);
+                    frames.push(frame);
+                    if last || options.max_frames.is_some_and(|max| frames.len() >= max) {
+                        complete = true;
+                        break;
+                    }
+                }
+                Ok(None) => {
+                    disconnected = true;
+                    break;
+                }
+                Err(_) => break,
+            }
+        }
+        self.end_interception(interception).await;
+        if disconnected {
+            return Err(self.disconnected_error());
+        }
+        let budget_exceeded = budget
+            .deadline()
+            .is_some_and(|d| d < timeout_deadline && d <= tokio::time::Instant::now());
+        if !complete && budget_exceeded {
+            return Err(Error::TotalTimeout {
+                step: "collect response".to_string(),
+            });
+        }
+        Ok(MultiResponse { frames, complete })
+    }
+
+    /// Waits for the first notification of `c` that `matcher` accepts and returns it
+    /// Only notifications arriving after the call are considered. They are also passed to the
+    /// other listeners and sessions as usual, matching or not. The characteristic is subscribed
+    /// for the wait if it is not yet, like with [`Handler::request_multi`].
+    /// # Errors
+    /// Returns [`Error::NotificationTimeout`] with the number of notifications that did not match
+    /// if none matched within `timeout_ms`, [`Error::Disconnected`] if the device disconnected
+    /// while waiting, [`Error::AdapterPoweredOff`] if the adapter was powered off meanwhile, or
+    /// an error if no device is connected, the characteristic is not
+    /// available or the subscribe fails
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// use uuid::{Uuid,uuid};
+    /// const CHARACTERISTIC_UUID: Uuid = uuid!("51FF12BB-3ED8-46E5-B4F9-D64E2FEC021B");
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     let is_ack = |frame: &[u8]| frame.first() == Some(&0x05);
+    ///     let frame = handler
+    ///         .wait_for_notification(CHARACTERISTIC_UUID, is_ack, 2000)
+    ///         .await
+    ///         .unwrap();
+    /// });
+    /// # }
+    /// ```
+    pub async fn wait_for_notification(
+        &self,
+        c: impl Into<CharacteristicRef>,
+        matcher: impl Fn(&[u8]) -> bool,
+        timeout_ms: u64,
+    ) -> Result<Vec<u8>, Error> {
+        let c = c.into();
+        let connection = self.connection_for(c.address.as_deref())?;
+        self.reconnect_if_idle(&connection).await?;
+        let deadline = tokio::time::Instant::now() + Duration::from_millis(timeout_ms);
+        let mut interception = {
+            let dev = self.lock_ready(&connection).await?;
+            let dev = dev.as_ref().ok_or(Error::NoDeviceConnected)?;
+            let charac = resolve_charac(dev, &c)?;
+            self.intercept(&connection, dev, &charac, true).await?
+        };
+        let mut non_matching = 0;
+        let matched = loop {
+            match tokio::time::timeout_at(deadline, interception.rx.recv()).await {
+                Ok(Some(frame)) if matcher(&frame) => break Ok(frame),
+                Ok(Some(_)) => non_matching += 1,
+                Ok(None) => break Err(self.disconnected_error()),
+                Err(_) => {
+                    break Err(Error::NotificationTimeout {
+                        charac: c.characteristic.to_string(),
+                        timeout_ms,
+                        non_matching,
+                    })
+                }
+            }
+        };
+        self.end_interception(interception).await;
+        matched
+    }
+
+    /// Subscribes the characteristic if needed and routes its notifications to the returned
+    /// interception until [`Handler::end_interception`]
+    async fn intercept(
+        &self,
+        connection: &Arc<Connection>,
+        dev: &Peripheral,
+        charac: &Characteristic,
+        tee: bool,
+    ) -> Result<Interception, Error> {
+        let subscribed = is_subscribed(connection, charac.uuid).await;
+        if !subscribed {
+            let target = subscribe_target(charac, self.subscribe_mode(charac.uuid).await)?;
+            self.check_required_write(connection, dev, charac.uuid).await?;
+            dev.subscribe(&target)
+                .await
+                .map_err(|e| self.auth_error(&models::peripheral_address(dev), e))?;
+        }
+        let (tx, rx) = mpsc::unbounded_channel();
+        connection.interceptors.lock().await.push(Interceptor {
+            uuid: charac.uuid,
+            tee,
+            tx,
+        });
+        Ok(Interception {
+            connection: connection.clone(),
+            uuid: charac.uuid,
+            subscribed,
+            rx,
+        })
+    }
+
+    /// Removes the interceptor and unsubscribes the characteristic if it was only subscribed
+    /// for it
+    async fn end_interception(&self, interception: Interception) {
+        let Interception {
+            connection,
+            uuid,
+            subscribed,
+            rx,
+        } = interception;
+        drop(rx);
+        let dev = connection.dev.lock().await;
+        connection.interceptors.lock().await.retain(|i| !i.tx.is_closed());
+        if subscribed || is_subscribed(&connection, uuid).await {
+            return;
+        }
+        let Some(dev) = dev.as_ref() else {
+            return;
+        };
+        if let Some(charac) = dev.characteristics().iter().find(|x| x.uuid == uuid) {
+            if let Err(e) = dev.unsubscribe(charac).await {
+                warn!("failed to unsubscribe {uuid} after intercepting its notifications: {e}");
+            }
+        }
+    }
+
+    async fn get_event_stream(
+        &self,
+    ) -> Result<Pin<Box<dyn Stream<Item = CentralEvent> + Send>>, Error> {
+        let adapters = self.scanning_adapters();
+        if let [scanned] = adapters.as_slice() {
+            return Ok(scanned.adapter.events().await?);
+        }
+        let mut streams = Vec::with_capacity(adapters.len());
+        for scanned in &adapters {
+            streams.push(scanned.adapter.events().await?);
+        }
+        Ok(Box::pin(futures::stream::select_all(streams)))
+    }
+
+    pub(crate) async fn handle_event(&self, event: CentralEvent) -> Result<(), Error> {
+        self.mark_event_loop_alive();
+        self.check_authorization();
+        match event {
+            CentralEvent::DeviceDisconnected(peripheral_id) => {
+                self.handle_disconnect(peripheral_id).await?;
+            }
+            CentralEvent::DeviceConnected(peripheral_id) => {
+                self.handle_connect(&peripheral_id);
+            }
+            CentralEvent::StateUpdate(state) => self.handle_adapter_state(state).await,
+            #[cfg(not(target_os = "android"))]
+            CentralEvent::DeviceDiscovered(peripheral_id)
+            | CentralEvent::DeviceUpdated(peripheral_id) => {
+                // BlueZ reports ServicesResolved toggling as an update of a connected device
+                #[cfg(target_os = "linux")]
+                self.interference.hint_services_changed(&peripheral_id);
+                let mut advertisements = self
+                    .advertisements
+                    .lock()
+                    .expect("advertisements lock poisoned");
+                let stats = advertisements.entry(peripheral_id).or_default();
+                stats.count += 1;
+                stats.interval.record(Instant::now());
+            }
+
+            _event => {}
+        }
+        Ok(())
+    }
+
+    /// Returns the state of the adapter
+    /// [`AdapterState::Unauthorized`] while the user or the device policy denies bluetooth for
+    /// the app, see [`Handler::authorization_status`]. Otherwise the state last reported by the
+    /// adapter, [`AdapterState::Unknown`] until the platform reported one: CoreBluetooth and
+    /// android report it on start, other platforms only report changes. Changes are published
+    /// as [`BleEvent::AdapterStateChanged`].
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri_plugin_blec::AdapterState;
+    /// let handler = tauri_plugin_blec::get_handler().unwrap();
+    /// if handler.adapter_state() == AdapterState::PoweredOff {
+    ///     println!("please switch bluetooth on");
+    /// }
+    /// # }
+    /// ```
+    pub fn adapter_state(&self) -> AdapterState {
+        if self.check_authorized().is_err() {
+            return AdapterState::Unauthorized;
+        }
+        *self.adapter_state.lock().expect("adapter state lock poisoned")
+    }
+
+    pub(crate) fn powered_off(&self) -> bool {
+        *self.adapter_state.lock().expect("adapter state lock poisoned") == AdapterState::PoweredOff
+    }
+
+    /// Error of an operation whose device disconnected while it was waiting,
+    /// [`Error::AdapterPoweredOff`] if the adapter was powered off
+    pub(crate) fn disconnected_error(&self) -> Error {
+        if self.powered_off() {
+            Error::AdapterPoweredOff
+        } else {
+            Error::Disconnected
+        }
+    }
+
+    /// Stores the state reported by the adapter and publishes [`BleEvent::AdapterStateChanged`]
+    /// if [`Handler::adapter_state`] changed, returns the previous reported state
+    fn set_adapter_state(&self, state: AdapterState) -> AdapterState {
+        let before = self.adapter_state();
+        let previous = std::mem::replace(
+            &mut *self.adapter_state.lock().expect("adapter state lock poisoned"),
+            state,
+        );
+        let after = self.adapter_state();
+        if before != after {
+            info!("adapter state: {before:?} -> {after:?}");
+            self.publish(BleEvent::AdapterStateChanged(after));
+        }
+        previous
+    }
+
+    /// Publishes [`BleEvent::AdapterStateChanged`] when the authorization turned the adapter
+    /// state into or out of [`AdapterState::Unauthorized`]
+    pub(crate) fn authorization_changed(&self, previous: AuthorizationStatus) {
+        let denied = |status| {
+            matches!(
+                status,
+                AuthorizationStatus::Denied | AuthorizationStatus::Restricted
+            )
+        };
+        if denied(previous) != denied(self.authorization_status()) {
+            self.publish(BleEvent::AdapterStateChanged(self.adapter_state()));
+        }
+    }
+
+    /// Tears down scans and connections once the adapter is powered off, so every caller gets
+    /// [`Error::AdapterPoweredOff`] instead of the errors of the vanished adapter
+    /// Nothing has to be set up again once it is powered on.
+    async fn handle_adapter_state(&self, state: CentralState) {
+        let state = match state {
+            CentralState::PoweredOn => AdapterState::PoweredOn,
+            CentralState::PoweredOff => AdapterState::PoweredOff,
+            _ => AdapterState::Unknown,
+        };
+        let previous = self.set_adapter_state(state);
+        if state != AdapterState::PoweredOff || previous == AdapterState::PoweredOff {
+            return;
+        }
+        warn!("adapter powered off, stopping scans and closing connections");
+        let (scan, _) = {
+            let mut state = self.state.lock().await;
+            (state.scan_task.take(), state.scan_stop.take())
+        };
+        if let Some(scan) = scan {
+            scan.abort();
+            // the aborted loop stops no adapter, the stacks resume scanning once powered on
+            for scanned in self.scanning_adapters() {
+                if let Err(e) = scanned.adapter.stop_scan().await {
+                    debug!("failed to stop scan on {:?} after the power off: {e}", scanned.id);
+                }
+            }
+            self.send_scan_update(false).await;
+        }
+        for connection in self.all_connections() {
+            let Some(id) = connection.link_id() else {
+                continue;
+            };
+            // queued operations fail with check_ready, the running one through auth_error
+            if let Err(e) = self.handle_disconnect(id).await {
+                error!(
+                    "failed to close the connection to {} after the adapter powered off: {e}",
+                    connection.address
+                );
+            }
+        }
+    }
+
+    /// Looks up an address without touching the adapter, e.g. to validate user input
+    /// Only the format of the address and in-memory caches are checked, so the result is
+    /// returned without any device I/O.
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     let known = handler.is_known_device("AA:BB:CC:DD:EE:FF").await;
+    ///     println!("valid: {}, cached: {}", known.valid_format, known.in_cache);
+    /// });
+    /// # }
+    /// ```
+    pub async fn is_known_device(&self, address: &str) -> KnownDevice {
+        let Some(address) = normalize_address(address) else {
+            return KnownDevice {
+                valid_format: false,
+                in_cache: false,
+                bonded: None,
+                last_seen: None,
+            };
+        };
+        let last_seen = self
+            .last_seen
+            .lock()
+            .expect("last seen lock poisoned")
+            .get(&address)
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| u64::try_from(d.as_millis()).unwrap_or(u64::MAX));
+        let bonded = self
+            .bond_states
+            .lock()
+            .expect("bond states lock poisoned")
+            .get(&address)
+            .copied();
+        KnownDevice {
+            valid_format: true,
+            in_cache: self.devices.lock().await.contains_key(&address),
+            bonded,
+            last_seen,
+        }
+    }
+
+    /// Time `address` was last reported by a scan
+    pub(crate) fn last_seen(&self, address: &str) -> Option<SystemTime> {
+        self.last_seen
+            .lock()
+            .expect("last seen lock poisoned")
+            .get(address)
+            .copied()
+    }
+
+    fn record_seen(&self, devices: &[BleDevice]) {
+        let now = SystemTime::now();
+        let mut last_seen = self.last_seen.lock().expect("last seen lock poisoned");
+        for d in devices {
+            last_seen.insert(d.address.clone(), now);
+        }
+    }
+
+    /// Sets the store used to keep state across app restarts, e.g. a [`crate::FileStore`]
+    /// The previously connected devices saved in the store are merged with the ones of this
+    /// session.
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use std::sync::Arc;
+    /// use tauri_plugin_blec::FileStore;
+    /// let handler = tauri_plugin_blec::get_handler().unwrap();
+    /// handler.set_store(Arc::new(FileStore::new("blec.json")));
+    /// # }
+    /// ```
+    pub fn set_store(&self, store: Arc<dyn BlecStore>) {
+        let saved: Vec<String> = store
+            .get(PREVIOUSLY_CONNECTED_KEY)
+            .and_then(|value| serde_json::from_str(&value).ok())
+            .unwrap_or_default();
+        self.previously_connected
+            .lock()
+            .expect("previously connected lock poisoned")
+            .extend(saved);
+        *self.store.lock().expect("store lock poisoned") = Some(store);
+        self.save_previously_connected();
+    }
+
+    /// Forgets which devices were connected, also in the store set with [`Handler::set_store`]
+    pub fn clear_previously_connected(&self) {
+        self.previously_connected
+            .lock()
+            .expect("previously connected lock poisoned")
+            .clear();
+        self.save_previously_connected();
+    }
+
+    /// Disconnects the device and drops everything known about it
+    /// The OS bond is removed where the platform allows it, see [`Handler::pair`]. The cached
+    /// handle, bond state, last sighting, fingerprint, recorded session timelines, clock sync,
+    /// time offset and the previously connected record of the address are dropped, also from
+    /// the store set with [`Handler::set_store`], and running waits for the device are
+    /// cancelled.
+    /// A failed disconnect or bond removal does not stop the other steps, it is listed in
+    /// [`ForgetReport::failures`]. The report is published as [`BleEvent::DeviceForgotten`].
+    /// # Errors
+    /// Returns [`Error::InvalidAddress`] if the address can not be parsed
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     let report = handler.forget_device("00:00:00:00:00:00").await.unwrap();
+    ///     for failure in report.failures {
+    ///         println!("{:?} failed: {}", failure.step, failure.error);
+    ///     }
+    /// });
+    /// # }
+    /// ```
+    pub async fn forget_device(&self, address: &str) -> Result<ForgetReport, Error> {
+        let address = self.parse_address(address)?;
+        info!("forgetting {address}");
+        let mut report = ForgetReport {
+            address: address.clone(),
+            disconnected: false,
+            bond_removed: false,
+            failures: vec![],
+        };
+        self.cancel_wait_for_device(&address);
+        if let Some(connection) = self.connection(&address) {
+            connection.cancel_auto_reconnect();
+            if connection.link().is_some() {
+                match self.disconnect_connection(&connection, false).await {
+                    Ok(()) => report.disconnected = true,
+                    Err(e) => report.fail(ForgetStep::Disconnect, &e),
+                }
+            } else {
+                // a pending idle reconnect ends with the device
+                connection
+                    .idle_reconnect
+                    .lock()
+                    .expect("idle reconnect lock poisoned")
+                    .take();
+                self.drop_connection(&connection);
+            }
+        }
+        match self.remove_bond(&address).await {
+            Ok(removed) => report.bond_removed = removed,
+            Err(e) => report.fail(ForgetStep::RemoveBond, &e),
+        }
+        for peripheral in self.devices.lock().await.remove(&address) {
+            #[cfg(target_os = "android")]
+            crate::android::forget_peripheral(&peripheral.id()).await;
+            #[cfg(not(target_os = "android"))]
+            self.advertisements
+                .lock()
+                .expect("advertisements lock poisoned")
+                .remove(&peripheral.id());
+        }
+        self.last_seen
+            .lock()
+            .expect("last seen lock poisoned")
+            .remove(&address);
+        self.bond_states
+            .lock()
+            .expect("bond states lock poisoned")
+            .remove(&address);
+        {
+            let mut fingerprints = self.fingerprints.lock().await;
+            fingerprints.current.retain(|_, current| *current != address);
+            fingerprints.retired.remove(&address);
+        }
+        self.timelines().forget(&address);
+        self.clocks.forget(&address);
+        self.drop_paused_transfers(&address);
+        self.forget_connected(&address);
+        self.publish(BleEvent::DeviceForgotten(report.clone()));
+        Ok(report)
+    }
+
+    /// Removes the OS bond of the device, returns false if it was not bonded
+    /// Where the bond can not be removed, only a device the plugin saw bonded is an error.
+    async fn remove_bond(&self, address: &str) -> Result<bool, Error> {
+        #[cfg(target_os = "android")]
+        {
+            if !self.is_known_peripheral(address).await && !self.lookup_peripheral(address).await {
+                return Ok(false);
+            }
+            Ok(self.bonding_peripheral(address).await?.remove_bond().await?)
+        }
+        #[cfg(target_os = "windows")]
+        {
+            crate::consent::windows_pairing::unpair(address).await
+        }
+        #[cfg(not(any(target_os = "android", target_os = "windows")))]
+        {
+            if self.bond_state(address) == Some(true) {
+                return Err(Error::UnsupportedOnPlatform("removing the bond"));
+            }
+            Ok(false)
+        }
+    }
+
+    fn forget_connected(&self, address: &str) {
+        let removed = self
+            .previously_connected
+            .lock()
+            .expect("previously connected lock poisoned")
+            .remove(address);
+        if removed {
+            self.save_previously_connected();
+        }
+    }
+
+    fn remember_connected(&self, address: String) {
+        let inserted = self
+            .previously_connected
+            .lock()
+            .expect("previously connected lock poisoned")
+            .insert(address);
+        if inserted {
+            self.save_previously_connected();
+        }
+    }
+
+    /// Writes the previously connected devices to the store on a blocking thread
+    /// Every write saves the set as it is when the write runs, so writes finishing out of order
+    /// can't leave an older set in the store.
+    fn save_previously_connected(&self) {
+        let Some(store) = self.store.lock().expect("store lock poisoned").clone() else {
+            return;
+        };
+        let previously_connected = self.previously_connected.clone();
+        let store_writes = self.store_writes.clone();
+        runtime::spawn_blocking(move || {
+            let _write = store_writes.lock().expect("store writes lock poisoned");
+            let value = {
+                let addresses = previously_connected
+                    .lock()
+                    .expect("previously connected lock poisoned");
+                if addresses.is_empty() {
+                    None
+                } else {
+                    let mut addresses: Vec<&String> = addresses.iter().collect();
+                    addresses.sort();
+                    Some(serde_json::to_string(&addresses))
+                }
+            };
+            match value {
+                None => store.remove(PREVIOUSLY_CONNECTED_KEY),
+                Some(Ok(value)) => store.set(PREVIOUSLY_CONNECTED_KEY, &value),
+                Some(Err(e)) => warn!("failed to serialize previously connected devices: {e}"),
+            }
+        });
+    }
+
+    fn mark_previously_connected(&self, devices: &mut [BleDevice]) {
+        let previously_connected = self
+            .previously_connected
+            .lock()
+            .expect("previously connected lock poisoned");
+        for d in devices {
+            d.previously_connected = previously_connected.contains(&d.address);
+        }
+    }
+
+    /// Returns the previous bond state of the address
+    #[cfg_attr(
+        not(any(target_os = "android", target_os = "windows")),
+        allow(dead_code)
+    )]
+    pub(crate) fn set_bond_state(&self, address: &str, bonded: bool) -> Option<bool> {
+        self.bond_states
+            .lock()
+            .expect("bond states lock poisoned")
+            .insert(
+                normalize_address(address).unwrap_or_else(|| address.to_string()),
+                bonded,
+            )
+    }
+
+    fn bond_state(&self, address: &str) -> Option<bool> {
+        let address = normalize_address(address).unwrap_or_else(|| address.to_string());
+        self.bond_states
+            .lock()
+            .expect("bond states lock poisoned")
+            .get(&address)
+            .copied()
+    }
+
+    /// Updates the cached bond state after the platform reported a change and publishes
+    /// [`BleEvent::BondChanged`] unless the state was already known
+    #[cfg_attr(not(any(target_os = "android", target_os = "linux")), allow(dead_code))]
+    pub(crate) fn bond_changed(&self, address: &str, bonded: bool) {
+        if self.set_bond_state(address, bonded) == Some(bonded) {
+            return;
+        }
+        info!("bond state of {address} changed, bonded: {bonded}");
+        self.publish(BleEvent::BondChanged(BondChange {
+            address: address.to_string(),
+            bonded,
+        }));
+    }
+
+    /// Follows bonds created or removed outside of the app, e.g. in the system settings
+    #[cfg(target_os = "android")]
+    pub(crate) async fn watch_bonds(&self) {
+        let mut changes = match crate::android::bond_changes() {
+            Ok(changes) => changes,
+            Err(e) => {
+                warn!("failed to listen for bond changes: {e}");
+                return;
+            }
+        };
+        while let Some((address, bonded)) = changes.next().await {
+            self.bond_changed(&address, bonded);
+        }
+    }
+
+    /// Follows bonds created or removed outside of the app, e.g. with `bluetoothctl`
+    /// BlueZ sends no event for the `Paired` property of a device, so it is polled every
+    /// [`BOND_POLL_INTERVAL`]. The first poll only records the bond states, a device removed
+    /// from BlueZ lost its bond.
+    #[cfg(target_os = "linux")]
+    pub(crate) async fn watch_bonds(&self) {
+        let session = match bluez_async::BluetoothSession::new().await {
+            Ok((connection, session)) => {
+                runtime::spawn(async move {
+                    if let Err(e) = connection.await {
+                        warn!("lost the BlueZ session of the bond states: {e}");
+                    }
+                });
+                session
+            }
+            Err(e) => {
+                warn!("failed to listen for bond changes: {e}");
+                return;
+            }
+        };
+        let mut previous: Option<HashMap<String, bool>> = None;
+        loop {
+            match session.get_devices().await {
+                Ok(devices) => {
+                    let current: HashMap<String, bool> = devices
+                        .into_iter()
+                        .map(|device| (device.mac_address.to_string(), device.paired))
+                        .collect();
+                    match &previous {
+                        None => {
+                            for (address, bonded) in &current {
+                                self.set_bond_state(address, *bonded);
+                            }
+                        }
+                        Some(previous) => {
+                            for (address, bonded) in &current {
+                                if previous.get(address) != Some(bonded) {
+                                    self.bond_changed(address, *bonded);
+                                }
+                            }
+                            for (address, _) in previous.iter().filter(|(_, bonded)| **bonded) {
+                                if !current.contains_key(address) {
+                                    self.bond_changed(address, false);
+                                }
+                            }
+                        }
+                    }
+                    previous = Some(current);
+                }
+                Err(e) => debug!("failed to read the bond states: {e}"),
+            }
+            sleep(BOND_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Translates authentication failures into [`Error::PairingRequired`] unless the device is
+    /// known to be bonded and any failure while the adapter is powered off into
+    /// [`Error::AdapterPoweredOff`], other errors are passed on
+    /// On Windows the pairing is then started in the background, see
+    /// [`BleEvent::PairingConsentRequired`].
+    pub(crate) fn auth_error(&self, address: &str, e: btleplug::Error) -> Error {
+        if self.powered_off() {
+            debug!("operation on {address} failed with the adapter powered off: {e}");
+            return Error::AdapterPoweredOff;
+        }
+        if is_auth_failure(&e) && self.bond_state(address) != Some(true) {
+            debug!("operation on {address} failed without a bond: {e}");
+            #[cfg(target_os = "windows")]
+            self.pair_in_background(address);
+            return Error::PairingRequired(address.to_string());
+        }
+        e.into()
+    }
+
+    /// Pairs with `address` in the background after an operation failed without a bond
+    /// Windows never hands the consent of a pairing it starts for an operation to the app, the
+    /// custom pairing publishes it as [`BleEvent::PairingConsentRequired`]. Only one pairing
+    /// per address runs at a time.
+    #[cfg(target_os = "windows")]
+    fn pair_in_background(&self, address: &str) {
+        let Ok(bdaddr) = address.parse::<btleplug::api::BDAddr>() else {
+            return;
+        };
+        let started = self
+            .background_pairings
+            .lock()
+            .expect("background pairings lock poisoned")
+            .insert(address.to_string());
+        if !started {
+            return;
+        }
+        let running = self.background_pairings.clone();
+        let publisher = self.publisher.clone();
+        let consents = self.pairing_consents.clone();
+        let address = address.to_string();
+        runtime::spawn_blocking(move || {
+            let status = crate::consent::windows_pairing::pair_blocking(
+                address.clone(),
+                u64::from(bdaddr),
+                publisher,
+                consents,
+            );
+            debug!("background pairing of {address} finished: {status:?}");
+            running
+                .lock()
+                .expect("background pairings lock poisoned")
+                .remove(&address);
+        });
+    }
+
+    /// Returns the health of the handler
+    pub fn health(&self) -> Health {
+        let idle = self
+            .last_central_event
+            .lock()
+            .expect("last central event lock poisoned")
+            .elapsed();
+        Health {
+            event_loop_degraded: self.event_loop_degraded.load(Ordering::Relaxed),
+            last_event_ms: u64::try_from(idle.as_millis()).unwrap_or(u64::MAX),
+        }
+    }
+
+    /// Sets how long the central event stream may be silent while a device is connected or a
+    /// scan runs before the adapter is checked
+    /// If the check fails, the event loop is marked degraded in [`Handler::health`],
+    /// `blec://event-loop-stalled` is emitted and the event stream is acquired again.
+    /// Defaults to 30 seconds, `None` disables the heartbeat.
+    pub fn set_heartbeat_interval(&self, interval: Option<Duration>) {
+        *self
+            .heartbeat_interval
+            .lock()
+            .expect("heartbeat interval lock poisoned") = interval;
+    }
+
+    fn mark_event_loop_alive(&self) {
+        *self
+            .last_central_event
+            .lock()
+            .expect("last central event lock poisoned") = Instant::now();
+        self.event_loop_degraded.store(false, Ordering::Relaxed);
+    }
+
+    /// Starts handling the central events of the adapter, connects, disconnects and scans rely
+    /// on it
+    /// The plugin starts the event loop on its own, this is meant for handlers created with
+    /// [`Handler::new`]. The loop runs until the returned guard is dropped.
+    /// # Errors
+    /// Returns [`Error::EventLoopRunning`] if the event loop of the handler is already running
+    pub fn spawn_event_loop(&'static self) -> Result<EventLoopGuard, Error> {
+        if self.event_loop_running.swap(true, Ordering::AcqRel) {
+            return Err(Error::EventLoopRunning);
+        }
+        #[allow(unused_mut)]
+        let mut tasks = vec![
+            runtime::spawn(self.run_heartbeat()),
+            runtime::spawn(self.run_event_loop()),
+        ];
+        #[cfg(any(target_os = "android", target_os = "linux"))]
+        tasks.push(runtime::spawn(self.watch_bonds()));
+        Ok(EventLoopGuard {
+            handler: self,
+            tasks,
+        })
+    }
+
+    async fn run_event_loop(&self) {
+        let mut retry_delay = EVENT_STREAM_RETRY_DELAY;
+        loop {
+            let stream = match self.get_event_stream().await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    error!("failed to get event stream, retrying in {retry_delay:?}: {e}");
+                    sleep(retry_delay).await;
+                    retry_delay = (retry_delay * 2).min(EVENT_STREAM_MAX_RETRY_DELAY);
+                    continue;
+                }
+            };
+            // the heartbeat requests a new stream if the current one stalled
+            let restart = self.event_stream_restart.notified();
+            let mut stream = std::pin::pin!(stream.take_until(restart));
+            let mut received = false;
+            while let Some(event) = stream.next().await {
+                received = true;
+                if let Err(e) = self.handle_event(event).await {
+                    error!("failed to handle event: {e}");
+                }
+            }
+            if stream.is_stopped() || received {
+                retry_delay = EVENT_STREAM_RETRY_DELAY;
+                warn!("event stream ended, acquiring a new one");
+                continue;
+            }
+            // a stream ending without any event would otherwise be acquired again in a busy loop
+            warn!("event stream ended without events, acquiring a new one in {retry_delay:?}");
+            sleep(retry_delay).await;
+            retry_delay = (retry_delay * 2).min(EVENT_STREAM_MAX_RETRY_DELAY);
+        }
+    }
+
+    /// Stops scans and monitors, closes every connection and background task of the handler
+    /// Meant for handlers created with [`Handler::new`], drop the [`EventLoopGuard`] afterwards.
+    /// The handler can be used again after a shutdown.
+    /// # Errors
+    /// Returns an error if stopping the scan or disconnecting fails, the remaining cleanup is
+    /// done anyway
+    pub async fn shutdown(&self) -> Result<(), Error> {
+        info!("shutting down the handler");
+        let mut result = Ok(());
+        if self.is_scanning().await {
+            result = result.and(self.stop_scan().await);
+        }
+        result = result.and(self.stop_battery_monitor().await);
+        for connection in self.all_connections() {
+            let linked = connection.link().is_some();
+            let disconnected = self.disconnect_connection(&connection, false).await;
+            // a connection without a link only had its reconnect cancelled
+            if linked {
+                result = result.and(disconnected);
+            }
+            connection.tasks.lock().await.abort();
+        }
+        if let Some(task) = self.state.lock().await.metrics_task.take() {
+            task.abort();
+        }
+        result
+    }
+
+    /// Checks the adapter whenever the event stream was silent for the heartbeat interval
+    async fn run_heartbeat(&self) {
+        loop {
+            let interval = *self
+                .heartbeat_interval
+                .lock()
+                .expect("heartbeat interval lock poisoned");
+            sleep(interval.unwrap_or(DEFAULT_HEARTBEAT_INTERVAL)).await;
+            self.check_authorization();
+            let Some(interval) = interval else {
+                continue;
+            };
+            if !self.is_connected() && !self.is_scanning().await {
+                continue;
+            }
+            let idle = self
+                .last_central_event
+                .lock()
+                .expect("last central event lock poisoned")
+                .elapsed();
+            if idle < interval {
+                continue;
+            }
+            match timeout(LIVENESS_CHECK_TIMEOUT, self.adapter().peripherals()).await {
+                Ok(Ok(_)) => {
+                    debug!("no central event for {idle:?}, adapter is responsive");
+                    self.mark_event_loop_alive();
+                }
+                Ok(Err(e)) => self.event_loop_stalled(idle, &e.to_string()).await,
+                Err(_) => self.event_loop_stalled(idle, "timed out").await,
+            }
+        }
+    }
+
+    async fn event_loop_stalled(&self, idle: Duration, reason: &str) {
+        error!("no central event for {idle:?} and liveness check failed: {reason}");
+        self.event_loop_degraded.store(true, Ordering::Relaxed);
+        let idle_ms = u64::try_from(idle.as_millis()).unwrap_or(u64::MAX);
+        self.publish(BleEvent::EventLoopStalled { idle_ms });
+        self.event_stream_restart.notify_one();
+    }
+
+    /// Enables keep-alive pings for the connection
+    /// Whenever no data was sent or received for `interval`, the given operation is executed
+    /// to keep devices with an inactivity timeout connected. The setting also applies to later
+    /// connections until [`Handler::clear_keep_alive`] is called.
+    /// If the ping fails repeatedly, the connection is considered lost and the device is disconnected.
+    /// # Errors
+    /// Returns an error if the method is not supported on this platform
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// use std::time::Duration;
+    /// use uuid::{Uuid,uuid};
+    /// use tauri_plugin_blec::KeepAliveMethod;
+    /// const CHARACTERISTIC_UUID: Uuid = uuid!("51FF12BB-3ED8-46E5-B4F9-D64E2FEC021B");
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     handler
+    ///         .set_keep_alive(Duration::from_secs(20), KeepAliveMethod::Read { characteristic: CHARACTERISTIC_UUID })
+    ///         .await
+    ///         .unwrap();
+    /// });
+    /// # }
+    /// ```
+    pub async fn set_keep_alive(
+        &'static self,
+        interval: Duration,
+        method: KeepAliveMethod,
+    ) -> Result<(), Error> {
+        #[cfg(not(target_os = "android"))]
+        if method == KeepAliveMethod::Rssi {
+            return Err(Error::UnsupportedKeepAlive("rssi"));
+        }
+        let keep_alive = KeepAlive { interval, method };
+        self.state.lock().await.keep_alive = Some(keep_alive.clone());
+        for connection in self.all_connections() {
+            if connection.is_connected() {
+                let mut tasks = connection.tasks.lock().await;
+                self.start_keep_alive(&connection, Some(&keep_alive), &mut tasks);
+            }
+        }
+        Ok(())
+    }
+
+    /// Disables the keep-alive pings set with [`Handler::set_keep_alive`]
+    pub async fn clear_keep_alive(&self) {
+        self.state.lock().await.keep_alive = None;
+        for connection in self.all_connections() {
+            if let Some(task) = connection.tasks.lock().await.keep_alive.take() {
+                task.abort();
+            }
+        }
+    }
+
+    fn start_keep_alive(
+        &'static self,
+        connection: &Arc<Connection>,
+        keep_alive: Option<&KeepAlive>,
+        tasks: &mut ConnectionTasks,
+    ) {
+        if let Some(task) = tasks.keep_alive.take() {
+            task.abort();
+        }
+        if let Some(keep_alive) = keep_alive {
+            tasks.keep_alive = Some(runtime::spawn(self.run_keep_alive(
+                connection.clone(),
+                keep_alive.interval,
+                keep_alive.method.clone(),
+            )));
+        }
+    }
+
+    async fn run_keep_alive(
+        &'static self,
+        connection: Arc<Connection>,
+        interval: Duration,
+        method: KeepAliveMethod,
+    ) {
+        let mut failures = 0;
+        // pings are not activity, otherwise they would keep the idle timeout from firing
+        let mut last_ping = Instant::now();
+        loop {
+            let idle = connection
+                .last_activity
+                .lock()
+                .expect("activity lock poisoned")
+                .elapsed()
+                .min(last_ping.elapsed());
+            if idle < interval {
+                // real traffic happened within the interval, no ping needed
+                sleep(interval - idle).await;
+                continue;
+            }
+            match self.keep_alive_ping(&connection, &method).await {
+                Ok(()) => {
+                    failures = 0;
+                    last_ping = Instant::now();
+                }
+                Err(Error::NoDeviceConnected | Error::Disconnected) => return,
+                Err(Error::CharacNotAvailable(c)) => {
+                    error!("keep-alive characteristic {c} not available, stopping keep-alive");
+                    return;
+                }
+                Err(e) => {
+                    failures += 1;
+                    warn!("keep-alive ping failed ({failures}/{KEEP_ALIVE_MAX_FAILURES}): {e}");
+                    if failures >= KEEP_ALIVE_MAX_FAILURES {
+                        self.keep_alive_lost(&connection).await;
+                        return;
+                    }
+                    sleep(interval).await;
+                }
+            }
+        }
+    }
+
+    async fn keep_alive_ping(
+        &self,
+        connection: &Connection,
+        method: &KeepAliveMethod,
+    ) -> Result<(), Error> {
+        let dev = self.lock_ready(connection).await?;
+        let dev = dev.as_ref().ok_or(Error::NoDeviceConnected)?;
+        debug!("sending keep-alive ping to {}", connection.address);
+        match method {
+            #[cfg(target_os = "android")]
+            KeepAliveMethod::Rssi => {
+                dev.read_rssi().await?;
+            }
+            #[cfg(not(target_os = "android"))]
+            KeepAliveMethod::Rssi => return Err(Error::UnsupportedKeepAlive("rssi")),
+            KeepAliveMethod::Read { characteristic } => {
+                let charac = dev
+                    .characteristics()
+                    .into_iter()
+                    .find(|x| x.uuid == *characteristic)
+                    .ok_or(Error::CharacNotAvailable(characteristic.to_string()))?;
+                dev.read(&charac).await?;
+            }
+            KeepAliveMethod::EmptyWrite { characteristic } => {
+                let charac = dev
+                    .characteristics()
+                    .into_iter()
+                    .find(|x| x.uuid == *characteristic)
+                    .ok_or(Error::CharacNotAvailable(characteristic.to_string()))?;
+                dev.write(&charac, &[], btleplug::api::WriteType::WithResponse)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Handles a connection that stopped responding to keep-alive pings like a lost link
+    async fn keep_alive_lost(&self, connection: &Arc<Connection>) {
+        error!(
+            "keep-alive of {} failed, treating connection as lost",
+            connection.address
+        );
+        // detach the running task so the disconnect handling does not abort it halfway
+        drop(connection.tasks.lock().await.keep_alive.take());
+        let Some(id) = connection.link_id() else {
+            return;
+        };
+        if let Err(e) = self.disconnect_connection(connection, false).await {
+            warn!("disconnect after keep-alive failure failed: {e}, cleaning up connection");
+            if let Err(e) = self.handle_disconnect(id).await {
+                error!("failed to clean up lost connection: {e}");
+            }
+        }
+    }
+
+    /// Disconnects once nothing happened on the connection for `timeout`, see
+    /// [`ConnectOptions::idle_disconnect_ms`]
+    async fn run_idle_disconnect(
+        &'static self,
+        connection: Arc<Connection>,
+        timeout: Duration,
+        reconnect: Option<IdleReconnect>,
+    ) {
+        loop {
+            let idle = connection
+                .last_activity
+                .lock()
+                .expect("activity lock poisoned")
+                .elapsed();
+            if idle >= timeout {
+                break;
+            }
+            sleep(timeout - idle).await;
+        }
+        info!("no activity on {} for {timeout:?}, disconnecting", connection.address);
+        // detach the running task so the disconnect handling does not abort it halfway
+        drop(connection.tasks.lock().await.idle.take());
+        connection.idle_disconnecting.store(true, Ordering::Release);
+        if let Err(e) = self.disconnect_connection(&connection, false).await {
+            warn!("idle disconnect failed: {e}");
+            connection.idle_disconnecting.store(false, Ordering::Release);
+            return;
+        }
+        if let Some(reconnect) = reconnect {
+            debug!("reconnecting to {} on the next operation", reconnect.address);
+            *connection
+                .idle_reconnect
+                .lock()
+                .expect("idle reconnect lock poisoned") = Some(reconnect);
+        } else {
+            self.drop_connection(&connection);
+        }
+    }
+
+    /// Connects again after an idle disconnect if [`ConnectOptions::reconnect_on_demand`] was set
+    /// Nothing happens if no reconnect is pending for the connection.
+    async fn reconnect_if_idle(&self, connection: &Connection) -> Result<(), Error> {
+        if SETTING_UP.try_with(|()| ()).is_ok() {
+            // the setup steps run within the pending connect, waiting for it would never end
+            return Ok(());
+        }
+        reconnect_pending(&connection.idle_reconnect, &connection.reconnecting).await
+    }
+
+    fn start_auto_reconnect(&'static self, connection: Arc<Connection>, reconnect: AutoReconnect) {
+        let cancel = Arc::new(Notify::new());
+        let previous = connection
+            .auto_reconnect
+            .lock()
+            .expect("auto reconnect lock poisoned")
+            .replace(cancel.clone());
+        if let Some(previous) = previous {
+            previous.notify_one();
+        }
+        runtime::spawn(self.run_auto_reconnect(connection, reconnect, cancel));
+    }
+
+    async fn run_auto_reconnect(
+        &'static self,
+        connection: Arc<Connection>,
+        reconnect: AutoReconnect,
+        cancel: Arc<Notify>,
+    ) {
+        let AutoReconnect {
+            address,
+            options,
+            policy,
+            ..
+        } = reconnect;
+        let mut backoff = policy.backoff;
+        let mut error = Error::ConnectionFailed;
+        for attempt in 1..=policy.max_attempts {
+            let wait = std::pin::pin!(sleep(backoff));
+            let cancelled = std::pin::pin!(cancel.notified());
+            if let Either::Right(_) = select(wait, cancelled).await {
+                connection.kept_listeners.lock().await.clear();
+                if !connection.is_connected() {
+                    self.set_connection_state(&address, ConnectionState::Disconnected);
+                }
+                self.drop_connection(&connection);
+                return;
+            }
+            backoff = (backoff * 2).min(MAX_RECONNECT_BACKOFF);
+            info!("reconnecting to {address} ({attempt}/{})", policy.max_attempts);
+            self.publish(BleEvent::ReconnectProgress(ReconnectProgress::Connecting {
+                address: address.clone(),
+            }));
+            let connected = self
+                .connect_with_options(&address, None, options.clone())
+                .await;
+            let finished = connected.is_ok() || attempt == policy.max_attempts;
+            if !connection.reconnect_running(&cancel, finished) {
+                debug!("reconnect to {address} was cancelled during the connect");
+                connection.kept_listeners.lock().await.clear();
+                if connected.is_ok() {
+                    if let Err(e) = self.close_connection(&connection, false).await {
+                        warn!("disconnect after the cancelled reconnect failed: {e}");
+                    }
+                }
+                self.drop_connection(&connection);
+                return;
+            }
+            match connected {
+                Ok(_) => {
+                    self.restore_listeners(&connection).await;
+                    self.publish(BleEvent::ReconnectProgress(ReconnectProgress::Connected {
+                        address,
+                    }));
+                    return;
+                }
+                Err(e) => {
+                    warn!("reconnect to {address} failed: {e}");
+                    error = e;
+                    if !finished {
+                        self.set_connection_state(&address, ConnectionState::Reconnecting);
+                    }
+                }
+            }
+        }
+        connection.reconnect_running(&cancel, true);
+        connection.kept_listeners.lock().await.clear();
+        error!("giving up on {address} after {} attempts", policy.max_attempts);
+        self.set_connection_state(&address, ConnectionState::Disconnected);
+        self.publish(BleEvent::ReconnectProgress(ReconnectProgress::Failed {
+            address: address.clone(),
+            error: error.to_string(),
+        }));
+        self.publish(BleEvent::Disconnected {
+            address: address.clone(),
+            reason: DisconnectReason::ReconnectFailed,
+        });
+        let tasks = connection.tasks.lock().await;
+        self.call_on_disconnect(&tasks, &address);
+        drop(tasks);
+        self.drop_connection(&connection);
+    }
+
+    /// Subscribes the listeners kept during an automatic reconnect again
+    /// Listeners of characteristics the device no longer offers are dropped.
+    async fn restore_listeners(&self, connection: &Connection) {
+        let kept = std::mem::take(&mut *connection.kept_listeners.lock().await);
+        let dev = connection.dev.lock().await;
+        let Some(dev) = dev.as_ref() else {
+            return;
+        };
+        let mut subscribed = HashSet::new();
+        let mut restored = vec![];
+        for listener in kept {
+            if !subscribed.contains(&listener.uuid) {
+                let characs = dev.characteristics();
+                let Some(charac) = characs.iter().find(|c| c.uuid == listener.uuid) else {
+                    warn!("{} is gone after the reconnect, dropping its listener", listener.uuid);
+                    continue;
+                };
+                if let Err(e) = dev.subscribe(charac).await {
+                    warn!("failed to subscribe to {} again: {e}", listener.uuid);
+                    continue;
+                }
+                subscribed.insert(listener.uuid);
+            }
+            restored.push(listener);
+        }
+        debug!("restored {} listeners after the reconnect", restored.len());
+        connection.listeners.lock().await.extend(restored);
+    }
+
+    /// Reads back the client characteristic configuration of every subscribed characteristic
+    /// of the connected device and subscribes the cleared ones again, see
+    /// [`Handler::verify_subscriptions`]
+    /// With `refresh` the services are discovered again first, the handles of the previous
+    /// discovery may be stale after another application triggered one. The operation queue of
+    /// the device is only held for one characteristic at a time, so other operations are not
+    /// blocked by the check.
+    pub(crate) async fn reconcile_subscriptions(
+        &self,
+        address: &str,
+        refresh: bool,
+    ) -> Result<SubscriptionReport, Error> {
+        let connection = self
+            .connection(address)
+            .filter(|c| c.link().is_some())
+            .ok_or(Error::NoDeviceConnected)?;
+        let link = connection.link_id();
+        if refresh {
+            let dev = self.reconcile_turn(&connection, link.as_ref()).await?;
+            let dev = dev.as_ref().ok_or(Error::NoDeviceConnected)?;
+            if let Err(e) = dev.discover_services().await {
+                warn!("failed to discover the services of {address} again: {e}");
+            }
+        }
+        let mut report = SubscriptionReport {
+            address: address.to_string(),
+            ..SubscriptionReport::default()
+        };
+        for uuid in subscriptions(&connection).await {
+            let dev = self.reconcile_turn(&connection, link.as_ref()).await?;
+            let dev = dev.as_ref().ok_or(Error::NoDeviceConnected)?;
+            let characs = dev.characteristics();
+            let Some(charac) = characs.iter().find(|c| c.uuid == uuid) else {
+                warn!("{uuid} is no longer offered by {address}");
+                report.failed.push(uuid);
+                continue;
+            };
+            let target = match subscribe_target(charac, self.subscribe_mode(uuid).await) {
+                Ok(target) => target,
+                Err(e) => {
+                    warn!("{uuid} can no longer be subscribed: {e}");
+                    report.failed.push(uuid);
+                    continue;
+                }
+            };
+            match subscription_enabled(dev, &target).await {
+                Ok(true) => {
+                    report.verified.push(uuid);
+                    continue;
+                }
+                Ok(false) => {}
+                Err(e) => {
+                    debug!("can not verify the subscription of {uuid}: {e}");
+                    report.unverifiable.push(uuid);
+                    continue;
+                }
+            }
+            warn!("subscription of {uuid} was cleared on {address}, subscribing again");
+            // the device may have reset what the write enabled along with the subscription
+            connection
+                .required_writes_done
+                .lock()
+                .expect("required writes lock poisoned")
+                .remove(&uuid);
+            let restored = match self.check_required_write(&connection, dev, uuid).await {
+                Ok(()) => dev.subscribe(&target).await.map_err(Error::from),
+                Err(e) => Err(e),
+            };
+            match restored {
+                Ok(()) => report.restored.push(uuid),
+                Err(e) => {
+                    warn!("failed to subscribe to {uuid} again: {e}");
+                    report.failed.push(uuid);
+                }
+            }
+        }
+        connection.mark_activity();
+        Ok(report)
+    }
+
+    /// Waits for the operation queue of the connection for one step of
+    /// [`Handler::reconcile_subscriptions`]
+    /// # Errors
+    /// Returns [`Error::Disconnected`] if the device reconnected since the check started
+    async fn reconcile_turn<'a>(
+        &self,
+        connection: &'a Connection,
+        link: Option<&PeripheralId>,
+    ) -> Result<tokio::sync::MutexGuard<'a, Option<Peripheral>>, Error> {
+        let dev = self.lock_ready(connection).await?;
+        if dev.as_ref().map(btleplug::api::Peripheral::id).as_ref() != link {
+            return Err(Error::Disconnected);
+        }
+        Ok(dev)
+    }
+
+    /// Returns the connected device of [`Handler::connect`]
+    /// On android the RSSI is read from the link, other platforms report the RSSI of the last
+    /// advertisement, which may be stale while connected.
+    /// # Errors
+    /// Returns an error if no device is connected
+    pub async fn connected_device(&self) -> Result<BleDevice, Error> {
+        let connection = self.main_connection().ok_or(Error::NoDeviceConnected)?;
+        let p = connection.dev.lock().await;
+        let p = p.as_ref().ok_or(Error::NoDeviceConnected)?;
+        #[allow(unused_mut)]
+        let mut d = BleDevice::from_peripheral(p).await?;
+        #[cfg(target_os = "android")]
+        match p.read_rssi().await {
+            Ok(rssi) => d.rssi = Some(rssi),
+            Err(e) => debug!("failed to read the rssi of the connected device: {e}"),
+        }
+        Ok(d)
+    }
+
+    /// Returns the characteristics of the device of [`Handler::connect`] as reported by btleplug
+    /// This exposes btleplug's own types and is not covered by the plugin's stability guarantees,
+    /// the contents are platform shaped. Empty if no device is connected.
+    pub async fn characteristics(&self) -> Vec<Characteristic> {
+        let Some(connection) = self.main_connection() else {
+            return vec![];
+        };
+        connection
+            .dev
+            .lock()
+            .await
+            .as_ref()
+            .map(|dev| dev.characteristics().into_iter().collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the services of the device of [`Handler::connect`] as reported by btleplug
+    /// Like [`Handler::characteristics`] this is platform shaped, the serialized form used by the
+    /// commands is [`Service`]. Empty if no device is connected.
+    pub async fn services(&self) -> Vec<btleplug::api::Service> {
+        let Some(connection) = self.main_connection() else {
+            return vec![];
+        };
+        services_of(&connection).await
+    }
+
+    /// Returns the whole GATT database of the device of [`Handler::connect`] with decoded
+    /// properties
+    /// Services are discovered first if the platform did not do so yet. All services are
+    /// returned, independent of the characteristics used so far.
+    /// # Errors
+    /// Returns [`Error::NoDeviceConnected`] if no device is connected or an error if the
+    /// discovery fails
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     for service in handler.discover_all_services().await.unwrap() {
+    ///         for c in service.characteristics {
+    ///             println!("{}/{}: notify {}", service.uuid, c.uuid, c.properties.notify);
+    ///         }
+    ///     }
+    /// });
+    /// # }
+    /// ```
+    pub async fn discover_all_services(&self) -> Result<Vec<ServiceInfo>, Error> {
+        self.check_authorized()?;
+        let connection = self.connection_for(None)?;
+        self.reconnect_if_idle(&connection).await?;
+        let dev = connection.dev.lock().await;
+        let dev = dev.as_ref().ok_or(Error::NoDeviceConnected)?;
+        if dev.services().is_empty() {
+            dev.discover_services().await?;
+        }
+        Ok(dev.services().iter().map(ServiceInfo::from).collect())
+    }
+
+    fn handle_connect(&self, peripheral_id: &PeripheralId) {
+        if let Some(connection) = self.connection_of(peripheral_id) {
+            debug!("connection to {peripheral_id} established");
+            let main = self.is_main(&connection.address);
+            self.set_connected(&connection, main, true);
+        } else {
+            // event not for a connecting device, ignore
+            debug!(
+                "connect event for device {peripheral_id} received without waiting for connection"
+            );
+        }
+    }
+
+    async fn send_connection_update(&self, state: bool) {
+        let tx = &mut self.state.lock().await.connection_update_channel;
+        let mut remove = vec![];
+        for (i, t) in tx.iter_mut().enumerate() {
+            if let Err(e) = t.send(state).await {
+                warn!("Failed to send connection update: {e}");
+                remove.push(i);
+            }
+        }
+    }
+
+    async fn send_scan_update(&self, state: bool) {
+        let tx = &mut self.state.lock().await.scan_update_channel;
+        let mut remove = vec![];
+        for (i, t) in tx.iter_mut().enumerate() {
+            if let Err(e) = t.send(state).await {
+                warn!("Failed to send scan update: {e}");
+                remove.push(i);
+            }
+        }
+    }
+}
+
+/// Polls the peripherals of every scanned adapter
+/// A device seen by several adapters is reported once, with the handle of the adapter that
+/// received the strongest RSSI.
+async fn poll_adapters(
+    adapters: &[ScanAdapter],
+    cache: &mut ScanPropertiesCache,
+    advertisements: impl Fn(&Peripheral) -> u64,
+) -> PolledDevices {
+    let mut polled = PolledDevices::default();
+    let mut strongest: HashMap<String, (Peripheral, Option<PeripheralProperties>)> =
+        HashMap::new();
+    let mut strongest_adapter = HashMap::new();
+    for scanned in adapters {
+        let discovered = scanned
+            .adapter
+            .peripherals()
+            .await
+            .expect("failed to get peripherals");
+        polled.peripherals.extend(discovered.iter().cloned());
+        for (p, properties) in peripheral_properties(discovered, cache, &advertisements).await {
+            let address = models::peripheral_address(&p);
+            let rssi = properties.as_ref().and_then(|props| props.rssi);
+            if let Some(id) = &scanned.id {
+                polled
+                    .sightings
+                    .entry(address.clone())
+                    .or_default()
+                    .push(AdapterSighting {
+                        adapter_id: id.clone(),
+                        rssi,
+                    });
+            }
+            let stronger = match strongest.get(&address) {
+                Some((_, current)) => rssi > current.as_ref().and_then(|props| props.rssi),
+                None => true,
+            };
+            if stronger {
+                strongest_adapter.insert(address.clone(), scanned.id.clone());
+                strongest.insert(address, (p, properties));
+            }
+        }
+    }
+    for seen in polled.sightings.values_mut() {
+        seen.sort_by_key(|s| std::cmp::Reverse(s.rssi));
+    }
+    polled.strongest = strongest.into_values().collect();
+    polled.strongest_adapter = strongest_adapter;
+    polled
+}
+
+/// Id of the adapter reported by the platform, the index if the platform does not report one
+#[cfg_attr(target_os = "android", allow(clippy::unused_async))]
+async fn adapter_id(adapter: &Adapter, index: usize) -> String {
+    #[cfg(target_os = "android")]
+    {
+        // the android adapter has no adapter info
+        let _ = adapter;
+        index.to_string()
+    }
+    #[cfg(not(target_os = "android"))]
+    {
+        adapter
+            .adapter_info()
+            .await
+            .unwrap_or_else(|_| index.to_string())
+    }
+}
+
+/// Fetches the properties of every peripheral once per scan tick
+/// Filtering, conversion, enrichment and fingerprinting all use these properties, so every
+/// peripheral costs a single properties call (a DBus round trip with BlueZ) per tick and
+/// peripherals not matching the filter are skipped before they are converted. Peripherals that
+/// did not advertise since the last fetch reuse the properties of the cache.
+/// Peripherals whose properties can not be read are dropped.
+async fn peripheral_properties<P: ScannedPeripheral>(
+    discovered: Vec<P>,
+    cache: &mut PropertiesCache<P::Id, P::Properties>,
+    advertisements: impl Fn(&P) -> u64,
+) -> Vec<(P, Option<P::Properties>)> {
+    let mut result = Vec::with_capacity(discovered.len());
+    for p in discovered {
+        let count = advertisements(&p);
+        if let Some(properties) = cache.get(&p.scan_id(), count) {
+            result.push((p, properties));
+            continue;
+        }
+        match p.fetch_properties().await {
+            Ok(properties) => {
+                cache.insert(p.scan_id(), count, properties.clone());
+                result.push((p, properties));
+            }
+            Err(e) => warn!("Failed to get properties of {}: {e}", p.label()),
+        }
+    }
+    result
+}
+
+/// Returns true if a listener, session or interceptor of the connection uses the
+/// characteristic
+async fn is_subscribed(connection: &Connection, uuid: Uuid) -> bool {
+    connection.listeners.lock().await.iter().any(|l| l.uuid == uuid)
+        || connection
+            .sessions
+            .lock()
+            .await
+            .iter()
+            .any(|s| s.characs.contains(&uuid))
+        || connection
+            .interceptors
+            .lock()
+            .await
+            .iter()
+            .any(|i| i.uuid == uuid)
+}
+
+/// Characteristics subscribed on the connection, by listeners and sessions
+async fn subscriptions(connection: &Connection) -> Vec<Uuid> {
+    let mut subscribed: Vec<Uuid> = connection
+        .listeners
+        .lock()
+        .await
+        .iter()
+        .map(|l| l.uuid)
+        .collect();
+    for session in connection.sessions.lock().await.iter() {
+        subscribed.extend(&session.characs);
+    }
+    subscribed.sort();
+    subscribed.dedup();
+    subscribed
+}
+
+/// Services of the device of the connection, empty without a link
+async fn services_of(connection: &Connection) -> Vec<btleplug::api::Service> {
+    connection
+        .dev
+        .lock()
+        .await
+        .as_ref()
+        .map(|dev| dev.services().into_iter().collect())
+        .unwrap_or_default()
+}
+
+/// Returns true if the device delivers the notifications or indications `target` was
+/// subscribed with, read back from its client characteristic configuration descriptor
+#[cfg(not(any(target_os = "macos", target_os = "ios")))]
+async fn subscription_enabled(dev: &Peripheral, target: &Characteristic) -> Result<bool, Error> {
+    let cccd = resolve_descriptor(target, models::CLIENT_CHARACTERISTIC_CONFIGURATION)?;
+    let state = Cccd::decode(&dev.read_descriptor(&cccd).await?)?;
+    if target.properties.contains(CharPropFlags::INDICATE) {
+        Ok(state.indications)
+    } else {
+        Ok(state.notifications)
+    }
+}
+
+/// Returns true if CoreBluetooth reports the characteristic as notifying
+/// CoreBluetooth does not allow reading the client characteristic configuration descriptor,
+/// `isNotifying` reflects the subscription it keeps for the app instead.
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+async fn subscription_enabled(dev: &Peripheral, target: &Characteristic) -> Result<bool, Error> {
+    apple_is_notifying(&dev.id(), target)
+        .ok_or_else(|| Error::CharacNotAvailable(target.uuid.to_string()))
+}
+
+/// Reads `isNotifying` of the characteristic from CoreBluetooth, retrieving the peripheral
+/// like [`apple_write_limits`]
+/// `None` if CoreBluetooth does not report the peripheral connected or does not know the
+/// characteristic.
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn apple_is_notifying(id: &PeripheralId, target: &Characteristic) -> Option<bool> {
+    use objc2_core_bluetooth::{CBCentralManager, CBPeripheralState, CBUUID};
+    use objc2_foundation::{NSArray, NSUUID};
+
+    // CoreBluetooth shortens the uuids of the Bluetooth base uuid to 16 or 32 bits
+    let parse = |uuid: &CBUUID| {
+        let uuid = unsafe { uuid.UUIDString() }.to_string();
+        match uuid.len() {
+            4 => u16::from_str_radix(&uuid, 16).ok().map(btleplug::api::bleuuid::uuid_from_u16),
+            8 => u32::from_str_radix(&uuid, 16).ok().map(btleplug::api::bleuuid::uuid_from_u32),
+            _ => Uuid::parse_str(&uuid).ok(),
+        }
+    };
+    let uuid = Uuid::parse_str(&id.to_string()).ok()?;
+    let identifiers = NSArray::from_vec(vec![NSUUID::from_bytes(*uuid.as_bytes())]);
+    let manager = unsafe { CBCentralManager::new() };
+    let peripherals = unsafe { manager.retrievePeripheralsWithIdentifiers(&identifiers) };
+    let peripheral = peripherals.firstObject()?;
+    if unsafe { peripheral.state() } != CBPeripheralState::Connected {
+        return None;
+    }
+    let services = unsafe { peripheral.services() }?;
+    let service = services
+        .iter()
+        .find(|s| parse(&unsafe { s.UUID() }) == Some(target.service_uuid))?;
+    let characteristics = unsafe { service.characteristics() }?;
+    let characteristic = characteristics
+        .iter()
+        .find(|c| parse(&unsafe { c.UUID() }) == Some(target.uuid))?;
+    Some(unsafe { characteristic.isNotifying() })
+}
+
+/// Reads `maximumWriteValueLength(for:)` of both write types from CoreBluetooth
+/// btleplug does not hand out its `CBPeripheral`, the peripheral is retrieved again by its
+/// identifier. `None` if CoreBluetooth does not report it connected, the limits are only valid
+/// for a connection.
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn apple_write_limits(id: &PeripheralId) -> Option<WriteLimits> {
+    use objc2_core_bluetooth::{CBCentralManager, CBCharacteristicWriteType, CBPeripheralState};
+    use objc2_foundation::{NSArray, NSUUID};
+
+    let uuid = Uuid::parse_str(&id.to_string()).ok()?;
+    let identifiers = NSArray::from_vec(vec![NSUUID::from_bytes(*uuid.as_bytes())]);
+    let manager = unsafe { CBCentralManager::new() };
+    let peripherals = unsafe { manager.retrievePeripheralsWithIdentifiers(&identifiers) };
+    let peripheral = peripherals.firstObject()?;
+    if unsafe { peripheral.state() } != CBPeripheralState::Connected {
+        return None;
+    }
+    let limit = |write_type| unsafe { peripheral.maximumWriteValueLengthForType(write_type) };
+    Some(WriteLimits {
+        with_response: Some(limit(CBCharacteristicWriteType::WithResponse)),
+        without_response: Some(limit(CBCharacteristicWriteType::WithoutResponse)),
+    })
+}
+
+/// Platform id of a device that is not listed by the adapter
+/// Android and windows identify devices by their address, apple platforms by a uuid. BlueZ ids
+/// are the D-Bus paths of the devices it knows, those are all listed, so there is no id for
+/// others.
+#[cfg(any(target_os = "android", target_os = "windows"))]
+fn platform_peripheral_id(address: &str) -> Option<PeripheralId> {
+    address.parse::<btleplug::api::BDAddr>().ok().map(PeripheralId::from)
+}
+
+#[cfg(target_vendor = "apple")]
+fn platform_peripheral_id(address: &str) -> Option<PeripheralId> {
+    Uuid::parse_str(address).ok().map(PeripheralId::from)
+}
+
+#[cfg(not(any(target_os = "android", target_os = "windows", target_vendor = "apple")))]
+fn platform_peripheral_id(_address: &str) -> Option<PeripheralId> {
+    None
+}
+
+/// Returns the services the adapter should filter for, a device advertising any of them passes
+/// The adapter filter only narrows the scan, [`matches_filter`] still checks every device
+/// since not all platforms apply it.
+fn adapter_filter(filter: &ScanFilter) -> btleplug::api::ScanFilter {
+    let services = match filter {
+        ScanFilter::Service(uuid) => vec![*uuid],
+        ScanFilter::AnyService(uuids) | ScanFilter::AllServices(uuids) => uuids.clone(),
+        ScanFilter::None | ScanFilter::ManufacturerData(..) => vec![],
+    };
+    btleplug::api::ScanFilter { services }
+}
+
+/// Returns the adapter filter of a scan that also reports the devices of the scan consumers,
+/// `covered` are their services and empty if one of them wants all devices
+fn covering_filter(filter: &ScanFilter, covered: Option<&[Uuid]>) -> btleplug::api::ScanFilter {
+    let mut scan_filter = adapter_filter(filter);
+    match covered {
+        None => {}
+        Some(covered) if covered.is_empty() || scan_filter.services.is_empty() => {
+            scan_filter.services.clear();
+        }
+        Some(covered) => {
+            scan_filter.services.extend_from_slice(covered);
+            scan_filter.services.sort();
+            scan_filter.services.dedup();
+        }
+    }
+    scan_filter
+}
+
+/// Advertised data of a device merged over a scan, see [`merge_advertisements`]
+#[derive(Default)]
+struct Advertised {
+    manufacturer_data: HashMap<u16, Vec<u8>>,
+    service_data: HashMap<Uuid, Vec<u8>>,
+    services: Vec<Uuid>,
+}
+
+/// Merges the data of every advertisement of the scan into the devices
+/// Advertisements and scan responses can carry different fields, the latest data of each
+/// company id and service wins and the advertised services accumulate.
+fn merge_advertisements(devices: &mut [BleDevice], merged: &mut HashMap<String, Advertised>) {
+    for d in devices {
+        let data = merged.entry(d.address.clone()).or_default();
+        data.manufacturer_data.extend(d.manufacturer_data.drain());
+        data.service_data.extend(d.service_data.drain());
+        for service in d.services.drain(..) {
+            if !data.services.contains(&service) {
+                data.services.push(service);
+            }
+        }
+        d.manufacturer_data.clone_from(&data.manufacturer_data);
+        d.service_data.clone_from(&data.service_data);
+        d.services.clone_from(&data.services);
+    }
+}
+
+/// Whether the device changed enough since it was last published to publish it again
+/// Counters and the signal strength change with every advertisement, the RSSI only counts once
+/// it moved by [`RSSI_CHANGE_THRESHOLD`].
+fn changed_meaningfully(published: &BleDevice, device: &BleDevice) -> bool {
+    let rssi_moved = match (published.rssi, device.rssi) {
+        (Some(old), Some(new)) => (new - old).abs() >= RSSI_CHANGE_THRESHOLD,
+        (old, new) => old.is_some() != new.is_some(),
+    };
+    let strip = |d: &BleDevice| BleDevice {
+        rssi: None,
+        advertisement_count: None,
+        adv_interval_ms: None,
+        adapters: d
+            .adapters
+            .iter()
+            .map(|s| AdapterSighting {
+                rssi: None,
+                ..s.clone()
+            })
+            .collect(),
+        ..d.clone()
+    };
+    rssi_moved || strip(published) != strip(device)
+}
+
+/// Splits the discovered devices into the ones passing the filters of the scan and the ones only
+/// the scan consumers are interested in, `covered` are their services and empty if one of them
+/// wants all devices
+fn split_for_consumers(
+    discovered: Vec<(Peripheral, Option<PeripheralProperties>)>,
+    filter: &ScanFilter,
+    name_filter: &ScanNameFilter,
+    covered: Option<&[Uuid]>,
+) -> (
+    Vec<(Peripheral, Option<PeripheralProperties>)>,
+    Vec<(Peripheral, Option<PeripheralProperties>)>,
+) {
+    let mut passing = vec![];
+    let mut for_consumers = vec![];
+    for (p, properties) in discovered {
+        let local_name = properties.as_ref().and_then(|p| p.local_name.as_deref());
+        if matches_filter(filter, properties.as_ref()) && name_filter.matches(local_name) {
+            passing.push((p, properties));
+        } else if covered.is_some_and(|covered| {
+            matches_filter(&ScanFilter::AnyService(covered.to_vec()), properties.as_ref())
+        }) {
+            for_consumers.push((p, properties));
+        }
+    }
+    (passing, for_consumers)
+}
+
+fn matches_filter(filter: &ScanFilter, properties: Option<&PeripheralProperties>) -> bool {
+    match filter {
+        ScanFilter::None => return true,
+        ScanFilter::AnyService(uuids) | ScanFilter::AllServices(uuids) if uuids.is_empty() => {
+            return true;
+        }
+        _ => {}
+    }
+    let Some(properties) = properties else {
+        // can't filter without properties
+        return false;
+    };
+    match filter {
+        ScanFilter::None => unreachable!("Earyl return for no filter"),
+        ScanFilter::Service(uuid) => properties.services.iter().any(|s| s == uuid),
+        ScanFilter::AnyService(uuids) => properties.services.iter().any(|s| uuids.contains(s)),
+        ScanFilter::AllServices(uuids) => uuids.iter().all(|s| properties.services.contains(s)),
+        ScanFilter::ManufacturerData(key, value) => properties
+            .manufacturer_data
+            .get(key)
+            .is_some_and(|v| v == value),
+    }
+}
+
+/// Finds the characteristic of the device matching the target
+/// # Errors
+/// Returns [`Error::AmbiguousCharacteristic`] if several services contain the characteristic and
+/// the target does not specify the service
+pub(crate) fn resolve_charac(
+    dev: &Peripheral,
+    target: &CharacteristicRef,
+) -> Result<Characteristic, Error> {
+    resolve_charac_of(&models::peripheral_address(dev), dev.characteristics(), target)
+}
+
+/// Picks the characteristic of `target` out of the characteristics of the device at `address`
+/// The address of the target is accepted in any form [`normalize_address`] understands.
+fn resolve_charac_of(
+    address: &str,
+    characteristics: impl IntoIterator<Item = Characteristic>,
+    target: &CharacteristicRef,
+) -> Result<Characteristic, Error> {
+    if let Some(requested) = &target.address {
+        if normalize_address(requested).as_deref() != Some(address) {
+            return Err(Error::UnknownPeripheral(requested.clone()));
+        }
+    }
+    find_charac(characteristics, target)
+}
+
+/// Every characteristic of the resolved services, see [`ConnectResult::characteristics`]
+pub(crate) fn characteristic_refs(
+    address: Option<&str>,
+    characteristics: impl IntoIterator<Item = Characteristic>,
+) -> Vec<CharacteristicRef> {
+    characteristics
+        .into_iter()
+        .map(|c| characteristic_ref(address, &c))
+        .collect()
+}
+
+/// The characteristics of [`ConnectResult::characteristics`]
+/// All of them for an empty list, otherwise each listed one resolved on the device at `address`.
+/// The refs carry `key`, the address of additional connections and none for the main one.
+/// # Errors
+/// Returns the error of the first listed characteristic that cannot be resolved
+pub(crate) fn select_characteristics(
+    address: &str,
+    key: Option<&str>,
+    characteristics: impl IntoIterator<Item = Characteristic>,
+    listed: &[CharacteristicRef],
+) -> Result<Vec<CharacteristicRef>, Error> {
+    let characteristics: Vec<Characteristic> = characteristics.into_iter().collect();
+    if listed.is_empty() {
+        return Ok(characteristic_refs(key, characteristics));
+    }
+    listed
+        .iter()
+        .map(|target| {
+            let charac = resolve_charac_of(address, characteristics.iter().cloned(), target)?;
+            Ok(characteristic_ref(key, &charac))
+        })
+        .collect()
+}
+
+fn characteristic_ref(address: Option<&str>, charac: &Characteristic) -> CharacteristicRef {
+    CharacteristicRef {
+        address: address.map(str::to_string),
+        service: Some(charac.service_uuid),
+        characteristic: charac.uuid,
+    }
+}
+
+/// Picks the characteristic of `target` out of the characteristics of a device
+fn find_charac(
+    characteristics: impl IntoIterator<Item = Characteristic>,
+    target: &CharacteristicRef,
+) -> Result<Characteristic, Error> {
+    let mut candidates: Vec<Characteristic> = characteristics
+        .into_iter()
+        .filter(|x| {
+            x.uuid == target.characteristic
+                && (target.service.is_none() || target.service == Some(x.service_uuid))
+        })
+        .collect();
+    match candidates.len() {
+        0 => Err(Error::CharacNotAvailable(target.characteristic.to_string())),
+        1 => Ok(candidates.remove(0)),
+        _ => Err(Error::AmbiguousCharacteristic(
+            candidates
+                .iter()
+                .map(|c| format!("{}/{}", c.service_uuid, c.uuid))
+                .collect(),
+        )),
+    }
+}
+
+pub(crate) fn resolve_descriptor(charac: &Characteristic, uuid: Uuid) -> Result<Descriptor, Error> {
+    charac
+        .descriptors
+        .iter()
+        .find(|d| d.uuid == uuid)
+        .cloned()
+        .ok_or_else(|| Error::DescriptorNotAvailable {
+            charac: charac.uuid.to_string(),
+            descriptor: uuid.to_string(),
+        })
+}
+
+/// Parses an address typed by the user into the form used as cache key
+/// Apple platforms identify devices by uuid, all others by MAC address.
+fn normalize_address(address: &str) -> Option<String> {
+    models::parse_address(address, AddressPolicy::Coerce).ok()
+}
+
+/// Writes and returns the ATT status of the response
+/// Only the android backend reports application errors as a status, btleplug fails the write.
+#[cfg(target_os = "android")]
+pub(crate) async fn write_with_status(
+    dev: &Peripheral,
+    charac: &Characteristic,
+    data: &[u8],
+    write_type: btleplug::api::WriteType,
+) -> Result<u8, btleplug::Error> {
+    dev.write_with_status(charac, data, write_type).await
+}
+
+/// Writes and returns the ATT status of the response
+/// Only the android backend reports application errors as a status, btleplug fails the write.
+#[cfg(not(target_os = "android"))]
+pub(crate) async fn write_with_status(
+    dev: &Peripheral,
+    charac: &Characteristic,
+    data: &[u8],
+    write_type: btleplug::api::WriteType,
+) -> Result<u8, btleplug::Error> {
+    dev.write(charac, data, write_type).await.map(|()| 0)
+}
+
+/// The platform errors for unsupported write types differ and some stacks silently drop the
+/// write, so the properties are checked before writing
+pub(crate) fn check_write(
+    charac: &Characteristic,
+    write_type: btleplug::api::WriteType,
+) -> Result<(), Error> {
+    let required = match write_type {
+        btleplug::api::WriteType::WithResponse => CharPropFlags::WRITE,
+        btleplug::api::WriteType::WithoutResponse => CharPropFlags::WRITE_WITHOUT_RESPONSE,
+    };
+    if charac.properties.contains(required) {
+        return Ok(());
+    }
+    Err(Error::WriteNotSupported {
+        charac: charac.uuid.to_string(),
+        write_type: format!("{write_type:?}"),
+        properties: format!("{:?}", charac.properties),
+    })
+}
+
+/// Some stacks accept subscribing to characteristics without notify or indicate and then never
+/// deliver data, so this is checked before writing the CCCD
+/// Characteristic to pass to the platform subscribe, only the flag of the kind to enable is
+/// kept so the android bridge writes the matching configuration value
+pub(crate) fn subscribe_target(
+    charac: &Characteristic,
+    mode: SubscribeMode,
+) -> Result<Characteristic, Error> {
+    check_notify(charac)?;
+    let flag = match mode {
+        SubscribeMode::Auto if charac.properties.contains(CharPropFlags::NOTIFY) => {
+            CharPropFlags::NOTIFY
+        }
+        SubscribeMode::Auto | SubscribeMode::Indicate => CharPropFlags::INDICATE,
+        SubscribeMode::Notify => CharPropFlags::NOTIFY,
+    };
+    if !charac.properties.contains(flag) {
+        return Err(Error::SubscribeModeNotSupported {
+            charac: charac.uuid.to_string(),
+            mode,
+            properties: format!("{:?}", charac.properties),
+        });
+    }
+    let mut target = charac.clone();
+    target
+        .properties
+        .remove((CharPropFlags::NOTIFY | CharPropFlags::INDICATE) - flag);
+    Ok(target)
+}
+
+fn check_notify(charac: &Characteristic) -> Result<(), Error> {
+    if charac
+        .properties
+        .intersects(CharPropFlags::NOTIFY | CharPropFlags::INDICATE)
+    {
+        return Ok(());
+    }
+    Err(Error::NotifyNotSupported {
+        charac: charac.uuid.to_string(),
+        properties: format!("{:?}", charac.properties),
+    })
+}
+
+fn enrich_device(
+    enricher: &DeviceEnricher,
+    address: &str,
+    properties: &PeripheralProperties,
+    publisher: &Publisher,
+) -> Option<serde_json::Value> {
+    let kind = CallbackKind::DeviceEnricher;
+    guard_callback(publisher, kind, address, &address, || enricher(properties)).flatten()
+}
+
+fn fingerprint_device(
+    fingerprint: &DeviceFingerprint,
+    address: &str,
+    properties: &PeripheralProperties,
+    publisher: &Publisher,
+) -> Option<String> {
+    let kind = CallbackKind::DeviceFingerprint;
+    guard_callback(publisher, kind, address, &address, || fingerprint(properties)).flatten()
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn listen_notify(
+    dev: Peripheral,
+    listeners: Arc<Mutex<Vec<Listener>>>,
+    sessions: Arc<Mutex<Vec<Session>>>,
+    interceptors: Arc<Mutex<Vec<Interceptor>>>,
+    activity: Arc<std::sync::Mutex<Instant>>,
+    publisher: Publisher,
+    clocks: Arc<Clocks>,
+    interference: Arc<InterferenceWatch>,
+) {
+    let address = models::peripheral_address(&dev);
+    let mut stream = dev
+        .notifications()
+        .await
+        .expect("failed to get notifications stream");
+    while let Some(data) = stream.next().await {
+        // taken first, the timestamps of all devices are compared
+        let received = Instant::now();
+        *activity.lock().expect("activity lock poisoned") = received;
+        interference.record_notification(received);
+        publisher
+            .timelines()
+            .record_notification(&address, data.uuid, data.value.len());
+        publisher.publish(BleEvent::Notification {
+            characteristic: data.uuid,
+            data: data.value.clone(),
+        });
+        let mut intercepted = false;
+        for i in interceptors.lock().await.iter() {
+            if i.uuid == data.uuid {
+                let _ = i.tx.send(data.value.clone());
+                intercepted |= !i.tee;
+            }
+        }
+        if intercepted {
+            continue;
+        }
+        // sessions are fed inline to keep arrival order across characteristics
+        let mut closed = false;
+        for s in sessions.lock().await.iter() {
+            if s.characs.contains(&data.uuid) {
+                let timestamp_us =
+                    u64::try_from(received.duration_since(s.opened).as_micros()).unwrap_or(u64::MAX);
+                closed |= s
+                    .tx
+                    .send(SessionFrame {
+                        characteristic: data.uuid,
+                        timestamp_us,
+                        data: data.value.clone(),
+                    })
+                    .is_err();
+            }
+        }
+        if closed {
+            prune_sessions(&dev, &listeners, &sessions).await;
+        }
+        // collected first, so inline callbacks can add or remove listeners
+        let sinks: Vec<ListenerSink> = listeners
+            .lock()
+            .await
+            .iter()
+            .filter(|l| l.uuid == data.uuid)
+            .map(|l| l.sink.clone())
+            .collect();
+        for sink in sinks {
+            deliver(&publisher, &clocks, sink, &address, received, data.uuid, &data.value);
+        }
+    }
+}
+
+/// What removing listeners leaves of the GATT subscription of their characteristic
+#[derive(Debug, PartialEq, Eq)]
+enum Release {
+    /// No such listener
+    Unknown,
+    /// Another listener or a session still uses the subscription
+    Held,
+    /// The last user is gone, the characteristic can be unsubscribed
+    Unsubscribe(Uuid),
+}
+
+/// Whether a listener or a session still uses the GATT subscription of `uuid`
+fn subscription_held(listeners: &[Listener], sessions: &[Session], uuid: Uuid) -> bool {
+    listeners.iter().any(|l| l.uuid == uuid) || sessions.iter().any(|s| s.characs.contains(&uuid))
+}
+
+/// Removes the listener of [`Handler::remove_listener`]
+fn release_listener(
+    listeners: &mut Vec<Listener>,
+    sessions: &[Session],
+    id: ListenerHandle,
+) -> Release {
+    let Some(pos) = listeners.iter().position(|l| l.id == id) else {
+        return Release::Unknown;
+    };
+    let uuid = listeners.remove(pos).uuid;
+    if subscription_held(listeners, sessions, uuid) {
+        Release::Held
+    } else {
+        Release::Unsubscribe(uuid)
+    }
+}
+
+/// What [`Handler::unsubscribe`] does with the GATT subscription of `uuid` once all of its
+/// listeners are removed
+fn release_all(listeners: &[Listener], sessions: &[Session], uuid: Uuid) -> Release {
+    if !listeners.iter().any(|l| l.uuid == uuid) {
+        Release::Unknown
+    } else if subscription_held(&[], sessions, uuid) {
+        Release::Held
+    } else {
+        Release::Unsubscribe(uuid)
+    }
+}
+
+/// Characteristics whose [`CharacteristicOptions::requires_write`] is met by writing `data`
+/// to `written`
+fn required_writes_met(
+    options: &HashMap<Uuid, CharacteristicOptions>,
+    written: Uuid,
+    data: &[u8],
+) -> Vec<Uuid> {
+    options
+        .iter()
+        .filter(|(_, o)| {
+            o.requires_write
+                .as_ref()
+                .is_some_and(|r| r.characteristic == written && r.data == data)
+        })
+        .map(|(uuid, _)| *uuid)
+        .collect()
+}
+
+/// Enforces the [`RequiredWrite`] of `uuid` before it is subscribed, `write` does the write
+/// of [`RequiredWrite::auto`]
+async fn enforce_required_write<F: std::future::Future<Output = Result<(), Error>>>(
+    uuid: Uuid,
+    required: Option<RequiredWrite>,
+    done: &std::sync::Mutex<HashSet<Uuid>>,
+    write: impl FnOnce(RequiredWrite) -> F,
+) -> Result<(), Error> {
+    let Some(required) = required else {
+        return Ok(());
+    };
+    if done.lock().expect("required writes lock poisoned").contains(&uuid) {
+        return Ok(());
+    }
+    if !required.auto {
+        return Err(Error::SubscribeBeforeWrite {
+            charac: uuid.to_string(),
+            required: required.characteristic.to_string(),
+        });
+    }
+    debug!(
+        "writing {} to {} before subscribing to {uuid}",
+        Payload(&required.data),
+        required.characteristic
+    );
+    write(required).await?;
+    done.lock().expect("required writes lock poisoned").insert(uuid);
+    Ok(())
+}
+
+/// Removes the sessions whose [`SessionHandle`] was dropped without [`Handler::close_session`]
+/// and unsubscribes the characteristics no remaining session or listener uses
+async fn prune_sessions(
+    dev: &Peripheral,
+    listeners: &Mutex<Vec<Listener>>,
+    sessions: &Mutex<Vec<Session>>,
+) {
+    let (closed, held) = {
+        let mut sessions = sessions.lock().await;
+        if !sessions.iter().any(|s| s.tx.is_closed()) {
+            return;
+        }
+        let (closed, open): (Vec<Session>, Vec<Session>) = std::mem::take(&mut *sessions)
+            .into_iter()
+            .partition(|s| s.tx.is_closed());
+        *sessions = open;
+        let held: HashSet<Uuid> = sessions
+            .iter()
+            .flat_map(|s| s.characs.iter().copied())
+            .collect();
+        (closed, held)
+    };
+    let released: HashSet<Uuid> = {
+        let listeners = listeners.lock().await;
+        closed
+            .iter()
+            .flat_map(|s| s.characs.iter().copied())
+            .filter(|c| !held.contains(c) && !listeners.iter().any(|l| l.uuid == *c))
+            .collect()
+    };
+    debug!("dropped {} sessions without a receiver", closed.len());
+    let characs = dev.characteristics();
+    for c in released {
+        if let Some(charac) = characs.iter().find(|x| x.uuid == c) {
+            if let Err(e) = dev.unsubscribe(charac).await {
+                warn!("failed to unsubscribe {c} of a dropped session: {e}");
+            }
+        }
+    }
+}
+
+/// Hands a notification to a listener, a panicking callback does not end the notification task
+pub(crate) fn deliver(
+    publisher: &Publisher,
+    clocks: &Clocks,
+    sink: ListenerSink,
+    address: &str,
+    received: Instant,
+    uuid: Uuid,
+    data: &[u8],
+) {
+    match sink {
+        ListenerSink::Inline(cb) => {
+            guard_callback(publisher, CallbackKind::Listener, address, &uuid, || cb(data));
+        }
+        ListenerSink::BlockingPool(cb) => {
+            let data = data.to_vec();
+            let publisher = publisher.clone();
+            let address = address.to_string();
+            runtime::spawn_blocking(move || {
+                let kind = CallbackKind::Listener;
+                guard_callback(&publisher, kind, &address, &uuid, || cb(&data));
+            });
+        }
+        ListenerSink::Channel(tx) => {
+            if let Err(e) = tx.try_send(data.to_vec()) {
+                warn!("dropping notification of {uuid}: {e}");
+            }
+        }
+        ListenerSink::Timed(tx) => {
+            let notification = TimedNotification {
+                address: address.to_string(),
+                characteristic: uuid,
+                timestamp_us: clocks.timestamp_us(address, received),
+                data: data.to_vec(),
+            };
+            if let Err(e) = tx.try_send(notification) {
+                warn!("dropping notification of {uuid}: {e}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn readdresses(events: &[DeviceReaddressed]) -> Vec<(&str, &str)> {
+        events
+            .iter()
+            .map(|e| (e.old_address.as_str(), e.new_address.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn fingerprint_retires_the_replaced_address() {
+        let mut cache = FingerprintCache::default();
+        assert!(cache.merge(&[("AA", Some("k"))]).is_empty());
+        let events = cache.merge(&[("AA", Some("k")), ("BB", Some("k"))]);
+        assert_eq!(readdresses(&events), [("AA", "BB")]);
+        assert!(cache.is_retired("AA"));
+        // the stale handle of the old address is still reported by the platform
+        assert!(cache.merge(&[("AA", Some("k")), ("BB", Some("k"))]).is_empty());
+        assert!(cache.is_retired("AA"));
+    }
+
+    #[test]
+    fn fingerprint_revives_an_address_taken_over_by_another_device() {
+        let mut cache = FingerprintCache::default();
+        cache.merge(&[("AA", Some("k"))]);
+        cache.merge(&[("BB", Some("k"))]);
+        assert!(cache.is_retired("AA"));
+        assert!(cache.merge(&[("AA", Some("other")), ("BB", Some("k"))]).is_empty());
+        assert!(!cache.is_retired("AA"));
+        cache.merge(&[("CC", None)]);
+        cache.merge(&[("DD", Some("other"))]);
+        assert!(cache.is_retired("AA"));
+        cache.merge(&[("AA", None)]);
+        assert!(!cache.is_retired("AA"));
+    }
+
+    #[test]
+    fn fingerprint_follows_a_device_back_to_its_old_address() {
+        let mut cache = FingerprintCache::default();
+        cache.merge(&[("AA", Some("k"))]);
+        cache.merge(&[("BB", Some("k"))]);
+        let events = cache.merge(&[("AA", Some("k"))]);
+        assert_eq!(readdresses(&events), [("BB", "AA")]);
+        assert!(!cache.is_retired("AA"));
+        assert!(cache.is_retired("BB"));
+    }
+
+    fn device(rssi: Option<i16>) -> BleDevice {
+        BleDevice {
+            address: "AA:BB:CC:DD:EE:FF".to_string(),
+            name: "sensor".to_string(),
+            is_connected: false,
+            manufacturer_data: HashMap::new(),
+            services: vec![],
+            service_data: HashMap::new(),
+            device_class: None,
+            br_edr_capable: None,
+            rssi,
+            advertisement_count: None,
+            adv_interval_ms: None,
+            fingerprint: None,
+            extra: None,
+            previously_connected: false,
+            adapter_id: None,
+            adapters: vec![],
+            appearance: None,
+            appearance_name: None,
+        }
+    }
+
+    #[test]
+    fn failed_connect_keeps_the_state_of_other_connections() {
+        let mut states = ConnectionStates {
+            main: Some("AA".to_string()),
+            ..Default::default()
+        };
+        states.set("AA", ConnectionState::Ready);
+        assert_eq!(states.set("BB", ConnectionState::Connecting), ConnectionState::Disconnected);
+        states.set("BB", ConnectionState::Disconnected);
+        assert_eq!(states.main(), ConnectionState::Ready);
+        assert_eq!(states.get("BB"), ConnectionState::Disconnected);
+        assert_eq!(states.main.as_deref(), Some("AA"));
+    }
+
+    #[test]
+    fn disconnect_of_the_main_connection_clears_it() {
+        let mut states = ConnectionStates {
+            main: Some("AA".to_string()),
+            ..Default::default()
+        };
+        states.set("AA", ConnectionState::Ready);
+        states.set("BB", ConnectionState::Ready);
+        assert_eq!(states.set("AA", ConnectionState::Disconnected), ConnectionState::Ready);
+        assert_eq!(states.main, None);
+        assert_eq!(states.main(), ConnectionState::Disconnected);
+        assert_eq!(states.get("BB"), ConnectionState::Ready);
+    }
+
+    #[test]
+    fn small_rssi_changes_are_not_published() {
+        let published = device(Some(-60));
+        assert!(!changed_meaningfully(&published, &device(Some(-60))));
+        assert!(!changed_meaningfully(&published, &device(Some(-64))));
+        assert!(!changed_meaningfully(&published, &device(Some(-56))));
+        assert!(changed_meaningfully(&published, &device(Some(-65))));
+        assert!(changed_meaningfully(&published, &device(Some(-55))));
+        assert!(changed_meaningfully(&published, &device(None)));
+    }
+
+    #[test]
+    fn counters_are_not_published() {
+        let published = device(Some(-60));
+        let mut counted = device(Some(-61));
+        counted.advertisement_count = Some(12);
+        counted.adv_interval_ms = Some(100);
+        counted.adapters = vec![AdapterSighting {
+            adapter_id: "hci0".to_string(),
+            rssi: Some(-61),
+        }];
+        let mut sighted = published.clone();
+        sighted.adapters = vec![AdapterSighting {
+            adapter_id: "hci0".to_string(),
+            rssi: Some(-70),
+        }];
+        assert!(!changed_meaningfully(&sighted, &counted));
+        assert!(changed_meaningfully(&published, &counted));
+    }
+
+    #[test]
+    fn advertised_data_changes_are_published() {
+        let published = device(Some(-60));
+        let mut changed = device(Some(-60));
+        changed.manufacturer_data.insert(0x004c, vec![1]);
+        assert!(changed_meaningfully(&published, &changed));
+        let mut renamed = device(Some(-62));
+        renamed.name = "sensor 2".to_string();
+        assert!(changed_meaningfully(&published, &renamed));
+    }
+
+    #[test]
+    fn properties_cache_fetches_only_advertising_devices() {
+        let mut cache = PropertiesCache::default();
+        let mut advertisements = vec![0u64; 500];
+        let mut fetches = 0;
+        for _ in 0..50 {
+            for count in advertisements.iter_mut().take(10) {
+                *count += 1;
+            }
+            for (id, count) in advertisements.iter().enumerate() {
+                if cache.get(&id, *count).is_none() {
+                    fetches += 1;
+                    cache.insert(id, *count, Some(*count));
+                }
             }
         }
-        Ok(services)
+        assert_eq!(fetches, 990);
     }
 
-    /// Stops scanning for devices
-    /// # Errors
-    /// Returns an error if stopping the scan fails
-    pub async fn stop_scan(&self) -> Result<(), Error> {
-        self.adapter.stop_scan().await?;
-        if let Some(handle) = self.state.lock().await.scan_task.take() {
-            handle.abort();
+    /// Peripheral of a BlueZ cache, every properties call is a DBus round trip of `fetch_cost`
+    struct CachedPeripheral {
+        id: usize,
+        fetch_cost: Duration,
+        fetches: Arc<AtomicUsize>,
+    }
+
+    impl ScannedPeripheral for CachedPeripheral {
+        type Id = usize;
+        type Properties = usize;
+
+        fn scan_id(&self) -> usize {
+            self.id
+        }
+
+        fn label(&self) -> String {
+            self.id.to_string()
+        }
+
+        async fn fetch_properties(&self) -> btleplug::Result<Option<usize>> {
+            self.fetches.fetch_add(1, Ordering::SeqCst);
+            // blocks like the DBus call the scan tick waits for
+            std::thread::sleep(self.fetch_cost);
+            Ok(Some(self.id))
         }
-        self.send_scan_update(false).await;
-        Ok(())
     }
 
-    async fn add_devices(
-        self_devices: &mut Arc<Mutex<HashMap<String, Peripheral>>>,
-        discovered: Vec<Peripheral>,
-    ) -> Vec<BleDevice> {
-        let mut devices = vec![];
-        for p in discovered {
-            match BleDevice::from_peripheral(&p).await {
-                Ok(dev) => {
-                    self_devices.lock().await.insert(dev.address.clone(), p);
-                    devices.push(dev);
-                }
-                Err(e) => {
-                    warn!("Failed to add device: {e}");
-                }
+    /// Runs 50 scan ticks over 500 known peripherals of which 10 advertise, `cached` keeps
+    /// the cache across the ticks like a scan does. Returns the number of properties fetches.
+    async fn scan_ticks(cached: bool, fetch_cost: Duration) -> usize {
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let mut advertisements = vec![0u64; 500];
+        let mut cache = PropertiesCache::default();
+        for _ in 0..50 {
+            for count in advertisements.iter_mut().take(10) {
+                *count += 1;
             }
+            if !cached {
+                cache = PropertiesCache::default();
+            }
+            let discovered = (0..advertisements.len())
+                .map(|id| CachedPeripheral {
+                    id,
+                    fetch_cost,
+                    fetches: fetches.clone(),
+                })
+                .collect();
+            let tick =
+                peripheral_properties(discovered, &mut cache, |p| advertisements[p.id]).await;
+            assert_eq!(tick.len(), advertisements.len());
+            assert!(tick.iter().all(|(p, properties)| *properties == Some(p.id)));
         }
-        devices.sort();
-        devices
+        fetches.load(Ordering::SeqCst)
     }
 
-    /// Sends data to the given characteristic of the connected device
-    /// # Errors
-    /// Returns an error if no device is connected or the characteristic is not available
-    /// or if the write operation fails
-    /// # Example
-    /// ```no_run
-    /// use tauri::async_runtime;
-    /// use uuid::{Uuid,uuid};
-    /// use tauri_plugin_blec::models::WriteType;
-    ///
-    /// const CHARACTERISTIC_UUID: Uuid = uuid!("51FF12BB-3ED8-46E5-B4F9-D64E2FEC021B");
-    /// async_runtime::block_on(async {
-    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
-    ///     let data = [1,2,3,4,5];
-    ///     let response = handler.send_data(CHARACTERISTIC_UUID,&data, WriteType::WithResponse).await.unwrap();
-    /// });
-    /// ```
-    pub async fn send_data(
-        &self,
-        c: Uuid,
-        data: &[u8],
-        write_type: models::WriteType,
-    ) -> Result<(), Error> {
-        let dev = self.connected_dev.lock().await;
-        let dev = dev.as_ref().ok_or(Error::NoDeviceConnected)?;
-        if let Some(charac) = dev.characteristics().iter().find(|x| x.uuid == c) {
-            dev.write(charac, data, write_type.into()).await?;
-            Ok(())
-        } else {
-            Err(Error::CharacNotAvailable(c.into()))
-        }
+    #[test]
+    fn critical_operations_end_by_id_or_with_their_guard() {
+        let operations = Arc::new(CriticalOperations::default());
+        let update = operations.start("firmware update");
+        let guard = CriticalOperation {
+            id: operations.start("calibration"),
+            operations: operations.clone(),
+        };
+        let mut names = operations.names();
+        names.sort();
+        assert_eq!(names, ["calibration", "firmware update"]);
+        operations.end(update);
+        assert_eq!(operations.names(), ["calibration"]);
+        drop(guard);
+        assert!(operations.names().is_empty());
     }
 
-    /// Receives data from the given characteristic of the connected device
-    /// Returns the data as a vector of bytes
-    /// # Errors
-    /// Returns an error if no device is connected or the characteristic is not available
-    /// or if the read operation fails
-    /// # Example
-    /// ```no_run
-    /// use tauri::async_runtime;
-    /// use uuid::{Uuid,uuid};
-    /// const CHARACTERISTIC_UUID: Uuid = uuid!("51FF12BB-3ED8-46E5-B4F9-D64E2FEC021B");
-    /// async_runtime::block_on(async {
-    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
-    ///     let response = handler.recv_data(CHARACTERISTIC_UUID).await.unwrap();
-    /// });
-    /// ```
-    pub async fn recv_data(&self, c: Uuid) -> Result<Vec<u8>, Error> {
-        let dev = self.connected_dev.lock().await;
-        let dev = dev.as_ref().ok_or(Error::NoDeviceConnected)?;
-        
-        if let Some(charac) = dev.characteristics().iter().find(|x| x.uuid == c) {
-            let data = dev.read(charac).await?;
-            Ok(data)
-        } else {
-            Err(Error::CharacNotAvailable(c.into()))
+    #[tokio::test(start_paused = true)]
+    async fn waiting_for_critical_operations_ends_with_the_last_one() {
+        let operations = Arc::new(CriticalOperations::default());
+        let id = operations.start("firmware update");
+        assert!(!operations.wait(Duration::from_secs(1)).await);
+        let ending = operations.clone();
+        tokio::spawn(async move {
+            sleep(Duration::from_secs(2)).await;
+            ending.end(id);
+        });
+        assert!(operations.wait(Duration::from_secs(5)).await);
+    }
+
+    #[tokio::test]
+    async fn scan_ticks_fetch_only_the_advertising_peripherals() {
+        assert_eq!(scan_ticks(false, Duration::ZERO).await, 25_000);
+        assert_eq!(scan_ticks(true, Duration::ZERO).await, 990);
+    }
+
+    /// Time of the scan ticks with and without the cache, run with
+    /// `cargo test --release scan_properties_throughput -- --ignored --nocapture`
+    #[tokio::test]
+    #[ignore = "benchmark"]
+    async fn scan_properties_throughput() {
+        // a DBus round trip to BlueZ on an idle system
+        const FETCH_COST: Duration = Duration::from_micros(100);
+        for cached in [false, true] {
+            let start = Instant::now();
+            let fetches = scan_ticks(cached, FETCH_COST).await;
+            let elapsed = start.elapsed();
+            println!(
+                "cached: {cached}, {fetches} fetches, {:.1}ms per tick",
+                elapsed.as_secs_f64() * 1000.0 / 50.0
+            );
         }
     }
 
-    /// Subscribe to notifications from the given characteristic
-    /// The callback will be called whenever a notification is received
-    /// # Errors
-    /// Returns an error if no device is connected or the characteristic is not available
-    /// or if the subscribe operation fails
-    /// # Example
-    /// ```no_run
-    /// use tauri::async_runtime;
-    /// use uuid::{Uuid,uuid};
-    /// const CHARACTERISTIC_UUID: Uuid = uuid!("51FF12BB-3ED8-46E5-B4F9-D64E2FEC021B");
-    /// async_runtime::block_on(async {
-    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
-    ///     let response = handler.subscribe(CHARACTERISTIC_UUID,|data| println!("received {data:?}")).await.unwrap();
-    /// });
-    /// ```
-    pub async fn subscribe(
-        &self,
-        c: Uuid,
-        callback: impl Fn(&[u8]) + Send + Sync + 'static,
-    ) -> Result<(), Error> {
-        let dev = self.connected_dev.lock().await;
-        let dev = dev.as_ref().ok_or(Error::NoDeviceConnected)?;
-        if let Some(charac) = dev.characteristics().iter().find(|x| x.uuid == c) {
-            dev.subscribe(charac).await?;
-            self.notify_listeners.lock().await.push(Listener {
-                uuid: charac.uuid,
-                callback: Arc::new(callback),
-            });
-            Ok(())
-        } else {
-            Err(Error::CharacNotAvailable(c.into()))
+    #[test]
+    fn properties_cache_returns_the_latest_fetch() {
+        let mut cache = PropertiesCache::default();
+        cache.insert("AA", 1, Some("first"));
+        assert_eq!(cache.get(&"AA", 1), Some(Some("first")));
+        assert_eq!(cache.get(&"AA", 2), None);
+        cache.insert("AA", 2, None);
+        assert_eq!(cache.get(&"AA", 2), Some(None));
+        assert_eq!(cache.get(&"BB", 0), None);
+    }
+
+    #[test]
+    fn panicking_listener_does_not_stop_the_delivery() {
+        let events = Publisher::new();
+        let mut rx = events.subscribe();
+        let received = Arc::new(std::sync::Mutex::new(vec![]));
+        fn panicking_listener(_: &[u8]) {
+            panic!("listener failed");
+        }
+        let panicking: ListenerCallback = Arc::new(panicking_listener);
+        let recording: ListenerCallback = {
+            let received = received.clone();
+            Arc::new(move |data: &[u8]| received.lock().unwrap().push(data.to_vec()))
+        };
+        let uuid = Uuid::from_u128(1);
+        let clocks = Clocks::default();
+        for _ in 0..2 {
+            let now = Instant::now();
+            let panicking = ListenerSink::Inline(panicking.clone());
+            deliver(&events, &clocks, panicking, "AA", now, uuid, &[1]);
+            let recording = ListenerSink::Inline(recording.clone());
+            deliver(&events, &clocks, recording, "AA", now, uuid, &[2]);
+        }
+        assert_eq!(*received.lock().unwrap(), vec![vec![2], vec![2]]);
+        for _ in 0..2 {
+            let Ok(BleEvent::CallbackPanicked(panic)) = rx.try_recv() else {
+                panic!("expected a callback panic");
+            };
+            assert_eq!(panic.callback, CallbackKind::Listener);
+            assert_eq!(panic.context, uuid.to_string());
         }
     }
 
-    /// Unsubscribe from notifications for the given characteristic
-    /// This will also remove the callback from the list of listeners
-    /// # Errors
-    /// Returns an error if no device is connected or the characteristic is not available
-    /// or if the unsubscribe operation fails
-    pub async fn unsubscribe(&self, c: Uuid) -> Result<(), Error> {
-        let dev = self.connected_dev.lock().await;
-        let dev = dev.as_ref().ok_or(Error::NoDeviceConnected)?;
-        if let Some(charac) = dev.characteristics().iter().find(|x| x.uuid == c) {
-            dev.unsubscribe(charac).await?;
-            let mut listeners = self.notify_listeners.lock().await;
-            listeners.retain(|l| l.uuid != charac.uuid);
-            Ok(())
-        } else {
-            Err(Error::CharacNotAvailable(c.into()))
+    #[test]
+    fn panicking_enricher_and_fingerprint_yield_nothing() {
+        let events = Publisher::new();
+        let mut rx = events.subscribe();
+        let properties = PeripheralProperties::default();
+        fn panicking_enricher(_: &PeripheralProperties) -> Option<serde_json::Value> {
+            panic!("enricher failed");
         }
+        fn panicking_fingerprint(_: &PeripheralProperties) -> Option<String> {
+            panic!("fingerprint failed");
+        }
+        let enricher: DeviceEnricher = Arc::new(panicking_enricher);
+        let fingerprint: DeviceFingerprint = Arc::new(panicking_fingerprint);
+        assert_eq!(enrich_device(&enricher, "AA", &properties, &events), None);
+        assert_eq!(fingerprint_device(&fingerprint, "AA", &properties, &events), None);
+        let kinds: Vec<_> = std::iter::from_fn(|| rx.try_recv().ok())
+            .map(|event| match event {
+                BleEvent::CallbackPanicked(panic) => panic.callback,
+                other => panic!("unexpected event {other:?}"),
+            })
+            .collect();
+        assert_eq!(kinds, [CallbackKind::DeviceEnricher, CallbackKind::DeviceFingerprint]);
     }
 
-    pub(super) async fn get_event_stream(
-        &self,
-    ) -> Result<Pin<Box<dyn Stream<Item = CentralEvent> + Send>>, Error> {
-        let events = self.adapter.events().await?;
-        Ok(events)
+    fn gatt_error(status: u8) -> btleplug::Error {
+        btleplug::Error::Other(Box::new(GattStatus {
+            status,
+            message: format!("Read from characteristic failed with status {status}"),
+        }))
     }
 
-    pub(crate) async fn handle_event(&self, event: CentralEvent) -> Result<(), Error> {
-        match event {
-            CentralEvent::DeviceDisconnected(peripheral_id) => {
-                self.handle_disconnect(peripheral_id).await?;
-            }
-            CentralEvent::DeviceConnected(peripheral_id) => {
-                self.handle_connect(peripheral_id).await;
-            }
+    fn bluez_error(code: &str) -> btleplug::Error {
+        btleplug::Error::RuntimeError(format!(
+            "org.bluez.Error.Failed: Operation failed with ATT error: {code}"
+        ))
+    }
 
-            _event => {}
+    #[test]
+    fn auth_failures_match_the_exact_status() {
+        for status in [0x05, 0x0f, 0x89] {
+            assert!(is_auth_failure(&gatt_error(status)), "status {status}");
         }
-        Ok(())
+        // 50 to 59 and 150 start with the digits of the auth statuses
+        for status in [0, 8, 50, 59, 150] {
+            assert!(!is_auth_failure(&gatt_error(status)), "status {status}");
+        }
+        assert!(is_auth_failure(&bluez_error("0x05")));
+        assert!(is_auth_failure(&bluez_error("0x0f")));
+        assert!(!is_auth_failure(&bluez_error("0x50")));
     }
 
-    /// Returns the connected device
-    /// # Errors
-    /// Returns an error if no device is connected
-    pub async fn connected_device(&self) -> Result<BleDevice, Error> {
-        let p = self.connected_dev.lock().await;
-        let p = p.as_ref().ok_or(Error::NoDeviceConnected)?;
-        let d = BleDevice::from_peripheral(p).await?;
-        Ok(d)
+    #[test]
+    fn auth_failures_without_a_status_match_the_error_name() {
+        let error = |msg: &str| btleplug::Error::RuntimeError(msg.to_string());
+        assert!(is_auth_failure(&error("ProtocolError: InsufficientEncryption")));
+        assert!(is_auth_failure(&error("Insufficient Authentication")));
+        assert!(!is_auth_failure(&error("encryption key size changed")));
+        assert!(!is_auth_failure(&error("authentication timed out, status 5x")));
     }
 
-    #[allow(clippy::redundant_closure_for_method_calls)]
-    async fn handle_connect(&self, peripheral_id: PeripheralId) {
-        let connected_device = self.connected_dev.lock().await.as_ref().map(|d| d.id());
-        if let Some(connected_device) = connected_device {
-            if connected_device == peripheral_id {
-                debug!("connection to {peripheral_id} established");
-                self.connected_tx
-                    .send(true)
-                    .expect("failed to send connected update");
-            } else {
-                // event not for currently connected device, ignore
-                debug!("Unexpected connect event for device {peripheral_id}, connected device is {connected_device}");
-            }
-        } else {
-            debug!(
-                "connect event for device {peripheral_id} received without waiting for connection"
-            );
+    #[test]
+    fn transient_errors_match_the_exact_status() {
+        assert_eq!(
+            TransientError::classify(&gatt_error(0x11)),
+            Some(TransientError::InsufficientResources)
+        );
+        assert_eq!(
+            TransientError::classify(&gatt_error(0x8f)),
+            Some(TransientError::Congested)
+        );
+        // 170 to 179 start with the digits of 17
+        for status in [1, 14, 170, 179] {
+            assert_eq!(TransientError::classify(&gatt_error(status)), None, "status {status}");
         }
+        assert_eq!(
+            TransientError::classify(&bluez_error("0x11")),
+            Some(TransientError::InsufficientResources)
+        );
+        assert_eq!(
+            TransientError::classify(&btleplug::Error::RuntimeError("link congested".into())),
+            Some(TransientError::Congested)
+        );
     }
 
-    async fn send_connection_update(&self, state: bool) {
-        let tx = &mut self.state.lock().await.connection_update_channel;
-        let mut remove = vec![];
-        for (i, t) in tx.iter_mut().enumerate() {
-            if let Err(e) = t.send(state).await {
-                warn!("Failed to send connection update: {e}");
-                remove.push(i);
+    /// Device answering the connects of an idle reconnect
+    #[derive(Default)]
+    struct MockConnect {
+        connected: AtomicBool,
+        connects: AtomicUsize,
+        failing: AtomicBool,
+    }
+
+    impl IdleConnect for Arc<MockConnect> {
+        fn is_connected(&self, _address: &str) -> bool {
+            self.connected.load(Ordering::SeqCst)
+        }
+
+        async fn connect(&self, _address: &str, _options: ConnectOptions) -> Result<(), Error> {
+            self.connects.fetch_add(1, Ordering::SeqCst);
+            sleep(Duration::from_millis(10)).await;
+            if self.failing.load(Ordering::SeqCst) {
+                return Err(Error::ConnectionFailed);
             }
+            self.connected.store(true, Ordering::SeqCst);
+            Ok(())
         }
     }
 
-    async fn send_scan_update(&self, state: bool) {
-        let tx = &mut self.state.lock().await.scan_update_channel;
-        let mut remove = vec![];
-        for (i, t) in tx.iter_mut().enumerate() {
-            if let Err(e) = t.send(state).await {
-                warn!("Failed to send scan update: {e}");
-                remove.push(i);
-            }
+    type MockPending = std::sync::Mutex<Option<IdleReconnect<Arc<MockConnect>>>>;
+
+    fn pending(device: &Arc<MockConnect>) -> MockPending {
+        std::sync::Mutex::new(Some(IdleReconnect {
+            handler: device.clone(),
+            address: "00:11:22:33:44:55".to_string(),
+            options: ConnectOptions::default(),
+        }))
+    }
+
+    #[tokio::test]
+    async fn idle_reconnect_does_nothing_without_a_pending_reconnect() {
+        let pending: MockPending = std::sync::Mutex::new(None);
+        let running = Mutex::new(());
+        // a held lock shows the operation does not wait for it
+        let _held = running.lock().await;
+        reconnect_pending(&pending, &running).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn idle_reconnect_connects_once() {
+        let device = Arc::new(MockConnect::default());
+        let pending = pending(&device);
+        let running = Mutex::new(());
+        reconnect_pending(&pending, &running).await.unwrap();
+        reconnect_pending(&pending, &running).await.unwrap();
+        assert_eq!(device.connects.load(Ordering::SeqCst), 1);
+        assert!(pending.lock().unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn concurrent_operations_share_the_idle_reconnect() {
+        let device = Arc::new(MockConnect::default());
+        let pending = pending(&device);
+        let running = Mutex::new(());
+        let (first, second) = tokio::join!(
+            reconnect_pending(&pending, &running),
+            reconnect_pending(&pending, &running)
+        );
+        first.unwrap();
+        second.unwrap();
+        assert_eq!(device.connects.load(Ordering::SeqCst), 1);
+        assert!(device.connected.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn failed_idle_reconnect_stays_pending() {
+        let device = Arc::new(MockConnect::default());
+        device.failing.store(true, Ordering::SeqCst);
+        let pending = pending(&device);
+        let running = Mutex::new(());
+        assert!(matches!(
+            reconnect_pending(&pending, &running).await,
+            Err(Error::ConnectionFailed)
+        ));
+        assert!(pending.lock().unwrap().is_some());
+        device.failing.store(false, Ordering::SeqCst);
+        reconnect_pending(&pending, &running).await.unwrap();
+        assert_eq!(device.connects.load(Ordering::SeqCst), 2);
+        assert!(pending.lock().unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn idle_reconnect_is_dropped_once_connected_again() {
+        let device = Arc::new(MockConnect::default());
+        device.connected.store(true, Ordering::SeqCst);
+        let pending = pending(&device);
+        reconnect_pending(&pending, &Mutex::new(())).await.unwrap();
+        assert_eq!(device.connects.load(Ordering::SeqCst), 0);
+        assert!(pending.lock().unwrap().is_none());
+    }
+
+    const HEART_RATE: Uuid = Uuid::from_u128(0x0000_180d_0000_1000_8000_0080_5f9b_34fb);
+    const BATTERY: Uuid = Uuid::from_u128(0x0000_180f_0000_1000_8000_0080_5f9b_34fb);
+
+    #[test]
+    fn scans_cover_the_services_of_the_scan_consumers() {
+        let filter = ScanFilter::Service(HEART_RATE);
+        assert_eq!(covering_filter(&filter, None).services, [HEART_RATE]);
+        let covered = covering_filter(&filter, Some(&[BATTERY, HEART_RATE]));
+        let mut expected = vec![HEART_RATE, BATTERY];
+        expected.sort();
+        assert_eq!(covered.services, expected);
+        // a consumer or the scan wanting all devices unfilters the adapters
+        assert!(covering_filter(&filter, Some(&[])).services.is_empty());
+        assert!(covering_filter(&ScanFilter::None, Some(&[BATTERY])).services.is_empty());
+    }
+
+    #[test]
+    fn consumer_services_match_without_passing_the_scan_filter() {
+        let battery = PeripheralProperties {
+            services: vec![BATTERY],
+            ..Default::default()
+        };
+        assert!(!matches_filter(&ScanFilter::Service(HEART_RATE), Some(&battery)));
+        assert!(matches_filter(&ScanFilter::AnyService(vec![BATTERY]), Some(&battery)));
+        assert!(matches_filter(&ScanFilter::AnyService(vec![]), None));
+        let both = ScanFilter::AllServices(vec![BATTERY, HEART_RATE]);
+        assert!(!matches_filter(&both, Some(&battery)));
+    }
+
+    /// Queues operations that record their id once they ran for `duration`, like the data
+    /// operations of the handler that check the gate once they hold the device
+    fn spawn_ops(
+        queue: &Arc<Mutex<Vec<u32>>>,
+        gate: &Arc<DisconnectGate>,
+        ids: std::ops::Range<u32>,
+        duration: Duration,
+    ) -> Vec<tokio::task::JoinHandle<Result<(), Error>>> {
+        ids.map(|id| {
+            let (queue, gate) = (queue.clone(), gate.clone());
+            tokio::spawn(async move {
+                let mut ran = queue.lock().await;
+                gate.check()?;
+                sleep(duration).await;
+                ran.push(id);
+                Ok(())
+            })
+        })
+        .collect()
+    }
+
+    async fn results(
+        ops: Vec<tokio::task::JoinHandle<Result<(), Error>>>,
+    ) -> Vec<Result<(), Error>> {
+        let mut results = vec![];
+        for op in ops {
+            results.push(op.await.unwrap());
         }
+        results
     }
-}
 
-async fn filter_peripherals(discovered: &mut Vec<Peripheral>, filter: &ScanFilter) {
-    if matches!(filter, ScanFilter::None) {
-        return;
+    #[tokio::test(start_paused = true)]
+    async fn flushing_disconnect_runs_the_queued_operations() {
+        let queue = Arc::new(Mutex::new(vec![]));
+        let gate = Arc::new(DisconnectGate::default());
+        // longer than the in-flight timeout together, a flush waits for all of them
+        let ops = spawn_ops(&queue, &gate, 0..3, Duration::from_secs(2));
+        sleep(Duration::from_millis(1)).await;
+        let ran = gate.drain(&queue, true, IN_FLIGHT_TIMEOUT).await.unwrap();
+        assert_eq!(*ran, vec![0, 1, 2]);
+        let late = spawn_ops(&queue, &gate, 3..4, Duration::ZERO);
+        drop(ran);
+        assert!(results(ops).await.iter().all(Result::is_ok));
+        assert!(matches!(results(late).await[..], [Err(Error::Disconnected)]));
     }
-    let mut remove = vec![];
-    for p in discovered.iter().enumerate() {
-        let Ok(Some(properties)) = p.1.properties().await else {
-            // can't filter without properties
-            remove.push(p.0);
-            continue;
+
+    #[tokio::test(start_paused = true)]
+    async fn disconnect_fails_the_queued_operations() {
+        let queue = Arc::new(Mutex::new(vec![]));
+        let gate = Arc::new(DisconnectGate::default());
+        let ops = spawn_ops(&queue, &gate, 0..3, Duration::from_secs(1));
+        sleep(Duration::from_millis(1)).await;
+        let ran = gate.drain(&queue, false, IN_FLIGHT_TIMEOUT).await.unwrap();
+        // only the running operation finished
+        assert_eq!(*ran, vec![0]);
+        drop(ran);
+        assert!(matches!(
+            results(ops).await[..],
+            [Ok(()), Err(Error::Disconnected), Err(Error::Disconnected)]
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn disconnect_waits_for_the_running_operation_at_most_the_in_flight_timeout() {
+        let queue = Arc::new(Mutex::new(vec![]));
+        let gate = Arc::new(DisconnectGate::default());
+        let ops = spawn_ops(&queue, &gate, 0..2, Duration::from_secs(60));
+        sleep(Duration::from_millis(1)).await;
+        let start = tokio::time::Instant::now();
+        assert!(gate.drain(&queue, false, IN_FLIGHT_TIMEOUT).await.is_none());
+        assert_eq!(start.elapsed(), IN_FLIGHT_TIMEOUT);
+        // the queued operation still fails once the running one finished
+        assert!(matches!(results(ops).await[..], [Ok(()), Err(Error::Disconnected)]));
+        assert!(gate.open());
+    }
+
+    #[tokio::test]
+    async fn operations_run_again_once_the_gate_is_opened() {
+        let queue = Mutex::new(());
+        let gate = DisconnectGate::default();
+        drop(gate.drain(&queue, true, IN_FLIGHT_TIMEOUT).await);
+        assert!(matches!(gate.check(), Err(Error::Disconnected)));
+        assert!(gate.open());
+        gate.check().unwrap();
+        // nothing was requested since
+        assert!(!gate.open());
+    }
+
+    /// Runs a scripted appearance pattern of one device through the removal debounce, one
+    /// scan poll per second, and returns the second its removal was reported
+    fn removed_at(seen: &[bool], debounce: Duration) -> Option<usize> {
+        let mut missing = MissingDevices::default();
+        let start = Instant::now();
+        seen.iter().enumerate().find_map(|(second, seen)| {
+            let now = start + Duration::from_secs(second as u64);
+            (!missing.keep(&"device", *seen, debounce, now)).then_some(second)
+        })
+    }
+
+    #[test]
+    fn device_is_removed_once_unseen_for_the_debounce() {
+        let pattern = [true, true, false, false, false, false, false];
+        assert_eq!(removed_at(&pattern, Duration::from_secs(3)), Some(5));
+        assert_eq!(removed_at(&pattern, Duration::ZERO), Some(2));
+        assert_eq!(removed_at(&pattern, Duration::from_secs(10)), None);
+    }
+
+    #[test]
+    fn flapping_device_is_never_removed() {
+        // appears and disappears every couple of seconds at the edge of range
+        let pattern = [true, false, false, true, false, true, false, false, true, false, true];
+        assert_eq!(removed_at(&pattern, Duration::from_secs(3)), None);
+        assert_eq!(removed_at(&pattern, Duration::from_secs(1)), Some(2));
+    }
+
+    #[test]
+    fn reappearing_restarts_the_debounce() {
+        let pattern = [true, false, false, true, false, false, false, false];
+        // unseen for two seconds before it reappeared, then removed three seconds after
+        assert_eq!(removed_at(&pattern, Duration::from_secs(3)), Some(7));
+    }
+
+    #[test]
+    fn removal_is_tracked_per_device() {
+        let mut missing = MissingDevices::default();
+        let start = Instant::now();
+        let debounce = Duration::from_secs(2);
+        assert!(missing.keep(&"gone", false, debounce, start));
+        assert!(missing.keep(&"flapping", false, debounce, start + Duration::from_secs(1)));
+        let later = start + Duration::from_secs(2);
+        assert!(!missing.keep(&"gone", false, debounce, later));
+        assert!(missing.keep(&"flapping", false, debounce, later));
+        assert!(missing.keep(&"flapping", true, debounce, later));
+        // a removed device reported again starts over
+        assert!(missing.keep(&"gone", false, debounce, later));
+    }
+
+    const BATTERY_LEVEL: Uuid = Uuid::from_u128(0x0000_2a19_0000_1000_8000_0080_5f9b_34fb);
+    const MEASUREMENT: Uuid = Uuid::from_u128(0x0000_2a37_0000_1000_8000_0080_5f9b_34fb);
+    const SENSOR_LOCATION: Uuid = Uuid::from_u128(0x0000_2a38_0000_1000_8000_0080_5f9b_34fb);
+
+    /// Battery level offered by both services, like devices reporting it next to their data
+    fn gatt() -> Vec<Characteristic> {
+        [(BATTERY, BATTERY_LEVEL), (HEART_RATE, MEASUREMENT), (HEART_RATE, BATTERY_LEVEL)]
+            .into_iter()
+            .map(|(service_uuid, uuid)| Characteristic {
+                uuid,
+                service_uuid,
+                properties: CharPropFlags::READ | CharPropFlags::NOTIFY,
+                descriptors: Default::default(),
+            })
+            .collect()
+    }
+
+    const MAIN: &str = "AA:BB:CC:DD:EE:FF";
+
+    #[test]
+    fn connect_without_characteristics_selects_all_of_them() {
+        // nothing to check for an empty ConnectOptions::characteristics
+        let selected = select_characteristics(MAIN, None, gatt(), &[]).unwrap();
+        assert_eq!(selected, characteristic_refs(None, gatt()));
+        assert_eq!(selected.len(), 3);
+        for target in &selected {
+            let charac = find_charac(gatt(), target).unwrap();
+            assert_eq!(Some(charac.service_uuid), target.service);
+            assert_eq!(charac.uuid, target.characteristic);
+        }
+        assert!(characteristic_refs(None, vec![]).is_empty());
+    }
+
+    #[test]
+    fn connect_with_a_partial_list_checks_only_the_listed_ones() {
+        let partial = [
+            CharacteristicRef::from(MEASUREMENT),
+            CharacteristicRef {
+                address: None,
+                service: Some(BATTERY),
+                characteristic: BATTERY_LEVEL,
+            },
+        ];
+        let selected = select_characteristics(MAIN, None, gatt(), &partial).unwrap();
+        // only the listed characteristics, resolved to their service
+        assert_eq!(
+            selected,
+            [
+                CharacteristicRef {
+                    address: None,
+                    service: Some(HEART_RATE),
+                    characteristic: MEASUREMENT,
+                },
+                partial[1].clone(),
+            ]
+        );
+        assert!(matches!(
+            select_characteristics(MAIN, None, gatt(), &[SENSOR_LOCATION.into()]),
+            Err(Error::CharacNotAvailable(_))
+        ));
+        assert!(matches!(
+            select_characteristics(MAIN, None, gatt(), &[BATTERY_LEVEL.into()]),
+            Err(Error::AmbiguousCharacteristic(candidates)) if candidates.len() == 2
+        ));
+    }
+
+    #[cfg(not(target_vendor = "apple"))]
+    #[test]
+    fn characteristics_are_resolved_with_any_form_of_the_address() {
+        let target = |address: &str| CharacteristicRef {
+            address: Some(address.to_string()),
+            service: Some(HEART_RATE),
+            characteristic: MEASUREMENT,
         };
-        match filter {
-            ScanFilter::None => unreachable!("Earyl return for no filter"),
-            ScanFilter::Service(uuid) => {
-                if !properties.services.iter().any(|s| s == uuid) {
-                    remove.push(p.0);
-                }
-            }
-            ScanFilter::AnyService(uuids) => {
-                if !properties.services.iter().any(|s| uuids.contains(s)) {
-                    remove.push(p.0);
-                }
-            }
-            ScanFilter::AllServices(uuids) => {
-                if !uuids.iter().all(|s| properties.services.contains(s)) {
-                    remove.push(p.0);
-                }
-            }
-            ScanFilter::ManufacturerData(key, value) => {
-                if !properties
-                    .manufacturer_data
-                    .get(key)
-                    .is_some_and(|v| v == value)
-                {
-                    remove.push(p.0);
-                }
-            }
+        let charac = resolve_charac_of(MAIN, gatt(), &target("aa-bb-cc-dd-ee-ff")).unwrap();
+        assert_eq!(charac.uuid, MEASUREMENT);
+        resolve_charac_of(MAIN, gatt(), &target(MAIN)).unwrap();
+        assert!(matches!(
+            resolve_charac_of(MAIN, gatt(), &target("11-22-33-44-55-66")),
+            Err(Error::UnknownPeripheral(address)) if address == "11-22-33-44-55-66"
+        ));
+    }
+
+    #[test]
+    fn additional_connections_select_their_characteristics_by_address() {
+        let selected = characteristic_refs(Some("AA:BB:CC:DD:EE:FF"), gatt());
+        assert!(selected.iter().all(|c| c.address.as_deref() == Some("AA:BB:CC:DD:EE:FF")));
+    }
+
+    fn listener(id: u64, uuid: Uuid) -> Listener {
+        Listener {
+            id: ListenerHandle(id),
+            uuid,
+            sink: ListenerSink::Channel(mpsc::channel(1).0),
         }
     }
 
-    for i in remove.iter().rev() {
-        discovered.swap_remove(*i);
+    fn session(characs: Vec<Uuid>) -> Session {
+        Session {
+            id: 1,
+            characs,
+            opened: Instant::now(),
+            tx: mpsc::unbounded_channel().0,
+        }
     }
-}
 
-async fn listen_notify(dev: Option<Peripheral>, listeners: Arc<Mutex<Vec<Listener>>>) {
-    let mut stream = dev
-        .expect("no device connected")
-        .notifications()
-        .await
-        .expect("failed to get notifications stream");
-    while let Some(data) = stream.next().await {
-        for l in listeners.lock().await.iter() {
-            info!("listener.uuid: {:?}", l.uuid);
-            if l.uuid == data.uuid {
-                let data = data.value.clone();
-                let cb = l.callback.clone();
-                async_runtime::spawn_blocking(move || cb(&data));
-            }
+    #[test]
+    fn last_listener_releases_the_subscription() {
+        let mut listeners = vec![
+            listener(1, MEASUREMENT),
+            listener(2, MEASUREMENT),
+            listener(3, BATTERY_LEVEL),
+        ];
+        let removed = release_listener(&mut listeners, &[], ListenerHandle(1));
+        assert_eq!(removed, Release::Held);
+        let removed = release_listener(&mut listeners, &[], ListenerHandle(2));
+        assert_eq!(removed, Release::Unsubscribe(MEASUREMENT));
+        // removed twice, or already gone after an unsubscribe
+        let removed = release_listener(&mut listeners, &[], ListenerHandle(2));
+        assert_eq!(removed, Release::Unknown);
+        assert_eq!(listeners.len(), 1);
+    }
+
+    #[test]
+    fn sessions_hold_the_subscription_of_removed_listeners() {
+        let sessions = [session(vec![MEASUREMENT])];
+        let mut listeners = vec![listener(1, MEASUREMENT), listener(2, BATTERY_LEVEL)];
+        let removed = release_listener(&mut listeners, &sessions, ListenerHandle(1));
+        assert_eq!(removed, Release::Held);
+        let removed = release_listener(&mut listeners, &sessions, ListenerHandle(2));
+        assert_eq!(removed, Release::Unsubscribe(BATTERY_LEVEL));
+    }
+
+    #[test]
+    fn unsubscribe_releases_every_listener_of_the_characteristic() {
+        let listeners = [
+            listener(1, MEASUREMENT),
+            listener(2, MEASUREMENT),
+            listener(3, BATTERY_LEVEL),
+        ];
+        assert_eq!(release_all(&listeners, &[], MEASUREMENT), Release::Unsubscribe(MEASUREMENT));
+        assert_eq!(
+            release_all(&listeners, &[session(vec![MEASUREMENT])], MEASUREMENT),
+            Release::Held
+        );
+        // a session alone is not unsubscribed, it is closed with close_session
+        assert_eq!(
+            release_all(&[], &[session(vec![SENSOR_LOCATION])], SENSOR_LOCATION),
+            Release::Unknown
+        );
+        assert!(subscription_held(&listeners, &[], BATTERY_LEVEL));
+        assert!(!subscription_held(&listeners, &[], SENSOR_LOCATION));
+    }
+
+    const CONTROL: Uuid = Uuid::from_u128(0x51ff_12bb_3ed8_46e5_b4f9_d64e_2fec_021c);
+
+    fn mode_write(auto: bool) -> RequiredWrite {
+        RequiredWrite {
+            characteristic: CONTROL,
+            data: vec![0x01],
+            auto,
         }
     }
+
+    /// Runs the enforcement with a device recording the prerequisite writes, `fails` makes
+    /// the write fail
+    async fn subscribe_with(
+        required: Option<RequiredWrite>,
+        done: &std::sync::Mutex<HashSet<Uuid>>,
+        fails: bool,
+    ) -> (Result<(), Error>, Vec<(Uuid, Vec<u8>)>) {
+        let recorded = std::sync::Mutex::new(vec![]);
+        let written = &recorded;
+        let write = |required: RequiredWrite| async move {
+            if fails {
+                return Err(Error::Btleplug(btleplug::Error::NotConnected));
+            }
+            written.lock().unwrap().push((required.characteristic, required.data));
+            Ok(())
+        };
+        let result = enforce_required_write(MEASUREMENT, required, done, write).await;
+        (result, recorded.into_inner().unwrap())
+    }
+
+    #[tokio::test]
+    async fn subscribe_without_a_required_write_is_not_checked() {
+        let done = std::sync::Mutex::default();
+        let (result, written) = subscribe_with(None, &done, false).await;
+        result.unwrap();
+        assert!(written.is_empty());
+    }
+
+    #[tokio::test]
+    async fn subscribe_before_the_required_write_is_rejected() {
+        let done = std::sync::Mutex::default();
+        let (result, written) = subscribe_with(Some(mode_write(false)), &done, false).await;
+        assert!(matches!(
+            result,
+            Err(Error::SubscribeBeforeWrite { charac, required })
+                if charac == MEASUREMENT.to_string() && required == CONTROL.to_string()
+        ));
+        assert!(written.is_empty());
+    }
+
+    #[tokio::test]
+    async fn required_write_is_done_automatically_once() {
+        let done = std::sync::Mutex::default();
+        let (result, written) = subscribe_with(Some(mode_write(true)), &done, false).await;
+        result.unwrap();
+        assert_eq!(written, [(CONTROL, vec![0x01])]);
+        let (result, written) = subscribe_with(Some(mode_write(true)), &done, false).await;
+        result.unwrap();
+        assert!(written.is_empty());
+    }
+
+    #[tokio::test]
+    async fn failed_automatic_write_keeps_the_constraint() {
+        let done = std::sync::Mutex::default();
+        let (result, _) = subscribe_with(Some(mode_write(true)), &done, true).await;
+        assert!(matches!(result, Err(Error::Btleplug(btleplug::Error::NotConnected))));
+        assert!(done.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn only_the_exact_write_meets_the_constraint() {
+        let options = HashMap::from([(
+            MEASUREMENT,
+            CharacteristicOptions {
+                requires_write: Some(mode_write(false)),
+                ..Default::default()
+            },
+        )]);
+        assert!(required_writes_met(&options, CONTROL, &[0x02]).is_empty());
+        assert!(required_writes_met(&options, MEASUREMENT, &[0x01]).is_empty());
+        let met = required_writes_met(&options, CONTROL, &[0x01]);
+        assert_eq!(met, [MEASUREMENT]);
+
+        // a write done by the app lets the subscribe through
+        let done = std::sync::Mutex::new(met.into_iter().collect::<HashSet<_>>());
+        let (result, written) = subscribe_with(Some(mode_write(false)), &done, false).await;
+        result.unwrap();
+        assert!(written.is_empty());
+    }
 }
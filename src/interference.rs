@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use btleplug::platform::PeripheralId;
+
+use futures::future::{select, Either};
+use tokio::sync::Notify;
+use tokio::time::sleep;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::error::Error;
+use crate::events::BleEvent;
+use crate::handler::Handler;
+
+/// Time BlueZ needs to resolve the services again after they were invalidated
+const SERVICES_SETTLE_TIME: Duration = Duration::from_secs(1);
+/// Minimum time between two automatic checks, property changes of the device arrive in bursts
+const CHECK_COOLDOWN: Duration = Duration::from_secs(5);
+
+/// Interference watches of the connected devices, see [`Handler::run_interference_watch`]
+#[derive(Default)]
+pub(crate) struct Interference {
+    /// Set with [`Handler::set_notification_silence_timeout`], applies to every connection
+    silence_timeout: Mutex<Option<Duration>>,
+    /// Watch of every connection by platform id
+    watches: Mutex<HashMap<PeripheralId, Arc<InterferenceWatch>>>,
+}
+
+/// State of the interference watch of one connection
+#[derive(Default)]
+pub(crate) struct InterferenceWatch {
+    /// Last notification of the device, or the start of the watch
+    last_notification: Mutex<Option<Instant>>,
+    /// Notified when the services of the device may have been resolved again
+    services_hint: Notify,
+}
+
+/// What made the plugin check the subscriptions of the connected device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub enum InterferenceCause {
+    /// BlueZ reported a change of the device while connected, e.g. `ServicesResolved` toggling
+    /// after another application triggered a service discovery. Only detected on linux.
+    ServicesChanged,
+    /// No notification arrived within [`Handler::set_notification_silence_timeout`]
+    NotificationSilence,
+    /// Checked on demand with [`Handler::verify_subscriptions`]
+    Verification,
+}
+
+/// Subscriptions found cleared on the device, published as
+/// [`BleEvent::ConnectionInterfered`]
+/// The client characteristic configuration of a subscribed characteristic no longer enables
+/// notifications, typically because another application on the host opened the device and the
+/// stack reset it.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionInterference {
+    pub address: String,
+    pub cause: InterferenceCause,
+    /// Characteristics subscribed again
+    pub restored: Vec<Uuid>,
+    /// Characteristics that could not be subscribed again or are no longer offered
+    pub failed: Vec<Uuid>,
+}
+
+/// Result of reading back the subscriptions of the connected device, see
+/// [`Handler::verify_subscriptions`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct SubscriptionReport {
+    pub address: String,
+    /// Enabled on the device as expected
+    pub verified: Vec<Uuid>,
+    /// Found cleared and subscribed again
+    pub restored: Vec<Uuid>,
+    /// Found cleared or gone and could not be subscribed again
+    pub failed: Vec<Uuid>,
+    /// Without a readable client characteristic configuration descriptor, or on apple
+    /// platforms not known to CoreBluetooth, their state is unknown
+    pub unverifiable: Vec<Uuid>,
+}
+
+impl Interference {
+    /// Starts tracking a new connection, replacing the watch of an earlier connection
+    pub(crate) fn watch(&self, id: PeripheralId) -> Arc<InterferenceWatch> {
+        let watch = Arc::new(InterferenceWatch::default());
+        self.watches().insert(id, watch.clone());
+        watch
+    }
+
+    /// Stops tracking a connection that closed
+    pub(crate) fn forget(&self, id: &PeripheralId) {
+        self.watches().remove(id);
+    }
+
+    /// Asks the watch of the connection to check the subscriptions once the services settled
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    pub(crate) fn hint_services_changed(&self, id: &PeripheralId) {
+        if let Some(watch) = self.watches().get(id) {
+            watch.services_hint.notify_one();
+        }
+    }
+
+    fn silence_timeout(&self) -> Option<Duration> {
+        *self
+            .silence_timeout
+            .lock()
+            .expect("silence timeout lock poisoned")
+    }
+
+    fn watches(&self) -> MutexGuard<'_, HashMap<PeripheralId, Arc<InterferenceWatch>>> {
+        self.watches.lock().expect("interference watches lock poisoned")
+    }
+}
+
+impl InterferenceWatch {
+    /// Counts a notification of the device against the silence timeout
+    pub(crate) fn record_notification(&self, at: Instant) {
+        *self.last_notification() = Some(at);
+    }
+
+    fn last_notification(&self) -> MutexGuard<'_, Option<Instant>> {
+        self.last_notification
+            .lock()
+            .expect("interference watch lock poisoned")
+    }
+
+    /// Time without notifications left until the silence timeout
+    fn silence_left(&self, timeout: Duration) -> Duration {
+        let silent = self
+            .last_notification()
+            .map_or(Duration::ZERO, |t| t.elapsed());
+        timeout.saturating_sub(silent)
+    }
+}
+
+impl Handler {
+    /// Reads back the client characteristic configuration descriptor of every subscribed
+    /// characteristic of the connected device and subscribes the cleared ones again
+    /// Another application on the host can open the same device, BlueZ then discovers the
+    /// services again and the subscriptions of the plugin silently stop delivering. Writes of
+    /// [`crate::CharacteristicOptions::requires_write`] are repeated before subscribing again.
+    /// Finding cleared subscriptions publishes [`BleEvent::ConnectionInterfered`].
+    /// On apple platforms CoreBluetooth does not allow reading the descriptor, its
+    /// `isNotifying` state is checked instead.
+    /// Checks the device connected with [`Handler::connect`], see
+    /// [`Handler::verify_device_subscriptions`] for the other connections.
+    /// # Errors
+    /// Returns an error if no device is connected
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     let report = handler.verify_subscriptions().await.unwrap();
+    ///     println!("restored {:?}, failed {:?}", report.restored, report.failed);
+    /// });
+    /// # }
+    /// ```
+    pub async fn verify_subscriptions(&self) -> Result<SubscriptionReport, Error> {
+        let address = self.connected_address().ok_or(Error::NoDeviceConnected)?;
+        self.check_interference(&address, InterferenceCause::Verification)
+            .await
+    }
+
+    /// Checks the subscriptions of one connected device like [`Handler::verify_subscriptions`],
+    /// also of a device connected with [`Handler::connect_additional`]
+    /// # Errors
+    /// Returns [`Error::NoDeviceConnected`] if the device is not connected
+    pub async fn verify_device_subscriptions(
+        &self,
+        address: &str,
+    ) -> Result<SubscriptionReport, Error> {
+        let address = self.parse_address(address)?;
+        self.check_interference(&address, InterferenceCause::Verification)
+            .await
+    }
+
+    /// Checks the subscriptions with [`Handler::verify_subscriptions`] whenever no
+    /// notification arrived for `timeout`, `None` to disable the check
+    /// Use it for devices that notify periodically. Every connection is watched on its own, a
+    /// check that finds every subscription enabled only starts the next timeout.
+    pub fn set_notification_silence_timeout(&self, timeout: Option<Duration>) {
+        *self
+            .interference
+            .silence_timeout
+            .lock()
+            .expect("silence timeout lock poisoned") = timeout;
+    }
+
+    async fn check_interference(
+        &self,
+        address: &str,
+        cause: InterferenceCause,
+    ) -> Result<SubscriptionReport, Error> {
+        let refresh = cause == InterferenceCause::ServicesChanged;
+        let report = self.reconcile_subscriptions(address, refresh).await?;
+        if !report.restored.is_empty() || !report.failed.is_empty() {
+            warn!(
+                "subscriptions of {} were cleared ({cause:?}), restored {:?}, failed {:?}",
+                report.address, report.restored, report.failed
+            );
+            self.publish(BleEvent::ConnectionInterfered(ConnectionInterference {
+                address: report.address.clone(),
+                cause,
+                restored: report.restored.clone(),
+                failed: report.failed.clone(),
+            }));
+        }
+        Ok(report)
+    }
+
+    /// Watches a connected device for interference until the task is aborted on disconnect
+    /// `watch` is the one of [`Interference::watch`] its notification task records to.
+    pub(crate) async fn run_interference_watch(
+        &'static self,
+        address: String,
+        watch: Arc<InterferenceWatch>,
+    ) {
+        watch.record_notification(Instant::now());
+        loop {
+            let timeout = self.interference.silence_timeout();
+            let silence = timeout.map(|timeout| watch.silence_left(timeout));
+            // polled without a silence timeout, so a timeout set later is picked up
+            let wait = std::pin::pin!(sleep(silence.unwrap_or(CHECK_COOLDOWN)));
+            let hinted = std::pin::pin!(watch.services_hint.notified());
+            let cause = match select(wait, hinted).await {
+                Either::Left(_) => {
+                    let elapsed = timeout.is_some_and(|t| watch.silence_left(t).is_zero());
+                    if silence.is_none() || !elapsed {
+                        continue;
+                    }
+                    InterferenceCause::NotificationSilence
+                }
+                Either::Right(_) => {
+                    sleep(SERVICES_SETTLE_TIME).await;
+                    InterferenceCause::ServicesChanged
+                }
+            };
+            debug!("checking subscriptions of {address} ({cause:?})");
+            match self.check_interference(&address, cause).await {
+                Ok(_) => {}
+                Err(Error::NoDeviceConnected | Error::Disconnected) => return,
+                Err(e) => warn!("failed to check subscriptions of {address}: {e}"),
+            }
+            watch.record_notification(Instant::now());
+            sleep(CHECK_COOLDOWN).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn silence_counts_from_the_last_notification() {
+        let watch = InterferenceWatch::default();
+        watch.record_notification(Instant::now() - Duration::from_secs(3));
+        assert!(watch.silence_left(Duration::from_secs(2)).is_zero());
+        assert!(watch.silence_left(Duration::from_secs(10)) > Duration::from_secs(6));
+        watch.record_notification(Instant::now());
+        assert!(!watch.silence_left(Duration::from_secs(2)).is_zero());
+    }
+}
@@ -1,69 +1,146 @@
+// the android bluetooth stack is only reachable through the tauri plugin
+#[cfg(all(target_os = "android", not(feature = "tauri")))]
+compile_error!("the tauri feature is required on android");
 #[cfg(target_os = "android")]
 mod android;
 #[cfg(all(not(target_arch = "wasm32"), not(target_arch = "xtensa")))]
+mod appearance;
+#[cfg(all(not(target_arch = "wasm32"), not(target_arch = "xtensa")))]
+mod authorization;
+#[cfg(all(not(target_arch = "wasm32"), not(target_arch = "xtensa")))]
+mod batch;
+#[cfg(all(not(target_arch = "wasm32"), not(target_arch = "xtensa")))]
+mod battery;
+#[cfg(all(
+    test,
+    feature = "ts-export",
+    feature = "tauri",
+    feature = "conformance",
+    not(target_arch = "wasm32"),
+    not(target_arch = "xtensa")
+))]
+mod bindings;
+#[cfg(all(not(target_arch = "wasm32"), not(target_arch = "xtensa")))]
+mod budget;
+#[cfg(all(not(target_arch = "wasm32"), not(target_arch = "xtensa")))]
+mod capture;
+#[cfg(all(not(target_arch = "wasm32"), not(target_arch = "xtensa")))]
+mod clock;
+#[cfg(all(feature = "tauri", not(target_arch = "wasm32"), not(target_arch = "xtensa")))]
 mod commands;
+#[cfg(all(
+    feature = "conformance",
+    not(target_arch = "wasm32"),
+    not(target_arch = "xtensa")
+))]
+mod conformance;
+#[cfg(all(not(target_arch = "wasm32"), not(target_arch = "xtensa")))]
+mod connections;
+#[cfg(all(not(target_arch = "wasm32"), not(target_arch = "xtensa")))]
+mod consent;
+#[cfg(all(not(target_arch = "wasm32"), not(target_arch = "xtensa")))]
+mod device_cache;
+#[cfg(all(not(target_arch = "wasm32"), not(target_arch = "xtensa")))]
+mod discovery;
 #[cfg(all(not(target_arch = "wasm32"), not(target_arch = "xtensa")))]
 mod error;
 #[cfg(all(not(target_arch = "wasm32"), not(target_arch = "xtensa")))]
+mod events;
+#[cfg(all(not(target_arch = "wasm32"), not(target_arch = "xtensa")))]
+mod follow;
+#[cfg(all(not(target_arch = "wasm32"), not(target_arch = "xtensa")))]
+pub mod gatt_values;
+#[cfg(all(not(target_arch = "wasm32"), not(target_arch = "xtensa")))]
 mod handler;
+#[cfg(all(not(target_arch = "wasm32"), not(target_arch = "xtensa")))]
+mod interference;
+#[cfg(all(not(target_arch = "wasm32"), not(target_arch = "xtensa")))]
+mod metrics;
+#[cfg(all(not(target_arch = "wasm32"), not(target_arch = "xtensa")))]
+mod mirror;
+#[cfg(all(not(target_arch = "wasm32"), not(target_arch = "xtensa")))]
+mod payload_log;
+#[cfg(all(feature = "tauri", not(target_arch = "wasm32"), not(target_arch = "xtensa")))]
+mod plugin;
+#[cfg(all(not(target_arch = "wasm32"), not(target_arch = "xtensa")))]
+pub mod models;
+#[cfg(all(
+    feature = "raw-gatt",
+    not(target_arch = "wasm32"),
+    not(target_arch = "xtensa")
+))]
+mod raw;
+#[cfg(all(not(target_arch = "wasm32"), not(target_arch = "xtensa")))]
+mod reconnect;
+#[cfg(all(not(target_arch = "wasm32"), not(target_arch = "xtensa")))]
+mod runtime;
+#[cfg(all(not(target_arch = "wasm32"), not(target_arch = "xtensa")))]
+mod scan_budget;
+#[cfg(all(not(target_arch = "wasm32"), not(target_arch = "xtensa")))]
+mod scan_consumers;
+#[cfg(all(not(target_arch = "wasm32"), not(target_arch = "xtensa")))]
+mod scan_throttle;
+#[cfg(all(not(target_arch = "wasm32"), not(target_arch = "xtensa")))]
+mod setup;
+#[cfg(all(not(target_arch = "wasm32"), not(target_arch = "xtensa")))]
+mod store;
+#[cfg(all(not(target_arch = "wasm32"), not(target_arch = "xtensa")))]
+mod timeline;
+#[cfg(all(not(target_arch = "wasm32"), not(target_arch = "xtensa")))]
+mod transfer;
+#[cfg(all(feature = "tauri", not(target_arch = "wasm32"), not(target_arch = "xtensa")))]
+mod wire;
 
 #[cfg(all(not(target_arch = "wasm32"), not(target_arch = "xtensa")))]
 mod lib {   
-    pub use crate::error::Error;
-    pub use crate::handler::Handler;
-
-    use futures::StreamExt;
-    use once_cell::sync::OnceCell;
-    use tauri::{
-        async_runtime,
-        plugin::{Builder, TauriPlugin},
-        Wry,
+    pub use crate::appearance::{appearance_info, appearance_name, AppearanceInfo};
+    pub use crate::authorization::AuthorizationStatus;
+    pub use crate::batch::{BatchProgress, BatchReport, BatchWrite, BatchWriteResult};
+    pub use crate::battery::BatteryLevel;
+    pub use crate::capture::{CaptureFormat, CaptureHandle, CaptureStats};
+    pub use crate::clock::{ClockSync, TimedNotification};
+    #[cfg(feature = "conformance")]
+    pub use crate::conformance::{CheckOutcome, CheckResult, ConformanceOptions, ConformanceReport};
+    pub use crate::consent::{PairingConsentRequest, PairingKind};
+    pub use crate::device_cache::DeviceCandidate;
+    pub use crate::discovery::{DiscoverOptions, DiscoveredEvent};
+    pub use crate::error::{CorrelatedError, Error};
+    pub use crate::events::{
+        AdapterState, BleEvent, BondChange, CallbackKind, CallbackPanic, DisconnectReason,
     };
+    pub use crate::handler::{
+        BleState, CharacteristicOptions, ConnectOptions, ConnectResult, ConnectionCapabilities,
+        ConnectionLimit, ConnectionLimitPolicy, ConnectionState, CriticalOperation, DeliveryMode,
+        DeviceReaddressed, DuplicateFilter, EventLoopGuard, FingerprintRule, ForgetFailure,
+        ForgetReport, ForgetStep, Handler, Health, KeepAliveMethod, KnownDevice, list_adapters,
+        ListenerHandle, MultiResponse, MultiResponseOptions, PairingEvent, PairingState,
+        ReadStrategy, ReconnectPolicy, RequiredWrite, RetryPolicy, ScanCapabilities, SessionFrame,
+        SessionHandle, SubscribeMode, TimedRead, WriteLimits, WriteResponse,
+    };
+    pub use crate::interference::{ConnectionInterference, InterferenceCause, SubscriptionReport};
+    pub use crate::metrics::{BleMetrics, LatencyHistogram, OperationMetrics, BUCKET_BOUNDS_MS};
+    pub use crate::mirror::{BlecMirror, MirroredValue};
+    pub use crate::payload_log::{payload_logging, set_payload_logging, PayloadLogging};
+    #[cfg(feature = "raw-gatt")]
+    pub use crate::raw::RawGattOp;
+    pub use crate::reconnect::ReconnectProgress;
+    pub use crate::scan_budget::{ScanEventBudget, ScanEventStats};
+    pub use crate::scan_consumers::{ScanConsumerId, ScanConsumerOptions};
+    pub use crate::scan_throttle::ScanThrottleWarning;
+    pub use crate::setup::{
+        ConnectionPriority, SetupAction, SetupNotification, SetupStep, SetupStepResult,
+        StepErrorPolicy,
+    };
+    pub use crate::store::{BlecStore, FileStore};
+    pub use crate::timeline::{
+        NotificationBucket, SessionTimeline, TimelineEntry, TimelineEvent, TimelineOperation,
+    };
+    pub use crate::transfer::{TransferOptions, TransferReport};
+    #[cfg(feature = "tauri")]
+    pub use crate::wire::WireFormat;
 
-    static HANDLER: OnceCell<Handler> = OnceCell::new();
-
-    /// Initializes the plugin.
-    /// # Panics
-    /// Panics if the handler cannot be initialized.
-    pub fn init() -> TauriPlugin<Wry> {
-        let handler = async_runtime::block_on(Handler::new()).expect("failed to initialize handler");
-        let _ = HANDLER.set(handler);
-
-        #[allow(unused)]
-        Builder::new("blec")
-            .invoke_handler(crate::commands::commands())
-            .setup(|app, api| {
-                #[cfg(target_os = "android")]
-                crate::android::init(app, api)?;
-                async_runtime::spawn(handle_events());
-                Ok(())
-            })
-            .build()
-    }
-
-    /// Returns the BLE handler to use blec from rust.
-    /// # Errors
-    /// Returns an error if the handler is not initialized.
-    pub fn get_handler() -> crate::error::Result<&'static Handler> {
-        let handler = HANDLER.get().ok_or(crate::error::Error::HandlerNotInitialized)?;
-        Ok(handler)
-    }
-
-    async fn handle_events() {
-        let handler = get_handler().expect("failed to get handler");
-        let stream = handler
-            .get_event_stream()
-            .await
-            .expect("failed to get event stream");
-        stream
-            .for_each(|event| async {
-                handler
-                    .handle_event(event)
-                    .await
-                    .expect("failed to handle event");
-            })
-            .await;
-    }
+    #[cfg(feature = "tauri")]
+    pub use crate::plugin::*;
 }
 
 #[cfg(all(not(target_arch = "wasm32"), not(target_arch = "xtensa")))]
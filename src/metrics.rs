@@ -0,0 +1,72 @@
+use std::time::Duration;
+
+/// Upper bounds in milliseconds of the histogram buckets, the last bucket collects all slower
+/// operations
+pub const BUCKET_BOUNDS_MS: [u64; 6] = [10, 25, 50, 100, 250, 1000];
+
+/// Latency histogram with the buckets `<10, <25, <50, <100, <250, <1000, >=1000` ms
+#[derive(Debug, Clone, Default, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct LatencyHistogram {
+    #[cfg_attr(feature = "ts-export", ts(type = "Array<number>"))]
+    pub buckets: [u64; BUCKET_BOUNDS_MS.len() + 1],
+    #[cfg_attr(feature = "ts-export", ts(type = "number"))]
+    pub count: u64,
+    #[cfg_attr(feature = "ts-export", ts(type = "number"))]
+    pub total_us: u64,
+    #[cfg_attr(feature = "ts-export", ts(type = "number"))]
+    pub max_us: u64,
+}
+
+impl LatencyHistogram {
+    fn record(&mut self, latency: Duration) {
+        let ms = u64::try_from(latency.as_millis()).unwrap_or(u64::MAX);
+        let bucket = BUCKET_BOUNDS_MS
+            .iter()
+            .position(|bound| ms < *bound)
+            .unwrap_or(BUCKET_BOUNDS_MS.len());
+        self.buckets[bucket] += 1;
+        let us = u64::try_from(latency.as_micros()).unwrap_or(u64::MAX);
+        self.count += 1;
+        self.total_us = self.total_us.saturating_add(us);
+        self.max_us = self.max_us.max(us);
+    }
+}
+
+/// Latencies of one kind of operation
+#[derive(Debug, Clone, Default, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct OperationMetrics {
+    /// Duration of the GATT call itself
+    pub gatt: LatencyHistogram,
+    /// Time spent waiting for earlier operations on the device before the GATT call started
+    pub queue_wait: LatencyHistogram,
+}
+
+impl OperationMetrics {
+    pub(crate) fn record(&mut self, queue_wait: Duration, gatt: Duration) {
+        self.queue_wait.record(queue_wait);
+        self.gatt.record(gatt);
+    }
+}
+
+/// Latency metrics returned by [`crate::Handler::get_metrics`]
+#[derive(Debug, Clone, Default, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct BleMetrics {
+    pub reads: OperationMetrics,
+    pub writes: OperationMetrics,
+    pub subscribes: OperationMetrics,
+    /// Times the stack reported congestion
+    #[cfg_attr(feature = "ts-export", ts(type = "number"))]
+    pub congestion: u64,
+    /// Times the device answered with ATT Insufficient Resources
+    #[cfg_attr(feature = "ts-export", ts(type = "number"))]
+    pub insufficient_resources: u64,
+    /// Reads and writes retried after one of the transient errors above
+    #[cfg_attr(feature = "ts-export", ts(type = "number"))]
+    pub retries: u64,
+}
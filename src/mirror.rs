@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+
+use tracing::{debug, warn};
+
+use crate::error::Error;
+use crate::follow::{Follow, Followed};
+use crate::handler::{Handler, ListenerHandle};
+use crate::models::CharacteristicRef;
+use crate::runtime;
+
+/// How often characteristics that do not notify are read by [`Handler::mirror_to_state`]
+const MIRROR_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Mirrors started with [`Handler::mirror_to_state`]
+#[derive(Default)]
+pub(crate) struct Mirrors {
+    /// Values of all mirrors, shared by every clone handed out by [`Handler::mirror`]
+    values: BlecMirror,
+    /// Tasks and listeners of the mirrors by key
+    running: Mutex<HashMap<String, Mirror>>,
+}
+
+impl Mirrors {
+    fn running(&self) -> std::sync::MutexGuard<'_, HashMap<String, Mirror>> {
+        self.running.lock().expect("mirrors lock poisoned")
+    }
+}
+
+struct Mirror {
+    task: runtime::JoinHandle<()>,
+    /// Listener on the connected device, gone with the next disconnect
+    listener: Arc<Mutex<Option<ListenerHandle>>>,
+}
+
+/// Characteristic followed by a mirror
+struct MirrorFollower {
+    handler: &'static Handler,
+    key: String,
+    charac: CharacteristicRef,
+    listener: Arc<Mutex<Option<ListenerHandle>>>,
+}
+
+impl Followed for MirrorFollower {
+    async fn watch(&self, _address: &str) -> Follow {
+        self.handler
+            .watch_mirrored(&self.key, &self.charac, &self.listener)
+            .await
+    }
+
+    async fn poll(&self, _address: &str) {
+        match self.handler.recv_data(self.charac.clone()).await {
+            Ok(value) => self.handler.mirrors.values.update(&self.key, &value),
+            Err(e) => debug!("failed to poll mirror {}: {e}", self.key),
+        }
+    }
+}
+
+/// Latest value of a characteristic mirrored with [`Handler::mirror_to_state`]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MirroredValue {
+    pub characteristic: CharacteristicRef,
+    /// Latest value read or notified, `None` until the first one arrived
+    pub value: Option<Vec<u8>>,
+    /// When the latest value arrived, also if it did not change
+    pub updated_at: Option<SystemTime>,
+    /// When the value last changed
+    pub changed_at: Option<SystemTime>,
+}
+
+/// Latest values of the mirrored characteristics by key, see [`Handler::mirror_to_state`]
+/// The plugin manages it as Tauri state once the handler is initialized, so other plugins can
+/// read the values synchronously without depending on the event names of this plugin. All
+/// clones share the same values.
+/// # Example
+/// ```no_run
+/// # #[cfg(feature = "tauri")] {
+/// use tauri::Manager;
+/// use tauri_plugin_blec::BlecMirror;
+///
+/// fn heart_rate(app: &tauri::AppHandle) -> Option<u8> {
+///     let value = app.try_state::<BlecMirror>()?.get("heart-rate")?.value?;
+///     value.get(1).copied()
+/// }
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct BlecMirror {
+    values: Arc<RwLock<HashMap<String, MirroredValue>>>,
+}
+
+impl BlecMirror {
+    /// Returns the mirror of the key, `None` if no mirror has the key
+    pub fn get(&self, key: &str) -> Option<MirroredValue> {
+        self.values
+            .read()
+            .expect("mirror lock poisoned")
+            .get(key)
+            .cloned()
+    }
+
+    /// Returns all active mirrors by key, also the ones without a value yet
+    pub fn mirrors(&self) -> HashMap<String, MirroredValue> {
+        self.values.read().expect("mirror lock poisoned").clone()
+    }
+
+    fn insert(&self, key: &str, characteristic: CharacteristicRef) {
+        let empty = MirroredValue {
+            characteristic,
+            value: None,
+            updated_at: None,
+            changed_at: None,
+        };
+        self.values
+            .write()
+            .expect("mirror lock poisoned")
+            .insert(key.to_string(), empty);
+    }
+
+    fn update(&self, key: &str, value: &[u8]) {
+        let mut values = self.values.write().expect("mirror lock poisoned");
+        let Some(mirrored) = values.get_mut(key) else {
+            return;
+        };
+        let now = SystemTime::now();
+        if mirrored.value.as_deref() != Some(value) {
+            mirrored.value = Some(value.to_vec());
+            mirrored.changed_at = Some(now);
+        }
+        mirrored.updated_at = Some(now);
+    }
+
+    fn remove(&self, key: &str) {
+        self.values.write().expect("mirror lock poisoned").remove(key);
+    }
+}
+
+impl Handler {
+    /// Keeps the latest value of the characteristic in [`BlecMirror`] under `key` until
+    /// [`Handler::stop_mirror`] is called
+    /// On every connect, also after reconnects, the value is read and the mirror subscribes to
+    /// the characteristic. Characteristics that do not notify are read every second instead, a
+    /// failed subscribe is tried again with the same interval, see
+    /// [`Handler::mirror_to_state_with_poll_interval`]. Values are kept while the device is
+    /// disconnected. Mirroring to a key again replaces the running mirror of the key.
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// use uuid::uuid;
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     handler.mirror_to_state(uuid!("00002A37-0000-1000-8000-00805F9B34FB"), "heart-rate");
+    ///     let latest = handler.mirror().get("heart-rate");
+    /// });
+    /// # }
+    /// ```
+    pub fn mirror_to_state(&'static self, c: impl Into<CharacteristicRef>, key: impl Into<String>) {
+        self.mirror_to_state_with_poll_interval(c, key, MIRROR_POLL_INTERVAL);
+    }
+
+    /// Mirrors like [`Handler::mirror_to_state`], reading characteristics that do not notify
+    /// every `poll_interval`
+    pub fn mirror_to_state_with_poll_interval(
+        &'static self,
+        c: impl Into<CharacteristicRef>,
+        key: impl Into<String>,
+        poll_interval: Duration,
+    ) {
+        let c = c.into();
+        let key = key.into();
+        let previous = self.mirrors.running().remove(&key);
+        if let Some(previous) = previous {
+            previous.task.abort();
+            let listener = previous.listener.lock().expect("mirror lock poisoned").take();
+            // removed in the background, so replacing a mirror does not have to be awaited
+            if let Some(listener) = listener {
+                runtime::spawn(async move {
+                    if let Err(e) = self.remove_listener(listener).await {
+                        debug!("failed to remove the listener of a replaced mirror: {e}");
+                    }
+                });
+            }
+        }
+        self.mirrors.values.insert(&key, c.clone());
+        let listener = Arc::new(Mutex::new(None));
+        let follower = MirrorFollower {
+            handler: self,
+            key: key.clone(),
+            charac: c,
+            listener: listener.clone(),
+        };
+        let task = runtime::spawn(self.follow_characteristic(follower, poll_interval));
+        self.mirrors.running().insert(key, Mirror { task, listener });
+    }
+
+    /// Stops the mirror of the key and removes its value from [`BlecMirror`]
+    /// Returns false if no mirror has the key.
+    /// # Errors
+    /// Returns an error if the unsubscribe fails
+    pub async fn stop_mirror(&self, key: &str) -> Result<bool, Error> {
+        let mirror = self.mirrors.running().remove(key);
+        self.mirrors.values.remove(key);
+        let Some(mirror) = mirror else {
+            return Ok(false);
+        };
+        mirror.task.abort();
+        let listener = mirror.listener.lock().expect("mirror lock poisoned").take();
+        if let Some(listener) = listener {
+            self.remove_listener(listener).await?;
+        }
+        Ok(true)
+    }
+
+    /// Values of the mirrors started with [`Handler::mirror_to_state`], the same as the
+    /// [`BlecMirror`] managed as Tauri state
+    pub fn mirror(&self) -> BlecMirror {
+        self.mirrors.values.clone()
+    }
+
+    /// Reads the value and subscribes to it
+    async fn watch_mirrored(
+        &'static self,
+        key: &str,
+        c: &CharacteristicRef,
+        listener: &Mutex<Option<ListenerHandle>>,
+    ) -> Follow {
+        match self.recv_data(c.clone()).await {
+            Ok(value) => self.mirrors.values.update(key, &value),
+            Err(Error::CharacNotAvailable(_)) => {
+                debug!("connected device has no characteristic for mirror {key}");
+                return Follow::Unavailable;
+            }
+            // notifications might still work for characteristics that can not be read
+            Err(e) => debug!("failed to read the value of mirror {key}: {e}"),
+        }
+        let owner = key.to_string();
+        let subscribed = self
+            .subscribe(c.clone(), move |data: &[u8]| self.mirrors.values.update(&owner, data))
+            .await;
+        match subscribed {
+            Ok(handle) => {
+                *listener.lock().expect("mirror lock poisoned") = Some(handle);
+                Follow::Subscribed
+            }
+            Err(Error::NotifyNotSupported { .. }) => {
+                debug!("characteristic of mirror {key} does not notify, polling it");
+                Follow::Polled
+            }
+            Err(e) => {
+                warn!("failed to subscribe mirror {key}, trying again: {e}");
+                Follow::Retry
+            }
+        }
+    }
+}
@@ -0,0 +1,690 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use btleplug::api::{CharPropFlags, Peripheral as _};
+
+#[cfg(target_os = "android")]
+use crate::android::Peripheral;
+#[cfg(not(target_os = "android"))]
+use btleplug::platform::Peripheral;
+
+pub use btleplug::api::PeripheralProperties;
+pub use btleplug::models::{fmt_addr, ScanFilter, Service, WriteType};
+
+/// Serialized form of [`WriteType`], only used to export its TypeScript binding
+#[cfg(feature = "ts-export")]
+#[allow(dead_code)]
+#[derive(ts_rs::TS)]
+#[ts(rename = "WriteType", rename_all = "camelCase")]
+pub(crate) enum WriteTypeRepr {
+    WithResponse,
+    WithoutResponse,
+}
+
+/// Serialized form of [`Service`], only used to export its TypeScript binding
+#[cfg(feature = "ts-export")]
+#[allow(dead_code)]
+#[derive(ts_rs::TS)]
+#[ts(rename = "Service")]
+pub(crate) struct ServiceRepr {
+    uuid: Uuid,
+    primary: bool,
+    characteristics: Vec<CharacteristicRepr>,
+}
+
+/// Serialized form of [`btleplug::api::Characteristic`], see [`ServiceRepr`]
+#[cfg(feature = "ts-export")]
+#[allow(dead_code)]
+#[derive(ts_rs::TS)]
+#[ts(rename = "Characteristic")]
+pub(crate) struct CharacteristicRepr {
+    uuid: Uuid,
+    service_uuid: Uuid,
+    /// Names of the set property flags joined with ` | `, e.g. `READ | NOTIFY`
+    properties: String,
+    descriptors: Vec<DescriptorRepr>,
+}
+
+/// Serialized form of [`btleplug::api::Descriptor`], see [`ServiceRepr`]
+#[cfg(feature = "ts-export")]
+#[allow(dead_code)]
+#[derive(ts_rs::TS)]
+#[ts(rename = "Descriptor")]
+pub(crate) struct DescriptorRepr {
+    uuid: Uuid,
+    service_uuid: Uuid,
+    characteristic_uuid: Uuid,
+}
+
+/// "BR/EDR Not Supported" bit of the advertising Flags AD type
+const FLAG_BR_EDR_NOT_SUPPORTED: u8 = 0x04;
+
+/// Advertisements needed before [`BleDevice::adv_interval_ms`] is reported
+const ADV_INTERVAL_MIN_PACKETS: usize = 5;
+/// Number of recent gaps between advertisements the median is taken from
+const ADV_INTERVAL_WINDOW: usize = 16;
+
+/// A BLE device as reported to the frontend
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct BleDevice {
+    pub address: String,
+    pub name: String,
+    pub is_connected: bool,
+    /// Manufacturer specific data by company id, merged over all advertisements of the scan
+    pub manufacturer_data: HashMap<u16, Vec<u8>>,
+    /// Service UUIDs advertised by the device, merged over all advertisements of the scan
+    pub services: Vec<Uuid>,
+    /// Service data by service UUID, merged over all advertisements of the scan
+    pub service_data: HashMap<Uuid, Vec<u8>>,
+    /// Class of Device, only reported for devices also seen over BR/EDR
+    pub device_class: Option<u32>,
+    /// Whether the device advertises Bluetooth Classic (BR/EDR) support
+    /// `None` if neither the advertising flags nor the device class are known
+    pub br_edr_capable: Option<bool>,
+    /// Signal strength in dBm of the last advertisement, updated with every scan result
+    pub rssi: Option<i16>,
+    /// Number of advertisements received during the scan
+    /// Only reported with [`crate::DuplicateFilter::ReportAll`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts-export", ts(optional, as = "Option<u32>"))]
+    pub advertisement_count: Option<u64>,
+    /// Rolling median of the time between advertisements, once enough were received
+    /// Only reported with [`crate::DuplicateFilter::ReportAll`]. Packets merged by the OS make
+    /// the interval appear longer, see [`crate::ScanCapabilities::adv_interval_exact`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts-export", ts(optional))]
+    pub adv_interval_ms: Option<u32>,
+    /// Key computed by the fingerprint set with [`crate::Handler::set_device_fingerprint`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts-export", ts(optional))]
+    pub fingerprint: Option<String>,
+    /// Data added by the enricher set with [`crate::Handler::set_device_enricher`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts-export", ts(optional))]
+    pub extra: Option<serde_json::Value>,
+    /// The device was connected successfully in this session, or in an earlier one if a
+    /// [`crate::BlecStore`] is set
+    pub previously_connected: bool,
+    /// Adapter used to connect, the one that received the strongest RSSI
+    /// Only reported by scans with [`crate::Handler::set_multi_adapter_scan`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts-export", ts(optional))]
+    pub adapter_id: Option<String>,
+    /// Every adapter that received advertisements of the device, strongest RSSI first
+    /// Only reported by scans with [`crate::Handler::set_multi_adapter_scan`]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[cfg_attr(feature = "ts-export", ts(optional, as = "Option<Vec<AdapterSighting>>"))]
+    pub adapters: Vec<AdapterSighting>,
+    /// Appearance value of the advertisement, only reported on android
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts-export", ts(optional))]
+    pub appearance: Option<u16>,
+    /// Name of the appearance, see [`crate::appearance_name`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts-export", ts(optional, as = "Option<String>"))]
+    pub appearance_name: Option<&'static str>,
+}
+
+/// Adapter that received advertisements of a device during a multi adapter scan
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct AdapterSighting {
+    pub adapter_id: String,
+    /// RSSI of the last advertisement the adapter received
+    pub rssi: Option<i16>,
+}
+
+/// A bluetooth adapter of the system, see [`crate::list_adapters`]
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct AdapterInfo {
+    /// Position in the adapter list of the OS
+    pub index: usize,
+    /// Adapter info of the platform, e.g. `hci1 (usb:v1D6Bp0246d0537)` on Linux, the index on
+    /// android
+    pub id: String,
+}
+
+/// Picks the adapter used by the handler, by index or name
+/// A name matches the whole [`AdapterInfo::id`] or its first word, e.g. `hci1` on Linux.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(untagged)]
+pub enum AdapterSelector {
+    Index(usize),
+    Name(String),
+}
+
+impl Default for AdapterSelector {
+    fn default() -> Self {
+        AdapterSelector::Index(0)
+    }
+}
+
+impl AdapterSelector {
+    pub(crate) fn matches(&self, adapter: &AdapterInfo) -> bool {
+        match self {
+            AdapterSelector::Index(index) => adapter.index == *index,
+            AdapterSelector::Name(name) => {
+                adapter.id == *name || adapter.id.split_whitespace().next() == Some(name)
+            }
+        }
+    }
+}
+
+/// Filter on the advertised local name of scanned devices, applied on top of the [`ScanFilter`]
+/// Devices that do not advertise a local name are excluded by every filter but
+/// [`ScanNameFilter::None`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase", tag = "type", content = "value")]
+pub enum ScanNameFilter {
+    #[default]
+    None,
+    Exact(String),
+    Prefix(String),
+    Contains(String),
+}
+
+impl ScanNameFilter {
+    /// Returns true if a device advertising the local name passes the filter
+    pub fn matches(&self, local_name: Option<&str>) -> bool {
+        let Some(name) = local_name else {
+            return matches!(self, Self::None);
+        };
+        match self {
+            Self::None => true,
+            Self::Exact(exact) => name == exact,
+            Self::Prefix(prefix) => name.starts_with(prefix.as_str()),
+            Self::Contains(part) => name.contains(part.as_str()),
+        }
+    }
+}
+
+/// Fully qualified characteristic for devices offering the same characteristic in multiple services
+/// Deserializes from a plain uuid or from `{ address, service, characteristic }`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(from = "CharacteristicRefRepr")]
+pub struct CharacteristicRef {
+    /// Address of the device, required if several devices are connected, see
+    /// [`crate::Handler::connect_additional`]
+    #[cfg_attr(feature = "ts-export", ts(optional = nullable))]
+    pub address: Option<String>,
+    /// Service containing the characteristic, required if multiple services contain it
+    #[cfg_attr(feature = "ts-export", ts(optional = nullable))]
+    pub service: Option<Uuid>,
+    pub characteristic: Uuid,
+}
+
+impl From<Uuid> for CharacteristicRef {
+    fn from(characteristic: Uuid) -> Self {
+        Self {
+            address: None,
+            service: None,
+            characteristic,
+        }
+    }
+}
+
+/// A [`CharacteristicRef`] as accepted from the frontend, a plain uuid or the full reference
+#[derive(Deserialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS), ts(rename = "CharacteristicRefInput"))]
+#[serde(untagged)]
+pub(crate) enum CharacteristicRefRepr {
+    Uuid(Uuid),
+    Ref {
+        #[cfg_attr(feature = "ts-export", ts(optional))]
+        address: Option<String>,
+        #[cfg_attr(feature = "ts-export", ts(optional))]
+        service: Option<Uuid>,
+        characteristic: Uuid,
+    },
+}
+
+impl From<CharacteristicRefRepr> for CharacteristicRef {
+    fn from(repr: CharacteristicRefRepr) -> Self {
+        match repr {
+            CharacteristicRefRepr::Uuid(uuid) => uuid.into(),
+            CharacteristicRefRepr::Ref {
+                address,
+                service,
+                characteristic,
+            } => Self {
+                address,
+                service,
+                characteristic,
+            },
+        }
+    }
+}
+
+/// Service of the GATT database of the connected device, see
+/// [`crate::Handler::discover_all_services`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceInfo {
+    pub uuid: Uuid,
+    pub primary: bool,
+    pub characteristics: Vec<CharacteristicInfo>,
+}
+
+/// Characteristic of a [`ServiceInfo`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct CharacteristicInfo {
+    pub uuid: Uuid,
+    pub properties: CharacteristicProperties,
+    /// UUIDs of the descriptors of the characteristic
+    pub descriptors: Vec<Uuid>,
+}
+
+/// Properties of a characteristic decoded from the GATT properties bitfield
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct CharacteristicProperties {
+    pub broadcast: bool,
+    pub read: bool,
+    pub write: bool,
+    pub write_without_response: bool,
+    pub notify: bool,
+    pub indicate: bool,
+    pub authenticated_signed_writes: bool,
+    pub extended_properties: bool,
+}
+
+impl From<CharPropFlags> for CharacteristicProperties {
+    fn from(flags: CharPropFlags) -> Self {
+        Self {
+            broadcast: flags.contains(CharPropFlags::BROADCAST),
+            read: flags.contains(CharPropFlags::READ),
+            write: flags.contains(CharPropFlags::WRITE),
+            write_without_response: flags.contains(CharPropFlags::WRITE_WITHOUT_RESPONSE),
+            notify: flags.contains(CharPropFlags::NOTIFY),
+            indicate: flags.contains(CharPropFlags::INDICATE),
+            authenticated_signed_writes: flags.contains(CharPropFlags::AUTHENTICATED_SIGNED_WRITES),
+            extended_properties: flags.contains(CharPropFlags::EXTENDED_PROPERTIES),
+        }
+    }
+}
+
+impl From<&btleplug::api::Service> for ServiceInfo {
+    fn from(service: &btleplug::api::Service) -> Self {
+        Self {
+            uuid: service.uuid,
+            primary: service.primary,
+            characteristics: service
+                .characteristics
+                .iter()
+                .map(|c| CharacteristicInfo {
+                    uuid: c.uuid,
+                    properties: c.properties.into(),
+                    descriptors: c.descriptors.iter().map(|d| d.uuid).collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// How strictly addresses passed to the handler are checked, see
+/// [`crate::Handler::set_address_policy`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub enum AddressPolicy {
+    /// Separators and case are normalized, e.g. `aa-bb-cc-dd-ee-ff` is accepted
+    #[default]
+    Coerce,
+    /// Only the canonical form of [`parse_address`] is accepted
+    Strict,
+}
+
+/// Parses an address into the canonical form used to identify devices
+/// MAC addresses are formatted with [`fmt_addr`], with [`AddressPolicy::Coerce`] the
+/// separators `:`, `-`, `.` and none at all are accepted in any case. Apple platforms identify
+/// devices by uuid, which is accepted in any format of [`Uuid::parse_str`].
+/// # Errors
+/// Returns [`crate::Error::InvalidAddress`] if the input is no address or not canonical with
+/// [`AddressPolicy::Strict`]
+/// # Example
+/// ```no_run
+/// use tauri_plugin_blec::models::{parse_address, AddressPolicy};
+///
+/// let canonical = parse_address("AA:BB:CC:DD:EE:FF", AddressPolicy::Strict).unwrap();
+/// for input in ["aa:bb:cc:dd:ee:ff", "AA-BB-CC-DD-EE-FF", "aabb.ccdd.eeff", "aabbccddeeff"] {
+///     assert_eq!(parse_address(input, AddressPolicy::Coerce).unwrap(), canonical);
+/// }
+/// assert!(parse_address("aa-bb-cc-dd-ee-ff", AddressPolicy::Strict).is_err());
+/// assert!(parse_address("aa:bb:cc:dd:ee", AddressPolicy::Coerce).is_err());
+/// ```
+pub fn parse_address(input: &str, policy: AddressPolicy) -> Result<String, crate::Error> {
+    let invalid = || crate::Error::InvalidAddress {
+        input: input.to_string(),
+    };
+    let canonical = canonical_address(input.trim()).ok_or_else(invalid)?;
+    if policy == AddressPolicy::Strict && canonical != input {
+        return Err(invalid());
+    }
+    Ok(canonical)
+}
+
+#[cfg(target_vendor = "apple")]
+fn canonical_address(address: &str) -> Option<String> {
+    Uuid::parse_str(address).ok().map(|id| id.to_string())
+}
+
+#[cfg(not(target_vendor = "apple"))]
+fn canonical_address(address: &str) -> Option<String> {
+    let digits: Vec<u8> = address
+        .bytes()
+        .filter(|b| !matches!(b, b':' | b'-' | b'.'))
+        .collect();
+    if digits.len() != 12 || !digits.iter().all(u8::is_ascii_hexdigit) {
+        return None;
+    }
+    let mut bytes = [0; 6];
+    for (byte, pair) in bytes.iter_mut().zip(digits.chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+    }
+    Some(fmt_addr(btleplug::api::BDAddr::from(bytes)))
+}
+
+/// Address used to identify the peripheral, apple platforms only expose a uuid
+pub(crate) fn peripheral_address(peripheral: &Peripheral) -> String {
+    #[cfg(target_vendor = "apple")]
+    {
+        peripheral.id().to_string()
+    }
+    #[cfg(not(target_vendor = "apple"))]
+    {
+        fmt_addr(peripheral.address())
+    }
+}
+
+impl BleDevice {
+    pub(crate) async fn from_peripheral(peripheral: &Peripheral) -> Result<Self, btleplug::Error> {
+        let properties = peripheral.properties().await?.unwrap_or_default();
+        Self::from_properties(peripheral, properties).await
+    }
+
+    /// Builds the device from already fetched properties
+    pub(crate) async fn from_properties(
+        peripheral: &Peripheral,
+        properties: PeripheralProperties,
+    ) -> Result<Self, btleplug::Error> {
+        let address = peripheral_address(peripheral);
+        let name = properties
+            .local_name
+            .unwrap_or_else(|| peripheral.id().to_string());
+        #[cfg(target_os = "android")]
+        let flags = peripheral.advertise_flags();
+        #[cfg(not(target_os = "android"))]
+        let flags: Option<u8> = None;
+        let br_edr_capable = flags
+            .map(|f| f & FLAG_BR_EDR_NOT_SUPPORTED == 0)
+            .or(properties.class.map(|_| true));
+        #[cfg(target_os = "android")]
+        let appearance = peripheral.appearance();
+        #[cfg(not(target_os = "android"))]
+        let appearance: Option<u16> = None;
+        Ok(Self {
+            address,
+            name,
+            is_connected: peripheral.is_connected().await?,
+            manufacturer_data: properties.manufacturer_data,
+            services: properties.services,
+            service_data: properties.service_data,
+            device_class: properties.class,
+            br_edr_capable,
+            rssi: properties.rssi,
+            advertisement_count: None,
+            adv_interval_ms: None,
+            fingerprint: None,
+            extra: None,
+            previously_connected: false,
+            adapter_id: None,
+            adapters: vec![],
+            appearance,
+            appearance_name: appearance.and_then(crate::appearance_name),
+        })
+    }
+}
+
+/// Arrival times of the advertisements of a device, see [`BleDevice::adv_interval_ms`]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct AdvInterval {
+    last: Option<Instant>,
+    gaps: VecDeque<Duration>,
+}
+
+impl AdvInterval {
+    /// Time of the last recorded advertisement
+    pub(crate) fn last(&self) -> Option<Instant> {
+        self.last
+    }
+
+    pub(crate) fn record(&mut self, at: Instant) {
+        if let Some(last) = self.last.replace(at) {
+            if self.gaps.len() == ADV_INTERVAL_WINDOW {
+                self.gaps.pop_front();
+            }
+            self.gaps.push_back(at.saturating_duration_since(last));
+        }
+    }
+
+    pub(crate) fn median_ms(&self) -> Option<u32> {
+        if self.gaps.len() + 1 < ADV_INTERVAL_MIN_PACKETS {
+            return None;
+        }
+        let mut gaps: Vec<Duration> = self.gaps.iter().copied().collect();
+        gaps.sort_unstable();
+        let median = gaps[gaps.len() / 2];
+        Some(u32::try_from(median.as_millis()).unwrap_or(u32::MAX))
+    }
+}
+
+/// Device Information service
+pub const DEVICE_INFORMATION_SERVICE: Uuid = uuid::uuid!("0000180a-0000-1000-8000-00805f9b34fb");
+/// PnP ID characteristic of the Device Information service
+pub const PNP_ID_CHARACTERISTIC: Uuid = uuid::uuid!("00002a50-0000-1000-8000-00805f9b34fb");
+/// Battery service
+pub const BATTERY_SERVICE: Uuid = uuid::uuid!("0000180f-0000-1000-8000-00805f9b34fb");
+/// Battery Level characteristic of the Battery service
+pub const BATTERY_LEVEL_CHARACTERISTIC: Uuid =
+    uuid::uuid!("00002a19-0000-1000-8000-00805f9b34fb");
+/// Client Characteristic Configuration descriptor, see [`crate::gatt_values::Cccd`]
+pub const CLIENT_CHARACTERISTIC_CONFIGURATION: Uuid =
+    uuid::uuid!("00002902-0000-1000-8000-00805f9b34fb");
+/// Alert Level characteristic, see [`crate::gatt_values::AlertLevel`]
+pub const ALERT_LEVEL_CHARACTERISTIC: Uuid = uuid::uuid!("00002a06-0000-1000-8000-00805f9b34fb");
+/// Current Time characteristic of the Current Time service, see
+/// [`crate::gatt_values::CurrentTime`]
+pub const CURRENT_TIME_CHARACTERISTIC: Uuid =
+    uuid::uuid!("00002a2b-0000-1000-8000-00805f9b34fb");
+
+/// Authority that assigned the vendor id of a [`PnpId`]
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase", tag = "type", content = "value")]
+pub enum VendorIdSource {
+    /// Company identifier assigned by the Bluetooth SIG
+    BluetoothSig,
+    /// Vendor id assigned by the USB Implementer's Forum
+    UsbIf,
+    /// Reserved value not defined by the specification
+    Reserved(u8),
+}
+
+impl From<u8> for VendorIdSource {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => Self::BluetoothSig,
+            2 => Self::UsbIf,
+            v => Self::Reserved(v),
+        }
+    }
+}
+
+/// Parsed value of the PnP ID characteristic (0x2A50)
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct PnpId {
+    pub vendor_id_source: VendorIdSource,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub product_version: u16,
+}
+
+impl PnpId {
+    /// Parses the 7 byte little endian characteristic value
+    /// # Errors
+    /// Returns [`crate::Error::InvalidPayload`] if the value is not 7 bytes long
+    pub fn parse(data: &[u8]) -> Result<Self, crate::Error> {
+        let [source, v0, v1, p0, p1, r0, r1] = data else {
+            return Err(crate::Error::InvalidPayload(format!(
+                "PnP ID must be 7 bytes, got {}",
+                data.len()
+            )));
+        };
+        Ok(Self {
+            vendor_id_source: (*source).into(),
+            vendor_id: u16::from_le_bytes([*v0, *v1]),
+            product_id: u16::from_le_bytes([*p0, *p1]),
+            product_version: u16::from_le_bytes([*r0, *r1]),
+        })
+    }
+}
+
+/// Values of the Device Information service
+/// Characteristics the device does not provide are `None`
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceInfo {
+    pub manufacturer_name: Option<String>,
+    pub model_number: Option<String>,
+    pub serial_number: Option<String>,
+    pub hardware_revision: Option<String>,
+    pub firmware_revision: Option<String>,
+    pub software_revision: Option<String>,
+    pub pnp_id: Option<PnpId>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(not(target_vendor = "apple"))]
+    #[test]
+    fn mac_addresses_in_every_accepted_format() {
+        let cases = [
+            ("AA:BB:CC:DD:EE:FF", true),
+            ("aa:bb:cc:dd:ee:ff", false),
+            ("AA-BB-CC-DD-EE-FF", false),
+            ("aa-bb-cc-dd-ee-ff", false),
+            ("aabb.ccdd.eeff", false),
+            ("AABBCCDDEEFF", false),
+            ("aabbccddeeff", false),
+            (" AA:BB:CC:DD:EE:FF\n", false),
+        ];
+        for (input, canonical) in cases {
+            let parsed = parse_address(input, AddressPolicy::Coerce);
+            assert_eq!(parsed.unwrap(), "AA:BB:CC:DD:EE:FF", "{input:?}");
+            let strict = parse_address(input, AddressPolicy::Strict);
+            assert_eq!(strict.is_ok(), canonical, "{input:?}");
+        }
+    }
+
+    #[cfg(not(target_vendor = "apple"))]
+    #[test]
+    fn invalid_mac_addresses() {
+        let cases = [
+            "",
+            "AA:BB:CC:DD:EE",
+            "AA:BB:CC:DD:EE:FF:00",
+            "AA:BB:CC:DD:EE:FG",
+            "AA BB CC DD EE FF",
+            "not an address",
+        ];
+        for input in cases {
+            for policy in [AddressPolicy::Coerce, AddressPolicy::Strict] {
+                assert!(
+                    matches!(
+                        parse_address(input, policy),
+                        Err(crate::Error::InvalidAddress { input: rejected }) if rejected == input
+                    ),
+                    "{input:?} with {policy:?}"
+                );
+            }
+        }
+    }
+
+    #[cfg(target_vendor = "apple")]
+    #[test]
+    fn apple_peripheral_uuids() {
+        let canonical = "4a1bbd33-0b2d-4a9e-8c1f-7d6f5a3e2b10";
+        for input in [
+            canonical,
+            "4A1BBD33-0B2D-4A9E-8C1F-7D6F5A3E2B10",
+            "4a1bbd330b2d4a9e8c1f7d6f5a3e2b10",
+        ] {
+            assert_eq!(parse_address(input, AddressPolicy::Coerce).unwrap(), canonical);
+        }
+        assert!(parse_address("AA:BB:CC:DD:EE:FF", AddressPolicy::Coerce).is_err());
+    }
+
+    #[test]
+    fn pnp_id_with_a_bluetooth_sig_vendor_id() {
+        // Apple's company identifier 0x004C, product 0x0220, version 1.2.3
+        let pnp = PnpId::parse(&[0x01, 0x4c, 0x00, 0x20, 0x02, 0x23, 0x01]).unwrap();
+        assert_eq!(
+            pnp,
+            PnpId {
+                vendor_id_source: VendorIdSource::BluetoothSig,
+                vendor_id: 0x004c,
+                product_id: 0x0220,
+                product_version: 0x0123,
+            }
+        );
+    }
+
+    #[test]
+    fn pnp_id_with_a_usb_if_vendor_id() {
+        // the USB vendor id of Logitech
+        let pnp = PnpId::parse(&[0x02, 0x6d, 0x04, 0x2b, 0xb0, 0x00, 0x10]).unwrap();
+        assert_eq!(
+            pnp,
+            PnpId {
+                vendor_id_source: VendorIdSource::UsbIf,
+                vendor_id: 0x046d,
+                product_id: 0xb02b,
+                product_version: 0x1000,
+            }
+        );
+    }
+
+    #[test]
+    fn pnp_id_with_a_reserved_vendor_id_source() {
+        let pnp = PnpId::parse(&[0x00, 0x01, 0x00, 0x02, 0x00, 0x03, 0x00]).unwrap();
+        assert_eq!(pnp.vendor_id_source, VendorIdSource::Reserved(0));
+        assert_eq!(VendorIdSource::from(0xff), VendorIdSource::Reserved(0xff));
+    }
+
+    #[test]
+    fn pnp_id_of_the_wrong_length() {
+        for data in [&[][..], &[0x01; 6], &[0x01; 8]] {
+            assert!(matches!(PnpId::parse(data), Err(crate::Error::InvalidPayload(_))));
+        }
+    }
+}
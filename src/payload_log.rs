@@ -0,0 +1,98 @@
+use std::fmt::{self, Write as _};
+use std::sync::Mutex;
+
+use tracing::debug;
+
+/// How payload bytes appear in log output and session timelines
+/// Defaults to [`PayloadLogging::Lengths`], so logs and exported timelines do not leak user data.
+/// Captures started with [`crate::Handler::start_capture`] are explicit data exports and always
+/// contain the full payloads.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase", tag = "mode", content = "maxBytes")]
+pub enum PayloadLogging {
+    /// Payloads are left out entirely, not even their length is recorded
+    Off,
+    /// Only the length of payloads is recorded
+    #[default]
+    Lengths,
+    /// The first bytes of payloads are recorded as hex, followed by the total length if the
+    /// payload was truncated
+    HexTruncated(usize),
+    /// Payloads are recorded as hex
+    Full,
+}
+
+static PAYLOAD_LOGGING: Mutex<PayloadLogging> = Mutex::new(PayloadLogging::Lengths);
+
+/// Sets how payloads are logged from now on
+/// Can be called before the plugin is initialized, so payloads logged during the setup already
+/// honor the policy.
+/// # Example
+/// ```no_run
+/// # #[cfg(feature = "tauri")] {
+/// use tauri_plugin_blec::PayloadLogging;
+/// tauri_plugin_blec::set_payload_logging(PayloadLogging::Off);
+/// tauri::Builder::default()
+///     .plugin(tauri_plugin_blec::init());
+/// # }
+/// ```
+pub fn set_payload_logging(policy: PayloadLogging) {
+    debug!("payload logging: {policy:?}");
+    *PAYLOAD_LOGGING
+        .lock()
+        .expect("payload logging lock poisoned") = policy;
+}
+
+/// Returns the policy set with [`set_payload_logging`]
+pub fn payload_logging() -> PayloadLogging {
+    *PAYLOAD_LOGGING
+        .lock()
+        .expect("payload logging lock poisoned")
+}
+
+/// Formats payload bytes according to the [`PayloadLogging`] policy
+/// Every log line or recording containing payload bytes goes through this.
+pub(crate) struct Payload<'a>(pub(crate) &'a [u8]);
+
+impl Payload<'_> {
+    /// Hex encoded payload as recorded in session timelines, `None` unless the policy records
+    /// payload bytes
+    pub(crate) fn hex(&self) -> Option<String> {
+        match payload_logging() {
+            PayloadLogging::Off | PayloadLogging::Lengths => None,
+            PayloadLogging::HexTruncated(max) => Some(hex(&self.0[..self.0.len().min(max)])),
+            PayloadLogging::Full => Some(hex(self.0)),
+        }
+    }
+
+    /// Length as recorded in session timelines, `None` if payloads are left out
+    pub(crate) fn recorded_len(&self) -> Option<usize> {
+        match payload_logging() {
+            PayloadLogging::Off => None,
+            _ => Some(self.0.len()),
+        }
+    }
+}
+
+impl fmt::Display for Payload<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let len = self.0.len();
+        match payload_logging() {
+            PayloadLogging::Off => f.write_str("<redacted>"),
+            PayloadLogging::Lengths => write!(f, "<{len} bytes>"),
+            PayloadLogging::HexTruncated(max) if len > max => {
+                write!(f, "{}... ({len} bytes)", hex(&self.0[..max]))
+            }
+            PayloadLogging::HexTruncated(_) | PayloadLogging::Full => f.write_str(&hex(self.0)),
+        }
+    }
+}
+
+fn hex(data: &[u8]) -> String {
+    let mut hex = String::with_capacity(data.len() * 2);
+    for b in data {
+        let _ = write!(hex, "{b:02x}");
+    }
+    hex
+}
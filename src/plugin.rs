@@ -0,0 +1,496 @@
+use crate::commands::WindowScopes;
+use crate::handler::EventLoopGuard;
+use crate::models::AdapterSelector;
+use crate::{
+    AdapterState, AuthorizationStatus, BatteryLevel, BleMetrics, BondChange, CallbackPanic,
+    ConnectionState, DisconnectReason, Handler, PairingConsentRequest, PairingEvent,
+    SetupNotification,
+};
+use futures::{Stream, StreamExt};
+use once_cell::sync::OnceCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tauri::{
+    async_runtime,
+    plugin::{Builder, TauriPlugin},
+    AppHandle, Emitter, ExitRequestApi, Manager, RunEvent, Runtime, WindowEvent, Wry,
+};
+use tokio::sync::Mutex;
+
+/// Event emitted to the frontend when a fingerprinted device changed its address
+pub const DEVICE_READDRESSED_EVENT: &str = "blec://device-readdressed";
+/// Event emitted to the frontend with the address of a device removed by the adapter during a scan
+pub const DEVICE_LOST_EVENT: &str = "blec://device-lost";
+/// Event emitted to the frontend with the [`BleMetrics`] if enabled with
+/// [`Handler::set_metrics_interval`]
+pub const METRICS_EVENT: &str = "blec://metrics";
+/// Event emitted to the frontend with the names of the running critical operations when an
+/// exit was delayed by [`Handler::set_exit_guard`]
+pub const EXIT_BLOCKED_EVENT: &str = "blec://exit-blocked";
+/// Event emitted to the frontend with the milliseconds since the last central event when the
+/// event loop stalled, see [`Handler::set_heartbeat_interval`]
+pub const EVENT_LOOP_STALLED_EVENT: &str = "blec://event-loop-stalled";
+/// Event emitted to the frontend with every [`SetupNotification`] of characteristics
+/// subscribed by a setup script
+pub const SETUP_NOTIFICATION_EVENT: &str = "blec://setup-notification";
+/// Event emitted to the frontend with every [`PairingEvent`]
+pub const PAIRING_EVENT: &str = "blec://pairing";
+/// Event emitted to the frontend with every change of the [`ConnectionState`] of a connection,
+/// with the address of the device
+pub const CONNECTION_STATE_EVENT: &str = "blec://connection-state";
+/// Event emitted to the frontend with the new MTU when the MTU of the connected device changed
+pub const MTU_CHANGED_EVENT: &str = "blec://mtu-changed";
+/// Event emitted to the frontend with every stage of a reconnect started with
+/// `reconnect_when_available`
+pub const RECONNECT_PROGRESS_EVENT: &str = "blec://reconnect-progress";
+/// Event emitted to the frontend with the new [`AuthorizationStatus`] when the user allowed or
+/// denied bluetooth for the app
+pub const AUTHORIZATION_CHANGED_EVENT: &str = "blec://authorization-changed";
+/// Event emitted to the frontend with the [`CallbackPanic`] when a callback passed to the
+/// handler panicked
+pub const ERROR_EVENT: &str = "blec://error";
+/// Event emitted to the frontend with the [`PairingConsentRequest`] when the OS asks to confirm
+/// a pairing, only on Windows
+pub const PAIRING_CONSENT_REQUIRED_EVENT: &str = "blec://pairing-consent-required";
+/// Event emitted to the frontend with the [`BondChange`] when the bond of a device was created
+/// or removed, only on android
+pub const BOND_CHANGED_EVENT: &str = "blec://bond-changed";
+/// Event emitted to the frontend with the new [`AdapterState`] when the adapter was powered
+/// off or on
+pub const ADAPTER_STATE_EVENT: &str = "blec://adapter-state";
+/// Event emitted to the frontend with the [`BatteryLevel`] when the battery monitor saw a new
+/// level, see [`Handler::start_battery_monitor`]
+pub const BATTERY_EVENT: &str = "blec://battery";
+/// Event emitted to the frontend with the [`BleDevice`] whenever a scan found a device or its
+/// advertisement changed, see [`Handler::start_discovery`]
+pub const DEVICE_DISCOVERED_EVENT: &str = "blec://device-discovered";
+/// Event emitted to the frontend with `{ correlationId, data }` while a scan started with the
+/// `scan` command runs, `data` is the device list of the scan whenever it changed
+pub const DEVICES_DISCOVERED_EVENT: &str = "blec://devices-discovered";
+/// Event emitted to the frontend with the [`crate::ScanEventStats`] when a scan with a
+/// [`crate::ScanEventBudget`] ended
+pub const SCAN_EVENT_STATS_EVENT: &str = "blec://scan-event-stats";
+/// Event emitted to the frontend with the [`crate::ForgetReport`] when a device was forgotten
+/// with [`Handler::forget_device`]
+pub const DEVICE_FORGOTTEN_EVENT: &str = "blec://device-forgotten";
+/// Event emitted to the frontend with `{ address }` after every successful connect, see
+/// [`crate::BleEvent::Connected`]
+pub const CONNECTED_EVENT: &str = "blec://connected";
+/// Event emitted to the frontend with `{ address, reason }` when a device disconnected, also
+/// with [`Handler::disconnect`], see [`crate::DisconnectReason`]
+pub const DISCONNECTED_EVENT: &str = "blec://disconnected";
+/// Event emitted to the frontend with the [`crate::ScanThrottleWarning`] when a scan was started
+/// close to the scan start limit of the OS, only on android
+pub const SCAN_THROTTLE_WARNING_EVENT: &str = "blec://scan-throttle-warning";
+/// Event emitted to the frontend with `{ retryAfterMs }` when a running scan received nothing
+/// and is likely throttled by the OS, only on android
+pub const SCAN_THROTTLED_EVENT: &str = "blec://scan-throttled";
+/// Event emitted to the frontend with the [`crate::ConnectionInterference`] when subscriptions
+/// of the connected device were found cleared, e.g. by another application using the device
+pub const CONNECTION_INTERFERED_EVENT: &str = "blec://connection-interfered";
+
+/// Payload of [`CONNECTED_EVENT`] and [`DISCONNECTED_EVENT`]
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ConnectionPayload {
+    address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<DisconnectReason>,
+}
+
+/// Payload of [`CONNECTION_STATE_EVENT`]
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ConnectionStatePayload {
+    address: String,
+    state: ConnectionState,
+}
+
+/// Payload of [`SCAN_THROTTLED_EVENT`]
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ScanThrottledPayload {
+    retry_after_ms: u64,
+}
+
+/// Lazily initialized state of the plugin, created by the first [`init_with`] or
+/// [`ensure_initialized`]
+static PLUGIN: OnceCell<PluginState> = OnceCell::new();
+
+#[derive(Default)]
+struct PluginState {
+    initialization: Initialization,
+    /// Adapter the handler is created with, see [`init_with_adapter`]
+    adapter: AdapterSelector,
+    handler: OnceCell<Handler>,
+    /// Serializes deferred initializations, so only one handler is created
+    init_lock: Mutex<()>,
+    /// App of the plugin, set once the plugin is set up
+    app: OnceCell<AppHandle<Wry>>,
+    /// Set once the background tasks of the handler are running
+    tasks_started: AtomicBool,
+    /// Event loop of the handler, running for the rest of the app
+    event_loop: OnceCell<EventLoopGuard>,
+}
+
+fn plugin_state() -> &'static PluginState {
+    PLUGIN.get_or_init(PluginState::default)
+}
+
+/// When the handler and with it the platform bluetooth manager are created, see
+/// [`init_with`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Initialization {
+    /// While the plugin is built, on macOS the OS permission prompt appears at app launch
+    /// Building the plugin inside an async runtime can not block, the handler is then created
+    /// like with [`Initialization::OnSetup`].
+    #[default]
+    Eager,
+    /// In the background once the plugin is set up, without blocking the thread building the
+    /// plugin. Commands called before it is done wait for it.
+    OnSetup,
+    /// With the first command or [`ensure_initialized`], whichever comes first
+    OnFirstUse,
+    /// Only with [`ensure_initialized`], commands called before fail with
+    /// [`crate::error::Error::HandlerNotInitialized`]
+    Explicit,
+}
+
+/// Initializes the plugin.
+/// # Panics
+/// Panics if the handler cannot be initialized.
+pub fn init() -> TauriPlugin<Wry> {
+    init_with(Initialization::Eager)
+}
+
+/// Initializes the plugin without blocking, the handler is created in the background once the
+/// plugin is set up, see [`Initialization::OnSetup`]
+/// Safe to call from async code, e.g. integration tests running inside a tokio runtime.
+/// # Example
+/// ```no_run
+/// # async fn build() {
+/// // inside a tokio runtime, where `init()` could not block
+/// let builder = tauri::Builder::default().plugin(tauri_plugin_blec::init_async());
+/// # }
+/// ```
+pub fn init_async() -> TauriPlugin<Wry> {
+    init_with(Initialization::OnSetup)
+}
+
+/// Initializes the plugin with the adapter matching the selector instead of the first one of
+/// the system, see [`list_adapters`]
+/// The adapter can be switched later on with [`Handler::select_adapter`].
+/// # Panics
+/// Panics if the handler cannot be initialized, also if no adapter matches.
+/// # Example
+/// ```no_run
+/// use tauri_plugin_blec::models::AdapterSelector;
+/// tauri::Builder::default()
+///     .plugin(tauri_plugin_blec::init_with_adapter(AdapterSelector::Name("hci1".into())));
+/// ```
+pub fn init_with_adapter(selector: AdapterSelector) -> TauriPlugin<Wry> {
+    build(Initialization::Eager, selector)
+}
+
+/// Initializes the plugin, deferring the creation of the handler unless
+/// [`Initialization::Eager`] is given
+/// Deferring lets the app show the OS bluetooth permission prompt at a moment it controls,
+/// e.g. after an onboarding screen explained why bluetooth is needed.
+/// # Panics
+/// Panics if the handler cannot be initialized with [`Initialization::Eager`].
+/// # Example
+/// ```no_run
+/// use tauri_plugin_blec::Initialization;
+/// tauri::Builder::default()
+///     .plugin(tauri_plugin_blec::init_with(Initialization::Explicit));
+/// // later, when the app is ready for the permission prompt
+/// tauri::async_runtime::block_on(async {
+///     let handler = tauri_plugin_blec::ensure_initialized().await.unwrap();
+/// });
+/// ```
+pub fn init_with(initialization: Initialization) -> TauriPlugin<Wry> {
+    build(initialization, AdapterSelector::default())
+}
+
+fn build(initialization: Initialization, adapter: AdapterSelector) -> TauriPlugin<Wry> {
+    let initialization = if initialization == Initialization::Eager
+        && tokio::runtime::Handle::try_current().is_ok()
+    {
+        // block_on panics inside a runtime, e.g. when the plugin is built in async tests
+        tracing::debug!("plugin built inside an async runtime, initializing on setup");
+        Initialization::OnSetup
+    } else {
+        initialization
+    };
+    let state = PLUGIN.get_or_init(|| PluginState {
+        initialization,
+        adapter,
+        ..PluginState::default()
+    });
+    if state.initialization == Initialization::Eager && state.handler.get().is_none() {
+        let handler = async_runtime::block_on(Handler::with_adapter(&state.adapter))
+            .expect("failed to initialize handler");
+        let _ = state.handler.set(handler);
+    }
+
+    #[allow(unused)]
+    Builder::new("blec")
+        .invoke_handler(crate::commands::commands())
+        .on_event(|app, event| match event {
+            RunEvent::WindowEvent { label, event, .. } => {
+                on_window_event(app, label, event);
+            }
+            RunEvent::ExitRequested { api, code, .. } => {
+                guard_exit(app, api, *code);
+            }
+            _ => {}
+        })
+        .setup(|app, api| {
+            #[cfg(target_os = "android")]
+            crate::android::init(app, api)?;
+            app.manage(crate::commands::Captures::default());
+            app.manage(WindowScopes::new(|| get_handler().ok()));
+            let state = plugin_state();
+            let _ = state.app.set(app.clone());
+            if state.handler.get().is_some() {
+                start_tasks(app.clone());
+            } else if state.initialization == Initialization::OnSetup {
+                async_runtime::spawn(async {
+                    if let Err(e) = ensure_initialized().await {
+                        tracing::error!("failed to initialize the BLE handler: {e}");
+                    }
+                });
+            }
+            Ok(())
+        })
+        .build()
+}
+
+/// Starts forwarding the events of the handler to the frontend, once the plugin is set up
+/// and the handler is initialized
+fn start_tasks(app: AppHandle<Wry>) {
+    let state = plugin_state();
+    if state.tasks_started.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    let handler = get_handler().expect("failed to get handler");
+    app.manage(handler.mirror());
+    let events = handler
+        .spawn_event_loop()
+        .expect("failed to start the event loop");
+    let _ = state.event_loop.set(events);
+    async_runtime::spawn(async move {
+        async_runtime::spawn(forward_stream(
+            app.clone(),
+            SETUP_NOTIFICATION_EVENT,
+            handler.setup_notifications(),
+        ));
+        async_runtime::spawn(forward_stream(app.clone(), PAIRING_EVENT, handler.pairing_events()));
+        async_runtime::spawn(forward_stream(
+            app.clone(),
+            EVENT_LOOP_STALLED_EVENT,
+            handler.event_loop_stalls(),
+        ));
+        async_runtime::spawn(forward_stream(
+            app.clone(),
+            DEVICE_READDRESSED_EVENT,
+            handler.readdressed_devices(),
+        ));
+        async_runtime::spawn(forward_stream(app.clone(), METRICS_EVENT, handler.metrics_reports()));
+        let states = handler
+            .connection_states()
+            .map(|(address, state)| ConnectionStatePayload { address, state });
+        async_runtime::spawn(forward_stream(app.clone(), CONNECTION_STATE_EVENT, states));
+        async_runtime::spawn(forward_stream(app.clone(), MTU_CHANGED_EVENT, handler.mtu_changes()));
+        async_runtime::spawn(forward_stream(
+            app.clone(),
+            RECONNECT_PROGRESS_EVENT,
+            handler.reconnect_progress(),
+        ));
+        async_runtime::spawn(forward_stream(
+            app.clone(),
+            AUTHORIZATION_CHANGED_EVENT,
+            handler.authorization_changes(),
+        ));
+        async_runtime::spawn(forward_stream(app.clone(), ERROR_EVENT, handler.callback_panics()));
+        async_runtime::spawn(forward_stream(
+            app.clone(),
+            PAIRING_CONSENT_REQUIRED_EVENT,
+            handler.pairing_consent_requests(),
+        ));
+        async_runtime::spawn(forward_stream(
+            app.clone(),
+            BOND_CHANGED_EVENT,
+            handler.bond_changes(),
+        ));
+        async_runtime::spawn(forward_stream(
+            app.clone(),
+            ADAPTER_STATE_EVENT,
+            handler.adapter_states(),
+        ));
+        async_runtime::spawn(forward_stream(app.clone(), BATTERY_EVENT, handler.battery_levels()));
+        async_runtime::spawn(forward_stream(
+            app.clone(),
+            DEVICE_DISCOVERED_EVENT,
+            handler.discovered_devices(),
+        ));
+        async_runtime::spawn(forward_stream(
+            app.clone(),
+            SCAN_EVENT_STATS_EVENT,
+            handler.scan_event_stats(),
+        ));
+        async_runtime::spawn(forward_stream(
+            app.clone(),
+            DEVICE_FORGOTTEN_EVENT,
+            handler.forgotten_devices(),
+        ));
+        let connects = handler.connects().map(|address| ConnectionPayload {
+            address,
+            reason: None,
+        });
+        async_runtime::spawn(forward_stream(app.clone(), CONNECTED_EVENT, connects));
+        let disconnections = handler
+            .disconnections()
+            .map(|(address, reason)| ConnectionPayload {
+                address,
+                reason: Some(reason),
+            });
+        async_runtime::spawn(forward_stream(app.clone(), DISCONNECTED_EVENT, disconnections));
+        async_runtime::spawn(forward_stream(
+            app.clone(),
+            SCAN_THROTTLE_WARNING_EVENT,
+            handler.scan_throttle_warnings(),
+        ));
+        let throttles = handler
+            .scan_throttles()
+            .map(|retry_after_ms| ScanThrottledPayload { retry_after_ms });
+        async_runtime::spawn(forward_stream(app.clone(), SCAN_THROTTLED_EVENT, throttles));
+        async_runtime::spawn(forward_stream(
+            app.clone(),
+            CONNECTION_INTERFERED_EVENT,
+            handler.connection_interferences(),
+        ));
+        forward_stream(app, DEVICE_LOST_EVENT, handler.removed_devices()).await;
+    });
+}
+
+/// Creates the handler if it was deferred with [`init_with`]
+/// Creating the platform bluetooth manager shows the OS permission prompt on macOS, so this
+/// should be called when the app is ready for the prompt. Returns the existing handler if it
+/// is already initialized.
+/// # Errors
+/// Returns an error if the bluetooth manager or adapter can not be created
+pub async fn ensure_initialized() -> crate::error::Result<&'static Handler> {
+    let state = plugin_state();
+    if let Some(handler) = state.handler.get() {
+        return Ok(handler);
+    }
+    let _init = state.init_lock.lock().await;
+    if let Some(handler) = state.handler.get() {
+        return Ok(handler);
+    }
+    tracing::info!("initializing the BLE handler");
+    let handler = Handler::with_adapter(&state.adapter).await?;
+    let handler = state.handler.get_or_init(|| handler);
+    if let Some(app) = state.app.get() {
+        start_tasks(app.clone());
+    }
+    Ok(handler)
+}
+
+/// Returns the handler for a command, initializing it with [`Initialization::OnFirstUse`] and
+/// waiting for it with [`Initialization::OnSetup`]
+pub(crate) async fn command_handler() -> crate::error::Result<&'static Handler> {
+    let Some(state) = PLUGIN.get() else {
+        return Err(crate::error::Error::HandlerNotInitialized);
+    };
+    match state.handler.get() {
+        Some(handler) => Ok(handler),
+        None if matches!(
+            state.initialization,
+            Initialization::OnFirstUse | Initialization::OnSetup
+        ) =>
+        {
+            ensure_initialized().await
+        }
+        None => Err(crate::error::Error::HandlerNotInitialized),
+    }
+}
+
+/// Returns the BLE handler to use blec from rust.
+/// # Errors
+/// Returns an error if the handler is not initialized, see [`ensure_initialized`].
+pub fn get_handler() -> crate::error::Result<&'static Handler> {
+    PLUGIN
+        .get()
+        .and_then(|state| state.handler.get())
+        .ok_or(crate::error::Error::HandlerNotInitialized)
+}
+
+/// Detaches the subscriptions of a destroyed window, they would be processed forever otherwise
+/// Returns the task releasing them, `None` for other events and windows without subscriptions.
+pub(crate) fn on_window_event<R: Runtime>(
+    app: &AppHandle<R>,
+    label: &str,
+    event: &WindowEvent,
+) -> Option<async_runtime::JoinHandle<()>> {
+    if !matches!(event, WindowEvent::Destroyed) {
+        return None;
+    }
+    app.try_state::<WindowScopes>()?.detach(label)
+}
+
+/// Delays the exit while critical operations run if enabled with [`Handler::set_exit_guard`]
+fn guard_exit(app: &AppHandle<Wry>, api: &ExitRequestApi, code: Option<i32>) {
+    let Ok(handler) = get_handler() else {
+        return;
+    };
+    let Some(timeout) = handler.exit_guard() else {
+        return;
+    };
+    // the exit after the wait must not be delayed again
+    if !handler.has_critical_operation() || !handler.delay_exit() {
+        return;
+    }
+    api.prevent_exit();
+    let names = handler.critical_operation_names();
+    tracing::warn!("delaying exit until critical operations finish: {names:?}");
+    if let Err(e) = app.emit(EXIT_BLOCKED_EVENT, names) {
+        tracing::warn!("failed to emit {EXIT_BLOCKED_EVENT}: {e}");
+    }
+    let app = app.clone();
+    async_runtime::spawn(async move {
+        if !handler.wait_for_critical_operations(timeout).await {
+            tracing::error!("critical operations did not finish in time, exiting anyway");
+        }
+        app.exit(code.unwrap_or(0));
+    });
+}
+
+/// Emits every item of the stream to the frontend
+async fn forward_stream<T: serde::Serialize + Clone>(
+    app: AppHandle<Wry>,
+    event: &'static str,
+    stream: impl Stream<Item = T>,
+) {
+    let mut stream = std::pin::pin!(stream);
+    while let Some(payload) = stream.next().await {
+        if let Err(e) = app.emit(event, payload) {
+            tracing::warn!("failed to emit {event}: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // block_on inside a runtime panics, so building the plugin must not create the handler
+    #[tokio::test]
+    async fn builds_the_plugin_inside_a_runtime() {
+        let _ = init();
+        let _ = init_async();
+        let state = PLUGIN.get().expect("building the plugin creates its state");
+        assert_eq!(state.initialization, Initialization::OnSetup);
+        assert!(state.handler.get().is_none());
+    }
+}
@@ -0,0 +1,90 @@
+use btleplug::api::{Characteristic, Descriptor, Peripheral as _};
+use tracing::debug;
+
+use crate::error::Error;
+use crate::handler::Handler;
+use crate::models::WriteType;
+
+/// Operation passed to [`Handler::raw_gatt`]
+/// The operations take the [`Characteristic`] and [`Descriptor`] values of the platform backend,
+/// e.g. from [`Handler::characteristics`], instead of looking them up by uuid and service.
+/// This reaches characteristics a [`crate::models::CharacteristicRef`] can not tell apart, like
+/// two characteristics with the same uuid in one service.
+/// The backends do not expose attribute handles, so operations on handle ranges like read by
+/// type are not available.
+///
+/// **Unstable:** variants may change or be removed once typed APIs cover them, without a major
+/// version bump.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum RawGattOp {
+    Read(Characteristic),
+    Write {
+        characteristic: Characteristic,
+        data: Vec<u8>,
+        write_type: WriteType,
+    },
+    Subscribe(Characteristic),
+    Unsubscribe(Characteristic),
+    ReadDescriptor(Descriptor),
+    WriteDescriptor { descriptor: Descriptor, data: Vec<u8> },
+}
+
+impl Handler {
+    /// Runs a GATT operation directly on the device of [`Handler::connect`]
+    /// Escape hatch for procedures the typed API does not cover yet. The operation waits in the
+    /// queue of the device like every other operation, but skips the characteristic options,
+    /// the retries, the metrics and the session timeline. Subscribing does not add a listener,
+    /// the notifications are published as [`crate::BleEvent::Notification`] and reach the
+    /// listeners already added for the uuid of the characteristic.
+    /// Returns the value for reads and an empty vector for all other operations.
+    ///
+    /// **Unstable:** only available with the `raw-gatt` feature, the API may change without a
+    /// major version bump.
+    /// # Errors
+    /// Returns an error if no device is connected, the connection is not ready or the backend
+    /// fails the operation
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// use uuid::{Uuid,uuid};
+    /// use tauri_plugin_blec::RawGattOp;
+    /// const CHARACTERISTIC_UUID: Uuid = uuid!("51FF12BB-3ED8-46E5-B4F9-D64E2FEC021B");
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     // the second characteristic with the uuid, in the same service as the first one
+    ///     let c = handler
+    ///         .characteristics()
+    ///         .await
+    ///         .into_iter()
+    ///         .filter(|c| c.uuid == CHARACTERISTIC_UUID)
+    ///         .nth(1)
+    ///         .unwrap();
+    ///     let value = handler.raw_gatt(RawGattOp::Read(c)).await.unwrap();
+    ///     println!("{value:?}");
+    /// });
+    /// # }
+    /// ```
+    pub async fn raw_gatt(&self, op: RawGattOp) -> Result<Vec<u8>, Error> {
+        let connection = self.main_connection().ok_or(Error::NoDeviceConnected)?;
+        let dev = self.lock_ready(&connection).await?;
+        let dev = dev.as_ref().ok_or(Error::NoDeviceConnected)?;
+        debug!("raw gatt operation {op:?}");
+        match op {
+            RawGattOp::Read(c) => return Ok(dev.read(&c).await?),
+            RawGattOp::Write {
+                characteristic,
+                data,
+                write_type,
+            } => dev.write(&characteristic, &data, write_type.into()).await?,
+            RawGattOp::Subscribe(c) => dev.subscribe(&c).await?,
+            RawGattOp::Unsubscribe(c) => dev.unsubscribe(&c).await?,
+            RawGattOp::ReadDescriptor(d) => return Ok(dev.read_descriptor(&d).await?),
+            RawGattOp::WriteDescriptor { descriptor, data } => {
+                dev.write_descriptor(&descriptor, &data).await?;
+            }
+        }
+        Ok(vec![])
+    }
+}
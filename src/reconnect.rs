@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::future::{select, Either};
+use futures::StreamExt;
+use tokio::sync::Notify;
+use tokio::time::timeout;
+use tracing::{debug, info, warn};
+
+use crate::discovery::ScanGuard;
+use crate::error::Error;
+use crate::events::BleEvent;
+use crate::handler::{ConnectOptions, ConnectResult, Handler, ScanSettings};
+use crate::models::{BleDevice, ScanFilter, ScanNameFilter};
+
+/// Running waits of [`Handler::wait_for_device`] and [`Handler::reconnect_when_available`]
+pub(crate) type PendingWaits = Mutex<HashMap<String, Arc<Notify>>>;
+
+/// Stage of [`Handler::reconnect_when_available`] or an automatic reconnect of
+/// [`crate::ConnectOptions::reconnect`], published as [`BleEvent::ReconnectProgress`]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase", tag = "stage")]
+pub enum ReconnectProgress {
+    /// Scanning until the device advertises again
+    WaitingForDevice { address: String },
+    /// The device advertised, under a new address if it was matched by its fingerprint
+    DeviceFound { address: String },
+    Connecting { address: String },
+    /// Connected and the setup script finished
+    Connected { address: String },
+    Failed { address: String, error: String },
+    /// Cancelled with [`Handler::cancel_wait_for_device`]
+    Cancelled { address: String },
+}
+
+impl Handler {
+    /// Scans until the device with the address advertises again and returns its advertisement
+    /// A device with a fingerprint, see [`Handler::set_device_fingerprint`], is also found
+    /// under a new address. The scan is stopped when the device was found, the time is up,
+    /// the wait is cancelled with [`Handler::cancel_wait_for_device`] or the future is dropped.
+    /// # Errors
+    /// Returns [`Error::DeviceNotFound`] if the device did not advertise in time,
+    /// [`Error::WaitCancelled`] if the wait was cancelled or an error if the scan fails
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     let device = handler.wait_for_device("00:00:00:00:00:00", 30_000).await.unwrap();
+    ///     println!("{} is back", device.address);
+    /// });
+    /// # }
+    /// ```
+    pub async fn wait_for_device(
+        &'static self,
+        address: &str,
+        timeout_ms: u64,
+    ) -> Result<BleDevice, Error> {
+        let address = &self.parse_address(address)?;
+        let cancel = Cancellation::register(&self.pending_waits, address);
+        self.wait_until_found(address, Duration::from_millis(timeout_ms), &cancel.notify)
+            .await
+    }
+
+    /// Waits until the device advertises again with [`Handler::wait_for_device`] and connects
+    /// to it with [`Handler::connect_with_options`], including its setup script
+    /// `timeout_ms` caps the whole call, the connect gets the time left after the device was
+    /// found or [`ConnectOptions::total_timeout_ms`] if that is shorter.
+    /// Every stage is published as [`BleEvent::ReconnectProgress`]. Cancelling with
+    /// [`Handler::cancel_wait_for_device`] also aborts a connect in progress and closes a link
+    /// that was already established.
+    /// # Errors
+    /// Returns the errors of [`Handler::wait_for_device`] and [`Handler::connect_with_options`]
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// use tauri_plugin_blec::ConnectOptions;
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     let address = "00:00:00:00:00:00";
+    ///     // the device reboots and advertises again after a few seconds
+    ///     handler
+    ///         .reconnect_when_available(address, 30_000, None, ConnectOptions::default())
+    ///         .await
+    ///         .unwrap();
+    /// });
+    /// # }
+    /// ```
+    pub async fn reconnect_when_available(
+        &'static self,
+        address: &str,
+        timeout_ms: u64,
+        on_disconnect: Option<Box<dyn Fn() + Send>>,
+        mut options: ConnectOptions,
+    ) -> Result<ConnectResult, Error> {
+        let address = &self.parse_address(address)?;
+        let start = Instant::now();
+        let total = Duration::from_millis(timeout_ms);
+        let cancel = Cancellation::register(&self.pending_waits, address);
+        self.publish_progress(ReconnectProgress::WaitingForDevice {
+            address: address.to_string(),
+        });
+        let device = match self.wait_until_found(address, total, &cancel.notify).await {
+            Ok(device) => device,
+            Err(e) => return Err(self.reconnect_failed(address, e)),
+        };
+        let found = device.address;
+        self.publish_progress(ReconnectProgress::DeviceFound {
+            address: found.clone(),
+        });
+
+        let left =
+            u64::try_from(total.saturating_sub(start.elapsed()).as_millis()).unwrap_or(u64::MAX);
+        options.total_timeout_ms = Some(options.total_timeout_ms.map_or(left, |t| t.min(left)));
+        self.publish_progress(ReconnectProgress::Connecting {
+            address: found.clone(),
+        });
+        // boxed, so a cancelled connect is dropped before the disconnect needs its locks
+        let connect = Box::pin(self.connect_with_options(&found, on_disconnect, options));
+        let cancelled = std::pin::pin!(cancel.notify.notified());
+        match select(connect, cancelled).await {
+            Either::Left((Ok(result), _)) => {
+                info!("reconnected to {found}");
+                self.publish_progress(ReconnectProgress::Connected { address: found });
+                Ok(result)
+            }
+            Either::Left((Err(e), _)) => Err(self.reconnect_failed(&found, e)),
+            Either::Right(((), connect)) => {
+                drop(connect);
+                if let Some(connection) = self.connection(&found) {
+                    if connection.link().is_some() {
+                        debug!("closing the link to {found} after the reconnect was cancelled");
+                        if let Err(e) = self.disconnect_connection(&connection, false).await {
+                            warn!("disconnect after cancelled reconnect failed: {e}");
+                        }
+                    }
+                    // the dropped connect did not clean up the connection
+                    self.drop_connection(&connection);
+                }
+                Err(self.reconnect_failed(&found, Error::WaitCancelled(found.clone())))
+            }
+        }
+    }
+
+    /// Cancels [`Handler::wait_for_device`] or [`Handler::reconnect_when_available`] for the
+    /// address
+    /// Returns false if no wait for the address is running
+    pub fn cancel_wait_for_device(&self, address: &str) -> bool {
+        let Ok(address) = self.parse_address(address) else {
+            return false;
+        };
+        let Some(notify) = self
+            .pending_waits
+            .lock()
+            .expect("pending waits lock poisoned")
+            .remove(&address)
+        else {
+            return false;
+        };
+        notify.notify_one();
+        true
+    }
+
+    async fn wait_until_found(
+        &'static self,
+        address: &str,
+        wait: Duration,
+        cancel: &Notify,
+    ) -> Result<BleDevice, Error> {
+        let fingerprint = self.fingerprint_of(address).await;
+        // subscribed before the scan starts, so the first advertisement is not missed
+        let devices = self.discovered_devices();
+        let timeout_ms = u64::try_from(wait.as_millis()).unwrap_or(u64::MAX);
+        let generation = self
+            .start_scan_loop(
+                None,
+                Some(timeout_ms),
+                ScanFilter::None,
+                ScanNameFilter::None,
+                ScanSettings::default(),
+            )
+            .await?;
+        let scan = ScanGuard::new(self, generation);
+        debug!("waiting for {address} to advertise");
+        let found = async {
+            let mut devices = std::pin::pin!(devices);
+            while let Some(device) = devices.next().await {
+                if device.address == address
+                    || (fingerprint.is_some() && device.fingerprint == fingerprint)
+                {
+                    return Some(device);
+                }
+            }
+            None
+        };
+        let found = std::pin::pin!(timeout(wait, found));
+        let cancelled = std::pin::pin!(cancel.notified());
+        let result = match select(found, cancelled).await {
+            Either::Left((Ok(Some(device)), _)) => Ok(device),
+            Either::Left(_) => Err(Error::DeviceNotFound(address.to_string())),
+            Either::Right(_) => Err(Error::WaitCancelled(address.to_string())),
+        };
+        scan.stop().await;
+        result
+    }
+
+    fn publish_progress(&self, progress: ReconnectProgress) {
+        self.publish(BleEvent::ReconnectProgress(progress));
+    }
+
+    /// Publishes the failed or cancelled reconnect and passes the error on
+    fn reconnect_failed(&self, address: &str, e: Error) -> Error {
+        warn!("reconnect to {address} failed: {e}");
+        let address = address.to_string();
+        self.publish_progress(if matches!(e, Error::WaitCancelled(_)) {
+            ReconnectProgress::Cancelled { address }
+        } else {
+            ReconnectProgress::Failed {
+                address,
+                error: e.to_string(),
+            }
+        });
+        e
+    }
+}
+
+/// Registration of a running wait, removed again when the wait ends
+struct Cancellation<'a> {
+    waits: &'a PendingWaits,
+    address: String,
+    notify: Arc<Notify>,
+}
+
+impl<'a> Cancellation<'a> {
+    fn register(waits: &'a PendingWaits, address: &str) -> Self {
+        let notify = Arc::new(Notify::new());
+        let replaced = waits
+            .lock()
+            .expect("pending waits lock poisoned")
+            .insert(address.to_string(), notify.clone());
+        // only one wait per address, the older one is cancelled
+        if let Some(replaced) = replaced {
+            replaced.notify_one();
+        }
+        Self {
+            waits,
+            address: address.to_string(),
+            notify,
+        }
+    }
+}
+
+impl Drop for Cancellation<'_> {
+    fn drop(&mut self) {
+        let mut waits = self.waits.lock().expect("pending waits lock poisoned");
+        if waits
+            .get(&self.address)
+            .is_some_and(|n| Arc::ptr_eq(n, &self.notify))
+        {
+            waits.remove(&self.address);
+        }
+    }
+}
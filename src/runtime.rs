@@ -0,0 +1,6 @@
+/// Tasks of the handler run on the tauri runtime with the `tauri` feature, so they can be
+/// spawned outside of an async context, and on the tokio runtime of the caller without it
+#[cfg(feature = "tauri")]
+pub(crate) use tauri::async_runtime::{spawn, spawn_blocking, JoinHandle};
+#[cfg(not(feature = "tauri"))]
+pub(crate) use tokio::task::{spawn, spawn_blocking, JoinHandle};
@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::models::BleDevice;
+
+/// Limits the device events a scan publishes, see [`crate::DiscoverOptions::event_budget`] and
+/// [`crate::Handler::start_discovery_with_event_budget`]
+/// Changes that exceed the budget wait per device and only the latest state of a device is
+/// published on a later tick. New devices go first, then devices whose advertisement changed
+/// and RSSI-only updates last. Removals are never held back but count against the budget. The
+/// devices sent to the channel of [`crate::Handler::discover`] are not limited, they are sent as
+/// one list per scan interval.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct ScanEventBudget {
+    /// Device events published per second at most, removals included
+    pub max_events_per_second: u32,
+}
+
+/// How a scan with a [`ScanEventBudget`] coalesced its events, published as
+/// [`crate::BleEvent::ScanEventStats`] when the scan ends
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct ScanEventStats {
+    /// Device events published, removals included
+    #[cfg_attr(feature = "ts-export", ts(type = "number"))]
+    pub published: u64,
+    /// Updates replaced by a newer state of the same device before they were published
+    #[cfg_attr(feature = "ts-export", ts(type = "number"))]
+    pub coalesced: u64,
+    /// Updates that waited for a later tick of the budget
+    #[cfg_attr(feature = "ts-export", ts(type = "number"))]
+    pub delayed: u64,
+    /// Updates still waiting when the device was removed or the scan ended, they were never
+    /// published
+    #[cfg_attr(feature = "ts-export", ts(type = "number"))]
+    pub dropped: u64,
+    /// Most updates waiting at the same time
+    pub max_pending: usize,
+}
+
+/// Order changes are published in when the budget is exhausted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Priority {
+    /// First event of the device during the scan
+    Added,
+    /// Something besides the RSSI changed
+    Changed,
+    RssiOnly,
+}
+
+/// Update of a device waiting for the budget
+struct Pending {
+    device: BleDevice,
+    /// Tick the first coalesced update arrived in, older updates are published first
+    since: u64,
+    delayed: bool,
+}
+
+/// Token bucket over the device events of one scan
+/// Tokens refill at the budgeted rate and at most one second worth of events is published at
+/// once. Updates that do not fit wait per device, a newer update replaces the waiting one.
+pub(crate) struct EventBudget {
+    rate: f64,
+    tokens: f64,
+    refilled: Instant,
+    tick: u64,
+    /// Last published state of every device
+    published: HashMap<String, BleDevice>,
+    pending: HashMap<String, Pending>,
+    stats: ScanEventStats,
+}
+
+impl EventBudget {
+    pub(crate) fn new(budget: ScanEventBudget) -> Self {
+        let rate = f64::from(budget.max_events_per_second.max(1));
+        Self {
+            rate,
+            tokens: rate,
+            refilled: Instant::now(),
+            tick: 0,
+            published: HashMap::new(),
+            pending: HashMap::new(),
+            stats: ScanEventStats::default(),
+        }
+    }
+
+    /// Takes the devices that changed in this tick and the addresses the scan lost, returns the
+    /// devices to publish
+    /// Removals are never held back, they use up the budget before any update.
+    pub(crate) fn admit(&mut self, changed: Vec<BleDevice>, removed: &[String]) -> Vec<BleDevice> {
+        self.refill();
+        self.tick += 1;
+        for address in removed {
+            self.published.remove(address);
+            if self.pending.remove(address).is_some() {
+                self.stats.dropped += 1;
+            }
+            self.take_token();
+            self.stats.published += 1;
+        }
+        for device in changed {
+            let since = match self.pending.remove(&device.address) {
+                Some(previous) => {
+                    self.stats.coalesced += 1;
+                    previous.since
+                }
+                None => self.tick,
+            };
+            self.pending.insert(
+                device.address.clone(),
+                Pending {
+                    device,
+                    since,
+                    delayed: false,
+                },
+            );
+        }
+        let mut waiting: Vec<(Priority, u64, String)> = self
+            .pending
+            .iter()
+            .map(|(address, p)| (self.priority(&p.device), p.since, address.clone()))
+            .collect();
+        waiting.sort();
+        let mut admitted = vec![];
+        for (_, _, address) in waiting {
+            if self.tokens < 1.0 {
+                break;
+            }
+            self.take_token();
+            let pending = self.pending.remove(&address).expect("pending update missing");
+            if pending.delayed {
+                self.stats.delayed += 1;
+            }
+            self.published.insert(address, pending.device.clone());
+            admitted.push(pending.device);
+        }
+        for pending in self.pending.values_mut() {
+            pending.delayed = true;
+        }
+        self.stats.published += admitted.len() as u64;
+        self.stats.max_pending = self.stats.max_pending.max(self.pending.len());
+        admitted
+    }
+
+    /// Ends the scan, the updates still waiting are dropped
+    pub(crate) fn finish(mut self) -> ScanEventStats {
+        self.stats.dropped += self.pending.len() as u64;
+        self.stats
+    }
+
+    fn priority(&self, device: &BleDevice) -> Priority {
+        let Some(published) = self.published.get(&device.address) else {
+            return Priority::Added;
+        };
+        let mut rssi_only = device.clone();
+        rssi_only.rssi = published.rssi;
+        if rssi_only == *published {
+            Priority::RssiOnly
+        } else {
+            Priority::Changed
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.refilled).min(Duration::from_secs(1));
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * self.rate).min(self.rate);
+        self.refilled = now;
+    }
+
+    fn take_token(&mut self) {
+        self.tokens = (self.tokens - 1.0).max(0.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(address: &str, name: &str, rssi: i16) -> BleDevice {
+        BleDevice {
+            address: address.to_string(),
+            name: name.to_string(),
+            is_connected: false,
+            manufacturer_data: HashMap::new(),
+            services: vec![],
+            service_data: HashMap::new(),
+            device_class: None,
+            br_edr_capable: None,
+            rssi: Some(rssi),
+            advertisement_count: None,
+            adv_interval_ms: None,
+            fingerprint: None,
+            extra: None,
+            previously_connected: false,
+            adapter_id: None,
+            adapters: vec![],
+            appearance: None,
+            appearance_name: None,
+        }
+    }
+
+    fn budget(max_events_per_second: u32) -> EventBudget {
+        EventBudget::new(ScanEventBudget {
+            max_events_per_second,
+        })
+    }
+
+    fn addresses(devices: &[BleDevice]) -> Vec<&str> {
+        devices.iter().map(|d| d.address.as_str()).collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn limits_the_events_to_the_rate() {
+        let mut budget = budget(2);
+        let changed = vec![device("A", "a", -50), device("B", "b", -50), device("C", "c", -50)];
+        assert_eq!(budget.admit(changed, &[]).len(), 2);
+        assert!(budget.admit(vec![], &[]).is_empty());
+        tokio::time::advance(Duration::from_millis(500)).await;
+        assert_eq!(addresses(&budget.admit(vec![], &[])), ["C"]);
+        let stats = budget.finish();
+        assert_eq!(stats.published, 3);
+        assert_eq!(stats.delayed, 1);
+        assert_eq!(stats.max_pending, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn refills_at_most_one_second_of_events() {
+        let mut budget = budget(2);
+        tokio::time::advance(Duration::from_secs(10)).await;
+        let changed = (0..5).map(|i| device(&i.to_string(), "d", -50)).collect();
+        assert_eq!(budget.admit(changed, &[]).len(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn publishes_only_the_latest_state_of_a_waiting_device() {
+        let mut budget = budget(1);
+        budget.admit(vec![device("A", "a", -50)], &[]);
+        budget.admit(vec![device("B", "b", -60)], &[]);
+        budget.admit(vec![device("B", "b", -70)], &[]);
+        tokio::time::advance(Duration::from_secs(1)).await;
+        let admitted = budget.admit(vec![], &[]);
+        assert_eq!(admitted, [device("B", "b", -70)]);
+        let stats = budget.finish();
+        assert_eq!(stats.coalesced, 1);
+        assert_eq!(stats.delayed, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn publishes_new_devices_before_changes_and_rssi_updates() {
+        let mut budget = budget(2);
+        budget.admit(vec![device("A", "a", -50), device("B", "b", -50)], &[]);
+        tokio::time::advance(Duration::from_secs(1)).await;
+        let changed = vec![
+            device("A", "a", -60),
+            device("B", "renamed", -50),
+            device("C", "c", -50),
+        ];
+        assert_eq!(addresses(&budget.admit(changed, &[])), ["C", "B"]);
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert_eq!(addresses(&budget.admit(vec![], &[])), ["A"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn removals_are_not_held_back() {
+        let mut budget = budget(1);
+        budget.admit(vec![device("A", "a", -50)], &[]);
+        assert!(budget.admit(vec![device("B", "b", -50)], &[]).is_empty());
+        // removals are published without a token left and take the tokens of waiting updates
+        budget.admit(vec![], &["A".to_string()]);
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert!(budget.admit(vec![], &["C".to_string()]).is_empty());
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert_eq!(addresses(&budget.admit(vec![], &[])), ["B"]);
+        assert_eq!(budget.finish().published, 4);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn removing_a_waiting_device_drops_its_update() {
+        let mut budget = budget(1);
+        budget.admit(vec![device("A", "a", -50), device("B", "b", -50)], &[]);
+        budget.admit(vec![], &["B".to_string()]);
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert!(budget.admit(vec![], &[]).is_empty());
+        let stats = budget.finish();
+        assert_eq!(stats.dropped, 1);
+        assert_eq!(stats.coalesced, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn updates_waiting_at_the_end_are_dropped() {
+        let mut budget = budget(1);
+        let changed = vec![device("A", "a", -50), device("B", "b", -50), device("C", "c", -50)];
+        budget.admit(changed, &[]);
+        budget.admit(vec![], &["C".to_string()]);
+        let stats = budget.finish();
+        assert_eq!(stats.published, 2);
+        assert_eq!(stats.dropped, 2);
+        assert_eq!(stats.max_pending, 2);
+    }
+}
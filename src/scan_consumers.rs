@@ -0,0 +1,373 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use futures::future::{select, Either};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::mpsc;
+use tokio::time::{sleep, Instant};
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::discovery::DiscoveredEvent;
+use crate::error::Error;
+use crate::events::BleEvent;
+use crate::handler::{Handler, ScanSettings};
+use crate::models::{BleDevice, ScanFilter, ScanNameFilter};
+use crate::runtime;
+use crate::scan_throttle::ScanStart;
+
+/// How often the shared scan is checked, restarted once another scan ended and narrowed if a
+/// restart was postponed
+const SHARED_SCAN_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Consumers registered with [`Handler::register_scan_consumer`]
+#[derive(Default)]
+pub(crate) struct ScanConsumers {
+    next_id: u64,
+    consumers: HashMap<u64, Consumer>,
+    /// Generation and services of the shared scan, empty for all devices, `None` if it was not
+    /// started
+    scan: Option<(u64, Vec<Uuid>)>,
+    /// Restarts the shared scan while there are consumers
+    supervisor: Option<runtime::JoinHandle<()>>,
+}
+
+struct Consumer {
+    services: Vec<Uuid>,
+    task: runtime::JoinHandle<()>,
+}
+
+/// Identifies a consumer registered with [`Handler::register_scan_consumer`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(transparent)]
+pub struct ScanConsumerId(#[cfg_attr(feature = "ts-export", ts(type = "number"))] u64);
+
+/// What a consumer of the shared scan is reported, see [`Handler::register_scan_consumer`]
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct ScanConsumerOptions {
+    /// Only report devices advertising any of these services, all devices if empty
+    #[serde(default)]
+    #[cfg_attr(feature = "ts-export", ts(optional, as = "Option<Vec<Uuid>>"))]
+    pub services: Vec<Uuid>,
+    /// Applied to [`BleDevice::name`]
+    #[serde(default)]
+    #[cfg_attr(feature = "ts-export", ts(optional, as = "Option<ScanNameFilter>"))]
+    pub name_filter: ScanNameFilter,
+    /// Minimum time between two updates of the same device, every update is reported if 0
+    #[serde(default)]
+    #[cfg_attr(feature = "ts-export", ts(optional, as = "Option<u32>"))]
+    pub debounce_ms: u64,
+    /// Devices not reported by the scan for this long are removed from the view of the
+    /// consumer, they are only removed when the adapter drops them if not set
+    #[cfg_attr(feature = "ts-export", ts(optional, as = "Option<u32>"))]
+    pub expiry_ms: Option<u64>,
+}
+
+impl ScanConsumerOptions {
+    fn matches(&self, device: &BleDevice) -> bool {
+        (self.services.is_empty() || device.services.iter().any(|s| self.services.contains(s)))
+            && self.name_filter.matches(Some(&device.name))
+    }
+}
+
+/// Device a consumer reported as added
+struct Seen {
+    last_sent: Instant,
+}
+
+impl Handler {
+    /// Registers a consumer of the shared scan that reports the changes of the devices it is
+    /// interested in to `tx`
+    /// All consumers share one scan for the union of their services, it starts with the first
+    /// consumer and stops when the last one is unregistered. Every consumer filters, debounces
+    /// and expires devices on its own, so consumers do not affect each other.
+    /// Scans started with [`Handler::discover`] or [`Handler::start_discovery`] also scan for
+    /// the services of the consumers while they run, the shared scan resumes once they ended.
+    /// A consumer whose receiver is dropped is unregistered.
+    /// # Errors
+    /// Returns an error if starting the shared scan fails
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// use tokio::sync::mpsc;
+    /// use tauri_plugin_blec::{DiscoveredEvent, ScanConsumerOptions};
+    ///
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     let (tx, mut rx) = mpsc::channel(16);
+    ///     let options = ScanConsumerOptions { expiry_ms: Some(10_000), ..Default::default() };
+    ///     let id = handler.register_scan_consumer(options, tx).await.unwrap();
+    ///     while let Some(event) = rx.recv().await {
+    ///         if let DiscoveredEvent::Removed { address } = event {
+    ///             println!("{address} is gone");
+    ///             break;
+    ///         }
+    ///     }
+    ///     handler.unregister_scan_consumer(id).await.unwrap();
+    /// });
+    /// # }
+    /// ```
+    pub async fn register_scan_consumer(
+        &'static self,
+        options: ScanConsumerOptions,
+        tx: mpsc::Sender<DiscoveredEvent>,
+    ) -> Result<ScanConsumerId, Error> {
+        // subscribed before the scan starts, so the first devices are not missed
+        let rx = self.events();
+        let id = {
+            let mut registry = self.scan_consumers();
+            let id = registry.next_id;
+            registry.next_id += 1;
+            let services = options.services.clone();
+            let task = runtime::spawn(self.run_consumer(id, rx, options, tx));
+            registry.consumers.insert(id, Consumer { services, task });
+            if registry.supervisor.is_none() {
+                registry.supervisor = Some(runtime::spawn(self.supervise_shared_scan()));
+            }
+            id
+        };
+        if let Err(e) = self.update_shared_scan(ScanStart::User).await {
+            let _ = self.unregister_scan_consumer(ScanConsumerId(id)).await;
+            return Err(e);
+        }
+        Ok(ScanConsumerId(id))
+    }
+
+    /// Removes a consumer registered with [`Handler::register_scan_consumer`]
+    /// The shared scan is narrowed to the services of the remaining consumers and stopped if
+    /// this was the last one.
+    /// # Errors
+    /// Returns [`Error::UnknownScanConsumer`] if no consumer has the id or an error if
+    /// updating the shared scan fails
+    pub async fn unregister_scan_consumer(
+        &'static self,
+        id: ScanConsumerId,
+    ) -> Result<(), Error> {
+        let consumer = self
+            .scan_consumers()
+            .consumers
+            .remove(&id.0)
+            .ok_or(Error::UnknownScanConsumer(id.0))?;
+        consumer.task.abort();
+        match self.update_shared_scan(ScanStart::Internal).await {
+            // the wider scan keeps running, the supervisor narrows it later
+            Err(Error::ScanThrottled { .. }) => Ok(()),
+            result => result,
+        }
+    }
+
+    /// Unregisters a consumer whose receiver or event channel closed
+    async fn drop_closed_consumer(&'static self, id: u64) {
+        if self.scan_consumers().consumers.remove(&id).is_none() {
+            return;
+        }
+        debug!("scan consumer {id} closed, unregistering it");
+        if let Err(e) = self.update_shared_scan(ScanStart::Internal).await {
+            warn!("failed to update the shared scan: {e}");
+        }
+    }
+
+    /// Starts, restarts or stops the shared scan to match the registered consumers
+    /// Another running scan already covers the services of the consumers and is left alone.
+    /// `start` tells whether the app asked for the change, see [`Handler::record_scan_start`].
+    async fn update_shared_scan(&'static self, start: ScanStart) -> Result<(), Error> {
+        let (wanted, shared) = {
+            let mut registry = self.scan_consumers();
+            let wanted = union_services(&registry.consumers);
+            if wanted.is_none() {
+                if let Some(supervisor) = registry.supervisor.take() {
+                    supervisor.abort();
+                }
+            }
+            (wanted, registry.scan.clone())
+        };
+        let running = match &shared {
+            Some((generation, _)) => self.is_scan_generation(*generation).await,
+            None => false,
+        };
+        let Some(services) = wanted else {
+            if let Some((generation, _)) = shared {
+                self.scan_consumers().scan = None;
+                if running {
+                    debug!("last scan consumer unregistered, stopping the shared scan");
+                    self.stop_scan_generation(generation).await?;
+                }
+            }
+            return Ok(());
+        };
+        if running && shared.is_some_and(|(_, scanned)| scanned == services) {
+            return Ok(());
+        }
+        if !running && self.is_scanning().await {
+            debug!("another scan covers the scan consumers");
+            return Ok(());
+        }
+        debug!("starting the shared scan for {services:?}");
+        let settings = ScanSettings {
+            consumers: true,
+            start,
+            ..ScanSettings::default()
+        };
+        let generation = self
+            .start_scan_loop(
+                None,
+                None,
+                ScanFilter::AnyService(services.clone()),
+                ScanNameFilter::None,
+                settings,
+            )
+            .await?;
+        self.scan_consumers().scan = Some((generation, services));
+        Ok(())
+    }
+
+    /// Services of the registered consumers, see [`union_services`]
+    pub(crate) fn scan_consumer_services(&self) -> Option<Vec<Uuid>> {
+        union_services(&self.scan_consumers().consumers)
+    }
+
+    /// Returns true if the running scan is the shared scan of the consumers
+    pub(crate) async fn is_shared_scan(&self) -> bool {
+        let shared = self.scan_consumers().scan.as_ref().map(|(generation, _)| *generation);
+        match shared {
+            Some(generation) => self.is_scan_generation(generation).await,
+            None => false,
+        }
+    }
+
+    fn scan_consumers(&self) -> std::sync::MutexGuard<'_, ScanConsumers> {
+        self.scan_consumers.lock().expect("scan consumer lock poisoned")
+    }
+
+    async fn supervise_shared_scan(&'static self) {
+        loop {
+            sleep(SHARED_SCAN_CHECK_INTERVAL).await;
+            // restarts of the supervisor are refused before they use up the starts of the app
+            if let Err(e) = self.update_shared_scan(ScanStart::Internal).await {
+                debug!("failed to restart the shared scan: {e}");
+            }
+        }
+    }
+}
+
+/// Services of all consumers, empty if one of them wants all devices and `None` without
+/// consumers
+fn union_services(consumers: &HashMap<u64, Consumer>) -> Option<Vec<Uuid>> {
+    if consumers.is_empty() {
+        return None;
+    }
+    if consumers.values().any(|c| c.services.is_empty()) {
+        return Some(vec![]);
+    }
+    let mut services: Vec<Uuid> = consumers
+        .values()
+        .flat_map(|c| c.services.iter().copied())
+        .collect();
+    services.sort();
+    services.dedup();
+    Some(services)
+}
+
+impl Handler {
+    async fn run_consumer(
+        &'static self,
+        id: u64,
+        rx: broadcast::Receiver<BleEvent>,
+        options: ScanConsumerOptions,
+        tx: mpsc::Sender<DiscoveredEvent>,
+    ) {
+        self.consume(id, rx, options, tx).await;
+        // the consumer would stay registered and keep the shared scan running otherwise
+        self.drop_closed_consumer(id).await;
+    }
+
+    /// Reports the devices to `tx` until it or the event channel is closed
+    async fn consume(
+        &'static self,
+        id: u64,
+        mut rx: broadcast::Receiver<BleEvent>,
+        options: ScanConsumerOptions,
+        tx: mpsc::Sender<DiscoveredEvent>,
+    ) {
+        let debounce = Duration::from_millis(options.debounce_ms);
+        let expiry = options.expiry_ms.map(Duration::from_millis);
+        let mut seen: HashMap<String, Seen> = HashMap::new();
+        loop {
+            let recv = std::pin::pin!(rx.recv());
+            let check = std::pin::pin!(async {
+                match expiry {
+                    Some(expiry) => sleep(expiry.min(SHARED_SCAN_CHECK_INTERVAL)).await,
+                    None => std::future::pending().await,
+                }
+            });
+            let events = match select(recv, check).await {
+                Either::Left((event, _)) => match event {
+                    Ok(event) => consumer_events(&options, debounce, &mut seen, event),
+                    Err(RecvError::Lagged(missed)) => {
+                        warn!("scan consumer {id} lagged, {missed} events were missed");
+                        continue;
+                    }
+                    Err(RecvError::Closed) => return,
+                },
+                Either::Right(_) => self.expire(&mut seen, expiry.unwrap_or(Duration::MAX)),
+            };
+            for event in events {
+                if tx.send(event).await.is_err() {
+                    debug!("receiver of scan consumer {id} dropped, no longer reporting devices");
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Removes the devices the scans did not report within `expiry`
+    fn expire(&self, seen: &mut HashMap<String, Seen>, expiry: Duration) -> Vec<DiscoveredEvent> {
+        let expired: Vec<String> = seen
+            .keys()
+            .filter(|address| {
+                self.last_seen(address)
+                    .and_then(|t| t.elapsed().ok())
+                    .is_none_or(|elapsed| elapsed >= expiry)
+            })
+            .cloned()
+            .collect();
+        expired
+            .into_iter()
+            .map(|address| {
+                seen.remove(&address);
+                DiscoveredEvent::Removed { address }
+            })
+            .collect()
+    }
+}
+
+/// Changes of the view of a consumer caused by an event
+fn consumer_events(
+    options: &ScanConsumerOptions,
+    debounce: Duration,
+    seen: &mut HashMap<String, Seen>,
+    event: BleEvent,
+) -> Vec<DiscoveredEvent> {
+    match event {
+        BleEvent::DeviceDiscovered(device) if options.matches(&device) => {
+            let now = Instant::now();
+            let Some(entry) = seen.get_mut(&device.address) else {
+                seen.insert(device.address.clone(), Seen { last_sent: now });
+                return vec![DiscoveredEvent::Added(device)];
+            };
+            if now.duration_since(entry.last_sent) < debounce {
+                return vec![];
+            }
+            entry.last_sent = now;
+            vec![DiscoveredEvent::Updated(device)]
+        }
+        BleEvent::DeviceRemoved(address) if seen.remove(&address).is_some() => {
+            vec![DiscoveredEvent::Removed { address }]
+        }
+        _ => vec![],
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use tracing::warn;
+
+use crate::error::Error;
+use crate::events::BleEvent;
+use crate::handler::Handler;
+
+/// Scan starts android allows an app within [`SCAN_START_WINDOW`] before scans return nothing
+const SCAN_START_LIMIT: usize = 5;
+const SCAN_START_WINDOW: Duration = Duration::from_secs(30);
+/// Starts of [`SCAN_START_LIMIT`] the plugin may use for its own scans, the others stay with
+/// the app
+const INTERNAL_SCAN_STARTS: usize = 2;
+/// Time an unfiltered scan may go without a single advertisement before it is considered
+/// throttled
+#[cfg_attr(not(target_os = "android"), allow(dead_code))]
+pub(crate) const THROTTLE_SANITY_WINDOW: Duration = Duration::from_secs(5);
+/// Only android throttles scan starts
+const THROTTLING_PLATFORM: bool = cfg!(target_os = "android");
+
+/// Who starts a scan, decides whether [`Handler::record_scan_start`] refuses it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum ScanStart {
+    /// Started by the app, refused once the OS would throttle it
+    #[default]
+    User,
+    /// Started by the plugin itself, e.g. the shared scan of the scan consumers, refused once
+    /// the plugin used [`INTERNAL_SCAN_STARTS`] of the window
+    Internal,
+    /// Adapters the plugin stopped are started again, only counted
+    /// Check [`Handler::can_restart_scan`] before stopping them.
+    Resume,
+}
+
+/// Scan starts of the app, recorded by [`Handler::record_scan_start`]
+#[derive(Default)]
+pub(crate) struct ScanStarts {
+    /// Starts within the last [`SCAN_START_WINDOW`], the oldest first, with whether the plugin
+    /// started them itself
+    starts: VecDeque<(Instant, bool)>,
+    /// Set when a scan returned nothing although it was started, until the OS lifts the
+    /// throttling
+    throttled_until: Option<Instant>,
+}
+
+impl ScanStarts {
+    fn prune(&mut self, now: Instant) {
+        while self
+            .starts
+            .front()
+            .is_some_and(|(start, _)| now.duration_since(*start) >= SCAN_START_WINDOW)
+        {
+            self.starts.pop_front();
+        }
+        if self.throttled_until.is_some_and(|until| until <= now) {
+            self.throttled_until = None;
+        }
+    }
+
+    /// Time until the oldest start leaves the window
+    fn window_resets_in(&self, now: Instant) -> Duration {
+        self.starts.front().map_or(SCAN_START_WINDOW, |(first, _)| {
+            (*first + SCAN_START_WINDOW).saturating_duration_since(now)
+        })
+    }
+
+    /// Time until a start of `kind` is no longer refused, `None` if it can start now
+    fn retry_after(&self, kind: ScanStart, now: Instant) -> Option<Duration> {
+        if kind == ScanStart::Resume {
+            return None;
+        }
+        if let Some(until) = self.throttled_until {
+            return Some(until.saturating_duration_since(now));
+        }
+        if self.starts.len() >= SCAN_START_LIMIT {
+            return Some(self.window_resets_in(now));
+        }
+        let mut internal = self.starts.iter().filter(|(_, internal)| *internal);
+        if kind == ScanStart::Internal && internal.clone().count() >= INTERNAL_SCAN_STARTS {
+            let (first, _) = internal.next().expect("internal starts counted");
+            return Some((*first + SCAN_START_WINDOW).saturating_duration_since(now));
+        }
+        None
+    }
+
+    /// Records a start of `kind` unless it is refused, returns the starts the app has left
+    fn start(&mut self, kind: ScanStart, now: Instant) -> Result<usize, Duration> {
+        self.prune(now);
+        if let Some(retry_after) = self.retry_after(kind, now) {
+            return Err(retry_after);
+        }
+        self.starts.push_back((now, kind != ScanStart::User));
+        Ok(SCAN_START_LIMIT.saturating_sub(self.starts.len()))
+    }
+}
+
+/// The app is about to reach the scan start limit of the OS, published as
+/// [`BleEvent::ScanThrottleWarning`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct ScanThrottleWarning {
+    /// Scans that can still be started before the OS throttles scanning
+    pub remaining: usize,
+    /// Milliseconds until the oldest counted start no longer counts
+    #[cfg_attr(feature = "ts-export", ts(type = "number"))]
+    pub resets_in_ms: u64,
+}
+
+impl Handler {
+    /// Counts a scan start towards the limit of the OS and warns before the limit is reached
+    /// Starts that would be throttled are refused, see [`ScanStart`] for the starts of the
+    /// plugin itself.
+    /// # Errors
+    /// Returns [`Error::ScanThrottled`] on android if the limit of 5 starts in 30 seconds is
+    /// reached or a previous scan was detected as throttled, for [`ScanStart::Internal`] also
+    /// if the plugin used its share of the starts
+    pub(crate) fn record_scan_start(&self, kind: ScanStart) -> Result<(), Error> {
+        if !THROTTLING_PLATFORM {
+            return Ok(());
+        }
+        let now = Instant::now();
+        let mut starts = self.scan_starts.lock().expect("scan starts lock poisoned");
+        let remaining = starts
+            .start(kind, now)
+            .map_err(|retry_after| Error::ScanThrottled {
+                retry_after_ms: duration_ms(retry_after),
+            })?;
+        if remaining <= 1 {
+            let warning = ScanThrottleWarning {
+                remaining,
+                resets_in_ms: duration_ms(starts.window_resets_in(now)),
+            };
+            drop(starts);
+            warn!("scan started close to the limit of the OS: {warning:?}");
+            self.publish(BleEvent::ScanThrottleWarning(warning));
+        }
+        Ok(())
+    }
+
+    /// Returns true if the plugin may stop the adapters of a running scan and start them
+    /// again with [`ScanStart::Resume`] without using up the starts of the app
+    pub(crate) fn can_restart_scan(&self) -> bool {
+        if !THROTTLING_PLATFORM {
+            return true;
+        }
+        let now = Instant::now();
+        let mut starts = self.scan_starts.lock().expect("scan starts lock poisoned");
+        starts.prune(now);
+        starts.retry_after(ScanStart::Internal, now).is_none()
+    }
+
+    /// Marks scanning as throttled after a started scan received no advertisement within
+    /// [`THROTTLE_SANITY_WINDOW`]
+    /// The OS lifts the throttling once the oldest start leaves the window, until then new
+    /// scans fail with [`Error::ScanThrottled`].
+    #[cfg_attr(not(target_os = "android"), allow(dead_code))]
+    pub(crate) fn report_silent_scan(&self) {
+        let now = Instant::now();
+        let retry_after = {
+            let mut starts = self.scan_starts.lock().expect("scan starts lock poisoned");
+            starts.prune(now);
+            let retry_after = starts.window_resets_in(now);
+            starts.throttled_until = Some(now + retry_after);
+            retry_after
+        };
+        let retry_after_ms = duration_ms(retry_after);
+        warn!("scan received nothing, scanning is likely throttled for {retry_after_ms}ms");
+        self.publish(BleEvent::ScanThrottled { retry_after_ms });
+    }
+}
+
+fn duration_ms(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn app_starts_are_refused_at_the_limit() {
+        let mut starts = ScanStarts::default();
+        let now = Instant::now();
+        for remaining in (0..SCAN_START_LIMIT).rev() {
+            assert_eq!(starts.start(ScanStart::User, now), Ok(remaining));
+        }
+        assert_eq!(starts.start(ScanStart::User, now), Err(SCAN_START_WINDOW));
+        let later = now + SCAN_START_WINDOW;
+        assert_eq!(starts.start(ScanStart::User, later), Ok(SCAN_START_LIMIT - 1));
+    }
+
+    #[test]
+    fn plugin_starts_leave_the_rest_to_the_app() {
+        let mut starts = ScanStarts::default();
+        let now = Instant::now();
+        let second = now + Duration::from_secs(10);
+        assert!(starts.start(ScanStart::Internal, now).is_ok());
+        assert!(starts.start(ScanStart::Internal, second).is_ok());
+        let refused = starts.start(ScanStart::Internal, second);
+        assert_eq!(refused, Err(Duration::from_secs(20)));
+        for _ in 0..SCAN_START_LIMIT - INTERNAL_SCAN_STARTS {
+            assert!(starts.start(ScanStart::User, second).is_ok());
+        }
+        assert!(starts.start(ScanStart::User, second).is_err());
+    }
+
+    #[test]
+    fn resumed_scans_are_counted_but_never_refused() {
+        let mut starts = ScanStarts::default();
+        let now = Instant::now();
+        for _ in 0..SCAN_START_LIMIT {
+            assert!(starts.start(ScanStart::User, now).is_ok());
+        }
+        starts.throttled_until = Some(now + SCAN_START_WINDOW);
+        assert_eq!(starts.start(ScanStart::Resume, now), Ok(0));
+        assert_eq!(starts.starts.len(), SCAN_START_LIMIT + 1);
+        assert!(starts.start(ScanStart::Internal, now).is_err());
+    }
+
+    #[test]
+    fn resumed_scans_count_towards_the_share_of_the_plugin() {
+        let mut starts = ScanStarts::default();
+        let now = Instant::now();
+        assert!(starts.start(ScanStart::Resume, now).is_ok());
+        assert!(starts.start(ScanStart::Internal, now).is_ok());
+        assert!(starts.retry_after(ScanStart::Internal, now).is_some());
+        assert!(starts.retry_after(ScanStart::User, now).is_none());
+    }
+}
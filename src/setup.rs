@@ -0,0 +1,226 @@
+use std::time::Duration;
+
+use tokio::time::sleep;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::budget::Budget;
+use crate::connections::Connection;
+use crate::error::Error;
+use crate::events::BleEvent;
+use crate::handler::{Handler, ListenerHandle};
+use crate::models::{CharacteristicRef, WriteType};
+use crate::payload_log::Payload;
+
+/// Connection priority requested by [`SetupStep::SetPriority`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub enum ConnectionPriority {
+    Balanced,
+    High,
+    LowPower,
+}
+
+impl ConnectionPriority {
+    /// Value of the matching `BluetoothGatt.CONNECTION_PRIORITY_*` constant
+    #[cfg_attr(not(target_os = "android"), allow(dead_code))]
+    pub(crate) fn android_value(self) -> i32 {
+        match self {
+            ConnectionPriority::Balanced => 0,
+            ConnectionPriority::High => 1,
+            ConnectionPriority::LowPower => 2,
+        }
+    }
+}
+
+/// A single step of the setup script run by [`Handler::connect_with_options`]
+#[derive(Debug, Clone, serde::Deserialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum SetupStep {
+    RequestMtu {
+        mtu: u16,
+    },
+    /// Only supported on android, skipped on other platforms
+    SetPriority {
+        priority: ConnectionPriority,
+    },
+    /// Notifications are published as [`BleEvent::SetupNotification`], the result is
+    /// [`SetupStepResult::Subscribed`] with the listener to pass to [`Handler::remove_listener`]
+    Subscribe {
+        #[cfg_attr(feature = "ts-export", ts(as = "crate::models::CharacteristicRefRepr"))]
+        characteristic: CharacteristicRef,
+    },
+    #[serde(rename_all = "camelCase")]
+    Write {
+        #[cfg_attr(feature = "ts-export", ts(as = "crate::models::CharacteristicRefRepr"))]
+        characteristic: CharacteristicRef,
+        #[cfg_attr(feature = "ts-export", ts(type = "Uint8Array | Array<number>"))]
+        data: Vec<u8>,
+        #[cfg_attr(feature = "ts-export", ts(as = "crate::models::WriteTypeRepr"))]
+        write_type: WriteType,
+    },
+    DelayMs {
+        #[cfg_attr(feature = "ts-export", ts(type = "number"))]
+        ms: u64,
+    },
+}
+
+/// What happens with the remaining steps when a step fails
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub enum StepErrorPolicy {
+    /// Stop the script, disconnect and fail the connect
+    #[default]
+    Abort,
+    /// Record the error and run the next step
+    Continue,
+}
+
+/// A setup step with its error policy
+#[derive(Debug, Clone, serde::Deserialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct SetupAction {
+    #[serde(flatten)]
+    pub step: SetupStep,
+    #[serde(default)]
+    #[cfg_attr(feature = "ts-export", ts(optional, as = "Option<StepErrorPolicy>"))]
+    pub on_error: StepErrorPolicy,
+}
+
+/// Outcome of a setup step, in the order of the script
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum SetupStepResult {
+    Done,
+    /// A [`SetupStep::Subscribe`] step registered the listener
+    Subscribed { listener: ListenerHandle },
+    Skipped { reason: String },
+    Failed { error: String },
+}
+
+/// Notification received on a characteristic subscribed by a setup script
+#[derive(Debug, Clone, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct SetupNotification {
+    pub characteristic: Uuid,
+    pub data: Vec<u8>,
+}
+
+impl Handler {
+    /// Runs the setup script on the device of the connection
+    /// # Errors
+    /// Returns [`Error::SetupFailed`] if a step with [`StepErrorPolicy::Abort`] fails and
+    /// [`Error::TotalTimeout`] if the budget is used up, regardless of the error policy
+    pub(crate) async fn run_setup(
+        &self,
+        connection: &Connection,
+        actions: Vec<SetupAction>,
+        budget: &Budget,
+    ) -> Result<Vec<SetupStepResult>, Error> {
+        let mut results = Vec::with_capacity(actions.len());
+        for (i, action) in actions.into_iter().enumerate() {
+            match &action.step {
+                SetupStep::Write {
+                    characteristic,
+                    data,
+                    write_type,
+                } => debug!(
+                    "running setup step {i}: write {} to {} {write_type:?}",
+                    Payload(data),
+                    characteristic.characteristic
+                ),
+                step => debug!("running setup step {i}: {step:?}"),
+            }
+            let step = format!("setup step {i}");
+            let run = self.run_setup_step(connection, action.step);
+            let result = match budget.run(&step, run).await {
+                Ok(result) => result,
+                Err(e @ Error::TotalTimeout { .. }) => return Err(e),
+                Err(e) if action.on_error == StepErrorPolicy::Abort => {
+                    return Err(Error::SetupFailed {
+                        step: i,
+                        error: e.to_string(),
+                    });
+                }
+                Err(e) => {
+                    warn!("setup step {i} failed, continuing: {e}");
+                    SetupStepResult::Failed {
+                        error: e.to_string(),
+                    }
+                }
+            };
+            results.push(result);
+        }
+        Ok(results)
+    }
+
+    async fn run_setup_step(
+        &self,
+        connection: &Connection,
+        step: SetupStep,
+    ) -> Result<SetupStepResult, Error> {
+        // the steps run on the connecting device, whatever address the refs carry
+        let target = |characteristic: CharacteristicRef| CharacteristicRef {
+            address: Some(connection.address.clone()),
+            ..characteristic
+        };
+        match step {
+            SetupStep::RequestMtu { mtu } => {
+                if !self.connection_capabilities().mtu_request {
+                    return Ok(SetupStepResult::Skipped {
+                        reason: "MTU requests are not supported on this platform".to_string(),
+                    });
+                }
+                self.request_mtu_of(connection, mtu).await?;
+            }
+            SetupStep::SetPriority { priority } => {
+                if !self.set_connection_priority(connection, priority).await? {
+                    return Ok(SetupStepResult::Skipped {
+                        reason: "connection priority is not supported on this platform"
+                            .to_string(),
+                    });
+                }
+            }
+            SetupStep::Subscribe { characteristic } => {
+                let uuid = characteristic.characteristic;
+                let publisher = self.publisher().clone();
+                let listener = self.subscribe(target(characteristic), move |data: &[u8]| {
+                    publisher.publish(BleEvent::SetupNotification(SetupNotification {
+                        characteristic: uuid,
+                        data: data.to_vec(),
+                    }));
+                })
+                .await?;
+                return Ok(SetupStepResult::Subscribed { listener });
+            }
+            SetupStep::Write {
+                characteristic,
+                data,
+                write_type,
+            } => {
+                self.send_data(target(characteristic), &data, write_type).await?;
+            }
+            SetupStep::DelayMs { ms } => sleep(Duration::from_millis(ms)).await,
+        }
+        Ok(SetupStepResult::Done)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // checked against the android constants in DeviceEnvironmentTest.kt
+    #[test]
+    fn connection_priorities_match_the_android_constants() {
+        assert_eq!(ConnectionPriority::Balanced.android_value(), 0);
+        assert_eq!(ConnectionPriority::High.android_value(), 1);
+        assert_eq!(ConnectionPriority::LowPower.android_value(), 2);
+    }
+}
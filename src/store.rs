@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use tracing::warn;
+
+/// Key value storage for handler state that should survive app restarts,
+/// set with [`crate::Handler::set_store`]
+/// Values are JSON strings. The handler calls the store from a blocking thread without holding
+/// any of its locks, so implementations may do file or database IO directly.
+pub trait BlecStore: Send + Sync {
+    fn get(&self, key: &str) -> Option<String>;
+    fn set(&self, key: &str, value: &str);
+    fn remove(&self, key: &str);
+}
+
+/// [`BlecStore`] keeping all values in one JSON file
+/// The file is read once and rewritten on every change, write errors are logged.
+pub struct FileStore {
+    path: PathBuf,
+    values: Mutex<HashMap<String, String>>,
+    /// Held while the file is written, so the values stay available to readers
+    file: Mutex<()>,
+}
+
+impl FileStore {
+    /// Opens the store, a missing or unreadable file starts an empty store
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let values = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self {
+            path,
+            values: Mutex::new(values),
+            file: Mutex::new(()),
+        }
+    }
+
+    /// Rewrites the file with the current values
+    /// The values are read under the file lock, so the last write always has the latest ones.
+    fn write(&self) {
+        let _file = self.file.lock().expect("store file lock poisoned");
+        let content = serde_json::to_string(&*self.values.lock().expect("store lock poisoned"));
+        let content = match content {
+            Ok(content) => content,
+            Err(e) => {
+                warn!("failed to serialize store: {e}");
+                return;
+            }
+        };
+        if let Err(e) = std::fs::write(&self.path, content) {
+            warn!("failed to write store {}: {e}", self.path.display());
+        }
+    }
+}
+
+impl BlecStore for FileStore {
+    fn get(&self, key: &str) -> Option<String> {
+        self.values
+            .lock()
+            .expect("store lock poisoned")
+            .get(key)
+            .cloned()
+    }
+
+    fn set(&self, key: &str, value: &str) {
+        self.values
+            .lock()
+            .expect("store lock poisoned")
+            .insert(key.to_string(), value.to_string());
+        self.write();
+    }
+
+    fn remove(&self, key: &str) {
+        let removed = self
+            .values
+            .lock()
+            .expect("store lock poisoned")
+            .remove(key)
+            .is_some();
+        if removed {
+            self.write();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_store_reloads_the_written_values() {
+        let path = std::env::temp_dir().join(format!("blec-store-{}.json", std::process::id()));
+        let store = FileStore::new(&path);
+        store.set("a", "1");
+        store.set("b", "2");
+        store.remove("a");
+        let reloaded = FileStore::new(&path);
+        assert_eq!(reloaded.get("a"), None);
+        assert_eq!(reloaded.get("b").as_deref(), Some("2"));
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,461 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::error::Error;
+use crate::events::{BleEvent, CallbackKind, DisconnectReason};
+use crate::handler::{ConnectionState, Handler};
+use crate::metrics::BleMetrics;
+use crate::payload_log::{set_payload_logging, Payload, PayloadLogging};
+use crate::setup::ConnectionPriority;
+
+/// Connect sessions kept for [`Handler::export_session_timeline`], the oldest is dropped first
+const MAX_SESSIONS: usize = 8;
+/// Entries kept per session, later entries are only counted
+const MAX_ENTRIES: usize = 10_000;
+
+/// Kind of operation in a [`TimelineEvent::Operation`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub enum TimelineOperation {
+    Read,
+    Write,
+    Subscribe,
+}
+
+/// What happened at a [`TimelineEntry`]
+#[derive(Debug, Clone, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum TimelineEvent {
+    /// The connection moved to a new stage
+    #[serde(rename_all = "camelCase")]
+    Stage {
+        state: ConnectionState,
+        /// How long the connection stayed in the stage, `None` for the current stage
+        #[cfg_attr(feature = "ts-export", ts(type = "number | null"))]
+        duration_ms: Option<u64>,
+    },
+    MtuChanged {
+        mtu: u16,
+    },
+    ConnectionPriority {
+        priority: ConnectionPriority,
+    },
+    #[serde(rename_all = "camelCase")]
+    Operation {
+        operation: TimelineOperation,
+        characteristic: Uuid,
+        /// Time spent waiting for earlier operations on the device
+        queue_wait_ms: f64,
+        latency_ms: f64,
+        /// `None` if the operation succeeded
+        error: Option<String>,
+        /// Length of the written or read payload, not recorded with [`PayloadLogging::Off`]
+        len: Option<usize>,
+        /// Hex encoded payload, only recorded with [`PayloadLogging::HexTruncated`] and
+        /// [`PayloadLogging::Full`]
+        data: Option<String>,
+    },
+    Disconnected {
+        reason: DisconnectReason,
+    },
+    /// A user callback panicked, see [`BleEvent::CallbackPanicked`]
+    CallbackPanicked {
+        callback: CallbackKind,
+        context: String,
+        message: String,
+    },
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct TimelineEntry {
+    /// Milliseconds since the connect started
+    #[cfg_attr(feature = "ts-export", ts(type = "number"))]
+    pub at_ms: u64,
+    #[serde(flatten)]
+    pub event: TimelineEvent,
+}
+
+/// Notifications of one characteristic received within one second of the session
+#[derive(Debug, Clone, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationBucket {
+    /// Seconds since the connect started
+    #[cfg_attr(feature = "ts-export", ts(type = "number"))]
+    pub second: u64,
+    pub characteristic: Uuid,
+    #[cfg_attr(feature = "ts-export", ts(type = "number"))]
+    pub count: u64,
+    #[cfg_attr(feature = "ts-export", ts(type = "number"))]
+    pub bytes: u64,
+}
+
+/// Timeline of a connect session returned by [`Handler::export_session_timeline`]
+#[derive(Debug, Clone, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct SessionTimeline {
+    pub address: String,
+    /// Start of the connect in milliseconds since the unix epoch
+    #[cfg_attr(feature = "ts-export", ts(type = "number"))]
+    pub started_at: u64,
+    /// Until the disconnect or, for the current session, until the export
+    #[cfg_attr(feature = "ts-export", ts(type = "number"))]
+    pub duration_ms: u64,
+    pub disconnect_reason: Option<DisconnectReason>,
+    /// Stages, MTU and priority changes, operations, callback panics and the disconnect in the
+    /// order they happened
+    pub entries: Vec<TimelineEntry>,
+    pub notifications: Vec<NotificationBucket>,
+    /// Entries not recorded because the session exceeded the entry limit
+    #[cfg_attr(feature = "ts-export", ts(type = "number"))]
+    pub dropped_entries: u64,
+    /// Metrics of the handler at the time of the export, they cover all sessions since the last
+    /// [`Handler::reset_metrics`]
+    pub metrics: BleMetrics,
+}
+
+/// Sessions recorded by the handler, see [`Handler::export_session_timeline`]
+#[derive(Default)]
+pub(crate) struct Timelines {
+    sessions: VecDeque<Recording>,
+}
+
+struct Recording {
+    address: String,
+    started: Instant,
+    started_at: u64,
+    ended_ms: Option<u64>,
+    disconnect_reason: Option<DisconnectReason>,
+    /// Index of the stage entry whose duration is still open
+    open_stage: Option<usize>,
+    entries: Vec<TimelineEntry>,
+    notifications: BTreeMap<(u64, Uuid), (u64, u64)>,
+    dropped_entries: u64,
+}
+
+impl Recording {
+    fn elapsed_ms(&self) -> u64 {
+        u64::try_from(self.started.elapsed().as_millis()).unwrap_or(u64::MAX)
+    }
+
+    /// Returns false if the entry limit was reached and the entry was only counted
+    fn push(&mut self, event: TimelineEvent) -> bool {
+        if self.entries.len() >= MAX_ENTRIES {
+            self.dropped_entries += 1;
+            return false;
+        }
+        self.entries.push(TimelineEntry {
+            at_ms: self.elapsed_ms(),
+            event,
+        });
+        true
+    }
+
+    fn push_stage(&mut self, state: ConnectionState) {
+        let at_ms = self.elapsed_ms();
+        if let Some(entry) = self.open_stage.take().and_then(|i| self.entries.get_mut(i)) {
+            if let TimelineEvent::Stage { duration_ms, .. } = &mut entry.event {
+                *duration_ms = Some(at_ms.saturating_sub(entry.at_ms));
+            }
+        }
+        if state == ConnectionState::Disconnected {
+            self.ended_ms = Some(at_ms);
+        }
+        let stage = TimelineEvent::Stage {
+            state,
+            duration_ms: None,
+        };
+        if self.push(stage) && state != ConnectionState::Disconnected {
+            self.open_stage = Some(self.entries.len() - 1);
+        }
+    }
+
+    fn export(&self, metrics: BleMetrics) -> SessionTimeline {
+        SessionTimeline {
+            address: self.address.clone(),
+            started_at: self.started_at,
+            duration_ms: self.ended_ms.unwrap_or_else(|| self.elapsed_ms()),
+            disconnect_reason: self.disconnect_reason,
+            entries: self.entries.clone(),
+            notifications: self
+                .notifications
+                .iter()
+                .map(|(&(second, characteristic), &(count, bytes))| NotificationBucket {
+                    second,
+                    characteristic,
+                    count,
+                    bytes,
+                })
+                .collect(),
+            dropped_entries: self.dropped_entries,
+            metrics,
+        }
+    }
+}
+
+impl Timelines {
+    /// Starts the timeline of a new connect session
+    pub(crate) fn start(&mut self, address: &str) {
+        if self.sessions.len() >= MAX_SESSIONS {
+            self.sessions.pop_front();
+        }
+        let started_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| u64::try_from(d.as_millis()).unwrap_or(u64::MAX))
+            .unwrap_or_default();
+        self.sessions.push_back(Recording {
+            address: address.to_string(),
+            started: Instant::now(),
+            started_at,
+            ended_ms: None,
+            disconnect_reason: None,
+            open_stage: None,
+            entries: vec![],
+            notifications: BTreeMap::new(),
+            dropped_entries: 0,
+        });
+    }
+
+    /// Drops the recorded sessions of the address, see [`Handler::forget_device`]
+    pub(crate) fn forget(&mut self, address: &str) {
+        self.sessions.retain(|s| s.address != address);
+    }
+
+    /// Latest session of the address, other devices may have started sessions since
+    fn current(&mut self, address: &str) -> Option<&mut Recording> {
+        self.sessions.iter_mut().rev().find(|s| s.address == address)
+    }
+
+    /// Records the events of the current session that belong on the timeline
+    pub(crate) fn record_event(&mut self, event: &BleEvent) {
+        match event {
+            BleEvent::ConnectionStateChanged { address, state } => {
+                if let Some(session) = self.current(address) {
+                    session.push_stage(*state);
+                }
+            }
+            BleEvent::Disconnected { address, reason } => {
+                if let Some(session) = self.current(address) {
+                    session.disconnect_reason = Some(*reason);
+                    session.push(TimelineEvent::Disconnected { reason: *reason });
+                }
+            }
+            BleEvent::CallbackPanicked(panic) => {
+                if let Some(session) = self.current(&panic.address) {
+                    session.push(TimelineEvent::CallbackPanicked {
+                        callback: panic.callback,
+                        context: panic.context.clone(),
+                        message: panic.message.clone(),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    #[cfg_attr(not(target_os = "android"), allow(dead_code))]
+    pub(crate) fn record_mtu(&mut self, address: &str, mtu: u16) {
+        if let Some(session) = self.current(address) {
+            session.push(TimelineEvent::MtuChanged { mtu });
+        }
+    }
+
+    #[cfg_attr(not(target_os = "android"), allow(dead_code))]
+    pub(crate) fn record_priority(&mut self, address: &str, priority: ConnectionPriority) {
+        if let Some(session) = self.current(address) {
+            session.push(TimelineEvent::ConnectionPriority { priority });
+        }
+    }
+
+    /// Records a GATT operation, the payload as allowed by the [`PayloadLogging`] policy
+    pub(crate) fn record_operation(
+        &mut self,
+        address: &str,
+        operation: TimelineOperation,
+        characteristic: Uuid,
+        queue_wait: Duration,
+        latency: Duration,
+        payload: Option<&[u8]>,
+        error: Option<String>,
+    ) {
+        let Some(session) = self.current(address) else {
+            return;
+        };
+        let payload = payload.map(Payload);
+        session.push(TimelineEvent::Operation {
+            operation,
+            characteristic,
+            queue_wait_ms: queue_wait.as_secs_f64() * 1000.0,
+            latency_ms: latency.as_secs_f64() * 1000.0,
+            error,
+            len: payload.as_ref().and_then(Payload::recorded_len),
+            data: payload.as_ref().and_then(Payload::hex),
+        });
+    }
+
+    /// Counts a notification in the bucket of the current second
+    pub(crate) fn record_notification(&mut self, address: &str, characteristic: Uuid, len: usize) {
+        let Some(session) = self.current(address) else {
+            return;
+        };
+        let second = session.started.elapsed().as_secs();
+        let (count, bytes) = session
+            .notifications
+            .entry((second, characteristic))
+            .or_default();
+        *count += 1;
+        *bytes += len as u64;
+    }
+}
+
+impl Handler {
+    /// Records the payload bytes of reads and writes in the session timelines
+    /// Shorthand for [`set_payload_logging`] with [`PayloadLogging::Full`] if enabled and the
+    /// default [`PayloadLogging::Lengths`] otherwise, the policy also applies to log output.
+    pub fn set_timeline_payloads(&self, enabled: bool) {
+        set_payload_logging(if enabled {
+            PayloadLogging::Full
+        } else {
+            PayloadLogging::Lengths
+        });
+    }
+
+    /// Returns the timeline of the latest connect session with the address
+    /// The timeline lists the connect stages with their durations, MTU and priority changes,
+    /// every read, write and subscribe with its latency and result and the disconnect reason,
+    /// notifications are counted per second and characteristic. The last few sessions are kept,
+    /// including sessions whose connect failed.
+    /// If a path is given, the timeline is also written to it as JSON.
+    /// # Errors
+    /// Returns [`Error::NoSessionTimeline`] if no session with the address was recorded or an
+    /// error if the file can not be written
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// let handler = tauri_plugin_blec::get_handler().unwrap();
+    /// let path = std::path::Path::new("session.json");
+    /// let timeline = handler
+    ///     .export_session_timeline("00:00:00:00:00:00", Some(path))
+    ///     .unwrap();
+    /// println!("{} entries", timeline.entries.len());
+    /// # }
+    /// ```
+    pub fn export_session_timeline(
+        &self,
+        address: &str,
+        path: Option<&Path>,
+    ) -> Result<SessionTimeline, Error> {
+        let metrics = self.get_metrics();
+        let timeline = self
+            .timelines()
+            .sessions
+            .iter()
+            .rev()
+            .find(|s| s.address == address)
+            .map(|s| s.export(metrics))
+            .ok_or_else(|| Error::NoSessionTimeline(address.to_string()))?;
+        if let Some(path) = path {
+            let file = BufWriter::new(File::create(path)?);
+            serde_json::to_writer_pretty(file, &timeline).map_err(std::io::Error::from)?;
+            debug!("wrote session timeline of {address} to {}", path.display());
+        }
+        Ok(timeline)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{guard_callback, Publisher};
+
+    const CHARAC: Uuid = Uuid::from_u128(0x51ff_12bb_3ed8_46e5_b4f9_d64e_2fec_021b);
+    const FIRST: &str = "00:00:00:00:00:01";
+    const SECOND: &str = "00:00:00:00:00:02";
+
+    fn record_read(timelines: &mut Timelines, address: &str) {
+        timelines.record_operation(
+            address,
+            TimelineOperation::Read,
+            CHARAC,
+            Duration::ZERO,
+            Duration::from_millis(5),
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    fn records_into_the_session_of_the_device() {
+        let mut timelines = Timelines::default();
+        timelines.start(FIRST);
+        timelines.start(SECOND);
+        record_read(&mut timelines, FIRST);
+        timelines.record_notification(FIRST, CHARAC, 3);
+        timelines.record_event(&BleEvent::Disconnected {
+            address: FIRST.to_string(),
+            reason: DisconnectReason::LinkLost,
+        });
+
+        let first = &timelines.sessions[0];
+        assert_eq!(first.address, FIRST);
+        assert_eq!(first.entries.len(), 2);
+        assert_eq!(first.notifications.len(), 1);
+        assert_eq!(first.disconnect_reason, Some(DisconnectReason::LinkLost));
+        let second = &timelines.sessions[1];
+        assert!(second.entries.is_empty());
+        assert!(second.notifications.is_empty());
+        assert_eq!(second.disconnect_reason, None);
+    }
+
+    #[test]
+    fn records_into_the_latest_session_of_the_device() {
+        let mut timelines = Timelines::default();
+        timelines.start(FIRST);
+        timelines.start(SECOND);
+        timelines.start(FIRST);
+        record_read(&mut timelines, FIRST);
+        assert!(timelines.sessions[0].entries.is_empty());
+        assert_eq!(timelines.sessions[2].entries.len(), 1);
+    }
+
+    #[test]
+    fn ignores_devices_without_a_session() {
+        let mut timelines = Timelines::default();
+        timelines.start(FIRST);
+        record_read(&mut timelines, SECOND);
+        timelines.record_notification(SECOND, CHARAC, 3);
+        assert!(timelines.sessions[0].entries.is_empty());
+        assert!(timelines.sessions[0].notifications.is_empty());
+    }
+
+    #[test]
+    fn records_callback_panics_of_the_device() {
+        let publisher = Publisher::new();
+        publisher.timelines().start(FIRST);
+        let kind = CallbackKind::Listener;
+        guard_callback::<()>(&publisher, kind, FIRST, &CHARAC, || panic!("listener failed"));
+        guard_callback::<()>(&publisher, kind, SECOND, &CHARAC, || panic!("not recorded"));
+
+        let timelines = publisher.timelines();
+        let [entry] = timelines.sessions[0].entries.as_slice() else {
+            panic!("expected one entry");
+        };
+        let TimelineEvent::CallbackPanicked { callback, context, message } = &entry.event else {
+            panic!("expected a callback panic, got {:?}", entry.event);
+        };
+        assert_eq!(*callback, CallbackKind::Listener);
+        assert_eq!(*context, CHARAC.to_string());
+        assert_eq!(message, "listener failed");
+    }
+}
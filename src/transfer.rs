@@ -0,0 +1,597 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::time::timeout;
+use tracing::{debug, info, warn};
+
+use crate::budget::Budget;
+use crate::error::Error;
+use crate::handler::Handler;
+use crate::models::{CharacteristicRef, WriteType};
+
+/// Chunk size if no MTU was negotiated, the payload of a write with the default ATT MTU of 23
+const DEFAULT_CHUNK_SIZE: usize = 20;
+/// Time [`TransferOptions::resume_on_reconnect`] waits for the device by default
+const DEFAULT_RECONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+/// Time a failed write waits for the disconnect event before the error is treated as fatal,
+/// the write usually fails before the stack reports the link loss
+const LINK_LOSS_GRACE: Duration = Duration::from_secs(2);
+
+/// Paused transfers that can be continued with [`Handler::resume_transfer`], by id
+pub(crate) type PausedTransfers = Mutex<HashMap<u64, Transfer>>;
+static NEXT_TRANSFER_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Options of [`Handler::transfer`]
+#[derive(Debug, Clone, serde::Deserialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct TransferOptions {
+    /// Bytes per write, defaults to [`Handler::max_write_len_for`], which follows MTU changes
+    #[serde(default)]
+    #[cfg_attr(feature = "ts-export", ts(optional))]
+    pub chunk_size: Option<usize>,
+    #[serde(default = "default_write_type")]
+    #[cfg_attr(feature = "ts-export", ts(optional, as = "Option<crate::models::WriteTypeRepr>"))]
+    pub write_type: WriteType,
+    /// When the link is lost, wait until the device is connected again and resume
+    /// automatically. Otherwise the transfer is paused until [`Handler::resume_transfer`].
+    #[serde(default)]
+    #[cfg_attr(feature = "ts-export", ts(optional, as = "Option<bool>"))]
+    pub resume_on_reconnect: bool,
+    /// How long to wait for the device with `resume_on_reconnect`, defaults to 30 seconds
+    /// The transfer is paused if the device does not reconnect in time.
+    #[serde(default)]
+    #[cfg_attr(feature = "ts-export", ts(optional, as = "Option<u32>"))]
+    pub reconnect_timeout_ms: Option<u64>,
+    /// Caps the whole call including the waits for reconnects, the write in flight is cancelled
+    /// and the transfer dropped when the time is up
+    #[serde(default)]
+    #[cfg_attr(feature = "ts-export", ts(optional, as = "Option<u32>"))]
+    pub total_timeout_ms: Option<u64>,
+}
+
+fn default_write_type() -> WriteType {
+    WriteType::WithResponse
+}
+
+impl Default for TransferOptions {
+    fn default() -> Self {
+        Self {
+            chunk_size: None,
+            write_type: default_write_type(),
+            resume_on_reconnect: false,
+            reconnect_timeout_ms: None,
+            total_timeout_ms: None,
+        }
+    }
+}
+
+/// Result of a completed transfer
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub struct TransferReport {
+    #[cfg_attr(feature = "ts-export", ts(type = "number"))]
+    pub id: u64,
+    pub bytes: usize,
+    /// CRC-32 (IEEE) of all transferred bytes
+    pub crc32: u32,
+    /// Number of times the transfer was resumed after a link loss
+    pub resumes: u32,
+}
+
+/// State of a transfer, kept while it is paused
+pub(crate) struct Transfer {
+    address: String,
+    characteristic: CharacteristicRef,
+    data: Vec<u8>,
+    options: TransferOptions,
+    /// End of the last acknowledged chunk
+    offset: usize,
+    /// CRC-32 state of `data[..offset]`
+    crc: u32,
+    resumes: u32,
+}
+
+/// Transfer that did not complete, see [`drive_transfer`]
+enum Interrupted {
+    Failed(Error),
+    /// The link was lost and the device did not reconnect, the transfer can be resumed
+    Paused(Transfer),
+}
+
+/// What a transfer needs of the connection, implemented by [`Handler`] and mocked in the tests
+trait TransferLink {
+    /// Returns the requested chunk size or the payload of a single write of the write type
+    fn chunk_size(&self, requested: Option<usize>, write_type: WriteType) -> usize;
+    async fn write_chunk(
+        &self,
+        c: &CharacteristicRef,
+        chunk: &[u8],
+        write_type: WriteType,
+    ) -> Result<(), Error>;
+    /// Returns true if the device disconnected, waits shortly for the disconnect event
+    async fn link_lost(&self) -> bool;
+    fn powered_off(&self) -> bool;
+    /// Waits until the device with the address is connected
+    async fn wait_for_reconnect(&self, address: &str, wait: Duration) -> bool;
+}
+
+impl TransferLink for Handler {
+    fn chunk_size(&self, requested: Option<usize>, write_type: WriteType) -> usize {
+        requested
+            .or_else(|| self.max_write_len_for(write_type))
+            .filter(|size| *size > 0)
+            .unwrap_or(DEFAULT_CHUNK_SIZE)
+    }
+
+    async fn write_chunk(
+        &self,
+        c: &CharacteristicRef,
+        chunk: &[u8],
+        write_type: WriteType,
+    ) -> Result<(), Error> {
+        self.send_data(c.clone(), chunk, write_type).await
+    }
+
+    async fn link_lost(&self) -> bool {
+        let mut connected = self.connection_watch();
+        timeout(LINK_LOSS_GRACE, connected.wait_for(|c| !*c))
+            .await
+            .is_ok_and(|res| res.is_ok())
+    }
+
+    fn powered_off(&self) -> bool {
+        Handler::powered_off(self)
+    }
+
+    async fn wait_for_reconnect(&self, address: &str, wait: Duration) -> bool {
+        let mut connected = self.connection_watch();
+        timeout(wait, async {
+            loop {
+                if connected.wait_for(|c| *c).await.is_err() {
+                    return false;
+                }
+                if self.connected_address().as_deref() == Some(address) {
+                    return true;
+                }
+                // another device was connected, wait for it to disconnect
+                if connected.wait_for(|c| !*c).await.is_err() {
+                    return false;
+                }
+            }
+        })
+        .await
+        .unwrap_or(false)
+    }
+}
+
+impl Handler {
+    /// Writes `data` to the characteristic in chunks, resuming from the last acknowledged chunk
+    /// if the link is lost
+    /// With [`TransferOptions::resume_on_reconnect`] the transfer waits until the device is
+    /// connected again. Otherwise, or if the device does not reconnect in time,
+    /// [`Error::TransferPaused`] is returned with the id to pass to [`Handler::resume_transfer`].
+    /// The transfer is a critical operation, see [`Handler::begin_critical_operation`].
+    /// Chunks written with [`WriteType::WithoutResponse`] are not acknowledged by the device,
+    /// so a resume may skip chunks that were lost in the stack.
+    /// # Errors
+    /// Returns an error if no device is connected, [`Error::TransferPaused`] if the link was
+    /// lost, [`Error::AdapterPoweredOff`] if it was lost because the adapter was powered off, or
+    /// the error of a failed write
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// use uuid::{Uuid,uuid};
+    /// use tauri_plugin_blec::TransferOptions;
+    /// const CHARACTERISTIC_UUID: Uuid = uuid!("51FF12BB-3ED8-46E5-B4F9-D64E2FEC021B");
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     let firmware = std::fs::read("firmware.bin").unwrap();
+    ///     let options = TransferOptions { resume_on_reconnect: true, ..Default::default() };
+    ///     let report = handler.transfer(CHARACTERISTIC_UUID, firmware, options).await.unwrap();
+    ///     println!("crc32 {:08x} after {} resumes", report.crc32, report.resumes);
+    /// });
+    /// # }
+    /// ```
+    pub async fn transfer(
+        &self,
+        c: impl Into<CharacteristicRef>,
+        data: Vec<u8>,
+        options: TransferOptions,
+    ) -> Result<TransferReport, Error> {
+        let address = self.connected_address().ok_or(Error::NoDeviceConnected)?;
+        let id = NEXT_TRANSFER_ID.fetch_add(1, Ordering::Relaxed);
+        let transfer = Transfer {
+            address,
+            characteristic: c.into(),
+            data,
+            options,
+            offset: 0,
+            crc: 0xffff_ffff,
+            resumes: 0,
+        };
+        self.run_transfer(id, transfer).await
+    }
+
+    /// Continues a paused transfer from the last acknowledged chunk
+    /// Transfers stay paused across a lost link. Any other disconnect of the device, e.g. with
+    /// [`Handler::disconnect`], and forgetting it drop its paused transfers.
+    /// # Errors
+    /// Returns [`Error::UnknownTransfer`] if no transfer with the id is paused,
+    /// [`Error::TransferDeviceNotConnected`] if its device is not connected
+    /// and the errors of [`Handler::transfer`]
+    pub async fn resume_transfer(&self, id: u64) -> Result<TransferReport, Error> {
+        let mut transfer = self
+            .paused_transfers
+            .lock()
+            .expect("paused transfers lock poisoned")
+            .remove(&id)
+            .ok_or(Error::UnknownTransfer(id))?;
+        if self.connected_address().as_ref() != Some(&transfer.address) {
+            let address = transfer.address.clone();
+            self.pause(id, transfer);
+            return Err(Error::TransferDeviceNotConnected(address));
+        }
+        transfer.resumes += 1;
+        self.run_transfer(id, transfer).await
+    }
+
+    /// Writes `data` to the characteristic in chunks of `chunk_size` bytes, one after another
+    /// Without a chunk size the payload is split by [`Handler::max_write_len_for`] of the write
+    /// type. Chunks written with [`WriteType::WithResponse`] wait for the acknowledgement of the
+    /// device before the next one is sent. Unlike [`Handler::transfer`] a lost link is not resumed.
+    /// # Errors
+    /// Returns an error if no device is connected and [`Error::ChunkWriteFailed`] with the
+    /// offset of the first chunk that could not be written and the error of its write as the
+    /// source
+    /// # Example
+    /// ```no_run
+    /// # #[cfg(feature = "tauri")] {
+    /// use tauri::async_runtime;
+    /// use uuid::{Uuid,uuid};
+    /// use tauri_plugin_blec::models::WriteType;
+    /// const CHARACTERISTIC_UUID: Uuid = uuid!("51FF12BB-3ED8-46E5-B4F9-D64E2FEC021B");
+    /// async_runtime::block_on(async {
+    ///     let handler = tauri_plugin_blec::get_handler().unwrap();
+    ///     let data = [0u8; 1000];
+    ///     handler
+    ///         .send_data_chunked(CHARACTERISTIC_UUID, &data, None, WriteType::WithResponse)
+    ///         .await
+    ///         .unwrap();
+    /// });
+    /// # }
+    /// ```
+    pub async fn send_data_chunked(
+        &self,
+        c: impl Into<CharacteristicRef>,
+        data: &[u8],
+        chunk_size: Option<usize>,
+        write_type: WriteType,
+    ) -> Result<(), Error> {
+        let address = self.connected_address().ok_or(Error::NoDeviceConnected)?;
+        let mut transfer = Transfer {
+            address,
+            characteristic: c.into(),
+            data: data.to_vec(),
+            options: TransferOptions {
+                chunk_size,
+                write_type,
+                ..Default::default()
+            },
+            offset: 0,
+            crc: 0xffff_ffff,
+            resumes: 0,
+        };
+        if let Err(e) = send_chunks(self, &mut transfer, &Budget::new(None)).await {
+            let offset = transfer.offset;
+            warn!(
+                "chunked write to {} failed at offset {offset}: {e}",
+                transfer.characteristic.characteristic
+            );
+            return Err(Error::ChunkWriteFailed {
+                offset,
+                source: Box::new(e),
+            });
+        }
+        Ok(())
+    }
+
+    /// Drops the state of a paused transfer
+    /// Returns false if no transfer with the id is paused
+    pub fn cancel_transfer(&self, id: u64) -> bool {
+        self.paused_transfers
+            .lock()
+            .expect("paused transfers lock poisoned")
+            .remove(&id)
+            .is_some()
+    }
+
+    async fn run_transfer(&self, id: u64, transfer: Transfer) -> Result<TransferReport, Error> {
+        let _critical = self.begin_critical_operation("transfer");
+        let budget = Budget::new(transfer.options.total_timeout_ms.map(Duration::from_millis));
+        match drive_transfer(self, id, transfer, &budget).await {
+            Ok(report) => Ok(report),
+            Err(Interrupted::Failed(e)) => Err(e),
+            Err(Interrupted::Paused(transfer)) => {
+                let offset = transfer.offset;
+                self.pause(id, transfer);
+                Err(Error::TransferPaused { id, offset })
+            }
+        }
+    }
+
+    fn pause(&self, id: u64, transfer: Transfer) {
+        self.paused_transfers
+            .lock()
+            .expect("paused transfers lock poisoned")
+            .insert(id, transfer);
+    }
+
+    /// Drops the paused transfers of the device, they can not be resumed on a new connection
+    pub(crate) fn drop_paused_transfers(&self, address: &str) {
+        drop_transfers_of(&self.paused_transfers, address);
+    }
+}
+
+fn drop_transfers_of(paused: &PausedTransfers, address: &str) {
+    paused
+        .lock()
+        .expect("paused transfers lock poisoned")
+        .retain(|id, transfer| {
+            let keep = transfer.address != address;
+            if !keep {
+                debug!("dropping paused transfer {id} of {address}");
+            }
+            keep
+        });
+}
+
+/// Sends the remaining chunks, waiting for the device to reconnect with
+/// [`TransferOptions::resume_on_reconnect`] whenever the link is lost
+async fn drive_transfer(
+    link: &impl TransferLink,
+    id: u64,
+    mut transfer: Transfer,
+    budget: &Budget,
+) -> Result<TransferReport, Interrupted> {
+    loop {
+        let Err(e) = send_chunks(link, &mut transfer, budget).await else {
+            debug!("transfer {id} completed");
+            return Ok(TransferReport {
+                id,
+                bytes: transfer.data.len(),
+                crc32: !transfer.crc,
+                resumes: transfer.resumes,
+            });
+        };
+        if matches!(e, Error::TotalTimeout { .. }) || !link.link_lost().await {
+            warn!("transfer {id} failed: {e}");
+            return Err(Interrupted::Failed(e));
+        }
+        // the device can not reconnect before the adapter is powered on again
+        if link.powered_off() {
+            warn!("transfer {id} failed, the adapter was powered off: {e}");
+            return Err(Interrupted::Failed(Error::AdapterPoweredOff));
+        }
+        info!(
+            "link lost during transfer {id} at {}/{} bytes: {e}",
+            transfer.offset,
+            transfer.data.len()
+        );
+        if transfer.options.resume_on_reconnect {
+            let wait = transfer
+                .options
+                .reconnect_timeout_ms
+                .map_or(DEFAULT_RECONNECT_TIMEOUT, Duration::from_millis);
+            let reconnected = budget
+                .run("waiting for reconnect", async {
+                    Ok(link.wait_for_reconnect(&transfer.address, wait).await)
+                })
+                .await
+                .map_err(Interrupted::Failed)?;
+            if reconnected {
+                info!("resuming transfer {id} at {} bytes", transfer.offset);
+                transfer.resumes += 1;
+                continue;
+            }
+        }
+        return Err(Interrupted::Paused(transfer));
+    }
+}
+
+async fn send_chunks(
+    link: &impl TransferLink,
+    transfer: &mut Transfer,
+    budget: &Budget,
+) -> Result<(), Error> {
+    while transfer.offset < transfer.data.len() {
+        // the MTU may change with a reconnect or a renegotiation, picked up at every chunk
+        let chunk_size = link.chunk_size(transfer.options.chunk_size, transfer.options.write_type);
+        let end = (transfer.offset + chunk_size).min(transfer.data.len());
+        let chunk = &transfer.data[transfer.offset..end];
+        let write = link.write_chunk(&transfer.characteristic, chunk, transfer.options.write_type);
+        budget.run("chunk write", write).await?;
+        transfer.crc = crc32_update(transfer.crc, chunk);
+        transfer.offset = end;
+    }
+    Ok(())
+}
+
+/// Updates a CRC-32 (IEEE) state, which starts at `0xffff_ffff` and is inverted at the end
+fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicBool;
+    use uuid::Uuid;
+
+    const CHUNK: usize = 4;
+
+    /// Device whose link drops after the scripted number of acknowledged writes
+    struct MockLink {
+        written: Mutex<Vec<u8>>,
+        writes: Mutex<usize>,
+        /// Write counts at which the link drops, each once
+        drops: Mutex<Vec<usize>>,
+        connected: AtomicBool,
+        reconnects: bool,
+    }
+
+    impl MockLink {
+        fn new(drops: Vec<usize>, reconnects: bool) -> Self {
+            Self {
+                written: Mutex::new(vec![]),
+                writes: Mutex::new(0),
+                drops: Mutex::new(drops),
+                connected: AtomicBool::new(true),
+                reconnects,
+            }
+        }
+
+        fn written(&self) -> Vec<u8> {
+            self.written.lock().unwrap().clone()
+        }
+    }
+
+    impl TransferLink for MockLink {
+        fn chunk_size(&self, requested: Option<usize>, _write_type: WriteType) -> usize {
+            requested.unwrap_or(CHUNK)
+        }
+
+        async fn write_chunk(
+            &self,
+            _c: &CharacteristicRef,
+            chunk: &[u8],
+            _write_type: WriteType,
+        ) -> Result<(), Error> {
+            let mut writes = self.writes.lock().unwrap();
+            let mut drops = self.drops.lock().unwrap();
+            if drops.first() == Some(&*writes) {
+                drops.remove(0);
+                self.connected.store(false, Ordering::SeqCst);
+            }
+            if !self.connected.load(Ordering::SeqCst) {
+                return Err(Error::Btleplug(btleplug::Error::NotConnected));
+            }
+            *writes += 1;
+            self.written.lock().unwrap().extend_from_slice(chunk);
+            Ok(())
+        }
+
+        async fn link_lost(&self) -> bool {
+            !self.connected.load(Ordering::SeqCst)
+        }
+
+        fn powered_off(&self) -> bool {
+            false
+        }
+
+        async fn wait_for_reconnect(&self, _address: &str, _wait: Duration) -> bool {
+            self.connected.store(self.reconnects, Ordering::SeqCst);
+            self.reconnects
+        }
+    }
+
+    fn firmware() -> Vec<u8> {
+        (0..=40).collect()
+    }
+
+    fn transfer(resume_on_reconnect: bool) -> Transfer {
+        Transfer {
+            address: "AA:BB:CC:DD:EE:FF".to_string(),
+            characteristic: Uuid::from_u128(0x51ff_12bb_3ed8_46e5_b4f9_d64e_2fec_021b).into(),
+            data: firmware(),
+            options: TransferOptions {
+                resume_on_reconnect,
+                ..Default::default()
+            },
+            offset: 0,
+            crc: 0xffff_ffff,
+            resumes: 0,
+        }
+    }
+
+    fn crc32(data: &[u8]) -> u32 {
+        !crc32_update(0xffff_ffff, data)
+    }
+
+    #[test]
+    fn crc32_of_the_check_input() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[tokio::test]
+    async fn resumes_after_a_mid_transfer_disconnect() {
+        let link = MockLink::new(vec![3, 7], true);
+        let report = drive_transfer(&link, 1, transfer(true), &Budget::new(None))
+            .await
+            .unwrap_or_else(|_| panic!("transfer did not complete"));
+        assert_eq!(report.resumes, 2);
+        assert_eq!(report.bytes, firmware().len());
+        assert_eq!(report.crc32, crc32(&firmware()));
+        // every chunk was written once, continued at the last acknowledged one
+        assert_eq!(link.written(), firmware());
+    }
+
+    #[tokio::test]
+    async fn mid_transfer_disconnect_pauses_at_the_acknowledged_offset() {
+        let link = MockLink::new(vec![3], false);
+        let budget = Budget::new(None);
+        let Err(Interrupted::Paused(mut paused)) =
+            drive_transfer(&link, 1, transfer(false), &budget).await
+        else {
+            panic!("transfer was not paused");
+        };
+        assert_eq!(paused.offset, 3 * CHUNK);
+        assert_eq!(link.written(), firmware()[..3 * CHUNK]);
+
+        // what resume_transfer does once the device is connected again
+        link.connected.store(true, Ordering::SeqCst);
+        paused.resumes += 1;
+        let report = drive_transfer(&link, 1, paused, &budget)
+            .await
+            .unwrap_or_else(|_| panic!("resumed transfer did not complete"));
+        assert_eq!(report.resumes, 1);
+        assert_eq!(report.crc32, crc32(&firmware()));
+        assert_eq!(link.written(), firmware());
+    }
+
+    #[tokio::test]
+    async fn device_not_reconnecting_pauses_the_transfer() {
+        let link = MockLink::new(vec![5], false);
+        let result = drive_transfer(&link, 1, transfer(true), &Budget::new(None)).await;
+        assert!(matches!(result, Err(Interrupted::Paused(t)) if t.offset == 5 * CHUNK));
+    }
+
+    #[tokio::test]
+    async fn disconnect_during_a_paused_transfer_drops_it() {
+        let link = MockLink::new(vec![2], false);
+        let Err(Interrupted::Paused(paused)) =
+            drive_transfer(&link, 1, transfer(false), &Budget::new(None)).await
+        else {
+            panic!("transfer was not paused");
+        };
+        let mut other = transfer(false);
+        other.address = "11:22:33:44:55:66".to_string();
+        let transfers = PausedTransfers::default();
+        transfers.lock().unwrap().extend([(1, paused), (2, other)]);
+        // the device reconnected and was then disconnected on purpose
+        drop_transfers_of(&transfers, "AA:BB:CC:DD:EE:FF");
+        let left: Vec<u64> = transfers.lock().unwrap().keys().copied().collect();
+        assert_eq!(left, [2]);
+    }
+}
@@ -0,0 +1,134 @@
+use ciborium::value::{Integer, Value};
+use serde::Serialize;
+use tauri::ipc::{InvokeResponseBody, Response};
+
+use crate::handler::SessionFrame;
+
+/// Encoding of the payloads sent to the frontend by `subscribe` and `open_session`
+/// JSON encodes every byte of a notification as a number, which roughly triples the IPC payload
+/// of small notifications. The binary formats are sent as raw IPC payloads and decoded by the
+/// guest-js bindings.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize)]
+#[cfg_attr(feature = "ts-export", derive(ts_rs::TS))]
+#[serde(rename_all = "camelCase")]
+pub enum WireFormat {
+    #[default]
+    Json,
+    /// Notifications are sent as their raw bytes and session frames as a CBOR map with the same
+    /// fields as the JSON encoding, the data as a byte string
+    Cbor,
+}
+
+impl WireFormat {
+    pub(crate) fn notification(self, data: Vec<u8>) -> Response {
+        match self {
+            Self::Json => json(&data),
+            Self::Cbor => Response::new(InvokeResponseBody::Raw(data)),
+        }
+    }
+
+    pub(crate) fn frame(self, frame: SessionFrame) -> Response {
+        match self {
+            Self::Json => json(&frame),
+            Self::Cbor => Response::new(InvokeResponseBody::Raw(cbor_frame(frame))),
+        }
+    }
+}
+
+fn json<T: Serialize>(value: &T) -> Response {
+    let json = serde_json::to_string(value).expect("failed to encode payload as JSON");
+    Response::new(InvokeResponseBody::Json(json))
+}
+
+/// CBOR map with the same fields as the JSON encoding of the frame
+fn cbor_frame(frame: SessionFrame) -> Vec<u8> {
+    let value = Value::Map(vec![
+        (
+            Value::Text("characteristic".to_string()),
+            Value::Text(frame.characteristic.to_string()),
+        ),
+        (
+            Value::Text("timestampUs".to_string()),
+            Value::Integer(Integer::from(frame.timestamp_us)),
+        ),
+        (Value::Text("data".to_string()), Value::Bytes(frame.data)),
+    ]);
+    let mut buf = Vec::with_capacity(64);
+    ciborium::into_writer(&value, &mut buf).expect("failed to encode frame as CBOR");
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use uuid::Uuid;
+
+    use super::*;
+
+    const CHARAC: Uuid = Uuid::from_u128(0x51ff_12bb_3ed8_46e5_b4f9_d64e_2fec_021b);
+
+    fn frame() -> SessionFrame {
+        SessionFrame {
+            characteristic: CHARAC,
+            timestamp_us: 1_000_000,
+            data: vec![0xa5; 20],
+        }
+    }
+
+    #[test]
+    fn cbor_frame_has_the_fields_of_the_json_frame() {
+        let decoded: Value = ciborium::from_reader(cbor_frame(frame()).as_slice()).unwrap();
+        let Value::Map(fields) = decoded else {
+            panic!("frame is not a CBOR map: {decoded:?}");
+        };
+        let fields: Vec<(String, Value)> = fields
+            .into_iter()
+            .map(|(key, value)| (key.into_text().unwrap(), value))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                ("characteristic".to_string(), Value::Text(CHARAC.to_string())),
+                ("timestampUs".to_string(), Value::Integer(1_000_000.into())),
+                ("data".to_string(), Value::Bytes(vec![0xa5; 20])),
+            ]
+        );
+        let json = serde_json::to_value(frame()).unwrap();
+        let mut json_keys: Vec<&String> = json.as_object().unwrap().keys().collect();
+        let mut cbor_keys: Vec<&String> = fields.iter().map(|(key, _)| key).collect();
+        json_keys.sort();
+        cbor_keys.sort();
+        assert_eq!(json_keys, cbor_keys);
+    }
+
+    #[test]
+    fn binary_payloads_are_smaller() {
+        let data = vec![0xa5; 20];
+        assert_eq!(serde_json::to_string(&data).unwrap().len(), 81);
+        assert_eq!(serde_json::to_string(&frame()).unwrap().len(), 168);
+        assert_eq!(cbor_frame(frame()).len(), 97);
+    }
+
+    /// Encoding throughput of both formats, run with
+    /// `cargo test --release wire_format_throughput -- --ignored --nocapture`
+    #[test]
+    #[ignore = "benchmark"]
+    fn wire_format_throughput() {
+        const FRAMES: u32 = 200_000;
+        let measure = |name: &str, encode: &dyn Fn() -> usize| {
+            let start = Instant::now();
+            let bytes: usize = (0..FRAMES).map(|_| encode()).sum();
+            let elapsed = start.elapsed();
+            println!(
+                "{name}: {:.0} frames/s, {} bytes per frame",
+                f64::from(FRAMES) / elapsed.as_secs_f64(),
+                bytes / FRAMES as usize
+            );
+        };
+        measure("json notification", &|| serde_json::to_string(&frame().data).unwrap().len());
+        measure("raw notification", &|| frame().data.len());
+        measure("json frame", &|| serde_json::to_string(&frame()).unwrap().len());
+        measure("cbor frame", &|| cbor_frame(frame()).len());
+    }
+}